@@ -0,0 +1,69 @@
+// Times the JIT against the bytecode VM on loop-heavy programs, both optimized at -O2.
+// Run with `cargo bench --bench jit --features jit`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tiny_lang::codegen::{Bytecode, compile};
+use tiny_lang::ir::build_ssa;
+use tiny_lang::jit::Jit;
+use tiny_lang::passes::{OptLevel, PassManager};
+use tiny_lang::vm::Vm;
+use tiny_lang::{Parser, parse_program, tokenize};
+
+const ITERATIONS: u32 = 10;
+
+// Nothing is printed, so the timings aren't mixed with the programs' output.
+const PROGRAMS: &[(&str, &str)] = &[
+    ("nested", "
+        sum = 0;
+        for (i = 0; i < 600; i = i + 1) {
+            for (j = 0; j < 600; j = j + 1) { sum = sum + (i * j) % 7; }
+        }
+    "),
+    ("collatz", "
+        longest = 0;
+        for (n = 1; n < 20000; n = n + 1) {
+            x = n;
+            steps = 0;
+            while (x != 1) { x = x % 2 == 0 ? x / 2 : 3 * x + 1; steps = steps + 1; }
+            if (steps > longest) { longest = steps; }
+        }
+    "),
+    ("primes", "
+        fn is_prime(n) {
+            d = 2;
+            while (d * d <= n) { if (n % d == 0) { return false; } d = d + 1; }
+            return true;
+        }
+        count = 0;
+        for (n = 2; n < 40000; n = n + 1) { if (is_prime(n)) { count = count + 1; } }
+    "),
+];
+
+fn time(run: impl Fn()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let passes = PassManager::with_level(OptLevel::O2);
+    for (name, source) in PROGRAMS {
+        let (tokens, errors) = tokenize(source);
+        assert!(errors.is_empty());
+        let program = passes.run_ast(parse_program(&mut Parser::new(tokens)).unwrap());
+
+        let mut bytecode: Bytecode = compile(&program).unwrap();
+        passes.run_bytecode(&mut bytecode);
+        let vm = time(|| Vm::new(black_box(&bytecode)).run().unwrap());
+
+        let mut module = build_ssa(&program);
+        passes.run_ssa(&mut module);
+        let jit = Jit::compile(&module).unwrap();
+        let native = time(|| black_box(&jit).run());
+
+        println!("{:<10} vm {:>10.2?}   jit {:>10.2?}   {:>6.1}x", name, vm, native, vm.as_secs_f64() / native.as_secs_f64());
+    }
+}
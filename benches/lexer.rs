@@ -0,0 +1,50 @@
+// Times both scanners, `tokenize` with its conversion to owned tokens, and lexing from a
+// reader, on the sample programs repeated into a larger input.
+// Run with `cargo bench --bench lexer`.
+use std::fs;
+use std::hint::black_box;
+use std::time::Instant;
+
+use tiny_lang::lexer::{Lexer, Scanner, tokenize, tokenize_with};
+
+const ITERATIONS: u32 = 20;
+
+fn time(name: &str, source: &str, lex: impl Fn(&str) -> usize) {
+    let start = Instant::now();
+    let mut tokens = 0;
+    for _ in 0..ITERATIONS {
+        tokens = black_box(lex(black_box(source)));
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let rate = source.len() as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0);
+    println!("{:<9} {:>6} bytes {:>6} tokens {:>10.2?} {:>8.2} MiB/s", name, source.len(), tokens, elapsed, rate);
+}
+
+fn main() {
+    let mut sample = String::new();
+    let mut paths: Vec<_> = fs::read_dir("tests").unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lang"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        sample.push_str(&fs::read_to_string(path).unwrap());
+        sample.push('\n');
+    }
+
+    // Compile the patterns before anything is timed.
+    tokenize_with("", Scanner::Regex);
+    for size in [4 * 1024, 64 * 1024] {
+        let source = sample.repeat(size / sample.len() + 1);
+        time("regex", &source, |source| tokenize_with(source, Scanner::Regex).0.len());
+        time("bytes", &source, |source| tokenize_with(source, Scanner::Bytes).0.len());
+        time("owned", &source, |source| tokenize(source).0.len());
+        time("reader", &source, |source| Lexer::from_reader(source.as_bytes()).count());
+    }
+
+    // Long strings and comments, where the scanner spends its time looking for delimiters.
+    let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+    let strings = format!("/* {} */\nprint(\"{}\\n\");\n// {}\n", text, text, text);
+    let source = strings.repeat(64 * 1024 / strings.len() + 1);
+    time("strings", &source, |source| tokenize_with(source, Scanner::Bytes).0.len());
+}
@@ -0,0 +1,65 @@
+// Times the bytecode VM on call-heavy programs, compiled with and without inlining.
+// Run with `cargo bench --bench vm`.
+use std::hint::black_box;
+use std::time::Instant;
+
+use tiny_lang::codegen::{Bytecode, compile, optimize_bytecode};
+use tiny_lang::opt::{eliminate_dead_code, fold_constants, inline_functions};
+use tiny_lang::vm::Vm;
+use tiny_lang::{Parser, parse_program, tokenize};
+
+const ITERATIONS: u32 = 10;
+
+// Nothing is printed, so the timings aren't mixed with the programs' output.
+const PROGRAMS: &[(&str, &str)] = &[
+    ("square", "
+        fn square(x) { return x * x; }
+        sum = 0;
+        i = 0;
+        while (i < 200000) { v = i % 100; sum = sum + square(v); i = i + 1; }
+    "),
+    ("clamp", "
+        fn max(a, b) { return a > b ? a : b; }
+        fn min(a, b) { return a < b ? a : b; }
+        sum = 0;
+        for (i = 0; i < 100000; i = i + 1) { x = i % 50; sum = sum + min(max(x, 10), 40); }
+    "),
+    ("forced", "
+        #[inline]
+        fn poly(x) { return x * x * x + 3 * x * x + 3 * x + 1 + (x % 7) * (x % 11) + (x % 13); }
+        sum = 0;
+        i = 0;
+        while (i < 100000) { v = i % 30; sum = sum + poly(v); i = i + 1; }
+    "),
+];
+
+fn bytecode(source: &str, inline: bool) -> Bytecode {
+    let (tokens, errors) = tokenize(source);
+    assert!(errors.is_empty());
+    let mut program = parse_program(&mut Parser::new(tokens)).unwrap();
+    fold_constants(&mut program);
+    let mut program = eliminate_dead_code(program);
+    if inline {
+        inline_functions(&mut program);
+        fold_constants(&mut program);
+    }
+    let mut bytecode = compile(&program).unwrap();
+    optimize_bytecode(&mut bytecode);
+    bytecode
+}
+
+fn time(name: &str, bytecode: &Bytecode) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        Vm::new(black_box(bytecode)).run().unwrap();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    println!("{:<16} {:>5} instructions {:>10.2?}", name, bytecode.code.len(), elapsed);
+}
+
+fn main() {
+    for (name, source) in PROGRAMS {
+        time(&format!("{} (calls)", name), &bytecode(source, false));
+        time(&format!("{} (inlined)", name), &bytecode(source, true));
+    }
+}
@@ -0,0 +1,644 @@
+use std::fmt::Write;
+
+use crate::intern::Symbol;
+use crate::ir::{Constant, Function, Module, Op, Terminator, ValueId};
+use crate::lexer::TokenKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    // The GNU assembler's default: `movq -8(%rbp), %rax`.
+    #[default]
+    Att,
+    // Destination first, as in Intel's manuals: `mov rax, [rbp-8]`.
+    Intel
+}
+
+impl Syntax {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "att" => Some(Syntax::Att),
+            "intel" => Some(Syntax::Intel),
+            _ => None,
+        }
+    }
+}
+
+// The registers the System V calling convention passes the first six arguments in.
+const ARGUMENTS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+// The runtime: printing goes through the C library, and dividing by zero stops the program
+// with a message the way the interpreter does. Written in Intel syntax, then AT&T syntax.
+const RUNTIME_INTEL: &str = "\
+# The runtime
+
+# print_int(rdi): printf(\"%lld\", rdi)
+tl_print_int:
+    push    rbp
+    mov     rbp, rsp
+    mov     rsi, rdi
+    lea     rdi, [rip+.Lformat_int]
+    xor     eax, eax
+    call    printf@PLT
+    leave
+    ret
+
+# print_bool(rdi): `true` unless rdi is 0
+tl_print_bool:
+    push    rbp
+    mov     rbp, rsp
+    lea     rsi, [rip+.Ltrue]
+    lea     rax, [rip+.Lfalse]
+    test    rdi, rdi
+    cmove   rsi, rax
+    lea     rdi, [rip+.Lformat_string]
+    xor     eax, eax
+    call    printf@PLT
+    leave
+    ret
+
+# print_string(rdi, rsi): the rsi bytes at rdi, with printf(\"%.*s\", rsi, rdi)
+tl_print_string:
+    push    rbp
+    mov     rbp, rsp
+    mov     rdx, rdi
+    lea     rdi, [rip+.Lformat_bytes]
+    xor     eax, eax
+    call    printf@PLT
+    leave
+    ret
+
+# putc(rdi): putchar(rdi)
+tl_putc:
+    jmp     putchar@PLT
+
+# Reached by a jump from any depth, so the stack is realigned first
+tl_divide_by_zero:
+    lea     rdi, [rip+.Ldivide_by_zero]
+    jmp     tl_fail
+tl_remainder_by_zero:
+    lea     rdi, [rip+.Lremainder_by_zero]
+# fail(rdi): fputs(rdi, stderr) and exit(1)
+tl_fail:
+    and     rsp, -16
+    mov     rsi, [rip+stderr@GOTPCREL]
+    mov     rsi, [rsi]
+    call    fputs@PLT
+    mov     rdi, 1
+    call    exit@PLT
+
+    .section .rodata
+.Lformat_int:
+    .asciz  \"%lld\"
+.Lformat_string:
+    .asciz  \"%s\"
+.Lformat_bytes:
+    .asciz  \"%.*s\"
+.Ltrue:
+    .asciz  \"true\"
+.Lfalse:
+    .asciz  \"false\"
+.Ldivide_by_zero:
+    .asciz  \"error: cannot divide by zero\\n\"
+.Lremainder_by_zero:
+    .asciz  \"error: cannot take the remainder of a division by zero\\n\"
+";
+
+const RUNTIME_ATT: &str = "\
+# The runtime
+
+# print_int(%rdi): printf(\"%lld\", %rdi)
+tl_print_int:
+    pushq   %rbp
+    movq    %rsp, %rbp
+    movq    %rdi, %rsi
+    leaq    .Lformat_int(%rip), %rdi
+    xorl    %eax, %eax
+    call    printf@PLT
+    leave
+    ret
+
+# print_bool(%rdi): `true` unless %rdi is 0
+tl_print_bool:
+    pushq   %rbp
+    movq    %rsp, %rbp
+    leaq    .Ltrue(%rip), %rsi
+    leaq    .Lfalse(%rip), %rax
+    testq   %rdi, %rdi
+    cmoveq  %rax, %rsi
+    leaq    .Lformat_string(%rip), %rdi
+    xorl    %eax, %eax
+    call    printf@PLT
+    leave
+    ret
+
+# print_string(%rdi, %rsi): the %rsi bytes at %rdi, with printf(\"%.*s\", %rsi, %rdi)
+tl_print_string:
+    pushq   %rbp
+    movq    %rsp, %rbp
+    movq    %rdi, %rdx
+    leaq    .Lformat_bytes(%rip), %rdi
+    xorl    %eax, %eax
+    call    printf@PLT
+    leave
+    ret
+
+# putc(%rdi): putchar(%rdi)
+tl_putc:
+    jmp     putchar@PLT
+
+# Reached by a jump from any depth, so the stack is realigned first
+tl_divide_by_zero:
+    leaq    .Ldivide_by_zero(%rip), %rdi
+    jmp     tl_fail
+tl_remainder_by_zero:
+    leaq    .Lremainder_by_zero(%rip), %rdi
+# fail(%rdi): fputs(%rdi, stderr) and exit(1)
+tl_fail:
+    andq    $-16, %rsp
+    movq    stderr@GOTPCREL(%rip), %rsi
+    movq    (%rsi), %rsi
+    call    fputs@PLT
+    movq    $1, %rdi
+    call    exit@PLT
+
+    .section .rodata
+.Lformat_int:
+    .asciz  \"%lld\"
+.Lformat_string:
+    .asciz  \"%s\"
+.Lformat_bytes:
+    .asciz  \"%.*s\"
+.Ltrue:
+    .asciz  \"true\"
+.Lfalse:
+    .asciz  \"false\"
+.Ldivide_by_zero:
+    .asciz  \"error: cannot divide by zero\\n\"
+.Lremainder_by_zero:
+    .asciz  \"error: cannot take the remainder of a division by zero\\n\"
+";
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the x86-64 backend", what)
+}
+
+// An assembler symbol for a tiny-lang name, which may not be ASCII.
+fn symbol(prefix: &str, name: &str) -> String {
+    let mut out = String::from(prefix);
+    for ch in name.chars() {
+        match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => out.push(ch),
+            _ => write!(out, "_u{:x}_", ch as u32).unwrap(),
+        }
+    }
+    out
+}
+
+fn ascii(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+enum Operand {
+    Reg(&'static str),
+    Imm(i64),
+    // A stack slot, below `rbp`.
+    Frame(i32),
+    // Memory at a symbol, addressed relative to `rip`.
+    Rip(String),
+    Target(String)
+}
+
+use Operand::{Frame, Imm, Reg, Rip, Target};
+
+fn comparison(operator: &TokenKind) -> Option<&'static str> {
+    match operator {
+        TokenKind::OpLess => Some("setl"),
+        TokenKind::OpGreater => Some("setg"),
+        TokenKind::OpLessEqual => Some("setle"),
+        TokenKind::OpGreaterEqual => Some("setge"),
+        TokenKind::OpEqual => Some("sete"),
+        TokenKind::OpNotEqual => Some("setne"),
+        _ => None,
+    }
+}
+
+// Every value lives in a stack slot of its own, below the saved `rbp`.
+fn slot(value: ValueId) -> Operand {
+    Frame(-8 * (value.0 as i32 + 1))
+}
+
+struct Lowering<'a> {
+    syntax: Syntax,
+    module: &'a Module,
+    function: &'a Function,
+    // Which values are bools, from `Module::bools`.
+    bools: &'a [bool],
+    // The function's symbol, which its blocks' labels start with.
+    name: String,
+    globals: &'a [Symbol],
+    out: String,
+    // String literals that are printed, which go in `.rodata`.
+    strings: &'a mut Vec<String>
+}
+
+impl Lowering<'_> {
+    fn operand(&self, operand: &Operand) -> String {
+        match (self.syntax, operand) {
+            (Syntax::Intel, Reg(name)) => name.to_string(),
+            (Syntax::Intel, Imm(n)) => n.to_string(),
+            (Syntax::Intel, Frame(offset)) => format!("[rbp{:+}]", offset),
+            (Syntax::Intel, Rip(symbol)) => format!("[rip+{}]", symbol),
+            (Syntax::Att, Reg(name)) => format!("%{}", name),
+            (Syntax::Att, Imm(n)) => format!("${}", n),
+            (Syntax::Att, Frame(offset)) => format!("{}(%rbp)", offset),
+            (Syntax::Att, Rip(symbol)) => format!("{}(%rip)", symbol),
+            (_, Target(label)) => label.clone(),
+        }
+    }
+
+    // One instruction, with its Intel mnemonic and operands, destination first, and its
+    // AT&T mnemonic; AT&T syntax puts the operands the other way round.
+    fn emit(&mut self, intel: &str, att: &str, operands: &[Operand]) {
+        let mut operands: Vec<String> = operands.iter().map(|operand| self.operand(operand)).collect();
+        let mnemonic = match self.syntax {
+            Syntax::Intel => intel,
+            Syntax::Att => {
+                operands.reverse();
+                att
+            }
+        };
+        let line = format!("    {:<7} {}", mnemonic, operands.join(", "));
+        writeln!(self.out, "{}", line.trim_end()).unwrap();
+    }
+
+    fn comment(&mut self, text: &str) {
+        writeln!(self.out, "    # {}", text).unwrap();
+    }
+
+    fn label(&mut self, label: &str) {
+        writeln!(self.out, "{}:", label).unwrap();
+    }
+
+    fn block_label(&self, block: usize) -> String {
+        format!(".L{}_b{}", self.name, block)
+    }
+
+    fn mov(&mut self, destination: Operand, source: Operand) {
+        match source {
+            // Only `movabs` takes a 64-bit immediate in AT&T syntax.
+            Imm(n) if i32::try_from(n).is_err() => self.emit("mov", "movabsq", &[destination, source]),
+            _ => self.emit("mov", "movq", &[destination, source]),
+        }
+    }
+
+    // Integer and bool constants are written where they're used. Every value is an
+    // integer: bools are 0 or 1.
+    fn load(&mut self, reg: &'static str, value: ValueId) -> Result<(), String> {
+        let source = match self.function.op(value) {
+            Op::Const(Constant::Integer(n)) => Imm(*n),
+            Op::Const(Constant::Bool(b)) => Imm(*b as i64),
+            Op::Const(Constant::Float(_)) => return Err(unsupported("Floating point values")),
+            Op::Const(Constant::String(_)) => return Err(unsupported("Strings other than print arguments")),
+            _ => slot(value),
+        };
+        self.mov(Reg(reg), source);
+        Ok(())
+    }
+
+    fn global(&self, name: &Symbol) -> Operand {
+        debug_assert!(self.globals.contains(name));
+        Rip(symbol("g_", name.as_str()))
+    }
+
+    // Sets `rax` to 1 if the flags say so, otherwise 0.
+    fn set(&mut self, condition: &str) {
+        self.emit(condition, condition, &[Reg("al")]);
+        self.emit("movzx", "movzbl", &[Reg("eax"), Reg("al")]);
+    }
+
+    fn call(&mut self, function: &str) {
+        self.emit("call", "call", &[Target(function.to_string())]);
+    }
+
+    fn instruction(&mut self, value: ValueId) -> Result<(), String> {
+        let op = self.function.op(value);
+        match op {
+            op if op.has_result() => self.comment(&format!("{} = {}", value, op)),
+            op => self.comment(&op.to_string()),
+        }
+        match op {
+            // Constants are immediates, parameters were stored on entry and phis are set by
+            // the blocks that jump to theirs.
+            Op::Const(_) | Op::Param(_) | Op::Phi(_) => return Ok(()),
+            Op::Unary(operator, operand) => {
+                self.load("rax", *operand)?;
+                match operator {
+                    TokenKind::OpSubtract => self.emit("neg", "negq", &[Reg("rax")]),
+                    TokenKind::OpAdd => {}
+                    TokenKind::OpBitNot => self.emit("not", "notq", &[Reg("rax")]),
+                    _ => {
+                        self.emit("test", "testq", &[Reg("rax"), Reg("rax")]);
+                        self.set("sete");
+                    }
+                }
+            }
+            Op::Binary(operator @ (TokenKind::OpDivide | TokenKind::OpMod), left, right) => {
+                self.load("rax", *left)?;
+                self.load("rcx", *right)?;
+                self.emit("test", "testq", &[Reg("rcx"), Reg("rcx")]);
+                let failure = if *operator == TokenKind::OpMod { "tl_remainder_by_zero" } else { "tl_divide_by_zero" };
+                self.emit("jz", "jz", &[Target(failure.to_string())]);
+                // The lowest integer divided by -1 overflows, and traps, so dividing by -1
+                // is dividing the negated dividend by 1 instead: the quotient wraps and the
+                // remainder is 0, as in the interpreter.
+                self.mov(Reg("rdx"), Reg("rax"));
+                self.emit("neg", "negq", &[Reg("rdx")]);
+                self.emit("cmp", "cmpq", &[Reg("rcx"), Imm(-1)]);
+                self.emit("cmove", "cmoveq", &[Reg("rax"), Reg("rdx")]);
+                self.mov(Reg("rdx"), Imm(1));
+                self.emit("cmove", "cmoveq", &[Reg("rcx"), Reg("rdx")]);
+                // rdx:rax is the dividend, sign-extended; the quotient goes in rax and the
+                // remainder in rdx.
+                self.emit("cqo", "cqto", &[]);
+                self.emit("idiv", "idivq", &[Reg("rcx")]);
+                if *operator == TokenKind::OpMod {
+                    self.mov(Reg("rax"), Reg("rdx"));
+                }
+            }
+            Op::Binary(operator, left, right) => {
+                self.load("rax", *left)?;
+                self.load("rcx", *right)?;
+                if let Some(condition) = comparison(operator) {
+                    self.emit("cmp", "cmpq", &[Reg("rax"), Reg("rcx")]);
+                    self.set(condition);
+                } else {
+                    let (intel, att, source) = match operator {
+                        TokenKind::OpAdd => ("add", "addq", "rcx"),
+                        TokenKind::OpSubtract => ("sub", "subq", "rcx"),
+                        TokenKind::OpMultiply => ("imul", "imulq", "rcx"),
+                        TokenKind::OpBitAnd => ("and", "andq", "rcx"),
+                        TokenKind::OpBitOr => ("or", "orq", "rcx"),
+                        TokenKind::OpBitXor => ("xor", "xorq", "rcx"),
+                        // The processor takes shift counts modulo 64, as the interpreter does.
+                        TokenKind::OpShiftLeft => ("shl", "shlq", "cl"),
+                        TokenKind::OpShiftRight => ("sar", "sarq", "cl"),
+                        operator => return Err(format!("`{}` isn't supported by the x86-64 backend", operator)),
+                    };
+                    self.emit(intel, att, &[Reg("rax"), Reg(source)]);
+                }
+            }
+            Op::Truthy(operand) => {
+                self.load("rax", *operand)?;
+                self.emit("test", "testq", &[Reg("rax"), Reg("rax")]);
+                self.set("setne");
+            }
+            Op::Call(callee, arguments) => {
+                let Some(function) = self.module.functions[1..].iter().find(|f| f.name == callee.as_str()) else {
+                    return Err(format!("`{}` isn't supported by the x86-64 backend", callee));
+                };
+                if function.parameters != arguments.len() {
+                    return Err(format!("`{}` takes {} arguments but {} were given", callee, function.parameters, arguments.len()));
+                }
+                for (&argument, reg) in arguments.iter().zip(ARGUMENTS) {
+                    self.load(reg, argument)?;
+                }
+                self.call(&symbol("tl_", callee.as_str()));
+            }
+            Op::LoadGlobal(name) => self.mov(Reg("rax"), self.global(name)),
+            Op::StoreGlobal(name, operand) => {
+                self.load("rax", *operand)?;
+                self.mov(self.global(name), Reg("rax"));
+                return Ok(());
+            }
+            Op::Array(_) | Op::Index(..) | Op::StoreIndex(..) => return Err(unsupported("Arrays")),
+            Op::Print(operand) => {
+                let op = self.function.op(*operand);
+                if let Op::Const(Constant::String(s)) = op {
+                    let label = format!(".Lstring{}", self.strings.len());
+                    self.strings.push(s.clone());
+                    self.emit("lea", "leaq", &[Reg("rdi"), Rip(label)]);
+                    self.mov(Reg("rsi"), Imm(s.len() as i64));
+                    self.call("tl_print_string");
+                } else {
+                    self.load("rdi", *operand)?;
+                    self.call(if self.bools[operand.0] { "tl_print_bool" } else { "tl_print_int" });
+                }
+                return Ok(());
+            }
+            Op::Putc(operand) => {
+                self.load("rdi", *operand)?;
+                self.call("tl_putc");
+                return Ok(());
+            }
+        }
+        self.mov(slot(value), Reg("rax"));
+        Ok(())
+    }
+
+    fn phis(&self, to: usize) -> Vec<ValueId> {
+        self.function.blocks[to].instructions.iter()
+            .copied()
+            .take_while(|&value| matches!(self.function.op(value), Op::Phi(_)))
+            .collect()
+    }
+
+    // Gives the phis at the start of `to` their values for control coming from `from`. The
+    // values all go on the stack first, as one phi may be another's operand.
+    fn edge(&mut self, from: usize, to: usize) -> Result<(), String> {
+        let phis = self.phis(to);
+        if phis.is_empty() {
+            return Ok(());
+        }
+        self.comment(&format!("the phis in b{}, coming from b{}", to, from));
+        let incoming = |phi: ValueId| match self.function.op(phi) {
+            Op::Phi(incoming) => incoming.iter().find(|&&(block, _)| block == from).unwrap().1,
+            _ => unreachable!(),
+        };
+        if let [phi] = phis[..] {
+            self.load("rax", incoming(phi))?;
+            self.mov(slot(phi), Reg("rax"));
+            return Ok(());
+        }
+        for &phi in &phis {
+            self.load("rax", incoming(phi))?;
+            self.emit("push", "pushq", &[Reg("rax")]);
+        }
+        for &phi in phis.iter().rev() {
+            self.emit("pop", "popq", &[Reg("rax")]);
+            self.mov(slot(phi), Reg("rax"));
+        }
+        Ok(())
+    }
+
+    // Jumps to `to`, unless its code comes next.
+    fn jump(&mut self, from: usize, to: usize) {
+        if to != from + 1 {
+            let label = self.block_label(to);
+            self.emit("jmp", "jmp", &[Target(label)]);
+        }
+    }
+
+    fn terminator(&mut self, id: usize, terminator: &Terminator) -> Result<(), String> {
+        self.comment(&terminator.to_string());
+        match terminator {
+            Terminator::Jump(target) => {
+                self.edge(id, *target)?;
+                self.jump(id, *target);
+            }
+            Terminator::Branch(condition, then_block, else_block) => {
+                self.load("rax", *condition)?;
+                self.emit("test", "testq", &[Reg("rax"), Reg("rax")]);
+                // Phis in the other block are set on the way there.
+                let otherwise = if self.phis(*else_block).is_empty() {
+                    self.block_label(*else_block)
+                } else {
+                    format!("{}_from_b{}", self.block_label(*else_block), id)
+                };
+                self.emit("jz", "jz", &[Target(otherwise.clone())]);
+                self.edge(id, *then_block)?;
+                if self.phis(*else_block).is_empty() {
+                    self.jump(id, *then_block);
+                } else {
+                    let label = self.block_label(*then_block);
+                    self.emit("jmp", "jmp", &[Target(label)]);
+                    self.label(&otherwise);
+                    self.edge(id, *else_block)?;
+                    self.jump(id, *else_block);
+                }
+            }
+            Terminator::Return(value) => {
+                match value {
+                    Some(value) => self.load("rax", *value)?,
+                    // `main` returns 0 to the C library.
+                    None => self.emit("xor", "xorl", &[Reg("eax"), Reg("eax")]),
+                }
+                self.emit("leave", "leave", &[]);
+                self.emit("ret", "ret", &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn function(mut self) -> Result<String, String> {
+        let function = self.function;
+        if function.parameters > ARGUMENTS.len() {
+            return Err(unsupported(&format!("Functions with more than {} parameters", ARGUMENTS.len())));
+        }
+        let size = (function.values.len() * 8).next_multiple_of(16);
+        writeln!(self.out, "\n# fn {}({}): {} values in {} bytes of stack", function.name, function.parameters, function.values.len(), size).unwrap();
+        let name = self.name.clone();
+        self.label(&name);
+        self.emit("push", "pushq", &[Reg("rbp")]);
+        self.mov(Reg("rbp"), Reg("rsp"));
+        // The frame is a multiple of 16 bytes, which keeps the stack aligned for calls.
+        self.emit("sub", "subq", &[Reg("rsp"), Imm(size as i64)]);
+        for (value, op) in function.values.iter().enumerate() {
+            if let Op::Param(index) = op {
+                self.comment(&format!("{} = {}", ValueId(value), op));
+                self.mov(slot(ValueId(value)), Reg(ARGUMENTS[*index]));
+            }
+        }
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            let label = self.block_label(id);
+            self.label(&label);
+            for &value in &block.instructions {
+                self.instruction(value)?;
+            }
+            self.terminator(id, &block.terminator)?;
+        }
+        Ok(self.out)
+    }
+}
+
+// GNU assembler source for x86-64 Linux from a module in SSA form, with each instruction
+// under the SSA instruction it comes from. Every value is a 64-bit integer in a stack slot
+// of its own, and strings can only be printed; programs with floats or arrays are an error.
+// Linking it with the C library, as `cc out.s`, makes an executable.
+pub fn emit_asm(module: &Module, syntax: Syntax) -> Result<String, String> {
+    let mut globals = Vec::new();
+    for function in &module.functions {
+        for block in &function.blocks {
+            for &value in &block.instructions {
+                if let Op::LoadGlobal(name) | Op::StoreGlobal(name, _) = function.op(value)
+                    && !globals.contains(name)
+                {
+                    globals.push(*name);
+                }
+            }
+        }
+    }
+
+    let bools = module.bools();
+    let mut strings = Vec::new();
+    let mut functions = Vec::new();
+    for (i, function) in module.functions.iter().enumerate() {
+        let name = if i == 0 { "main".to_string() } else { symbol("tl_", &function.name) };
+        let lowering = Lowering { syntax, module, function, bools: &bools[i], name, globals: &globals, out: String::new(), strings: &mut strings };
+        functions.push(lowering.function()?);
+    }
+
+    let mut out = String::from("# Generated by tiny-lang\n");
+    if syntax == Syntax::Intel {
+        out.push_str("    .intel_syntax noprefix\n");
+    }
+    out.push_str("    .text\n    .globl main\n");
+    for function in functions {
+        out.push_str(&function);
+    }
+    out.push('\n');
+    out.push_str(match syntax {
+        Syntax::Intel => RUNTIME_INTEL,
+        Syntax::Att => RUNTIME_ATT,
+    });
+    for (i, s) in strings.iter().enumerate() {
+        writeln!(out, ".Lstring{}:\n    .ascii  \"{}\"", i, ascii(s)).unwrap();
+    }
+    if !globals.is_empty() {
+        out.push_str("\n    .data\n");
+        for name in &globals {
+            writeln!(out, "{}:\n    .quad   0", symbol("g_", name.as_str())).unwrap();
+        }
+    }
+    out.push_str("\n    .section .note.GNU-stack,\"\",@progbits\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str, syntax: Syntax) -> Result<String, String> {
+        emit_asm(&build_ssa(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap()), syntax)
+    }
+
+    #[test]
+    fn writes_either_syntax_with_symbols_and_strings_the_assembler_takes() {
+        let source = "fn dé(n) { return n * 2; }\nprint(dé(20), \"\\\"ok\\\"\\n\");\n";
+        let att = emit(source, Syntax::Att).unwrap();
+        assert!(att.contains("tl_d_ue9_:\n"), "{}", att);
+        assert!(att.contains("    imulq   %rcx, %rax\n"), "{}", att);
+        assert!(att.contains(".Lstring0:\n    .ascii  \"\\042ok\\042\\012\"\n"), "{}", att);
+        let intel = emit(source, Syntax::Intel).unwrap();
+        assert!(intel.starts_with("# Generated by tiny-lang\n    .intel_syntax noprefix\n"));
+        assert!(intel.contains("    imul    rax, rcx\n"), "{}", intel);
+    }
+
+    #[test]
+    fn what_the_backend_cant_lower_is_an_error() {
+        assert_eq!(emit("print(1.5);", Syntax::Att).unwrap_err(), "Floating point values aren't supported by the x86-64 backend");
+        assert_eq!(emit("a = [1];\nprint(a[0]);", Syntax::Att).unwrap_err(), "Arrays aren't supported by the x86-64 backend");
+        assert_eq!(emit("print(len(\"ab\"));", Syntax::Att).unwrap_err(), "`len` isn't supported by the x86-64 backend");
+        let source = "fn f(a, b, c, d, e, f, g) { return a; }\nprint(f(1, 2, 3, 4, 5, 6, 7));";
+        assert_eq!(emit(source, Syntax::Att).unwrap_err(), "Functions with more than 6 parameters aren't supported by the x86-64 backend");
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use crate::diagnostics::Span;
+use crate::lexer::quote_string;
+use crate::stdlib::BUILTINS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Fetch(usize),
+    Store(usize),
+    LFetch(usize),
+    LStore(usize),
+    Push(i64),
+    PushBool(bool),
+    PushFloat(f64),
+    // Pushes an entry of the string table as a string value.
+    PushStr(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    Neg,
+    Not,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+    Pop,
+    // Jump targets are instruction indices; they are turned into byte offsets when listed.
+    Jmp(usize),
+    Jz(usize),
+    // Calls name an entry in the function table.
+    Call(usize),
+    // Calls the builtin at this index of `stdlib::BUILTINS`.
+    Builtin(usize),
+    // Pops this many values and pushes an array of them, the first popped last.
+    Array(usize),
+    // Pops an index and an array and pushes the element there.
+    Index,
+    // Pops an index and an array and stores the value under them there, leaving the value.
+    SetIndex,
+    // Pops an array or a string and pushes its length.
+    Len,
+    Ret,
+    Prtc,
+    Prti,
+    Prts,
+    Halt
+}
+
+impl Instruction {
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::Fetch(_) | Instruction::Store(_) | Instruction::LFetch(_) | Instruction::LStore(_)
+            | Instruction::Push(_) | Instruction::PushBool(_) | Instruction::PushFloat(_) | Instruction::PushStr(_)
+            | Instruction::Jmp(_) | Instruction::Jz(_) | Instruction::Call(_) | Instruction::Builtin(_)
+            | Instruction::Array(_) => 5,
+            _ => 1,
+        }
+    }
+}
+
+// One instruction as it's listed, except that jumps show the index of their target.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Fetch(slot) => write!(f, "fetch [{}]", slot),
+            Instruction::Store(slot) => write!(f, "store [{}]", slot),
+            Instruction::LFetch(slot) => write!(f, "lfetch [{}]", slot),
+            Instruction::LStore(slot) => write!(f, "lstore [{}]", slot),
+            Instruction::Call(function) => write!(f, "call  [{}]", function),
+            // One that doesn't exist is shown by index, such as in a corrupt `.tbc` file's error.
+            Instruction::Builtin(builtin) => match BUILTINS.get(*builtin) {
+                Some(builtin) => write!(f, "bcall {}", builtin.name),
+                None => write!(f, "bcall [{}]", builtin),
+            },
+            Instruction::Array(n) => write!(f, "array {}", n),
+            Instruction::Index => write!(f, "index"),
+            Instruction::SetIndex => write!(f, "setidx"),
+            Instruction::Len => write!(f, "len"),
+            Instruction::Ret => write!(f, "ret"),
+            Instruction::Push(n) => write!(f, "push  {}", n),
+            Instruction::PushBool(b) => write!(f, "push  {}", b),
+            Instruction::PushFloat(x) => write!(f, "push  {:?}", x),
+            Instruction::PushStr(index) => write!(f, "pushs [{}]", index),
+            // Listings show byte offsets instead.
+            Instruction::Jmp(target) => write!(f, "jmp   {}", target),
+            Instruction::Jz(target) => write!(f, "jz    {}", target),
+            Instruction::Add => write!(f, "add"),
+            Instruction::Sub => write!(f, "sub"),
+            Instruction::Mul => write!(f, "mul"),
+            Instruction::Div => write!(f, "div"),
+            Instruction::Mod => write!(f, "mod"),
+            Instruction::Lt => write!(f, "lt"),
+            Instruction::Gt => write!(f, "gt"),
+            Instruction::Le => write!(f, "le"),
+            Instruction::Ge => write!(f, "ge"),
+            Instruction::Eq => write!(f, "eq"),
+            Instruction::Ne => write!(f, "ne"),
+            Instruction::Neg => write!(f, "neg"),
+            Instruction::Not => write!(f, "not"),
+            Instruction::BitAnd => write!(f, "band"),
+            Instruction::BitOr => write!(f, "bor"),
+            Instruction::BitXor => write!(f, "bxor"),
+            Instruction::BitNot => write!(f, "bnot"),
+            Instruction::Shl => write!(f, "shl"),
+            Instruction::Shr => write!(f, "shr"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Prtc => write!(f, "prtc"),
+            Instruction::Prti => write!(f, "prti"),
+            Instruction::Prts => write!(f, "prts"),
+            Instruction::Halt => write!(f, "halt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub arity: usize,
+    pub locals: usize,
+    // Instruction index of the function's first instruction.
+    pub entry: usize
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bytecode {
+    pub globals: Vec<String>,
+    pub strings: Vec<String>,
+    pub functions: Vec<FunctionInfo>,
+    pub code: Vec<Instruction>,
+    // The statement each instruction was compiled from, by index, or for arithmetic that can
+    // fail the expression. It's empty when that isn't known: `.tbc` files don't store it.
+    pub spans: Vec<Span>
+}
+
+impl Bytecode {
+    pub fn span(&self, pc: usize) -> Option<Span> {
+        self.spans.get(pc).copied()
+    }
+
+    // The byte offset of each instruction, and of the end of the code.
+    pub fn offsets(&self) -> Vec<usize> {
+        let mut offsets = Vec::with_capacity(self.code.len() + 1);
+        let mut pc = 0;
+        for instruction in &self.code {
+            offsets.push(pc);
+            pc += instruction.size();
+        }
+        offsets.push(pc);
+        offsets
+    }
+}
+
+impl fmt::Display for Bytecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Datasize: {} Strings: {}", self.globals.len(), self.strings.len())?;
+        for s in &self.strings {
+            writeln!(f, "{}", quote_string(s))?;
+        }
+
+        let offsets = self.offsets();
+        for function in &self.functions {
+            writeln!(f, "fn {} arity {} locals {} at {}", function.name, function.arity, function.locals, offsets[function.entry])?;
+        }
+
+        for (i, instruction) in self.code.iter().enumerate() {
+            let pc = offsets[i];
+            write!(f, "{:5} ", pc)?;
+            match instruction {
+                Instruction::Jmp(target) | Instruction::Jz(target) => {
+                    let name = if matches!(instruction, Instruction::Jmp(_)) { "jmp" } else { "jz" };
+                    let address = offsets[*target];
+                    let relative = address as i64 - (pc as i64 + 1);
+                    writeln!(f, "{:<6} ({}) {}", name, relative, address)?
+                }
+                instruction => writeln!(f, "{}", instruction)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_byte_offsets_and_where_jumps_go() {
+        use Instruction::*;
+        let bytecode = Bytecode {
+            globals: vec!["x".to_string()],
+            strings: vec!["a\n".to_string()],
+            functions: vec![FunctionInfo { name: "f".to_string(), arity: 1, locals: 1, entry: 4 }],
+            code: vec![Push(1), Jz(3), PushStr(0), Halt, LFetch(0), Ret],
+            spans: Vec::new(),
+        };
+        assert_eq!(bytecode.offsets(), [0, 5, 10, 15, 16, 21, 22]);
+        assert_eq!(bytecode.to_string(), "\
+Datasize: 1 Strings: 1
+\"a\\n\"
+fn f arity 1 locals 1 at 16
+    0 push  1
+    5 jz     (9) 15
+   10 pushs [0]
+   15 halt
+   16 lfetch [0]
+   21 ret
+");
+    }
+}
@@ -0,0 +1,679 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::sema::{Type, Types, argument_count, infer_types};
+use crate::stdlib::{self, BUILTINS, Kind};
+
+const INDENT: &str = "    ";
+
+// Names a tiny-lang program may use that would clash with C.
+const RESERVED: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "int", "long", "main", "printf", "putchar",
+    "register", "return", "short", "signed", "sizeof", "static", "stdout", "struct", "switch",
+    "typedef", "union", "unsigned", "void", "volatile", "while", "fputs", "concat", "malloc", "strcat",
+    "strcmp", "strcpy", "strlen",
+];
+
+fn variable(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        // A module's `math.square` is `math__square`.
+        name.replace('.', "__")
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\{:03o}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn collect_expression(expr: &Expression, names: &mut BTreeSet<String>) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+        Expression::Symbol(name, _) => {
+            names.insert(name.to_string());
+        }
+        Expression::Unary {operand, ..} => collect_expression(operand, names),
+        Expression::Binary {left, right, ..} => {
+            collect_expression(left, names);
+            collect_expression(right, names);
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            collect_expression(assignee, names);
+            collect_expression(value, names);
+        }
+        Expression::Call {arguments, ..} | Expression::Array {elements: arguments, ..} => {
+            arguments.iter().for_each(|e| collect_expression(e, names))
+        }
+        Expression::Index {target, index, ..} => {
+            collect_expression(target, names);
+            collect_expression(index, names);
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            collect_expression(condition, names);
+            collect_expression(then_value, names);
+            collect_expression(else_value, names);
+        }
+    }
+}
+
+pub(super) fn collect_statement(stmt: &Statement, names: &mut BTreeSet<String>) {
+    match stmt {
+        Statement::Block {body, ..} => body.iter().for_each(|s| collect_statement(s, names)),
+        Statement::Attributed {statement, ..} => collect_statement(statement, names),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => collect_expression(expression, names),
+        Statement::Print {arguments, ..} => arguments.iter().for_each(|e| collect_expression(e, names)),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            collect_expression(condition, names);
+            collect_statement(then_branch, names);
+            if let Some(else_branch) = else_branch {
+                collect_statement(else_branch, names);
+            }
+        }
+        Statement::While {condition, body, ..} => {
+            collect_expression(condition, names);
+            collect_statement(body, names);
+        }
+        Statement::DoWhile {body, condition, ..} => {
+            collect_statement(body, names);
+            collect_expression(condition, names);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                collect_expression(expr, names);
+            }
+            collect_statement(body, names);
+        }
+        // Variables used by functions are collected separately, see `emit_c`.
+        Statement::Function {..} | Statement::Import {..} => {}
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                collect_expression(value, names);
+            }
+        }
+    }
+}
+
+// Expressions that produce a boolean by their syntax alone, which the JavaScript and Rust
+// backends write as their own bools.
+pub(super) fn is_boolean(expr: &Expression) -> bool {
+    match expr {
+        Expression::Boolean(..) => true,
+        Expression::Unary {operator, ..} => *operator == TokenKind::OpNot,
+        Expression::Binary {operator, ..} => matches!(operator,
+            TokenKind::OpLess | TokenKind::OpLessEqual | TokenKind::OpGreater | TokenKind::OpGreaterEqual
+            | TokenKind::OpEqual | TokenKind::OpNotEqual | TokenKind::OpAnd | TokenKind::OpOr),
+        _ => false,
+    }
+}
+
+// Whether `expr` is a string, given the variables that hold strings.
+pub(super) fn is_string(expr: &Expression, strings: &HashSet<String>) -> bool {
+    match expr {
+        Expression::String(..) => true,
+        Expression::Symbol(name, _) => strings.contains(name.as_str()),
+        Expression::Binary {left, operator: TokenKind::OpAdd, right, ..} => is_string(left, strings) || is_string(right, strings),
+        Expression::Assignment {value, ..} => is_string(value, strings),
+        Expression::Conditional {then_value, else_value, ..} => is_string(then_value, strings) && is_string(else_value, strings),
+        Expression::Call {callee, ..} => stdlib::builtin(callee.as_str()).is_some_and(|builtin| builtin.returns == Kind::String),
+        _ => false,
+    }
+}
+
+fn assign_strings_in_expression(expr: &Expression, strings: &mut HashSet<String>) {
+    let mut each = |expr: &Expression| assign_strings_in_expression(expr, strings);
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => {}
+        Expression::Unary {operand, ..} => each(operand),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..} => {
+            each(left);
+            each(right);
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            each(value);
+            if let Expression::Symbol(name, _) = assignee.as_ref() && is_string(value, strings) {
+                strings.insert(name.to_string());
+            }
+        }
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => elements.iter().for_each(each),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            each(condition);
+            each(then_value);
+            each(else_value);
+        }
+    }
+}
+
+fn assign_strings(stmt: &Statement, strings: &mut HashSet<String>) {
+    let mut each = |expr: &Expression| assign_strings_in_expression(expr, strings);
+    match stmt {
+        Statement::Block {body, ..} | Statement::Function {body, ..} => body.iter().for_each(|s| assign_strings(s, strings)),
+        Statement::Attributed {statement, ..} => assign_strings(statement, strings),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => each(expression),
+        Statement::Print {arguments, ..} => arguments.iter().for_each(each),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            each(condition);
+            assign_strings(then_branch, strings);
+            if let Some(else_branch) = else_branch {
+                assign_strings(else_branch, strings);
+            }
+        }
+        Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+            each(condition);
+            assign_strings(body, strings);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            [initializer, condition, step].into_iter().flatten().for_each(each);
+            assign_strings(body, strings);
+        }
+        Statement::Return {value, ..} => value.iter().for_each(each),
+        Statement::Import {..} => {}
+    }
+}
+
+// The variables that are ever assigned a string, anywhere in the program; the type checker
+// has made sure they're never assigned anything else. Everything else is a `long`.
+pub(super) fn string_variables(program: &[Statement]) -> HashSet<String> {
+    let mut strings = HashSet::new();
+    loop {
+        let before = strings.len();
+        program.iter().for_each(|stmt| assign_strings(stmt, &mut strings));
+        if strings.len() == before {
+            return strings;
+        }
+    }
+}
+
+fn signature(name: Symbol, parameters: &[Symbol]) -> String {
+    let parameters: Vec<String> = parameters.iter().map(|p| format!("long {}", variable(p.as_str()))).collect();
+    let parameters = if parameters.is_empty() { "void".to_string() } else { parameters.join(", ") };
+    format!("long {}({})", variable(name.as_str()), parameters)
+}
+
+// Joins two strings into a new one, which is never freed.
+const CONCAT: &str = "\
+static const char *concat(const char *left, const char *right) {
+    char *result = malloc(strlen(left) + strlen(right) + 1);
+    strcpy(result, left);
+    strcat(result, right);
+    return result;
+}
+";
+
+// Division by zero is an error as in the interpreter, and the lowest integer divided by -1
+// wraps around rather than overflowing, which C leaves undefined.
+const DIVIDE: &str = "\
+static long tl_div(long left, long right) {
+    if (right == 0) {
+        fprintf(stderr, \"error: cannot divide by zero\\n\");
+        exit(1);
+    }
+    return right == -1 ? (long) -(unsigned long) left : left / right;
+}
+
+static long tl_mod(long left, long right) {
+    if (right == 0) {
+        fprintf(stderr, \"error: cannot take the remainder of a division by zero\\n\");
+        exit(1);
+    }
+    return right == -1 ? 0 : left % right;
+}
+";
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the C backend", what)
+}
+
+// Whether evaluating `expr` can do something a value next to it could see or be seen by:
+// assign, call or fail.
+pub(super) fn has_effects(expr: &Expression) -> bool {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => false,
+        Expression::Assignment {..} | Expression::Call {..} => true,
+        Expression::Binary {operator: TokenKind::OpDivide | TokenKind::OpMod, ..} => true,
+        Expression::Unary {operand, ..} => has_effects(operand),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..} => {
+            has_effects(left) || has_effects(right)
+        }
+        Expression::Array {elements, ..} => elements.iter().any(has_effects),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            has_effects(condition) || has_effects(then_value) || has_effects(else_value)
+        }
+    }
+}
+
+struct Emitter {
+    out: String,
+    depth: usize,
+    // C has no bool type, so what prints as `true` or `false` is what the checker says is a bool.
+    types: Types,
+    strings: HashSet<String>,
+    // Whether `concat` is used, and `strcmp` or `strlen`.
+    concat: bool,
+    compare: bool,
+    functions: Vec<Symbol>,
+    // The builtins used, as indexes into `BUILTINS`, whose definitions go at the top.
+    builtins: BTreeSet<usize>,
+    // Whether `tl_div` and `tl_mod` are used.
+    divides: bool,
+    // The types of the temporaries the function being written holds values in, see `in_order`.
+    temporaries: Vec<&'static str>,
+}
+
+impl Emitter {
+    fn expression(&mut self, expr: &Expression) -> Result<String, String> {
+        Ok(match expr {
+            Expression::Number(n, _) => format!("{}L", n),
+            Expression::Char(c, _) => format!("{}L", *c as u32),
+            Expression::Boolean(b, _) => format!("{}L", *b as i64),
+            Expression::String(s, _) => string_literal(s),
+            Expression::Float(..) => return Err(unsupported("Floating point values")),
+            Expression::Array {..} | Expression::Index {..} => return Err(unsupported("Arrays")),
+            Expression::Symbol(name, _) => variable(name.as_str()),
+            // Integer arithmetic wraps like the interpreter's, so it's done unsigned, where
+            // overflow isn't undefined.
+            Expression::Unary {operator: TokenKind::OpSubtract, operand, ..} => {
+                format!("(long) -(unsigned long) {}", self.operand_expression(operand)?)
+            }
+            Expression::Unary {operator, operand, ..} => format!("{}{}", operator, self.operand_expression(operand)?),
+            // `&&` and `||` evaluate in order already.
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAnd | TokenKind::OpOr), right, ..} => {
+                format!("{} {} {}", self.operand_expression(left)?, operator, self.operand_expression(right)?)
+            }
+            Expression::Binary {left, operator: TokenKind::OpAdd, right, ..} if is_string(expr, &self.strings) => {
+                self.concat = true;
+                let written = vec![self.expression(left)?, self.expression(right)?];
+                self.in_order(&[left, right], written, |w| format!("concat({}, {})", w[0], w[1]))
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpEqual | TokenKind::OpNotEqual), right, ..}
+                if is_string(left, &self.strings) =>
+            {
+                self.compare = true;
+                let written = vec![self.expression(left)?, self.expression(right)?];
+                self.in_order(&[left, right], written, |w| format!("strcmp({}, {}) {} 0", w[0], w[1], operator))
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                let written = vec![self.operand_expression(left)?, self.operand_expression(right)?];
+                if matches!(operator, TokenKind::OpDivide | TokenKind::OpMod) {
+                    self.divides = true;
+                }
+                self.in_order(&[left, right], written, |w| match operator {
+                    TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpMultiply => {
+                        format!("(long) ((unsigned long) {} {} (unsigned long) {})", w[0], operator, w[1])
+                    }
+                    TokenKind::OpDivide => format!("tl_div({}, {})", w[0], w[1]),
+                    TokenKind::OpMod => format!("tl_mod({}, {})", w[0], w[1]),
+                    // Shift counts wrap modulo 64 like the interpreter; C leaves larger counts
+                    // undefined.
+                    TokenKind::OpShiftLeft | TokenKind::OpShiftRight => format!("{} {} ({} & 63L)", w[0], operator, w[1]),
+                    _ => format!("{} {} {}", w[0], operator, w[1]),
+                })
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                format!("{} = {}", self.expression(assignee)?, self.expression(value)?)
+            }
+            // User functions take precedence over builtins with the same name. Only strings
+            // have a length here.
+            Expression::Call {callee, arguments, ..} if callee.as_str() == "len" && !self.functions.contains(callee) => {
+                match arguments.as_slice() {
+                    [argument] if is_string(argument, &self.strings) => {
+                        self.compare = true;
+                        format!("(long) strlen({})", self.expression(argument)?)
+                    }
+                    [_] => return Err(unsupported("Arrays")),
+                    _ => return Err(argument_count(*callee, 1, arguments.len())),
+                }
+            }
+            Expression::Call {callee, arguments, ..}
+                if !self.functions.contains(callee) && let Some(builtin) = stdlib::index(callee.as_str()) =>
+            {
+                self.builtin(builtin, arguments)?
+            }
+            Expression::Call {callee, arguments, ..} => {
+                if arguments.iter().any(|argument| is_string(argument, &self.strings)) {
+                    return Err(unsupported("Strings passed to functions"));
+                }
+                let written = arguments.iter().map(|argument| self.expression(argument)).collect::<Result<Vec<_>, _>>()?;
+                let arguments: Vec<&Expression> = arguments.iter().collect();
+                self.in_order(&arguments, written, |arguments| format!("{}({})", variable(callee.as_str()), arguments.join(", ")))
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let condition = self.operand_expression(condition)?;
+                format!("{} ? {} : {}", condition, self.operand_expression(then_value)?, self.operand_expression(else_value)?)
+            }
+        })
+    }
+
+    // A call to the builtin at `index` in `BUILTINS`. `str`, `ord` and `int` of what's
+    // already of the type they convert to are that value.
+    fn builtin(&mut self, index: usize, arguments: &[Expression]) -> Result<String, String> {
+        let builtin = &BUILTINS[index];
+        if arguments.len() != builtin.arity() {
+            return Err(argument_count(Symbol::from(builtin.name), builtin.arity(), arguments.len()));
+        }
+        let kinds: Vec<Kind> = arguments.iter()
+            .map(|argument| if is_string(argument, &self.strings) { Kind::String } else { Kind::Long })
+            .collect();
+        if matches!(builtin.name, "str" | "ord" | "int") && kinds[0] == builtin.returns {
+            return self.expression(&arguments[0]);
+        }
+        // `str` of a bool is "true" or "false", as `print` shows it.
+        if builtin.name == "str" && self.types.of(&arguments[0]) == Type::Bool {
+            return Ok(format!("({} ? \"true\" : \"false\")", self.operand_expression(&arguments[0])?));
+        }
+        if let Some(i) = (0..kinds.len()).find(|&i| kinds[i] != builtin.takes[i]) {
+            let expected = if builtin.takes[i] == Kind::String { "strings" } else { "numbers" };
+            return Err(unsupported(&format!("Arguments to `{}` other than {}", builtin.name, expected)));
+        }
+        self.builtins.insert(index);
+        let written = arguments.iter().map(|argument| self.expression(argument)).collect::<Result<Vec<_>, _>>()?;
+        let arguments: Vec<&Expression> = arguments.iter().collect();
+        Ok(self.in_order(&arguments, written, |arguments| format!("tl_{}({})", builtin.name, arguments.join(", "))))
+    }
+
+    // `make` of the `written` values, evaluated left to right as the interpreter does. C
+    // leaves the order of operands and arguments unspecified, so a value that something
+    // after it has an effect on or through is worked out first into a temporary.
+    fn in_order(&mut self, values: &[&Expression], mut written: Vec<String>, make: impl FnOnce(&[String]) -> String) -> String {
+        let mut first = Vec::new();
+        for (i, value) in values.iter().enumerate() {
+            let constant = matches!(value, Expression::Number(..) | Expression::Char(..) | Expression::Boolean(..) | Expression::String(..));
+            if !constant && values[i + 1..].iter().any(|later| has_effects(later)) {
+                let temporary = format!("tl_t{}", self.temporaries.len());
+                self.temporaries.push(if is_string(value, &self.strings) { "const char *" } else { "long " });
+                first.push(format!("{} = {}", temporary, written[i]));
+                written[i] = temporary;
+            }
+        }
+        let made = make(&written);
+        if first.is_empty() {
+            made
+        } else {
+            format!("({}, {})", first.join(", "), made)
+        }
+    }
+
+    // Declares the function's temporaries at `position`, ahead of its statements.
+    fn declare_temporaries(&mut self, position: usize) {
+        let declarations: String = std::mem::take(&mut self.temporaries).iter().enumerate()
+            .map(|(i, type_)| format!("{}{}tl_t{} = 0;\n", INDENT.repeat(self.depth), type_, i))
+            .collect();
+        self.out.insert_str(position, &declarations);
+    }
+
+    // Nested operators are always parenthesized rather than relying on C precedence.
+    fn operand_expression(&mut self, expr: &Expression) -> Result<String, String> {
+        match expr {
+            Expression::Binary {..} | Expression::Assignment {..} | Expression::Unary {..} | Expression::Conditional {..} => {
+                Ok(format!("({})", self.expression(expr)?))
+            }
+            Expression::Number(n, _) if *n < 0 => Ok(format!("({})", self.expression(expr)?)),
+            _ => self.expression(expr),
+        }
+    }
+
+    // How a variable is declared, starting out as 0 or the empty string.
+    fn declaration(&self, name: &str) -> String {
+        if self.strings.contains(name) {
+            format!("const char *{} = \"\"", variable(name))
+        } else {
+            format!("long {} = 0", variable(name))
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn body(&mut self, stmt: &Statement) -> Result<(), String> {
+        self.depth += 1;
+        match stmt {
+            Statement::Block {body, ..} => body.iter().try_for_each(|s| self.statement(s))?,
+            _ => self.statement(stmt)?,
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Attributed {statement, ..} => self.statement(statement)?,
+            Statement::Block {body, ..} if body.is_empty() => self.line(";"),
+            Statement::Block {..} => {
+                self.line("{");
+                self.body(stmt)?;
+                self.line("}");
+            }
+            Statement::Expression {expression: e, ..} => {
+                let e = self.expression(e)?;
+                self.line(&format!("{};", e));
+            }
+            Statement::Print {arguments, ..} => {
+                for arg in arguments {
+                    let line = match arg {
+                        Expression::String(s, _) => format!("fputs({}, stdout);", string_literal(s)),
+                        e if self.types.of(e) == Type::Bool => format!("fputs({} ? \"true\" : \"false\", stdout);", self.operand_expression(e)?),
+                        e if is_string(e, &self.strings) => format!("printf(\"%s\", {});", self.expression(e)?),
+                        e => format!("printf(\"%ld\", {});", self.expression(e)?),
+                    };
+                    self.line(&line);
+                }
+            }
+            Statement::Putc {expression: e, ..} => {
+                let e = self.operand_expression(e)?;
+                self.line(&format!("putchar((int) {});", e));
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let condition = self.expression(condition)?;
+                self.line(&format!("if ({}) {{", condition));
+                self.body(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.body(else_branch)?;
+                }
+                self.line("}");
+            }
+            Statement::While {condition, body, ..} => {
+                let condition = self.expression(condition)?;
+                self.line(&format!("while ({}) {{", condition));
+                self.body(body)?;
+                self.line("}");
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                self.line("do {");
+                self.body(body)?;
+                let condition = self.expression(condition)?;
+                self.line(&format!("}} while ({});", condition));
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                let mut clause = |expr: &Option<Expression>| expr.as_ref().map_or(Ok(String::new()), |e| self.expression(e));
+                let clauses = [clause(initializer)?, clause(condition)?, clause(step)?];
+                self.line(&format!("for ({}) {{", clauses.join("; ")));
+                self.body(body)?;
+                self.line("}");
+            }
+            Statement::Function {name, parameters, body, ..} => {
+                self.line(&format!("{} {{", signature(*name, parameters)));
+                self.depth += 1;
+                let locals = function_locals(parameters, body);
+                for local in &locals[parameters.len()..] {
+                    let declaration = self.declaration(local.as_str());
+                    self.line(&format!("{};", declaration));
+                }
+                let temporaries = self.out.len();
+                if locals.len() > parameters.len() {
+                    self.line("");
+                }
+                body.iter().try_for_each(|s| self.statement(s))?;
+                self.declare_temporaries(temporaries);
+                self.line("return 0;");
+                self.depth -= 1;
+                self.line("}");
+            }
+            Statement::Return {value: Some(value), ..} if is_string(value, &self.strings) => {
+                return Err(unsupported("Strings returned from functions"));
+            }
+            Statement::Return {value: Some(value), ..} => {
+                let value = self.expression(value)?;
+                self.line(&format!("return {};", value));
+            }
+            Statement::Return {value: None, ..} => self.line("return 0;"),
+            // Linking has put the module in its place.
+            Statement::Import {..} => {}
+        }
+        Ok(())
+    }
+}
+
+// Readable C89 for the program: integers and booleans are `long`s and strings are
+// `const char *`s, printed with `printf`. Floats, arrays and passing strings to functions
+// aren't supported, except to the builtins that take them, and are an error.
+pub fn emit_c(program: &[Statement]) -> Result<String, String> {
+    let mut globals = BTreeSet::new();
+    program.iter().for_each(|s| collect_statement(s, &mut globals));
+
+    let functions: Vec<&Statement> = program.iter()
+        .filter(|s| matches!(s, Statement::Function {..}))
+        .collect();
+    for function in &functions {
+        if let Statement::Function {parameters, body, ..} = function {
+            // Anything a function uses that isn't one of its locals is a global.
+            let locals = function_locals(parameters, body);
+            let mut names = BTreeSet::new();
+            body.iter().for_each(|s| collect_statement(s, &mut names));
+            globals.extend(names.into_iter().filter(|n| !locals.iter().any(|l| l.as_str() == n)));
+        }
+    }
+
+    // The definitions come first, so that what they use is known by the time the
+    // declarations ahead of them are written.
+    let names = functions.iter()
+        .filter_map(|s| match s {
+            Statement::Function {name, ..} => Some(*name),
+            _ => None,
+        })
+        .collect();
+    let mut emitter = Emitter {
+        out: String::new(), depth: 0, types: infer_types(program), strings: string_variables(program), concat: false, compare: false,
+        functions: names, builtins: BTreeSet::new(), divides: false, temporaries: Vec::new(),
+    };
+    for function in &functions {
+        emitter.statement(function)?;
+        emitter.line("");
+    }
+    emitter.line("int main(void) {");
+    let main = emitter.out.len();
+    emitter.depth += 1;
+    for stmt in program.iter().filter(|s| !matches!(s, Statement::Function {..})) {
+        emitter.statement(stmt)?;
+    }
+    emitter.declare_temporaries(main);
+    emitter.line("return 0;");
+    emitter.depth -= 1;
+    emitter.line("}");
+    let mut definitions = std::mem::take(&mut emitter.out);
+    let builtins = std::mem::take(&mut emitter.builtins);
+    // What the builtins need done first; the statements have been written by now.
+    let mut setup: Vec<&str> = Vec::new();
+    for &builtin in &builtins {
+        if !BUILTINS[builtin].setup.is_empty() && !setup.contains(&BUILTINS[builtin].setup) {
+            setup.push(BUILTINS[builtin].setup);
+        }
+    }
+    let lines: String = setup.iter().map(|line| format!("{}{}\n", INDENT, line)).collect();
+    definitions.insert_str(main, &lines);
+    if setup.iter().any(|line| line.contains("argv")) {
+        definitions = definitions.replacen("int main(void) {", "int main(int argc, char **argv) {", 1);
+    }
+
+    let mut headers = BTreeSet::from(["stdio.h"]);
+    if emitter.concat || emitter.divides {
+        headers.insert("stdlib.h");
+    }
+    if emitter.concat || emitter.compare {
+        headers.insert("string.h");
+    }
+    headers.extend(builtins.iter().flat_map(|&builtin| BUILTINS[builtin].headers));
+    for header in headers {
+        emitter.line(&format!("#include <{}>", header));
+    }
+    emitter.line("");
+    if emitter.concat {
+        emitter.out.push_str(CONCAT);
+        emitter.line("");
+    }
+    if emitter.divides {
+        emitter.out.push_str(DIVIDE);
+        emitter.line("");
+    }
+    for builtin in builtins {
+        emitter.out.push_str(BUILTINS[builtin].c);
+        emitter.line("");
+    }
+    for name in &globals {
+        let declaration = emitter.declaration(name);
+        emitter.line(&format!("static {};", declaration));
+    }
+    if !globals.is_empty() {
+        emitter.line("");
+    }
+    for function in &functions {
+        if let Statement::Function {name, parameters, ..} = function {
+            emitter.line(&format!("{};", signature(*name, parameters)));
+        }
+    }
+    if !functions.is_empty() {
+        emitter.line("");
+    }
+    Ok(emitter.out + &definitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str) -> Result<String, String> {
+        emit_c(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap())
+    }
+
+    #[test]
+    fn what_c_has_no_type_for_is_an_error() {
+        assert!(emit("s = \"a\" + str(1);\nprint(s, len(s) > 1);\n").unwrap().contains("fputs(((long) strlen(s) > 1L) ? \"true\""));
+        assert_eq!(emit("print(1.5);").unwrap_err(), "Floating point values aren't supported by the C backend");
+        assert_eq!(emit("a = [1];").unwrap_err(), "Arrays aren't supported by the C backend");
+        assert_eq!(emit("fn f(s) { return 1; }\nf(\"x\");\n").unwrap_err(), "Strings passed to functions aren't supported by the C backend");
+        assert_eq!(emit("fn f() { return \"x\"; }").unwrap_err(), "Strings returned from functions aren't supported by the C backend");
+    }
+
+    #[test]
+    fn arguments_with_effects_after_them_are_worked_out_first() {
+        let c = emit("fn f(a, b) { return a - b; }\nx = 1;\nprint(f(x, f(2, 3)), f(4, x));\n").unwrap();
+        assert!(c.contains("int main(void) {\n    long tl_t0 = 0;\n    x = 1L;\n"));
+        assert!(c.contains("printf(\"%ld\", (tl_t0 = x, f(tl_t0, f(2L, 3L))));"));
+        assert!(c.contains("printf(\"%ld\", f(4L, x));"));
+        assert!(c.contains("return (long) ((unsigned long) a - (unsigned long) b);"));
+    }
+}
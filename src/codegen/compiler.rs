@@ -0,0 +1,436 @@
+use crate::diagnostics::{Diagnostic, Span};
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::sema::argument_count;
+use crate::stdlib::{self, BUILTINS};
+use super::bytecode::{Bytecode, FunctionInfo, Instruction};
+
+#[derive(Clone, Copy)]
+enum Variable {
+    Global(usize),
+    Local(usize)
+}
+
+#[derive(Default)]
+pub struct Compiler {
+    bytecode: Bytecode,
+    // Locals of the function being compiled, if any.
+    locals: Option<Vec<Symbol>>,
+    // The statement being compiled, which instructions are attributed to.
+    span: Span,
+    // Calls that can't be compiled, which the checker catches before a program gets here.
+    errors: Vec<Diagnostic>
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.bytecode.code.push(instruction);
+        self.bytecode.spans.push(self.span);
+        self.bytecode.code.len() - 1
+    }
+
+    // Emits an instruction that can fail at runtime, such as `div`, attributed to the
+    // expression at `span` rather than to its statement.
+    fn emit_at(&mut self, instruction: Instruction, span: Span) -> usize {
+        let statement = std::mem::replace(&mut self.span, span);
+        let at = self.emit(instruction);
+        self.span = statement;
+        at
+    }
+
+    fn here(&self) -> usize {
+        self.bytecode.code.len()
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.bytecode.code[at] {
+            Instruction::Jmp(t) | Instruction::Jz(t) => *t = target,
+            i => panic!("Cannot patch non-jump instruction {:?}", i),
+        }
+    }
+
+    fn global(&mut self, name: &str) -> usize {
+        match self.bytecode.globals.iter().position(|g| g == name) {
+            Some(slot) => slot,
+            None => {
+                self.bytecode.globals.push(name.to_string());
+                self.bytecode.globals.len() - 1
+            }
+        }
+    }
+
+    fn variable(&mut self, name: Symbol) -> Variable {
+        if let Some(locals) = &self.locals
+            && let Some(slot) = locals.iter().position(|&l| l == name) {
+            return Variable::Local(slot);
+        }
+        Variable::Global(self.global(name.as_str()))
+    }
+
+    fn load(&mut self, variable: Variable) {
+        match variable {
+            Variable::Global(slot) => self.emit(Instruction::Fetch(slot)),
+            Variable::Local(slot) => self.emit(Instruction::LFetch(slot)),
+        };
+    }
+
+    fn store(&mut self, variable: Variable) {
+        match variable {
+            Variable::Global(slot) => self.emit(Instruction::Store(slot)),
+            Variable::Local(slot) => self.emit(Instruction::LStore(slot)),
+        };
+    }
+
+    // Checks a call to `name`, which takes `expected` arguments, with `found` arguments.
+    fn arity(&mut self, name: Symbol, expected: usize, found: usize, span: Span) -> bool {
+        if expected != found {
+            self.errors.push(Diagnostic::error(argument_count(name, expected, found), span).with_code("E0307"));
+        }
+        expected == found
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        match self.bytecode.strings.iter().position(|existing| existing == s) {
+            Some(index) => index,
+            None => {
+                self.bytecode.strings.push(s.to_string());
+                self.bytecode.strings.len() - 1
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        let outer = std::mem::replace(&mut self.span, stmt.span());
+        match stmt {
+            Statement::Block {body, ..} => {
+                for stmt in body {
+                    self.statement(stmt);
+                }
+            }
+            Statement::Attributed {statement, ..} => self.statement(statement),
+            Statement::Expression {expression: Expression::Assignment {assignee, value, ..}, ..}
+                if !matches!(assignee.as_ref(), Expression::Index {..}) =>
+            {
+                // An assignment used as a statement doesn't need to leave its value behind.
+                self.expression(value);
+                let variable = self.assignee(assignee);
+                self.store(variable);
+            }
+            Statement::Expression {expression, ..} => {
+                // Evaluate for side effects, then drop the result.
+                self.expression(expression);
+                self.emit(Instruction::Pop);
+            }
+            Statement::Print {arguments, ..} => {
+                for arg in arguments {
+                    match arg {
+                        Expression::String(s, _) => {
+                            let index = self.string(s);
+                            self.emit(Instruction::Push(index as i64));
+                            self.emit(Instruction::Prts);
+                        }
+                        e => {
+                            self.expression(e);
+                            self.emit(Instruction::Prti);
+                        }
+                    }
+                }
+            }
+            Statement::Putc {expression, ..} => {
+                self.expression(expression);
+                self.emit(Instruction::Prtc);
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                self.expression(condition);
+                let to_else = self.emit(Instruction::Jz(0));
+                self.statement(then_branch);
+                match else_branch {
+                    Some(else_branch) => {
+                        let to_end = self.emit(Instruction::Jmp(0));
+                        let else_start = self.here();
+                        self.patch(to_else, else_start);
+                        self.statement(else_branch);
+                        let end = self.here();
+                        self.patch(to_end, end);
+                    }
+                    None => {
+                        let end = self.here();
+                        self.patch(to_else, end);
+                    }
+                }
+            }
+            Statement::While {condition, body, ..} => {
+                let start = self.here();
+                self.expression(condition);
+                let to_end = self.emit(Instruction::Jz(0));
+                self.statement(body);
+                self.emit(Instruction::Jmp(start));
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                let start = self.here();
+                self.statement(body);
+                self.expression(condition);
+                let to_end = self.emit(Instruction::Jz(0));
+                self.emit(Instruction::Jmp(start));
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                    self.emit(Instruction::Pop);
+                }
+                let start = self.here();
+                let to_end = condition.as_ref().map(|condition| {
+                    self.expression(condition);
+                    self.emit(Instruction::Jz(0))
+                });
+                self.statement(body);
+                if let Some(step) = step {
+                    self.expression(step);
+                    self.emit(Instruction::Pop);
+                }
+                self.emit(Instruction::Jmp(start));
+                if let Some(to_end) = to_end {
+                    let end = self.here();
+                    self.patch(to_end, end);
+                }
+            }
+            // Function bodies are compiled after the main program.
+            Statement::Function {..} | Statement::Import {..} => {}
+            Statement::Return {value, ..} => {
+                match value {
+                    Some(value) => self.expression(value),
+                    None => { self.emit(Instruction::Push(0)); }
+                }
+                self.emit(Instruction::Ret);
+            }
+        }
+        self.span = outer;
+    }
+
+    fn assignee(&mut self, assignee: &Expression) -> Variable {
+        match assignee {
+            Expression::Symbol(name, _) => self.variable(*name),
+            e => panic!("Invalid assignment target: {:?}", e),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Number(n, _) => {
+                self.emit(Instruction::Push(*n));
+            }
+            Expression::Char(c, _) => {
+                self.emit(Instruction::Push(*c as i64));
+            }
+            Expression::Float(x, _) => {
+                self.emit(Instruction::PushFloat(*x));
+            }
+            Expression::Boolean(b, _) => {
+                self.emit(Instruction::PushBool(*b));
+            }
+            Expression::String(s, _) => {
+                let index = self.string(s);
+                self.emit(Instruction::PushStr(index));
+            }
+            Expression::Symbol(name, _) => {
+                let variable = self.variable(*name);
+                self.load(variable);
+            }
+            Expression::Unary {operator, operand, span} => {
+                self.expression(operand);
+                match operator {
+                    TokenKind::OpSubtract => { self.emit_at(Instruction::Neg, *span); }
+                    TokenKind::OpAdd => {}
+                    TokenKind::OpNot => { self.emit(Instruction::Not); }
+                    TokenKind::OpBitNot => { self.emit(Instruction::BitNot); }
+                    t => panic!("Unsupported unary operator: {:?}", t),
+                }
+            }
+            Expression::Binary {left, operator: TokenKind::OpAnd, right, ..} => {
+                // Short circuit: skip the right side when the left is false.
+                self.expression(left);
+                let to_false = self.emit(Instruction::Jz(0));
+                self.truthiness(right);
+                let to_end = self.emit(Instruction::Jmp(0));
+                let false_branch = self.here();
+                self.patch(to_false, false_branch);
+                self.emit(Instruction::PushBool(false));
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Expression::Binary {left, operator: TokenKind::OpOr, right, ..} => {
+                // Short circuit: skip the right side when the left is true.
+                self.expression(left);
+                let to_right = self.emit(Instruction::Jz(0));
+                self.emit(Instruction::PushBool(true));
+                let to_end = self.emit(Instruction::Jmp(0));
+                let right_branch = self.here();
+                self.patch(to_right, right_branch);
+                self.truthiness(right);
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Expression::Binary {left, operator, right, span} => {
+                self.expression(left);
+                self.expression(right);
+                let instruction = match operator {
+                    TokenKind::OpAdd => Instruction::Add,
+                    TokenKind::OpSubtract => Instruction::Sub,
+                    TokenKind::OpMultiply => Instruction::Mul,
+                    TokenKind::OpDivide => Instruction::Div,
+                    TokenKind::OpMod => Instruction::Mod,
+                    TokenKind::OpBitAnd => Instruction::BitAnd,
+                    TokenKind::OpBitOr => Instruction::BitOr,
+                    TokenKind::OpBitXor => Instruction::BitXor,
+                    TokenKind::OpShiftLeft => Instruction::Shl,
+                    TokenKind::OpShiftRight => Instruction::Shr,
+                    TokenKind::OpLess => Instruction::Lt,
+                    TokenKind::OpLessEqual => Instruction::Le,
+                    TokenKind::OpGreater => Instruction::Gt,
+                    TokenKind::OpGreaterEqual => Instruction::Ge,
+                    TokenKind::OpEqual => Instruction::Eq,
+                    TokenKind::OpNotEqual => Instruction::Ne,
+                    t => panic!("Unsupported binary operator: {:?}", t),
+                };
+                if matches!(instruction, Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div | Instruction::Mod) {
+                    self.emit_at(instruction, *span);
+                } else {
+                    self.emit(instruction);
+                }
+            }
+            Expression::Assignment {assignee, value, ..} if let Expression::Index {target, index, ..} = assignee.as_ref() => {
+                // The value is evaluated first, as the interpreter does.
+                self.expression(value);
+                self.expression(target);
+                self.expression(index);
+                self.emit_at(Instruction::SetIndex, index.span());
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                // Store, then fetch the value back so the assignment has a result.
+                self.expression(value);
+                let variable = self.assignee(assignee);
+                self.store(variable);
+                self.load(variable);
+            }
+            Expression::Call {callee, arguments, span} => {
+                for arg in arguments {
+                    self.expression(arg);
+                }
+                // User functions take precedence over builtins with the same name.
+                let function = self.bytecode.functions.iter().position(|f| f.name == callee.as_str());
+                let builtin = stdlib::index(callee.as_str());
+                let instruction = match function {
+                    Some(function) if self.arity(*callee, self.bytecode.functions[function].arity, arguments.len(), *span) => {
+                        Instruction::Call(function)
+                    }
+                    None if callee.as_str() == "len" && self.arity(*callee, 1, arguments.len(), *span) => {
+                        self.emit_at(Instruction::Len, arguments[0].span());
+                        return;
+                    }
+                    None if let Some(builtin) = builtin && self.arity(*callee, BUILTINS[builtin].arity(), arguments.len(), *span) => {
+                        self.emit_at(Instruction::Builtin(builtin), *span);
+                        return;
+                    }
+                    None if callee.as_str() != "len" && builtin.is_none() => {
+                        let message = format!("call to undeclared function `{}`", callee);
+                        self.errors.push(Diagnostic::error(message, *span).with_code("E0302"));
+                        return;
+                    }
+                    // The error has been reported, and the code won't be run.
+                    _ => return,
+                };
+                self.emit(instruction);
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                self.expression(condition);
+                let to_else = self.emit(Instruction::Jz(0));
+                self.expression(then_value);
+                let to_end = self.emit(Instruction::Jmp(0));
+                let else_start = self.here();
+                self.patch(to_else, else_start);
+                self.expression(else_value);
+                let end = self.here();
+                self.patch(to_end, end);
+            }
+            Expression::Array {elements, ..} => {
+                for element in elements {
+                    self.expression(element);
+                }
+                self.emit(Instruction::Array(elements.len()));
+            }
+            Expression::Index {target, index, ..} => {
+                self.expression(target);
+                self.expression(index);
+                self.emit_at(Instruction::Index, index.span());
+            }
+        }
+    }
+
+    // Leaves 0 or 1 on the stack.
+    fn truthiness(&mut self, expr: &Expression) {
+        self.expression(expr);
+        self.emit(Instruction::Push(0));
+        self.emit(Instruction::Ne);
+    }
+}
+
+// The program's bytecode, or what's wrong with its calls if it hasn't been checked.
+pub fn compile(program: &[Statement]) -> Result<Bytecode, Vec<Diagnostic>> {
+    let mut compiler = Compiler::default();
+
+    let functions: Vec<(&Vec<Symbol>, &Vec<Statement>, Span)> = program.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Function {name, parameters, body, ..} => {
+                let locals = function_locals(parameters, body).len();
+                compiler.bytecode.functions.push(FunctionInfo { name: name.to_string(), arity: parameters.len(), locals, entry: 0 });
+                Some((parameters, body, stmt.span()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    for stmt in program {
+        compiler.statement(stmt);
+    }
+    compiler.emit(Instruction::Halt);
+
+    for (index, (parameters, body, span)) in functions.into_iter().enumerate() {
+        compiler.bytecode.functions[index].entry = compiler.here();
+        compiler.span = span;
+        compiler.locals = Some(function_locals(parameters, body));
+        for stmt in body {
+            compiler.statement(stmt);
+        }
+        // Falling off the end of a function returns 0.
+        compiler.emit(Instruction::Push(0));
+        compiler.emit(Instruction::Ret);
+    }
+    compiler.locals = None;
+
+    if compiler.errors.is_empty() { Ok(compiler.bytecode) } else { Err(compiler.errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, parse_program, tokenize};
+
+    #[test]
+    fn bad_calls_are_reported_rather_than_compiled() {
+        let compile_source = |source: &str| compile(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap());
+        let errors = compile_source("fn f(a) { return a; }\nfoo(1);\nprint(f(1, 2), len(1, 2));\n").unwrap_err();
+        let messages: Vec<(&str, &str)> = errors.iter().map(|error| (error.code.unwrap(), error.message.as_str())).collect();
+        assert_eq!(messages, [
+            ("E0302", "call to undeclared function `foo`"),
+            ("E0307", "`f` takes 1 argument but 2 were given"),
+            ("E0307", "`len` takes 1 argument but 2 were given"),
+        ]);
+        let bytecode = compile_source("fn len(a, b) { return a; }\nprint(len(1, 2), abs(-1));\n").unwrap();
+        assert!(bytecode.code.contains(&Instruction::Call(0)));
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::sema::argument_count;
+use crate::stdlib::{self, BUILTINS, Kind};
+
+use super::c::{collect_statement, has_effects, is_boolean, is_string, string_variables};
+
+const INDENT: &str = "    ";
+
+// Variables and functions are apart in tiny-lang but share one namespace in JavaScript, so
+// each has a prefix of its own, which also keeps them clear of JavaScript's keywords and
+// globals and of the runtime's names. A module's `math.square` is `f_math__square`.
+fn variable(name: &str) -> String {
+    format!("v_{}", name.replace('.', "__"))
+}
+
+fn function(name: &str) -> String {
+    format!("f_{}", name.replace('.', "__"))
+}
+
+// Escapes `s` for a literal quoted with `quote`, a double quote or a backtick.
+fn escape(s: &str, quote: char) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            // `${` starts an interpolation in a template literal.
+            '$' if quote == '`' && chars.peek() == Some(&'{') => out.push_str("\\$"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Writes output exactly under Node; browsers only have `console.log`, which always ends a
+// line, so there it's written a line at a time.
+const RUNTIME: &str = "\
+let output = \"\";
+const write = typeof process === \"object\" && process.stdout
+    ? (text) => { process.stdout.write(text); }
+    : (text) => {
+        output += text;
+        const end = output.lastIndexOf(\"\\n\");
+        if (end >= 0) {
+            console.log(output.slice(0, end));
+            output = output.slice(end + 1);
+        }
+    };
+";
+
+// Anything left without a newline at the end, for `console.log`.
+const FLUSH: &str = "\
+if (output) {
+    console.log(output);
+}
+";
+
+// Writes a code point the way the interpreter's `putc` does.
+const PUTC: &str = "\
+function putc(code) {
+    const n = Number(BigInt.asUintN(32, code));
+    write(n <= 0x10ffff && (n < 0xd800 || n > 0xdfff) ? String.fromCodePoint(n) : \"\\ufffd\");
+}
+";
+
+// Dividing by zero is an error, as in the interpreter. `BigInt` division already truncates
+// toward zero, and `i64::MIN / -1` is wrapped by the caller.
+const DIVIDE: &str = "\
+function tl_div(l, r) {
+    if (r === 0n) {
+        tl_fail(\"cannot divide by zero\");
+    }
+    return l / r;
+}
+
+function tl_mod(l, r) {
+    if (r === 0n) {
+        tl_fail(\"cannot take the remainder of a division by zero\");
+    }
+    return l % r;
+}
+";
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the JavaScript backend", what)
+}
+
+struct Emitter {
+    out: String,
+    depth: usize,
+    strings: HashSet<String>,
+    functions: Vec<Symbol>,
+    // Whether `putc` is used.
+    putc: bool,
+    // Whether `/` or `%` is, which go through `tl_div` and `tl_mod`.
+    divides: bool,
+    // The builtins used, as indexes into `BUILTINS`, whose definitions go at the top.
+    builtins: BTreeSet<usize>
+}
+
+impl Emitter {
+    // Integers are `BigInt`s, and each arithmetic result is wrapped to 64 bits as in the
+    // interpreter. Bools and strings are JavaScript's own.
+    fn expression(&mut self, expr: &Expression) -> Result<String, String> {
+        Ok(match expr {
+            Expression::Number(n, _) => format!("{}n", n),
+            Expression::Char(c, _) => format!("{}n", *c as u32),
+            Expression::Boolean(b, _) => b.to_string(),
+            Expression::String(s, _) => format!("\"{}\"", escape(s, '"')),
+            // A float would have to be a number, which throws when it meets a `BigInt`.
+            Expression::Float(..) => return Err(unsupported("Floating point values")),
+            Expression::Array {..} | Expression::Index {..} => return Err(unsupported("Arrays")),
+            Expression::Symbol(name, _) => variable(name.as_str()),
+            // `+` would convert a `BigInt` to a number, which throws.
+            Expression::Unary {operator: TokenKind::OpAdd, operand, ..} => self.number(operand)?,
+            Expression::Unary {operator: TokenKind::OpNot, operand, ..} => format!("!{}", self.operand_expression(operand)?),
+            Expression::Unary {operator: TokenKind::OpSubtract, operand, ..} => format!("BigInt.asIntN(64, -{})", self.number(operand)?),
+            Expression::Unary {operator, operand, ..} => format!("{}{}", operator, self.number(operand)?),
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAnd | TokenKind::OpOr), right, ..} => {
+                format!("{} {} {}", self.boolean(left)?, operator, self.boolean(right)?)
+            }
+            // Shift counts wrap modulo 64 as in the interpreter, and so do shifted values.
+            Expression::Binary {left, operator: TokenKind::OpShiftLeft, right, ..} => {
+                format!("BigInt.asIntN(64, {} << ({} & 63n))", self.number(left)?, self.number(right)?)
+            }
+            Expression::Binary {left, operator: TokenKind::OpShiftRight, right, ..} => {
+                format!("{} >> ({} & 63n)", self.number(left)?, self.number(right)?)
+            }
+            // A bool equals the integer it counts as.
+            Expression::Binary {left, operator: operator @ (TokenKind::OpEqual | TokenKind::OpNotEqual), right, ..} => {
+                let operator = if *operator == TokenKind::OpEqual { "===" } else { "!==" };
+                let (left, right) = if is_boolean(left) == is_boolean(right) {
+                    (self.operand_expression(left)?, self.operand_expression(right)?)
+                } else {
+                    (self.number(left)?, self.number(right)?)
+                };
+                format!("{} {} {}", left, operator, right)
+            }
+            Expression::Binary {left, operator: TokenKind::OpAdd, right, ..} if is_string(expr, &self.strings) => {
+                format!("{} + {}", self.operand_expression(left)?, self.operand_expression(right)?)
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpMultiply), right, ..} => {
+                format!("BigInt.asIntN(64, {} {} {})", self.number(left)?, operator, self.number(right)?)
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpDivide | TokenKind::OpMod), right, ..} => {
+                self.divides = true;
+                let function = if *operator == TokenKind::OpDivide { "tl_div" } else { "tl_mod" };
+                format!("BigInt.asIntN(64, {}({}, {}))", function, self.number(left)?, self.number(right)?)
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                format!("{} {} {}", self.number(left)?, operator, self.number(right)?)
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                format!("{} = {}", self.expression(assignee)?, self.expression(value)?)
+            }
+            // User functions take precedence over builtins with the same name.
+            Expression::Call {callee, arguments, ..} if callee.as_str() == "len" && !self.functions.contains(callee) => {
+                match arguments.as_slice() {
+                    [argument] if is_string(argument, &self.strings) => format!("BigInt([...{}].length)", self.operand_expression(argument)?),
+                    [_] => return Err(unsupported("Arrays")),
+                    _ => return Err(argument_count(*callee, 1, arguments.len())),
+                }
+            }
+            Expression::Call {callee, arguments, ..}
+                if !self.functions.contains(callee) && let Some(builtin) = stdlib::index(callee.as_str()) =>
+            {
+                self.builtin(builtin, arguments)?
+            }
+            Expression::Call {callee, arguments, ..} => {
+                let arguments = arguments.iter().map(|argument| self.expression(argument)).collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", function(callee.as_str()), arguments.join(", "))
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let condition = self.operand_expression(condition)?;
+                format!("{} ? {} : {}", condition, self.operand_expression(then_value)?, self.operand_expression(else_value)?)
+            }
+        })
+    }
+
+    // A call to the builtin at `index` in `BUILTINS`, whose numbers are `BigInt`s, with bools
+    // as 0 and 1. `str`, `ord` and `int` of what's already of the type they convert to are
+    // that value.
+    fn builtin(&mut self, index: usize, arguments: &[Expression]) -> Result<String, String> {
+        let builtin = &BUILTINS[index];
+        if builtin.js.is_empty() {
+            return Err(format!("`{}` isn't supported by the JavaScript backend, which has no input, files or environment in a browser", builtin.name));
+        }
+        if arguments.len() != builtin.arity() {
+            return Err(argument_count(Symbol::from(builtin.name), builtin.arity(), arguments.len()));
+        }
+        let kinds: Vec<Kind> = arguments.iter()
+            .map(|argument| if is_string(argument, &self.strings) { Kind::String } else { Kind::Long })
+            .collect();
+        if let Some(i) = (0..kinds.len()).find(|&i| kinds[i] != builtin.takes[i]) {
+            match builtin.name {
+                "str" => return self.expression(&arguments[0]),
+                "ord" | "int" => return self.number(&arguments[0]),
+                _ => {}
+            }
+            let expected = if builtin.takes[i] == Kind::String { "strings" } else { "numbers" };
+            return Err(unsupported(&format!("Arguments to `{}` other than {}", builtin.name, expected)));
+        }
+        self.builtins.insert(index);
+        let arguments = arguments.iter()
+            // `str` of a bool is "true" or "false", as `print` shows it.
+            .map(|argument| if is_string(argument, &self.strings) || builtin.name == "str" { self.expression(argument) } else { self.number(argument) })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("tl_{}({})", builtin.name, arguments.join(", ")))
+    }
+
+    // Nested operators are always parenthesized rather than relying on JavaScript precedence.
+    fn operand_expression(&mut self, expr: &Expression) -> Result<String, String> {
+        match expr {
+            Expression::Binary {..} | Expression::Assignment {..} | Expression::Unary {..} | Expression::Conditional {..} => {
+                Ok(format!("({})", self.expression(expr)?))
+            }
+            Expression::Number(n, _) if *n < 0 => Ok(format!("({})", self.expression(expr)?)),
+            _ => self.expression(expr),
+        }
+    }
+
+    // An operand of arithmetic, where bools count as 0 and 1.
+    fn number(&mut self, expr: &Expression) -> Result<String, String> {
+        match expr {
+            Expression::Boolean(b, _) => Ok(format!("{}n", *b as i64)),
+            _ if is_boolean(expr) => Ok(format!("BigInt({})", self.expression(expr)?)),
+            _ => self.operand_expression(expr),
+        }
+    }
+
+    // An operand of `&&` or `||`, which are always bools in tiny-lang.
+    fn boolean(&mut self, expr: &Expression) -> Result<String, String> {
+        if is_boolean(expr) {
+            self.operand_expression(expr)
+        } else {
+            Ok(format!("!!{}", self.operand_expression(expr)?))
+        }
+    }
+
+    // How a variable is declared, starting out as 0 or the empty string.
+    fn declaration(&self, name: &str) -> String {
+        if self.strings.contains(name) {
+            format!("let {} = \"\";", variable(name))
+        } else {
+            format!("let {} = 0n;", variable(name))
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn body(&mut self, stmt: &Statement) -> Result<(), String> {
+        self.depth += 1;
+        match stmt {
+            Statement::Block {body, ..} => body.iter().try_for_each(|s| self.statement(s))?,
+            _ => self.statement(stmt)?,
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Attributed {statement, ..} => self.statement(statement)?,
+            Statement::Block {body, ..} if body.is_empty() => self.line(";"),
+            Statement::Block {..} => {
+                self.line("{");
+                self.body(stmt)?;
+                self.line("}");
+            }
+            Statement::Expression {expression: e, ..} => {
+                let e = self.expression(e)?;
+                self.line(&format!("{};", e));
+            }
+            // A `print` is one write of a template literal, which formats each value the way
+            // the interpreter does. The interpreter prints each argument as it gets to it, so
+            // one that could print or fail starts another.
+            Statement::Print {arguments, ..} => {
+                let mut template = String::new();
+                for arg in arguments {
+                    match arg {
+                        Expression::String(s, _) => template.push_str(&escape(s, '`')),
+                        e => {
+                            if has_effects(e) && !template.is_empty() {
+                                self.line(&format!("write(`{}`);", std::mem::take(&mut template)));
+                            }
+                            template.push_str(&format!("${{{}}}", self.expression(e)?));
+                        }
+                    }
+                }
+                if !template.is_empty() {
+                    self.line(&format!("write(`{}`);", template));
+                }
+            }
+            Statement::Putc {expression: e, ..} => {
+                self.putc = true;
+                let e = if is_boolean(e) { self.number(e)? } else { self.expression(e)? };
+                self.line(&format!("putc({});", e));
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let condition = self.expression(condition)?;
+                self.line(&format!("if ({}) {{", condition));
+                self.body(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.body(else_branch)?;
+                }
+                self.line("}");
+            }
+            Statement::While {condition, body, ..} => {
+                let condition = self.expression(condition)?;
+                self.line(&format!("while ({}) {{", condition));
+                self.body(body)?;
+                self.line("}");
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                self.line("do {");
+                self.body(body)?;
+                let condition = self.expression(condition)?;
+                self.line(&format!("}} while ({});", condition));
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                let mut clause = |expr: &Option<Expression>| expr.as_ref().map_or(Ok(String::new()), |e| self.expression(e));
+                let clauses = [clause(initializer)?, clause(condition)?, clause(step)?];
+                self.line(&format!("for ({}) {{", clauses.join("; ")));
+                self.body(body)?;
+                self.line("}");
+            }
+            Statement::Function {name, parameters, body, ..} => {
+                let names: Vec<String> = parameters.iter().map(|p| variable(p.as_str())).collect();
+                self.line(&format!("function {}({}) {{", function(name.as_str()), names.join(", ")));
+                self.depth += 1;
+                let locals = function_locals(parameters, body);
+                for local in &locals[parameters.len()..] {
+                    let declaration = self.declaration(local.as_str());
+                    self.line(&declaration);
+                }
+                if locals.len() > parameters.len() {
+                    self.line("");
+                }
+                body.iter().try_for_each(|s| self.statement(s))?;
+                self.line("return 0n;");
+                self.depth -= 1;
+                self.line("}");
+            }
+            Statement::Return {value: Some(value), ..} => {
+                let value = self.expression(value)?;
+                self.line(&format!("return {};", value));
+            }
+            Statement::Return {value: None, ..} => self.line("return 0n;"),
+            // Linking has put the module in its place.
+            Statement::Import {..} => {}
+        }
+        Ok(())
+    }
+}
+
+// A self-contained script for the program that runs in Node or a browser and prints
+// exactly what the interpreter would. Integers are `BigInt`s wrapped to 64 bits;
+// floats, arrays and the builtins that read input, files or the environment aren't
+// supported, and are an error.
+pub fn emit_js(program: &[Statement]) -> Result<String, String> {
+    let mut globals = BTreeSet::new();
+    program.iter().for_each(|s| collect_statement(s, &mut globals));
+
+    let functions: Vec<&Statement> = program.iter()
+        .filter(|s| matches!(s, Statement::Function {..}))
+        .collect();
+    let names = functions.iter()
+        .filter_map(|s| match s {
+            Statement::Function {name, ..} => Some(*name),
+            _ => None,
+        })
+        .collect();
+    let mut emitter = Emitter {
+        out: String::new(), depth: 0, strings: string_variables(program), functions: names, putc: false,
+        divides: false, builtins: BTreeSet::new(),
+    };
+    for function in &functions {
+        emitter.statement(function)?;
+        emitter.line("");
+    }
+    for stmt in program.iter().filter(|s| !matches!(s, Statement::Function {..})) {
+        emitter.statement(stmt)?;
+    }
+    let definitions = std::mem::take(&mut emitter.out);
+
+    emitter.line("// Generated by tiny-lang");
+    emitter.line("\"use strict\";");
+    emitter.line("");
+    emitter.out.push_str(RUNTIME);
+    emitter.line("");
+    if emitter.putc {
+        emitter.out.push_str(PUTC);
+        emitter.line("");
+    }
+    let mut items: Vec<&str> = Vec::new();
+    if emitter.divides {
+        items.extend([stdlib::JS_FAIL, DIVIDE]);
+    }
+    for &builtin in &emitter.builtins {
+        for &item in BUILTINS[builtin].js {
+            if !items.contains(&item) {
+                items.push(item);
+            }
+        }
+    }
+    for item in items {
+        emitter.out.push_str(item);
+        emitter.line("");
+    }
+    // Functions only read globals, so every global is a top-level variable.
+    for name in &globals {
+        let declaration = emitter.declaration(name);
+        emitter.line(&declaration);
+    }
+    if !globals.is_empty() {
+        emitter.line("");
+    }
+    emitter.out.push_str(&definitions);
+    emitter.out.push_str(FLUSH);
+    Ok(emitter.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str) -> Result<String, String> {
+        emit_js(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap())
+    }
+
+    #[test]
+    fn builtins_are_defined_and_what_a_browser_cant_do_is_an_error() {
+        let js = emit("seed(5);\nprint(rand(10), str(1 < 2), len(\"ab\"));\n").unwrap();
+        assert!(js.contains("function tl_seed(") && js.contains("function tl_rand(") && !js.contains("function tl_abs("));
+        assert_eq!(js.matches("let tl_random").count(), 1);
+        assert!(js.contains("write(`${tl_rand(10n)}`);\nwrite(`${tl_str(1n < 2n)}`);\nwrite(`${BigInt([...\"ab\"].length)}`);"));
+        let arithmetic = emit("x = 1;\nprint(-x * 2 + x % 3, \"a\" + str(x));\n").unwrap();
+        assert!(arithmetic.contains("BigInt.asIntN(64, (BigInt.asIntN(64, (BigInt.asIntN(64, -v_x)) * 2n)) + (BigInt.asIntN(64, tl_mod(v_x, 3n))))"), "{}", arithmetic);
+        assert!(arithmetic.contains("\"a\" + tl_str(v_x)") && arithmetic.contains("function tl_fail("));
+        let clashing = emit("f = 1;\nconsole = 2;\nfn f(a) { return a; }\nprint(f(f + console));\n").unwrap();
+        assert!(clashing.contains("function f_f(v_a) {") && clashing.contains("let v_console = 0n;"), "{}", clashing);
+        assert_eq!(emit("print(1.5);").unwrap_err(), "Floating point values aren't supported by the JavaScript backend");
+        assert_eq!(emit("a = [1];").unwrap_err(), "Arrays aren't supported by the JavaScript backend");
+        assert!(emit("print(getc());").unwrap_err().starts_with("`getc` isn't supported by the JavaScript backend"));
+        assert_eq!(emit("print(min(1));").unwrap_err(), "`min` takes 2 arguments but 1 was given");
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::ir::{Constant, Function, Module, Op, Terminator, ValueId};
+use crate::lexer::TokenKind;
+use crate::passes::OptLevel;
+
+// The IR is written as text and built by LLVM's own `opt` and `llc`, rather than through
+// bindings such as inkwell, which would tie the crate to the libraries of one LLVM version
+// to build against; any LLVM whose tools are installed will do.
+
+// Run-time support: printing, and division that fails with the interpreter's error on a
+// zero divisor instead of being undefined. Dividing the lowest integer by -1, which `sdiv` and
+// `srem` leave undefined too, wraps as it does by default in the interpreter.
+const PRELUDE: &str = "\
+declare i32 @printf(i8*, ...)
+declare i32 @putchar(i32)
+declare i64 @write(i32, i8*, i64)
+declare void @exit(i32)
+
+@fmt.int = private unnamed_addr constant [5 x i8] c\"%lld\\00\"
+@fmt.str = private unnamed_addr constant [3 x i8] c\"%s\\00\"
+@str.true = private unnamed_addr constant [5 x i8] c\"true\\00\"
+@str.false = private unnamed_addr constant [6 x i8] c\"false\\00\"
+@err.div = private unnamed_addr constant [29 x i8] c\"error: cannot divide by zero\\0A\"
+@err.mod = private unnamed_addr constant [55 x i8] c\"error: cannot take the remainder of a division by zero\\0A\"
+
+; Reports `message`, `len` bytes long, on stderr and exits with status 1, as the other
+; backends do when a program fails.
+define private void @rt.fail(i8* %message, i64 %len) noreturn {
+  call i64 @write(i32 2, i8* %message, i64 %len)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define private i64 @rt.div(i64 %l, i64 %r) {
+  %zero = icmp eq i64 %r, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @rt.fail(i8* getelementptr inbounds ([29 x i8], [29 x i8]* @err.div, i64 0, i64 0), i64 29)
+  unreachable
+nonzero:
+  %minus = icmp eq i64 %r, -1
+  br i1 %minus, label %negate, label %ok
+negate:
+  %n = sub i64 0, %l
+  ret i64 %n
+ok:
+  %q = sdiv i64 %l, %r
+  ret i64 %q
+}
+
+define private i64 @rt.mod(i64 %l, i64 %r) {
+  %zero = icmp eq i64 %r, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @rt.fail(i8* getelementptr inbounds ([55 x i8], [55 x i8]* @err.mod, i64 0, i64 0), i64 55)
+  unreachable
+nonzero:
+  %minus = icmp eq i64 %r, -1
+  br i1 %minus, label %none, label %ok
+none:
+  ret i64 0
+ok:
+  %m = srem i64 %l, %r
+  ret i64 %m
+}
+";
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the LLVM backend", what)
+}
+
+// A pointer to the first byte of a constant array of `len` bytes.
+fn pointer(global: &str, len: usize) -> String {
+    format!("i8* getelementptr inbounds ([{len} x i8], [{len} x i8]* {global}, i64 0, i64 0)")
+}
+
+fn byte_string(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes().chain([0]) {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn comparison(operator: &TokenKind) -> Option<&'static str> {
+    match operator {
+        TokenKind::OpLess => Some("slt"),
+        TokenKind::OpGreater => Some("sgt"),
+        TokenKind::OpLessEqual => Some("sle"),
+        TokenKind::OpGreaterEqual => Some("sge"),
+        TokenKind::OpEqual => Some("eq"),
+        TokenKind::OpNotEqual => Some("ne"),
+        _ => None,
+    }
+}
+
+struct Lowering<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    // Which values are bools, from `Module::bools`.
+    bools: &'a [bool],
+    out: String,
+    // String literals that are printed, which become globals.
+    strings: &'a mut Vec<String>
+}
+
+impl Lowering<'_> {
+    // Every value is an `i64`. Constants are written where they're used.
+    fn operand(&self, value: ValueId) -> Result<String, String> {
+        match self.function.op(value) {
+            Op::Const(Constant::Integer(n)) => Ok(n.to_string()),
+            Op::Const(Constant::Bool(b)) => Ok((*b as i64).to_string()),
+            Op::Const(Constant::Float(_)) => Err(unsupported("Floating point values")),
+            Op::Const(Constant::String(_)) => Err(unsupported("Strings other than print arguments")),
+            Op::Param(index) => Ok(format!("%p{}", index)),
+            _ => Ok(format!("%v{}", value.0)),
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str("  ");
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // Sets `value` to 1 if the `i1` named `bit` is set, otherwise 0.
+    fn widen(&mut self, value: ValueId, bit: &str) {
+        self.line(&format!("%v{} = zext i1 {} to i64", value.0, bit));
+    }
+
+    fn instruction(&mut self, value: ValueId) -> Result<(), String> {
+        let v = value.0;
+        match self.function.op(value) {
+            Op::Const(_) | Op::Param(_) => {}
+            Op::Unary(operator, operand) => {
+                let operand = self.operand(*operand)?;
+                match operator {
+                    TokenKind::OpSubtract => self.line(&format!("%v{} = sub i64 0, {}", v, operand)),
+                    TokenKind::OpAdd => self.line(&format!("%v{} = add i64 {}, 0", v, operand)),
+                    TokenKind::OpBitNot => self.line(&format!("%v{} = xor i64 {}, -1", v, operand)),
+                    _ => {
+                        self.line(&format!("%v{}.bit = icmp eq i64 {}, 0", v, operand));
+                        self.widen(value, &format!("%v{}.bit", v));
+                    }
+                }
+            }
+            Op::Binary(operator, left, right) => {
+                let (l, r) = (self.operand(*left)?, self.operand(*right)?);
+                if let Some(condition) = comparison(operator) {
+                    self.line(&format!("%v{}.bit = icmp {} i64 {}, {}", v, condition, l, r));
+                    self.widen(value, &format!("%v{}.bit", v));
+                    return Ok(());
+                }
+                let instruction = match operator {
+                    TokenKind::OpAdd => "add",
+                    TokenKind::OpSubtract => "sub",
+                    TokenKind::OpMultiply => "mul",
+                    TokenKind::OpBitAnd => "and",
+                    TokenKind::OpBitOr => "or",
+                    TokenKind::OpBitXor => "xor",
+                    TokenKind::OpDivide | TokenKind::OpMod => {
+                        let helper = if *operator == TokenKind::OpDivide { "div" } else { "mod" };
+                        self.line(&format!("%v{} = call i64 @rt.{}(i64 {}, i64 {})", v, helper, l, r));
+                        return Ok(());
+                    }
+                    // Shift counts wrap modulo 64, as in the interpreter.
+                    TokenKind::OpShiftLeft | TokenKind::OpShiftRight => {
+                        let shift = if *operator == TokenKind::OpShiftLeft { "shl" } else { "ashr" };
+                        self.line(&format!("%v{}.count = and i64 {}, 63", v, r));
+                        self.line(&format!("%v{} = {} i64 {}, %v{}.count", v, shift, l, v));
+                        return Ok(());
+                    }
+                    operator => return Err(format!("`{}` isn't supported by the LLVM backend", operator)),
+                };
+                self.line(&format!("%v{} = {} i64 {}, {}", v, instruction, l, r));
+            }
+            Op::Truthy(operand) => {
+                let operand = self.operand(*operand)?;
+                self.line(&format!("%v{}.bit = icmp ne i64 {}, 0", v, operand));
+                self.widen(value, &format!("%v{}.bit", v));
+            }
+            Op::Call(callee, arguments) => {
+                let Some(function) = self.module.functions[1..].iter().find(|f| f.name == callee.as_str()) else {
+                    return Err(format!("`{}` isn't supported by the LLVM backend", callee));
+                };
+                if function.parameters != arguments.len() {
+                    return Err(format!("`{}` takes {} arguments but {} were given", callee, function.parameters, arguments.len()));
+                }
+                let arguments = arguments.iter().map(|&a| Ok(format!("i64 {}", self.operand(a)?))).collect::<Result<Vec<_>, String>>()?;
+                self.line(&format!("%v{} = call i64 @\"tl.{}\"({})", v, callee, arguments.join(", ")));
+            }
+            Op::LoadGlobal(name) => self.line(&format!("%v{} = load i64, i64* @\"g.{}\"", v, name)),
+            Op::StoreGlobal(name, value) => {
+                let value = self.operand(*value)?;
+                self.line(&format!("store i64 {}, i64* @\"g.{}\"", value, name));
+            }
+            Op::Array(_) | Op::Index(..) | Op::StoreIndex(..) => return Err(unsupported("Arrays")),
+            Op::Print(operand) => {
+                let op = self.function.op(*operand);
+                if let Op::Const(Constant::String(s)) = op {
+                    let global = format!("@str.{}", self.strings.len());
+                    let argument = pointer(&global, s.len() + 1);
+                    self.strings.push(s.clone());
+                    self.line(&format!("call i32 (i8*, ...) @printf({}, {})", pointer("@fmt.str", 3), argument));
+                } else if self.bools[operand.0] {
+                    let operand = self.operand(*operand)?;
+                    self.line(&format!("%v{}.bit = icmp ne i64 {}, 0", v, operand));
+                    self.line(&format!("%v{}.str = select i1 %v{}.bit, {}, {}", v, v, pointer("@str.true", 5), pointer("@str.false", 6)));
+                    self.line(&format!("call i32 (i8*, ...) @printf({}, i8* %v{}.str)", pointer("@fmt.str", 3), v));
+                } else {
+                    let operand = self.operand(*operand)?;
+                    self.line(&format!("call i32 (i8*, ...) @printf({}, i64 {})", pointer("@fmt.int", 5), operand));
+                }
+            }
+            Op::Putc(operand) => {
+                let operand = self.operand(*operand)?;
+                self.line(&format!("%v{}.char = trunc i64 {} to i32", v, operand));
+                self.line(&format!("call i32 @putchar(i32 %v{}.char)", v));
+            }
+            Op::Phi(incoming) => {
+                let incoming = incoming.iter()
+                    .map(|&(block, value)| Ok(format!("[ {}, %b{} ]", self.operand(value)?, block)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                self.line(&format!("%v{} = phi i64 {}", v, incoming.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, block: usize, terminator: &Terminator) -> Result<(), String> {
+        match terminator {
+            Terminator::Jump(target) => self.line(&format!("br label %b{}", target)),
+            Terminator::Branch(condition, then_block, else_block) => {
+                let condition = self.operand(*condition)?;
+                self.line(&format!("%b{}.cond = icmp ne i64 {}, 0", block, condition));
+                self.line(&format!("br i1 %b{}.cond, label %b{}, label %b{}", block, then_block, else_block));
+            }
+            Terminator::Return(Some(value)) => {
+                let value = self.operand(*value)?;
+                self.line(&format!("ret i64 {}", value));
+            }
+            Terminator::Return(None) => self.line("ret i32 0"),
+        }
+        Ok(())
+    }
+
+    fn function(mut self, top_level: bool) -> Result<String, String> {
+        let function = self.function;
+        if top_level {
+            writeln!(self.out, "define i32 @main() {{").unwrap();
+        } else {
+            let parameters: Vec<String> = (0..function.parameters).map(|i| format!("i64 %p{}", i)).collect();
+            writeln!(self.out, "define private i64 @\"tl.{}\"({}) {{", function.name, parameters.join(", ")).unwrap();
+        }
+        for (id, block) in function.blocks.iter().enumerate() {
+            writeln!(self.out, "b{}:", id).unwrap();
+            for &value in &block.instructions {
+                self.instruction(value)?;
+            }
+            self.terminator(id, &block.terminator)?;
+        }
+        self.out.push_str("}\n");
+        Ok(self.out)
+    }
+}
+
+// LLVM IR for a module in SSA form, in which every value is a 64-bit integer: booleans are
+// 0 or 1, and strings can only be printed. Programs with floats or arrays are an error.
+pub fn emit_llvm(module: &Module) -> Result<String, String> {
+    let mut globals = Vec::new();
+    for function in &module.functions {
+        for block in &function.blocks {
+            for &value in &block.instructions {
+                if let Op::LoadGlobal(name) | Op::StoreGlobal(name, _) = function.op(value)
+                    && !globals.contains(name)
+                {
+                    globals.push(*name);
+                }
+            }
+        }
+    }
+
+    let bools = module.bools();
+    let mut strings = Vec::new();
+    let mut functions = Vec::new();
+    for (i, function) in module.functions.iter().enumerate() {
+        let lowering = Lowering { module, function, bools: &bools[i], out: String::new(), strings: &mut strings };
+        functions.push(lowering.function(i == 0)?);
+    }
+
+    let mut out = String::from("; Generated by tiny-lang\n\n");
+    out.push_str(PRELUDE);
+    out.push('\n');
+    for name in &globals {
+        writeln!(out, "@\"g.{}\" = internal global i64 0", name).unwrap();
+    }
+    for (i, s) in strings.iter().enumerate() {
+        writeln!(out, "@str.{} = private unnamed_addr constant [{} x i8] c\"{}\"", i, s.len() + 1, byte_string(s)).unwrap();
+    }
+    for function in functions {
+        out.push('\n');
+        out.push_str(&function);
+    }
+    Ok(out)
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("`{}` failed with {}", program, status)),
+        Err(error) => Err(format!("couldn't run `{}`: {}", program, error)),
+    }
+}
+
+// Optimizes LLVM IR with `opt` and compiles it with `llc` to an object file at `output`,
+// or, unless `object` is set, on to an executable linked by the system's C compiler.
+pub fn build_native(ir: &str, level: OptLevel, output: &Path, object: bool) -> Result<(), String> {
+    let temporary = std::env::temp_dir().join(format!("tiny-lang-{}", std::process::id()));
+    let (source, optimized) = (temporary.with_extension("ll"), temporary.with_extension("bc"));
+    let object_file = if object { output.to_path_buf() } else { temporary.with_extension("o") };
+    std::fs::write(&source, ir).map_err(|error| format!("couldn't write {}: {}", source.display(), error))?;
+
+    let level = level.to_string();
+    let result = run(Command::new("opt").arg(&level).arg(&source).arg("-o").arg(&optimized))
+        .and_then(|()| {
+            run(Command::new("llc").arg(&level).args(["-filetype=obj", "-relocation-model=pic"]).arg(&optimized).arg("-o").arg(&object_file))
+        })
+        .and_then(|()| if object { Ok(()) } else { run(Command::new("cc").arg(&object_file).arg("-o").arg(output)) });
+    for file in [&source, &optimized] {
+        let _ = std::fs::remove_file(file);
+    }
+    if !object {
+        let _ = std::fs::remove_file(&object_file);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str) -> Result<String, String> {
+        emit_llvm(&build_ssa(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap()))
+    }
+
+    #[test]
+    fn defines_each_function_with_its_values_and_phis() {
+        let ir = emit("fn f(n) { i = 0; while (i < n) { i = i + 1; } return i / 2; }\nprint(f(3));\n").unwrap();
+        assert!(ir.contains("define i32 @main() {\n"), "{}", ir);
+        assert!(ir.contains("define private i64 @\"tl.f\"(i64 %p0) {\n"), "{}", ir);
+        assert!(ir.contains(" = phi i64 [ "), "{}", ir);
+        assert!(ir.contains(" = call i64 @rt.div("), "{}", ir);
+    }
+
+    #[test]
+    fn what_the_backend_cant_lower_is_an_error() {
+        assert_eq!(emit("print(1.5);").unwrap_err(), "Floating point values aren't supported by the LLVM backend");
+        assert_eq!(emit("a = [1];\nprint(a[0]);").unwrap_err(), "Arrays aren't supported by the LLVM backend");
+        assert_eq!(emit("print(len(\"ab\"));").unwrap_err(), "`len` isn't supported by the LLVM backend");
+    }
+}
@@ -0,0 +1,23 @@
+mod bytecode;
+mod compiler;
+mod peephole;
+mod tbc;
+mod c;
+mod asm;
+mod wasm;
+mod rust;
+mod js;
+#[cfg(feature = "llvm")]
+mod llvm;
+
+pub use bytecode::{Bytecode, FunctionInfo, Instruction};
+pub use compiler::compile;
+pub use peephole::optimize_bytecode;
+pub use tbc::{MAGIC, VERSION};
+pub use c::emit_c;
+pub use asm::{Syntax, emit_asm};
+pub use wasm::emit_wasm;
+pub use rust::emit_rust;
+pub use js::emit_js;
+#[cfg(feature = "llvm")]
+pub use llvm::{build_native, emit_llvm};
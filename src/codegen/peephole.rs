@@ -0,0 +1,184 @@
+use super::bytecode::{Bytecode, Instruction};
+
+// Whether `instruction` always leaves a number on the stack, so that adding 0 to it or
+// multiplying it by 1 can't change it. `add` isn't one, since it also joins strings.
+fn pushes_number(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Push(_) | Instruction::PushFloat(_) | Instruction::Sub | Instruction::Mul | Instruction::Div
+        | Instruction::Mod | Instruction::Neg | Instruction::BitAnd | Instruction::BitOr | Instruction::BitXor
+        | Instruction::BitNot | Instruction::Shl | Instruction::Shr
+    )
+}
+
+// Instructions that only push a value, so that popping it again undoes them.
+fn pushes(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Fetch(_) | Instruction::LFetch(_) | Instruction::Push(_) | Instruction::PushBool(_)
+        | Instruction::PushFloat(_) | Instruction::PushStr(_)
+    )
+}
+
+fn identity(operand: &Instruction, operator: &Instruction) -> bool {
+    matches!(
+        (operand, operator),
+        (Instruction::Push(0), Instruction::Add | Instruction::Sub) | (Instruction::Push(1), Instruction::Mul | Instruction::Div)
+    )
+}
+
+// Follows a jump to a jump to wherever that one goes, giving up on loops of jumps.
+fn thread(code: &[Instruction], start: usize) -> usize {
+    let mut target = start;
+    for _ in 0..code.len() {
+        match code.get(target) {
+            Some(Instruction::Jmp(next)) => target = *next,
+            _ => return target,
+        }
+    }
+    start
+}
+
+// One pass over the code; whether it changed anything.
+fn pass(bytecode: &mut Bytecode) -> bool {
+    let code = &mut bytecode.code;
+    let mut changed = false;
+    for i in 0..code.len() {
+        if let Instruction::Jmp(target) | Instruction::Jz(target) = code[i] {
+            let threaded = thread(code, target);
+            if threaded != target {
+                changed = true;
+                match &mut code[i] {
+                    Instruction::Jmp(target) | Instruction::Jz(target) => *target = threaded,
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    // Instructions control can arrive at other than from the one before, which patterns
+    // spanning several instructions mustn't run into.
+    let mut targets = vec![false; code.len() + 1];
+    for instruction in code.iter() {
+        if let Instruction::Jmp(target) | Instruction::Jz(target) = instruction {
+            targets[*target] = true;
+        }
+    }
+    for function in &bytecode.functions {
+        targets[function.entry] = true;
+    }
+
+    let mut removed = vec![false; code.len()];
+    let mut i = 0;
+    while i < code.len() {
+        let next = code.get(i + 1).filter(|_| !targets[i + 1]);
+        let after = code.get(i + 2).filter(|_| next.is_some() && !targets[i + 2]);
+        match (code[i], next, after) {
+            (Instruction::Jmp(target), ..) if target == i + 1 => removed[i] = true,
+            (Instruction::Jz(target), ..) if target == i + 1 => {
+                code[i] = Instruction::Pop;
+                changed = true;
+            }
+            // The value is dropped again straight away.
+            (instruction, Some(Instruction::Pop), _) if pushes(&instruction) => {
+                removed[i] = true;
+                removed[i + 1] = true;
+                i += 1;
+            }
+            (Instruction::Fetch(from), Some(Instruction::Store(to)), _)
+            | (Instruction::LFetch(from), Some(Instruction::LStore(to)), _) if from == *to => {
+                removed[i] = true;
+                removed[i + 1] = true;
+                i += 1;
+            }
+            (instruction, Some(operand), Some(operator)) if pushes_number(&instruction) && identity(operand, operator) => {
+                removed[i + 1] = true;
+                removed[i + 2] = true;
+                i += 2;
+            }
+            // Nothing jumps to what follows until the next target.
+            (Instruction::Jmp(_) | Instruction::Ret | Instruction::Halt, ..) => {
+                while i + 1 < code.len() && !targets[i + 1] {
+                    i += 1;
+                    removed[i] = true;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if !removed.contains(&true) {
+        return changed;
+    }
+
+    // Where each instruction ends up, or the next one that's kept if it's removed.
+    let mut moved = vec![0; code.len() + 1];
+    let mut kept = 0;
+    for (i, &removed) in removed.iter().enumerate() {
+        moved[i] = kept;
+        kept += usize::from(!removed);
+    }
+    moved[code.len()] = kept;
+    let mut index = 0;
+    code.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+    if !bytecode.spans.is_empty() {
+        let mut index = 0;
+        bytecode.spans.retain(|_| {
+            index += 1;
+            !removed[index - 1]
+        });
+    }
+    for instruction in code.iter_mut() {
+        if let Instruction::Jmp(target) | Instruction::Jz(target) = instruction {
+            *target = moved[*target];
+        }
+    }
+    for function in &mut bytecode.functions {
+        function.entry = moved[function.entry];
+    }
+    true
+}
+
+// Rewrites short runs of instructions into fewer that do the same: jumps to jumps go
+// straight to the end of the chain, jumps to the next instruction go, values pushed only to
+// be popped aren't pushed, `x = x` goes, adding 0 or multiplying by 1 goes, and so does
+// anything after a jump, `ret` or `halt` that nothing jumps to. Returns how many
+// instructions were removed.
+pub fn optimize_bytecode(bytecode: &mut Bytecode) -> usize {
+    let before = bytecode.code.len();
+    while pass(bytecode) {}
+    before - bytecode.code.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::compile;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn listing(bytecode: &Bytecode) -> Vec<String> {
+        bytecode.code.iter().map(Instruction::to_string).collect()
+    }
+
+    #[test]
+    fn removes_what_does_nothing() {
+        let source = "x = 1;\nx = x;\nx;\nprint(x * 1 + 0);\n";
+        let mut bytecode = compile(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap()).unwrap();
+        assert_eq!(optimize_bytecode(&mut bytecode), 6);
+        // `x` might be a string, which `* 1` would fail on, but `x * 1` is a number.
+        assert_eq!(listing(&bytecode), ["push  1", "store [0]", "fetch [0]", "push  1", "mul", "prti", "halt"]);
+    }
+
+    #[test]
+    fn threads_jumps_and_drops_what_follows_them() {
+        use Instruction::*;
+        let mut bytecode = Bytecode { code: vec![Jmp(2), Push(5), Jmp(4), Push(6), Push(7), Prti, Jz(7), Halt], ..Bytecode::default() };
+        assert_eq!(optimize_bytecode(&mut bytecode), 4);
+        // Once the first jump goes straight to `push 7` it goes to the next instruction, so
+        // it goes too, and a `jz` to the next instruction only pops what it would test.
+        assert_eq!(listing(&bytecode), ["push  7", "prti", "pop", "halt"]);
+    }
+}
@@ -0,0 +1,587 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+
+use crate::sema::{Type, Types, argument_count, infer_types};
+use crate::stdlib::{self, BUILTINS, Kind};
+
+use super::c::{collect_statement, has_effects, is_boolean, is_string, string_variables};
+
+const INDENT: &str = "    ";
+
+// Names a tiny-lang program may use that would clash with Rust or with the generated code.
+const RESERVED: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield", "main", "g", "putc", "Globals",
+];
+
+fn variable(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        // A module's `math.square` is `math__square`.
+        name.replace('.', "__")
+    }
+}
+
+// Whether an expression is made only of literals, so Rust can't infer that it's an `i64`.
+fn is_constant(expr: &Expression) -> bool {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Boolean(..) => true,
+        Expression::Unary {operand, ..} => is_constant(operand),
+        Expression::Binary {left, right, ..} => is_constant(left) && is_constant(right),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            is_constant(condition) && is_constant(then_value) && is_constant(else_value)
+        }
+        _ => false,
+    }
+}
+
+// Writes a code point the way the interpreter's `putc` does.
+const PUTC: &str = "\
+fn putc(code: i64) {
+    print!(\"{}\", char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+}
+";
+
+// Dividing by zero is an error and `i64::MIN / -1` wraps, as in the interpreter.
+const DIVIDE: &str = "\
+fn tl_div(l: i64, r: i64) -> i64 {
+    if r == 0 {
+        tl_fail(\"cannot divide by zero\".to_string());
+    }
+    l.wrapping_div(r)
+}
+
+fn tl_mod(l: i64, r: i64) -> i64 {
+    if r == 0 {
+        tl_fail(\"cannot take the remainder of a division by zero\".to_string());
+    }
+    l.wrapping_rem(r)
+}
+";
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the Rust backend", what)
+}
+
+struct Emitter {
+    out: String,
+    depth: usize,
+    // Rust's bools aren't `i64`s, so what prints as `true` or `false` is what the checker
+    // says is a bool.
+    types: Types,
+    strings: HashSet<String>,
+    globals: BTreeSet<String>,
+    functions: Vec<Symbol>,
+    // What globals and functions are reached through: `self` in functions and `g` at the
+    // top level, or nothing if there are no globals.
+    this: Option<&'static str>,
+    // The locals of the function being written, or `None` at the top level.
+    locals: Option<Vec<String>>,
+    // Whether integer literals need an `i64` suffix.
+    suffix: bool,
+    // Whether `putc` is used.
+    putc: bool,
+    // Whether `/` or `%` is, which go through `tl_div` and `tl_mod`.
+    divides: bool,
+    // The builtins used, as indexes into `BUILTINS`, whose definitions go at the top.
+    builtins: BTreeSet<usize>
+}
+
+impl Emitter {
+    fn name(&self, name: &str) -> String {
+        let global = match &self.locals {
+            Some(locals) => !locals.iter().any(|local| local == name),
+            None => self.globals.contains(name),
+        };
+        match self.this {
+            Some(this) if global => format!("{}.{}", this, variable(name)),
+            _ => variable(name),
+        }
+    }
+
+    fn integer(&self, n: i64) -> String {
+        if self.suffix { format!("{}i64", n) } else { n.to_string() }
+    }
+
+    // An expression as an `i64`, or a `String` if it's a string.
+    fn value(&mut self, expr: &Expression) -> Result<String, String> {
+        Ok(match expr {
+            Expression::Number(n, _) => self.integer(*n),
+            Expression::Char(c, _) => self.integer(*c as i64),
+            Expression::Boolean(b, _) => self.integer(*b as i64),
+            Expression::String(s, _) => format!("String::from({:?})", s),
+            Expression::Float(..) => return Err(unsupported("Floating point values")),
+            Expression::Array {..} | Expression::Index {..} => return Err(unsupported("Arrays")),
+            Expression::Symbol(name, _) if self.strings.contains(name.as_str()) => format!("{}.clone()", self.name(name.as_str())),
+            Expression::Symbol(name, _) => self.name(name.as_str()),
+            _ if is_boolean(expr) => format!("({}) as i64", self.condition(expr)?),
+            Expression::Unary {operator: TokenKind::OpAdd, operand, ..} => self.value(operand)?,
+            // `!` on an integer is bitwise, like tiny-lang's `~`.
+            Expression::Unary {operator: TokenKind::OpBitNot, operand, ..} => format!("!{}", self.operand(operand)?),
+            // Integer arithmetic wraps, as in the interpreter.
+            Expression::Unary {operator: TokenKind::OpSubtract, operand, ..} => format!("i64::wrapping_neg({})", self.value(operand)?),
+            Expression::Unary {operator, operand, ..} => format!("{}{}", operator, self.operand(operand)?),
+            Expression::Binary {left, operator: TokenKind::OpAdd, right, ..} if is_string(expr, &self.strings) => {
+                format!("format!(\"{{}}{{}}\", {}, {})", self.value(left)?, self.value(right)?)
+            }
+            // Shift counts wrap modulo 64, as in the interpreter.
+            Expression::Binary {left, operator: operator @ (TokenKind::OpShiftLeft | TokenKind::OpShiftRight), right, ..} => {
+                let method = if *operator == TokenKind::OpShiftLeft { "wrapping_shl" } else { "wrapping_shr" };
+                format!("i64::{}({}, {} as u32)", method, self.value(left)?, self.operand(right)?)
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpMultiply), right, ..} => {
+                let method = match operator {
+                    TokenKind::OpAdd => "wrapping_add",
+                    TokenKind::OpSubtract => "wrapping_sub",
+                    _ => "wrapping_mul",
+                };
+                format!("i64::{}({}, {})", method, self.value(left)?, self.value(right)?)
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpDivide | TokenKind::OpMod), right, ..} => {
+                self.divides = true;
+                let function = if *operator == TokenKind::OpDivide { "tl_div" } else { "tl_mod" };
+                format!("{}({}, {})", function, self.value(left)?, self.value(right)?)
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                format!("{} {} {}", self.operand(left)?, operator, self.operand(right)?)
+            }
+            // Assignments are statements in Rust, so one used as a value is a block.
+            Expression::Assignment {assignee, value, ..} => {
+                let assignment = self.assignment(assignee, value)?;
+                format!("{{ {}; {} }}", assignment, self.value(assignee)?)
+            }
+            // User functions take precedence over builtins with the same name.
+            Expression::Call {callee, arguments, ..} if callee.as_str() == "len" && !self.functions.contains(callee) => {
+                match arguments.as_slice() {
+                    [argument] if is_string(argument, &self.strings) => format!("({}.chars().count() as i64)", self.operand(argument)?),
+                    [_] => return Err(unsupported("Arrays")),
+                    _ => return Err(argument_count(*callee, 1, arguments.len())),
+                }
+            }
+            Expression::Call {callee, arguments, ..}
+                if !self.functions.contains(callee) && let Some(builtin) = stdlib::index(callee.as_str()) =>
+            {
+                self.builtin(builtin, arguments)?
+            }
+            Expression::Call {callee, arguments, ..} => {
+                if arguments.iter().any(|argument| is_string(argument, &self.strings)) {
+                    return Err(unsupported("Strings passed to functions"));
+                }
+                let arguments = arguments.iter().map(|argument| self.value(argument)).collect::<Result<Vec<_>, _>>()?;
+                match self.this {
+                    Some(this) => format!("{}.{}({})", this, variable(callee.as_str()), arguments.join(", ")),
+                    None => format!("{}({})", variable(callee.as_str()), arguments.join(", ")),
+                }
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let condition = self.condition(condition)?;
+                format!("if {} {{ {} }} else {{ {} }}", condition, self.value(then_value)?, self.value(else_value)?)
+            }
+        })
+    }
+
+    // A call to the builtin at `index` in `BUILTINS`. `str`, `ord` and `int` of what's
+    // already of the type they convert to are that value.
+    fn builtin(&mut self, index: usize, arguments: &[Expression]) -> Result<String, String> {
+        let builtin = &BUILTINS[index];
+        if arguments.len() != builtin.arity() {
+            return Err(argument_count(Symbol::from(builtin.name), builtin.arity(), arguments.len()));
+        }
+        let kinds: Vec<Kind> = arguments.iter()
+            .map(|argument| if is_string(argument, &self.strings) { Kind::String } else { Kind::Long })
+            .collect();
+        if matches!(builtin.name, "str" | "ord" | "int") && kinds[0] == builtin.returns {
+            return self.value(&arguments[0]);
+        }
+        // `str` of a bool is "true" or "false", as `print` shows it.
+        if builtin.name == "str" && self.types.of(&arguments[0]) == Type::Bool {
+            return Ok(format!("String::from(if {} {{ \"true\" }} else {{ \"false\" }})", self.condition(&arguments[0])?));
+        }
+        if let Some(i) = (0..kinds.len()).find(|&i| kinds[i] != builtin.takes[i]) {
+            let expected = if builtin.takes[i] == Kind::String { "strings" } else { "numbers" };
+            return Err(unsupported(&format!("Arguments to `{}` other than {}", builtin.name, expected)));
+        }
+        self.builtins.insert(index);
+        let arguments = arguments.iter().map(|argument| self.value(argument)).collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("tl_{}({})", builtin.name, arguments.join(", ")))
+    }
+
+    // Nested operators are always parenthesized rather than relying on Rust precedence.
+    fn operand(&mut self, expr: &Expression) -> Result<String, String> {
+        match expr {
+            Expression::Binary {..} | Expression::Assignment {..} | Expression::Unary {..} | Expression::Conditional {..} => {
+                Ok(format!("({})", self.value(expr)?))
+            }
+            Expression::Number(n, _) if *n < 0 => Ok(format!("({})", self.value(expr)?)),
+            _ => self.value(expr),
+        }
+    }
+
+    // An expression as a `bool`, for conditions: truthy numbers aren't 0 and truthy strings
+    // aren't empty.
+    fn condition(&mut self, expr: &Expression) -> Result<String, String> {
+        Ok(match expr {
+            Expression::Boolean(b, _) => b.to_string(),
+            Expression::Unary {operator: TokenKind::OpNot, operand, ..} => format!("!{}", self.condition_operand(operand)?),
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAnd | TokenKind::OpOr), right, ..} => {
+                format!("{} {} {}", self.condition_operand(left)?, operator, self.condition_operand(right)?)
+            }
+            Expression::Binary {left, operator, right, ..} if is_boolean(expr) => {
+                format!("{} {} {}", self.operand(left)?, operator, self.operand(right)?)
+            }
+            _ if is_string(expr, &self.strings) => format!("!{}.is_empty()", self.operand(expr)?),
+            _ => format!("{} != 0", self.operand(expr)?),
+        })
+    }
+
+    fn condition_operand(&mut self, expr: &Expression) -> Result<String, String> {
+        match expr {
+            Expression::Boolean(..) => self.condition(expr),
+            _ => Ok(format!("({})", self.condition(expr)?)),
+        }
+    }
+
+    fn assignment(&mut self, assignee: &Expression, value: &Expression) -> Result<String, String> {
+        let Expression::Symbol(name, _) = assignee else {
+            return Err(unsupported("Arrays"));
+        };
+        let value = self.value(value)?;
+        Ok(format!("{} = {}", self.name(name.as_str()), value))
+    }
+
+    // How a variable is declared, starting out as 0 or the empty string.
+    fn declaration(&self, name: &str) -> String {
+        if self.strings.contains(name) {
+            format!("let mut {} = String::new();", variable(name))
+        } else {
+            format!("let mut {}: i64 = 0;", variable(name))
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn body(&mut self, stmt: &Statement) -> Result<(), String> {
+        self.depth += 1;
+        match stmt {
+            Statement::Block {body, ..} => body.iter().try_for_each(|s| self.statement(s))?,
+            _ => self.statement(stmt)?,
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn expression_statement(&mut self, expr: &Expression) -> Result<(), String> {
+        let line = match expr {
+            Expression::Assignment {assignee, value, ..} => self.assignment(assignee, value)?,
+            _ => self.value(expr)?,
+        };
+        self.line(&format!("{};", line));
+        Ok(())
+    }
+
+    // A `print` is one `print!`, or a `println!` if it ends with a newline. The interpreter
+    // prints each argument as it gets to it, so one that could print or fail starts another.
+    fn print(&mut self, arguments: &[Expression]) -> Result<(), String> {
+        let mut format = String::new();
+        let mut values = Vec::new();
+        for argument in arguments {
+            match argument {
+                Expression::String(s, _) => format.push_str(&s.replace('{', "{{").replace('}', "}}")),
+                e => {
+                    if has_effects(e) {
+                        self.print_line(std::mem::take(&mut format), std::mem::take(&mut values));
+                    }
+                    format.push_str("{}");
+                    let value = if self.types.of(e) == Type::Bool {
+                        self.condition(e)?
+                    } else {
+                        self.suffix = is_constant(e);
+                        let value = self.value(e);
+                        self.suffix = false;
+                        value?
+                    };
+                    values.push(value);
+                }
+            }
+        }
+        self.print_line(format, values);
+        Ok(())
+    }
+
+    fn print_line(&mut self, format: String, values: Vec<String>) {
+        let (mac, format) = match format.strip_suffix('\n') {
+            Some(line) => ("println", line.to_string()),
+            None => ("print", format),
+        };
+        let mut arguments = Vec::new();
+        if !format.is_empty() {
+            arguments.push(format!("{:?}", format));
+        }
+        arguments.extend(values);
+        if mac == "print" && arguments.is_empty() {
+            return;
+        }
+        self.line(&format!("{}!({});", mac, arguments.join(", ")));
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Attributed {statement, ..} => self.statement(statement)?,
+            Statement::Block {body, ..} if body.is_empty() => {}
+            Statement::Block {..} => {
+                self.line("{");
+                self.body(stmt)?;
+                self.line("}");
+            }
+            Statement::Expression {expression, ..} => self.expression_statement(expression)?,
+            Statement::Print {arguments, ..} => self.print(arguments)?,
+            Statement::Putc {expression, ..} => {
+                self.putc = true;
+                let value = self.value(expression)?;
+                self.line(&format!("putc({});", value));
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let condition = self.condition(condition)?;
+                self.line(&format!("if {} {{", condition));
+                self.body(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.body(else_branch)?;
+                }
+                self.line("}");
+            }
+            Statement::While {condition, body, ..} => {
+                let condition = self.condition(condition)?;
+                self.line(&format!("while {} {{", condition));
+                self.body(body)?;
+                self.line("}");
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                self.line("loop {");
+                self.body(body)?;
+                self.depth += 1;
+                let condition = self.condition_operand(condition)?;
+                self.line(&format!("if !{} {{", condition));
+                self.line(&format!("{}break;", INDENT));
+                self.line("}");
+                self.depth -= 1;
+                self.line("}");
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                if let Some(initializer) = initializer {
+                    self.expression_statement(initializer)?;
+                }
+                match condition {
+                    Some(condition) => {
+                        let condition = self.condition(condition)?;
+                        self.line(&format!("while {} {{", condition));
+                    }
+                    None => self.line("loop {"),
+                }
+                self.body(body)?;
+                if let Some(step) = step {
+                    self.depth += 1;
+                    self.expression_statement(step)?;
+                    self.depth -= 1;
+                }
+                self.line("}");
+            }
+            Statement::Function {name, parameters, body, ..} => {
+                let mut signature: Vec<String> = parameters.iter().map(|p| format!("mut {}: i64", variable(p.as_str()))).collect();
+                if self.this.is_some() {
+                    signature.insert(0, "&self".to_string());
+                }
+                self.line(&format!("fn {}({}) -> i64 {{", variable(name.as_str()), signature.join(", ")));
+                self.depth += 1;
+                let locals = function_locals(parameters, body);
+                for local in &locals[parameters.len()..] {
+                    let declaration = self.declaration(local.as_str());
+                    self.line(&declaration);
+                }
+                if locals.len() > parameters.len() {
+                    self.line("");
+                }
+                let (this, outer) = (self.this, self.locals.take());
+                self.this = this.map(|_| "self");
+                self.locals = Some(locals.iter().map(Symbol::to_string).collect());
+                // A trailing `return` becomes the function's tail expression.
+                match body.split_last() {
+                    Some((Statement::Return {value: Some(value), ..}, rest)) if !is_string(value, &self.strings) => {
+                        rest.iter().try_for_each(|s| self.statement(s))?;
+                        let value = self.value(value)?;
+                        self.line(&value);
+                    }
+                    _ => {
+                        body.iter().try_for_each(|s| self.statement(s))?;
+                        self.line("0");
+                    }
+                }
+                self.this = this;
+                self.locals = outer;
+                self.depth -= 1;
+                self.line("}");
+            }
+            Statement::Return {value: Some(value), ..} if is_string(value, &self.strings) => {
+                return Err(unsupported("Strings returned from functions"));
+            }
+            Statement::Return {value: Some(value), ..} => {
+                let value = self.value(value)?;
+                self.line(&format!("return {};", value));
+            }
+            Statement::Return {value: None, ..} if self.locals.is_none() => self.line("return;"),
+            Statement::Return {value: None, ..} => self.line("return 0;"),
+            // Linking has put the module in its place.
+            Statement::Import {..} => {}
+        }
+        Ok(())
+    }
+}
+
+// A standalone Rust program equivalent to `program`: integers and booleans are `i64`s,
+// strings are `String`s and printing is `print!` and `println!`. Functions are methods of
+// a `Globals` struct that holds the globals they read, if there are any. Floats, arrays
+// and passing strings to functions aren't supported, and are an error.
+pub fn emit_rust(program: &[Statement]) -> Result<String, String> {
+    let functions: Vec<&Statement> = program.iter()
+        .filter(|s| matches!(s, Statement::Function {..}))
+        .collect();
+    let mut globals = BTreeSet::new();
+    for function in &functions {
+        if let Statement::Function {parameters, body, ..} = function {
+            // Anything a function uses that isn't one of its locals is a global.
+            let locals = function_locals(parameters, body);
+            let mut names = BTreeSet::new();
+            body.iter().for_each(|s| collect_statement(s, &mut names));
+            globals.extend(names.into_iter().filter(|n| !locals.iter().any(|l| l.as_str() == n)));
+        }
+    }
+    let mut top_level = BTreeSet::new();
+    program.iter().for_each(|s| collect_statement(s, &mut top_level));
+
+    let names = functions.iter()
+        .filter_map(|s| match s {
+            Statement::Function {name, ..} => Some(*name),
+            _ => None,
+        })
+        .collect();
+    let this = (!globals.is_empty()).then_some("g");
+    let mut emitter = Emitter {
+        out: String::new(), depth: 0, types: infer_types(program), strings: string_variables(program), globals,
+        functions: names, this, locals: None, suffix: false, putc: false, divides: false, builtins: BTreeSet::new(),
+    };
+    emitter.line("// Generated by tiny-lang");
+    emitter.line("#![allow(unused, non_snake_case)]");
+    emitter.line("");
+    if this.is_some() {
+        emitter.line("#[derive(Default)]");
+        emitter.line("struct Globals {");
+        for name in emitter.globals.clone() {
+            let ty = if emitter.strings.contains(&name) { "String" } else { "i64" };
+            emitter.line(&format!("{}{}: {},", INDENT, variable(&name), ty));
+        }
+        emitter.line("}");
+        emitter.line("");
+        emitter.line("impl Globals {");
+        emitter.depth += 1;
+    }
+    for (i, function) in functions.iter().enumerate() {
+        if i > 0 {
+            emitter.line("");
+        }
+        emitter.statement(function)?;
+    }
+    if this.is_some() {
+        emitter.depth -= 1;
+        emitter.line("}");
+    }
+    if !functions.is_empty() {
+        emitter.line("");
+    }
+
+    let mut main = Emitter { out: String::new(), depth: 1, ..emitter };
+    let statements: Vec<&Statement> = program.iter().filter(|s| !matches!(s, Statement::Function {..})).collect();
+    let locals: Vec<&String> = top_level.iter().filter(|name| !main.globals.contains(*name)).collect();
+    if main.this.is_some() {
+        main.line("let mut g = Globals::default();");
+    }
+    for name in &locals {
+        let declaration = main.declaration(name);
+        main.line(&declaration);
+    }
+    if main.this.is_some() || !locals.is_empty() {
+        main.line("");
+    }
+    statements.iter().try_for_each(|s| main.statement(s))?;
+    let body = std::mem::take(&mut main.out);
+
+    let mut out = emitter.out;
+    if main.putc {
+        out.push_str(PUTC);
+        out.push('\n');
+    }
+    let mut items: Vec<&str> = Vec::new();
+    if main.divides {
+        items.extend([stdlib::RUST_FAIL, DIVIDE]);
+    }
+    for &builtin in &main.builtins {
+        for &item in BUILTINS[builtin].rust {
+            if !items.contains(&item) {
+                items.push(item);
+            }
+        }
+    }
+    for item in items {
+        out.push_str(item);
+        out.push('\n');
+    }
+    out.push_str("fn main() {\n");
+    // What the builtins need done first; the statements have been written by now.
+    for &builtin in &main.builtins {
+        if !BUILTINS[builtin].rust_setup.is_empty() {
+            out.push_str(&format!("{}{}\n", INDENT, BUILTINS[builtin].rust_setup));
+        }
+    }
+    out.push_str(&body);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str) -> Result<String, String> {
+        emit_rust(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap())
+    }
+
+    #[test]
+    fn builtins_are_defined_and_what_rust_isnt_given_is_an_error() {
+        let rust = emit("seed(5);\nprint(rand(10), str(42) + \"!\");\n").unwrap();
+        assert!(rust.contains("fn tl_seed(") && rust.contains("fn tl_rand(") && !rust.contains("fn tl_abs("));
+        assert_eq!(rust.matches("static TL_RANDOM").count(), 1);
+        assert!(emit("print(str(1 < 2));").unwrap().contains("String::from(if 1 < 2 { \"true\" } else { \"false\" })"));
+        assert!(rust.contains("format!(\"{}{}\", tl_str(42), String::from(\"!\"))"));
+        let arithmetic = emit("x = 1;\nprint(-x * 2 + x / 3 % x);\n").unwrap();
+        assert!(arithmetic.contains("i64::wrapping_add(i64::wrapping_mul(i64::wrapping_neg(x), 2), tl_mod(tl_div(x, 3), x))"), "{}", arithmetic);
+        assert!(arithmetic.contains("fn tl_fail(") && arithmetic.contains("fn tl_div("));
+        assert_eq!(emit("print(1.5);").unwrap_err(), "Floating point values aren't supported by the Rust backend");
+        assert_eq!(emit("a = [1];").unwrap_err(), "Arrays aren't supported by the Rust backend");
+        assert_eq!(emit("fn f(s) { return 1; }\nf(\"x\");\n").unwrap_err(), "Strings passed to functions aren't supported by the Rust backend");
+        assert_eq!(emit("fn f() { return \"x\"; }").unwrap_err(), "Strings returned from functions aren't supported by the Rust backend");
+        assert_eq!(emit("abs(\"x\");").unwrap_err(), "Arguments to `abs` other than numbers aren't supported by the Rust backend");
+    }
+}
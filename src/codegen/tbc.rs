@@ -0,0 +1,395 @@
+// The `.tbc` file format, which stores compiled bytecode so it can be run again without
+// the source. All numbers are little-endian:
+//
+//     magic      b"TBC\0"
+//     version    u16
+//     globals    u32 count, then each name as a string
+//     strings    u32 count, then each string: the constant pool `pushs` indexes
+//     functions  u32 count, then each one's name, arity, locals and entry as u32s
+//     code       u32 count, then each instruction: an opcode byte and its operand, if any
+//
+// A string is a u32 length followed by that many bytes of UTF-8. Operands are u32 indices,
+// or `array`'s count of elements, except that `push` has an i64, `pushf` the f64's bits and `pushb` a byte.
+
+use crate::stdlib::BUILTINS;
+
+use super::bytecode::{Bytecode, FunctionInfo, Instruction};
+
+pub const MAGIC: &[u8; 4] = b"TBC\0";
+// Bumped whenever the format or the meaning of an opcode changes.
+pub const VERSION: u16 = 1;
+// The most locals a function can have, so that a corrupt count can't make every call to it
+// allocate gigabytes.
+const MAX_LOCALS: usize = 1 << 16;
+
+// Opcodes, which are fixed once released.
+const FETCH: u8 = 0x01;
+const STORE: u8 = 0x02;
+const LFETCH: u8 = 0x03;
+const LSTORE: u8 = 0x04;
+const PUSH: u8 = 0x05;
+const PUSH_BOOL: u8 = 0x06;
+const PUSH_FLOAT: u8 = 0x07;
+const PUSH_STR: u8 = 0x08;
+const JMP: u8 = 0x09;
+const JZ: u8 = 0x0a;
+const CALL: u8 = 0x0b;
+const RET: u8 = 0x0c;
+const HALT: u8 = 0x0d;
+const POP: u8 = 0x0e;
+const BUILTIN: u8 = 0x0f;
+const ADD: u8 = 0x10;
+const SUB: u8 = 0x11;
+const MUL: u8 = 0x12;
+const DIV: u8 = 0x13;
+const MOD: u8 = 0x14;
+const LT: u8 = 0x15;
+const GT: u8 = 0x16;
+const LE: u8 = 0x17;
+const GE: u8 = 0x18;
+const EQ: u8 = 0x19;
+const NE: u8 = 0x1a;
+const NEG: u8 = 0x1b;
+const NOT: u8 = 0x1c;
+const BIT_AND: u8 = 0x1d;
+const BIT_OR: u8 = 0x1e;
+const BIT_XOR: u8 = 0x1f;
+const BIT_NOT: u8 = 0x20;
+const SHL: u8 = 0x21;
+const SHR: u8 = 0x22;
+const ARRAY: u8 = 0x23;
+const INDEX: u8 = 0x24;
+const SET_INDEX: u8 = 0x25;
+const LEN: u8 = 0x26;
+const PRTC: u8 = 0x30;
+const PRTI: u8 = 0x31;
+const PRTS: u8 = 0x32;
+
+// Instructions without an operand, by opcode.
+const SIMPLE: &[(u8, Instruction)] = &[
+    (RET, Instruction::Ret), (HALT, Instruction::Halt), (POP, Instruction::Pop), (ADD, Instruction::Add),
+    (SUB, Instruction::Sub), (MUL, Instruction::Mul), (DIV, Instruction::Div), (MOD, Instruction::Mod),
+    (LT, Instruction::Lt), (GT, Instruction::Gt), (LE, Instruction::Le), (GE, Instruction::Ge),
+    (EQ, Instruction::Eq), (NE, Instruction::Ne), (NEG, Instruction::Neg), (NOT, Instruction::Not),
+    (BIT_AND, Instruction::BitAnd), (BIT_OR, Instruction::BitOr), (BIT_XOR, Instruction::BitXor),
+    (BIT_NOT, Instruction::BitNot), (SHL, Instruction::Shl), (SHR, Instruction::Shr),
+    (PRTC, Instruction::Prtc), (PRTI, Instruction::Prti), (PRTS, Instruction::Prts),
+    (INDEX, Instruction::Index), (SET_INDEX, Instruction::SetIndex), (LEN, Instruction::Len),
+];
+
+fn put_u32(out: &mut Vec<u8>, n: usize) {
+    out.extend_from_slice(&u32::try_from(n).expect("bytecode too large for a .tbc file").to_le_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_instruction(out: &mut Vec<u8>, instruction: &Instruction) {
+    let (opcode, index) = match *instruction {
+        Instruction::Fetch(slot) => (FETCH, slot),
+        Instruction::Store(slot) => (STORE, slot),
+        Instruction::LFetch(slot) => (LFETCH, slot),
+        Instruction::LStore(slot) => (LSTORE, slot),
+        Instruction::PushStr(index) => (PUSH_STR, index),
+        Instruction::Jmp(target) => (JMP, target),
+        Instruction::Jz(target) => (JZ, target),
+        Instruction::Call(function) => (CALL, function),
+        Instruction::Builtin(builtin) => (BUILTIN, builtin),
+        Instruction::Array(n) => (ARRAY, n),
+        Instruction::Push(n) => {
+            out.push(PUSH);
+            out.extend_from_slice(&n.to_le_bytes());
+            return;
+        }
+        Instruction::PushFloat(x) => {
+            out.push(PUSH_FLOAT);
+            out.extend_from_slice(&x.to_bits().to_le_bytes());
+            return;
+        }
+        Instruction::PushBool(b) => {
+            out.extend_from_slice(&[PUSH_BOOL, b as u8]);
+            return;
+        }
+        simple => {
+            out.push(SIMPLE.iter().find(|(_, i)| *i == simple).unwrap().0);
+            return;
+        }
+    };
+    out.push(opcode);
+    put_u32(out, index);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.bytes.get(self.pos..self.pos + n).ok_or("the file is truncated")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "a string isn't valid UTF-8".to_string())
+    }
+
+    // A count of items that are each at least `size` bytes, checked against what's left so
+    // a corrupt count can't make us allocate too much.
+    fn count(&mut self, size: usize) -> Result<usize, String> {
+        let count = self.u32()?;
+        if count.saturating_mul(size) > self.bytes.len() - self.pos {
+            return Err("the file is truncated".to_string());
+        }
+        Ok(count)
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, String> {
+        let opcode = self.byte()?;
+        let instruction = match opcode {
+            FETCH => Instruction::Fetch(self.u32()?),
+            STORE => Instruction::Store(self.u32()?),
+            LFETCH => Instruction::LFetch(self.u32()?),
+            LSTORE => Instruction::LStore(self.u32()?),
+            PUSH_STR => Instruction::PushStr(self.u32()?),
+            JMP => Instruction::Jmp(self.u32()?),
+            JZ => Instruction::Jz(self.u32()?),
+            CALL => Instruction::Call(self.u32()?),
+            BUILTIN => Instruction::Builtin(self.u32()?),
+            ARRAY => Instruction::Array(self.u32()?),
+            PUSH => Instruction::Push(self.u64()? as i64),
+            PUSH_FLOAT => Instruction::PushFloat(f64::from_bits(self.u64()?)),
+            PUSH_BOOL => Instruction::PushBool(self.byte()? != 0),
+            _ => match SIMPLE.iter().find(|(code, _)| *code == opcode) {
+                Some((_, instruction)) => *instruction,
+                None => return Err(format!("unknown opcode 0x{:02x}", opcode)),
+            },
+        };
+        Ok(instruction)
+    }
+}
+
+// How many values `instruction` pops and how many it pushes, in `bytecode`, whose indices
+// have been checked.
+fn stack_effect(bytecode: &Bytecode, instruction: Instruction) -> (usize, usize) {
+    match instruction {
+        Instruction::Fetch(_) | Instruction::LFetch(_) | Instruction::Push(_) | Instruction::PushBool(_)
+        | Instruction::PushFloat(_) | Instruction::PushStr(_) => (0, 1),
+        Instruction::Store(_) | Instruction::LStore(_) | Instruction::Pop | Instruction::Jz(_)
+        | Instruction::Prtc | Instruction::Prti | Instruction::Prts => (1, 0),
+        Instruction::Neg | Instruction::Not | Instruction::BitNot | Instruction::Len => (1, 1),
+        Instruction::Call(function) => (bytecode.functions[function].arity, 1),
+        Instruction::Builtin(builtin) => (BUILTINS[builtin].arity(), 1),
+        Instruction::Array(n) => (n, 1),
+        Instruction::SetIndex => (3, 1),
+        Instruction::Jmp(_) | Instruction::Ret | Instruction::Halt => (0, 0),
+        _ => (2, 1),
+    }
+}
+
+// Follows every path through the code from the top level's start and each function's entry,
+// checking that no instruction pops more than is on the stack, that locals are only used in
+// a function and within its locals, that a function returns one value, and that each
+// instruction is always reached with the stack as deep. `prts` must follow the `push` of a
+// string table index, as the compiler emits it.
+fn check_paths(bytecode: &Bytecode) -> Result<(), String> {
+    let code = &bytecode.code;
+    let error = |pc: usize, reason: &str| Err(format!("instruction {} (`{}`) {}", pc, code[pc], reason));
+    let mut targets = vec![false; code.len()];
+    for instruction in code {
+        if let Instruction::Jmp(target) | Instruction::Jz(target) = *instruction {
+            targets[target] = true;
+        }
+    }
+    bytecode.functions.iter().for_each(|function| targets[function.entry] = true);
+    // The function each instruction is run in, if any, and how deep the stack is before it.
+    let mut states: Vec<Option<(Option<usize>, usize)>> = vec![None; code.len()];
+    let mut pending: Vec<(usize, Option<usize>, usize)> = vec![(0, None, 0)];
+    pending.extend((0..bytecode.functions.len()).map(|i| (bytecode.functions[i].entry, Some(i), 0)));
+    while let Some((pc, function, depth)) = pending.pop() {
+        if pc == code.len() {
+            return Err("the code runs off its end".to_string());
+        }
+        match states[pc] {
+            Some(state) if state == (function, depth) => continue,
+            Some(_) => return error(pc, "is reached with different stacks"),
+            None => states[pc] = Some((function, depth)),
+        }
+        let instruction = code[pc];
+        let (pops, pushes) = stack_effect(bytecode, instruction);
+        if pops > depth {
+            return error(pc, "pops more than is on the stack");
+        }
+        let depth = depth - pops + pushes;
+        match instruction {
+            Instruction::LFetch(slot) | Instruction::LStore(slot) => match function {
+                Some(function) if slot < bytecode.functions[function].locals => {}
+                Some(_) => return error(pc, "uses a local the function doesn't have"),
+                None => return error(pc, "uses a local outside of a function"),
+            },
+            Instruction::Prts => match pc.checked_sub(1).map(|previous| code[previous]) {
+                Some(Instruction::Push(index)) if !targets[pc] && usize::try_from(index).is_ok_and(|index| index < bytecode.strings.len()) => {}
+                _ => return error(pc, "doesn't print an entry of the string table"),
+            },
+            Instruction::Ret if function.is_none() => return error(pc, "returns outside of a function"),
+            Instruction::Ret if depth != 1 => return error(pc, "doesn't return exactly one value"),
+            _ => {}
+        }
+        match instruction {
+            Instruction::Jmp(target) => pending.push((target, function, depth)),
+            Instruction::Jz(target) => pending.extend([(target, function, depth), (pc + 1, function, depth)]),
+            Instruction::Ret | Instruction::Halt => {}
+            _ => pending.push((pc + 1, function, depth)),
+        }
+    }
+    Ok(())
+}
+
+impl Bytecode {
+    // The program as a `.tbc` file.
+    pub fn to_tbc(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        put_u32(&mut out, self.globals.len());
+        self.globals.iter().for_each(|name| put_string(&mut out, name));
+        put_u32(&mut out, self.strings.len());
+        self.strings.iter().for_each(|s| put_string(&mut out, s));
+        put_u32(&mut out, self.functions.len());
+        for function in &self.functions {
+            put_string(&mut out, &function.name);
+            put_u32(&mut out, function.arity);
+            put_u32(&mut out, function.locals);
+            put_u32(&mut out, function.entry);
+        }
+        put_u32(&mut out, self.code.len());
+        self.code.iter().for_each(|instruction| put_instruction(&mut out, instruction));
+        out
+    }
+
+    // Reads a `.tbc` file, checking that every index in it is in range and that the code
+    // keeps the stack balanced, so that the VM can trust it as it trusts the compiler's
+    // output.
+    pub fn from_tbc(bytes: &[u8]) -> Result<Bytecode, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err("not a tiny-lang bytecode file".to_string());
+        }
+        let mut reader = Reader { bytes, pos: MAGIC.len() };
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(format!("the file is version {} of the format, but this build reads version {}", version, VERSION));
+        }
+        let mut bytecode = Bytecode::default();
+        for _ in 0..reader.count(4)? {
+            bytecode.globals.push(reader.string()?);
+        }
+        for _ in 0..reader.count(4)? {
+            bytecode.strings.push(reader.string()?);
+        }
+        for _ in 0..reader.count(16)? {
+            let name = reader.string()?;
+            let (arity, locals, entry) = (reader.u32()?, reader.u32()?, reader.u32()?);
+            bytecode.functions.push(FunctionInfo { name, arity, locals, entry });
+        }
+        for _ in 0..reader.count(1)? {
+            bytecode.code.push(reader.instruction()?);
+        }
+        if reader.pos != bytes.len() {
+            return Err("there are bytes after the code".to_string());
+        }
+
+        let len = bytecode.code.len();
+        if !matches!(bytecode.code.last(), Some(Instruction::Halt | Instruction::Ret | Instruction::Jmp(_))) {
+            return Err("the code runs off its end".to_string());
+        }
+        for function in &bytecode.functions {
+            if function.entry >= len || function.arity > function.locals || function.locals > MAX_LOCALS {
+                return Err(format!("function `{}` is malformed", function.name));
+            }
+        }
+        for (i, instruction) in bytecode.code.iter().enumerate() {
+            let valid = match *instruction {
+                Instruction::Fetch(slot) | Instruction::Store(slot) => slot < bytecode.globals.len(),
+                Instruction::PushStr(index) => index < bytecode.strings.len(),
+                Instruction::Jmp(target) | Instruction::Jz(target) => target < len,
+                Instruction::Call(function) => function < bytecode.functions.len(),
+                Instruction::Builtin(builtin) => builtin < BUILTINS.len(),
+                _ => true,
+            };
+            if !valid {
+                return Err(format!("instruction {} (`{}`) refers to something that doesn't exist", i, instruction));
+            }
+        }
+        check_paths(&bytecode)?;
+        Ok(bytecode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::compile;
+    use crate::vm::{RunConfig, Vm};
+    use crate::{Parser, parse_program, tokenize};
+
+    const PROGRAM: &str = "fn fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\n\
+                           a = [1, 2];\nfor (i = 0; i < 3 && a[0] < 9; i = i + 1) { a[0] = a[0] * 2; }\n\
+                           print(\"fib \", fib(10), \" \", a, \" \", abs(-1), \"\\n\");\n";
+
+    fn tbc() -> Vec<u8> {
+        compile(&parse_program(&mut Parser::new(tokenize(PROGRAM).0)).unwrap()).unwrap().to_tbc()
+    }
+
+    #[test]
+    fn the_compilers_output_loads() {
+        let bytecode = Bytecode::from_tbc(&tbc()).unwrap();
+        assert_eq!(bytecode.to_tbc(), tbc());
+        let code = |code: Vec<Instruction>| {
+            let bytecode = Bytecode { strings: vec!["s".to_string()], code, ..Bytecode::default() };
+            Bytecode::from_tbc(&bytecode.to_tbc()).unwrap_err()
+        };
+        assert_eq!(code(vec![Instruction::Push(1), Instruction::Add, Instruction::Halt]), "instruction 1 (`add`) pops more than is on the stack");
+        assert_eq!(code(vec![Instruction::LFetch(0), Instruction::Halt]), "instruction 0 (`lfetch [0]`) uses a local outside of a function");
+        assert_eq!(code(vec![Instruction::Push(1), Instruction::Prts, Instruction::Halt]), "instruction 1 (`prts`) doesn't print an entry of the string table");
+        let unbalanced = vec![Instruction::Push(0), Instruction::Jz(3), Instruction::Push(1), Instruction::Halt];
+        assert_eq!(code(unbalanced), "instruction 3 (`halt`) is reached with different stacks");
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected_or_run_without_panicking() {
+        let tbc = tbc();
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..300 {
+            let mut bytes = tbc.clone();
+            for _ in 0..1 + random() % 3 {
+                let at = random() % bytes.len();
+                bytes[at] = random() as u8;
+            }
+            if let Ok(bytecode) = Bytecode::from_tbc(&bytes) {
+                let _ = Vm::new(&bytecode).run_with(&RunConfig::default().fuel(10_000));
+            }
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use crate::intern::Symbol;
+use crate::ir::{Constant, Function, Module, Op, Terminator, ValueId};
+use crate::lexer::TokenKind;
+
+const I32: u8 = 0x7F;
+const I64: u8 = 0x7E;
+// The block type of a block that takes and leaves nothing.
+const EMPTY: u8 = 0x40;
+
+const UNREACHABLE: u8 = 0x00;
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0B;
+const BR: u8 = 0x0C;
+const BR_TABLE: u8 = 0x0E;
+const RETURN: u8 = 0x0F;
+const CALL: u8 = 0x10;
+const SELECT: u8 = 0x1B;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const GLOBAL_GET: u8 = 0x23;
+const GLOBAL_SET: u8 = 0x24;
+const I32_CONST: u8 = 0x41;
+const I64_CONST: u8 = 0x42;
+const I64_EQZ: u8 = 0x50;
+const I64_EQ: u8 = 0x51;
+const I64_NE: u8 = 0x52;
+const I64_ADD: u8 = 0x7C;
+const I64_SUB: u8 = 0x7D;
+const I64_MUL: u8 = 0x7E;
+const I64_DIV_S: u8 = 0x7F;
+const I64_REM_S: u8 = 0x81;
+const I64_AND: u8 = 0x83;
+const I64_OR: u8 = 0x84;
+const I64_XOR: u8 = 0x85;
+const I64_SHL: u8 = 0x86;
+const I64_SHR_S: u8 = 0x87;
+const I32_WRAP_I64: u8 = 0xA7;
+const I64_EXTEND_I32_U: u8 = 0xAD;
+
+// What the host provides, from the `env` module, with the types of their parameters.
+// Strings are an offset and a length in the exported memory, in UTF-8.
+const IMPORTS: &[(&str, &[u8])] = &[
+    ("print_int", &[I64]),
+    ("print_bool", &[I32]),
+    ("print_string", &[I32, I32]),
+    ("putc", &[I32]),
+];
+const PRINT_INT: u32 = 0;
+const PRINT_BOOL: u32 = 1;
+const PRINT_STRING: u32 = 2;
+const PUTC: u32 = 3;
+
+fn unsigned(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn signed(out: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        // Done once the rest is all sign bits, and the sign bit of this byte agrees.
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    unsigned(out, name.len() as u64);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn section(out: &mut Vec<u8>, id: u8, count: usize, contents: &[u8]) {
+    let mut body = Vec::new();
+    unsigned(&mut body, count as u64);
+    body.extend_from_slice(contents);
+    out.push(id);
+    unsigned(out, body.len() as u64);
+    out.extend(body);
+}
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the WebAssembly backend", what)
+}
+
+fn comparison(operator: &TokenKind) -> Option<u8> {
+    match operator {
+        TokenKind::OpEqual => Some(I64_EQ),
+        TokenKind::OpNotEqual => Some(I64_NE),
+        TokenKind::OpLess => Some(0x53),
+        TokenKind::OpGreater => Some(0x55),
+        TokenKind::OpLessEqual => Some(0x57),
+        TokenKind::OpGreaterEqual => Some(0x59),
+        _ => None,
+    }
+}
+
+struct Lowering<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    // Which values are bools, from `Module::bools`.
+    bools: &'a [bool],
+    globals: &'a [Symbol],
+    code: Vec<u8>,
+    // The bytes of the strings the module prints, which become its memory.
+    data: &'a mut Vec<u8>
+}
+
+impl Lowering<'_> {
+    // Parameters are the first locals, then every other value has one, then the block to
+    // go to next.
+    fn local(&self, value: ValueId) -> u32 {
+        match self.function.op(value) {
+            Op::Param(index) => *index as u32,
+            _ => (self.function.parameters + value.0) as u32,
+        }
+    }
+
+    fn next_block(&self) -> u32 {
+        (self.function.parameters + self.function.values.len()) as u32
+    }
+
+    fn op(&mut self, opcode: u8, immediate: Option<u32>) {
+        self.code.push(opcode);
+        if let Some(immediate) = immediate {
+            unsigned(&mut self.code, immediate as u64);
+        }
+    }
+
+    fn i64_const(&mut self, n: i64) {
+        self.code.push(I64_CONST);
+        signed(&mut self.code, n);
+    }
+
+    fn i32_const(&mut self, n: i32) {
+        self.code.push(I32_CONST);
+        signed(&mut self.code, n as i64);
+    }
+
+    // Every value is an `i64`: bools are 0 or 1. Constants are written where they're used.
+    fn push(&mut self, value: ValueId) -> Result<(), String> {
+        match self.function.op(value) {
+            Op::Const(Constant::Integer(n)) => self.i64_const(*n),
+            Op::Const(Constant::Bool(b)) => self.i64_const(*b as i64),
+            Op::Const(Constant::Float(_)) => return Err(unsupported("Floating point values")),
+            Op::Const(Constant::String(_)) => return Err(unsupported("Strings other than print arguments")),
+            _ => self.op(LOCAL_GET, Some(self.local(value))),
+        }
+        Ok(())
+    }
+
+    fn global(&self, name: &Symbol) -> u32 {
+        self.globals.iter().position(|global| global == name).unwrap() as u32
+    }
+
+    fn instruction(&mut self, value: ValueId) -> Result<(), String> {
+        match self.function.op(value) {
+            Op::Const(_) | Op::Param(_) | Op::Phi(_) => return Ok(()),
+            Op::Unary(operator, operand) => match operator {
+                TokenKind::OpSubtract => {
+                    self.i64_const(0);
+                    self.push(*operand)?;
+                    self.op(I64_SUB, None);
+                }
+                TokenKind::OpAdd => self.push(*operand)?,
+                TokenKind::OpBitNot => {
+                    self.push(*operand)?;
+                    self.i64_const(-1);
+                    self.op(I64_XOR, None);
+                }
+                _ => {
+                    self.push(*operand)?;
+                    self.op(I64_EQZ, None);
+                    self.op(I64_EXTEND_I32_U, None);
+                }
+            },
+            // Dividing the lowest integer by -1 traps, so dividing by -1 is dividing the
+            // negated dividend by 1 instead: the quotient wraps and the remainder is 0, as in
+            // the interpreter. Division by zero still traps.
+            Op::Binary(operator @ (TokenKind::OpDivide | TokenKind::OpMod), left, right) => {
+                let by_minus_one = |lowering: &mut Self| -> Result<(), String> {
+                    lowering.push(*right)?;
+                    lowering.i64_const(-1);
+                    lowering.op(I64_EQ, None);
+                    Ok(())
+                };
+                self.i64_const(0);
+                self.push(*left)?;
+                self.op(I64_SUB, None);
+                self.push(*left)?;
+                by_minus_one(self)?;
+                self.op(SELECT, None);
+                self.i64_const(1);
+                self.push(*right)?;
+                by_minus_one(self)?;
+                self.op(SELECT, None);
+                self.op(if *operator == TokenKind::OpDivide { I64_DIV_S } else { I64_REM_S }, None);
+            }
+            Op::Binary(operator, left, right) => {
+                self.push(*left)?;
+                self.push(*right)?;
+                if let Some(opcode) = comparison(operator) {
+                    self.op(opcode, None);
+                    self.op(I64_EXTEND_I32_U, None);
+                } else {
+                    // Shift counts wrap modulo 64, as in the interpreter.
+                    let opcode = match operator {
+                        TokenKind::OpAdd => I64_ADD,
+                        TokenKind::OpSubtract => I64_SUB,
+                        TokenKind::OpMultiply => I64_MUL,
+                        TokenKind::OpBitAnd => I64_AND,
+                        TokenKind::OpBitOr => I64_OR,
+                        TokenKind::OpBitXor => I64_XOR,
+                        TokenKind::OpShiftLeft => I64_SHL,
+                        TokenKind::OpShiftRight => I64_SHR_S,
+                        operator => return Err(format!("`{}` isn't supported by the WebAssembly backend", operator)),
+                    };
+                    self.op(opcode, None);
+                }
+            }
+            Op::Truthy(operand) => {
+                self.push(*operand)?;
+                self.i64_const(0);
+                self.op(I64_NE, None);
+                self.op(I64_EXTEND_I32_U, None);
+            }
+            Op::Call(callee, arguments) => {
+                let Some(index) = self.module.functions.iter().skip(1).position(|f| f.name == callee.as_str()) else {
+                    return Err(format!("`{}` isn't supported by the WebAssembly backend", callee));
+                };
+                let parameters = self.module.functions[index + 1].parameters;
+                if parameters != arguments.len() {
+                    return Err(format!("`{}` takes {} arguments but {} were given", callee, parameters, arguments.len()));
+                }
+                for &argument in arguments {
+                    self.push(argument)?;
+                }
+                self.op(CALL, Some((IMPORTS.len() + index + 1) as u32));
+            }
+            Op::LoadGlobal(name) => self.op(GLOBAL_GET, Some(self.global(name))),
+            Op::StoreGlobal(name, operand) => {
+                self.push(*operand)?;
+                self.op(GLOBAL_SET, Some(self.global(name)));
+                return Ok(());
+            }
+            Op::Array(_) | Op::Index(..) | Op::StoreIndex(..) => return Err(unsupported("Arrays")),
+            Op::Print(operand) => {
+                let op = self.function.op(*operand);
+                if let Op::Const(Constant::String(s)) = op {
+                    let offset = i32::try_from(self.data.len()).map_err(|_| unsupported("Strings this long"))?;
+                    self.data.extend_from_slice(s.as_bytes());
+                    self.i32_const(offset);
+                    self.i32_const(s.len() as i32);
+                    self.op(CALL, Some(PRINT_STRING));
+                } else if self.bools[operand.0] {
+                    self.push(*operand)?;
+                    self.op(I32_WRAP_I64, None);
+                    self.op(CALL, Some(PRINT_BOOL));
+                } else {
+                    self.push(*operand)?;
+                    self.op(CALL, Some(PRINT_INT));
+                }
+                return Ok(());
+            }
+            Op::Putc(operand) => {
+                self.push(*operand)?;
+                self.op(I32_WRAP_I64, None);
+                self.op(CALL, Some(PUTC));
+                return Ok(());
+            }
+        }
+        self.op(LOCAL_SET, Some(self.local(value)));
+        Ok(())
+    }
+
+    // Gives the phis at the start of `to` their values for control coming from `from`. The
+    // values all go on the stack first, as one phi may be another's operand.
+    fn edge(&mut self, from: usize, to: usize) -> Result<(), String> {
+        let function = self.function;
+        let phis: Vec<(ValueId, ValueId)> = function.blocks[to].instructions.iter()
+            .map_while(|&phi| match function.op(phi) {
+                Op::Phi(incoming) => Some((phi, incoming.iter().find(|&&(block, _)| block == from).unwrap().1)),
+                _ => None,
+            })
+            .collect();
+        for &(_, value) in &phis {
+            self.push(value)?;
+        }
+        for &(phi, _) in phis.iter().rev() {
+            self.op(LOCAL_SET, Some(self.local(phi)));
+        }
+        Ok(())
+    }
+
+    // Sets the block to go to next, once the phis there have their values.
+    fn go_to(&mut self, from: usize, to: usize) -> Result<(), String> {
+        self.edge(from, to)?;
+        self.i32_const(to as i32);
+        self.op(LOCAL_SET, Some(self.next_block()));
+        Ok(())
+    }
+
+    // WebAssembly only has structured control flow, so the blocks go in a loop that picks
+    // the next one to run with a `br_table`: each block's code follows the end of a nested
+    // `block`, and branching to that `block` runs it.
+    fn body(mut self) -> Result<Vec<u8>, String> {
+        let count = self.function.blocks.len();
+        self.op(LOOP, None);
+        self.code.push(EMPTY);
+        for _ in 0..count {
+            self.op(BLOCK, None);
+            self.code.push(EMPTY);
+        }
+        self.op(LOCAL_GET, Some(self.next_block()));
+        self.op(BR_TABLE, Some(count as u32));
+        for depth in 0..count as u32 {
+            unsigned(&mut self.code, depth as u64);
+        }
+        unsigned(&mut self.code, 0);
+        self.op(END, None);
+
+        for (id, block) in self.function.blocks.iter().enumerate() {
+            for &value in &block.instructions {
+                self.instruction(value)?;
+            }
+            // How far out the loop is from this block's code.
+            let outer = (count - 1 - id) as u32;
+            match &block.terminator {
+                // The next block's code comes right after this one's.
+                Terminator::Jump(target) if *target == id + 1 => self.edge(id, *target)?,
+                Terminator::Jump(target) => {
+                    self.go_to(id, *target)?;
+                    self.op(BR, Some(outer));
+                }
+                Terminator::Branch(condition, then_block, else_block) => {
+                    self.push(*condition)?;
+                    self.i64_const(0);
+                    self.op(I64_NE, None);
+                    self.op(IF, None);
+                    self.code.push(EMPTY);
+                    self.go_to(id, *then_block)?;
+                    self.op(ELSE, None);
+                    self.go_to(id, *else_block)?;
+                    self.op(END, None);
+                    self.op(BR, Some(outer));
+                }
+                Terminator::Return(value) => {
+                    if let Some(value) = value {
+                        self.push(*value)?;
+                    }
+                    self.op(RETURN, None);
+                }
+            }
+            if id + 1 < count {
+                self.op(END, None);
+            }
+        }
+        // Every block ends by branching or returning, so control never leaves the loop.
+        self.op(END, None);
+        self.op(UNREACHABLE, None);
+        self.op(END, None);
+
+        let mut body = Vec::new();
+        unsigned(&mut body, 2);
+        unsigned(&mut body, self.function.values.len() as u64);
+        body.push(I64);
+        unsigned(&mut body, 1);
+        body.push(I32);
+        body.extend(self.code);
+        Ok(body)
+    }
+}
+
+// A WebAssembly module for a module in SSA form, in which every value is an `i64`:
+// booleans are 0 or 1, and strings can only be printed. Printing calls functions the host
+// imports from `env`, and the top level is exported as `_start`, with the strings in the
+// exported `memory`. Programs with floats or arrays are an error.
+pub fn emit_wasm(module: &Module) -> Result<Vec<u8>, String> {
+    let mut globals = Vec::new();
+    for function in &module.functions {
+        for block in &function.blocks {
+            for &value in &block.instructions {
+                if let Op::LoadGlobal(name) | Op::StoreGlobal(name, _) = function.op(value)
+                    && !globals.contains(name)
+                {
+                    globals.push(*name);
+                }
+            }
+        }
+    }
+
+    let bools = module.bools();
+    let mut data = Vec::new();
+    let mut bodies = Vec::new();
+    for (function, bools) in module.functions.iter().zip(&bools) {
+        let lowering = Lowering { module, function, bools, globals: &globals, code: Vec::new(), data: &mut data };
+        bodies.push(lowering.body()?);
+    }
+
+    // Each function type is its parameters and its results, all `i64`s.
+    let mut types: Vec<(Vec<u8>, Vec<u8>)> = IMPORTS.iter().map(|(_, parameters)| (parameters.to_vec(), Vec::new())).collect();
+    let mut signatures = Vec::new();
+    for (i, function) in module.functions.iter().enumerate() {
+        let signature = (vec![I64; function.parameters], if i == 0 { Vec::new() } else { vec![I64] });
+        let index = types.iter().position(|t| *t == signature).unwrap_or_else(|| {
+            types.push(signature);
+            types.len() - 1
+        });
+        signatures.push(index);
+    }
+
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    let mut contents = Vec::new();
+    for (parameters, results) in &types {
+        contents.push(0x60);
+        unsigned(&mut contents, parameters.len() as u64);
+        contents.extend_from_slice(parameters);
+        unsigned(&mut contents, results.len() as u64);
+        contents.extend_from_slice(results);
+    }
+    section(&mut out, 1, types.len(), &contents);
+
+    let mut contents = Vec::new();
+    for (i, (import, _)) in IMPORTS.iter().enumerate() {
+        name(&mut contents, "env");
+        name(&mut contents, import);
+        contents.push(0x00);
+        unsigned(&mut contents, i as u64);
+    }
+    section(&mut out, 2, IMPORTS.len(), &contents);
+
+    let mut contents = Vec::new();
+    for &signature in &signatures {
+        unsigned(&mut contents, signature as u64);
+    }
+    section(&mut out, 3, signatures.len(), &contents);
+
+    // One 64 KiB page for every 64 KiB of strings, and at least one.
+    let mut contents = vec![0x00];
+    unsigned(&mut contents, data.len().div_ceil(65536).max(1) as u64);
+    section(&mut out, 5, 1, &contents);
+
+    if !globals.is_empty() {
+        let mut contents = Vec::new();
+        for _ in &globals {
+            contents.extend_from_slice(&[I64, 0x01, I64_CONST, 0, END]);
+        }
+        section(&mut out, 6, globals.len(), &contents);
+    }
+
+    let mut contents = Vec::new();
+    name(&mut contents, "_start");
+    contents.push(0x00);
+    unsigned(&mut contents, IMPORTS.len() as u64);
+    name(&mut contents, "memory");
+    contents.push(0x02);
+    unsigned(&mut contents, 0);
+    section(&mut out, 7, 2, &contents);
+
+    let mut contents = Vec::new();
+    for body in &bodies {
+        unsigned(&mut contents, body.len() as u64);
+        contents.extend_from_slice(body);
+    }
+    section(&mut out, 10, bodies.len(), &contents);
+
+    if !data.is_empty() {
+        let mut contents = vec![0x00, I32_CONST, 0, END];
+        unsigned(&mut contents, data.len() as u64);
+        contents.extend_from_slice(&data);
+        section(&mut out, 11, 1, &contents);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn emit(source: &str) -> Result<Vec<u8>, String> {
+        emit_wasm(&build_ssa(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap()))
+    }
+
+    // Each section's id and contents, after the header.
+    fn sections(mut bytes: &[u8]) -> Vec<(u8, &[u8])> {
+        let mut sections = Vec::new();
+        while let [id, rest @ ..] = bytes {
+            let (mut size, mut shift, mut read) = (0, 0, 0);
+            for &byte in rest {
+                size |= ((byte & 0x7f) as usize) << shift;
+                shift += 7;
+                read += 1;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            sections.push((*id, &rest[read..read + size]));
+            bytes = &rest[read + size..];
+        }
+        sections
+    }
+
+    #[test]
+    fn lays_out_the_sections_a_module_needs() {
+        let module = emit("g = 2;\nfn f(n) { return n * g; }\nprint(f(21), \"done\\n\");\n").unwrap();
+        assert_eq!(&module[..8], b"\0asm\x01\0\0\0");
+        let sections = sections(&module[8..]);
+        let ids: Vec<u8> = sections.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, [1, 2, 3, 5, 6, 7, 10, 11]);
+        let has = |id: u8, text: &[u8]| sections.iter().any(|&(i, contents)| i == id && contents.windows(text.len()).any(|w| w == text));
+        assert!(has(2, b"print_int") && has(7, b"_start") && has(7, b"memory") && has(11, b"done\n"));
+    }
+
+    #[test]
+    fn what_the_backend_cant_lower_is_an_error() {
+        assert_eq!(emit("print(1.5);").unwrap_err(), "Floating point values aren't supported by the WebAssembly backend");
+        assert_eq!(emit("a = [1];\nprint(a[0]);").unwrap_err(), "Arrays aren't supported by the WebAssembly backend");
+        assert_eq!(emit("print(len(\"ab\"));").unwrap_err(), "`len` isn't supported by the WebAssembly backend");
+    }
+}
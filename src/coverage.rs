@@ -0,0 +1,164 @@
+// `run --coverage`: counts how many times each statement runs, and reports it by line, as
+// text annotating the source or in the lcov format that coverage tools read.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::diagnostics::LineIndex;
+use crate::intern::Symbol;
+use crate::interpreter::{Action, Hook, Scope};
+use crate::parser::ast::Statement;
+
+// Every statement in `statements` and the ones in them, other than blocks and function
+// declarations, which run nothing themselves.
+fn each_statement(statements: &[Statement], visit: &mut impl FnMut(&Statement)) {
+    for statement in statements {
+        match statement {
+            Statement::Block { body, .. } | Statement::Function { body, .. } => each_statement(body, visit),
+            Statement::Attributed { statement, .. } => each_statement(std::slice::from_ref(statement), visit),
+            Statement::If { then_branch, else_branch, .. } => {
+                visit(statement);
+                each_statement(std::slice::from_ref(then_branch), visit);
+                if let Some(else_branch) = else_branch {
+                    each_statement(std::slice::from_ref(else_branch), visit);
+                }
+            }
+            Statement::While { body, .. } | Statement::DoWhile { body, .. } | Statement::For { body, .. } => {
+                visit(statement);
+                each_statement(std::slice::from_ref(body), visit);
+            }
+            Statement::Expression { .. } | Statement::Print { .. } | Statement::Putc { .. } | Statement::Return { .. } => {
+                visit(statement)
+            }
+            Statement::Import { .. } => {}
+        }
+    }
+}
+
+// Each function a program declares, where, and the first statement of its body, whose
+// count is how many times it was called.
+struct Function {
+    name: Symbol,
+    start: usize,
+    first: Option<usize>
+}
+
+fn each_function(statements: &[Statement], functions: &mut Vec<Function>) {
+    for statement in statements {
+        if let Statement::Function { name, body, span, .. } = statement {
+            let mut first = None;
+            each_statement(body, &mut |statement| {
+                first.get_or_insert(statement.span().start);
+            });
+            functions.push(Function { name: *name, start: span.start, first });
+            each_function(body, functions);
+        }
+    }
+}
+
+// The counts for one run of a program, keyed by where each statement starts.
+pub struct Coverage {
+    hits: HashMap<usize, u64>,
+    functions: Vec<Function>
+}
+
+impl Coverage {
+    // Coverage of `program`, before any of it has run.
+    pub fn new(program: &[Statement]) -> Self {
+        let mut hits = HashMap::new();
+        each_statement(program, &mut |statement| {
+            hits.insert(statement.span().start, 0);
+        });
+        let mut functions = Vec::new();
+        each_function(program, &mut functions);
+        Coverage { hits, functions }
+    }
+
+    fn count(&self, start: Option<usize>) -> u64 {
+        start.and_then(|start| self.hits.get(&start)).copied().unwrap_or_default()
+    }
+
+    // How many times each line with a statement on it ran: as often as the statement on it
+    // that ran most.
+    pub fn lines(&self, source: &str) -> BTreeMap<usize, u64> {
+        let lines = LineIndex::new(source);
+        let mut counts = BTreeMap::new();
+        for (&start, &hits) in &self.hits {
+            let count = counts.entry(lines.line_column(start).0).or_default();
+            *count = hits.max(*count);
+        }
+        counts
+    }
+
+    // The source annotated with how many times each line ran, `#####` for one that never
+    // did, after a summary line.
+    pub fn report(&self, name: &str, source: &str) -> String {
+        let counts = self.lines(source);
+        let hit = counts.values().filter(|&&count| count > 0).count();
+        let percent = if counts.is_empty() { 100.0 } else { 100.0 * hit as f64 / counts.len() as f64 };
+        let mut out = format!("coverage: {}: {} of {} lines run ({:.1}%)\n", name, hit, counts.len(), percent);
+        for (i, text) in source.lines().enumerate() {
+            let count = match counts.get(&(i + 1)) {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(count) => count.to_string(),
+            };
+            writeln!(out, "{:>9} | {:>4} | {}", count, i + 1, text).unwrap();
+        }
+        out
+    }
+
+    // The counts as an lcov tracefile record for the file `name`.
+    pub fn lcov(&self, name: &str, source: &str) -> String {
+        let lines = LineIndex::new(source);
+        let mut out = format!("TN:\nSF:{}\n", name);
+        for function in &self.functions {
+            writeln!(out, "FN:{},{}", lines.line_column(function.start).0, function.name).unwrap();
+        }
+        for function in &self.functions {
+            writeln!(out, "FNDA:{},{}", self.count(function.first), function.name).unwrap();
+        }
+        let called = self.functions.iter().filter(|function| self.count(function.first) > 0).count();
+        writeln!(out, "FNF:{}\nFNH:{}", self.functions.len(), called).unwrap();
+        let counts = self.lines(source);
+        for (line, count) in &counts {
+            writeln!(out, "DA:{},{}", line, count).unwrap();
+        }
+        let hit = counts.values().filter(|&&count| count > 0).count();
+        writeln!(out, "LF:{}\nLH:{}\nend_of_record", counts.len(), hit).unwrap();
+        out
+    }
+}
+
+impl Hook for Coverage {
+    fn statement(&mut self, statement: &Statement, _: &Scope<'_>) -> Action {
+        if let Some(hits) = self.hits.get_mut(&statement.span().start) {
+            *hits += 1;
+        }
+        Action::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn counts_the_lines_that_ran() {
+        let source = "fn unused() {\n    return 1;\n}\ni = 0;\nwhile (i < 3)\n    i = i + 1;\nif (i > 5) print(i);\n";
+        let (tokens, _) = tokenize(source);
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let mut coverage = Coverage::new(&program);
+        Interpreter::with_output(std::io::sink()).with_hook(&mut coverage).run(&program).unwrap();
+
+        let counts: Vec<(usize, u64)> = coverage.lines(source).into_iter().collect();
+        assert_eq!(counts, [(2, 0), (4, 1), (5, 1), (6, 3), (7, 1)]);
+        let lcov = coverage.lcov("test.lang", source);
+        assert!(lcov.contains("FN:1,unused\nFNDA:0,unused\nFNF:1\nFNH:0\n"));
+        assert!(lcov.ends_with("DA:7,1\nLF:5\nLH:4\nend_of_record\n"));
+        assert!(coverage.report("test.lang", source).starts_with("coverage: test.lang: 4 of 5 lines run (80.0%)\n"));
+    }
+}
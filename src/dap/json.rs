@@ -0,0 +1,295 @@
+// Just enough JSON for the Debug Adapter Protocol: parsing requests and writing responses
+// and events.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // Fields in the order they were written.
+    Object(Vec<(String, Json)>)
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Json {
+        Json::Object(fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser { text, position: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => Err(parser.error("expected the end of the input")),
+        }
+    }
+
+    // The field `name` of an object, or null if there's no such field.
+    pub fn get(&self, name: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(field, _)| field == name).map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(elements: Vec<Json>) -> Self {
+        Json::Array(elements)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// Compact, as it goes over the wire.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            // Integers are written without a fraction, and there's no infinity or NaN in JSON.
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { "," } else { "" }, element)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    position: usize
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at offset {}", message, self.position)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.position += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn whitespace(&mut self) {
+        while self.peek().is_some_and(|ch| matches!(ch, ' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        self.whitespace();
+        if self.next() == Some(ch) { Ok(()) } else { Err(self.error(&format!("expected `{}`", ch))) }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.position..].starts_with(keyword) {
+            self.position += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => {
+                self.position += 1;
+                let mut elements = Vec::new();
+                self.whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    return Ok(Json::Array(elements));
+                }
+                loop {
+                    elements.push(self.value()?);
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Json::Array(elements)),
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut fields = Vec::new();
+                self.whitespace();
+                if self.peek() == Some('}') {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.whitespace();
+                    let name = self.string()?;
+                    self.expect(':')?;
+                    fields.push((name, self.value()?));
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Json::Object(fields)),
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => {
+                let start = self.position;
+                while self.peek().is_some_and(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E')) {
+                    self.position += 1;
+                }
+                self.text[start..self.position].parse().map(Json::Number).map_err(|_| self.error("invalid number"))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.position..self.position + 4).ok_or_else(|| self.error("expected 4 hex digits"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("expected 4 hex digits"))?;
+        self.position += 4;
+        Ok(code)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.next() != Some('"') {
+            return Err(self.error("expected a string"));
+        }
+        let mut s = String::new();
+        loop {
+            match self.next().ok_or_else(|| self.error("unterminated string"))? {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = self.hex()?;
+                            // A character outside the BMP is a surrogate pair.
+                            if (0xd800..0xdc00).contains(&code) && self.text[self.position..].starts_with("\\u") {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.push(escaped);
+                }
+                ch => s.push(ch),
+            }
+        }
+    }
+}
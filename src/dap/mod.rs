@@ -0,0 +1,534 @@
+// `tiny-lang dap`: the debugger as a Debug Adapter Protocol server, so that editors such as
+// VS Code can set breakpoints, step and show variables. Each message is JSON after a
+// `Content-Length` header. The program runs in the interpreter on this thread while another
+// reads requests, and those that arrive as it runs are handled between its statements.
+
+mod json;
+
+pub use json::Json;
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::debugger::shown;
+use crate::diagnostics::{Diagnostic, LineIndex, Renderer};
+use crate::intern::Symbol;
+use crate::interpreter::{Action, Hook, Interpreter, RuntimeError, Scope, Value};
+use crate::lexer::tokenize;
+use crate::parser::ast::Statement;
+use crate::parser::parser::{Parser, parse_program};
+use crate::sema::check_types;
+
+// The program's one thread, as the protocol sees it.
+const THREAD: i64 = 1;
+
+// The `variablesReference` of the globals. The locals of the call `depth` deep are
+// `GLOBALS + depth`, and a stack frame's id is its depth.
+const GLOBALS: i64 = 1;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// The next message from the client, or `None` once it has closed the connection.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end() {
+            "" if length.is_some() => break,
+            "" => {}
+            header => {
+                if let Some(value) = header.strip_prefix("Content-Length:") {
+                    length = Some(value.trim().parse().map_err(|_| invalid("an invalid Content-Length header"))?);
+                }
+            }
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid("a message that isn't UTF-8"))?;
+    Json::parse(&body).map(Some).map_err(|message| invalid(&message))
+}
+
+// The connection's sending half, which numbers each message it sends.
+struct Client<W> {
+    output: W,
+    seq: i64
+}
+
+impl<W: Write> Client<W> {
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        self.seq += 1;
+        let Json::Object(mut fields) = message else { unreachable!("messages are objects") };
+        fields.insert(0, ("seq".to_string(), Json::from(self.seq)));
+        let body = Json::Object(fields).to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.output.flush()
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(Json::object([
+            ("type", Json::from("response")),
+            ("request_seq", request.get("seq").clone()),
+            ("command", request.get("command").clone()),
+            ("success", Json::from(true)),
+            ("body", body),
+        ]))
+    }
+
+    fn fail(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(Json::object([
+            ("type", Json::from("response")),
+            ("request_seq", request.get("seq").clone()),
+            ("command", request.get("command").clone()),
+            ("success", Json::from(false)),
+            ("message", Json::from(message)),
+        ]))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(Json::object([("type", Json::from("event")), ("event", Json::from(event)), ("body", body)]))
+    }
+
+    fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+        self.event("output", Json::object([("category", Json::from(category)), ("output", Json::from(text))]))
+    }
+}
+
+// Where the program prints: to the client, as output events.
+struct ProgramOutput<W>(Rc<RefCell<Client<W>>>);
+
+impl<W: Write> Write for ProgramOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().output("stdout", &String::from_utf8_lossy(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// When to pause next, besides at breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    // Before the first statement, with `stopOnEntry`.
+    Entry,
+    // Before the next statement, because the client asked it to pause.
+    Pause,
+    Step,
+    // Before the next statement no deeper in calls than `depth`.
+    Next { depth: usize },
+    // Before the next statement once the call `depth` deep has returned.
+    Out { depth: usize },
+    Continue
+}
+
+// A program to debug, and where it came from.
+struct Program {
+    path: String,
+    source: String,
+    statements: Vec<Statement>
+}
+
+// Reads and checks the program at `path`, or says what's wrong with it.
+fn load(path: &str) -> Result<Program, String> {
+    let source = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let renderer = Renderer::new(path, &source, false);
+    let rendered = |diagnostics: Vec<Diagnostic>| {
+        diagnostics.iter().map(|diagnostic| renderer.render(diagnostic)).collect::<String>()
+    };
+    let (tokens, errors) = tokenize(source.as_str());
+    if !errors.is_empty() {
+        return Err(rendered(errors.iter().map(Diagnostic::from).collect()));
+    }
+    let statements = parse_program(&mut Parser::new(tokens)).map_err(|error| rendered(vec![Diagnostic::from(&error)]))?;
+    let errors = check_types(&statements);
+    if !errors.is_empty() {
+        return Err(rendered(errors.iter().map(Diagnostic::from).collect()));
+    }
+    Ok(Program { path: path.to_string(), source, statements })
+}
+
+fn variables(variables: Vec<(Symbol, &Value)>) -> Json {
+    Json::from(variables.into_iter().map(|(name, value)| Json::object([
+        ("name", Json::from(name.as_str())),
+        ("value", Json::from(shown(value))),
+        ("type", Json::from(value.type_name())),
+        ("variablesReference", Json::from(0i64)),
+    ])).collect::<Vec<_>>())
+}
+
+struct Session<'s, W> {
+    client: Rc<RefCell<Client<W>>>,
+    requests: &'s Receiver<Json>,
+    program: &'s Program,
+    lines: LineIndex<'s>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    // The line of the statement that last ran, so that a breakpoint pauses once as its line
+    // is reached rather than before each statement on it.
+    line: usize
+}
+
+impl<W: Write> Session<'_, W> {
+    fn source(&self) -> Json {
+        let name = Path::new(&self.program.path).file_name().unwrap_or_default().to_string_lossy();
+        Json::object([("name", Json::from(&*name)), ("path", Json::from(self.program.path.as_str()))])
+    }
+
+    // Handles one request: `scope` is where the program is paused, if it is. Returns what the
+    // program should do if the request resumes or ends it.
+    fn handle(&mut self, request: &Json, scope: Option<&Scope<'_>>) -> io::Result<Option<Action>> {
+        let mut client = self.client.borrow_mut();
+        let arguments = request.get("arguments");
+        let resumed = matches!(request.get("command").as_str(), Some("continue" | "next" | "stepIn" | "stepOut"));
+        match (request.get("command").as_str().unwrap_or_default(), scope) {
+            ("setBreakpoints", _) => {
+                let count = self.program.source.lines().count() as i64;
+                let lines: Vec<i64> = arguments.get("breakpoints").as_array().iter()
+                    .filter_map(|breakpoint| breakpoint.get("line").as_i64())
+                    .collect();
+                self.breakpoints = lines.iter().filter(|&&line| (1..=count).contains(&line)).map(|&line| line as usize).collect();
+                let breakpoints = lines.iter().map(|&line| Json::object([
+                    ("verified", Json::from((1..=count).contains(&line))),
+                    ("line", Json::from(line)),
+                ])).collect::<Vec<_>>();
+                client.respond(request, Json::object([("breakpoints", Json::from(breakpoints))]))?;
+            }
+            ("threads", _) => {
+                let thread = Json::object([("id", Json::from(THREAD)), ("name", Json::from("main"))]);
+                client.respond(request, Json::object([("threads", Json::from(vec![thread]))]))?;
+            }
+            ("pause", None) => {
+                self.mode = Mode::Pause;
+                client.respond(request, Json::object([]))?;
+            }
+            ("pause", Some(_)) => client.respond(request, Json::object([]))?,
+            ("continue", Some(_)) => self.mode = Mode::Continue,
+            ("next", Some(scope)) => self.mode = Mode::Next { depth: scope.depth() },
+            ("stepIn", Some(_)) => self.mode = Mode::Step,
+            ("stepOut", Some(scope)) => self.mode = Mode::Out { depth: scope.depth() },
+            ("stackTrace", Some(scope)) => {
+                let calls = scope.calls();
+                let frames = (0..=scope.depth()).rev().map(|depth| {
+                    let name = match depth {
+                        0 => "top level".to_string(),
+                        depth => calls[depth - 1].0.to_string(),
+                    };
+                    // The innermost frame is where the program is, and each of the others is
+                    // at the call that entered the next.
+                    let line = match calls.get(depth) {
+                        Some(&(_, call)) => self.lines.line_column(call.start).0,
+                        None => self.line,
+                    };
+                    Json::object([
+                        ("id", Json::from(depth)),
+                        ("name", Json::from(name)),
+                        ("source", self.source()),
+                        ("line", Json::from(line)),
+                        ("column", Json::from(1i64)),
+                    ])
+                }).collect::<Vec<_>>();
+                let total = frames.len();
+                client.respond(request, Json::object([("stackFrames", Json::from(frames)), ("totalFrames", Json::from(total))]))?;
+            }
+            ("scopes", Some(_)) => {
+                let depth = arguments.get("frameId").as_i64().unwrap_or_default();
+                let scope = |name: &str, reference: i64| Json::object([
+                    ("name", Json::from(name)),
+                    ("variablesReference", Json::from(reference)),
+                    ("expensive", Json::from(false)),
+                ]);
+                let mut scopes = Vec::new();
+                if depth > 0 {
+                    scopes.push(scope("Locals", GLOBALS + depth));
+                }
+                scopes.push(scope("Globals", GLOBALS));
+                client.respond(request, Json::object([("scopes", Json::from(scopes))]))?;
+            }
+            ("variables", Some(scope)) => {
+                let shown = match arguments.get("variablesReference").as_i64() {
+                    Some(GLOBALS) => scope.globals(),
+                    Some(reference) if reference > GLOBALS => scope.frame((reference - GLOBALS) as usize),
+                    _ => Vec::new(),
+                };
+                client.respond(request, Json::object([("variables", variables(shown))]))?;
+            }
+            // Expressions are variable names, in the frame asked about, as when hovering over
+            // one in the editor.
+            ("evaluate", Some(scope)) => {
+                let name = arguments.get("expression").as_str().unwrap_or_default().trim();
+                let depth = arguments.get("frameId").as_i64().map_or(scope.depth(), |depth| depth as usize);
+                let locals = scope.frame(depth);
+                let local = locals.iter().find(|(local, _)| local.as_str() == name).map(|&(_, value)| value);
+                match local.or_else(|| scope.globals().into_iter().find(|(global, _)| global.as_str() == name).map(|(_, value)| value)) {
+                    Some(value) => {
+                        let body = Json::object([("result", Json::from(shown(value))), ("variablesReference", Json::from(0i64))]);
+                        client.respond(request, body)?;
+                    }
+                    None => client.fail(request, &format!("`{}` isn't assigned here", name))?,
+                }
+            }
+            ("disconnect" | "terminate", _) => {
+                client.respond(request, Json::object([]))?;
+                return Ok(Some(Action::Stop));
+            }
+            ("continue" | "next" | "stepIn" | "stepOut" | "stackTrace" | "scopes" | "variables" | "evaluate", None) => {
+                client.fail(request, "the program isn't paused")?;
+            }
+            (command, _) => client.fail(request, &format!("`{}` isn't supported", command))?,
+        }
+        if resumed && scope.is_some() {
+            client.respond(request, Json::object([("allThreadsContinued", Json::from(true))]))?;
+            return Ok(Some(Action::Continue));
+        }
+        Ok(None)
+    }
+
+    // Why the program should pause before a statement on `line`, if it should.
+    fn reason(&self, line: usize, reached: bool, depth: usize) -> Option<&'static str> {
+        match self.mode {
+            Mode::Entry => Some("entry"),
+            Mode::Pause => Some("pause"),
+            Mode::Step => Some("step"),
+            Mode::Next { depth: next } if depth <= next => Some("step"),
+            Mode::Out { depth: out } if depth < out => Some("step"),
+            _ if reached && self.breakpoints.contains(&line) => Some("breakpoint"),
+            _ => None,
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> io::Result<Action> {
+        while let Ok(request) = self.requests.try_recv() {
+            if let Some(action) = self.handle(&request, None)? {
+                return Ok(action);
+            }
+        }
+        // A block pauses at the statements in it, and declaring a function runs nothing.
+        if matches!(statement, Statement::Block { .. } | Statement::Function { .. } | Statement::Attributed { .. }) {
+            return Ok(Action::Continue);
+        }
+        let line = self.lines.line_column(statement.span().start).0;
+        let reached = line != std::mem::replace(&mut self.line, line);
+        let Some(reason) = self.reason(line, reached, scope.depth()) else { return Ok(Action::Continue) };
+        self.client.borrow_mut().event("stopped", Json::object([
+            ("reason", Json::from(reason)),
+            ("threadId", Json::from(THREAD)),
+            ("allThreadsStopped", Json::from(true)),
+        ]))?;
+        // Without a client, there's no way to go on.
+        while let Ok(request) = self.requests.recv() {
+            if let Some(action) = self.handle(&request, Some(scope))? {
+                return Ok(action);
+            }
+        }
+        Ok(Action::Stop)
+    }
+}
+
+impl<W: Write> Hook for Session<'_, W> {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        self.statement(statement, scope).unwrap_or(Action::Stop)
+    }
+}
+
+// Debugs one program for the client that sends requests to `input` and reads what's sent
+// to `output`, from its `initialize` request until it disconnects.
+pub fn serve(mut input: impl BufRead + Send + 'static, output: impl Write) -> io::Result<()> {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(Some(request)) = read_message(&mut input) {
+            if sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
+    let client = Rc::new(RefCell::new(Client { output, seq: 0 }));
+
+    // Until the program's launched there's nothing to debug.
+    let (program, stop_on_entry) = loop {
+        let Ok(request) = requests.recv() else { return Ok(()) };
+        let mut client = client.borrow_mut();
+        match request.get("command").as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = Json::object([
+                    ("supportsConfigurationDoneRequest", Json::from(true)),
+                    ("supportsEvaluateForHovers", Json::from(true)),
+                    ("supportsTerminateRequest", Json::from(true)),
+                ]);
+                client.respond(&request, capabilities)?;
+            }
+            "launch" => {
+                let arguments = request.get("arguments");
+                let path = arguments.get("program").as_str().unwrap_or_default();
+                match load(path) {
+                    Ok(program) => {
+                        client.respond(&request, Json::object([]))?;
+                        // Breakpoints can be set now that there's a program to set them in.
+                        client.event("initialized", Json::object([]))?;
+                        break (program, arguments.get("stopOnEntry").as_bool().unwrap_or_default());
+                    }
+                    Err(message) => {
+                        client.output("stderr", &message)?;
+                        client.fail(&request, "the program couldn't be loaded")?;
+                        client.event("terminated", Json::object([]))?;
+                    }
+                }
+            }
+            "disconnect" | "terminate" => return client.respond(&request, Json::object([])),
+            command => client.fail(&request, &format!("`{}` needs a program; launch one first", command))?,
+        }
+    };
+
+    let mut session = Session {
+        client: Rc::clone(&client),
+        requests: &requests,
+        program: &program,
+        lines: LineIndex::new(&program.source),
+        breakpoints: BTreeSet::new(),
+        mode: if stop_on_entry { Mode::Entry } else { Mode::Continue },
+        line: 0,
+    };
+    // The client sets breakpoints and the like before the program starts.
+    loop {
+        let Ok(request) = requests.recv() else { return Ok(()) };
+        if request.get("command").as_str() == Some("configurationDone") {
+            client.borrow_mut().respond(&request, Json::object([]))?;
+            break;
+        }
+        if session.handle(&request, None)?.is_some() {
+            return Ok(());
+        }
+    }
+
+    let result = Interpreter::with_output(ProgramOutput(Rc::clone(&client))).with_hook(&mut session).run(&program.statements);
+    let code: i64 = match result {
+        Ok(()) => 0,
+        // The client disconnected.
+        Err(RuntimeError::Stopped { .. }) => return Ok(()),
+        Err(error) => {
+            let message = Renderer::new(&program.path, &program.source, false).render(&Diagnostic::from(&error));
+            client.borrow_mut().output("stderr", &message)?;
+            1
+        }
+    };
+    client.borrow_mut().event("exited", Json::object([("exitCode", Json::from(code))]))?;
+    client.borrow_mut().event("terminated", Json::object([]))?;
+    while let Ok(request) = requests.recv() {
+        if session.handle(&request, None)?.is_some() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Waits on `port` on localhost for an editor to connect, and debugs a program for it.
+pub fn listen(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("listening for a debug adapter client on {}", listener.local_addr()?);
+    let (stream, _) = listener.accept()?;
+    serve(BufReader::new(stream.try_clone()?), stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        seq: i64,
+        // What the program printed, from output events.
+        printed: String
+    }
+
+    impl TestClient {
+        fn request<const N: usize>(&mut self, command: &str, arguments: [(&str, Json); N]) -> Json {
+            self.seq += 1;
+            let request = Json::object([
+                ("seq", Json::from(self.seq)),
+                ("type", Json::from("request")),
+                ("command", Json::from(command)),
+                ("arguments", Json::object(arguments)),
+            ]).to_string();
+            write!(self.writer, "Content-Length: {}\r\n\r\n{}", request.len(), request).unwrap();
+            self.until(|message| message.get("type").as_str() == Some("response") && message.get("command").as_str() == Some(command))
+        }
+
+        // The next message `wanted` says is the one, skipping the others.
+        fn until(&mut self, wanted: impl Fn(&Json) -> bool) -> Json {
+            loop {
+                let message = read_message(&mut self.reader).unwrap().expect("the server hung up");
+                if message.get("event").as_str() == Some("output") {
+                    self.printed.push_str(message.get("body").get("output").as_str().unwrap());
+                }
+                if wanted(&message) {
+                    return message;
+                }
+            }
+        }
+
+        fn event(&mut self, event: &str) -> Json {
+            self.until(|message| message.get("event").as_str() == Some(event))
+        }
+    }
+
+    #[test]
+    fn stops_at_a_breakpoint_and_shows_the_stack_and_variables() {
+        let path = std::env::temp_dir().join(format!("tiny-lang-dap-{}.lang", std::process::id()));
+        fs::write(&path, "fn twice(n) {\n    m = n * 2;\n    return m;\n}\ny = twice(4);\nprint(y);\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(BufReader::new(stream.try_clone().unwrap()), stream).unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let mut client = TestClient { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream, seq: 0, printed: String::new() };
+
+        client.request("initialize", [("adapterID", Json::from("tiny-lang"))]);
+        let launched = client.request("launch", [("program", Json::from(path.as_str()))]);
+        assert_eq!(launched.get("success"), &Json::Bool(true));
+        client.event("initialized");
+        let breakpoints = [("breakpoints", Json::from(vec![Json::object([("line", Json::from(3i64))])]))];
+        let set = client.request("setBreakpoints", breakpoints);
+        assert_eq!(set.get("body").get("breakpoints").as_array()[0].get("verified"), &Json::Bool(true));
+        client.request("configurationDone", []);
+
+        let stopped = client.event("stopped");
+        assert_eq!(stopped.get("body").get("reason").as_str(), Some("breakpoint"));
+        let trace = client.request("stackTrace", [("threadId", Json::from(THREAD))]);
+        let frames: Vec<(&str, i64)> = trace.get("body").get("stackFrames").as_array().iter()
+            .map(|frame| (frame.get("name").as_str().unwrap(), frame.get("line").as_i64().unwrap()))
+            .collect();
+        assert_eq!(frames, [("twice", 3), ("top level", 5)]);
+        let locals = client.request("variables", [("variablesReference", Json::from(GLOBALS + 1))]);
+        let locals: Vec<String> = locals.get("body").get("variables").as_array().iter()
+            .map(|variable| format!("{} = {}", variable.get("name").as_str().unwrap(), variable.get("value").as_str().unwrap()))
+            .collect();
+        assert_eq!(locals, ["m = 8", "n = 4"]);
+
+        client.request("continue", [("threadId", Json::from(THREAD))]);
+        let exited = client.event("exited");
+        assert_eq!(exited.get("body").get("exitCode").as_i64(), Some(0));
+        assert_eq!(client.printed, "8");
+        client.request("disconnect", []);
+        server.join().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+// `tiny-lang debug`: runs a program in the interpreter, pausing before statements to take
+// commands. It's a hook, so the program runs exactly as `run` would run it.
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::diagnostics::LineIndex;
+use crate::intern::Symbol;
+use crate::interpreter::{Action, Hook, Interpreter, RuntimeError, Scope, Value};
+use crate::lexer::quote_string;
+use crate::parser::ast::Statement;
+
+const PROMPT: &str = "(debug) ";
+
+const HELP: &str = "\
+break <line>    Pause whenever a statement on the line is about to run (b)
+delete <line>   Remove the breakpoint on the line (d)
+step            Run one statement, stepping into calls (s)
+next            Run one statement, stepping over calls (n)
+continue        Run until the next breakpoint (c)
+print <name>    Show the variable as the program would read it here (p)
+locals          Show the current function's variables
+globals         Show the global variables
+backtrace       Show the calls that led here (bt)
+list            Show the source around the current line (l)
+quit            Stop the program (q)
+An empty line repeats the last command.
+";
+
+// When to pause next, besides at breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    // Before the next statement.
+    Step,
+    // Before the next statement no deeper in calls than `depth`.
+    Next { depth: usize },
+    Continue
+}
+
+pub struct Debugger<'s, R, W> {
+    source: &'s str,
+    lines: LineIndex<'s>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    // The line of the statement that last ran, so that a breakpoint pauses once as its line
+    // is reached rather than before each statement on it.
+    line: usize,
+    // The last command, which an empty line repeats.
+    last: String,
+    input: R,
+    output: W
+}
+
+// How `print` shows a value: strings quoted, so that they can be told from numbers.
+pub(crate) fn shown(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote_string(s),
+        value => value.to_string(),
+    }
+}
+
+impl<'s, R: BufRead, W: Write> Debugger<'s, R, W> {
+    // A debugger for a program parsed from `source` that reads commands from `input` and
+    // writes to `output`, pausing before the first statement.
+    pub fn new(source: &'s str, input: R, output: W) -> Self {
+        Debugger {
+            source,
+            lines: LineIndex::new(source),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            line: 0,
+            last: String::new(),
+            input,
+            output,
+        }
+    }
+
+    fn source_line(&self, line: usize) -> Option<&'s str> {
+        self.source.lines().nth(line.checked_sub(1)?)
+    }
+
+    fn show_line(&mut self, line: usize, current: bool) -> io::Result<()> {
+        let text = self.source_line(line).unwrap_or_default();
+        let marker = match (current, self.breakpoints.contains(&line)) {
+            (true, _) => '>',
+            (false, true) => '*',
+            (false, false) => ' ',
+        };
+        writeln!(self.output, "{} {:>4} | {}", marker, line, text)
+    }
+
+    fn show_variables(&mut self, variables: Vec<(Symbol, &Value)>, none: &str) -> io::Result<()> {
+        if variables.is_empty() {
+            writeln!(self.output, "{}", none)?;
+        }
+        for (name, value) in variables {
+            writeln!(self.output, "{} = {}", name, shown(value))?;
+        }
+        Ok(())
+    }
+
+    fn line_number(&mut self, argument: Option<&str>) -> io::Result<Option<usize>> {
+        let line = argument.and_then(|argument| argument.parse().ok()).filter(|&line| self.source_line(line).is_some());
+        if line.is_none() {
+            writeln!(self.output, "expected a line number from 1 to {}", self.source.lines().count())?;
+        }
+        Ok(line)
+    }
+
+    // Takes commands until one resumes the program, returning what it should do.
+    fn pause(&mut self, line: usize, scope: &Scope<'_>) -> io::Result<Action> {
+        // Whatever the program printed so far comes before the prompt.
+        io::stdout().flush()?;
+        self.show_line(line, true)?;
+        loop {
+            write!(self.output, "{}", PROMPT)?;
+            self.output.flush()?;
+            let mut command = String::new();
+            if self.input.read_line(&mut command)? == 0 {
+                return Ok(Action::Stop);
+            }
+            let command = match command.trim() {
+                "" => self.last.clone(),
+                command => command.to_string(),
+            };
+            self.last.clone_from(&command);
+            let mut words = command.split_whitespace();
+            let (name, argument) = (words.next().unwrap_or_default(), words.next());
+            match name {
+                "" => {}
+                "break" | "b" => {
+                    if let Some(line) = self.line_number(argument)? {
+                        self.breakpoints.insert(line);
+                        writeln!(self.output, "breakpoint at line {}", line)?;
+                    }
+                }
+                "delete" | "d" => {
+                    if let Some(line) = self.line_number(argument)? {
+                        if self.breakpoints.remove(&line) {
+                            writeln!(self.output, "deleted the breakpoint at line {}", line)?;
+                        } else {
+                            writeln!(self.output, "there's no breakpoint at line {}", line)?;
+                        }
+                    }
+                }
+                "step" | "s" => {
+                    self.mode = Mode::Step;
+                    return Ok(Action::Continue);
+                }
+                "next" | "n" => {
+                    self.mode = Mode::Next { depth: scope.depth() };
+                    return Ok(Action::Continue);
+                }
+                "continue" | "c" => {
+                    self.mode = Mode::Continue;
+                    return Ok(Action::Continue);
+                }
+                "print" | "p" => match argument {
+                    Some(variable) => match scope.variable(variable) {
+                        Some(value) => writeln!(self.output, "{} = {}", variable, shown(value))?,
+                        None => writeln!(self.output, "`{}` isn't assigned here", variable)?,
+                    },
+                    None => writeln!(self.output, "expected a variable to print")?,
+                },
+                "locals" => self.show_variables(scope.locals(), "no locals at the top level")?,
+                "globals" => self.show_variables(scope.globals(), "no globals assigned yet")?,
+                "backtrace" | "bt" => {
+                    let mut current = line;
+                    for (depth, &(function, call)) in scope.calls().iter().enumerate().rev() {
+                        writeln!(self.output, "#{} `{}` at line {}", scope.depth() - depth - 1, function, current)?;
+                        current = self.lines.line_column(call.start).0;
+                    }
+                    writeln!(self.output, "#{} top level at line {}", scope.depth(), current)?;
+                }
+                "list" | "l" => {
+                    let last = self.source.lines().count();
+                    for shown in line.saturating_sub(3).max(1)..=(line + 3).min(last) {
+                        self.show_line(shown, shown == line)?;
+                    }
+                }
+                "help" | "h" => write!(self.output, "{}", HELP)?,
+                "quit" | "q" => return Ok(Action::Stop),
+                _ => writeln!(self.output, "unknown command `{}`; try `help`", name)?,
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Hook for Debugger<'_, R, W> {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        // A block pauses at the statements in it, and declaring a function runs nothing.
+        if matches!(statement, Statement::Block { .. } | Statement::Function { .. } | Statement::Attributed { .. }) {
+            return Action::Continue;
+        }
+        let line = self.lines.line_column(statement.span().start).0;
+        let reached = line != std::mem::replace(&mut self.line, line);
+        let pause = match self.mode {
+            Mode::Step => true,
+            Mode::Next { depth } => scope.depth() <= depth,
+            Mode::Continue => false,
+        } || reached && self.breakpoints.contains(&line);
+        if !pause {
+            return Action::Continue;
+        }
+        // Without a terminal to talk to, there's no way to go on.
+        self.pause(line, scope).unwrap_or(Action::Stop)
+    }
+}
+
+// Debugs `program`, parsed from `source`, with commands from stdin. The program prints to
+// stdout as usual and the debugger to stderr. Quitting is `RuntimeError::Stopped`.
+pub fn run(source: &str, program: &[Statement]) -> Result<(), RuntimeError> {
+    let mut debugger = Debugger::new(source, io::stdin().lock(), io::stderr());
+    Interpreter::new().with_hook(&mut debugger).run(program)?;
+    eprintln!("the program finished");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn pauses_at_breakpoints_and_shows_variables() {
+        let source = "fn twice(n) {\n    m = n * 2;\n    return m;\n}\nx = \"a\";\ny = twice(4);\nprint(y);\n";
+        let (tokens, _) = tokenize(source);
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let commands = "break 3\nc\nlocals\nbt\nn\np x\nq\n";
+        let mut transcript = Vec::new();
+        let mut debugger = Debugger::new(source, commands.as_bytes(), &mut transcript);
+        let mut printed = Vec::new();
+        let error = Interpreter::with_output(&mut printed).with_hook(&mut debugger).run(&program).unwrap_err();
+        assert!(matches!(error, RuntimeError::Stopped { .. }));
+        assert!(printed.is_empty());
+
+        let transcript = String::from_utf8(transcript).unwrap();
+        assert_eq!(
+            transcript,
+            "\
+>    5 | x = \"a\";
+(debug) breakpoint at line 3
+(debug) >    3 |     return m;
+(debug) m = 8
+n = 4
+(debug) #0 `twice` at line 3
+#1 top level at line 6
+(debug) >    7 | print(y);
+(debug) x = \"a\"
+(debug) "
+        );
+    }
+}
@@ -0,0 +1,454 @@
+// The longer story behind each diagnostic code, for `tiny-lang explain`.
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    // A program that gets the diagnostic, and how to fix it.
+    pub example: &'static str,
+    pub fix: &'static str
+}
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "E0101",
+        title: "unrecognized token",
+        description: "The lexer found text that doesn't start any token, such as a stray `@` or `$`, or a\n\
+                      character literal that's never closed.",
+        example: "x = 1 @ 2;",
+        fix: "Remove the character, or close the character literal it belongs to.",
+    },
+    Explanation {
+        code: "E0102",
+        title: "invalid escape sequence",
+        description: "Strings only understand the escapes `\\n`, `\\t`, `\\\\`, `\\\"` and `\\0`. Character\n\
+                      literals understand `\\n`, `\\t`, `\\r`, `\\\\`, `\\'` and `\\0`, and also `\\xNN` and `\\u{NNNN}`\n\
+                      for a character by its code in hex, in two digits or up to six.",
+        example: "print(\"a\\qb\");",
+        fix: "Use one of the supported escapes, or write `\\\\` for a literal backslash.",
+    },
+    Explanation {
+        code: "E0103",
+        title: "invalid UTF-8",
+        description: "Source files must be UTF-8. The error points at the first byte that isn't.",
+        example: "A file saved as Latin-1 containing `print(\"caf\\xe9\");`",
+        fix: "Save the file as UTF-8, or pass `--lossy` to replace invalid bytes with U+FFFD.",
+    },
+    Explanation {
+        code: "E0104",
+        title: "invalid directive",
+        description: "`#if NAME`, `#else` and `#endif` choose which parts of the file are compiled: what's\n\
+                      between `#if NAME` and its `#else` or `#endif` if NAME is defined with `-D NAME`,\n\
+                      and what's between the `#else` and `#endif` if it isn't. Each is on a line of its\n\
+                      own, an `#else` or `#endif` needs an `#if` before it, and an `#if` has at most one\n\
+                      `#else`. The only other directive is `#include \"file\"`.",
+        example: "#if DEBUG\nprint(\"debugging\\n\");\n#endif\n#endif",
+        fix: "Remove the extra directive, or put it on a line of its own.",
+    },
+    Explanation {
+        code: "E0105",
+        title: "`#if` without `#endif`",
+        description: "Every `#if` needs an `#endif` to say where the part it chooses ends.",
+        example: "#if C\nputc(65);\nprint(1);",
+        fix: "Add an `#endif` after the last line the `#if` is for.",
+    },
+    Explanation {
+        code: "E0106",
+        title: "file can't be included",
+        description: "`#include \"file\"` puts the tokens of another file where it is. The file is found\n\
+                      relative to the directory of the one including it, and this one couldn't be read.",
+        example: "#include \"utils.lang\"\nprint(twice(2));",
+        fix: "Check the file's name and where it is relative to the file including it.",
+    },
+    Explanation {
+        code: "E0107",
+        title: "file includes itself",
+        description: "A file's `#include`s, or theirs, lead back to a file that's already being included,\n\
+                      which would never end. The notes show the chain of `#include`s.",
+        example: "// In a.lang, which b.lang includes\n#include \"b.lang\"",
+        fix: "Move what both files need into a third that each includes, and include it once.",
+    },
+    Explanation {
+        code: "E0108",
+        title: "can't import module",
+        description: "`import name;` looks for `name.lang` next to the file importing it, and then in each\n\
+                      directory `-I` adds, in order. None of them had one, or it couldn't be read.",
+        example: "import geometry;",
+        fix: "Check the module's name, or add the directory it's in with `-I`.",
+    },
+    Explanation {
+        code: "E0109",
+        title: "module imported twice",
+        description: "A file imports each module once. Another file can import it too, and shares it:\n\
+                      a module's top level only runs once.",
+        example: "import geometry;\nimport geometry;",
+        fix: "Remove the second `import`.",
+    },
+    Explanation {
+        code: "E0110",
+        title: "module imports itself",
+        description: "A module's `import`s, or theirs, lead back to a module that's still being imported.\n\
+                      Each module's top level runs before its importer's, so neither could run first.\n\
+                      The notes show the chain of `import`s.",
+        example: "// In a.lang, which b.lang imports\nimport b;",
+        fix: "Move what both modules need into a third that each imports.",
+    },
+    Explanation {
+        code: "E0111",
+        title: "name from a module that isn't imported",
+        description: "A name such as `geometry.area` is the function or global `area` of the module\n\
+                      `geometry`, which the file has to import itself, even if a module it imports does.",
+        example: "print(geometry.area(2, 3));",
+        fix: "Add `import geometry;` at the top level of the file.",
+    },
+    Explanation {
+        code: "E0112",
+        title: "integer literal out of range",
+        description: "Integers are 64 bits, from -9223372036854775808 to 9223372036854775807. A negative\n\
+                      number is `-` applied to a literal, so the literal itself goes up to\n\
+                      9223372036854775807.",
+        example: "print(99999999999999999999);",
+        fix: "Use a smaller number, or a float such as `1e20` if it doesn't need to be exact.",
+    },
+    Explanation {
+        code: "E0113",
+        title: "unterminated block comment",
+        description: "A `/*` comment runs until the first `*/` after it, and this one has none, so the rest\n\
+                      of the file would be in it. Block comments don't nest.",
+        example: "x = 1; /* the end\nprint(x);",
+        fix: "Close the comment with `*/`, or use `//` for a comment to the end of the line.",
+    },
+    Explanation {
+        code: "E0114",
+        title: "unterminated string literal",
+        description: "A string that's opened with `\"`, `\"\"\"` or `r\"` has to be closed by the same quote\n\
+                      before the end of the file. A `\\\"` inside a `\"` string is an escaped quote, not the end.",
+        example: "print(\"hello);",
+        fix: "Close the string, or write `\\\\` for a backslash just before the closing quote.",
+    },
+    Explanation {
+        code: "E0115",
+        title: "float literal out of range",
+        description: "Floats are 64 bits, up to about 1.8e308. A literal bigger than that, such as one with\n\
+                      too big an exponent, would be infinity, which no literal can be. One too small is\n\
+                      rounded to 0.",
+        example: "print(1e400);",
+        fix: "Use a smaller exponent.",
+    },
+    Explanation {
+        code: "E0201",
+        title: "unexpected token",
+        description: "The parser needed a particular token, such as a `;` or `)`, and found another.\n\
+                      A misspelt keyword reads as a name, so `whlie (x) { ... }` is missing a `;`\n\
+                      after a call to `whlie`; a note points at names that look like keywords.",
+        example: "print(1)\nprint(2);",
+        fix: "Add the missing token. The error points at where it was expected.",
+    },
+    Explanation {
+        code: "E0202",
+        title: "expected an expression",
+        description: "A value was needed, but the next token can't start one.",
+        example: "x = * 2;",
+        fix: "Write the missing operand, or remove the extra operator.",
+    },
+    Explanation {
+        code: "E0203",
+        title: "invalid assignment target",
+        description: "Only variables and array elements can be assigned to or incremented.",
+        example: "1 = x;\n++(a + b);",
+        fix: "Assign to a variable or an element instead: `x = 1;`, `a[0] = 2;`.",
+    },
+    Explanation {
+        code: "E0204",
+        title: "call of something other than a function name",
+        description: "Functions are called by name. Other expressions, such as array elements or the\n\
+                      result of another call, can't be called.",
+        example: "handlers = [1, 2];\nhandlers[0](5);",
+        fix: "Call the function by its name, such as `handle(5);`.",
+    },
+    Explanation {
+        code: "E0205",
+        title: "nested function",
+        description: "Functions can only be declared at the top level of a program, not inside another\n\
+                      function.",
+        example: "fn outer() {\n    fn inner() { return 1; }\n}",
+        fix: "Move the inner function to the top level.",
+    },
+    Explanation {
+        code: "E0206",
+        title: "`return` outside of a function",
+        description: "`return` leaves a function, so it can't appear at the top level.",
+        example: "return 1;",
+        fix: "Remove the `return`, or move the code into a function.",
+    },
+    Explanation {
+        code: "E0207",
+        title: "unknown attribute",
+        description: "Functions can be marked with `#[inline]`, to inline calls to them whatever their size,\n\
+                      or `#[noinline]`, to never inline them. Functions and statements can have\n\
+                      `#[allow(...)]`, `#[warn(...)]` and `#[deny(...)]`, which set the level of the lints\n\
+                      named for the warnings in them. No other attributes exist.",
+        example: "#[inlin]\nfn twice(x) { return x * 2; }",
+        fix: "Check the spelling, or remove the attribute.",
+    },
+    Explanation {
+        code: "E0208",
+        title: "conflicting attributes",
+        description: "A function can't be marked both `#[inline]` and `#[noinline]`.",
+        example: "#[inline]\n#[noinline]\nfn twice(x) { return x * 2; }",
+        fix: "Keep the one that's meant.",
+    },
+    Explanation {
+        code: "E0209",
+        title: "invalid attribute",
+        description: "`#[inline]` and `#[noinline]` take no arguments and only go before a function.\n\
+                      `#[allow(...)]`, `#[warn(...)]` and `#[deny(...)]` take one or more lints or lint\n\
+                      groups, as `-A`, `-W` and `--deny` do.",
+        example: "#[allow(unusd)]\nx = 1;",
+        fix: "Move the attribute, or check the lints' names with `tiny-lang --help`.",
+    },
+    Explanation {
+        code: "E0210",
+        title: "import outside the top level",
+        description: "Modules are linked in before the program runs, so `import` can't be inside a\n\
+                      function or a block, or after an `if` or a loop.",
+        example: "fn f() {\n    import geometry;\n}",
+        fix: "Move the `import` to the top level, with those of the other modules.",
+    },
+    Explanation {
+        code: "E0301",
+        title: "use of an undeclared variable",
+        description: "A variable is declared by assigning to it. Functions can read the variables they\n\
+                      assign and their parameters, as well as globals assigned at the top level.",
+        example: "count = 0;\nprint(cout);",
+        fix: "Check the spelling, or assign the variable before it's used.",
+    },
+    Explanation {
+        code: "E0302",
+        title: "call to an undeclared function",
+        description: "Only functions declared with `fn` somewhere in the program, and builtins such as\n\
+                      `len` and `abs`, can be called.",
+        example: "fn add(a, b) { return a + b; }\nprint(ad(1, 2));",
+        fix: "Check the spelling, or declare the function.",
+    },
+    Explanation {
+        code: "E0303",
+        title: "operator applied to the wrong types",
+        description: "Arithmetic needs numbers, `+` also joins two strings, bitwise operators need\n\
+                      integers, and `==` compares two numbers or two strings.",
+        example: "x = \"abc\" * 3;",
+        fix: "Convert one side, or use an operator that applies to both types.",
+    },
+    Explanation {
+        code: "E0304",
+        title: "unary operator applied to the wrong type",
+        description: "`-` and `+` need a number, `~` an integer, and `!` a number or bool.",
+        example: "x = -\"abc\";",
+        fix: "Apply the operator to a value of the right type.",
+    },
+    Explanation {
+        code: "E0305",
+        title: "value of the wrong type",
+        description: "Conditions must be numbers or bools, only arrays can be indexed, indices and\n\
+                      `putc` arguments must be integers, and `len` takes an array or a string.",
+        example: "if (\"x\") { print(1); }",
+        fix: "Use a value of the expected type, such as `if (len(\"x\") > 0)`.",
+    },
+    Explanation {
+        code: "E0306",
+        title: "variable assigned different types",
+        description: "A variable's type is inferred from its first assignment, and later assignments must\n\
+                      give it a value of the same type.",
+        example: "x = 1;\nx = \"hi\";",
+        fix: "Use a different variable for the value of the other type.",
+    },
+    Explanation {
+        code: "E0307",
+        title: "wrong number of arguments",
+        description: "A call must pass a function exactly as many arguments as it has parameters, and\n\
+                      a builtin as many as it takes, such as one for `len`.",
+        example: "fn add(a, b) { return a + b; }\nprint(add(1));",
+        fix: "Pass one argument for each parameter.",
+    },
+    Explanation {
+        code: "E0308",
+        title: "function defined more than once",
+        description: "Each function in a program must have a name of its own, which calls refer to it by.",
+        example: "fn f() { return 1; }\nfn f() { return 2; }",
+        fix: "Rename one of the functions, or remove the one that isn't wanted.",
+    },
+    Explanation {
+        code: "E0401",
+        title: "operator applied to the wrong types at runtime",
+        description: "The operands' types are only known when the program runs, and the operator\n\
+                      doesn't apply to them. See E0303 for each operator's rules.",
+        example: "fn twice(x) { return x * 2; }\nprint(twice(\"a\"));",
+        fix: "Pass values of the types the operator expects.",
+    },
+    Explanation {
+        code: "E0402",
+        title: "unary operator applied to the wrong type at runtime",
+        description: "As E0304, for an operand whose type is only known when the program runs.",
+        example: "fn negate(x) { return -x; }\nprint(negate(\"a\"));",
+        fix: "Pass a value of the type the operator expects.",
+    },
+    Explanation {
+        code: "E0403",
+        title: "bad call to a host function or a builtin",
+        description: "The function is provided by the program tiny-lang is embedded in, which says how\n\
+                      many arguments it takes and of which types, or is a builtin such as `chr` or\n\
+                      `int`. The call passed something else, or the function itself reported that it\n\
+                      failed, such as `int(\"x\")`, which has no integer value.",
+        example: "// With a host function `sqrt` that takes a float\nprint(sqrt(\"four\"));",
+        fix: "Pass arguments of the types the host function takes.",
+    },
+    Explanation {
+        code: "E0404",
+        title: "the program ran out of fuel",
+        description: "The program was only allowed to run so many steps, with `--fuel` or by the program\n\
+                      it's embedded in, and it used them all before it finished. A program that never\n\
+                      stops, such as one stuck in a loop, always ends like this.",
+        example: "// With --fuel 1000\nwhile (1);",
+        fix: "Make sure the program's loops end, or allow it more fuel.",
+    },
+    Explanation {
+        code: "E0405",
+        title: "calls nested too deeply",
+        description: "Every call waits for the calls it makes to return, and only so many can be waiting\n\
+                      at once. This usually means a recursive function never reaches the case where it\n\
+                      stops calling itself.",
+        example: "fn down(n) { return down(n - 1); }\nprint(down(10));",
+        fix: "Give the recursion a case that returns without calling again, or use a loop.",
+    },
+    Explanation {
+        code: "E0406",
+        title: "integer division by zero",
+        description: "`/` or `%` was applied to integers with a right operand of 0, which has no result.\n\
+                      Dividing a float by zero isn't an error: it gives an infinity or NaN.",
+        example: "fn average(total, count) { return total / count; }\nprint(average(10, 0));",
+        fix: "Check that the divisor isn't 0 before dividing.",
+    },
+    Explanation {
+        code: "E0407",
+        title: "integer overflow",
+        description: "Integers are 64 bits, and the result of this arithmetic doesn't fit in them. It's\n\
+                      only an error when overflow traps, with `--overflow trap`; by default the result\n\
+                      wraps around, and with `--overflow saturate` it's clamped to the nearest limit.",
+        example: "// With --overflow trap\nbig = 9223372036854775807;\nprint(big + 1);",
+        fix: "Keep the values smaller, or choose the overflow behavior the program relies on.",
+    },
+    Explanation {
+        code: "E0408",
+        title: "the program was stopped",
+        description: "Something watching the program run, such as `tiny-lang debug` when you quit it,\n\
+                      stopped it before it finished. Nothing is wrong with the program itself.",
+        example: "// In `tiny-lang debug`, typing `quit` at the first statement\nprint(1);",
+        fix: "Let the program run to the end, such as with `continue` in the debugger.",
+    },
+    Explanation {
+        code: "E0409",
+        title: "index out of bounds",
+        description: "An array of length n has elements at the indices 0 to n - 1, and the index was\n\
+                      below 0 or at least n.",
+        example: "primes = [2, 3, 5];\nprint(primes[3]);",
+        fix: "Check the index against `len` of the array before using it.",
+    },
+    Explanation {
+        code: "E0410",
+        title: "value of the wrong type at runtime",
+        description: "As E0305, for a value whose type is only known when the program runs, such as an\n\
+                      argument indexed as an array that isn't one.",
+        example: "fn first(a) { return a[0]; }\nprint(first(7));",
+        fix: "Pass a value of the type the program expects.",
+    },
+    Explanation {
+        code: "E0411",
+        title: "call to an undefined function at runtime",
+        description: "As E0302, for a program run without being checked first, such as by a program\n\
+                      tiny-lang is embedded in.",
+        example: "print(ad(1, 2));",
+        fix: "Check the spelling, or declare the function.",
+    },
+    Explanation {
+        code: "E0412",
+        title: "wrong number of arguments at runtime",
+        description: "As E0307, for a program run without being checked first.",
+        example: "fn add(a, b) { return a + b; }\nprint(add(1));",
+        fix: "Pass one argument for each parameter.",
+    },
+    Explanation {
+        code: "W0101",
+        title: "variable may be read before it's assigned (lint `use_before_assignment`)",
+        description: "Some path through the program reads the variable without assigning it first, for\n\
+                      example when it's only assigned in one branch of an `if`. It reads as 0 there.",
+        example: "if (n > 0) { sign = 1; }\nprint(sign);",
+        fix: "Assign the variable on every path, such as before the `if`.",
+    },
+    Explanation {
+        code: "W0102",
+        title: "variable is never read (lint `unused_variables`)",
+        description: "The variable is assigned but nothing reads it.",
+        example: "unused = 1;\nprint(2);",
+        fix: "Remove the variable, or use it.",
+    },
+    Explanation {
+        code: "W0103",
+        title: "assigned value is never read (lint `unused_assignments`)",
+        description: "Every path from the assignment assigns the variable again, or ends, before\n\
+                      reading it.",
+        example: "x = 1;\nx = 2;\nprint(x);",
+        fix: "Remove the first assignment.",
+    },
+    Explanation {
+        code: "W0104",
+        title: "unreachable statement (lint `unreachable_code`)",
+        description: "The statement follows a `return`, or a loop whose condition is always true. There's\n\
+                      no `break`, so control never gets past either.",
+        example: "while (1) { print(1); }\nprint(2);",
+        fix: "Remove the statement, or give the loop a condition that becomes false.",
+    },
+    Explanation {
+        code: "W0105",
+        title: "local variable shadows a global (lint `shadowing`, off by default)",
+        description: "A function's parameter or local has the same name as a global, so the function\n\
+                      can't read the global, and assigning to it doesn't change the global.",
+        example: "total = 0;\nfn add(n) { total = total + n; }",
+        fix: "Rename the local, or pass the global in as a parameter.",
+    },
+];
+
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+// An explanation as `tiny-lang explain` prints it.
+pub fn format_explanation(explanation: &Explanation) -> String {
+    let indent = |text: &str| text.lines().map(|line| format!("    {}\n", line)).collect::<String>();
+    format!(
+        "{}: {}\n\n{}\n\nFor example:\n\n{}\n{}\n",
+        explanation.code, explanation.title, explanation.description, indent(explanation.example), explanation.fix,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn invalid_escape_names_the_escapes_the_lexer_understands() {
+        let description = EXPLANATIONS.iter().find(|explanation| explanation.code == "E0102").unwrap().description;
+        let (strings, chars) = description.split_once("Character").unwrap();
+        let escapes = |text: &str| -> Vec<String> {
+            text.split('`').skip(1).step_by(2).map(|escape| escape.replace("NNNN", "1F600").replace("NN", "41")).collect()
+        };
+        for escape in escapes(strings) {
+            assert_eq!(tokenize(&format!("\"{}\"", escape)).1, [], "{}", escape);
+        }
+        for escape in escapes(chars) {
+            assert_eq!(tokenize(&format!("'{}'", escape)).1, [], "{}", escape);
+        }
+        // Nor does either understand the other's.
+        assert_ne!(tokenize("\"\\'\\r\"").1, []);
+        assert_ne!(tokenize("'\\\"'").1, []);
+    }
+}
@@ -0,0 +1,86 @@
+use super::{Diagnostic, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny
+}
+
+// A kind of warning, which `-W`, `-A` and `--deny` refer to by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lint {
+    pub name: &'static str,
+    // The code of the warnings it reports.
+    pub code: &'static str,
+    pub default: Level,
+    pub description: &'static str
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint { name: "use_before_assignment", code: "W0101", default: Level::Warn, description: "variables that may be read before they're assigned" },
+    Lint { name: "unused_variables", code: "W0102", default: Level::Warn, description: "variables that are assigned but never read" },
+    Lint { name: "unused_assignments", code: "W0103", default: Level::Warn, description: "assignments whose value is never read" },
+    Lint { name: "unreachable_code", code: "W0104", default: Level::Warn, description: "statements that can never run" },
+    Lint { name: "shadowing", code: "W0105", default: Level::Allow, description: "function variables with the same name as a global" },
+];
+
+// Names that stand for several lints at once.
+pub const LINT_GROUPS: &[(&str, &[&str])] = &[
+    ("unused", &["unused_variables", "unused_assignments"]),
+];
+
+// The level of each lint, starting from its default. Later settings override earlier ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintLevels {
+    levels: Vec<(&'static str, Level)>
+}
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        Self {levels: LINTS.iter().map(|lint| (lint.name, lint.default)).collect()}
+    }
+}
+
+impl LintLevels {
+    // `name` is a lint or its code, a group, or `warnings` for every lint. Unknown names are
+    // an error.
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+        let group = LINT_GROUPS.iter().find(|(group, _)| *group == name).map(|(_, lints)| *lints);
+        let mut found = false;
+        for (lint, (_, current)) in LINTS.iter().zip(&mut self.levels) {
+            if name == "warnings" || lint.name == name || lint.code == name || group.is_some_and(|group| group.contains(&lint.name)) {
+                // `warnings` only changes the lints that are on, so `--deny warnings` doesn't
+                // turn any on.
+                if !(name == "warnings" && level != Level::Allow && *current == Level::Allow) {
+                    *current = level;
+                }
+                found = true;
+            }
+        }
+        if found { Ok(()) } else { Err(format!("unknown lint `{}`", name)) }
+    }
+
+    // Whether `set` knows the name.
+    pub fn is_known(name: &str) -> bool {
+        LintLevels::default().set(name, Level::Allow).is_ok()
+    }
+
+    pub fn level(&self, name: &str) -> Level {
+        self.levels.iter().find(|(lint, _)| *lint == name).map_or(Level::Warn, |&(_, level)| level)
+    }
+
+    // Drops allowed warnings and makes denied ones errors. Errors are left alone.
+    pub fn apply(&self, diagnostic: Diagnostic) -> Option<Diagnostic> {
+        if diagnostic.severity != Severity::Warning {
+            return Some(diagnostic);
+        }
+        let lint = LINTS.iter().find(|lint| Some(lint.code) == diagnostic.code);
+        match lint.map_or(Level::Warn, |lint| self.level(lint.name)) {
+            Level::Allow => None,
+            Level::Warn => Some(diagnostic),
+            Level::Deny => Some(Diagnostic { severity: Severity::Error, ..diagnostic }),
+        }
+    }
+}
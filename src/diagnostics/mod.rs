@@ -0,0 +1,64 @@
+mod span;
+mod render;
+mod lint;
+mod explain;
+mod suggest;
+
+pub use span::{LineIndex, SourceFile, Span};
+pub use render::{Renderer, stderr_supports_color};
+pub use explain::{EXPLANATIONS, Explanation, explain, format_explanation};
+pub use lint::{LINT_GROUPS, LINTS, Level, Lint, LintLevels};
+pub use suggest::{edit_distance, most_similar};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    // Likely mistakes that don't stop the program from running.
+    Warning
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Looked up by `tiny-lang explain`; each lint's warnings share a code.
+    pub code: Option<&'static str>,
+    pub message: String,
+    // The first is where the problem is and has no message of its own; the rest are
+    // other places worth pointing at, each with what's there.
+    pub spans: Vec<Label>
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            spans: vec![Label { span, message: String::new() }],
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self {severity: Severity::Warning, ..Self::error(message, span)}
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, span: Span) -> Self {
+        self.spans.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.spans[0].span
+    }
+}
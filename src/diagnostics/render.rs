@@ -0,0 +1,132 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use super::{Diagnostic, Severity, SourceFile, Span};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+pub fn stderr_supports_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+pub struct Renderer<'a> {
+    name: &'a str,
+    source: &'a str,
+    files: &'a [SourceFile],
+    color: bool
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(name: &'a str, source: &'a str, color: bool) -> Self {
+        Self {name, source, files: &[], color}
+    }
+
+    // For a source with included files after the main one's text.
+    pub fn with_files(self, files: &'a [SourceFile]) -> Self {
+        Self {files, ..self}
+    }
+
+    // The file `span` is in, its text, and the span in that.
+    fn locate(&self, span: Span) -> (&'a str, &'a str, Span, Option<&'a SourceFile>) {
+        let main = self.files.first().map_or(self.source.len(), |file| file.span.start);
+        match self.files.iter().find(|file| file.span.start <= span.start && span.start < file.span.end) {
+            Some(file) => {
+                let local = Span::new(span.start - file.span.start, span.end.min(file.span.end) - file.span.start);
+                (&file.name, &self.source[file.span.start..file.span.end], local, Some(file))
+            }
+            None => (self.name, &self.source[..main], span, None),
+        }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let (label, style) = match diagnostic.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        let label = match diagnostic.code {
+            Some(code) => format!("{}[{}]", label, code),
+            None => label.to_string(),
+        };
+        let (primary, notes) = diagnostic.spans.split_first().unwrap();
+        let mut out = self.snippet(self.paint(style, &label), &diagnostic.message, primary.span, style, "^");
+        // The `#include`s and `import`s that brought the code in, innermost first.
+        let mut file = self.locate(primary.span).3;
+        while let Some(included) = file {
+            let how = if included.imported { "imported" } else { "included" };
+            out.push_str(&self.snippet(self.paint(BOLD, "note"), &format!("`{}` is {} here", included.name, how), included.included_at, BLUE, "-"));
+            file = self.locate(included.included_at).3;
+        }
+        for note in notes {
+            out.push_str(&self.snippet(self.paint(BOLD, "note"), &note.message, note.span, BLUE, "-"));
+        }
+        out
+    }
+
+    // A headline followed by the line `span` starts on, with the span underlined.
+    fn snippet(&self, label: String, message: &str, span: Span, style: &str, marker: &str) -> String {
+        let (name, source, span, _) = self.locate(span);
+        let (line, column) = span.line_column(source);
+
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let text = &source[line_start..line_end];
+
+        // Keep tabs in the padding so the carets line up with the snippet.
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline_end = span.end.clamp(start, line_end);
+        let width = source[start..underline_end].chars().count().max(1);
+
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = self.paint(BLUE, "|");
+
+        let mut out = String::new();
+        out.push_str(&format!("{}{}\n", label, self.paint(BOLD, &format!(": {}", message))));
+        out.push_str(&format!("{}{} {}:{}:{}\n", gutter, self.paint(BLUE, "-->"), name, line, column));
+        out.push_str(&format!("{} {}\n", gutter, bar));
+        out.push_str(&format!("{} {} {}\n", self.paint(BLUE, &number), bar, text));
+        out.push_str(&format!("{} {} {}{}\n", gutter, bar, padding, self.paint(style, &marker.repeat(width))));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn underlines_the_span_and_each_note() {
+        let source = "let x = 1;\n\tprint(x + y);\n";
+        let diagnostic = Diagnostic::error("undeclared variable `y`", Span::new(22, 23))
+            .with_code("E0301")
+            .with_note("`x` is declared here", Span::new(4, 5));
+        assert_eq!(Renderer::new("main.lang", source, false).render(&diagnostic), "\
+error[E0301]: undeclared variable `y`
+ --> main.lang:2:12
+  |
+2 | \tprint(x + y);
+  | \t          ^
+note: `x` is declared here
+ --> main.lang:1:5
+  |
+1 | let x = 1;
+  |     -
+");
+    }
+}
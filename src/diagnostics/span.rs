@@ -0,0 +1,63 @@
+// Byte offsets into the source, `end` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {start, end}
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    // The smallest span covering both.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    // 1-based line and column (in characters) of the start of the span.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        LineIndex::new(source).line_column(self.start)
+    }
+}
+
+// A file whose text `#include` or `import` put after the main file's in the source, at
+// `span`, with the directive or statement that brought it in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub name: String,
+    pub span: Span,
+    pub included_at: Span,
+    // Whether it's a module, which `import` brought in.
+    pub imported: bool
+}
+
+// Precomputed line starts for repeated offset lookups.
+pub struct LineIndex<'a> {
+    source: &'a str,
+    starts: Vec<usize>
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self {source, starts}
+    }
+
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.starts[line]..offset].chars().count() + 1;
+        (line + 1, column)
+    }
+}
@@ -0,0 +1,30 @@
+// Edit distance between two names in characters, where swapping two neighbours counts as one edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // Distances from each prefix of `a` to each prefix of `b`, two rows back to count swaps.
+    let mut previous: Vec<usize> = Vec::new();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut next = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            next[j] = (row[j - 1] + cost).min(row[j] + 1).min(next[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                next[j] = next[j].min(previous[j - 2] + 1);
+            }
+        }
+        previous = std::mem::replace(&mut row, next);
+    }
+    row[b.len()]
+}
+
+// The candidate whose name is closest to `name`, if it's within a third of the name's length.
+// Ties go to the first.
+pub fn most_similar<T>(name: &str, candidates: impl IntoIterator<Item = T>, name_of: impl Fn(&T) -> &str) -> Option<T> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(name, name_of(&candidate)), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
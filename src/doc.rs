@@ -0,0 +1,131 @@
+// `tiny-lang doc`: a summary of a program's functions, each with its signature and the
+// `///` comments before it, as Markdown or as HTML.
+
+use std::fmt::Write;
+
+use crate::diagnostics::LineIndex;
+use crate::highlight::escape_html;
+use crate::intern::Symbol;
+use crate::parser::ast::{Attribute, Statement};
+use crate::parser::comments::Comments;
+
+pub struct FunctionDoc {
+    pub name: Symbol,
+    pub parameters: Vec<Symbol>,
+    pub attributes: Vec<Attribute>,
+    pub line: usize,
+    pub doc: Option<String>
+}
+
+impl FunctionDoc {
+    pub fn signature(&self) -> String {
+        let parameters: Vec<&str> = self.parameters.iter().map(|parameter| parameter.as_str()).collect();
+        format!("fn {}({})", self.name, parameters.join(", "))
+    }
+
+    fn attributes(&self) -> String {
+        self.attributes.iter().map(|attribute| attribute.to_string()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+// The functions the program declares, in the order they're written.
+pub fn functions(source: &str, program: &[Statement], comments: &Comments) -> Vec<FunctionDoc> {
+    let lines = LineIndex::new(source);
+    program.iter()
+        .filter_map(|statement| match statement {
+            Statement::Function { name, parameters, attributes, span, .. } => Some(FunctionDoc {
+                name: *name,
+                parameters: parameters.clone(),
+                attributes: attributes.clone(),
+                line: lines.line_column(span.start).0,
+                doc: comments.doc(statement),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn to_markdown(title: &str, functions: &[FunctionDoc]) -> String {
+    let mut out = format!("# {}\n\n", title);
+    if functions.is_empty() {
+        out.push_str("No functions.\n");
+        return out;
+    }
+    for function in functions {
+        writeln!(out, "- `{}`", function.signature()).unwrap();
+    }
+    for function in functions {
+        write!(out, "\n## `{}`\n\nLine {}", function.signature(), function.line).unwrap();
+        if !function.attributes.is_empty() {
+            write!(out, ", `{}`", function.attributes()).unwrap();
+        }
+        out.push_str(".\n");
+        if let Some(doc) = &function.doc {
+            writeln!(out, "\n{}", doc).unwrap();
+        }
+    }
+    out
+}
+
+pub fn to_html(title: &str, functions: &[FunctionDoc]) -> String {
+    let mut out = format!("<h1>{}</h1>\n", escape_html(title));
+    if functions.is_empty() {
+        out.push_str("<p>No functions.</p>\n");
+        return out;
+    }
+    out.push_str("<ul>\n");
+    for function in functions {
+        writeln!(out, "<li><a href=\"#{}\"><code>{}</code></a></li>", function.name, escape_html(&function.signature())).unwrap();
+    }
+    out.push_str("</ul>\n");
+    for function in functions {
+        writeln!(out, "<h2 id=\"{}\"><code>{}</code></h2>", function.name, escape_html(&function.signature())).unwrap();
+        write!(out, "<p>Line {}", function.line).unwrap();
+        if !function.attributes.is_empty() {
+            write!(out, ", <code>{}</code>", function.attributes()).unwrap();
+        }
+        out.push_str(".</p>\n");
+        // A blank line in the comments starts a new paragraph.
+        for paragraph in function.doc.iter().flat_map(|doc| doc.split("\n\n")) {
+            writeln!(out, "<p>{}</p>", escape_html(paragraph.trim())).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{BorrowedToken, LexerOptions, Token, tokenize_with_options};
+    use crate::parser::comments::attach_comments;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn documents_functions_with_their_doc_comments() {
+        let source = "// not documentation\n/// Adds `a` and `b`.\n///\n/// Both are numbers.\n#[inline]\nfn add(a, b) {\n    return a + b;\n}\nx = 1;\n//// a rule\nfn f() {}\n";
+        let options = LexerOptions { preserve_trivia: true, ..LexerOptions::default() };
+        let tokens: Vec<Token> = tokenize_with_options(source, options).0.into_iter().map(BorrowedToken::into_owned).collect();
+        let program = parse_program(&mut Parser::new(tokens.clone())).unwrap();
+        let functions = functions(source, &program, &attach_comments(source, &tokens, &program));
+
+        assert_eq!(to_markdown("test.lang", &functions), "\
+# test.lang
+
+- `fn add(a, b)`
+- `fn f()`
+
+## `fn add(a, b)`
+
+Line 6, `#[inline]`.
+
+Adds `a` and `b`.
+
+Both are numbers.
+
+## `fn f()`
+
+Line 11.
+");
+        assert!(to_html("test.lang", &functions).contains("<p>Line 6, <code>#[inline]</code>.</p>\n<p>Adds `a` and `b`.</p>\n<p>Both are numbers.</p>\n"));
+    }
+}
@@ -0,0 +1,253 @@
+// Running tiny-lang from another program, as a scripting or expression language, without
+// going through the lexer, parser and checks one by one.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use crate::diagnostics::{Diagnostic, LintLevels, Severity, Span};
+use crate::interpreter::{HostFn, HostFunction, Interpreter, Overflow, RuntimeError, Value};
+use crate::lexer::tokenize;
+use crate::parser::ast::Statement;
+use crate::parser::error::ParseError;
+use crate::parser::parser::{Parser, parse_lone_expression, parse_program};
+use crate::passes::{OptLevel, PassManager};
+use crate::sema::{SemaError, check_assignments, check_reachable, check_shadowing, check_types, check_unused, resolve};
+use crate::stdlib::Capabilities;
+
+// How `run_str` runs a program.
+pub struct Config<'a> {
+    // Where the program prints; stdout unless set.
+    pub output: Box<dyn Write + 'a>,
+    // What `getc` and `readint` read; stdin unless set.
+    pub input: Box<dyn Read + 'a>,
+    // How much to optimize the program before running it.
+    pub level: OptLevel,
+    // Which warnings are reported, and which of them are errors.
+    pub lints: LintLevels,
+    // How many statements the program may run before it's stopped with
+    // `Error::FuelExhausted`, counting each one every time it runs; unlimited unless set.
+    pub fuel: Option<u64>,
+    // What integer arithmetic that overflows does; it wraps unless set.
+    pub overflow: Overflow,
+    // Where `rand` starts; the same every run unless set.
+    pub seed: i64,
+    // Whether `clock` and `sleep` go by a clock that only `sleep` moves, without really
+    // sleeping, as tests want; the real one unless set.
+    pub virtual_time: bool,
+    // What the program may reach beyond its input and output, such as files; nothing unless
+    // set, so an embedded program is hermetic.
+    pub capabilities: Capabilities,
+    // What `argc` and `argv` give the program, its name first; none unless set.
+    pub arguments: Vec<String>,
+    // Functions the program can call, by name.
+    hosts: Vec<(String, Rc<HostFunction>)>
+}
+
+impl Default for Config<'_> {
+    fn default() -> Self {
+        Config { output: Box::new(io::stdout()), input: Box::new(io::stdin()), level: OptLevel::default(), lints: LintLevels::default(), fuel: None, overflow: Overflow::default(), seed: 0, virtual_time: false, capabilities: Capabilities::default(), arguments: Vec::new(), hosts: Vec::new() }
+    }
+}
+
+impl<'a> Config<'a> {
+    // The default configuration, but printing to `output`, such as a `&mut Vec<u8>`.
+    pub fn with_output(output: impl Write + 'a) -> Self {
+        Config { output: Box::new(output), ..Config::default() }
+    }
+
+    // Makes `function` callable from the program as `name`, such as
+    // `config.register_fn("sqrt", |x: f64| x.sqrt())`. Its arguments are checked against
+    // its Rust argument types when it's called, and its result converted back.
+    pub fn register_fn<Args: 'static>(&mut self, name: &str, function: impl HostFn<Args>) -> &mut Self {
+        self.hosts.push((name.to_string(), Rc::new(HostFunction::new(function))));
+        self
+    }
+}
+
+// What's left once a program has run.
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    // The warnings the program was checked with, in source order.
+    pub warnings: Vec<Diagnostic>,
+    // Every global the program assigned, by name, as the program left it.
+    pub globals: BTreeMap<String, Value>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // The source didn't lex, parse or check, with everything that's wrong with it in source
+    // order.
+    Compile(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+    // The program used up its fuel before it finished, at the statement at `span`.
+    FuelExhausted { span: Span }
+}
+
+impl Error {
+    // The error as diagnostics, ready to be rendered against the source.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Compile(diagnostics) => diagnostics.clone(),
+            Error::Runtime(error) => vec![Diagnostic::from(error)],
+            Error::FuelExhausted { span } => vec![Diagnostic::from(&RuntimeError::FuelExhausted { span: *span })],
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Compile(diagnostics) => match diagnostics.first() {
+                Some(diagnostic) => write!(f, "{}", diagnostic.message),
+                None => write!(f, "the program doesn't compile"),
+            },
+            Error::Runtime(error) => write!(f, "{}", error),
+            Error::FuelExhausted { span } => write!(f, "{}", RuntimeError::FuelExhausted { span: *span }),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        match error {
+            RuntimeError::FuelExhausted { span } => Error::FuelExhausted { span },
+            error => Error::Runtime(error),
+        }
+    }
+}
+
+fn sorted(mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span().start);
+    diagnostics
+}
+
+// Lexes and parses `source` with `parse`, or returns what's wrong with it.
+fn parse<T>(source: &str, parse: fn(&mut Parser) -> Result<T, ParseError>) -> Result<T, Error> {
+    let (tokens, errors) = tokenize(source);
+    if !errors.is_empty() {
+        return Err(Error::Compile(sorted(errors.iter().map(Diagnostic::from).collect())));
+    }
+    parse(&mut Parser::new(tokens)).map_err(|error| Error::Compile(vec![Diagnostic::from(&error)]))
+}
+
+// Checks and runs a whole program, as `tiny-lang run` does.
+pub fn run_str(source: &str, config: &mut Config) -> Result<RunOutcome, Error> {
+    let program = parse(source, parse_program)?;
+    // Calls to the host's functions are to functions the program doesn't declare.
+    let undeclared = resolve(&program).1.into_iter().filter(|error| match error {
+        SemaError::UndeclaredFunction { name, .. } => !config.hosts.iter().any(|(host, _)| host == name.as_str()),
+        _ => false,
+    });
+    let mut diagnostics: Vec<Diagnostic> = undeclared.map(|error| Diagnostic::from(&error)).collect();
+    diagnostics.extend(check_types(&program).iter().map(Diagnostic::from));
+    let warnings = [check_assignments, check_unused, check_reachable, check_shadowing]
+        .iter()
+        .flat_map(|check| check(&program));
+    diagnostics.extend(warnings.map(|warning| Diagnostic::from(&warning)));
+    let diagnostics = sorted(diagnostics.into_iter().filter_map(|d| config.lints.apply(d)).collect());
+    if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+        return Err(Error::Compile(diagnostics));
+    }
+
+    let program = PassManager::with_level(config.level).run_ast(program);
+    let mut interpreter = Interpreter::with_output(&mut config.output).with_input(&mut config.input).with_overflow(config.overflow).with_seed(config.seed)
+        .with_capabilities(config.capabilities).with_arguments(config.arguments.clone());
+    if let Some(fuel) = config.fuel {
+        interpreter = interpreter.with_fuel(fuel);
+    }
+    if config.virtual_time {
+        interpreter = interpreter.with_virtual_time();
+    }
+    for (name, function) in &config.hosts {
+        interpreter.register_host(name, Rc::clone(function));
+    }
+    interpreter.run(&program)?;
+    let globals = interpreter.globals().map(|(name, value)| (name.to_string(), value.clone())).collect();
+    Ok(RunOutcome { warnings: diagnostics, globals })
+}
+
+// The value of a single expression such as `2 * (3 + 4) > 10`. It can't call functions,
+// and any variables in it read as 0.
+pub fn eval_expr(source: &str) -> Result<Value, Error> {
+    let expression = parse(source, parse_lone_expression)?;
+    let program = [Statement::Expression {span: expression.span(), expression}];
+    let errors = check_types(&program);
+    if !errors.is_empty() {
+        return Err(Error::Compile(sorted(errors.iter().map(Diagnostic::from).collect())));
+    }
+    let [Statement::Expression {expression, ..}] = &program else { unreachable!() };
+    Ok(Interpreter::with_output(io::sink()).evaluate(expression)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_a_program_and_keeps_its_globals() {
+        let mut output = Vec::new();
+        let outcome = run_str("total = 0;\nfor (i = 1; i <= 4; i = i + 1) total = total + i;\nprint(total, \"\\n\");\n", &mut Config::with_output(&mut output)).unwrap();
+        assert_eq!(output, b"10\n");
+        assert_eq!(outcome.globals["total"], Value::Integer(10));
+    }
+
+    #[test]
+    fn calls_host_functions() {
+        let mut output = Vec::new();
+        let mut config = Config::with_output(&mut output);
+        config.register_fn("sqrt", |x: f64| x.sqrt()).register_fn("shout", |s: String| s.to_uppercase());
+        run_str("print(sqrt(16), \" \", shout(\"hi\"));", &mut config).unwrap();
+        let error = run_str("print(sqrt(\"x\"));", &mut config).unwrap_err();
+        assert_eq!(error.to_string(), "argument 1 of `sqrt` must be a float, found string");
+        let error = run_str("print(sqrt(1, 2));", &mut config).unwrap_err();
+        assert_eq!(error.to_string(), "`sqrt` takes 1 argument but 2 were given");
+        drop(config);
+        assert_eq!(output, b"4.0 HI");
+    }
+
+    #[test]
+    fn bad_programs_are_errors_rather_than_panics() {
+        let mut config = Config::with_output(io::sink());
+        let error = run_str("a = [1, 2, 3];\nprint(a[5]);\n", &mut config).unwrap_err();
+        assert!(matches!(error, Error::Runtime(RuntimeError::IndexOutOfBounds { index: 5, length: 3, .. })), "{:?}", error);
+        let error = run_str("foo(1);\n", &mut config).unwrap_err();
+        assert_eq!(error.diagnostics()[0].code, Some("E0302"));
+        let error = run_str("fn f(a) { return a; }\nprint(f(1, 2));\n", &mut config).unwrap_err();
+        assert_eq!(error.diagnostics()[0].code, Some("E0307"));
+        // Through a function, the checker can't tell what type a value is.
+        let error = run_str("fn first(a) { return a[0]; }\nprint(first(7));\n", &mut config).unwrap_err();
+        assert_eq!(error.to_string(), "expected an array, found an integer");
+        let error = run_str("fn first(a) { return a[0]; }\nputc(first([\"x\"]));\n", &mut config).unwrap_err();
+        assert_eq!(error.to_string(), "expected an integer character code, found a string");
+        assert_eq!(eval_expr("nope(1)").unwrap_err().diagnostics()[0].code, Some("E0411"));
+    }
+
+    #[test]
+    fn deep_recursion_on_a_small_stack_is_an_error() {
+        // A spawned thread's default 2 MiB doesn't hold `MAX_CALL_DEPTH` calls in a debug build.
+        let run = || {
+            let result = run_str("fn down(n) { return down(n - 1); }\nprint(down(10));\n", &mut Config::with_output(io::sink()));
+            matches!(result, Err(Error::Runtime(RuntimeError::StackOverflow { .. })))
+        };
+        assert!(std::thread::Builder::new().stack_size(2 << 20).spawn(run).unwrap().join().unwrap());
+    }
+
+    #[test]
+    fn stops_a_program_that_runs_out_of_fuel() {
+        let mut config = Config::with_output(io::sink());
+        config.fuel = Some(1000);
+        assert!(matches!(run_str("while (1);", &mut config), Err(Error::FuelExhausted {..})));
+        assert!(run_str("for (i = 0; i < 10; i = i + 1) print(i);", &mut config).is_ok());
+    }
+
+    #[test]
+    fn evaluates_an_expression() {
+        assert_eq!(eval_expr("2 * (3 + 4) > 10"), Ok(Value::Bool(true)));
+        assert!(matches!(eval_expr("1 +"), Err(Error::Compile(_))));
+        assert!(matches!(eval_expr("1; 2"), Err(Error::Compile(_))));
+    }
+}
@@ -0,0 +1,94 @@
+// How `fmt` lays a program out, from a `tinyfmt.toml` in the file's directory or one above
+// it. Only the TOML the options need is understood: `key = value` lines with integers and
+// strings, and `#` comments.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE: &str = "tinyfmt.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BraceStyle {
+    // `if (x) {`
+    #[default]
+    SameLine,
+    // `if (x)`, then `{` on a line of its own.
+    NextLine
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatConfig {
+    // Spaces per level of indentation.
+    pub indent_width: usize,
+    pub brace_style: BraceStyle,
+    // An `if`'s or a loop's condition that would make its line longer than this is broken
+    // before each `&&` or `||` at its top.
+    pub max_width: usize
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        FormatConfig { indent_width: 4, brace_style: BraceStyle::SameLine, max_width: 100 }
+    }
+}
+
+impl FormatConfig {
+    pub fn parse(text: &str) -> Result<FormatConfig, String> {
+        let mut config = FormatConfig::default();
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", i + 1, message);
+            let line = line.split_once('#').map_or(line, |(before, _)| before).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("expected `key = value`, found `{}`", line)));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let width = |least: usize| match value.parse::<usize>() {
+                Ok(width) if width >= least => Ok(width),
+                _ => Err(error(format!("`{}` must be a whole number of at least {}, not `{}`", key, least, value))),
+            };
+            match key {
+                "indent_width" => config.indent_width = width(1)?,
+                "max_width" => config.max_width = width(20)?,
+                "brace_style" => config.brace_style = match value.trim_matches('"') {
+                    "same_line" if value.starts_with('"') => BraceStyle::SameLine,
+                    "next_line" if value.starts_with('"') => BraceStyle::NextLine,
+                    _ => return Err(error(format!("`brace_style` must be \"same_line\" or \"next_line\", not `{}`", value))),
+                },
+                _ => return Err(error(format!("unknown option `{}`", key))),
+            }
+        }
+        Ok(config)
+    }
+
+    // The nearest `tinyfmt.toml` to `file`, in its directory or the ones above it.
+    pub fn find(file: &Path) -> Option<PathBuf> {
+        let directory = file.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let directory = directory.canonicalize().ok()?;
+        directory.ancestors().map(|directory| directory.join(CONFIG_FILE)).find(|path| path.is_file())
+    }
+
+    // The configuration that applies to `file`: that of the nearest `tinyfmt.toml`, or the
+    // default if there's none.
+    pub fn for_file(file: &Path) -> Result<FormatConfig, String> {
+        let Some(path) = FormatConfig::find(file) else { return Ok(FormatConfig::default()) };
+        let text = fs::read_to_string(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        FormatConfig::parse(&text).map_err(|message| format!("{}: {}", path.display(), message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_options() {
+        let config = FormatConfig::parse("# house style\nindent_width = 2\nbrace_style = \"next_line\" # Allman\n\n").unwrap();
+        assert_eq!(config, FormatConfig { indent_width: 2, brace_style: BraceStyle::NextLine, ..FormatConfig::default() });
+        assert_eq!(FormatConfig::parse("max_width = 10").unwrap_err(), "line 1: `max_width` must be a whole number of at least 20, not `10`");
+        assert_eq!(FormatConfig::parse("x\ntabs = 1").unwrap_err(), "line 1: expected `key = value`, found `x`");
+        assert_eq!(FormatConfig::parse("tabs = 1").unwrap_err(), "line 1: unknown option `tabs`");
+    }
+}
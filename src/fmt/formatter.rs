@@ -0,0 +1,357 @@
+use crate::fmt::config::{BraceStyle, FormatConfig};
+use crate::lexer::{TokenKind, quote_char, quote_string};
+use crate::parser::ast::{AssignmentForm, Expression, Statement};
+use crate::parser::comments::{Comment, Comments};
+use crate::parser::parser::{BindingPower, infix_bp};
+
+fn precedence(expr: &Expression) -> BindingPower {
+    match expr {
+        Expression::Binary {operator, ..} => infix_bp(operator).map_or(BindingPower::Primary, |(left, _)| left),
+        Expression::Assignment {form: AssignmentForm::Prefix, ..} => BindingPower::Unary,
+        Expression::Assignment {..} => BindingPower::Assignment,
+        Expression::Conditional {..} => BindingPower::Conditional,
+        Expression::Unary {..} => BindingPower::Unary,
+        _ => BindingPower::Primary,
+    }
+}
+
+fn parenthesize(expr: &Expression, needed: bool, comments: &Comments) -> String {
+    if needed {
+        format!("({})", expression(expr, comments))
+    } else {
+        expression(expr, comments)
+    }
+}
+
+pub fn format_expression(expr: &Expression) -> String {
+    expression(expr, &Comments::default())
+}
+
+// `comments` has the string literals that are printed as they were written.
+fn expression(expr: &Expression, comments: &Comments) -> String {
+    match expr {
+        Expression::Number(n, _) => n.to_string(),
+        Expression::Char(c, _) => quote_char(*c),
+        Expression::Float(x, _) => format!("{:?}", x),
+        Expression::Boolean(b, _) => b.to_string(),
+        Expression::String(s, span) => comments.literal(*span).map_or_else(|| quote_string(s), str::to_string),
+        Expression::Symbol(name, _) => name.to_string(),
+        Expression::Unary {operator, operand, ..} => {
+            // `- -1` must not collapse into `--1`, nor `+ ++x` into `+++x`.
+            let needed = precedence(operand) < BindingPower::Unary
+                || matches!(operand.as_ref(), Expression::Number(n, _) if *n < 0)
+                || matches!(operand.as_ref(), Expression::Unary {..} | Expression::Assignment {form: AssignmentForm::Prefix, ..});
+            format!("{}{}", operator, parenthesize(operand, needed, comments))
+        }
+        Expression::Binary {left, operator, right, ..} => {
+            let bp = precedence(expr);
+            // Operators are left associative, so an equal-precedence right operand needs parens.
+            let left = parenthesize(left, precedence(left) < bp, comments);
+            let right = parenthesize(right, precedence(right) <= bp, comments);
+            format!("{} {} {}", left, operator, right)
+        }
+        // What was written as `x += e` or `++x` is printed that way again.
+        Expression::Assignment {assignee, value, form: AssignmentForm::Compound, ..}
+            if let Expression::Binary {operator, right, ..} = value.as_ref() =>
+        {
+            format!("{} {}= {}", expression(assignee, comments), operator, expression(right, comments))
+        }
+        Expression::Assignment {assignee, value, form: AssignmentForm::Prefix, ..}
+            if let Expression::Binary {operator, ..} = value.as_ref() =>
+        {
+            format!("{}{}{}", operator, operator, expression(assignee, comments))
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            format!("{} = {}", expression(assignee, comments), expression(value, comments))
+        }
+        Expression::Call {callee, arguments, ..} => {
+            let arguments: Vec<String> = arguments.iter().map(|e| expression(e, comments)).collect();
+            format!("{}({})", callee, arguments.join(", "))
+        }
+        Expression::Array {elements, ..} => {
+            let elements: Vec<String> = elements.iter().map(|e| expression(e, comments)).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            // Right associative: only a nested conditional in the condition needs parens.
+            let condition = parenthesize(condition, precedence(condition) <= BindingPower::Conditional, comments);
+            let else_value = parenthesize(else_value, precedence(else_value) < BindingPower::Conditional, comments);
+            format!("{} ? {} : {}", condition, expression(then_value, comments), else_value)
+        }
+        Expression::Index {target, index, ..} => {
+            format!("{}[{}]", parenthesize(target, precedence(target) < BindingPower::Call, comments), expression(index, comments))
+        }
+    }
+}
+
+struct Formatter<'c> {
+    out: String,
+    depth: usize,
+    comments: &'c Comments,
+    config: &'c FormatConfig
+}
+
+impl Formatter<'_> {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&" ".repeat(self.depth * self.config.indent_width));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // A statement's head, which may run over several lines, and the `{` of its body.
+    fn open(&mut self, head: Vec<String>) {
+        let (last, rest) = head.split_last().expect("a statement's head has a line");
+        rest.iter().for_each(|line| self.line(line));
+        match self.config.brace_style {
+            BraceStyle::SameLine => self.line(&format!("{} {{", last)),
+            BraceStyle::NextLine => {
+                self.line(last);
+                self.line("{");
+            }
+        }
+    }
+
+    // The `}` of a body and the head of what follows it, such as `else`.
+    fn reopen(&mut self, mut head: Vec<String>) {
+        match self.config.brace_style {
+            BraceStyle::SameLine => head[0] = format!("}} {}", head[0]),
+            BraceStyle::NextLine => self.line("}"),
+        }
+        self.open(head);
+    }
+
+    // `prefix`, the condition and `suffix`, allowing for a ` {` after them. One that's too
+    // long for the line is broken before each `&&` or `||` at the top of the condition,
+    // the lines after the first indented once more.
+    fn condition(&self, prefix: &str, condition: &Expression, suffix: &str) -> Vec<String> {
+        let text = format!("{}{}{}", prefix, expression(condition, self.comments), suffix);
+        if self.depth * self.config.indent_width + text.chars().count() + 2 <= self.config.max_width {
+            return vec![text];
+        }
+        let Expression::Binary {operator: operator @ (TokenKind::OpAnd | TokenKind::OpOr), ..} = condition else {
+            return vec![text];
+        };
+        let mut operands = Vec::new();
+        chain(condition, operator, &mut operands);
+        let bp = precedence(condition);
+        let mut lines = vec![format!("{}{}", prefix, parenthesize(operands[0], precedence(operands[0]) < bp, self.comments))];
+        for operand in &operands[1..] {
+            let continuation = " ".repeat(self.config.indent_width);
+            lines.push(format!("{}{} {}", continuation, operator, parenthesize(operand, precedence(operand) <= bp, self.comments)));
+        }
+        lines.last_mut().unwrap().push_str(suffix);
+        lines
+    }
+
+    // Comments on lines of their own, at the current indentation.
+    fn comment_lines(&mut self, comments: &[Comment]) {
+        for comment in comments {
+            self.line(&comment.text);
+        }
+    }
+
+    // Comments at the end of the line just written.
+    fn trailing(&mut self, comments: &[Comment]) {
+        if comments.is_empty() {
+            return;
+        }
+        self.out.pop();
+        for comment in comments {
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+        }
+        self.out.push('\n');
+    }
+
+    // Bodies of `if`/`while` are always braced.
+    fn body(&mut self, stmt: &Statement) {
+        self.depth += 1;
+        match stmt {
+            Statement::Block {body, ..} => {
+                body.iter().for_each(|s| self.statement(s));
+                self.comment_lines(self.comments.closing(stmt));
+            }
+            _ => self.statement(stmt),
+        }
+        self.depth -= 1;
+    }
+
+    fn if_chain(&mut self, then_branch: &Statement, else_branch: &Option<Box<Statement>>) {
+        self.body(then_branch);
+        match else_branch.as_deref() {
+            Some(Statement::If {condition, then_branch, else_branch, ..}) => {
+                self.reopen(self.condition("else if (", condition, ")"));
+                self.if_chain(then_branch, else_branch);
+            }
+            Some(else_branch) => {
+                self.reopen(vec!["else".to_string()]);
+                self.body(else_branch);
+                self.line("}");
+            }
+            None => self.line("}"),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        self.comment_lines(self.comments.leading(stmt));
+        match stmt {
+            Statement::Block {body, ..} if body.is_empty() && self.comments.closing(stmt).is_empty() => self.line(";"),
+            Statement::Block {..} => {
+                self.line("{");
+                self.body(stmt);
+                self.line("}");
+            }
+            Statement::Expression {expression: e, ..} => self.line(&format!("{};", expression(e, self.comments))),
+            Statement::Print {arguments, ..} => {
+                let arguments: Vec<String> = arguments.iter().map(|e| expression(e, self.comments)).collect();
+                self.line(&format!("print({});", arguments.join(", ")));
+            }
+            Statement::Putc {expression: e, ..} => self.line(&format!("putc({});", expression(e, self.comments))),
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                self.open(self.condition("if (", condition, ")"));
+                self.if_chain(then_branch, else_branch);
+            }
+            Statement::While {condition, body, ..} => {
+                self.open(self.condition("while (", condition, ")"));
+                self.body(body);
+                self.line("}");
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                self.open(vec!["do".to_string()]);
+                self.body(body);
+                for line in self.condition("} while (", condition, ");") {
+                    self.line(&line);
+                }
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                let clause = |expr: &Option<Expression>| expr.as_ref().map_or(String::new(), |e| expression(e, self.comments));
+                let condition = condition.as_ref().map_or(String::new(), |c| format!(" {}", expression(c, self.comments)));
+                let step = step.as_ref().map_or(String::new(), |s| format!(" {}", expression(s, self.comments)));
+                self.open(vec![format!("for ({};{};{})", clause(initializer), condition, step)]);
+                self.body(body);
+                self.line("}");
+            }
+            Statement::Function {name, parameters, body, attributes, ..} => {
+                for attribute in attributes {
+                    self.line(&attribute.to_string());
+                }
+                self.open(vec![format!("fn {}({})", name, parameters.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", "))]);
+                self.depth += 1;
+                body.iter().for_each(|s| self.statement(s));
+                self.comment_lines(self.comments.closing(stmt));
+                self.depth -= 1;
+                self.line("}");
+            }
+            Statement::Return {value: Some(value), ..} => self.line(&format!("return {};", expression(value, self.comments))),
+            Statement::Return {value: None, ..} => self.line("return;"),
+            Statement::Import {module, ..} => self.line(&format!("import {};", module)),
+            Statement::Attributed {attributes, statement, ..} => {
+                for attribute in attributes {
+                    self.line(&attribute.to_string());
+                }
+                self.statement(statement);
+            }
+        }
+        self.trailing(self.comments.trailing(stmt));
+    }
+}
+
+// The operands of a chain of `operator`s, such as `a && b && c`, left to right.
+fn chain<'e>(expr: &'e Expression, operator: &TokenKind, operands: &mut Vec<&'e Expression>) {
+    match expr {
+        Expression::Binary {left, operator: op, right, ..} if op == operator => {
+            chain(left, operator, operands);
+            operands.push(right);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+pub fn format_program(program: &[Statement]) -> String {
+    format_program_with(program, &Comments::default(), &FormatConfig::default())
+}
+
+// Formats the program as `config` says, keeping the comments attached to it, each where it
+// was relative to its statement.
+pub fn format_program_with(program: &[Statement], comments: &Comments, config: &FormatConfig) -> String {
+    let mut formatter = Formatter { out: String::new(), depth: 0, comments, config };
+    for stmt in program {
+        formatter.statement(stmt);
+    }
+    formatter.comment_lines(comments.end());
+    formatter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{BorrowedToken, LexerOptions, Token, tokenize_with_options};
+    use crate::parser::comments::attach_comments;
+    use crate::parser::parser::{Parser, parse_program};
+
+    fn format(source: &str, config: &FormatConfig) -> Option<String> {
+        let options = LexerOptions { preserve_trivia: true, ..LexerOptions::default() };
+        let tokens: Vec<Token> = tokenize_with_options(source, options).0.into_iter().map(BorrowedToken::into_owned).collect();
+        let program = parse_program(&mut Parser::new(tokens.clone())).ok()?;
+        Some(format_program_with(&program, &attach_comments(source, &tokens, &program), config))
+    }
+
+    #[test]
+    fn formatting_again_changes_nothing() {
+        let configs = [
+            FormatConfig::default(),
+            FormatConfig { indent_width: 2, brace_style: BraceStyle::NextLine, max_width: 20 },
+        ];
+        let long = "// wrapped\nif (alpha > 1 && beta < 2 && (gamma || delta)) print(1); else if (q) { } // else\ndo x = x + 1; while (x < 100000 && y);\n";
+        let mut sources = vec![long.to_string()];
+        for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests")).unwrap() {
+            sources.push(std::fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+        for config in &configs {
+            // Some of the tests are of the lexer, and don't parse.
+            for source in &sources {
+                let Some(once) = format(source, config) else { continue };
+                assert_eq!(format(&once, config).as_ref(), Some(&once), "formatting again changed:\n{}", once);
+            }
+        }
+        assert_eq!(format(long, &configs[1]).unwrap(), "\
+// wrapped
+if (alpha > 1
+  && beta < 2
+  && (gamma || delta))
+{
+  print(1);
+}
+else if (q)
+{
+} // else
+do
+{
+  x = x + 1;
+} while (x < 100000
+  && y);
+");
+    }
+
+    #[test]
+    fn compound_assignments_are_kept() {
+        let source = "x += 1;\ns[i] *= 2 + y;\nfor (i = 0; i < 3; ++i) --a[i];\nprint(-(--x), x = y -= 2);\nx = x + 1;\n";
+        assert_eq!(format(source, &FormatConfig::default()).unwrap(), "\
+x += 1;
+s[i] *= 2 + y;
+for (i = 0; i < 3; ++i) {
+    --a[i];
+}
+print(-(--x), x = y -= 2);
+x = x + 1;
+");
+    }
+
+    #[test]
+    fn raw_and_triple_quoted_strings_stay_as_written() {
+        let source = "print(r\"raw\\n\", \"not\\n\", r\"\" + r\"{\\}\");\n";
+        assert_eq!(format(source, &FormatConfig::default()).unwrap(), source);
+        let source = "if (x) {\n    s = \"\"\"first \"line\"\n  second\\t\"\"\" + \"\";\n}\n";
+        assert_eq!(format(source, &FormatConfig::default()).unwrap(), source);
+    }
+}
@@ -0,0 +1,5 @@
+mod config;
+mod formatter;
+
+pub use config::{BraceStyle, CONFIG_FILE, FormatConfig};
+pub use formatter::{format_expression, format_program, format_program_with};
@@ -0,0 +1,206 @@
+// Classifies the source for highlighting, token by token, keeping comments. Names are
+// classified by what they refer to when the program parses, and by what comes around them
+// when it doesn't, so a file that's being edited still highlights.
+
+mod render;
+
+pub use render::{THEMES, Theme, to_ansi, to_html};
+pub(crate) use render::escape_html;
+
+use crate::diagnostics::Span;
+use crate::lexer::{BorrowedToken, LexerOptions, Token, TokenKind, tokenize_with_options};
+use crate::parser::parser::{Parser, parse_program};
+use crate::preprocess::preprocess;
+use crate::sema::{DeclarationKind, resolve};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightClass {
+    Keyword,
+    Operator,
+    // Parentheses, braces, brackets, `;` and `,`.
+    Punctuation,
+    Number,
+    // Strings and characters.
+    String,
+    Boolean,
+    Comment,
+    // `#[inline]` and the like, all of it.
+    Attribute,
+    // A function's name where it's declared, and where it's called.
+    FunctionDefinition,
+    Function,
+    // A parameter where it's declared, and where it's used.
+    ParameterDefinition,
+    Parameter,
+    // A variable at the assignment that first binds it, and everywhere else.
+    VariableDefinition,
+    Variable
+}
+
+impl HighlightClass {
+    pub const ALL: [HighlightClass; 14] = [
+        HighlightClass::Keyword,
+        HighlightClass::Operator,
+        HighlightClass::Punctuation,
+        HighlightClass::Number,
+        HighlightClass::String,
+        HighlightClass::Boolean,
+        HighlightClass::Comment,
+        HighlightClass::Attribute,
+        HighlightClass::FunctionDefinition,
+        HighlightClass::Function,
+        HighlightClass::ParameterDefinition,
+        HighlightClass::Parameter,
+        HighlightClass::VariableDefinition,
+        HighlightClass::Variable,
+    ];
+
+    // The class's name in kebab case, such as for a CSS class.
+    pub fn name(self) -> &'static str {
+        match self {
+            HighlightClass::Keyword => "keyword",
+            HighlightClass::Operator => "operator",
+            HighlightClass::Punctuation => "punctuation",
+            HighlightClass::Number => "number",
+            HighlightClass::String => "string",
+            HighlightClass::Boolean => "boolean",
+            HighlightClass::Comment => "comment",
+            HighlightClass::Attribute => "attribute",
+            HighlightClass::FunctionDefinition => "function-definition",
+            HighlightClass::Function => "function",
+            HighlightClass::ParameterDefinition => "parameter-definition",
+            HighlightClass::Parameter => "parameter",
+            HighlightClass::VariableDefinition => "variable-definition",
+            HighlightClass::Variable => "variable",
+        }
+    }
+
+    // The LSP semantic token type for the class, which marks definitions with the
+    // `declaration` modifier instead.
+    pub fn token_type(self) -> &'static str {
+        match self {
+            HighlightClass::Punctuation => "operator",
+            HighlightClass::Boolean => "keyword",
+            HighlightClass::Attribute => "decorator",
+            HighlightClass::FunctionDefinition | HighlightClass::Function => "function",
+            HighlightClass::ParameterDefinition | HighlightClass::Parameter => "parameter",
+            HighlightClass::VariableDefinition | HighlightClass::Variable => "variable",
+            class => class.name(),
+        }
+    }
+
+    pub fn is_definition(self) -> bool {
+        matches!(self, HighlightClass::FunctionDefinition | HighlightClass::ParameterDefinition | HighlightClass::VariableDefinition)
+    }
+}
+
+// The class of a token other than a name, from its kind alone.
+fn lexical_class<S>(kind: &TokenKind<S>) -> Option<HighlightClass> {
+    Some(match kind {
+        TokenKind::EndOfInput | TokenKind::Whitespace(_) | TokenKind::Indentifier(_) => return None,
+        TokenKind::KeywordTrue | TokenKind::KeywordFalse => HighlightClass::Boolean,
+        TokenKind::KeywordIf | TokenKind::KeywordElse | TokenKind::KeywordWhile | TokenKind::KeywordFor
+        | TokenKind::KeywordDo | TokenKind::KeywordPrint | TokenKind::KeywordPutc | TokenKind::KeywordFn
+        | TokenKind::KeywordReturn | TokenKind::KeywordImport => HighlightClass::Keyword,
+        TokenKind::OpenParen | TokenKind::CloseParen | TokenKind::OpenBrace | TokenKind::CloseBrace
+        | TokenKind::OpenBracket | TokenKind::CloseBracket | TokenKind::Semicolon | TokenKind::Comma => {
+            HighlightClass::Punctuation
+        }
+        TokenKind::Integer(_) | TokenKind::Float(_) => HighlightClass::Number,
+        TokenKind::String(_) | TokenKind::Char(_) => HighlightClass::String,
+        TokenKind::Comment(_) | TokenKind::DocComment(_) => HighlightClass::Comment,
+        // `#` only starts an attribute or a directive.
+        TokenKind::Hash => HighlightClass::Attribute,
+        _ => HighlightClass::Operator,
+    })
+}
+
+// Each token in `source` worth highlighting, in order, with its class. Whitespace isn't, and
+// nor is anything the lexer couldn't make a token of.
+pub fn highlight(source: &str) -> Vec<(Span, HighlightClass)> {
+    let (tokens, _) = tokenize_with_options(source, LexerOptions { preserve_trivia: true, ..LexerOptions::default() });
+    let code: Vec<&BorrowedToken> = tokens.iter().filter(|token| !token.kind.is_trivia()).collect();
+
+    // What each name refers to, if the program parses with none of its `#if` names defined.
+    let owned: Vec<Token> = code.iter().map(|&token| token.clone().into_owned()).collect();
+    let parsed = preprocess(source, owned, &[]).ok().and_then(|tokens| parse_program(&mut Parser::new(tokens)).ok());
+    let table = parsed.map(|program| resolve(&program).0);
+
+    let mut classes = Vec::new();
+    let mut attribute = false;
+    // Where the line of the `#if`, `#else` or `#endif` being highlighted ends.
+    let mut directive = 0;
+    for (i, token) in code.iter().enumerate() {
+        let previous = |back: usize| i.checked_sub(back).map(|j| &code[j].kind);
+        let next = code.get(i + 1).map(|token| &token.kind);
+        let class = match &token.kind {
+            _ if attribute || token.span.start < directive => HighlightClass::Attribute,
+            TokenKind::Indentifier(_) => {
+                let declaration = table.as_ref().and_then(|table| table.declaration_at(token.span));
+                let defines = declaration.is_some_and(|declaration| declaration.span == token.span);
+                match declaration.map(|declaration| declaration.kind) {
+                    _ if matches!(previous(1), Some(TokenKind::KeywordFn)) => HighlightClass::FunctionDefinition,
+                    _ if in_parameters(&code, i) => HighlightClass::ParameterDefinition,
+                    Some(DeclarationKind::Function | DeclarationKind::Builtin) => HighlightClass::Function,
+                    Some(DeclarationKind::Parameter) => HighlightClass::Parameter,
+                    Some(_) if defines => HighlightClass::VariableDefinition,
+                    Some(_) => HighlightClass::Variable,
+                    None if next == Some(&TokenKind::OpenParen) => HighlightClass::Function,
+                    None => HighlightClass::Variable,
+                }
+            }
+            kind => match lexical_class(kind) {
+                Some(class) => class,
+                None => continue,
+            },
+        };
+        // An attribute runs from its `#` to the `]` that closes it.
+        if token.kind == TokenKind::Hash && next != Some(&TokenKind::OpenBracket) {
+            directive = source[token.span.start..].find('\n').map_or(source.len(), |end| token.span.start + end);
+        }
+        attribute = match &token.kind {
+            TokenKind::Hash => next == Some(&TokenKind::OpenBracket),
+            TokenKind::CloseBracket => false,
+            _ => attribute,
+        };
+        classes.push((token.span, class));
+    }
+
+    // Comments go back in among the other tokens.
+    classes.extend(tokens.iter().filter(|token| matches!(token.kind, TokenKind::Comment(_) | TokenKind::DocComment(_))).map(|token| (token.span, HighlightClass::Comment)));
+    classes.sort_by_key(|(span, _)| span.start);
+    classes
+}
+
+// Whether the name at `i` is in a function's parameter list: after `fn`, its name and `(`,
+// with only names and commas in between.
+fn in_parameters(code: &[&BorrowedToken], i: usize) -> bool {
+    let mut j = i;
+    while j > 0 && matches!(code[j - 1].kind, TokenKind::Indentifier(_) | TokenKind::Comma) {
+        j -= 1;
+    }
+    j >= 3 && code[j - 1].kind == TokenKind::OpenParen && matches!(code[j - 2].kind, TokenKind::Indentifier(_))
+        && code[j - 3].kind == TokenKind::KeywordFn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_names_by_their_role() {
+        let source = "// sum\n#[inline]\nfn add(a, b) { c = a + b; return c; }\nx = add(1, \"s\");\nprint(x, y());\n";
+        let classes: Vec<(&str, HighlightClass)> = highlight(source).into_iter()
+            .map(|(span, class)| (&source[span.start..span.end], class))
+            .filter(|(_, class)| !matches!(class, HighlightClass::Punctuation | HighlightClass::Operator))
+            .collect();
+        use HighlightClass::*;
+        assert_eq!(classes, [
+            ("// sum", Comment), ("#", Attribute), ("[", Attribute), ("inline", Attribute), ("]", Attribute),
+            ("fn", Keyword), ("add", FunctionDefinition), ("a", ParameterDefinition), ("b", ParameterDefinition),
+            ("c", VariableDefinition), ("a", Parameter), ("b", Parameter), ("return", Keyword), ("c", Variable),
+            ("x", VariableDefinition), ("add", Function), ("1", Number), ("\"s\"", String),
+            ("print", Keyword), ("x", Variable), ("y", Function),
+        ]);
+    }
+}
@@ -0,0 +1,218 @@
+// Source rendered in color from `highlight`, as an HTML fragment with its own stylesheet or
+// with ANSI escapes for a terminal, in one of a few built-in themes.
+
+use std::fmt::Write;
+
+use super::{HighlightClass, highlight};
+
+type Rgb = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub color: Option<Rgb>,
+    pub bold: bool,
+    pub italic: bool
+}
+
+const fn color(color: Rgb) -> Style {
+    Style { color: Some(color), bold: false, italic: false }
+}
+
+const fn bold(color: Rgb) -> Style {
+    Style { color: Some(color), bold: true, italic: false }
+}
+
+const fn italic(color: Rgb) -> Style {
+    Style { color: Some(color), bold: false, italic: true }
+}
+
+const PLAIN: Style = Style { color: None, bold: false, italic: false };
+const BOLD: Style = Style { color: None, bold: true, italic: false };
+const ITALIC: Style = Style { color: None, bold: false, italic: true };
+
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Rgb,
+    pub foreground: Rgb,
+    // Classes that aren't here are in the foreground color.
+    styles: &'static [(HighlightClass, Style)]
+}
+
+pub const THEMES: &[Theme] = &[
+    Theme {
+        name: "light",
+        background: (0xfa, 0xfa, 0xfa),
+        foreground: (0x38, 0x3a, 0x42),
+        styles: &[
+            (HighlightClass::Keyword, bold((0xa6, 0x26, 0xa4))),
+            (HighlightClass::Operator, color((0x01, 0x84, 0xbc))),
+            (HighlightClass::Number, color((0x98, 0x68, 0x01))),
+            (HighlightClass::String, color((0x50, 0xa1, 0x4f))),
+            (HighlightClass::Boolean, color((0x98, 0x68, 0x01))),
+            (HighlightClass::Comment, italic((0xa0, 0xa1, 0xa7))),
+            (HighlightClass::Attribute, color((0xc1, 0x84, 0x01))),
+            (HighlightClass::FunctionDefinition, bold((0x40, 0x78, 0xf2))),
+            (HighlightClass::Function, color((0x40, 0x78, 0xf2))),
+            (HighlightClass::ParameterDefinition, bold((0xe4, 0x56, 0x49))),
+            (HighlightClass::Parameter, color((0xe4, 0x56, 0x49))),
+            (HighlightClass::VariableDefinition, BOLD),
+        ],
+    },
+    Theme {
+        name: "dark",
+        background: (0x28, 0x2c, 0x34),
+        foreground: (0xab, 0xb2, 0xbf),
+        styles: &[
+            (HighlightClass::Keyword, bold((0xc6, 0x78, 0xdd))),
+            (HighlightClass::Operator, color((0x56, 0xb6, 0xc2))),
+            (HighlightClass::Number, color((0xd1, 0x9a, 0x66))),
+            (HighlightClass::String, color((0x98, 0xc3, 0x79))),
+            (HighlightClass::Boolean, color((0xd1, 0x9a, 0x66))),
+            (HighlightClass::Comment, italic((0x7f, 0x84, 0x8e))),
+            (HighlightClass::Attribute, color((0xe5, 0xc0, 0x7b))),
+            (HighlightClass::FunctionDefinition, bold((0x61, 0xaf, 0xef))),
+            (HighlightClass::Function, color((0x61, 0xaf, 0xef))),
+            (HighlightClass::ParameterDefinition, bold((0xe0, 0x6c, 0x75))),
+            (HighlightClass::Parameter, color((0xe0, 0x6c, 0x75))),
+            (HighlightClass::VariableDefinition, BOLD),
+        ],
+    },
+    // For slides and print: black on white, with keywords and definitions bold.
+    Theme {
+        name: "mono",
+        background: (0xff, 0xff, 0xff),
+        foreground: (0x00, 0x00, 0x00),
+        styles: &[
+            (HighlightClass::Keyword, BOLD),
+            (HighlightClass::Comment, ITALIC),
+            (HighlightClass::FunctionDefinition, BOLD),
+            (HighlightClass::ParameterDefinition, BOLD),
+            (HighlightClass::VariableDefinition, BOLD),
+        ],
+    },
+];
+
+impl Theme {
+    pub fn named(name: &str) -> Option<&'static Theme> {
+        THEMES.iter().find(|theme| theme.name == name)
+    }
+
+    pub fn style(&self, class: HighlightClass) -> Style {
+        self.styles.iter().find(|&&(styled, _)| styled == class).map_or(PLAIN, |&(_, style)| style)
+    }
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+// Calls `piece` with each stretch of `source` in turn and its class, if it has one.
+fn pieces(source: &str, mut piece: impl FnMut(&str, Option<HighlightClass>)) {
+    let mut end = 0;
+    for (span, class) in highlight(source) {
+        if span.start > end {
+            piece(&source[end..span.start], None);
+        }
+        piece(&source[span.start..span.end], Some(class));
+        end = span.end;
+    }
+    if end < source.len() {
+        piece(&source[end..], None);
+    }
+}
+
+// A `<style>` for the theme and the source in a `<pre>`, each token in a `<span>` whose
+// class is its `HighlightClass::name`.
+pub fn to_html(source: &str, theme: &Theme) -> String {
+    let mut out = String::from("<style>\n");
+    writeln!(out, ".tiny-lang {{ background: {}; color: {}; padding: 1em; }}", hex(theme.background), hex(theme.foreground)).unwrap();
+    for class in HighlightClass::ALL {
+        let style = theme.style(class);
+        let mut rules = String::new();
+        if let Some(color) = style.color {
+            write!(rules, " color: {};", hex(color)).unwrap();
+        }
+        if style.bold {
+            rules.push_str(" font-weight: bold;");
+        }
+        if style.italic {
+            rules.push_str(" font-style: italic;");
+        }
+        if !rules.is_empty() {
+            writeln!(out, ".tiny-lang .{} {{{} }}", class.name(), rules).unwrap();
+        }
+    }
+    out.push_str("</style>\n<pre class=\"tiny-lang\"><code>");
+    pieces(source, |text, class| match class {
+        Some(class) => {
+            write!(out, "<span class=\"{}\">", class.name()).unwrap();
+            out.push_str(&escape_html(text));
+            out.push_str("</span>");
+        }
+        None => out.push_str(&escape_html(text)),
+    });
+    out.push_str("</code></pre>\n");
+    out
+}
+
+// The source with 24-bit color escapes for a terminal. The terminal's own colors are left
+// for the background and for anything the theme doesn't color, which is all of it in
+// `mono`, so a theme reads on a light terminal or a dark one.
+pub fn to_ansi(source: &str, theme: &Theme) -> String {
+    let mut out = String::new();
+    pieces(source, |text, class| {
+        let style = class.map_or(PLAIN, |class| theme.style(class));
+        let mut codes = Vec::new();
+        if style.bold {
+            codes.push("1".to_string());
+        }
+        if style.italic {
+            codes.push("3".to_string());
+        }
+        if let Some((r, g, b)) = style.color {
+            codes.push(format!("38;2;{};{};{}", r, g, b));
+        }
+        if codes.is_empty() {
+            out.push_str(text);
+        } else {
+            write!(out, "\x1b[{}m{}\x1b[0m", codes.join(";"), text).unwrap();
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_classes_as_spans_and_escapes() {
+        let theme = Theme::named("dark").unwrap();
+        let html = to_html("if (a < 1) print(\"&\"); // x\n", theme);
+        assert!(html.starts_with("<style>\n.tiny-lang { background: #282c34; color: #abb2bf; padding: 1em; }\n"));
+        assert!(html.contains(".tiny-lang .keyword { color: #c678dd; font-weight: bold; }\n"));
+        assert!(html.ends_with(concat!(
+            "<pre class=\"tiny-lang\"><code><span class=\"keyword\">if</span> <span class=\"punctuation\">(</span>",
+            "<span class=\"variable\">a</span> <span class=\"operator\">&lt;</span> <span class=\"number\">1</span>",
+            "<span class=\"punctuation\">)</span> <span class=\"keyword\">print</span><span class=\"punctuation\">(</span>",
+            "<span class=\"string\">&quot;&amp;&quot;</span><span class=\"punctuation\">)</span>",
+            "<span class=\"punctuation\">;</span> <span class=\"comment\">// x</span>\n</code></pre>\n",
+        )));
+        let ansi = to_ansi("x = f(1); // y", Theme::named("mono").unwrap());
+        assert_eq!(ansi, "\x1b[1mx\x1b[0m = f(1); \x1b[3m// y\x1b[0m");
+    }
+}
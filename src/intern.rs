@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+// An interned identifier. Equal names always get the same symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// Names are leaked so they can be handed out as `&'static str`; a program only has so many.
+#[derive(Default)]
+pub struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &'static str {
+        self.names[symbol.0 as usize]
+    }
+}
+
+// Shared by the whole process so symbols can be compared and printed anywhere.
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        INTERNER.lock().unwrap().intern(name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().resolve(self)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Symbols are only meaningful within one process, so they're serialized as their names.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_names_get_one_symbol() {
+        let mut interner = Interner::default();
+        let first = interner.intern("count");
+        let other = interner.intern("total");
+        assert_eq!(interner.intern("count"), first);
+        assert_ne!(first, other);
+        assert_eq!(interner.resolve(other), "total");
+        assert_eq!(Symbol::from("count").to_string(), "count");
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::sema::argument_count;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    // An operator applied to operands of types it doesn't support, e.g. `"a" + 1`.
+    TypeMismatch { operator: TokenKind, left: &'static str, right: &'static str, span: Span },
+    InvalidOperand { operator: TokenKind, operand: &'static str, span: Span },
+    // A call to a host function with the wrong arguments, or that failed.
+    HostCall { name: Symbol, message: String, span: Span },
+    // The program ran more statements than its fuel allowed; `span` is the one it stopped at.
+    FuelExhausted { span: Span },
+    // Calls nested deeper than the interpreter allows. `chain` is each function on the stack
+    // with the call that entered it, outermost first, ending with the call at `span`.
+    StackOverflow { chain: Vec<(Symbol, Span)>, span: Span },
+    // An integer `/` or `%` by zero.
+    DivisionByZero { operator: TokenKind, span: Span },
+    // Integer arithmetic whose result doesn't fit in an i64, when overflow traps.
+    Overflow { operator: TokenKind, span: Span },
+    // A hook stopped the program before the statement at `span`, such as a debugger quitting.
+    Stopped { span: Span },
+    // An index below zero or past the end of the array.
+    IndexOutOfBounds { index: i64, length: usize, span: Span },
+    // Anything else of the wrong type, such as indexing a number.
+    WrongType { expected: &'static str, found: &'static str, span: Span },
+    // A call to a function that no `fn` declares and that isn't a builtin or a host function.
+    UndefinedFunction { name: Symbol, span: Span },
+    // A call to a function with more or fewer arguments than it has parameters.
+    ArgumentCount { name: Symbol, expected: usize, found: usize, span: Span }
+}
+
+// What goes wrong indexing an array of `length` elements at `index`, which the VM reports
+// the same way.
+pub(crate) fn out_of_bounds(index: i64, length: usize) -> String {
+    format!("index {} is out of bounds for an array of length {}", index, length)
+}
+
+// The same for a value of the wrong type.
+pub(crate) fn wrong_type(expected: &str, found: &str) -> String {
+    let article = if found.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+    format!("expected {}, found {} {}", expected, article, found)
+}
+
+// The same for overflowing.
+pub(crate) fn overflow(operator: &TokenKind) -> String {
+    format!("integer overflow in `{}`", operator)
+}
+
+// What goes wrong dividing by zero with `/` or `%`, which the VM reports the same way.
+pub(crate) fn division_by_zero(operator: &TokenKind) -> &'static str {
+    match operator {
+        TokenKind::OpMod => "cannot take the remainder of a division by zero",
+        _ => "cannot divide by zero",
+    }
+}
+
+// What goes wrong when calls nest too deep, the last one in `chain` being the one too many,
+// which the VM and the JIT report the same way.
+pub(crate) fn nested_too_deep(chain: &[Symbol]) -> String {
+    format!("calls are nested more than {} deep: {}", chain.len() - 1, call_chain(chain))
+}
+
+// The functions in `chain` as `a -> b -> c`, with a function calling itself shown once with
+// a count, and the middle of a long chain left out.
+fn call_chain(chain: &[Symbol]) -> String {
+    let mut runs: Vec<(Symbol, usize)> = Vec::new();
+    for &name in chain {
+        match runs.last_mut() {
+            Some((last, count)) if *last == name => *count += 1,
+            _ => runs.push((name, 1)),
+        }
+    }
+    let shown = |&(name, count): &(Symbol, usize)| {
+        if count == 1 { format!("`{}`", name) } else { format!("`{}` ({} times)", name, count) }
+    };
+    if runs.len() <= 8 {
+        return runs.iter().map(shown).collect::<Vec<_>>().join(" -> ");
+    }
+    let (first, last) = (&runs[..4], &runs[runs.len() - 4..]);
+    let first: Vec<String> = first.iter().map(shown).collect();
+    let last: Vec<String> = last.iter().map(shown).collect();
+    format!("{} -> ... -> {}", first.join(" -> "), last.join(" -> "))
+}
+
+impl RuntimeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::TypeMismatch { .. } => "E0401",
+            RuntimeError::InvalidOperand { .. } => "E0402",
+            RuntimeError::HostCall { .. } => "E0403",
+            RuntimeError::FuelExhausted { .. } => "E0404",
+            RuntimeError::StackOverflow { .. } => "E0405",
+            RuntimeError::DivisionByZero { .. } => "E0406",
+            RuntimeError::Overflow { .. } => "E0407",
+            RuntimeError::Stopped { .. } => "E0408",
+            RuntimeError::IndexOutOfBounds { .. } => "E0409",
+            RuntimeError::WrongType { .. } => "E0410",
+            RuntimeError::UndefinedFunction { .. } => "E0411",
+            RuntimeError::ArgumentCount { .. } => "E0412",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::TypeMismatch { span, .. } | RuntimeError::InvalidOperand { span, .. }
+            | RuntimeError::HostCall { span, .. } | RuntimeError::FuelExhausted { span }
+            | RuntimeError::StackOverflow { span, .. } | RuntimeError::DivisionByZero { span, .. }
+            | RuntimeError::Overflow { span, .. } | RuntimeError::Stopped { span }
+            | RuntimeError::IndexOutOfBounds { span, .. } | RuntimeError::WrongType { span, .. }
+            | RuntimeError::UndefinedFunction { span, .. } | RuntimeError::ArgumentCount { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::TypeMismatch { operator, left, right, .. } => {
+                write!(f, "cannot apply `{}` to {} and {}", operator, left, right)
+            }
+            RuntimeError::InvalidOperand { operator, operand, .. } => {
+                write!(f, "cannot apply `{}` to {}", operator, operand)
+            }
+            RuntimeError::HostCall { message, .. } => write!(f, "{}", message),
+            RuntimeError::FuelExhausted { .. } => write!(f, "the program ran out of fuel"),
+            RuntimeError::StackOverflow { chain, .. } => {
+                let names: Vec<Symbol> = chain.iter().map(|&(name, _)| name).collect();
+                write!(f, "{}", nested_too_deep(&names))
+            }
+            RuntimeError::DivisionByZero { operator, .. } => write!(f, "{}", division_by_zero(operator)),
+            RuntimeError::Overflow { operator, .. } => write!(f, "{}", overflow(operator)),
+            RuntimeError::Stopped { .. } => write!(f, "the program was stopped"),
+            RuntimeError::IndexOutOfBounds { index, length, .. } => write!(f, "{}", out_of_bounds(*index, *length)),
+            RuntimeError::WrongType { expected, found, .. } => write!(f, "{}", wrong_type(expected, found)),
+            RuntimeError::UndefinedFunction { name, .. } => write!(f, "call to undefined function `{}`", name),
+            RuntimeError::ArgumentCount { name, expected, found, .. } => write!(f, "{}", argument_count(*name, *expected, *found)),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        let mut diagnostic = Diagnostic::error(error.to_string(), error.span()).with_code(error.code());
+        // Where the recursion goes round: the calls that led to the one that overflowed,
+        // each place once, innermost first.
+        if let RuntimeError::StackOverflow { chain, span } = error {
+            let mut seen = vec![*span];
+            for &(name, call) in chain.iter().rev() {
+                if !seen.contains(&call) && seen.len() <= 3 {
+                    seen.push(call);
+                    diagnostic = diagnostic.with_note(format!("`{}` is called here", name), call);
+                }
+            }
+        }
+        diagnostic
+    }
+}
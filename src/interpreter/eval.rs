@@ -0,0 +1,597 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::stdlib::{self, Capabilities, Context};
+use super::error::RuntimeError;
+use super::hook::{Action, Hook, Scope};
+use super::host::{HostError, HostFn, HostFunction};
+use super::overflow::Overflow;
+use super::stack;
+use super::value::Value;
+
+type Array = Rc<RefCell<Vec<Value>>>;
+
+// How deeply calls may nest by default: well within what an 8 MiB stack, the main thread's
+// usual size, holds in a release build. A thread with less stack than that takes stops
+// sooner, once what's left of it gets low.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+fn article(noun: &str) -> &'static str {
+    if noun.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" }
+}
+
+struct Function {
+    parameters: Vec<Symbol>,
+    locals: Vec<Symbol>,
+    body: Vec<Statement>
+}
+
+enum Flow {
+    Normal,
+    Return(Value)
+}
+
+pub struct Interpreter<'a> {
+    globals: HashMap<Symbol, Value>,
+    functions: HashMap<Symbol, Rc<Function>>,
+    frames: Vec<HashMap<Symbol, Value>>,
+    // The function each frame belongs to and where it was called from, outermost first.
+    calls: Vec<(Symbol, Span)>,
+    // How many calls may be nested before the program stops with a stack overflow.
+    max_depth: usize,
+    // Functions the embedding program provides.
+    hosts: HashMap<Symbol, Rc<HostFunction>>,
+    // Where `print` and `putc` write.
+    output: Box<dyn Write + 'a>,
+    // What builtins such as `getc` read from.
+    context: Context<'a>,
+    // How many more statements may run, if that's limited.
+    fuel: Option<u64>,
+    // What integer arithmetic does when it overflows.
+    overflow: Overflow,
+    // What's watching the program run, if anything.
+    hook: Option<Box<dyn Hook + 'a>>
+}
+
+impl Default for Interpreter<'_> {
+    fn default() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    // An interpreter whose program prints to stdout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // An interpreter whose program prints to `output` instead, such as a `&mut Vec<u8>` to
+    // capture what it prints. As with stdout, failing to write is a panic.
+    pub fn with_output(output: impl Write + 'a) -> Self {
+        Interpreter {
+            globals: HashMap::new(),
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            calls: Vec::new(),
+            max_depth: MAX_CALL_DEPTH,
+            hosts: HashMap::new(),
+            output: Box::new(output),
+            context: Context::default(),
+            fuel: None,
+            overflow: Overflow::default(),
+            hook: None,
+        }
+    }
+
+    // Has `getc` and `readint` read `input` instead of stdin.
+    pub fn with_input(mut self, input: impl Read + 'a) -> Self {
+        self.context = Context::with_input(input);
+        self
+    }
+
+    // Starts `rand` from `seed` rather than the same seed every run.
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.context.seed(seed);
+        self
+    }
+
+    // Has `clock` and `sleep` go by a clock that only `sleep` moves, without really
+    // sleeping, so that a program that times itself does the same thing every run.
+    pub fn with_virtual_time(mut self) -> Self {
+        self.context.virtual_time();
+        self
+    }
+
+    // Gives the program `arguments` for `argc` and `argv`, the first being its name.
+    pub fn with_arguments(mut self, arguments: Vec<String>) -> Self {
+        self.context.arguments(arguments);
+        self
+    }
+
+    // Lets the program use what `capabilities` allows, such as files; by default it can only
+    // read its input and print.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.context.allow(capabilities);
+        self
+    }
+
+    // Calls `hook` before each statement runs.
+    pub fn with_hook(mut self, hook: impl Hook + 'a) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    // Makes integer arithmetic that overflows behave as `overflow` says, rather than wrap.
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    // Lets calls nest `depth` deep instead of `MAX_CALL_DEPTH`. Each call takes some of the
+    // Rust stack, so a much deeper limit needs a thread with a bigger stack than the default.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    // Lets the program run at most `fuel` statements, counting each statement every time it
+    // runs, before it stops with `RuntimeError::FuelExhausted`. Without a limit, a program
+    // such as `while (1);` never returns.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    // Makes `function` callable from tiny-lang code as `name`. Functions the program declares
+    // take precedence over it, and it takes precedence over builtins such as `len`.
+    pub fn register_fn<Args: 'static>(&mut self, name: &str, function: impl HostFn<Args>) {
+        self.register_host(name, Rc::new(HostFunction::new(function)));
+    }
+
+    pub(crate) fn register_host(&mut self, name: &str, function: Rc<HostFunction>) {
+        self.hosts.insert(Symbol::from(name), function);
+    }
+
+    pub fn run(&mut self, program: &[Statement]) -> Result<(), RuntimeError> {
+        // Functions can be called before the statement that declares them. Checking rejects
+        // two with one name, but like the VM, a program run without it calls the first.
+        for stmt in program.iter().rev() {
+            if let Statement::Function {..} = stmt {
+                self.execute(stmt)?;
+            }
+        }
+        for stmt in program.iter().filter(|stmt| !matches!(stmt, Statement::Function {..})) {
+            self.execute(stmt)?;
+        }
+        Ok(())
+    }
+
+    // The globals assigned so far, in no particular order.
+    pub fn globals(&self) -> impl Iterator<Item = (Symbol, &Value)> {
+        self.globals.iter().map(|(&name, value)| (name, value))
+    }
+
+    pub fn execute(&mut self, stmt: &Statement) -> Result<(), RuntimeError> {
+        self.statement(stmt)?;
+        Ok(())
+    }
+
+    fn lookup(&self, name: Symbol) -> Value {
+        if let Some(frame) = self.frames.last()
+            && let Some(value) = frame.get(&name) {
+            return value.clone();
+        }
+        // Variables that were never assigned read as zero.
+        self.globals.get(&name).cloned().unwrap_or(Value::Integer(0))
+    }
+
+    fn assign(&mut self, name: Symbol, value: Value) {
+        if let Some(frame) = self.frames.last_mut()
+            && let Some(slot) = frame.get_mut(&name) {
+            *slot = value;
+            return;
+        }
+        self.globals.insert(name, value);
+    }
+
+    fn builtin(&mut self, callee: Symbol, arguments: &[Expression], span: Span) -> Result<Option<Value>, RuntimeError> {
+        match callee.as_str() {
+            "len" => {
+                if arguments.len() != 1 {
+                    return Err(RuntimeError::ArgumentCount { name: callee, expected: 1, found: arguments.len(), span });
+                }
+                let n = match self.evaluate(&arguments[0])? {
+                    Value::Array(elements) => elements.borrow().len(),
+                    Value::String(s) => s.chars().count(),
+                    v => return Err(RuntimeError::WrongType { expected: "an array or a string", found: v.type_name(), span: arguments[0].span() }),
+                };
+                Ok(Some(Value::Integer(n as i64)))
+            }
+            name => {
+                let Some(builtin) = stdlib::builtin(name) else { return Ok(None) };
+                let error = |message: String| RuntimeError::HostCall { name: callee, message, span };
+                if arguments.len() != builtin.arity() {
+                    let plural = if builtin.arity() == 1 { "" } else { "s" };
+                    return Err(error(format!("`{}` takes {} argument{} but {} were given", callee, builtin.arity(), plural, arguments.len())));
+                }
+                let values = arguments.iter().map(|argument| self.evaluate(argument)).collect::<Result<Vec<_>, _>>()?;
+                (builtin.call)(&mut self.context, &values).map(Some).map_err(error)
+            }
+        }
+    }
+
+    // Checks `index` against the array's bounds.
+    fn element(&mut self, target: &Expression, index: &Expression) -> Result<(Array, usize), RuntimeError> {
+        let elements = match self.evaluate(target)? {
+            Value::Array(elements) => elements,
+            v => return Err(RuntimeError::WrongType { expected: "an array", found: v.type_name(), span: target.span() }),
+        };
+        let span = index.span();
+        let index = match self.evaluate(index)? {
+            v if let Some(n) = v.integer() => n,
+            v => return Err(RuntimeError::WrongType { expected: "an integer index", found: v.type_name(), span }),
+        };
+        let length = elements.borrow().len();
+        if index < 0 || index as usize >= length {
+            return Err(RuntimeError::IndexOutOfBounds { index, length, span });
+        }
+        Ok((elements, index as usize))
+    }
+
+    // Checks the arguments of a call to a host function against its signature.
+    fn call_host(&mut self, host: &HostFunction, callee: Symbol, arguments: &[Expression], span: Span) -> Result<Value, RuntimeError> {
+        let error = |message: String| RuntimeError::HostCall { name: callee, message, span };
+        if arguments.len() != host.arity() {
+            let plural = if host.arity() == 1 { "" } else { "s" };
+            return Err(error(format!("`{}` takes {} argument{} but {} were given", callee, host.arity(), plural, arguments.len())));
+        }
+        let values = arguments.iter().map(|argument| self.evaluate(argument)).collect::<Result<Vec<_>, _>>()?;
+        host.call(&values).map_err(|failure| match failure {
+            HostError::Argument { index, expected, found } => {
+                error(format!("argument {} of `{}` must be {} {}, found {}", index + 1, callee, article(expected), expected, found))
+            }
+            HostError::Failed(message) => error(format!("`{}` failed: {}", callee, message)),
+        })
+    }
+
+    fn call(&mut self, callee: Symbol, arguments: &[Expression], span: Span) -> Result<Value, RuntimeError> {
+        let function = match self.functions.get(&callee) {
+            Some(function) => Rc::clone(function),
+            None if let Some(host) = self.hosts.get(&callee) => {
+                return self.call_host(&Rc::clone(host), callee, arguments, span);
+            }
+            // User functions take precedence over builtins with the same name.
+            None => match self.builtin(callee, arguments, span)? {
+                Some(value) => return Ok(value),
+                None => return Err(RuntimeError::UndefinedFunction { name: callee, span }),
+            },
+        };
+        if arguments.len() != function.parameters.len() {
+            return Err(RuntimeError::ArgumentCount { name: callee, expected: function.parameters.len(), found: arguments.len(), span });
+        }
+
+        let mut frame: HashMap<Symbol, Value> = function.locals.iter()
+            .map(|&name| (name, Value::Integer(0)))
+            .collect();
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            let value = self.evaluate(argument)?;
+            frame.insert(*parameter, value);
+        }
+
+        if self.calls.len() >= self.max_depth || stack::remaining().is_some_and(|left| left < stack::RESERVE) {
+            let mut chain = self.calls.clone();
+            chain.push((callee, span));
+            return Err(RuntimeError::StackOverflow { chain, span });
+        }
+        self.frames.push(frame);
+        self.calls.push((callee, span));
+        let result = self.body(&function.body);
+        self.calls.pop();
+        self.frames.pop();
+        match result? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal => Ok(Value::Integer(0)),
+        }
+    }
+
+    fn body(&mut self, body: &[Statement]) -> Result<Flow, RuntimeError> {
+        for stmt in body {
+            if let flow @ Flow::Return(_) = self.statement(stmt)? {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<Flow, RuntimeError> {
+        if let Some(fuel) = &mut self.fuel {
+            *fuel = fuel.checked_sub(1).ok_or(RuntimeError::FuelExhausted { span: stmt.span() })?;
+        }
+        if let Some(hook) = &mut self.hook {
+            let scope = Scope { globals: &self.globals, frames: &self.frames, calls: &self.calls };
+            if hook.statement(stmt, &scope) == Action::Stop {
+                return Err(RuntimeError::Stopped { span: stmt.span() });
+            }
+        }
+        match stmt {
+            Statement::Block {body, ..} => return self.body(body),
+            Statement::Attributed {statement, ..} => return self.statement(statement),
+            Statement::Expression {expression, ..} => {
+                self.evaluate(expression)?;
+            }
+            Statement::Print {arguments, ..} => {
+                for arg in arguments {
+                    let value = self.evaluate(arg)?;
+                    write!(self.output, "{}", value).expect("failed to write the program's output");
+                }
+            }
+            Statement::Putc {expression, ..} => {
+                let code = match self.evaluate(expression)? {
+                    v if let Some(n) = v.integer() => n,
+                    v => return Err(RuntimeError::WrongType { expected: "an integer character code", found: v.type_name(), span: expression.span() }),
+                };
+                let ch = char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+                write!(self.output, "{}", ch).expect("failed to write the program's output");
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                if self.evaluate(condition)?.is_truthy() {
+                    return self.statement(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.statement(else_branch);
+                }
+            }
+            Statement::While {condition, body, ..} => {
+                while self.evaluate(condition)?.is_truthy() {
+                    if let flow @ Flow::Return(_) = self.statement(body)? {
+                        return Ok(flow);
+                    }
+                }
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                loop {
+                    if let flow @ Flow::Return(_) = self.statement(body)? {
+                        return Ok(flow);
+                    }
+                    if !self.evaluate(condition)?.is_truthy() {
+                        break;
+                    }
+                }
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                if let Some(initializer) = initializer {
+                    self.evaluate(initializer)?;
+                }
+                loop {
+                    // A missing condition loops forever.
+                    if let Some(condition) = condition
+                        && !self.evaluate(condition)?.is_truthy() {
+                        break;
+                    }
+                    if let flow @ Flow::Return(_) = self.statement(body)? {
+                        return Ok(flow);
+                    }
+                    if let Some(step) = step {
+                        self.evaluate(step)?;
+                    }
+                }
+            }
+            Statement::Function {name, parameters, body, ..} => {
+                let function = Function {
+                    parameters: parameters.clone(),
+                    locals: function_locals(parameters, body),
+                    body: body.clone(),
+                };
+                self.functions.insert(*name, Rc::new(function));
+            }
+            Statement::Return {value, ..} => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Integer(0),
+                };
+                return Ok(Flow::Return(value));
+            }
+            // Linking has put the module in its place.
+            Statement::Import {..} => {}
+        }
+        Ok(Flow::Normal)
+    }
+
+    pub fn evaluate(&mut self, expr: &Expression) -> Result<Value, RuntimeError> {
+        let value = match expr {
+            Expression::Number(n, _) => Value::Integer(*n),
+            Expression::Char(c, _) => Value::Integer(*c as i64),
+            Expression::Float(x, _) => Value::Float(*x),
+            Expression::Boolean(b, _) => Value::Bool(*b),
+            Expression::String(s, _) => Value::String(s.clone()),
+            Expression::Symbol(name, _) => self.lookup(*name),
+            Expression::Unary {operator: TokenKind::OpNot, operand, ..} => Value::from(!self.evaluate(operand)?.is_truthy()),
+            Expression::Unary {operator, operand, span} => {
+                let value = self.evaluate(operand)?;
+                let result = match operator {
+                    TokenKind::OpSubtract if let Some(n) = value.integer() => {
+                        let n = self.overflow.negate(n).ok_or(RuntimeError::Overflow { operator: operator.clone(), span: *span })?;
+                        Some(Value::Integer(n))
+                    }
+                    TokenKind::OpSubtract => value.negate(),
+                    TokenKind::OpBitNot => value.bitwise(&Value::Integer(0), |a, _| !a),
+                    // `+x` is `x + 0`, which keeps the operand's numeric type.
+                    TokenKind::OpAdd => value.arithmetic(&Value::Integer(0), |a, b| a + b, |a, b| a + b),
+                    t => panic!("Unsupported unary operator: {:?}", t),
+                };
+                result.ok_or_else(|| RuntimeError::InvalidOperand {
+                    operator: operator.clone(),
+                    operand: value.type_name(),
+                    span: *span,
+                })?
+            }
+            Expression::Binary {left, operator: TokenKind::OpAnd, right, ..} => {
+                Value::from(self.evaluate(left)?.is_truthy() && self.evaluate(right)?.is_truthy())
+            }
+            Expression::Binary {left, operator: TokenKind::OpOr, right, ..} => {
+                Value::from(self.evaluate(left)?.is_truthy() || self.evaluate(right)?.is_truthy())
+            }
+            Expression::Binary {left, operator, right, span} => {
+                let l = self.evaluate(left)?;
+                let r = self.evaluate(right)?;
+                if matches!(operator, TokenKind::OpDivide | TokenKind::OpMod) && l.divides_by_zero(&r) {
+                    return Err(RuntimeError::DivisionByZero { operator: operator.clone(), span: *span });
+                }
+                let result = match operator {
+                    TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpMultiply | TokenKind::OpDivide | TokenKind::OpMod
+                        if let Some((a, b)) = l.integers(&r) =>
+                    {
+                        let n = self.overflow.binary(operator, a, b)
+                            .ok_or(RuntimeError::Overflow { operator: operator.clone(), span: *span })?;
+                        Some(Value::Integer(n))
+                    }
+                    TokenKind::OpAdd => l.concat(&r).or_else(|| l.arithmetic(&r, |a, b| a + b, |a, b| a + b)),
+                    TokenKind::OpSubtract => l.arithmetic(&r, |a, b| a - b, |a, b| a - b),
+                    TokenKind::OpMultiply => l.arithmetic(&r, |a, b| a * b, |a, b| a * b),
+                    TokenKind::OpDivide => l.arithmetic(&r, |a, b| a / b, |a, b| a / b),
+                    TokenKind::OpMod => l.arithmetic(&r, |a, b| a % b, |a, b| a % b),
+                    TokenKind::OpBitAnd => l.bitwise(&r, |a, b| a & b),
+                    TokenKind::OpBitOr => l.bitwise(&r, |a, b| a | b),
+                    TokenKind::OpBitXor => l.bitwise(&r, |a, b| a ^ b),
+                    // Shift counts wrap modulo 64 rather than overflowing.
+                    TokenKind::OpShiftLeft => l.bitwise(&r, |a, b| a.wrapping_shl(b as u32)),
+                    TokenKind::OpShiftRight => l.bitwise(&r, |a, b| a.wrapping_shr(b as u32)),
+                    TokenKind::OpLess => l.compare(&r, i64::lt, f64::lt),
+                    TokenKind::OpLessEqual => l.compare(&r, i64::le, f64::le),
+                    TokenKind::OpGreater => l.compare(&r, i64::gt, f64::gt),
+                    TokenKind::OpGreaterEqual => l.compare(&r, i64::ge, f64::ge),
+                    TokenKind::OpEqual => l.equals(&r).map(Value::from),
+                    TokenKind::OpNotEqual => l.equals(&r).map(|equal| Value::from(!equal)),
+                    t => panic!("Unsupported binary operator: {:?}", t),
+                };
+                result.ok_or_else(|| RuntimeError::TypeMismatch {
+                    operator: operator.clone(),
+                    left: l.type_name(),
+                    right: r.type_name(),
+                    span: *span,
+                })?
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                let value = self.evaluate(value)?;
+                match assignee.as_ref() {
+                    Expression::Symbol(name, _) => self.assign(*name, value.clone()),
+                    Expression::Index {target, index, ..} => {
+                        let (elements, index) = self.element(target, index)?;
+                        elements.borrow_mut()[index] = value.clone();
+                    }
+                    e => panic!("Invalid assignment target: {:?}", e),
+                }
+                value
+            }
+            Expression::Call {callee, arguments, span} => self.call(*callee, arguments, *span)?,
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.evaluate(then_value)?
+                } else {
+                    self.evaluate(else_value)?
+                }
+            }
+            Expression::Array {elements, ..} => {
+                let elements = elements.iter().map(|e| self.evaluate(e)).collect::<Result<_, _>>()?;
+                Value::array(elements)
+            }
+            Expression::Index {target, index, ..} => {
+                let (elements, index) = self.element(target, index)?;
+                elements.borrow()[index].clone()
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::{Interpreter, Overflow, RuntimeError, Value};
+
+    #[test]
+    fn output_goes_to_the_sink() {
+        let (tokens, errors) = tokenize("fn twice(n) { return n * 2; }\nprint(\"twice 21 is \", twice(21), \"\\n\");\nputc(955);\n");
+        assert!(errors.is_empty());
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let mut output = Vec::new();
+        Interpreter::with_output(&mut output).run(&program).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "twice 21 is 42\nλ");
+    }
+
+    #[test]
+    fn the_first_of_two_functions_with_one_name_is_called() {
+        let (tokens, _) = tokenize("print(f());\nfn f() { return 1; }\nprint(f());\nfn f() { return 2; }\nprint(f());\n");
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let mut output = Vec::new();
+        Interpreter::with_output(&mut output).run(&program).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "111");
+    }
+
+    #[test]
+    fn dividing_by_zero_is_an_error() {
+        let (tokens, _) = tokenize("x = 0;\nprint(1.0 / x);\nprint(7 % x);\n");
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let error = Interpreter::with_output(std::io::sink()).run(&program).unwrap_err();
+        assert!(matches!(error, RuntimeError::DivisionByZero { .. }));
+        assert_eq!(error.span().start, 31);
+    }
+
+    #[test]
+    fn overflow_wraps_saturates_or_traps() {
+        let (tokens, _) = tokenize("x = 9223372036854775807 + 1;\n");
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let run = |overflow| {
+            let mut interpreter = Interpreter::with_output(std::io::sink()).with_overflow(overflow);
+            interpreter.run(&program).map(|()| interpreter.globals().next().unwrap().1.clone())
+        };
+        assert_eq!(run(Overflow::Wrapping), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(run(Overflow::Saturating), Ok(Value::Integer(i64::MAX)));
+        assert!(matches!(run(Overflow::Trapping), Err(RuntimeError::Overflow { .. })));
+    }
+
+    #[test]
+    fn deep_recursion_is_an_error() {
+        let (tokens, _) = tokenize("fn down(n) { return down(n - 1); }\nprint(down(10));\n");
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let error = Interpreter::with_output(std::io::sink()).with_max_depth(50).run(&program).unwrap_err();
+        let RuntimeError::StackOverflow { chain, .. } = &error else { panic!("{:?}", error) };
+        assert_eq!(chain.len(), 51);
+        assert_eq!(error.to_string(), "calls are nested more than 50 deep: `down` (51 times)");
+    }
+
+    #[test]
+    fn bad_indexing_is_an_error() {
+        let run = |source: &str| {
+            let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+            Interpreter::with_output(std::io::sink()).run(&program).unwrap_err()
+        };
+        let error = run("a = [1, 2, 3];\nprint(a[5]);\n");
+        assert_eq!(error, RuntimeError::IndexOutOfBounds { index: 5, length: 3, span: error.span() });
+        assert_eq!((error.code(), error.span().start), ("E0409", 23));
+        assert_eq!(run("a = [1];\na[-1] = 2;\n").code(), "E0409");
+        let error = run("n = 4;\nprint(n[0]);\n");
+        assert_eq!(error.to_string(), "expected an array, found an integer");
+        assert_eq!((error.code(), error.span().start), ("E0410", 13));
+        assert_eq!(run("a = [1];\nprint(a[\"x\"]);\n").to_string(), "expected an integer index, found a string");
+    }
+
+    #[test]
+    fn bad_calls_are_errors() {
+        let run = |source: &str| {
+            let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+            Interpreter::with_output(std::io::sink()).run(&program).unwrap_err()
+        };
+        let error = run("foo(1);\n");
+        assert_eq!((error.code(), error.to_string().as_str()), ("E0411", "call to undefined function `foo`"));
+        let error = run("fn f(a) { return a; }\nprint(f(1, 2));\n");
+        assert_eq!((error.code(), error.to_string().as_str()), ("E0412", "`f` takes 1 argument but 2 were given"));
+        assert_eq!(run("print(len());\n").code(), "E0412");
+    }
+}
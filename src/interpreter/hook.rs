@@ -0,0 +1,101 @@
+// Hooks let a tool watch a program as the interpreter runs it, such as a debugger deciding
+// whether to stop before each statement.
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use crate::parser::ast::Statement;
+use super::value::Value;
+
+// What the program can see where it is: the variables of the function it's in, if any, and
+// the globals, with the calls that got it there and their variables.
+pub struct Scope<'s> {
+    pub(super) globals: &'s HashMap<Symbol, Value>,
+    // The variables of each call, outermost first.
+    pub(super) frames: &'s [HashMap<Symbol, Value>],
+    pub(super) calls: &'s [(Symbol, Span)]
+}
+
+fn sorted(variables: &HashMap<Symbol, Value>) -> Vec<(Symbol, &Value)> {
+    let mut variables: Vec<(Symbol, &Value)> = variables.iter().map(|(&name, value)| (name, value)).collect();
+    variables.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+    variables
+}
+
+impl<'s> Scope<'s> {
+    // The variable `name` as the program would read it there, if it has been assigned.
+    pub fn variable(&self, name: &str) -> Option<&'s Value> {
+        let name = Symbol::from(name);
+        match self.frames.last() {
+            Some(locals) if locals.contains_key(&name) => locals.get(&name),
+            _ => self.globals.get(&name),
+        }
+    }
+
+    // The current function's parameters and locals, by name; none at the top level.
+    pub fn locals(&self) -> Vec<(Symbol, &'s Value)> {
+        self.frame(self.depth())
+    }
+
+    // The parameters and locals of the call `depth` deep, by name, counting the outermost
+    // call as 1; none for 0, the top level.
+    pub fn frame(&self, depth: usize) -> Vec<(Symbol, &'s Value)> {
+        depth.checked_sub(1).and_then(|index| self.frames.get(index)).map(sorted).unwrap_or_default()
+    }
+
+    // The globals assigned so far, by name.
+    pub fn globals(&self) -> Vec<(Symbol, &'s Value)> {
+        sorted(self.globals)
+    }
+
+    // Each function being run and the call that entered it, outermost first.
+    pub fn calls(&self) -> &'s [(Symbol, Span)] {
+        self.calls
+    }
+
+    // How many calls deep the program is; 0 at the top level.
+    pub fn depth(&self) -> usize {
+        self.calls.len()
+    }
+}
+
+// What the program does after a hook has been called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    // Stop with `RuntimeError::Stopped`.
+    Stop
+}
+
+pub trait Hook {
+    // Called before each statement runs, including blocks and the statements in them.
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action;
+}
+
+// A hook can be lent to the interpreter and looked at again once the program has run.
+impl<H: Hook + ?Sized> Hook for &mut H {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        (**self).statement(statement, scope)
+    }
+}
+
+// No hook at all, for one that's only sometimes wanted.
+impl<H: Hook> Hook for Option<H> {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        match self {
+            Some(hook) => hook.statement(statement, scope),
+            None => Action::Continue,
+        }
+    }
+}
+
+// Two hooks, called in order. The program stops if either says so.
+impl<A: Hook, B: Hook> Hook for (A, B) {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        match self.0.statement(statement, scope) {
+            Action::Continue => self.1.statement(statement, scope),
+            Action::Stop => Action::Stop,
+        }
+    }
+}
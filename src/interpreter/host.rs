@@ -0,0 +1,197 @@
+// Functions an embedding program provides for tiny-lang code to call. They're ordinary Rust
+// closures over types that convert to and from `Value`, and the interpreter checks each
+// call's arguments against them before calling.
+
+use super::value::Value;
+
+// A Rust type a tiny-lang argument can be passed as.
+pub trait FromValue: Sized {
+    // What the type is called in errors, like `Value::type_name`.
+    const TYPE: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for i64 {
+    const TYPE: &'static str = "integer";
+
+    // Booleans count as 0 and 1, as they do in arithmetic.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(n) => Some(*n),
+            Value::Bool(b) => Some(*b as i64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    const TYPE: &'static str = "float";
+
+    // Integers are converted, as they are when they meet a float in arithmetic.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(x) => Some(*x),
+            Value::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE: &'static str = "bool";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const TYPE: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Any value at all, for functions that look at the type themselves.
+impl FromValue for Value {
+    const TYPE: &'static str = "value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+// A Rust type a host function can return. `Err` is a failure the call reports as an error.
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, String>;
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::Integer(self))
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::Float(self))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::Bool(self))
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::String(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::String(self.to_string()))
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(self)
+    }
+}
+
+// Functions that return nothing return 0, as tiny-lang functions that fall off their end do.
+impl IntoValue for () {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(Value::Integer(0))
+    }
+}
+
+impl<T: IntoValue, E: ToString> IntoValue for Result<T, E> {
+    fn into_value(self) -> Result<Value, String> {
+        self.map_err(|error| error.to_string())?.into_value()
+    }
+}
+
+// Why a call to a host function didn't produce a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostError {
+    // The argument at `index`, counting from 0, isn't of the type the function takes.
+    Argument { index: usize, expected: &'static str, found: &'static str },
+    Failed(String)
+}
+
+// A closure that can be registered as a host function: one taking up to four arguments of
+// `FromValue` types and returning an `IntoValue` type. `Args` is the tuple of its argument
+// types, which only tells the implementations apart.
+pub trait HostFn<Args>: 'static {
+    fn arity(&self) -> usize;
+
+    fn call(&self, arguments: &[Value]) -> Result<Value, HostError>;
+}
+
+macro_rules! host_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> HostFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoValue,
+            $($arg: FromValue,)*
+        {
+            fn arity(&self) -> usize {
+                0 $(+ { let _ = stringify!($arg); 1 })*
+            }
+
+            // Each type parameter names the argument of that type too.
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(&self, arguments: &[Value]) -> Result<Value, HostError> {
+                let mut arguments = arguments.iter().enumerate();
+                $(
+                    let (index, value) = arguments.next().unwrap();
+                    let $arg = $arg::from_value(value)
+                        .ok_or(HostError::Argument { index, expected: $arg::TYPE, found: value.type_name() })?;
+                )*
+                self($($arg),*).into_value().map_err(HostError::Failed)
+            }
+        }
+    };
+}
+
+host_fn!();
+host_fn!(A);
+host_fn!(A, B);
+host_fn!(A, B, C);
+host_fn!(A, B, C, D);
+
+type Call = dyn Fn(&[Value]) -> Result<Value, HostError>;
+
+// A registered host function, with its argument types erased.
+pub struct HostFunction {
+    arity: usize,
+    call: Box<Call>
+}
+
+impl HostFunction {
+    pub fn new<Args: 'static>(function: impl HostFn<Args>) -> Self {
+        HostFunction { arity: function.arity(), call: Box::new(move |arguments| function.call(arguments)) }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    // Calls the function with arguments of which there are `arity`.
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, HostError> {
+        (self.call)(arguments)
+    }
+}
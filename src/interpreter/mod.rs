@@ -0,0 +1,15 @@
+mod value;
+mod error;
+mod eval;
+mod hook;
+mod host;
+mod overflow;
+mod stack;
+
+pub use value::Value;
+pub use error::RuntimeError;
+pub(crate) use error::{division_by_zero, nested_too_deep, out_of_bounds, overflow, wrong_type};
+pub use eval::{Interpreter, MAX_CALL_DEPTH};
+pub use overflow::Overflow;
+pub use hook::{Action, Hook, Scope};
+pub use host::{FromValue, HostError, HostFn, HostFunction, IntoValue};
@@ -0,0 +1,74 @@
+// What integer arithmetic does when its result doesn't fit in an i64.
+
+use crate::lexer::TokenKind;
+
+type Op = fn(i64, i64) -> i64;
+type CheckedOp = fn(i64, i64) -> Option<i64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    // The result wraps around, as two's complement arithmetic does: `i64::MAX + 1` is
+    // `i64::MIN`.
+    #[default]
+    Wrapping,
+    // The result is clamped to `i64::MIN` or `i64::MAX`.
+    Saturating,
+    // Overflowing is a runtime error.
+    Trapping
+}
+
+impl Overflow {
+    // The behavior an `--overflow` value names.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "wrap" => Some(Overflow::Wrapping),
+            "saturate" => Some(Overflow::Saturating),
+            "trap" => Some(Overflow::Trapping),
+            _ => None,
+        }
+    }
+
+    // `a operator b` for one of `+ - * / %`, or `None` if it overflows and that traps.
+    // Dividing by zero must already have been ruled out. The only remainder that can
+    // overflow, `i64::MIN % -1`, is 0 whatever the behavior.
+    pub fn binary(self, operator: &TokenKind, a: i64, b: i64) -> Option<i64> {
+        let (checked, wrapping, saturating): (CheckedOp, Op, Op) = match operator {
+            TokenKind::OpAdd => (i64::checked_add, i64::wrapping_add, i64::saturating_add),
+            TokenKind::OpSubtract => (i64::checked_sub, i64::wrapping_sub, i64::saturating_sub),
+            TokenKind::OpMultiply => (i64::checked_mul, i64::wrapping_mul, i64::saturating_mul),
+            TokenKind::OpDivide => (i64::checked_div, i64::wrapping_div, i64::saturating_div),
+            TokenKind::OpMod => return Some(a.wrapping_rem(b)),
+            t => panic!("Unsupported arithmetic operator: {:?}", t),
+        };
+        match self {
+            Overflow::Wrapping => Some(wrapping(a, b)),
+            Overflow::Saturating => Some(saturating(a, b)),
+            Overflow::Trapping => checked(a, b),
+        }
+    }
+
+    pub fn negate(self, n: i64) -> Option<i64> {
+        match self {
+            Overflow::Wrapping => Some(n.wrapping_neg()),
+            Overflow::Saturating => Some(n.saturating_neg()),
+            Overflow::Trapping => n.checked_neg(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_behavior_handles_overflow_its_own_way() {
+        let add = |overflow: Overflow| overflow.binary(&TokenKind::OpAdd, i64::MAX, 1);
+        assert_eq!(add(Overflow::Wrapping), Some(i64::MIN));
+        assert_eq!(add(Overflow::Saturating), Some(i64::MAX));
+        assert_eq!(add(Overflow::Trapping), None);
+        assert_eq!(Overflow::Trapping.binary(&TokenKind::OpMod, i64::MIN, -1), Some(0));
+        assert_eq!(Overflow::Saturating.negate(i64::MIN), Some(i64::MAX));
+        assert_eq!(Overflow::parse("trap"), Some(Overflow::Trapping));
+        assert_eq!(Overflow::parse("panic"), None);
+    }
+}
@@ -0,0 +1,77 @@
+// How much of the current thread's stack is left, so that a call can be refused before it
+// overflows the stack rather than after. Each call the interpreter makes takes some of it,
+// far more in a debug build than in a release one, and a thread the embedding program
+// spawns may have much less than the main thread.
+
+use std::cell::Cell;
+use std::ffi::{c_int, c_void};
+
+// What a call may still need once it's been let through: enough for evaluating what's in
+// it, however a debug build lays out its frames.
+pub(super) const RESERVE: usize = 256 << 10;
+
+#[cfg(target_os = "linux")]
+unsafe extern "C" {
+    fn pthread_self() -> usize;
+    fn pthread_getattr_np(thread: usize, attr: *mut c_void) -> c_int;
+    fn pthread_attr_getstack(attr: *const c_void, addr: *mut *mut c_void, size: *mut usize) -> c_int;
+    fn pthread_attr_destroy(attr: *mut c_void) -> c_int;
+}
+
+// The lowest address of the current thread's stack, which grows down towards it.
+#[cfg(target_os = "linux")]
+fn stack_start() -> Option<usize> {
+    // Big and aligned enough for any `pthread_attr_t`, which is opaque.
+    let mut attr = [0u64; 16];
+    let attr = attr.as_mut_ptr() as *mut c_void;
+    let (mut addr, mut size) = (std::ptr::null_mut(), 0);
+    // SAFETY: `attr` is initialized by `pthread_getattr_np` before it's read, and destroyed
+    // once the stack has been read from it.
+    unsafe {
+        if pthread_getattr_np(pthread_self(), attr) != 0 {
+            return None;
+        }
+        let found = pthread_attr_getstack(attr, &mut addr, &mut size);
+        pthread_attr_destroy(attr);
+        (found == 0).then_some(addr as usize)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn stack_start() -> Option<usize> {
+    None
+}
+
+thread_local! {
+    static START: Cell<Option<Option<usize>>> = const { Cell::new(None) };
+}
+
+// How many bytes of stack are left below this function's frame, if that can be found out.
+pub(super) fn remaining() -> Option<usize> {
+    let start = START.with(|start| {
+        let found = start.get().unwrap_or_else(stack_start);
+        start.set(Some(found));
+        found
+    })?;
+    let here = 0u8;
+    Some((&raw const here as usize).saturating_sub(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn deeper() -> Option<usize> {
+        let frame = std::hint::black_box([0u8; 4096]);
+        remaining().map(|left| left + frame[0] as usize)
+    }
+
+    #[test]
+    fn what_is_left_goes_down_as_the_stack_grows() {
+        let (outer, inner) = std::thread::spawn(|| (remaining(), deeper())).join().unwrap();
+        if cfg!(target_os = "linux") {
+            assert!(outer.zip(inner).is_some_and(|(outer, inner)| inner + 4096 <= outer), "{:?} {:?}", outer, inner);
+        }
+    }
+}
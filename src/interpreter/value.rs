@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    // Arrays live on the heap and are shared by reference.
+    Array(Rc<RefCell<Vec<Value>>>)
+}
+
+impl Value {
+    pub fn as_integer(&self) -> i64 {
+        match self {
+            Value::Integer(n) => *n,
+            // Booleans count as 0 and 1 in arithmetic.
+            Value::Bool(b) => *b as i64,
+            // Floats are never truncated implicitly.
+            Value::Float(x) => panic!("Expected an integer, found float {:?}", x),
+            Value::String(s) => panic!("Expected an integer, found string {:?}", s),
+            Value::Array(_) => panic!("Expected an integer, found an array"),
+        }
+    }
+
+    pub fn array(elements: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+        }
+    }
+
+    // The integer a value counts as in arithmetic, if any.
+    pub fn integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            Value::Bool(b) => Some(*b as i64),
+            _ => None,
+        }
+    }
+
+    // Integer operands stay integers; if either side is a float, both are converted to floats.
+    // `None` if either side isn't a number.
+    fn numbers(&self, other: &Value) -> Option<Numbers> {
+        match (self, other) {
+            (Value::Float(l), Value::Float(r)) => Some(Numbers::Floats(*l, *r)),
+            (Value::Float(l), r) => Some(Numbers::Floats(*l, r.integer()? as f64)),
+            (l, Value::Float(r)) => Some(Numbers::Floats(l.integer()? as f64, *r)),
+            (l, r) => Some(Numbers::Integers(l.integer()?, r.integer()?)),
+        }
+    }
+
+    // Both operands as integers, if arithmetic on them is integer arithmetic.
+    pub fn integers(&self, other: &Value) -> Option<(i64, i64)> {
+        match self.numbers(other)? {
+            Numbers::Integers(l, r) => Some((l, r)),
+            Numbers::Floats(..) => None,
+        }
+    }
+
+    pub fn arithmetic(&self, other: &Value, int: fn(i64, i64) -> i64, float: fn(f64, f64) -> f64) -> Option<Value> {
+        match self.numbers(other)? {
+            Numbers::Integers(l, r) => Some(Value::Integer(int(l, r))),
+            Numbers::Floats(l, r) => Some(Value::Float(float(l, r))),
+        }
+    }
+
+    // As `arithmetic`, but also `None` where the integer operation fails, such as dividing by
+    // zero, instead of panicking.
+    pub fn checked_arithmetic(&self, other: &Value, int: fn(i64, i64) -> Option<i64>, float: fn(f64, f64) -> f64) -> Option<Value> {
+        match self.numbers(other)? {
+            Numbers::Integers(l, r) => Some(Value::Integer(int(l, r)?)),
+            Numbers::Floats(l, r) => Some(Value::Float(float(l, r))),
+        }
+    }
+
+    // Whether `self / divisor` or `self % divisor` divides an integer by zero. Floats divide
+    // by zero to an infinity or NaN instead.
+    pub fn divides_by_zero(&self, divisor: &Value) -> bool {
+        matches!(self.numbers(divisor), Some(Numbers::Integers(_, 0)))
+    }
+
+    pub fn compare(&self, other: &Value, int: fn(&i64, &i64) -> bool, float: fn(&f64, &f64) -> bool) -> Option<Value> {
+        match self.numbers(other)? {
+            Numbers::Integers(l, r) => Some(Value::from(int(&l, &r))),
+            Numbers::Floats(l, r) => Some(Value::from(float(&l, &r))),
+        }
+    }
+
+    // Strings are equal to strings with the same contents and numbers to numbers;
+    // comparing a string with a number is a type error, not `false`.
+    pub fn equals(&self, other: &Value) -> Option<bool> {
+        match (self, other) {
+            (Value::String(l), Value::String(r)) => Some(l == r),
+            _ => match self.numbers(other)? {
+                Numbers::Integers(l, r) => Some(l == r),
+                Numbers::Floats(l, r) => Some(l == r),
+            },
+        }
+    }
+
+    pub fn concat(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::String(l), Value::String(r)) => Some(Value::String(format!("{}{}", l, r))),
+            _ => None,
+        }
+    }
+
+    // Bitwise operators only apply to integers.
+    pub fn bitwise(&self, other: &Value, op: fn(i64, i64) -> i64) -> Option<Value> {
+        Some(Value::Integer(op(self.integer()?, other.integer()?)))
+    }
+
+    pub fn negate(&self) -> Option<Value> {
+        match self {
+            Value::Float(x) => Some(Value::Float(-x)),
+            v => Some(Value::Integer(-v.integer()?)),
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Integer(n) => *n != 0,
+            Value::Float(x) => *x != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Array(elements) => !elements.borrow().is_empty(),
+        }
+    }
+}
+
+enum Numbers {
+    Integers(i64, i64),
+    Floats(f64, f64)
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            // Debug formatting keeps the `.0` on whole numbers.
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
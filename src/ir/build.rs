@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals, names_in_statement};
+
+use super::cfg::BlockId;
+use super::ssa::{Block, Constant, Function, Module, Op, Terminator, ValueId};
+use super::verify::verify;
+
+// Every name a function mentions that isn't one of its locals.
+fn globals_used(parameters: &[Symbol], body: &[Statement]) -> Vec<Symbol> {
+    let locals = function_locals(parameters, body);
+    let mut names = Vec::new();
+    body.iter().for_each(|stmt| names_in_statement(stmt, &mut names));
+    names.retain(|name| !locals.contains(name));
+    names
+}
+
+// Builds one function with the algorithm of Braun et al.: a variable read in a block that
+// doesn't assign it is looked up in its predecessors, with a phi where they may differ.
+// Blocks are sealed once all their predecessors are known; until then their phis wait.
+struct Builder {
+    function: Function,
+    // Variables that are values rather than globals in memory.
+    variables: HashSet<Symbol>,
+    definitions: HashMap<(Symbol, BlockId), ValueId>,
+    sealed: Vec<bool>,
+    incomplete: HashMap<BlockId, Vec<(Symbol, ValueId)>>,
+    current: BlockId,
+    // Unassigned variables read as 0.
+    zero: Option<ValueId>
+}
+
+impl Builder {
+    fn new(name: &str, parameters: usize, variables: HashSet<Symbol>) -> Self {
+        let function = Function { name: name.to_string(), parameters, blocks: Vec::new(), values: Vec::new() };
+        let mut builder = Builder {
+            function,
+            variables,
+            definitions: HashMap::new(),
+            sealed: Vec::new(),
+            incomplete: HashMap::new(),
+            current: 0,
+            zero: None,
+        };
+        let entry = builder.block();
+        builder.seal(entry);
+        builder
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.function.blocks.push(Block { instructions: Vec::new(), terminator: Terminator::Return(None), predecessors: Vec::new() });
+        self.sealed.push(false);
+        self.function.blocks.len() - 1
+    }
+
+    fn value(&mut self, op: Op) -> ValueId {
+        self.function.values.push(op);
+        ValueId(self.function.values.len() - 1)
+    }
+
+    fn emit(&mut self, op: Op) -> ValueId {
+        let value = self.value(op);
+        self.function.blocks[self.current].instructions.push(value);
+        value
+    }
+
+    fn constant(&mut self, constant: Constant) -> ValueId {
+        self.emit(Op::Const(constant))
+    }
+
+    fn zero(&mut self) -> ValueId {
+        if let Some(zero) = self.zero {
+            return zero;
+        }
+        // At the very start of the entry, which comes before everything else.
+        let zero = self.value(Op::Const(Constant::Integer(0)));
+        self.function.blocks[0].instructions.insert(0, zero);
+        self.zero = Some(zero);
+        zero
+    }
+
+    // Ends the current block. What follows goes in `next`.
+    fn terminate(&mut self, terminator: Terminator, next: BlockId) {
+        for successor in terminator.successors() {
+            self.function.blocks[successor].predecessors.push(self.current);
+        }
+        self.function.blocks[self.current].terminator = terminator;
+        self.current = next;
+    }
+
+    fn seal(&mut self, block: BlockId) {
+        for (variable, phi) in self.incomplete.remove(&block).unwrap_or_default() {
+            self.add_phi_operands(variable, phi, block);
+        }
+        self.sealed[block] = true;
+    }
+
+    fn write(&mut self, variable: Symbol, block: BlockId, value: ValueId) {
+        self.definitions.insert((variable, block), value);
+    }
+
+    fn phi(&mut self, block: BlockId) -> ValueId {
+        let phi = self.value(Op::Phi(Vec::new()));
+        let instructions = &mut self.function.blocks[block].instructions;
+        let first_non_phi = instructions.iter().position(|&v| !matches!(self.function.values[v.0], Op::Phi(_)));
+        instructions.insert(first_non_phi.unwrap_or(instructions.len()), phi);
+        phi
+    }
+
+    fn read(&mut self, variable: Symbol, block: BlockId) -> ValueId {
+        if let Some(&value) = self.definitions.get(&(variable, block)) {
+            return value;
+        }
+        let predecessors = self.function.blocks[block].predecessors.clone();
+        let value = if !self.sealed[block] {
+            let phi = self.phi(block);
+            self.incomplete.entry(block).or_default().push((variable, phi));
+            phi
+        } else if predecessors.is_empty() {
+            self.zero()
+        } else if let [predecessor] = predecessors[..] {
+            self.read(variable, predecessor)
+        } else {
+            // Written first, so that a loop back to this block finds the phi.
+            let phi = self.phi(block);
+            self.write(variable, block, phi);
+            self.add_phi_operands(variable, phi, block);
+            phi
+        };
+        self.write(variable, block, value);
+        value
+    }
+
+    fn add_phi_operands(&mut self, variable: Symbol, phi: ValueId, block: BlockId) {
+        let predecessors = self.function.blocks[block].predecessors.clone();
+        let incoming = predecessors.into_iter().map(|p| (p, self.read(variable, p))).collect();
+        self.function.values[phi.0] = Op::Phi(incoming);
+    }
+
+    fn load(&mut self, name: Symbol) -> ValueId {
+        if self.variables.contains(&name) {
+            self.read(name, self.current)
+        } else {
+            self.emit(Op::LoadGlobal(name))
+        }
+    }
+
+    fn store(&mut self, name: Symbol, value: ValueId) {
+        if self.variables.contains(&name) {
+            self.write(name, self.current, value);
+        } else {
+            self.emit(Op::StoreGlobal(name, value));
+        }
+    }
+
+    // A value that's one of two, depending on which of two blocks control came through.
+    fn join(&mut self, then_value: (BlockId, ValueId), else_value: (BlockId, ValueId)) -> ValueId {
+        let phi = self.phi(self.current);
+        self.function.values[phi.0] = Op::Phi(vec![then_value, else_value]);
+        phi
+    }
+
+    fn expression(&mut self, expr: &Expression) -> ValueId {
+        match expr {
+            Expression::Number(n, _) => self.constant(Constant::Integer(*n)),
+            Expression::Char(c, _) => self.constant(Constant::Integer(*c as i64)),
+            Expression::Float(x, _) => self.constant(Constant::Float(*x)),
+            Expression::Boolean(b, _) => self.constant(Constant::Bool(*b)),
+            Expression::String(s, _) => self.constant(Constant::String(s.clone())),
+            Expression::Symbol(name, _) => self.load(*name),
+            Expression::Unary {operator, operand, ..} => {
+                let operand = self.expression(operand);
+                self.emit(Op::Unary(operator.clone(), operand))
+            }
+            Expression::Binary {left, operator: operator @ (TokenKind::OpAnd | TokenKind::OpOr), right, ..} => {
+                // The right side is only evaluated if the left doesn't decide the result.
+                let left = self.expression(left);
+                let (right_block, join) = (self.block(), self.block());
+                let short = self.constant(Constant::Bool(*operator == TokenKind::OpOr));
+                let from_left = self.current;
+                let branch = match operator {
+                    TokenKind::OpAnd => Terminator::Branch(left, right_block, join),
+                    _ => Terminator::Branch(left, join, right_block),
+                };
+                self.terminate(branch, right_block);
+                self.seal(right_block);
+                let right = self.expression(right);
+                let right = self.emit(Op::Truthy(right));
+                let from_right = self.current;
+                self.terminate(Terminator::Jump(join), join);
+                self.seal(join);
+                self.join((from_left, short), (from_right, right))
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.emit(Op::Binary(operator.clone(), left, right))
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                // The value is evaluated before the element it goes in.
+                let value = self.expression(value);
+                match assignee.as_ref() {
+                    Expression::Index {target, index, ..} => {
+                        let target = self.expression(target);
+                        let index = self.expression(index);
+                        self.emit(Op::StoreIndex(target, index, value));
+                    }
+                    Expression::Symbol(name, _) => self.store(*name, value),
+                    e => panic!("Invalid assignment target: {:?}", e),
+                }
+                value
+            }
+            Expression::Call {callee, arguments, ..} => {
+                let arguments = arguments.iter().map(|a| self.expression(a)).collect();
+                self.emit(Op::Call(*callee, arguments))
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let condition = self.expression(condition);
+                let (then_block, else_block, join) = (self.block(), self.block(), self.block());
+                self.terminate(Terminator::Branch(condition, then_block, else_block), then_block);
+                self.seal(then_block);
+                self.seal(else_block);
+                let then_value = self.expression(then_value);
+                let from_then = self.current;
+                self.terminate(Terminator::Jump(join), else_block);
+                let else_value = self.expression(else_value);
+                let from_else = self.current;
+                self.terminate(Terminator::Jump(join), join);
+                self.seal(join);
+                self.join((from_then, then_value), (from_else, else_value))
+            }
+            Expression::Array {elements, ..} => {
+                let elements = elements.iter().map(|e| self.expression(e)).collect();
+                self.emit(Op::Array(elements))
+            }
+            Expression::Index {target, index, ..} => {
+                let target = self.expression(target);
+                let index = self.expression(index);
+                self.emit(Op::Index(target, index))
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Block {body, ..} => body.iter().for_each(|s| self.statement(s)),
+            Statement::Attributed {statement, ..} => self.statement(statement),
+            Statement::Expression {expression, ..} => {
+                self.expression(expression);
+            }
+            Statement::Print {arguments, ..} => {
+                for argument in arguments {
+                    let value = self.expression(argument);
+                    self.emit(Op::Print(value));
+                }
+            }
+            Statement::Putc {expression, ..} => {
+                let value = self.expression(expression);
+                self.emit(Op::Putc(value));
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let condition = self.expression(condition);
+                let (then_block, else_block, join) = (self.block(), self.block(), self.block());
+                self.terminate(Terminator::Branch(condition, then_block, else_block), then_block);
+                self.seal(then_block);
+                self.seal(else_block);
+                self.statement(then_branch);
+                self.terminate(Terminator::Jump(join), else_block);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.terminate(Terminator::Jump(join), join);
+                self.seal(join);
+            }
+            // Loops are built rotated, as an `if` around a `do`-`while`, so that the body is
+            // the loop's first block and runs whenever the loop is entered.
+            Statement::While {condition, body, ..} => self.rotated_loop(Some(condition), body, None),
+            Statement::DoWhile {body, condition, ..} => {
+                let (body_block, exit) = (self.block(), self.block());
+                self.terminate(Terminator::Jump(body_block), body_block);
+                self.statement(body);
+                let condition = self.expression(condition);
+                self.terminate(Terminator::Branch(condition, body_block, exit), exit);
+                self.seal(body_block);
+                self.seal(exit);
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.rotated_loop(condition.as_ref(), body, step.as_ref());
+            }
+            // Functions are built separately.
+            Statement::Function {..} | Statement::Import {..} => {}
+            Statement::Return {value, ..} => {
+                let value = match value {
+                    Some(value) => self.expression(value),
+                    None => self.constant(Constant::Integer(0)),
+                };
+                // Anything after a return is unreachable and goes in a block of its own.
+                let next = self.block();
+                self.terminate(Terminator::Return(Some(value)), next);
+                self.seal(next);
+            }
+        }
+    }
+
+    // Tests `condition` before the first iteration and again after each; a missing
+    // condition loops forever.
+    fn rotated_loop(&mut self, condition: Option<&Expression>, body: &Statement, step: Option<&Expression>) {
+        let (body_block, exit) = (self.block(), self.block());
+        let test = |builder: &mut Self, next: BlockId| match condition {
+            Some(condition) => {
+                let condition = builder.expression(condition);
+                builder.terminate(Terminator::Branch(condition, body_block, exit), next);
+            }
+            None => builder.terminate(Terminator::Jump(body_block), next),
+        };
+        test(self, body_block);
+        self.statement(body);
+        if let Some(step) = step {
+            self.expression(step);
+        }
+        test(self, exit);
+        self.seal(body_block);
+        self.seal(exit);
+    }
+
+    // Ends the function the way falling off its end does.
+    fn finish(mut self, returns: bool) -> Function {
+        let value = returns.then(|| self.constant(Constant::Integer(0)));
+        let current = self.current;
+        self.terminate(Terminator::Return(value), current);
+        self.function.remove_unreachable();
+        self.function.remove_trivial_phis();
+        self.function.renumber();
+        self.function
+    }
+}
+
+// The program in SSA form: the top level as `main`, then each function. Top-level variables
+// that no function mentions are values; the rest stay globals in memory.
+pub fn build_ssa(program: &[Statement]) -> Module {
+    let mut shared = HashSet::new();
+    let mut functions = Vec::new();
+    for stmt in program {
+        if let Statement::Function {name, parameters, body, ..} = stmt {
+            shared.extend(globals_used(parameters, body));
+            functions.push((name, parameters, body));
+        }
+    }
+
+    let mut top_level = Vec::new();
+    program.iter().for_each(|stmt| names_in_statement(stmt, &mut top_level));
+    let variables = top_level.into_iter().filter(|name| !shared.contains(name)).collect();
+    let mut main = Builder::new("main", 0, variables);
+    program.iter().for_each(|stmt| main.statement(stmt));
+    let mut module = Module { functions: vec![main.finish(false)] };
+
+    for (name, parameters, body) in functions {
+        let variables = function_locals(parameters, body).into_iter().collect();
+        let mut builder = Builder::new(name.as_str(), parameters.len(), variables);
+        for (index, parameter) in parameters.iter().enumerate() {
+            let value = builder.emit(Op::Param(index));
+            builder.write(*parameter, 0, value);
+        }
+        body.iter().for_each(|stmt| builder.statement(stmt));
+        module.functions.push(builder.finish(true));
+    }
+
+    for function in &module.functions {
+        debug_assert_eq!(verify(function), Ok(()), "{}", function);
+    }
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::build_ssa;
+
+    fn built(source: &str) -> String {
+        let (tokens, errors) = tokenize(source);
+        assert!(errors.is_empty());
+        build_ssa(&parse_program(&mut Parser::new(tokens)).unwrap()).to_string()
+    }
+
+    #[test]
+    fn joins_branches_with_phis_and_keeps_globals_functions_read_in_memory() {
+        assert_eq!(built("g = 1;\nfn f(n) { if (n > 0) { x = g; } else { x = 2; } return x + g; }\nprint(f(3));\n"), "\
+fn main(0) {
+b0:
+    %0 = const 1
+    store @g, %0
+    %2 = const 3
+    %3 = call f(%2)
+    print %3
+    ret
+}
+
+fn f(1) {
+b0:
+    %0 = param 0
+    %1 = const 0
+    %2 = gt %0, %1
+    br %2, b1, b2
+b1: ; preds b0
+    %3 = load @g
+    jmp b3
+b2: ; preds b0
+    %4 = const 2
+    jmp b3
+b3: ; preds b1, b2
+    %5 = phi [b1: %3], [b2: %4]
+    %6 = load @g
+    %7 = add %5, %6
+    ret %7
+}
+");
+    }
+
+    #[test]
+    fn short_circuits_and_and_or() {
+        assert_eq!(built("fn f(a, b) { return a && b || !a; }\n"), "\
+fn main(0) {
+b0:
+    ret
+}
+
+fn f(2) {
+b0:
+    %0 = param 0
+    %1 = param 1
+    %2 = const false
+    br %0, b1, b2
+b1: ; preds b0
+    %3 = truthy %1
+    jmp b2
+b2: ; preds b0, b1
+    %4 = phi [b0: %2], [b1: %3]
+    %5 = const true
+    br %4, b4, b3
+b3: ; preds b2
+    %6 = not %0
+    %7 = truthy %6
+    jmp b4
+b4: ; preds b2, b3
+    %8 = phi [b2: %5], [b3: %7]
+    ret %8
+}
+");
+    }
+}
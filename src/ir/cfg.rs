@@ -0,0 +1,202 @@
+use crate::codegen::{Bytecode, Instruction};
+
+pub type BlockId = usize;
+
+// A run of instructions that's only entered at its first and only left after its last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    // Indices into the bytecode's instructions, `end` exclusive.
+    pub start: usize,
+    pub end: usize,
+    // A conditional jump's target comes before the instruction it falls through to.
+    pub successors: Vec<BlockId>,
+    pub predecessors: Vec<BlockId>
+}
+
+// The control-flow graph of the top level or of one function. Calls don't end a block;
+// each function has a graph of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    // `main` for the top level.
+    pub name: String,
+    // In code order, so the first is the entry.
+    pub blocks: Vec<BasicBlock>
+}
+
+impl Cfg {
+    // The graph of the instructions from `start` up to `end`. Blocks that nothing reaches
+    // are kept, without predecessors.
+    pub fn build(name: &str, code: &[Instruction], start: usize, end: usize) -> Cfg {
+        let mut leaders = vec![false; end - start + 1];
+        leaders[0] = true;
+        leaders[end - start] = true;
+        for i in start..end {
+            match code[i] {
+                Instruction::Jmp(target) | Instruction::Jz(target) => {
+                    if (start..end).contains(&target) {
+                        leaders[target - start] = true;
+                    }
+                    leaders[i + 1 - start] = true;
+                }
+                Instruction::Ret | Instruction::Halt => leaders[i + 1 - start] = true,
+                _ => {}
+            }
+        }
+
+        let starts: Vec<usize> = (start..end).filter(|&i| leaders[i - start]).collect();
+        let mut blocks: Vec<BasicBlock> = starts.iter().enumerate()
+            .map(|(id, &first)| BasicBlock {
+                start: first,
+                end: starts.get(id + 1).copied().unwrap_or(end),
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            })
+            .collect();
+
+        let block_at = |index: usize| starts.binary_search(&index).ok();
+        for id in 0..blocks.len() {
+            let last = blocks[id].end - 1;
+            let fallthrough = block_at(blocks[id].end);
+            let successors: Vec<BlockId> = match code[last] {
+                Instruction::Jmp(target) => block_at(target).into_iter().collect(),
+                Instruction::Jz(target) => block_at(target).into_iter().chain(fallthrough).collect(),
+                Instruction::Ret | Instruction::Halt => Vec::new(),
+                _ => fallthrough.into_iter().collect(),
+            };
+            for &successor in &successors {
+                if !blocks[successor].predecessors.contains(&id) {
+                    blocks[successor].predecessors.push(id);
+                }
+            }
+            blocks[id].successors = successors;
+        }
+        Cfg { name: name.to_string(), blocks }
+    }
+
+    // The block holding the instruction at `index`, if this graph has it.
+    pub fn block_of(&self, index: usize) -> Option<BlockId> {
+        self.blocks.iter().position(|block| (block.start..block.end).contains(&index))
+    }
+
+    // Which blocks control can get to from the entry.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.blocks.len()];
+        let mut stack: Vec<BlockId> = if self.blocks.is_empty() { Vec::new() } else { vec![0] };
+        while let Some(id) = stack.pop() {
+            if !std::mem::replace(&mut reached[id], true) {
+                stack.extend(&self.blocks[id].successors);
+            }
+        }
+        reached
+    }
+}
+
+// A graph for the top level, then one for each function in the order they're declared.
+pub fn build_cfgs(bytecode: &Bytecode) -> Vec<Cfg> {
+    let code = &bytecode.code;
+    // Each function's code runs up to the next one's, and the top level's up to the first.
+    let mut entries: Vec<usize> = bytecode.functions.iter().map(|function| function.entry).collect();
+    entries.sort_unstable();
+    let end_of = |start: usize| entries.iter().copied().find(|&entry| entry > start).unwrap_or(code.len());
+
+    let mut cfgs = vec![Cfg::build("main", code, 0, entries.first().copied().unwrap_or(code.len()))];
+    for function in &bytecode.functions {
+        cfgs.push(Cfg::build(&function.name, code, function.entry, end_of(function.entry)));
+    }
+    cfgs
+}
+
+fn instruction_text(bytecode: &Bytecode, cfg: &Cfg, instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Jmp(target) => format!("jmp   B{}", cfg.block_of(*target).unwrap_or(*target)),
+        Instruction::Jz(target) => format!("jz    B{}", cfg.block_of(*target).unwrap_or(*target)),
+        Instruction::Call(index) => format!("call  {}", bytecode.functions[*index].name),
+        instruction => instruction.to_string(),
+    }
+}
+
+// Each block's instructions, with jumps naming the block they go to, and its successors.
+pub fn cfgs_to_text(bytecode: &Bytecode, cfgs: &[Cfg]) -> String {
+    let offsets = bytecode.offsets();
+    let mut out = String::new();
+    for (i, cfg) in cfgs.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}:\n", cfg.name));
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let predecessors: Vec<String> = block.predecessors.iter().map(|p| format!("B{}", p)).collect();
+            out.push_str(&format!("  B{}: ; preds: {}\n", id, if predecessors.is_empty() { "none".to_string() } else { predecessors.join(", ") }));
+            let instructions = bytecode.code[block.start..block.end].iter().zip(&offsets[block.start..]);
+            for (instruction, offset) in instructions {
+                out.push_str(&format!("    {:5} {}\n", offset, instruction_text(bytecode, cfg, instruction)));
+            }
+            let successors: Vec<String> = block.successors.iter().map(|s| format!("B{}", s)).collect();
+            if !successors.is_empty() {
+                out.push_str(&format!("    -> {}\n", successors.join(", ")));
+            }
+        }
+    }
+    out
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// A Graphviz digraph with a cluster for each graph. A conditional jump's edges are labelled
+// with whether it's taken.
+pub fn cfgs_to_dot(bytecode: &Bytecode, cfgs: &[Cfg]) -> String {
+    let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+    for (f, cfg) in cfgs.iter().enumerate() {
+        out.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", f, escape(&cfg.name)));
+        let reachable = cfg.reachable();
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let mut label = format!("B{}\\l", id);
+            for instruction in &bytecode.code[block.start..block.end] {
+                label.push_str(&escape(&instruction_text(bytecode, cfg, instruction)));
+                label.push_str("\\l");
+            }
+            let style = if reachable[id] { "" } else { ", style=dashed" };
+            out.push_str(&format!("        f{}_b{} [label=\"{}\"{}];\n", f, id, label, style));
+        }
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let conditional = matches!(bytecode.code[block.end - 1], Instruction::Jz(_));
+            for (i, successor) in block.successors.iter().enumerate() {
+                let label = match (conditional, i) {
+                    (true, 0) => " [label=\"zero\"]",
+                    (true, _) => " [label=\"nonzero\"]",
+                    _ => "",
+                };
+                out.push_str(&format!("        f{}_b{} -> f{}_b{}{};\n", f, id, f, successor, label));
+            }
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_blocks_at_jumps_and_their_targets() {
+        use Instruction::*;
+        let code = [Push(1), Jz(4), Push(2), Jmp(5), Push(3), Halt, Push(4)];
+        let cfg = Cfg::build("main", &code, 0, code.len());
+        let edges: Vec<(usize, usize, Vec<BlockId>, Vec<BlockId>)> = cfg.blocks.iter()
+            .map(|block| (block.start, block.end, block.successors.clone(), block.predecessors.clone()))
+            .collect();
+        assert_eq!(edges, [
+            (0, 2, vec![2, 1], vec![]),
+            (2, 4, vec![3], vec![0]),
+            (4, 5, vec![3], vec![0]),
+            (5, 6, vec![], vec![1, 2]),
+            (6, 7, vec![], vec![]),
+        ]);
+        assert_eq!(cfg.reachable(), [true, true, true, true, false]);
+        assert_eq!(cfg.block_of(3), Some(1));
+    }
+}
@@ -0,0 +1,229 @@
+use super::cfg::BlockId;
+use super::ssa::{Block, Function, Module, Op, Terminator};
+use super::verify::verify;
+
+// A natural loop: its header and every block that can get back to it without leaving.
+struct Loop {
+    header: BlockId,
+    blocks: Vec<bool>
+}
+
+// The loops in `function`, innermost first.
+fn loops(function: &Function) -> Vec<Loop> {
+    let dominators = function.dominators();
+    let mut loops: Vec<Loop> = Vec::new();
+    for (latch, block) in function.blocks.iter().enumerate() {
+        if dominators[latch].is_none() {
+            continue;
+        }
+        for header in block.terminator.successors() {
+            if !Function::dominates(&dominators, header, latch) {
+                continue;
+            }
+            let index = match loops.iter().position(|l| l.header == header) {
+                Some(index) => index,
+                None => {
+                    let mut blocks = vec![false; function.blocks.len()];
+                    blocks[header] = true;
+                    loops.push(Loop { header, blocks });
+                    loops.len() - 1
+                }
+            };
+            let blocks = &mut loops[index].blocks;
+            let mut stack = vec![latch];
+            while let Some(block) = stack.pop() {
+                if !std::mem::replace(&mut blocks[block], true) {
+                    stack.extend(&function.blocks[block].predecessors);
+                }
+            }
+        }
+    }
+    loops.sort_by_key(|l| l.blocks.iter().filter(|&&inside| inside).count());
+    loops
+}
+
+// The block that control always comes through to enter the loop, and only then. A new one
+// is put on the edge into the header if the block before it can go elsewhere; `None` if
+// the header is entered from more than one place.
+fn preheader(function: &mut Function, l: &Loop) -> Option<BlockId> {
+    let header = l.header;
+    let mut outside = function.blocks[header].predecessors.iter().copied().filter(|&p| !l.blocks[p]);
+    let (Some(entry), None) = (outside.next(), outside.next()) else { return None };
+    if let Terminator::Jump(_) = function.blocks[entry].terminator {
+        return Some(entry);
+    }
+
+    let preheader = function.blocks.len();
+    function.blocks.push(Block { instructions: Vec::new(), terminator: Terminator::Jump(header), predecessors: vec![entry] });
+    if let Terminator::Branch(_, then_block, else_block) = &mut function.blocks[entry].terminator {
+        for target in [then_block, else_block] {
+            if *target == header {
+                *target = preheader;
+            }
+        }
+    }
+    for predecessor in &mut function.blocks[header].predecessors {
+        if *predecessor == entry {
+            *predecessor = preheader;
+        }
+    }
+    for &value in &function.blocks[header].instructions {
+        if let Op::Phi(incoming) = &mut function.values[value.0] {
+            for (from, _) in incoming {
+                if *from == entry {
+                    *from = preheader;
+                }
+            }
+        }
+    }
+    Some(preheader)
+}
+
+// Moves the instructions of one loop that give the same value on every iteration to the
+// end of its preheader. Instructions that can't fail and have no effects can come from
+// anywhere in the loop, since running them when the loop wouldn't have is harmless. Those
+// that can fail, such as arithmetic, must be in the header ahead of anything with effects,
+// so that they'd have failed at the same point anyway. Effects themselves never move.
+fn hoist(function: &mut Function, l: &Loop, preheader: BlockId) {
+    let mut inside = vec![false; function.values.len()];
+    let mut writes_memory = false;
+    for block in (0..function.blocks.len()).filter(|&b| l.blocks[b]) {
+        for &value in &function.blocks[block].instructions {
+            inside[value.0] = true;
+            writes_memory |= matches!(function.op(value), Op::StoreGlobal(..) | Op::Call(..));
+        }
+    }
+
+    for block in function.reverse_postorder() {
+        if !l.blocks[block] {
+            continue;
+        }
+        let mut effects_before = block != l.header;
+        let mut kept = Vec::new();
+        for value in std::mem::take(&mut function.blocks[block].instructions) {
+            let op = function.op(value);
+            let movable = match op {
+                // A fresh array each time round, which the loop may change.
+                Op::Phi(_) | Op::Array(_) => false,
+                Op::LoadGlobal(_) => !writes_memory,
+                op if !op.has_effects() => true,
+                Op::Unary(..) | Op::Binary(..) => !effects_before,
+                _ => false,
+            };
+            if movable && op.operands().iter().all(|operand| !inside[operand.0]) {
+                inside[value.0] = false;
+                function.blocks[preheader].instructions.push(value);
+            } else {
+                effects_before |= op.has_effects();
+                kept.push(value);
+            }
+        }
+        function.blocks[block].instructions = kept;
+    }
+}
+
+// Hoists loop-invariant code out of every loop in `function`, inner loops first so that
+// what leaves them can go on to leave the loops around them.
+pub fn hoist_invariants(function: &mut Function) {
+    // Giving a loop a preheader adds a block, which may belong to the loops around it, so
+    // the loops are found again each time.
+    while loops(function).iter().any(|l| {
+        let blocks = function.blocks.len();
+        preheader(function, l);
+        function.blocks.len() > blocks
+    }) {}
+    for l in loops(function) {
+        if let Some(preheader) = preheader(function, &l) {
+            hoist(function, &l, preheader);
+        }
+    }
+    function.remove_unreachable();
+    function.renumber();
+}
+
+// Hoists loop-invariant code out of every function in `module`.
+pub fn hoist_loop_invariants(module: &mut Module) {
+    for function in &mut module.functions {
+        hoist_invariants(function);
+        debug_assert_eq!(verify(function), Ok(()), "{}", function);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::hoist_loop_invariants;
+
+    // The last function in `source` once its loop invariants have been hoisted.
+    fn hoisted(source: &str) -> String {
+        let (tokens, errors) = tokenize(source);
+        assert!(errors.is_empty());
+        let mut module = build_ssa(&parse_program(&mut Parser::new(tokens)).unwrap());
+        hoist_loop_invariants(&mut module);
+        module.functions.last().unwrap().to_string()
+    }
+
+    #[test]
+    fn hoists_what_every_iteration_computes_the_same() {
+        assert_eq!(hoisted("fn f(n) { i = 0; s = 0; while (i < n) { s = s + n * 2; i = i + 1; } return s; }"), "\
+fn f(1) {
+b0:
+    %0 = param 0
+    %1 = const 0
+    %2 = const 0
+    %3 = lt %1, %0
+    br %3, b1, b3
+b1: ; preds b0
+    %4 = const 2
+    %5 = mul %0, %4
+    %6 = const 1
+    jmp b2
+b2: ; preds b1, b2
+    %7 = phi [b1: %2], [b2: %9]
+    %8 = phi [b1: %1], [b2: %10]
+    %9 = add %7, %5
+    %10 = add %8, %6
+    %11 = lt %10, %0
+    br %11, b2, b3
+b3: ; preds b0, b2
+    %12 = phi [b0: %2], [b2: %9]
+    ret %12
+}
+");
+    }
+
+    #[test]
+    fn leaves_what_has_effects_or_depends_on_the_loop() {
+        assert_eq!(hoisted("fn f(n, a) { i = 0; while (i < n) { print(n + 1); a[0] = n; i = i + a[0] * 2; } return i; }"), "\
+fn f(2) {
+b0:
+    %0 = param 0
+    %1 = param 1
+    %2 = const 0
+    %3 = lt %2, %0
+    br %3, b1, b3
+b1: ; preds b0
+    %4 = const 1
+    %5 = add %0, %4
+    %6 = const 0
+    %7 = const 0
+    %8 = const 2
+    jmp b2
+b2: ; preds b1, b2
+    %9 = phi [b1: %2], [b2: %14]
+    print %5
+    store %1[%6], %0
+    %12 = index %1, %7
+    %13 = mul %12, %8
+    %14 = add %9, %13
+    %15 = lt %14, %0
+    br %15, b2, b3
+b3: ; preds b0, b2
+    %16 = phi [b0: %2], [b2: %14]
+    ret %16
+}
+");
+    }
+}
@@ -0,0 +1,13 @@
+mod build;
+mod cfg;
+mod licm;
+mod simplify;
+mod ssa;
+mod verify;
+
+pub use build::build_ssa;
+pub use cfg::{BasicBlock, BlockId, Cfg, build_cfgs, cfgs_to_dot, cfgs_to_text};
+pub use licm::{hoist_invariants, hoist_loop_invariants};
+pub use simplify::{simplify, simplify_module};
+pub use ssa::{Block, Constant, Function, Module, Op, Terminator, ValueId};
+pub use verify::verify;
@@ -0,0 +1,293 @@
+use crate::interpreter::Value;
+use crate::lexer::TokenKind;
+use crate::opt::{evaluate_binary, evaluate_unary};
+
+use super::ssa::{Constant, Function, Module, Op, ValueId};
+use super::verify::verify;
+
+fn constant(function: &Function, value: ValueId) -> Option<Value> {
+    match function.op(value) {
+        Op::Const(Constant::Integer(n)) => Some(Value::Integer(*n)),
+        Op::Const(Constant::Float(x)) => Some(Value::Float(*x)),
+        Op::Const(Constant::Bool(b)) => Some(Value::Bool(*b)),
+        Op::Const(Constant::String(s)) => Some(Value::String(s.clone())),
+        _ => None,
+    }
+}
+
+fn to_constant(value: Value) -> Option<Constant> {
+    match value {
+        Value::Integer(n) => Some(Constant::Integer(n)),
+        Value::Float(x) => Some(Constant::Float(x)),
+        Value::Bool(b) => Some(Constant::Bool(b)),
+        Value::String(s) => Some(Constant::String(s)),
+        Value::Array(_) => None,
+    }
+}
+
+fn integer_constant(function: &Function, value: ValueId) -> Option<i64> {
+    match function.op(value) {
+        Op::Const(Constant::Integer(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+// Which values are always integers. Other numbers and bools can't be rewritten the same
+// way: `x * 1` is 1 when `x` is `true`, and `x % 1` of a float isn't 0. Every phi starts
+// out as an integer and stops being one once an operand isn't, so loops settle.
+fn integers(function: &Function) -> Vec<bool> {
+    let mut integers = vec![true; function.values.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in &function.blocks {
+            for &value in &block.instructions {
+                let integer = match function.op(value) {
+                    Op::Const(constant) => matches!(constant, Constant::Integer(_)),
+                    Op::Unary(TokenKind::OpBitNot, _) => true,
+                    Op::Unary(TokenKind::OpSubtract | TokenKind::OpAdd, operand) => integers[operand.0],
+                    Op::Binary(
+                        TokenKind::OpBitAnd | TokenKind::OpBitOr | TokenKind::OpBitXor | TokenKind::OpShiftLeft
+                        | TokenKind::OpShiftRight, ..
+                    ) => true,
+                    Op::Binary(
+                        TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpMultiply | TokenKind::OpDivide
+                        | TokenKind::OpMod, left, right
+                    ) => integers[left.0] && integers[right.0],
+                    Op::Phi(incoming) => incoming.iter().all(|&(_, operand)| integers[operand.0]),
+                    _ => false,
+                };
+                if integers[value.0] && !integer {
+                    integers[value.0] = false;
+                    changed = true;
+                }
+            }
+        }
+    }
+    integers
+}
+
+enum Rewrite {
+    Constant(Constant),
+    // A value that's already been computed, which its uses take instead.
+    Value(ValueId),
+    // A left shift by this many bits.
+    Shift(ValueId, u32)
+}
+
+// What an instruction can be rewritten to, if anything. Operators with constant operands
+// are evaluated, unless that would be an error; otherwise identities like `x + 0` and
+// `x * 1` give `x`, `x * 0` and `x % 1` give 0, and multiplying by a power of two is a
+// shift, which wraps where the multiplication would overflow.
+fn rewrite(function: &Function, integers: &[bool], bools: &[bool], value: ValueId) -> Option<Rewrite> {
+    let (operator, mut left, mut right) = match function.op(value) {
+        Op::Unary(operator, operand) => {
+            let folded = evaluate_unary(operator, &constant(function, *operand)?)?;
+            return to_constant(folded).map(Rewrite::Constant);
+        }
+        Op::Truthy(operand) if bools[operand.0] => return Some(Rewrite::Value(*operand)),
+        Op::Truthy(operand) => {
+            return Some(Rewrite::Constant(Constant::Bool(constant(function, *operand)?.is_truthy())));
+        }
+        Op::Binary(operator, left, right) => (operator, *left, *right),
+        _ => return None,
+    };
+    if let (Some(l), Some(r)) = (constant(function, left), constant(function, right)) {
+        return to_constant(evaluate_binary(operator, &l, &r)?).map(Rewrite::Constant);
+    }
+
+    // The constant goes on the right of operators where the order doesn't matter.
+    let commutative = matches!(
+        operator,
+        TokenKind::OpAdd | TokenKind::OpMultiply | TokenKind::OpBitAnd | TokenKind::OpBitOr | TokenKind::OpBitXor
+    );
+    if commutative && integer_constant(function, left).is_some() {
+        std::mem::swap(&mut left, &mut right);
+    }
+    if !integers[left.0] || !integers[right.0] {
+        return None;
+    }
+    let zero = Rewrite::Constant(Constant::Integer(0));
+    match (operator, integer_constant(function, right)) {
+        (TokenKind::OpSubtract | TokenKind::OpBitXor, _) if left == right => Some(zero),
+        (TokenKind::OpBitAnd | TokenKind::OpBitOr, _) if left == right => Some(Rewrite::Value(left)),
+        (
+            TokenKind::OpAdd | TokenKind::OpSubtract | TokenKind::OpBitOr | TokenKind::OpBitXor | TokenKind::OpShiftLeft
+            | TokenKind::OpShiftRight,
+            Some(0),
+        )
+        | (TokenKind::OpMultiply | TokenKind::OpDivide, Some(1))
+        | (TokenKind::OpBitAnd, Some(-1)) => Some(Rewrite::Value(left)),
+        (TokenKind::OpMultiply | TokenKind::OpBitAnd, Some(0)) | (TokenKind::OpMod, Some(1)) => Some(zero),
+        (TokenKind::OpMultiply, Some(n)) if n > 0 && n & (n - 1) == 0 => Some(Rewrite::Shift(left, n.trailing_zeros())),
+        _ => None,
+    }
+}
+
+// Folds constants and simplifies arithmetic in `function`, in the order the blocks run, so
+// that what one rewrite leaves behind can be rewritten in turn.
+pub fn simplify(function: &mut Function) {
+    let mut integers = integers(function);
+    // Calls aren't known to be bools here, which only leaves their `truthy` in place.
+    let mut bools = function.bools(|_| false);
+    for block in function.reverse_postorder() {
+        let mut instructions = Vec::new();
+        for value in std::mem::take(&mut function.blocks[block].instructions) {
+            match rewrite(function, &integers, &bools, value) {
+                Some(Rewrite::Constant(constant)) => {
+                    function.values[value.0] = Op::Const(constant);
+                    instructions.push(value);
+                }
+                Some(Rewrite::Value(other)) => function.replace_uses(value, other),
+                Some(Rewrite::Shift(operand, bits)) => {
+                    let shift = ValueId(function.values.len());
+                    function.values.push(Op::Const(Constant::Integer(bits as i64)));
+                    integers.push(true);
+                    bools.push(false);
+                    function.values[value.0] = Op::Binary(TokenKind::OpShiftLeft, operand, shift);
+                    instructions.extend([shift, value]);
+                }
+                None => instructions.push(value),
+            }
+        }
+        function.blocks[block].instructions = instructions;
+    }
+    function.remove_trivial_phis();
+    function.remove_unused();
+    function.renumber();
+}
+
+// Simplifies every function in `module`.
+pub fn simplify_module(module: &mut Module) {
+    for function in &mut module.functions {
+        simplify(function);
+        debug_assert_eq!(verify(function), Ok(()), "{}", function);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::simplify_module;
+
+    // The last function in `source` once it's been simplified.
+    fn simplified(source: &str) -> String {
+        let (tokens, errors) = tokenize(source);
+        assert!(errors.is_empty());
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let mut module = build_ssa(&program);
+        simplify_module(&mut module);
+        module.functions.last().unwrap().to_string()
+    }
+
+    #[test]
+    fn folds_constants() {
+        assert_eq!(simplified("print(2 * 3 + 1, -(4 % 3), \"a\" + \"b\");"), "\
+fn main(0) {
+b0:
+    %0 = const 7
+    print %0
+    %2 = const -1
+    print %2
+    %4 = const \"ab\"
+    print %4
+    ret
+}
+");
+    }
+
+    #[test]
+    fn leaves_errors_to_the_program() {
+        assert_eq!(simplified("print(1 / 0);"), "\
+fn main(0) {
+b0:
+    %0 = const 1
+    %1 = const 0
+    %2 = div %0, %1
+    print %2
+    ret
+}
+");
+    }
+
+    #[test]
+    fn multiplies_by_powers_of_two_with_shifts() {
+        assert_eq!(simplified("fn f(n) { x = n | 0; return x * 8 + 4 * x + x * 3; }"), "\
+fn f(1) {
+b0:
+    %0 = param 0
+    %1 = const 0
+    %2 = bor %0, %1
+    %3 = const 3
+    %4 = shl %2, %3
+    %5 = const 2
+    %6 = shl %2, %5
+    %7 = add %4, %6
+    %8 = const 3
+    %9 = mul %2, %8
+    %10 = add %7, %9
+    ret %10
+}
+");
+    }
+
+    #[test]
+    fn applies_identities() {
+        assert_eq!(simplified("fn f(n) { x = n | 0; return (x + 0) * 1 + x % 1 + (x - x) + (x & x) * 0; }"), "\
+fn f(1) {
+b0:
+    %0 = param 0
+    %1 = const 0
+    %2 = bor %0, %1
+    ret %2
+}
+");
+    }
+
+    #[test]
+    fn leaves_values_that_may_not_be_integers() {
+        // `n` could be a float or a bool, and `b` is a bool.
+        assert_eq!(simplified("fn f(n) { b = n < 1; return n * 1 + n % 1 + b * 2; }"), "\
+fn f(1) {
+b0:
+    %0 = param 0
+    %1 = const 1
+    %2 = lt %0, %1
+    %3 = const 1
+    %4 = mul %0, %3
+    %5 = const 1
+    %6 = mod %0, %5
+    %7 = add %4, %6
+    %8 = const 2
+    %9 = mul %2, %8
+    %10 = add %7, %9
+    ret %10
+}
+");
+    }
+
+    #[test]
+    fn simplifies_loop_counters() {
+        assert_eq!(simplified("i = 0; while (i < 3) { i = (i + 1) * 1; } print(i);"), "\
+fn main(0) {
+b0:
+    %0 = const 0
+    %1 = const true
+    br %1, b1, b2
+b1: ; preds b0, b1
+    %2 = phi [b0: %0], [b1: %4]
+    %3 = const 1
+    %4 = add %2, %3
+    %5 = const 3
+    %6 = lt %4, %5
+    br %6, b1, b2
+b2: ; preds b0, b1
+    %7 = phi [b0: %0], [b1: %4]
+    print %7
+    ret
+}
+");
+    }
+}
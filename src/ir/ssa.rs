@@ -0,0 +1,534 @@
+use std::fmt;
+
+use crate::intern::Symbol;
+use crate::lexer::{TokenKind, quote_string};
+
+use super::cfg::BlockId;
+
+// An instruction's result. Every instruction has one, but only some produce a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Const(Constant),
+    // The function's parameters, in order.
+    Param(usize),
+    Unary(TokenKind, ValueId),
+    Binary(TokenKind, ValueId, ValueId),
+    // Whether a value is truthy, as a bool; what `&&` and `||` give.
+    Truthy(ValueId),
+    // Calls a function or builtin by name.
+    Call(Symbol, Vec<ValueId>),
+    // Globals that a function might read or write stay in memory. Other variables are values.
+    LoadGlobal(Symbol),
+    StoreGlobal(Symbol, ValueId),
+    Array(Vec<ValueId>),
+    Index(ValueId, ValueId),
+    StoreIndex(ValueId, ValueId, ValueId),
+    Print(ValueId),
+    Putc(ValueId),
+    // The value from whichever predecessor control came from; they come first in a block.
+    Phi(Vec<(BlockId, ValueId)>)
+}
+
+impl Op {
+    pub fn has_result(&self) -> bool {
+        !matches!(self, Op::StoreGlobal(..) | Op::StoreIndex(..) | Op::Print(_) | Op::Putc(_))
+    }
+
+    // Whether leaving the instruction out could change what the program does: it calls,
+    // stores or prints, or it can fail.
+    pub fn has_effects(&self) -> bool {
+        !matches!(
+            self,
+            Op::Const(_) | Op::Param(_) | Op::Truthy(_) | Op::LoadGlobal(_) | Op::Array(_) | Op::Phi(_)
+            | Op::Unary(TokenKind::OpNot, _)
+        )
+    }
+
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Op::Const(_) | Op::Param(_) | Op::LoadGlobal(_) => Vec::new(),
+            Op::Unary(_, value) | Op::Truthy(value) | Op::StoreGlobal(_, value) | Op::Print(value) | Op::Putc(value) => vec![*value],
+            Op::Binary(_, left, right) | Op::Index(left, right) => vec![*left, *right],
+            Op::StoreIndex(array, index, value) => vec![*array, *index, *value],
+            Op::Call(_, values) | Op::Array(values) => values.clone(),
+            Op::Phi(incoming) => incoming.iter().map(|&(_, value)| value).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Op::Const(_) | Op::Param(_) | Op::LoadGlobal(_) => Vec::new(),
+            Op::Unary(_, value) | Op::Truthy(value) | Op::StoreGlobal(_, value) | Op::Print(value) | Op::Putc(value) => vec![value],
+            Op::Binary(_, left, right) | Op::Index(left, right) => vec![left, right],
+            Op::StoreIndex(array, index, value) => vec![array, index, value],
+            Op::Call(_, values) | Op::Array(values) => values.iter_mut().collect(),
+            Op::Phi(incoming) => incoming.iter_mut().map(|(_, value)| value).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    // To the first block if the value is truthy, otherwise to the second.
+    Branch(ValueId, BlockId, BlockId),
+    // The top level returns nothing.
+    Return(Option<ValueId>)
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then_block, else_block) => vec![*then_block, *else_block],
+            Terminator::Return(_) => Vec::new(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Terminator::Branch(value, ..) | Terminator::Return(Some(value)) => vec![*value],
+            Terminator::Jump(_) | Terminator::Return(None) => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Terminator::Branch(value, ..) | Terminator::Return(Some(value)) => vec![value],
+            Terminator::Jump(_) | Terminator::Return(None) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<ValueId>,
+    pub terminator: Terminator,
+    pub predecessors: Vec<BlockId>
+}
+
+// A function in SSA form. Instructions live in `values` and blocks list theirs in order;
+// the first block is the entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    // `main` for the top level.
+    pub name: String,
+    pub parameters: usize,
+    pub blocks: Vec<Block>,
+    pub values: Vec<Op>
+}
+
+impl Function {
+    pub fn op(&self, value: ValueId) -> &Op {
+        &self.values[value.0]
+    }
+
+    // The blocks control can reach, each after the blocks that dominate it.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.blocks.len()];
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            // The last successor is visited first, so the first ends up right after the block.
+            let successors = self.blocks[block].terminator.successors();
+            if let Some(&successor) = successors.iter().rev().nth(next) {
+                stack.push((block, next + 1));
+                if !std::mem::replace(&mut visited[successor], true) {
+                    stack.push((successor, 0));
+                }
+            } else {
+                order.push(block);
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    // Each block's immediate dominator, the entry's being itself, or `None` for blocks
+    // control never reaches.
+    pub fn dominators(&self) -> Vec<Option<BlockId>> {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            position[block] = i;
+        }
+
+        let mut dominators = vec![None; self.blocks.len()];
+        dominators[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut reached = self.blocks[block].predecessors.iter().copied().filter(|&p| dominators[p].is_some());
+                let Some(first) = reached.next() else { continue };
+                let dominator = reached.fold(first, |mut a, mut b| {
+                    while a != b {
+                        while position[a] > position[b] {
+                            a = dominators[a].unwrap();
+                        }
+                        while position[b] > position[a] {
+                            b = dominators[b].unwrap();
+                        }
+                    }
+                    a
+                });
+                if dominators[block] != Some(dominator) {
+                    dominators[block] = Some(dominator);
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    // Which values are always bools, and so print as `true` or `false`: comparisons, `!`,
+    // truthiness and bool constants, phis whose operands all are, and calls `returns_bool`
+    // says yes to. Every phi starts out as a bool and stops being one once an operand
+    // isn't, so loops settle.
+    pub fn bools(&self, returns_bool: impl Fn(Symbol) -> bool) -> Vec<bool> {
+        let mut bools = vec![true; self.values.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &self.blocks {
+                for &value in &block.instructions {
+                    let bool = match self.op(value) {
+                        Op::Const(constant) => matches!(constant, Constant::Bool(_)),
+                        Op::Truthy(_) | Op::Unary(TokenKind::OpNot, _) => true,
+                        Op::Binary(operator, ..) => matches!(
+                            operator,
+                            TokenKind::OpLess | TokenKind::OpGreater | TokenKind::OpLessEqual
+                            | TokenKind::OpGreaterEqual | TokenKind::OpEqual | TokenKind::OpNotEqual
+                        ),
+                        Op::Phi(incoming) => incoming.iter().all(|&(_, operand)| bools[operand.0]),
+                        Op::Call(callee, _) => returns_bool(*callee),
+                        _ => false,
+                    };
+                    if bools[value.0] && !bool {
+                        bools[value.0] = false;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        bools
+    }
+
+    // Whether every path from the entry to `block` goes through `dominator`.
+    pub fn dominates(dominators: &[Option<BlockId>], dominator: BlockId, mut block: BlockId) -> bool {
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match dominators[block] {
+                Some(next) if next != block => block = next,
+                _ => return false,
+            }
+        }
+    }
+
+    // Puts the blocks in reverse postorder, dropping those control can't reach along with
+    // their phi operands.
+    pub fn remove_unreachable(&mut self) {
+        let order = self.reverse_postorder();
+        let mut moved = vec![None; self.blocks.len()];
+        for (new, &old) in order.iter().enumerate() {
+            moved[old] = Some(new);
+        }
+        let mut blocks: Vec<Option<Block>> = std::mem::take(&mut self.blocks).into_iter().map(Some).collect();
+        self.blocks = order.iter().map(|&old| blocks[old].take().unwrap()).collect();
+        for block in &mut self.blocks {
+            block.predecessors = block.predecessors.iter().filter_map(|&p| moved[p]).collect();
+            match &mut block.terminator {
+                Terminator::Jump(target) => *target = moved[*target].unwrap(),
+                Terminator::Branch(_, then_block, else_block) => {
+                    *then_block = moved[*then_block].unwrap();
+                    *else_block = moved[*else_block].unwrap();
+                }
+                Terminator::Return(_) => {}
+            }
+            for &value in &block.instructions {
+                if let Op::Phi(incoming) = &mut self.values[value.0] {
+                    *incoming = incoming.iter().filter_map(|&(p, v)| Some((moved[p]?, v))).collect();
+                }
+            }
+        }
+    }
+
+    // Replaces every use of `from` with `to`.
+    pub fn replace_uses(&mut self, from: ValueId, to: ValueId) {
+        for op in &mut self.values {
+            for operand in op.operands_mut() {
+                if *operand == from {
+                    *operand = to;
+                }
+            }
+        }
+        for block in &mut self.blocks {
+            for operand in block.terminator.operands_mut() {
+                if *operand == from {
+                    *operand = to;
+                }
+            }
+        }
+    }
+
+    // A phi whose operands are all one value, or itself, is that value.
+    pub fn remove_trivial_phis(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 0..self.blocks.len() {
+                for i in (0..self.blocks[block].instructions.len()).rev() {
+                    let phi = self.blocks[block].instructions[i];
+                    let Op::Phi(incoming) = &self.values[phi.0] else { continue };
+                    let mut others = incoming.iter().map(|&(_, v)| v).filter(|&v| v != phi);
+                    let Some(same) = others.next() else { continue };
+                    if others.all(|v| v == same) {
+                        self.blocks[block].instructions.remove(i);
+                        self.replace_uses(phi, same);
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    // Drops instructions that can be left out and whose values nothing uses.
+    pub fn remove_unused(&mut self) {
+        let mut changed = true;
+        while changed {
+            let mut used = vec![false; self.values.len()];
+            for block in &self.blocks {
+                let operands = block.instructions.iter().flat_map(|&value| self.op(value).operands());
+                for operand in operands.chain(block.terminator.operands()) {
+                    used[operand.0] = true;
+                }
+            }
+            changed = false;
+            for block in &mut self.blocks {
+                let before = block.instructions.len();
+                block.instructions.retain(|&value| used[value.0] || self.values[value.0].has_effects());
+                changed |= block.instructions.len() < before;
+            }
+        }
+    }
+
+    // Numbers the values in the order they appear, dropping any that no block has.
+    pub fn renumber(&mut self) {
+        let mut moved = vec![None; self.values.len()];
+        let mut values = Vec::new();
+        for block in &self.blocks {
+            for &value in &block.instructions {
+                moved[value.0] = Some(ValueId(values.len()));
+                values.push(self.values[value.0].clone());
+            }
+        }
+        let renumber = |value: &mut ValueId| *value = moved[value.0].expect("use of a value no block defines");
+        for op in &mut values {
+            op.operands_mut().into_iter().for_each(renumber);
+        }
+        for block in &mut self.blocks {
+            block.instructions.iter_mut().for_each(renumber);
+            block.terminator.operands_mut().into_iter().for_each(renumber);
+        }
+        self.values = values;
+    }
+}
+
+// The top level, then each function in the order they're declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub functions: Vec<Function>
+}
+
+impl Module {
+    // Which values of each function are always bools, as `Function::bools` says, where a
+    // call is one when everything the function it calls returns is. Every function starts
+    // out returning bools and stops once it returns something else, so recursion settles.
+    pub fn bools(&self) -> Vec<Vec<bool>> {
+        let mut returns = vec![true; self.functions.len()];
+        loop {
+            let bools: Vec<Vec<bool>> = self.functions.iter().map(|function| {
+                function.bools(|callee| {
+                    let called = self.functions[1..].iter().position(|f| f.name == callee.as_str());
+                    called.is_some_and(|i| returns[i + 1])
+                })
+            }).collect();
+            let next: Vec<bool> = self.functions.iter().zip(&bools).map(|(function, bools)| {
+                function.blocks.iter().all(|block| match block.terminator {
+                    Terminator::Return(Some(value)) => bools[value.0],
+                    _ => true,
+                })
+            }).collect();
+            if next == returns {
+                return bools;
+            }
+            returns = next;
+        }
+    }
+}
+
+fn mnemonic(operator: &TokenKind) -> &'static str {
+    match operator {
+        TokenKind::OpAdd => "add",
+        TokenKind::OpSubtract => "sub",
+        TokenKind::OpMultiply => "mul",
+        TokenKind::OpDivide => "div",
+        TokenKind::OpMod => "mod",
+        TokenKind::OpLess => "lt",
+        TokenKind::OpGreater => "gt",
+        TokenKind::OpLessEqual => "le",
+        TokenKind::OpGreaterEqual => "ge",
+        TokenKind::OpEqual => "eq",
+        TokenKind::OpNotEqual => "ne",
+        TokenKind::OpNot => "not",
+        TokenKind::OpBitAnd => "band",
+        TokenKind::OpBitOr => "bor",
+        TokenKind::OpBitXor => "bxor",
+        TokenKind::OpBitNot => "bnot",
+        TokenKind::OpShiftLeft => "shl",
+        TokenKind::OpShiftRight => "shr",
+        _ => "?",
+    }
+}
+
+fn list(values: &[ValueId]) -> String {
+    values.iter().map(ValueId::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Const(Constant::Integer(n)) => write!(f, "const {}", n),
+            Op::Const(Constant::Float(x)) => write!(f, "const {:?}", x),
+            Op::Const(Constant::Bool(b)) => write!(f, "const {}", b),
+            Op::Const(Constant::String(s)) => write!(f, "const {}", quote_string(s)),
+            Op::Param(index) => write!(f, "param {}", index),
+            // Unary `+` is `x + 0`, keeping the number's type.
+            Op::Unary(TokenKind::OpSubtract, value) => write!(f, "neg {}", value),
+            Op::Unary(TokenKind::OpAdd, value) => write!(f, "pos {}", value),
+            Op::Unary(operator, value) => write!(f, "{} {}", mnemonic(operator), value),
+            Op::Binary(operator, left, right) => write!(f, "{} {}, {}", mnemonic(operator), left, right),
+            Op::Truthy(value) => write!(f, "truthy {}", value),
+            Op::Call(callee, arguments) => write!(f, "call {}({})", callee, list(arguments)),
+            Op::LoadGlobal(name) => write!(f, "load @{}", name),
+            Op::StoreGlobal(name, value) => write!(f, "store @{}, {}", name, value),
+            Op::Array(elements) => write!(f, "array [{}]", list(elements)),
+            Op::Index(array, index) => write!(f, "index {}, {}", array, index),
+            Op::StoreIndex(array, index, value) => write!(f, "store {}[{}], {}", array, index, value),
+            Op::Print(value) => write!(f, "print {}", value),
+            Op::Putc(value) => write!(f, "putc {}", value),
+            Op::Phi(incoming) => {
+                let incoming: Vec<String> = incoming.iter().map(|(block, value)| format!("[b{}: {}]", block, value)).collect();
+                write!(f, "phi {}", incoming.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jmp b{}", target),
+            Terminator::Branch(condition, then_block, else_block) => write!(f, "br {}, b{}, b{}", condition, then_block, else_block),
+            Terminator::Return(Some(value)) => write!(f, "ret {}", value),
+            Terminator::Return(None) => write!(f, "ret"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "fn {}({}) {{", self.name, self.parameters)?;
+        for (id, block) in self.blocks.iter().enumerate() {
+            write!(f, "b{}:", id)?;
+            if !block.predecessors.is_empty() {
+                let predecessors: Vec<String> = block.predecessors.iter().map(|p| format!("b{}", p)).collect();
+                write!(f, " ; preds {}", predecessors.join(", "))?;
+            }
+            writeln!(f)?;
+            for &value in &block.instructions {
+                match self.op(value) {
+                    op if op.has_result() => writeln!(f, "    {} = {}", value, op)?,
+                    op => writeln!(f, "    {}", op)?,
+                }
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::build_ssa;
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::*;
+
+    fn module(source: &str) -> Module {
+        let (tokens, errors) = tokenize(source);
+        assert!(errors.is_empty());
+        build_ssa(&parse_program(&mut Parser::new(tokens)).unwrap())
+    }
+
+    #[test]
+    fn finds_each_blocks_immediate_dominator() {
+        // b0 branches on `a`, b2 joins and branches on `a && b`, and b4 joins `||`.
+        let function = module("fn f(a, b) { return a && b || !a; }").functions.pop().unwrap();
+        let dominators = function.dominators();
+        assert_eq!(dominators, [Some(0), Some(0), Some(0), Some(2), Some(2)]);
+        assert!(Function::dominates(&dominators, 2, 4));
+        assert!(!Function::dominates(&dominators, 1, 2));
+        assert_eq!(function.reverse_postorder()[0], 0);
+    }
+
+    #[test]
+    fn knows_which_calls_give_bools_through_recursion() {
+        let module = module("\
+fn even(n) { return n == 0 ? true : !even(n - 1); }
+fn next(n) { return n + 1; }
+print(even(2), next(1));
+");
+        let main = &module.functions[0];
+        let bools = module.bools();
+        let calls: Vec<(String, bool)> = main.blocks.iter().flat_map(|block| &block.instructions)
+            .filter_map(|&value| match main.op(value) {
+                Op::Call(callee, _) => Some((callee.as_str().to_string(), bools[0][value.0])),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, [("even".to_string(), true), ("next".to_string(), false)]);
+    }
+}
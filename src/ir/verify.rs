@@ -0,0 +1,132 @@
+use super::ssa::{Function, Op, Terminator, ValueId};
+
+// Checks that `function` is well formed: its jumps go to blocks it has, each block's
+// predecessors are the blocks that jump to it, phis come first with one operand for each
+// predecessor, every instruction is in exactly one block, and every use is dominated by the
+// instruction it uses. Unreachable blocks are allowed, but only reachable uses are checked.
+pub fn verify(function: &Function) -> Result<(), String> {
+    let name = &function.name;
+    if function.blocks.is_empty() {
+        return Err(format!("`{}` has no blocks", name));
+    }
+
+    let mut predecessors = vec![Vec::new(); function.blocks.len()];
+    for (id, block) in function.blocks.iter().enumerate() {
+        for successor in block.terminator.successors() {
+            let Some(predecessors) = predecessors.get_mut(successor) else {
+                return Err(format!("b{} in `{}` jumps to b{}, which doesn't exist", id, name, successor));
+            };
+            predecessors.push(id);
+        }
+    }
+    for (id, (block, expected)) in function.blocks.iter().zip(&mut predecessors).enumerate() {
+        let mut actual = block.predecessors.clone();
+        actual.sort_unstable();
+        expected.sort_unstable();
+        if actual != *expected {
+            return Err(format!("b{} in `{}` lists the wrong predecessors", id, name));
+        }
+    }
+
+    // Where each value is defined: its block and its position there.
+    let mut defined = vec![None; function.values.len()];
+    for (id, block) in function.blocks.iter().enumerate() {
+        let mut phis = true;
+        for (position, &value) in block.instructions.iter().enumerate() {
+            let Some(slot) = defined.get_mut(value.0) else {
+                return Err(format!("b{} in `{}` has {}, which doesn't exist", id, name, value));
+            };
+            if slot.replace((id, position)).is_some() {
+                return Err(format!("{} is in more than one place in `{}`", value, name));
+            }
+            match function.op(value) {
+                Op::Phi(incoming) => {
+                    if !phis {
+                        return Err(format!("phi {} in `{}` comes after other instructions", value, name));
+                    }
+                    let mut from: Vec<_> = incoming.iter().map(|&(block, _)| block).collect();
+                    from.sort_unstable();
+                    if from != predecessors[id] {
+                        return Err(format!("phi {} in `{}` doesn't have one operand for each predecessor", value, name));
+                    }
+                }
+                _ => phis = false,
+            }
+        }
+    }
+
+    let dominators = function.dominators();
+    let dominated = |value: ValueId, block: usize, position: usize| -> Result<(), String> {
+        let Some(Some((at, defined_at))) = defined.get(value.0).copied() else {
+            return Err(format!("{} is used in `{}` but never defined", value, name));
+        };
+        if !function.op(value).has_result() {
+            return Err(format!("{} is used in `{}` but has no result", value, name));
+        }
+        let dominates = if at == block { defined_at < position } else { Function::dominates(&dominators, at, block) };
+        if dominates {
+            Ok(())
+        } else {
+            Err(format!("{} is used in `{}` somewhere it isn't defined", value, name))
+        }
+    };
+    for (id, block) in function.blocks.iter().enumerate() {
+        if dominators[id].is_none() {
+            continue;
+        }
+        for (position, &value) in block.instructions.iter().enumerate() {
+            match function.op(value) {
+                // A phi's operand must be available at the end of the block it comes from.
+                Op::Phi(incoming) => {
+                    for &(from, operand) in incoming {
+                        if dominators[from].is_some() {
+                            dominated(operand, from, usize::MAX)?;
+                        }
+                    }
+                }
+                op => op.operands().into_iter().try_for_each(|operand| dominated(operand, id, position))?,
+            }
+        }
+        if let Terminator::Branch(value, ..) | Terminator::Return(Some(value)) = block.terminator {
+            dominated(value, id, usize::MAX)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::{Function, Op, Terminator, ValueId, build_ssa};
+    use crate::lexer::TokenKind;
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::verify;
+
+    #[test]
+    fn rejects_functions_that_arent_well_formed() {
+        // b0 branches on `n` to b1, which has `x = 2`, or to b2. b3 joins them with the phi
+        // `%3` and adds `n` to it.
+        let (tokens, _) = tokenize("fn f(n) { x = 1; if (n) { x = 2; } return x + n; }");
+        let function = build_ssa(&parse_program(&mut Parser::new(tokens)).unwrap()).functions.pop().unwrap();
+        assert_eq!(verify(&function), Ok(()));
+
+        let broken = |change: &dyn Fn(&mut Function)| {
+            let mut function = function.clone();
+            change(&mut function);
+            verify(&function).unwrap_err()
+        };
+        assert_eq!(broken(&|f| f.blocks[0].terminator = Terminator::Jump(9)), "b0 in `f` jumps to b9, which doesn't exist");
+        assert_eq!(broken(&|f| f.blocks[1].predecessors.clear()), "b1 in `f` lists the wrong predecessors");
+        assert_eq!(broken(&|f| {
+            let first = f.blocks[0].instructions[0];
+            f.blocks[1].instructions.push(first);
+        }), "%0 is in more than one place in `f`");
+        assert_eq!(broken(&|f| {
+            let phi = f.blocks[3].instructions.remove(0);
+            f.blocks[3].instructions.push(phi);
+        }), "phi %3 in `f` comes after other instructions");
+        // `%2`, in b1, made to use the sum `%4` from b3.
+        assert_eq!(broken(&|f| f.values[2] = Op::Unary(TokenKind::OpNot, ValueId(4))), "%4 is used in `f` somewhere it isn't defined");
+        assert_eq!(broken(&|f| f.blocks[3].terminator = Terminator::Return(Some(ValueId(99)))), "%99 is used in `f` but never defined");
+    }
+}
@@ -0,0 +1,240 @@
+use crate::intern::Symbol;
+use crate::ir::{Constant, Function, Module, Op, Terminator, ValueId};
+use crate::lexer::TokenKind;
+
+use super::runtime;
+use super::x86::{ARGUMENTS, Alu, Assembler, Condition, Label, RAX, RCX, RDI, RSI, Reg};
+
+fn unsupported(what: &str) -> String {
+    format!("{} aren't supported by the JIT", what)
+}
+
+// Every value has a slot in its function's stack frame, below the saved `rbp`.
+fn slot(value: ValueId) -> i32 {
+    -8 * (value.0 as i32 + 1)
+}
+
+fn comparison(operator: &TokenKind) -> Option<Condition> {
+    match operator {
+        TokenKind::OpLess => Some(Condition::Less),
+        TokenKind::OpGreater => Some(Condition::Greater),
+        TokenKind::OpLessEqual => Some(Condition::LessEqual),
+        TokenKind::OpGreaterEqual => Some(Condition::GreaterEqual),
+        TokenKind::OpEqual => Some(Condition::Equal),
+        TokenKind::OpNotEqual => Some(Condition::NotEqual),
+        _ => None,
+    }
+}
+
+pub(super) struct Lowering<'a> {
+    module: &'a Module,
+    asm: Assembler,
+    // Each function's entry, in the module's order.
+    functions: Vec<Label>,
+    globals: &'a [Symbol],
+    // The address of the first global's cell; the rest follow it.
+    global_base: usize,
+    // Which values of each function are bools, from `Module::bools`.
+    bools: Vec<Vec<bool>>,
+    // String literals that are printed, which the code points into.
+    strings: Vec<String>
+}
+
+impl<'a> Lowering<'a> {
+    pub(super) fn new(module: &'a Module, globals: &'a [Symbol], global_base: usize) -> Self {
+        let mut asm = Assembler::default();
+        let functions = module.functions.iter().map(|_| asm.label()).collect();
+        Lowering { module, asm, functions, globals, global_base, bools: module.bools(), strings: Vec::new() }
+    }
+
+    // Integer and bool constants are written where they're used. Every value is an
+    // integer: bools are 0 or 1.
+    fn load(&mut self, reg: Reg, function: &Function, value: ValueId) -> Result<(), String> {
+        match function.op(value) {
+            Op::Const(Constant::Integer(n)) => self.asm.immediate(reg, *n),
+            Op::Const(Constant::Bool(b)) => self.asm.immediate(reg, *b as i64),
+            Op::Const(Constant::Float(_)) => return Err(unsupported("floating point values")),
+            Op::Const(Constant::String(_)) => return Err(unsupported("strings other than print arguments")),
+            _ => self.asm.load(reg, slot(value)),
+        }
+        Ok(())
+    }
+
+    fn global(&self, name: &Symbol) -> i64 {
+        let index = self.globals.iter().position(|global| global == name).unwrap();
+        (self.global_base + 8 * index) as i64
+    }
+
+    fn instruction(&mut self, function: &Function, bools: &[bool], value: ValueId) -> Result<(), String> {
+        match function.op(value) {
+            Op::Const(_) | Op::Param(_) | Op::Phi(_) => return Ok(()),
+            Op::Unary(operator, operand) => {
+                self.load(RAX, function, *operand)?;
+                match operator {
+                    TokenKind::OpSubtract => self.asm.negate(),
+                    TokenKind::OpAdd => {}
+                    TokenKind::OpBitNot => self.asm.not(),
+                    _ => self.asm.test(Condition::Equal),
+                }
+            }
+            Op::Binary(operator @ (TokenKind::OpDivide | TokenKind::OpMod), left, right) => {
+                self.load(RDI, function, *left)?;
+                self.load(RSI, function, *right)?;
+                let helper = if *operator == TokenKind::OpDivide { runtime::divide as *const () } else { runtime::remainder as *const () };
+                self.asm.call_address(helper);
+            }
+            Op::Binary(operator, left, right) => {
+                self.load(RAX, function, *left)?;
+                self.load(RCX, function, *right)?;
+                if let Some(condition) = comparison(operator) {
+                    self.asm.compare(condition);
+                } else {
+                    let op = match operator {
+                        TokenKind::OpAdd => Alu::Add,
+                        TokenKind::OpSubtract => Alu::Sub,
+                        TokenKind::OpMultiply => Alu::Mul,
+                        TokenKind::OpBitAnd => Alu::And,
+                        TokenKind::OpBitOr => Alu::Or,
+                        TokenKind::OpBitXor => Alu::Xor,
+                        // Shift counts wrap modulo 64, as in the interpreter.
+                        TokenKind::OpShiftLeft => Alu::Shl,
+                        TokenKind::OpShiftRight => Alu::Sar,
+                        operator => return Err(format!("`{}` isn't supported by the JIT", operator)),
+                    };
+                    self.asm.alu(op);
+                }
+            }
+            Op::Truthy(operand) => {
+                self.load(RAX, function, *operand)?;
+                self.asm.test(Condition::NotEqual);
+            }
+            Op::Call(callee, arguments) => {
+                let Some(index) = self.module.functions.iter().skip(1).position(|f| f.name == callee.as_str()) else {
+                    return Err(format!("`{}` isn't supported by the JIT", callee));
+                };
+                let parameters = self.module.functions[index + 1].parameters;
+                if parameters != arguments.len() {
+                    return Err(format!("`{}` takes {} arguments but {} were given", callee, parameters, arguments.len()));
+                }
+                let name = callee.as_str();
+                self.asm.immediate(RDI, name.as_ptr() as i64);
+                self.asm.immediate(RSI, name.len() as i64);
+                self.asm.call_address(runtime::enter as *const ());
+                for (&argument, &reg) in arguments.iter().zip(&ARGUMENTS) {
+                    self.load(reg, function, argument)?;
+                }
+                self.asm.call(self.functions[index + 1]);
+                self.asm.store(slot(value), RAX);
+                self.asm.call_address(runtime::leave as *const ());
+                return Ok(());
+            }
+            Op::LoadGlobal(name) => {
+                self.asm.immediate(RCX, self.global(name));
+                self.asm.load_indirect();
+            }
+            Op::StoreGlobal(name, operand) => {
+                self.load(RAX, function, *operand)?;
+                self.asm.immediate(RCX, self.global(name));
+                self.asm.store_indirect();
+                return Ok(());
+            }
+            Op::Array(_) | Op::Index(..) | Op::StoreIndex(..) => return Err(unsupported("arrays")),
+            Op::Print(operand) => {
+                let op = function.op(*operand);
+                if let Op::Const(Constant::String(s)) = op {
+                    // The copy's bytes stay where they are when it's moved into the `Jit`.
+                    self.strings.push(s.clone());
+                    let kept = self.strings.last().unwrap();
+                    let (pointer, len) = (kept.as_ptr() as i64, kept.len() as i64);
+                    self.asm.immediate(RDI, pointer);
+                    self.asm.immediate(RSI, len);
+                    self.asm.call_address(runtime::print_string as *const ());
+                } else {
+                    self.load(RDI, function, *operand)?;
+                    let helper = if bools[operand.0] { runtime::print_bool as *const () } else { runtime::print_integer as *const () };
+                    self.asm.call_address(helper);
+                }
+                return Ok(());
+            }
+            Op::Putc(operand) => {
+                self.load(RDI, function, *operand)?;
+                self.asm.call_address(runtime::putc as *const ());
+                return Ok(());
+            }
+        }
+        self.asm.store(slot(value), RAX);
+        Ok(())
+    }
+
+    // Gives the phis at the start of `to` their values for control coming from `from`. The
+    // values all go on the stack first, as one phi may be another's operand.
+    fn edge(&mut self, function: &Function, from: usize, to: usize) -> Result<(), String> {
+        let phis: Vec<(ValueId, ValueId)> = function.blocks[to].instructions.iter()
+            .map_while(|&phi| match function.op(phi) {
+                Op::Phi(incoming) => Some((phi, incoming.iter().find(|&&(block, _)| block == from).unwrap().1)),
+                _ => None,
+            })
+            .collect();
+        for &(_, value) in &phis {
+            self.load(RAX, function, value)?;
+            self.asm.push_rax();
+        }
+        for &(phi, _) in phis.iter().rev() {
+            self.asm.pop_rax();
+            self.asm.store(slot(phi), RAX);
+        }
+        Ok(())
+    }
+
+    pub(super) fn function(&mut self, index: usize) -> Result<(), String> {
+        let function = &self.module.functions[index];
+        if function.parameters > ARGUMENTS.len() {
+            return Err(unsupported(&format!("functions with more than {} parameters", ARGUMENTS.len())));
+        }
+        let bools = self.bools[index].clone();
+        let blocks: Vec<Label> = function.blocks.iter().map(|_| self.asm.label()).collect();
+        self.asm.place(self.functions[index]);
+        let size = (function.values.len() * 8).next_multiple_of(16);
+        self.asm.prologue(i32::try_from(size).map_err(|_| unsupported("functions this large"))?);
+        for (value, op) in function.values.iter().enumerate() {
+            if let Op::Param(i) = op {
+                self.asm.store(slot(ValueId(value)), ARGUMENTS[*i]);
+            }
+        }
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            self.asm.place(blocks[id]);
+            for &value in &block.instructions {
+                self.instruction(function, &bools, value)?;
+            }
+            match &block.terminator {
+                Terminator::Jump(target) => {
+                    self.edge(function, id, *target)?;
+                    self.asm.jump(blocks[*target]);
+                }
+                Terminator::Branch(condition, then_block, else_block) => {
+                    let otherwise = self.asm.label();
+                    self.load(RAX, function, *condition)?;
+                    self.asm.test_rax();
+                    self.asm.jump_if_zero(otherwise);
+                    self.edge(function, id, *then_block)?;
+                    self.asm.jump(blocks[*then_block]);
+                    self.asm.place(otherwise);
+                    self.edge(function, id, *else_block)?;
+                    self.asm.jump(blocks[*else_block]);
+                }
+                Terminator::Return(value) => {
+                    if let Some(value) = value {
+                        self.load(RAX, function, *value)?;
+                    }
+                    self.asm.epilogue();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn finish(self) -> (Vec<u8>, Vec<String>) {
+        (self.asm.finish(), self.strings)
+    }
+}
@@ -0,0 +1,111 @@
+// Compiles a module in SSA form to x86-64 machine code in memory and runs it. Every value
+// is a 64-bit integer, as in the LLVM backend, so programs with floats, arrays, builtins or
+// strings other than print arguments can't be compiled; callers run those some other way.
+// The machine code is encoded here rather than by Cranelift, which the crate doesn't depend
+// on and which would need signing off on before it's added; x86.rs has the handful of
+// instructions that takes.
+
+mod lower;
+mod runtime;
+mod x86;
+
+use std::cell::Cell;
+use std::ffi::{c_int, c_void};
+use std::{io, ptr};
+
+use crate::intern::Symbol;
+use crate::ir::{Module, Op};
+
+use lower::Lowering;
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const PROT_EXEC: c_int = 4;
+const MAP_PRIVATE: c_int = 2;
+const MAP_ANONYMOUS: c_int = 0x20;
+
+unsafe extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+// Machine code in a mapping of its own, which is made executable once it's been written,
+// and never writable again.
+struct Code {
+    ptr: *mut c_void,
+    len: usize
+}
+
+impl Code {
+    fn new(bytes: &[u8]) -> io::Result<Self> {
+        let len = bytes.len();
+        // SAFETY: a fresh anonymous mapping aliases nothing.
+        let ptr = unsafe { mmap(ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0) };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let code = Code { ptr, len };
+        // SAFETY: the mapping is `len` writable bytes that nothing else refers to.
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, len) };
+        // SAFETY: `ptr` and `len` describe the mapping just made.
+        if unsafe { mprotect(ptr, len, PROT_READ | PROT_EXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(code)
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` describe a mapping made by `new` whose code isn't running.
+        unsafe { munmap(self.ptr, self.len) };
+    }
+}
+
+pub struct Jit {
+    code: Code,
+    // The cells the code reads and writes globals in.
+    globals: Box<[Cell<i64>]>,
+    // The strings the code prints, which it points into.
+    _strings: Vec<String>
+}
+
+impl Jit {
+    // Compiles every function in `module`, or says what it uses that can't be compiled.
+    pub fn compile(module: &Module) -> Result<Self, String> {
+        let mut names: Vec<Symbol> = Vec::new();
+        for function in &module.functions {
+            for block in &function.blocks {
+                for &value in &block.instructions {
+                    if let Op::LoadGlobal(name) | Op::StoreGlobal(name, _) = function.op(value)
+                        && !names.contains(name)
+                    {
+                        names.push(*name);
+                    }
+                }
+            }
+        }
+        let globals: Box<[Cell<i64>]> = names.iter().map(|_| Cell::new(0)).collect();
+
+        // The top level comes first, so the code starts with it.
+        let mut lowering = Lowering::new(module, &names, globals.as_ptr() as usize);
+        for index in 0..module.functions.len() {
+            lowering.function(index)?;
+        }
+        let (bytes, strings) = lowering.finish();
+        let code = Code::new(&bytes).map_err(|error| format!("couldn't map memory for the JIT: {}", error))?;
+        Ok(Jit { code, globals, _strings: strings })
+    }
+
+    // Runs the top level, with every global starting out as 0.
+    pub fn run(&self) {
+        for global in &self.globals {
+            global.set(0);
+        }
+        // SAFETY: the code starts with the top level, which takes no arguments and follows the
+        // C calling convention; everything it points at lives as long as `self`.
+        let entry: extern "C" fn() = unsafe { std::mem::transmute(self.code.ptr) };
+        entry();
+    }
+}
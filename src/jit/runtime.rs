@@ -0,0 +1,77 @@
+// What compiled code calls for anything more than arithmetic. Each takes its arguments the
+// way the System V calling convention passes integers.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::{io, process, slice};
+
+use crate::intern::Symbol;
+use crate::interpreter::{MAX_CALL_DEPTH, division_by_zero, nested_too_deep};
+use crate::lexer::TokenKind;
+
+thread_local! {
+    // The functions being called, outermost first.
+    static CALLS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+// Stops the program the way a runtime error in the interpreter does, with its code and
+// message, keeping what it's printed so far. Compiled code doesn't know where it came from
+// in the source, so there's no span.
+fn fail(code: &str, message: &str) -> ! {
+    let _ = io::stdout().flush();
+    eprintln!("error[{}]: {}", code, message);
+    process::exit(1);
+}
+
+// The lowest integer divided by -1 wraps, as in the interpreter.
+pub(super) extern "C" fn divide(left: i64, right: i64) -> i64 {
+    match right {
+        0 => fail("E0406", division_by_zero(&TokenKind::OpDivide)),
+        _ => left.wrapping_div(right),
+    }
+}
+
+pub(super) extern "C" fn remainder(left: i64, right: i64) -> i64 {
+    match right {
+        0 => fail("E0406", division_by_zero(&TokenKind::OpMod)),
+        _ => left.wrapping_rem(right),
+    }
+}
+
+// Called before each call, with the callee's name, which starts the frame off; calls nest
+// at most as deep as in the interpreter.
+pub(super) extern "C" fn enter(pointer: *const u8, len: usize) {
+    // SAFETY: the name is a `&'static str` of an interned `Symbol`.
+    let name = unsafe { std::str::from_utf8_unchecked(slice::from_raw_parts(pointer, len)) };
+    CALLS.with_borrow_mut(|calls| {
+        calls.push(name);
+        if calls.len() > MAX_CALL_DEPTH {
+            let chain: Vec<Symbol> = calls.iter().map(|&name| Symbol::intern(name)).collect();
+            fail("E0405", &nested_too_deep(&chain));
+        }
+    });
+}
+
+// Called once a call has returned.
+pub(super) extern "C" fn leave() {
+    CALLS.with_borrow_mut(|calls| calls.pop());
+}
+
+pub(super) extern "C" fn print_integer(value: i64) {
+    print!("{}", value);
+}
+
+pub(super) extern "C" fn print_bool(value: i64) {
+    print!("{}", value != 0);
+}
+
+// `pointer` and `len` are a string the `Jit` keeps for as long as its code can run.
+pub(super) extern "C" fn print_string(pointer: *const u8, len: usize) {
+    // SAFETY: the bytes are a live `String`'s, so they're valid UTF-8.
+    let s = unsafe { std::str::from_utf8_unchecked(slice::from_raw_parts(pointer, len)) };
+    print!("{}", s);
+}
+
+pub(super) extern "C" fn putc(code: i64) {
+    print!("{}", char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+}
@@ -0,0 +1,285 @@
+// Just enough of x86-64 machine code for the JIT: 64-bit moves between registers and
+// stack slots, arithmetic on `rax` and `rcx`, and jumps and calls to labels.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) struct Reg(u8);
+
+pub(super) const RAX: Reg = Reg(0);
+pub(super) const RCX: Reg = Reg(1);
+pub(super) const RDX: Reg = Reg(2);
+pub(super) const RSI: Reg = Reg(6);
+pub(super) const RDI: Reg = Reg(7);
+pub(super) const R8: Reg = Reg(8);
+pub(super) const R9: Reg = Reg(9);
+
+// Where the System V calling convention passes the first six integer arguments.
+pub(super) const ARGUMENTS: [Reg; 6] = [RDI, RSI, RDX, RCX, R8, R9];
+
+// An operation on `rax` and `rcx` that leaves its result in `rax`.
+#[derive(Clone, Copy)]
+pub(super) enum Alu {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Mul,
+    // Shifts by `cl`, which the processor masks to the low six bits.
+    Shl,
+    Sar
+}
+
+// The flags `setcc` tests after a signed comparison.
+#[derive(Clone, Copy)]
+pub(super) enum Condition {
+    Equal = 0x4,
+    NotEqual = 0x5,
+    Less = 0xC,
+    GreaterEqual = 0xD,
+    LessEqual = 0xE,
+    Greater = 0xF
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) struct Label(usize);
+
+#[derive(Default)]
+pub(super) struct Assembler {
+    code: Vec<u8>,
+    // Where each label is, once it's been placed.
+    labels: Vec<Option<usize>>,
+    // The 32-bit displacements to fill in with the distance to a label.
+    fixups: Vec<(usize, Label)>
+}
+
+impl Assembler {
+    pub(super) fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub(super) fn place(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    // The REX prefix for a 64-bit operation, extended for registers past `rdi`.
+    fn rex(&mut self, reg: Reg, rm: Reg) {
+        self.code.push(0x48 | ((reg.0 >> 3) << 2) | (rm.0 >> 3));
+    }
+
+    // `[rbp + offset]`, with `reg` in the ModRM byte.
+    fn frame_operand(&mut self, reg: Reg, offset: i32) {
+        self.code.push(0x80 | ((reg.0 & 7) << 3) | 5);
+        self.bytes(&offset.to_le_bytes());
+    }
+
+    // `mov reg, [rbp + offset]`
+    pub(super) fn load(&mut self, reg: Reg, offset: i32) {
+        self.rex(reg, RAX);
+        self.code.push(0x8B);
+        self.frame_operand(reg, offset);
+    }
+
+    // `mov [rbp + offset], reg`
+    pub(super) fn store(&mut self, offset: i32, reg: Reg) {
+        self.rex(reg, RAX);
+        self.code.push(0x89);
+        self.frame_operand(reg, offset);
+    }
+
+    // `mov reg, imm64`
+    pub(super) fn immediate(&mut self, reg: Reg, value: i64) {
+        self.rex(RAX, reg);
+        self.code.push(0xB8 + (reg.0 & 7));
+        self.bytes(&value.to_le_bytes());
+    }
+
+    // `mov rax, [rcx]`
+    pub(super) fn load_indirect(&mut self) {
+        self.bytes(&[0x48, 0x8B, 0x01]);
+    }
+
+    // `mov [rcx], rax`
+    pub(super) fn store_indirect(&mut self) {
+        self.bytes(&[0x48, 0x89, 0x01]);
+    }
+
+    pub(super) fn push_rax(&mut self) {
+        self.code.push(0x50);
+    }
+
+    pub(super) fn pop_rax(&mut self) {
+        self.code.push(0x58);
+    }
+
+    // `rax = rax <op> rcx`
+    pub(super) fn alu(&mut self, op: Alu) {
+        match op {
+            Alu::Add => self.bytes(&[0x48, 0x01, 0xC8]),
+            Alu::Sub => self.bytes(&[0x48, 0x29, 0xC8]),
+            Alu::And => self.bytes(&[0x48, 0x21, 0xC8]),
+            Alu::Or => self.bytes(&[0x48, 0x09, 0xC8]),
+            Alu::Xor => self.bytes(&[0x48, 0x31, 0xC8]),
+            Alu::Mul => self.bytes(&[0x48, 0x0F, 0xAF, 0xC1]),
+            Alu::Shl => self.bytes(&[0x48, 0xD3, 0xE0]),
+            Alu::Sar => self.bytes(&[0x48, 0xD3, 0xF8]),
+        }
+    }
+
+    // `neg rax`
+    pub(super) fn negate(&mut self) {
+        self.bytes(&[0x48, 0xF7, 0xD8]);
+    }
+
+    // `not rax`
+    pub(super) fn not(&mut self) {
+        self.bytes(&[0x48, 0xF7, 0xD0]);
+    }
+
+    // Sets `rax` to 1 if the signed comparison of `rax` with `rcx` holds, otherwise 0.
+    pub(super) fn compare(&mut self, condition: Condition) {
+        self.bytes(&[0x48, 0x39, 0xC8]);
+        self.set(condition);
+    }
+
+    // Sets `rax` to 1 if `rax` is zero or, with `NotEqual`, if it isn't.
+    pub(super) fn test(&mut self, condition: Condition) {
+        self.test_rax();
+        self.set(condition);
+    }
+
+    // `test rax, rax`
+    pub(super) fn test_rax(&mut self) {
+        self.bytes(&[0x48, 0x85, 0xC0]);
+    }
+
+    // `setcc al; movzx eax, al`
+    fn set(&mut self, condition: Condition) {
+        self.bytes(&[0x0F, 0x90 | condition as u8, 0xC0, 0x0F, 0xB6, 0xC0]);
+    }
+
+    fn relative(&mut self, opcode: &[u8], label: Label) {
+        self.bytes(opcode);
+        self.fixups.push((self.code.len(), label));
+        self.bytes(&[0; 4]);
+    }
+
+    pub(super) fn jump(&mut self, label: Label) {
+        self.relative(&[0xE9], label);
+    }
+
+    pub(super) fn jump_if_zero(&mut self, label: Label) {
+        self.relative(&[0x0F, 0x84], label);
+    }
+
+    pub(super) fn call(&mut self, label: Label) {
+        self.relative(&[0xE8], label);
+    }
+
+    // Calls the function at an absolute address, which clobbers `rax`.
+    pub(super) fn call_address(&mut self, function: *const ()) {
+        self.immediate(RAX, function as i64);
+        self.bytes(&[0xFF, 0xD0]);
+    }
+
+    // `push rbp; mov rbp, rsp; sub rsp, size`. The size keeps the stack aligned to 16 bytes
+    // for calls.
+    pub(super) fn prologue(&mut self, size: i32) {
+        self.bytes(&[0x55, 0x48, 0x89, 0xE5, 0x48, 0x81, 0xEC]);
+        self.bytes(&size.to_le_bytes());
+    }
+
+    // `leave; ret`
+    pub(super) fn epilogue(&mut self) {
+        self.bytes(&[0xC9, 0xC3]);
+    }
+
+    // The code with every jump and call pointing at its label.
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        for &(at, label) in &self.fixups {
+            let target = self.labels[label.0].expect("label was never placed");
+            let distance = target as i64 - (at as i64 + 4);
+            self.code[at..at + 4].copy_from_slice(&(distance as i32).to_le_bytes());
+        }
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What GNU as gives for the same instructions, with `{disp32}` for the frame operands.
+    #[test]
+    fn encodes_what_an_assembler_does() {
+        let mut asm = Assembler::default();
+        asm.load(RAX, -8);
+        asm.load(R9, 16);
+        asm.store(-24, RDX);
+        asm.store(-32, R8);
+        asm.immediate(RAX, 0x1122_3344_5566_7788);
+        asm.immediate(R9, -2);
+        asm.load_indirect();
+        asm.store_indirect();
+        asm.push_rax();
+        asm.pop_rax();
+        for op in [Alu::Add, Alu::Sub, Alu::And, Alu::Or, Alu::Xor, Alu::Mul, Alu::Shl, Alu::Sar] {
+            asm.alu(op);
+        }
+        asm.negate();
+        asm.not();
+        asm.compare(Condition::Less);
+        asm.test(Condition::NotEqual);
+        asm.prologue(0x1000);
+        asm.epilogue();
+        let expected: &[&[u8]] = &[
+            &[0x48, 0x8B, 0x85, 0xF8, 0xFF, 0xFF, 0xFF],
+            &[0x4C, 0x8B, 0x8D, 0x10, 0x00, 0x00, 0x00],
+            &[0x48, 0x89, 0x95, 0xE8, 0xFF, 0xFF, 0xFF],
+            &[0x4C, 0x89, 0x85, 0xE0, 0xFF, 0xFF, 0xFF],
+            &[0x48, 0xB8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+            &[0x49, 0xB9, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            &[0x48, 0x8B, 0x01],
+            &[0x48, 0x89, 0x01],
+            &[0x50],
+            &[0x58],
+            &[0x48, 0x01, 0xC8],
+            &[0x48, 0x29, 0xC8],
+            &[0x48, 0x21, 0xC8],
+            &[0x48, 0x09, 0xC8],
+            &[0x48, 0x31, 0xC8],
+            &[0x48, 0x0F, 0xAF, 0xC1],
+            &[0x48, 0xD3, 0xE0],
+            &[0x48, 0xD3, 0xF8],
+            &[0x48, 0xF7, 0xD8],
+            &[0x48, 0xF7, 0xD0],
+            &[0x48, 0x39, 0xC8, 0x0F, 0x9C, 0xC0, 0x0F, 0xB6, 0xC0],
+            &[0x48, 0x85, 0xC0, 0x0F, 0x95, 0xC0, 0x0F, 0xB6, 0xC0],
+            &[0x55, 0x48, 0x89, 0xE5, 0x48, 0x81, 0xEC, 0x00, 0x10, 0x00, 0x00],
+            &[0xC9, 0xC3],
+        ];
+        assert_eq!(asm.finish(), expected.concat());
+    }
+
+    #[test]
+    fn jumps_and_calls_reach_their_labels_either_way() {
+        let mut asm = Assembler::default();
+        let (top, end) = (asm.label(), asm.label());
+        asm.place(top);
+        asm.jump_if_zero(end);
+        asm.call(top);
+        asm.jump(top);
+        asm.place(end);
+        asm.call_address(0x1234 as *const ());
+        assert_eq!(asm.finish(), [
+            &[0x0F, 0x84, 10, 0, 0, 0][..],
+            &[0xE8, 0xF5, 0xFF, 0xFF, 0xFF],
+            &[0xE9, 0xF0, 0xFF, 0xFF, 0xFF],
+            &[0x48, 0xB8, 0x34, 0x12, 0, 0, 0, 0, 0, 0, 0xFF, 0xD0],
+        ].concat());
+    }
+}
@@ -0,0 +1,341 @@
+use std::borrow::Cow;
+
+use memchr::{memchr, memchr2};
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use super::lexer::Handler;
+use super::token::TokenKind;
+
+type Kind = TokenKind<Cow<'static, str>>;
+
+const DIGIT: u8 = 1;
+const IDENTIFIER_START: u8 = 2;
+const IDENTIFIER: u8 = 4;
+const SPACE: u8 = 8;
+
+// Character classes of ASCII bytes. Other bytes are the start or middle of a
+// multi-byte character and are classified by decoding it.
+static CLASSES: [u8; 256] = classes();
+
+const fn classes() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut b = 0;
+    while b < 128 {
+        let c = b as u8;
+        if c.is_ascii_digit() {
+            table[b] = DIGIT | IDENTIFIER;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            table[b] = IDENTIFIER_START | IDENTIFIER;
+        } else if matches!(c, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c) {
+            table[b] = SPACE;
+        }
+        b += 1;
+    }
+    table
+}
+
+// The token can't be decided without reading more of the source.
+#[derive(Debug)]
+pub(super) struct Incomplete;
+
+type Scan<T> = Result<T, Incomplete>;
+
+// The text from the current position to the end of what has been read so far.
+// `eof` says whether that is also the end of the source.
+#[derive(Clone, Copy)]
+struct Input<'a> {
+    s: &'a str,
+    eof: bool
+}
+
+impl Input<'_> {
+    fn at(&self, i: usize) -> Scan<Option<u8>> {
+        match self.s.as_bytes().get(i) {
+            Some(&b) => Ok(Some(b)),
+            None if self.eof => Ok(None),
+            None => Err(Incomplete),
+        }
+    }
+
+    fn char_at(&self, i: usize) -> Scan<Option<char>> {
+        Ok(self.at(i)?.and_then(|_| self.s[i..].chars().next()))
+    }
+
+    fn is(&self, i: usize, class: u8) -> Scan<bool> {
+        Ok(self.at(i)?.is_some_and(|b| CLASSES[b as usize] & class != 0))
+    }
+
+    fn found(&self, i: usize, n: Option<usize>) -> Scan<Option<usize>> {
+        match n {
+            Some(n) => Ok(Some(i + n)),
+            None if self.eof => Ok(None),
+            None => Err(Incomplete),
+        }
+    }
+
+    fn rest(&self, i: usize) -> &[u8] {
+        self.s.as_bytes().get(i..).unwrap_or_default()
+    }
+
+    // The index of the first `needle` from `i` on.
+    fn find(&self, i: usize, needle: u8) -> Scan<Option<usize>> {
+        self.found(i, memchr(needle, self.rest(i)))
+    }
+
+    // The index of the first `a` or `b` from `i` on.
+    fn find2(&self, i: usize, a: u8, b: u8) -> Scan<Option<usize>> {
+        self.found(i, memchr2(a, b, self.rest(i)))
+    }
+
+    // The length of the run of characters from `i` on that `ascii` accepts,
+    // checking anything beyond ASCII with `other`.
+    fn run(&self, mut i: usize, ascii: u8, other: fn(char) -> bool) -> Scan<usize> {
+        let start = i;
+        while let Some(c) = self.char_at(i)? {
+            let accepted = match u8::try_from(c) {
+                Ok(b) if b.is_ascii() => CLASSES[b as usize] & ascii != 0,
+                _ => other(c),
+            };
+            if !accepted {
+                break;
+            }
+            i += c.len_utf8();
+        }
+        Ok(i - start)
+    }
+}
+
+fn digits(input: Input, i: usize) -> Scan<usize> {
+    input.run(i, DIGIT, |_| false)
+}
+
+// `[eE][-+]?[0-9]+` at `i`.
+fn exponent(input: Input, i: usize) -> Scan<Option<usize>> {
+    if !matches!(input.at(i)?, Some(b'e' | b'E')) {
+        return Ok(None);
+    }
+    let sign = matches!(input.at(i + 1)?, Some(b'-' | b'+')) as usize;
+    let n = digits(input, i + 1 + sign)?;
+    Ok((n > 0).then_some(1 + sign + n))
+}
+
+fn number(input: Input) -> Scan<(Handler, usize)> {
+    let len = digits(input, 0)?;
+    if input.at(len)? == Some(b'.') && input.is(len + 1, DIGIT)? {
+        let len = len + 1 + digits(input, len + 1)?;
+        return Ok((Handler::Float, len + exponent(input, len)?.unwrap_or(0)));
+    }
+    Ok(match exponent(input, len)? {
+        Some(n) => (Handler::Float, len + n),
+        None => (Handler::Integer, len),
+    })
+}
+
+// `"..."`, where a backslash escapes whatever follows it.
+fn string(input: Input) -> Scan<Option<usize>> {
+    let mut i = 1;
+    loop {
+        let Some(end) = input.find2(i, b'"', b'\\')? else { return Ok(None) };
+        if input.s.as_bytes()[end] == b'"' {
+            return Ok(Some(end + 1));
+        }
+        if input.at(end + 1)?.is_none() {
+            return Ok(None);
+        }
+        i = end + 2;
+    }
+}
+
+fn quotes(input: Input, i: usize) -> Scan<usize> {
+    let mut n = 0;
+    while n < 3 && input.at(i + n)? == Some(b'"') {
+        n += 1;
+    }
+    Ok(n)
+}
+
+// `"""..."""`, which may contain one or two quotes in a row.
+fn triple_string(input: Input) -> Scan<Option<usize>> {
+    if quotes(input, 0)? < 3 {
+        return Ok(None);
+    }
+    let mut i = 3;
+    loop {
+        let Some(next) = input.find2(i, b'"', b'\\')? else { return Ok(None) };
+        // The quotes before a backslash or anything else are part of the string.
+        let n = quotes(input, next)?;
+        if n == 3 {
+            return Ok(Some(next + 3));
+        }
+        match input.at(next + n)? {
+            None => return Ok(None),
+            Some(b'\\') if input.at(next + n + 1)?.is_none() => return Ok(None),
+            Some(b'\\') => i = next + n + 2,
+            Some(_) => i = next + n + 1,
+        }
+    }
+}
+
+// `'c'`, `'\''` or a backslash and anything up to the closing quote on the same line.
+fn char_literal(input: Input) -> Scan<Option<usize>> {
+    match input.at(1)? {
+        Some(b'\\') if input.at(2)? == Some(b'\'') => Ok((input.at(3)? == Some(b'\'')).then_some(4)),
+        Some(b'\\') => {
+            let end = input.find2(2, b'\'', b'\n')?;
+            Ok(end.filter(|&end| end > 2 && input.s.as_bytes()[end] == b'\'').map(|end| end + 1))
+        }
+        None | Some(b'\'' | b'\n') => Ok(None),
+        Some(_) => {
+            let end = 1 + input.char_at(1)?.map_or(1, char::len_utf8);
+            Ok((input.at(end)? == Some(b'\'')).then_some(end + 1))
+        }
+    }
+}
+
+// A block comment ends at the first `*/` after its opening `/*`.
+fn block_comment(input: Input) -> Scan<Option<usize>> {
+    let mut i = 2;
+    while let Some(star) = input.find(i, b'*')? {
+        if input.at(star + 1)? == Some(b'/') {
+            return Ok(Some(star + 2));
+        }
+        i = star + 1;
+    }
+    Ok(None)
+}
+
+fn line_comment(input: Input) -> Scan<usize> {
+    Ok(input.find(2, b'\n')?.unwrap_or(input.s.len()))
+}
+
+fn operator(kind: Kind, len: usize) -> Scan<Option<(Handler, usize)>> {
+    Ok(Some((Handler::Default(kind, len), len)))
+}
+
+// Picks `long` if the byte after the first is `second`, otherwise `short`.
+fn either(input: Input, second: u8, long: Kind, short: Kind) -> Scan<Option<(Handler, usize)>> {
+    if input.at(1)? == Some(second) {
+        operator(long, 2)
+    } else {
+        operator(short, 1)
+    }
+}
+
+// The same choice the regex patterns make: the longest token at the start of `s`,
+// and how to turn it into a token. Unless `eof` is set, `s` may be followed by more
+// of the source, and anything that depends on it is `Incomplete`.
+pub(super) fn match_token(s: &str, eof: bool) -> Result<Option<(Handler, usize)>, Incomplete> {
+    let input = Input { s, eof };
+    let Some(first) = input.at(0)? else { return Ok(None) };
+    let class = CLASSES[first as usize];
+
+    if class & DIGIT != 0 {
+        return number(input).map(Some);
+    }
+    if first == b'r' && input.at(1)? == Some(b'"') {
+        return Ok(Some(match input.find(2, b'"')? {
+            Some(end) => (Handler::RawString, end + 1),
+            None => (Handler::UnterminatedString(2), s.len()),
+        }));
+    }
+    let c = input.char_at(0)?.unwrap();
+    if class & IDENTIFIER_START != 0 || (!first.is_ascii() && is_xid_start(c)) {
+        let mut len = c.len_utf8() + input.run(c.len_utf8(), IDENTIFIER, is_xid_continue)?;
+        // A name from an imported module is qualified by the module's, as in `math.square`.
+        if input.at(len)? == Some(b'.')
+            && let Some(next) = input.char_at(len + 1)?
+            && (next == '_' || is_xid_start(next))
+        {
+            len += 1 + next.len_utf8() + input.run(len + 1 + next.len_utf8(), IDENTIFIER, is_xid_continue)?;
+        }
+        return Ok(Some((Handler::Identifier, len)));
+    }
+    if class & SPACE != 0 || (!first.is_ascii() && c.is_whitespace()) {
+        return Ok(Some((Handler::Whitespace, input.run(0, SPACE, char::is_whitespace)?)));
+    }
+
+    match first {
+        b'"' => Ok(Some(match triple_string(input)? {
+            Some(len) => (Handler::String(3), len),
+            None if quotes(input, 0)? == 3 => (Handler::UnterminatedString(3), s.len()),
+            None => match string(input)? {
+                Some(len) => (Handler::String(1), len),
+                None => (Handler::UnterminatedString(1), s.len()),
+            },
+        })),
+        b'\'' => Ok(char_literal(input)?.map(|len| (Handler::Character, len))),
+        b'/' => match input.at(1)? {
+            Some(b'*') => match block_comment(input)? {
+                Some(len) => Ok(Some((Handler::Comment, len))),
+                None => Ok(Some((Handler::UnterminatedComment, s.len()))),
+            },
+            Some(b'/') => Ok(Some((Handler::Comment, line_comment(input)?))),
+            _ => either(input, b'=', TokenKind::OpDivideAssign, TokenKind::OpDivide),
+        },
+        b'(' => operator(TokenKind::OpenParen, 1),
+        b')' => operator(TokenKind::CloseParen, 1),
+        b'{' => operator(TokenKind::OpenBrace, 1),
+        b'}' => operator(TokenKind::CloseBrace, 1),
+        b'[' => operator(TokenKind::OpenBracket, 1),
+        b']' => operator(TokenKind::CloseBracket, 1),
+        b';' => operator(TokenKind::Semicolon, 1),
+        b',' => operator(TokenKind::Comma, 1),
+        b'?' => operator(TokenKind::Question, 1),
+        b':' => operator(TokenKind::Colon, 1),
+        b'#' => operator(TokenKind::Hash, 1),
+        b'^' => operator(TokenKind::OpBitXor, 1),
+        b'~' => operator(TokenKind::OpBitNot, 1),
+        b'=' => either(input, b'=', TokenKind::OpEqual, TokenKind::OpAssign),
+        b'!' => either(input, b'=', TokenKind::OpNotEqual, TokenKind::OpNot),
+        b'&' => either(input, b'&', TokenKind::OpAnd, TokenKind::OpBitAnd),
+        b'|' => either(input, b'|', TokenKind::OpOr, TokenKind::OpBitOr),
+        b'*' => either(input, b'=', TokenKind::OpMultiplyAssign, TokenKind::OpMultiply),
+        b'%' => either(input, b'=', TokenKind::OpModAssign, TokenKind::OpMod),
+        b'<' => match input.at(1)? {
+            Some(b'<') => operator(TokenKind::OpShiftLeft, 2),
+            _ => either(input, b'=', TokenKind::OpLessEqual, TokenKind::OpLess),
+        },
+        b'>' => match input.at(1)? {
+            Some(b'>') => operator(TokenKind::OpShiftRight, 2),
+            _ => either(input, b'=', TokenKind::OpGreaterEqual, TokenKind::OpGreater),
+        },
+        b'+' => match input.at(1)? {
+            Some(b'+') => operator(TokenKind::OpIncrement, 2),
+            _ => either(input, b'=', TokenKind::OpAddAssign, TokenKind::OpAdd),
+        },
+        b'-' => match input.at(1)? {
+            Some(b'-') => operator(TokenKind::OpDecrement, 2),
+            _ => either(input, b'=', TokenKind::OpSubtractAssign, TokenKind::OpSubtract),
+        },
+        _ => Ok(None),
+    }
+}
+
+// The comment or string at the start of `s`, the whole of the source that's left, if it's
+// never closed. No regex pattern matches these, so the regex scanner asks first.
+pub(super) fn unterminated(s: &str) -> Option<(Handler, usize)> {
+    if !(s.starts_with("/*") || s.starts_with('"') || s.starts_with("r\"")) {
+        return None;
+    }
+    let found = match_token(s, true).ok().flatten();
+    found.filter(|(handler, _)| matches!(handler, Handler::UnterminatedComment | Handler::UnterminatedString(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{LexerOptions, Scanner, tokenize_with_options};
+
+    #[test]
+    fn lexes_what_the_regex_patterns_do() {
+        let source = "/// doc\nfn f(x_1, é) { return x_1 <= 0x ? 1.5e3 : 2e-1; } // rest\n\
+                      s = \"a\\\"b\\q\" + r\"\\raw\"; c = '\\u{1F600}'; d = '\\x41'; @ $\n\
+                      a <<= b >> 1 != 2 && !c || ~d; 123abc /* block */ \"open";
+        for preserve_trivia in [false, true] {
+            let lex = |scanner| tokenize_with_options(source, LexerOptions { scanner, preserve_trivia });
+            let (bytes, regex) = (lex(Scanner::Bytes), lex(Scanner::Regex));
+            assert_eq!(bytes.0, regex.0);
+            assert_eq!(bytes.1, regex.1);
+            assert!(!bytes.1.is_empty());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexError {
+    UnrecognizedToken { offset: usize, text: String },
+    UnknownEscape { offset: usize, sequence: String },
+    InvalidUtf8 { offset: usize },
+    IntegerOutOfRange { offset: usize, text: String },
+    FloatOutOfRange { offset: usize, text: String },
+    UnterminatedComment { offset: usize },
+    // `quote` is the width of the opening quote: `"`, `"""` or `r"`.
+    UnterminatedString { offset: usize, quote: usize }
+}
+
+impl LexError {
+    pub fn offset(&self) -> usize {
+        match self {
+            LexError::UnrecognizedToken { offset, .. } | LexError::UnknownEscape { offset, .. }
+            | LexError::InvalidUtf8 { offset } | LexError::IntegerOutOfRange { offset, .. }
+            | LexError::FloatOutOfRange { offset, .. }
+            | LexError::UnterminatedComment { offset } | LexError::UnterminatedString { offset, .. } => *offset,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexError::UnrecognizedToken { .. } => "E0101",
+            LexError::UnknownEscape { .. } => "E0102",
+            LexError::InvalidUtf8 { .. } => "E0103",
+            LexError::IntegerOutOfRange { .. } => "E0112",
+            LexError::FloatOutOfRange { .. } => "E0115",
+            LexError::UnterminatedComment { .. } => "E0113",
+            LexError::UnterminatedString { .. } => "E0114",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnrecognizedToken { offset, text } | LexError::IntegerOutOfRange { offset, text }
+            | LexError::FloatOutOfRange { offset, text } => {
+                Span::new(*offset, offset + text.len())
+            }
+            LexError::UnknownEscape { offset, sequence } => Span::new(*offset, offset + sequence.len()),
+            LexError::InvalidUtf8 { offset } => Span::new(*offset, *offset),
+            // Just the opening, since the rest of the source follows it.
+            LexError::UnterminatedComment { offset } => Span::new(*offset, offset + "/*".len()),
+            LexError::UnterminatedString { offset, quote } => Span::new(*offset, offset + quote),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnrecognizedToken { text, .. } => write!(f, "unrecognized token {:?}", text),
+            LexError::UnknownEscape { sequence, .. } => write!(f, "invalid escape sequence `{}`", sequence),
+            LexError::InvalidUtf8 { offset } => write!(f, "invalid UTF-8 at byte {}", offset),
+            LexError::IntegerOutOfRange { .. } => write!(f, "integer literal out of range"),
+            LexError::FloatOutOfRange { .. } => write!(f, "float literal out of range"),
+            LexError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+impl From<&LexError> for Diagnostic {
+    fn from(error: &LexError) -> Self {
+        Diagnostic::error(error.to_string(), error.span()).with_code(error.code())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::diagnostics::LineIndex;
+use super::bytes;
+use super::token::{Token, TokenKind, quote_string};
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// The Rosetta Code task lists character literals as integers.
+fn name(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Char(_) => "Integer",
+        kind => kind.name(),
+    }
+}
+
+// Rosetta Code lexical analyzer layout: line, column, token name and value.
+pub fn tokens_to_text(tokens: &[Token], source: &str) -> String {
+    let index = LineIndex::new(source);
+    let mut out = String::new();
+    for token in tokens {
+        let (line, column) = index.line_column(token.span.start);
+        out.push_str(&token_to_text(token, line, column));
+        out.push('\n');
+    }
+    out
+}
+
+// The row for one token that starts at `line` and `column`, for printing tokens as they're
+// lexed.
+pub fn token_to_text(token: &Token, line: usize, column: usize) -> String {
+    let value = match &token.kind {
+        TokenKind::Integer(n) => n.to_string(),
+        TokenKind::Char(c) => (*c as u32).to_string(),
+        TokenKind::Float(x) => format!("{:?}", x),
+        TokenKind::Indentifier(name) => name.to_string(),
+        TokenKind::String(s) | TokenKind::Comment(s) | TokenKind::DocComment(s) | TokenKind::Whitespace(s) => quote_string(s),
+        _ => String::new(),
+    };
+    format!("{:5} {:6} {:<15} {}", line, column, name(&token.kind), value).trim_end().to_string()
+}
+
+// One object per token with the line, column, name and (if any) value fields of the
+// Rosetta Code lexical analyzer output.
+pub fn tokens_to_json(tokens: &[Token], source: &str) -> String {
+    let index = LineIndex::new(source);
+    let mut out = String::from("[\n");
+    for (i, token) in tokens.iter().enumerate() {
+        let (line, column) = index.line_column(token.span.start);
+        out.push_str("  ");
+        out.push_str(&token_to_json(token, line, column));
+        if i + 1 < tokens.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("]\n");
+    out
+}
+
+// The object for one token that starts at `line` and `column`.
+pub fn token_to_json(token: &Token, line: usize, column: usize) -> String {
+    let mut out = format!("{{\"line\": {}, \"column\": {}, \"name\": \"{}\"", line, column, name(&token.kind));
+    match &token.kind {
+        TokenKind::Integer(n) => out.push_str(&format!(", \"value\": {}", n)),
+        TokenKind::Char(c) => out.push_str(&format!(", \"value\": {}", *c as u32)),
+        TokenKind::Float(x) => out.push_str(&format!(", \"value\": {:?}", x)),
+        TokenKind::Indentifier(name) => out.push_str(&format!(", \"value\": {}", json_string(name.as_str()))),
+        TokenKind::String(s) | TokenKind::Comment(s) | TokenKind::DocComment(s) | TokenKind::Whitespace(s) => {
+            out.push_str(&format!(", \"value\": {}", json_string(s)));
+        }
+        _ => {}
+    }
+    out.push('}');
+    out
+}
+
+// Whether `text` lexes as a token `first` bytes long followed by the rest, however the
+// source carries on after it.
+fn splits_at(text: &str, first: usize) -> bool {
+    matches!(bytes::match_token(text, false), Ok(Some((_, len))) if len == first)
+}
+
+// Source that lexes back into the same tokens, separated by a space or newline only
+// where they would otherwise run together.
+pub fn tokens_to_source(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut previous = String::new();
+    for token in tokens.iter().filter(|token| token.kind != TokenKind::EndOfInput) {
+        let text = token.kind.to_string();
+        if !previous.is_empty() {
+            let separator = ["", " ", "\n"].into_iter()
+                .find(|separator| splits_at(&format!("{}{}{}", previous, separator, text), previous.len()))
+                .unwrap_or("");
+            out.push_str(separator);
+        }
+        out.push_str(&text);
+        previous = text;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn text_is_rosetta_codes_layout_and_source_lexes_back_the_same() {
+        let source = "x = 'a';\nprint(\"hi\\n\");\n";
+        let (tokens, _) = tokenize(source);
+        let rows = ["    1      1 Identifier      x", "    1      3 Op_assign", "    1      5 Integer         97", "    1      8 Semicolon"];
+        assert_eq!(tokens_to_text(&tokens[..4], source), rows.map(|row| format!("{}\n", row)).concat());
+        // Tokens that would run together are kept apart, and only those.
+        let source = "a = b - -1; c = d+ +e; f = 1.5; if (a) { print(r\"x\\n\", 'q'); }";
+        let kinds = |source: &str| tokenize(source).0.into_iter().map(|token| token.kind).collect::<Vec<_>>();
+        let written = tokens_to_source(&tokenize(source).0);
+        assert_eq!(kinds(&written), kinds(source));
+        assert!(written.starts_with("a=b- -1;c=d+ +e;f=1.5;if(a){"), "{}", written);
+    }
+}
@@ -1,14 +1,33 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::io::Read;
+use std::sync::LazyLock;
+
 use regex::Regex;
-use super::token::Token;
+use regex_automata::{Anchored, Input, MatchKind, PatternSet, meta};
+use super::bytes;
+use super::error::LexError;
+use super::reader::ReaderLexer;
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use super::token::{BorrowedToken, Token, TokenKind};
 
 #[derive(Clone)]
-enum Handler {
-    Default(Token, usize),
-    Skip,
-    String,
+pub(super) enum Handler {
+    Default(TokenKind<Cow<'static, str>>, usize),
+    Whitespace,
+    Comment,
+    // The width of the quotes on each side.
+    String(usize),
+    RawString,
+    // An opening `/*` or quote that nothing closes, and the width of the quote. The rest of
+    // the source is in it.
+    UnterminatedComment,
+    UnterminatedString(usize),
     Character,
     Identifier,
-    Integer
+    Integer,
+    Float
 }
 
 struct RegexPattern {
@@ -16,23 +35,76 @@ struct RegexPattern {
     handler: Handler
 }
 
-pub struct Lexer {
-    patterns: Vec<RegexPattern>,
-    pub tokens: Vec<Token>,
-    source: String,
-    pos: usize
+// How the lexer finds the token at the current position. Both accept the same language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scanner {
+    // Hand-written matching over byte classes.
+    #[default]
+    Bytes,
+    // The regex patterns below, matched together.
+    Regex
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LexerOptions {
+    pub scanner: Scanner,
+    // Return comments and whitespace as tokens instead of skipping them.
+    pub preserve_trivia: bool
+}
+
+pub struct Lexer<'src> {
+    options: LexerOptions,
+    pub tokens: Vec<BorrowedToken<'src>>,
+    pub errors: Vec<LexError>,
+    source: &'src str,
+    pub(super) pos: usize,
+    // Where `source` starts in the whole input, for lexers that only see part of it.
+    pub(super) base: usize
+}
+
+// Identifiers that are reserved as keywords.
+pub const KEYWORDS: &[&str] = &["print", "putc", "while", "for", "do", "if", "else", "fn", "return", "true", "false", "import"];
+
+// The keyword `name` spells, if it's one of `KEYWORDS`.
+fn keyword<S>(name: &str) -> Option<TokenKind<S>> {
+    let kind = match name {
+        "print" => TokenKind::KeywordPrint,
+        "putc" => TokenKind::KeywordPutc,
+        "while" => TokenKind::KeywordWhile,
+        "for" => TokenKind::KeywordFor,
+        "do" => TokenKind::KeywordDo,
+        "if" => TokenKind::KeywordIf,
+        "else" => TokenKind::KeywordElse,
+        "fn" => TokenKind::KeywordFn,
+        "return" => TokenKind::KeywordReturn,
+        "true" => TokenKind::KeywordTrue,
+        "false" => TokenKind::KeywordFalse,
+        "import" => TokenKind::KeywordImport,
+        _ => return None,
+    };
+    Some(kind)
 }
 
-impl Lexer {
+fn hex(digits: &str) -> Option<u32> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+impl<'src> Lexer<'src> {
     fn advance_n(&mut self, n: usize) {
         self.pos += n;
     }
 
-    fn push(&mut self, token: Token) {
-        self.tokens.push(token);
+    fn push(&mut self, kind: TokenKind<Cow<'src, str>>, len: usize) {
+        let start = self.base + self.pos;
+        let span = Span::new(start, start + len);
+        self.tokens.push(Token { kind, span });
+        self.advance_n(len);
     }
 
-    fn remainder(&self) -> &str {
+    fn remainder(&self) -> &'src str {
         &self.source[self.pos..]
     }
 
@@ -40,243 +112,324 @@ impl Lexer {
         self.pos >= self.source.len()
     }
 
-    fn char_literal_to_number(&self, s: &str) -> u32 {
-        let inner = &s[1..s.len()-1];
-        
-        let ch = if inner.starts_with('\\') {
-            match &inner[1..] {
-                "n" => '\n',
-                "t" => '\t',
-                "r" => '\r',
-                "\\" => '\\',
-                "'" => '\'',
-                "0" => '\0',
-                _ => panic!("Unknown escape sequence"),
+    // The handler for the longest match at the current position, and the match's length.
+    // Ties go to the pattern listed first.
+    fn match_pattern(&self) -> Option<(Handler, usize)> {
+        let remainder = self.remainder();
+        if self.options.scanner == Scanner::Bytes {
+            // The whole source is there, so no match is incomplete.
+            return bytes::match_token(remainder, true).ok().flatten();
+        }
+        if let Some(found) = bytes::unterminated(remainder) {
+            return Some(found);
+        }
+        let (patterns, set) = &*PATTERNS;
+        let mut matches = PatternSet::new(set.pattern_len());
+        set.which_overlapping_matches(&Input::new(remainder).anchored(Anchored::Yes), &mut matches);
+        matches.iter()
+            .map(|id| (id.as_usize(), patterns[id].regex.find(remainder).map_or(0, |mat| mat.end())))
+            .min_by_key(|&(i, len)| (Reverse(len), i))
+            .map(|(i, len)| (patterns[i].handler.clone(), len))
+    }
+
+    // Records the unrecognized text and skips ahead to where some pattern matches again.
+    fn skip_unrecognized(&mut self) {
+        let start = self.pos;
+        while !self.at_eof() && (self.pos == start || self.match_pattern().is_none()) {
+            let width = self.remainder().chars().next().map_or(1, char::len_utf8);
+            self.advance_n(width);
+        }
+        let text = self.source[start..self.pos].to_string();
+        self.errors.push(LexError::UnrecognizedToken { offset: self.base + start, text });
+    }
+
+    // The character of a literal such as `'a'`, `'\n'`, `'\x41'` or `'\u{e9}'`.
+    // Invalid escapes are reported and read as `'\0'`.
+    fn char_literal(&mut self, s: &str, start: usize) -> char {
+        let inner = &s[1..s.len() - 1];
+        let Some(escape) = inner.strip_prefix('\\') else {
+            return inner.chars().next().unwrap();
+        };
+        let c = match escape {
+            "n" => Some('\n'),
+            "t" => Some('\t'),
+            "r" => Some('\r'),
+            "\\" => Some('\\'),
+            "'" => Some('\''),
+            "0" => Some('\0'),
+            _ => match escape.strip_prefix('x') {
+                Some(digits) if digits.len() == 2 => hex(digits),
+                Some(_) => None,
+                None => escape.strip_prefix("u{")
+                    .and_then(|braced| braced.strip_suffix('}'))
+                    .filter(|digits| digits.len() <= 6)
+                    .and_then(hex),
+            }.and_then(char::from_u32),
+        };
+        c.unwrap_or_else(|| {
+            self.errors.push(LexError::UnknownEscape { offset: start + 1, sequence: inner.to_string() });
+            '\0'
+        })
+    }
+
+    // Replaces escape sequences in the contents of a string literal starting at `start`.
+    // Unknown escapes are reported and kept as written.
+    fn unescape(&mut self, contents: &'src str, start: usize) -> Cow<'src, str> {
+        if !contents.contains('\\') {
+            return Cow::Borrowed(contents);
+        }
+        let mut out = String::with_capacity(contents.len());
+        let mut chars = contents.char_indices();
+        while let Some((i, ch)) = chars.next() {
+            if ch != '\\' {
+                out.push(ch);
+                continue;
             }
-        } else {
-            inner.chars().next().unwrap()
-    };
-    
-    ch as u32
-}
+            let Some((_, escape)) = chars.next() else { break };
+            match escape {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                '\\' => out.push('\\'),
+                '"' => out.push('"'),
+                '0' => out.push('\0'),
+                c => {
+                    let sequence = format!("\\{}", c);
+                    self.errors.push(LexError::UnknownEscape { offset: start + i, sequence: sequence.clone() });
+                    out.push_str(&sequence);
+                }
+            }
+        }
+        Cow::Owned(out)
+    }
 
-    fn handle_pattern(&mut self, handler: &Handler, regex: &Regex) {
+    pub(super) fn handle_pattern(&mut self, handler: &Handler, len: usize) {
         match handler {
             Handler::Default(token, l) => {
-                self.advance_n(l.clone());
-                self.push(token.clone());
+                self.push(token.clone(), *l);
             }
-            Handler::Skip => {
-                if let Some(mat) = regex.find(self.remainder()) {
-                    self.advance_n(mat.end());
-                }
+            Handler::Whitespace | Handler::Comment if !self.options.preserve_trivia => {
+                self.advance_n(len);
             }
-            Handler::String => {
-                if let Some(mat) = regex.find(self.remainder()) {
-                    let match_str = mat.as_str();
-                    let match_str = &match_str[1..match_str.len()-1].to_string();
-                    let len = match_str.len() + 2;
-                    self.push(Token::String(match_str.clone()));
-                    self.advance_n(len);
-                }
+            Handler::Whitespace => {
+                self.push(TokenKind::Whitespace(Cow::Borrowed(&self.remainder()[..len])), len);
+            }
+            Handler::Comment => {
+                let text = &self.remainder()[..len];
+                // `////` and longer are rules, not documentation.
+                let kind = if text.starts_with("///") && !text.starts_with("////") {
+                    TokenKind::DocComment(Cow::Borrowed(text))
+                } else {
+                    TokenKind::Comment(Cow::Borrowed(text))
+                };
+                self.push(kind, len);
+            }
+            Handler::String(quote) => {
+                let match_str = &self.remainder()[..len];
+                let contents = self.unescape(&match_str[*quote..len - *quote], self.base + self.pos + *quote);
+                self.push(TokenKind::String(contents), len);
+            }
+            // Raw strings keep their contents exactly as written.
+            Handler::RawString => {
+                let match_str = &self.remainder()[..len];
+                self.push(TokenKind::String(Cow::Borrowed(&match_str[2..len - 1])), len);
+            }
+            Handler::UnterminatedComment => {
+                self.errors.push(LexError::UnterminatedComment { offset: self.base + self.pos });
+                self.advance_n(len);
+            }
+            Handler::UnterminatedString(quote) => {
+                self.errors.push(LexError::UnterminatedString { offset: self.base + self.pos, quote: *quote });
+                self.advance_n(len);
             }
             Handler::Character => {
-                if let Some(mat) = regex.find(self.remainder()) {
-                    let match_str = mat.as_str();
-                    let char = self.char_literal_to_number(match_str);
-                    let len = match_str.len() + 2;
-                    self.push(Token::Integer(char as i64));
-                    self.advance_n(len);
-                }
+                let match_str = &self.remainder()[..len];
+                let c = self.char_literal(match_str, self.base + self.pos);
+                self.push(TokenKind::Char(c), len);
             }
             Handler::Identifier => {
-                if let Some(mat) = regex.find(self.remainder()) {
-                    let match_str = mat.as_str().to_string();
-                    let len = match_str.len();
-                    self.push(Token::Indentifier(match_str));
-                    self.advance_n(len);
-                }
+                let match_str = &self.remainder()[..len];
+                let kind = keyword(match_str).unwrap_or_else(|| TokenKind::Indentifier(Symbol::intern(match_str)));
+                self.push(kind, len);
             }
             Handler::Integer => {
-                if let Some(mat) = regex.find(self.remainder()) {
-                    let match_str = mat.as_str().to_string();
-                    let len = match_str.len();
-                    let n: i64 = match_str.parse().unwrap();
-                    self.push(Token::Integer(n));
-                    self.advance_n(len);
-                }
+                let text = &self.remainder()[..len];
+                // The digits are all there is to parse, so only too many of them can fail.
+                let n = text.parse().unwrap_or_else(|_| {
+                    self.errors.push(LexError::IntegerOutOfRange { offset: self.base + self.pos, text: text.to_string() });
+                    0
+                });
+                self.push(TokenKind::Integer(n), len);
+            }
+            Handler::Float => {
+                let text = &self.remainder()[..len];
+                // Too big an exponent parses as infinity, which isn't a number a literal can be.
+                let x = Some(text.parse::<f64>().unwrap()).filter(|x| x.is_finite()).unwrap_or_else(|| {
+                    self.errors.push(LexError::FloatOutOfRange { offset: self.base + self.pos, text: text.to_string() });
+                    0.0
+                });
+                self.push(TokenKind::Float(x), len);
             }
         }
     }
 }
 
-pub fn tokenize(source: impl Into<String>) -> Vec<Token> {
-    let mut lexer = create_lexer(source);
+// Strings without escapes borrow their text from `source`, as do comments and whitespace.
+pub fn tokenize_with_options(source: &str, options: LexerOptions) -> (Vec<BorrowedToken<'_>>, Vec<LexError>) {
+    let mut lexer = create_lexer(source, options);
 
     while !lexer.at_eof() {
-        let mut matched = false;
-        let mut match_info = None;
-        for i in 0..lexer.patterns.len() {
-            let remainder = lexer.remainder();
-            if let Some(mat) = lexer.patterns[i].regex.find(remainder) {
-                if mat.start() == 0 {
-                    match_info = Some(i);
-                    break;
-                }
-            }
+        if let Some((handler, len)) = lexer.match_pattern() {
+            lexer.handle_pattern(&handler, len);
+        } else {
+            lexer.skip_unrecognized();
         }
+    }
 
-        if let Some(i) = match_info {
-            let handler = lexer.patterns[i].handler.clone();
-            let regex = lexer.patterns[i].regex.clone();
-            lexer.handle_pattern(&handler, &regex);
-            matched = true;
-        }
+    lexer.push(TokenKind::EndOfInput, 0);
 
-        if !matched {
-            panic!("Lexer::Error -> unrecognized token near {}", lexer.remainder());
-        }
+    (lexer.tokens, lexer.errors)
+}
+
+impl Lexer<'_> {
+    // Lexes `reader` as it's read instead of all at once.
+    pub fn from_reader<R: Read>(reader: R) -> ReaderLexer<R> {
+        ReaderLexer::new(reader)
     }
+}
 
-    lexer.push(Token::EndOfInput);
+pub fn tokenize_with(source: &str, scanner: Scanner) -> (Vec<BorrowedToken<'_>>, Vec<LexError>) {
+    tokenize_with_options(source, LexerOptions { scanner, ..LexerOptions::default() })
+}
 
-    lexer.tokens
+pub fn tokenize_borrowed(source: &str) -> (Vec<BorrowedToken<'_>>, Vec<LexError>) {
+    tokenize_with(source, Scanner::default())
 }
 
-fn create_lexer(source: impl Into<String>) -> Lexer {
+pub fn tokenize(source: &str) -> (Vec<Token>, Vec<LexError>) {
+    let (tokens, errors) = tokenize_borrowed(source);
+    (tokens.into_iter().map(BorrowedToken::into_owned).collect(), errors)
+}
+
+// Patterns are anchored so they only match at the current position.
+fn pattern(regex: &str, handler: Handler) -> RegexPattern {
+    RegexPattern { regex: Regex::new(&format!("^(?:{})", regex)).unwrap(), handler }
+}
+
+// Compiled once and shared by every lexer.
+static PATTERNS: LazyLock<(Vec<RegexPattern>, meta::Regex)> = LazyLock::new(|| {
+    let patterns = vec![
+        pattern(r#"r"[^"]*""#, Handler::RawString),
+        // A name from an imported module is qualified by the module's, as in `math.square`.
+        pattern(r"[_\p{XID_Start}]\p{XID_Continue}*(\.[_\p{XID_Start}]\p{XID_Continue}*)?", Handler::Identifier),
+        // Negative numbers are a unary minus applied to the literal.
+        pattern(r"[0-9]+(\.[0-9]+([eE][-+]?[0-9]+)?|[eE][-+]?[0-9]+)", Handler::Float),
+        pattern(r"[0-9]+", Handler::Integer),
+        // Quotes inside the string can't run into the closing `"""`.
+        pattern(r#""""(?s:[^"\\]|\\.|"[^"\\]|"\\.|""[^"\\]|""\\.)*""""#, Handler::String(3)),
+        pattern(r#""(?s:[^"\\]|\\.)*""#, Handler::String(1)),
+        pattern(r"'([^'\\\n]|\\'|\\[^'\n]+)'", Handler::Character),
+        pattern(r"/\*[^*]*\*+([^/*][^*]*\*+)*/", Handler::Comment),
+        pattern(r"//[^\n]*", Handler::Comment),
+        pattern(r"\s+", Handler::Whitespace),
+        pattern(r"\(", Handler::Default(TokenKind::OpenParen, "(".len())),
+        pattern(r"\)", Handler::Default(TokenKind::CloseParen, ")".len())),
+        pattern(r"\{", Handler::Default(TokenKind::OpenBrace, "{".len())),
+        pattern(r"\}", Handler::Default(TokenKind::CloseBrace, "}".len())),
+        pattern(r"\[", Handler::Default(TokenKind::OpenBracket, "[".len())),
+        pattern(r"\]", Handler::Default(TokenKind::CloseBracket, "]".len())),
+        pattern(r"==", Handler::Default(TokenKind::OpEqual, "==".len())),
+        pattern(r"!=", Handler::Default(TokenKind::OpNotEqual, "!=".len())),
+        pattern(r"=", Handler::Default(TokenKind::OpAssign, "=".len())),
+        pattern(r"!", Handler::Default(TokenKind::OpNot, "!".len())),
+        pattern(r"<<", Handler::Default(TokenKind::OpShiftLeft, "<<".len())),
+        pattern(r">>", Handler::Default(TokenKind::OpShiftRight, ">>".len())),
+        pattern(r"<=", Handler::Default(TokenKind::OpLessEqual, "<=".len())),
+        pattern(r"<", Handler::Default(TokenKind::OpLess, "<".len())),
+        pattern(r">=", Handler::Default(TokenKind::OpGreaterEqual, ">=".len())),
+        pattern(r">", Handler::Default(TokenKind::OpGreater, ">".len())),
+        pattern(r"&&", Handler::Default(TokenKind::OpAnd, "&&".len())),
+        pattern(r"\|\|", Handler::Default(TokenKind::OpOr, "||".len())),
+        pattern(r"\+\+", Handler::Default(TokenKind::OpIncrement, "++".len())),
+        pattern(r"--", Handler::Default(TokenKind::OpDecrement, "--".len())),
+        pattern(r"\+=", Handler::Default(TokenKind::OpAddAssign, "+=".len())),
+        pattern(r"-=", Handler::Default(TokenKind::OpSubtractAssign, "-=".len())),
+        pattern(r"\*=", Handler::Default(TokenKind::OpMultiplyAssign, "*=".len())),
+        pattern(r"/=", Handler::Default(TokenKind::OpDivideAssign, "/=".len())),
+        pattern(r"%=", Handler::Default(TokenKind::OpModAssign, "%=".len())),
+        pattern(r"&", Handler::Default(TokenKind::OpBitAnd, "&".len())),
+        pattern(r"\|", Handler::Default(TokenKind::OpBitOr, "|".len())),
+        pattern(r"\^", Handler::Default(TokenKind::OpBitXor, "^".len())),
+        pattern(r"~", Handler::Default(TokenKind::OpBitNot, "~".len())),
+        pattern(r";", Handler::Default(TokenKind::Semicolon, ";".len())),
+        pattern(r",", Handler::Default(TokenKind::Comma, ",".len())),
+        pattern(r"\?", Handler::Default(TokenKind::Question, "?".len())),
+        pattern(r":", Handler::Default(TokenKind::Colon, ":".len())),
+        pattern(r"#", Handler::Default(TokenKind::Hash, "#".len())),
+        pattern(r"\+", Handler::Default(TokenKind::OpAdd, "+".len())),
+        pattern(r"-", Handler::Default(TokenKind::OpSubtract, "-".len())),
+        pattern(r"/", Handler::Default(TokenKind::OpDivide, "/".len())),
+        pattern(r"\*", Handler::Default(TokenKind::OpMultiply, "*".len())),
+        pattern(r"%", Handler::Default(TokenKind::OpMod, "%".len())),
+    ];
+    let sources: Vec<&str> = patterns.iter().map(|p| p.regex.as_str()).collect();
+    let set = meta::Regex::builder()
+        .configure(meta::Config::new().match_kind(MatchKind::All))
+        .build_many(&sources)
+        .unwrap();
+    (patterns, set)
+});
+
+pub(super) fn create_lexer(source: &str, options: LexerOptions) -> Lexer<'_> {
     Lexer {
+        options,
         pos: 0,
-        source: source.into(),
+        base: 0,
+        source,
         tokens: Vec::new(),
-        patterns: vec![
-            RegexPattern {
-                regex: Regex::new(r"print").unwrap(),
-                handler: Handler::Default(Token::KeywordPrint, "print".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"putc").unwrap(),
-                handler: Handler::Default(Token::KeywordPutc, "putc".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"while").unwrap(),
-                handler: Handler::Default(Token::KeywordWhile, "while".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"if").unwrap(),
-                handler: Handler::Default(Token::KeywordIf, "if".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"else").unwrap(),
-                handler: Handler::Default(Token::KeywordElse, "else".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"[_a-zA-Z][_a-zA-Z0-9]*").unwrap(),
-                handler: Handler::Identifier,
-            },
-            RegexPattern {
-                regex: Regex::new(r"-?[0-9]+").unwrap(),
-                handler: Handler::Integer,
-            },
-            RegexPattern {
-                regex: Regex::new(r#""[^"]*""#).unwrap(),
-                handler: Handler::String,
-            },
-            RegexPattern {
-                regex: Regex::new(r"'([^'\n]|\\n|\\\\)'").unwrap(),
-                handler: Handler::Character,
-            },
-            RegexPattern {
-                regex: Regex::new(r"(?s)/\*.*?\*/").unwrap(),
-                handler: Handler::Skip,
-            },
-            RegexPattern {
-                regex: Regex::new(r"\s+").unwrap(),
-                handler: Handler::Skip,
-            },
-            RegexPattern {
-                regex: Regex::new(r"\(").unwrap(),
-                handler: Handler::Default(Token::OpenParen, "(".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\)").unwrap(),
-                handler: Handler::Default(Token::CloseParen, ")".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\{").unwrap(),
-                handler: Handler::Default(Token::OpenBrace, "{".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\}").unwrap(),
-                handler: Handler::Default(Token::CloseBrace, "}".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"==").unwrap(),
-                handler: Handler::Default(Token::OpEqual, "==".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"!=").unwrap(),
-                handler: Handler::Default(Token::OpNotEqual, "!=".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"=").unwrap(),
-                handler: Handler::Default(Token::OpAssign, "=".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"!").unwrap(),
-                handler: Handler::Default(Token::OpNot, "!".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"<=").unwrap(),
-                handler: Handler::Default(Token::OpLessEqual, "<=".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"<").unwrap(),
-                handler: Handler::Default(Token::OpLess, "<".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r">=").unwrap(),
-                handler: Handler::Default(Token::OpGreaterEqual, ">=".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r">").unwrap(),
-                handler: Handler::Default(Token::OpGreater, ">".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"&&").unwrap(),
-                handler: Handler::Default(Token::OpAnd, "&&".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\|\|").unwrap(),
-                handler: Handler::Default(Token::OpOr, "||".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r";").unwrap(),
-                handler: Handler::Default(Token::Semicolon, ";".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r",").unwrap(),
-                handler: Handler::Default(Token::Comma, ",".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\+").unwrap(),
-                handler: Handler::Default(Token::OpAdd, "+".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"-").unwrap(),
-                handler: Handler::Default(Token::OpSubtract, "-".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"/").unwrap(),
-                handler: Handler::Default(Token::OpDivide, "/".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"\*").unwrap(),
-                handler: Handler::Default(Token::OpMultiply, "*".len()),
-            },
-            RegexPattern {
-                regex: Regex::new(r"%").unwrap(),
-                handler: Handler::Default(Token::OpMod, "%".len()),
-            },
-        ],
+        errors: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_integer_too_big_for_64_bits_is_an_error_and_lexing_goes_on() {
+        for scanner in [Scanner::Bytes, Scanner::Regex] {
+            let (tokens, errors) = tokenize_with("print(99999999999999999999); 9223372036854775807", scanner);
+            assert_eq!(errors, [LexError::IntegerOutOfRange { offset: 6, text: "99999999999999999999".to_string() }]);
+            assert_eq!(errors[0].to_string(), "integer literal out of range");
+            let kinds: Vec<_> = tokens.iter().map(|token| &token.kind).collect();
+            assert_eq!(kinds[2], &TokenKind::Integer(0));
+            assert_eq!(kinds[5], &TokenKind::Integer(i64::MAX));
+        }
+    }
+
+    #[test]
+    fn a_float_too_big_for_64_bits_is_an_error() {
+        for scanner in [Scanner::Bytes, Scanner::Regex] {
+            let (tokens, errors) = tokenize_with("x = 1e400; y = 1.5e308; z = 1e-400;", scanner);
+            assert_eq!(errors, [LexError::FloatOutOfRange { offset: 4, text: "1e400".to_string() }]);
+            assert_eq!(errors[0].to_string(), "float literal out of range");
+            assert_eq!(tokens[2].kind, TokenKind::Float(0.0));
+            assert_eq!(tokens[6].kind, TokenKind::Float(1.5e308));
+            assert_eq!(tokens[10].kind, TokenKind::Float(0.0));
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn an_unterminated_comment_or_string_is_an_error_at_its_opening() {
+        for scanner in [Scanner::Bytes, Scanner::Regex] {
+            let errors = |source| tokenize_with(source, scanner).1;
+            assert_eq!(errors("x = 1; /* a\n* b */ /* c\nprint(x);"), [LexError::UnterminatedComment { offset: 19 }]);
+            assert_eq!(errors("x = \"a\\\";"), [LexError::UnterminatedString { offset: 4, quote: 1 }]);
+            assert_eq!(errors("x = \"\"\"a\"\";"), [LexError::UnterminatedString { offset: 4, quote: 3 }]);
+            assert_eq!(errors("x = r\"a;"), [LexError::UnterminatedString { offset: 4, quote: 2 }]);
+            let (tokens, errors) = tokenize_with("\"a\" \"b", scanner);
+            assert_eq!(errors[0].span(), Span::new(4, 5));
+            assert_eq!(errors[0].to_string(), "unterminated string literal");
+            assert_eq!(tokens.len(), 2);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::ffi::{c_int, c_void};
+use std::fs::File;
+use std::ops::Deref;
+use std::os::fd::AsRawFd;
+use std::{io, slice};
+
+const PROT_READ: c_int = 1;
+const MAP_PRIVATE: c_int = 2;
+
+unsafe extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+// A read-only mapping of a whole regular file. The file mustn't change while it's mapped.
+pub(super) struct Mmap {
+    ptr: *mut c_void,
+    len: usize
+}
+
+// The mapping is only ever read.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    pub(super) fn map(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        // Empty mappings aren't allowed.
+        if len == 0 {
+            return Ok(Mmap { ptr: std::ptr::null_mut(), len });
+        }
+        // SAFETY: the mapping is private and read-only, and stays valid after `file` is closed.
+        let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: `ptr` is a live mapping of `len` readable bytes.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: `ptr` and `len` describe a mapping made by `open` that nothing borrows anymore.
+            unsafe { munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_a_whole_file() {
+        let path = std::env::temp_dir().join(format!("tiny-lang-mmap-{}.lang", std::process::id()));
+        std::fs::write(&path, "let x = 1;\n").unwrap();
+        let mapped = Mmap::map(&File::open(&path).unwrap()).unwrap();
+        assert_eq!(&*mapped, b"let x = 1;\n");
+        // Emptying the file while it's still mapped would make reading the mapping fault.
+        drop(mapped);
+        std::fs::write(&path, "").unwrap();
+        assert!(Mmap::map(&File::open(&path).unwrap()).unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
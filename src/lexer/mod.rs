@@ -1,5 +1,17 @@
 mod token;
+mod error;
+mod format;
+mod source;
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+mod mmap;
+mod bytes;
+mod reader;
+#[allow(clippy::module_inception)]
 mod lexer;
 
-pub use token::Token;
-pub use lexer::tokenize;
\ No newline at end of file
+pub use token::{BorrowedToken, Token, TokenKind, quote_char, quote_string};
+pub use error::LexError;
+pub use format::{token_to_json, token_to_text, tokens_to_json, tokens_to_source, tokens_to_text};
+pub use source::{SourceBytes, Utf8Policy, decode_source, read_source};
+pub use lexer::{KEYWORDS, Lexer, LexerOptions, Scanner, tokenize, tokenize_borrowed, tokenize_with, tokenize_with_options};
+pub use reader::ReaderLexer;
@@ -0,0 +1,281 @@
+use std::io::{self, ErrorKind, Read};
+
+use super::bytes::{self, Incomplete};
+use super::error::LexError;
+use super::lexer::{LexerOptions, Scanner, create_lexer};
+use super::token::{BorrowedToken, Token, TokenKind};
+use crate::diagnostics::Span;
+
+const CHUNK: usize = 64 * 1024;
+
+// Lexes a source as it's read, holding on to about a chunk of text at a time.
+// Errors in the source are collected in `errors` as `tokenize` does, while failed
+// reads and invalid UTF-8 end the stream with an `io::Error`.
+pub struct ReaderLexer<R> {
+    reader: R,
+    // Text that has been read, starting at `offset` in the source. Everything before
+    // `pos` has been lexed.
+    buffer: String,
+    offset: usize,
+    pos: usize,
+    // Bytes read after the last complete character.
+    pending: Vec<u8>,
+    // The line and column, from 1, that `counted` in the source is at. Lines are counted
+    // as the buffer is lexed, since what's before it is dropped.
+    counted: usize,
+    line: usize,
+    column: usize,
+    // Where the token last returned starts.
+    start: (usize, usize),
+    // Always scanned by bytes, which knows when a token needs more input.
+    options: LexerOptions,
+    eof: bool,
+    done: bool,
+    pub errors: Vec<LexError>,
+    // The line and column of each of `errors`.
+    error_positions: Vec<(usize, usize)>
+}
+
+fn invalid_utf8(offset: usize) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, LexError::InvalidUtf8 { offset })
+}
+
+impl<R: Read> ReaderLexer<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, LexerOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: LexerOptions) -> Self {
+        ReaderLexer {
+            reader,
+            options: LexerOptions { scanner: Scanner::Bytes, ..options },
+            buffer: String::new(),
+            offset: 0,
+            pos: 0,
+            pending: Vec::new(),
+            counted: 0,
+            line: 1,
+            column: 1,
+            start: (1, 1),
+            eof: false,
+            done: false,
+            errors: Vec::new(),
+            error_positions: Vec::new(),
+        }
+    }
+
+    // Reads the next chunk onto the end of the buffer.
+    fn fill(&mut self) -> io::Result<()> {
+        let start = self.pending.len();
+        self.pending.resize(start + CHUNK, 0);
+        let n = loop {
+            match self.reader.read(&mut self.pending[start..]) {
+                Ok(n) => break n,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        };
+        self.pending.truncate(start + n);
+        let at = self.offset + self.buffer.len();
+        if n == 0 {
+            self.eof = true;
+            return if self.pending.is_empty() { Ok(()) } else { Err(invalid_utf8(at)) };
+        }
+        // A character split between reads is finished by the next one.
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(invalid_utf8(at + error.valid_up_to())),
+        };
+        self.buffer.push_str(std::str::from_utf8(&self.pending[..valid]).unwrap());
+        self.pending.drain(..valid);
+        Ok(())
+    }
+
+    // The line and column where the token last returned starts, each from 1, as a
+    // `LineIndex` over the whole source would give them.
+    pub fn line_column(&self) -> (usize, usize) {
+        self.start
+    }
+
+    // Each of `errors` with the line and column it's at, since the source it's in may be
+    // gone by the time it's reported.
+    pub fn located_errors(&self) -> impl Iterator<Item = (&LexError, (usize, usize))> {
+        self.errors.iter().zip(self.error_positions.iter().copied())
+    }
+
+    // The line and column of `offset` in the source, which mustn't be before anything
+    // located so far.
+    fn locate(&mut self, offset: usize) -> (usize, usize) {
+        self.count_to(offset);
+        (self.line, self.column)
+    }
+
+    // Records `errors`, which are in the buffer from where lines have been counted to.
+    fn push_errors(&mut self, mut errors: Vec<LexError>) {
+        errors.sort_by_key(LexError::offset);
+        for error in errors {
+            let position = self.locate(error.offset());
+            self.errors.push(error);
+            self.error_positions.push(position);
+        }
+    }
+
+    // Counts the lines and columns in the buffer up to `offset` in the source.
+    fn count_to(&mut self, offset: usize) {
+        for c in self.buffer[self.counted - self.offset..offset - self.offset].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.counted = offset;
+    }
+
+    // Drops text that has been lexed once there's a chunk of it.
+    fn compact(&mut self) {
+        if self.pos >= CHUNK {
+            self.count_to(self.offset + self.pos);
+            self.buffer.drain(..self.pos);
+            self.offset += self.pos;
+            self.pos = 0;
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.buffer[self.pos..].chars().next().map_or(1, char::len_utf8)
+    }
+
+    // Records the unrecognized text and skips ahead to where some token matches again.
+    fn skip_unrecognized(&mut self) -> io::Result<()> {
+        let start = self.pos;
+        self.pos += self.width();
+        loop {
+            if self.pos == self.buffer.len() {
+                if self.eof {
+                    break;
+                }
+                self.fill()?;
+                continue;
+            }
+            match bytes::match_token(&self.buffer[self.pos..], self.eof) {
+                Ok(None) => self.pos += self.width(),
+                Ok(Some(_)) => break,
+                Err(Incomplete) => self.fill()?,
+            }
+        }
+        let text = self.buffer[start..self.pos].to_string();
+        self.push_errors(vec![LexError::UnrecognizedToken { offset: self.offset + start, text }]);
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> io::Result<Token> {
+        loop {
+            if self.pos == self.buffer.len() {
+                if self.eof {
+                    self.done = true;
+                    let end = self.offset + self.pos;
+                    self.start = self.locate(end);
+                    return Ok(Token { kind: TokenKind::EndOfInput, span: Span::new(end, end) });
+                }
+                self.fill()?;
+                continue;
+            }
+            let (handler, len) = match bytes::match_token(&self.buffer[self.pos..], self.eof) {
+                Ok(Some(found)) => found,
+                Ok(None) => {
+                    self.skip_unrecognized()?;
+                    continue;
+                }
+                Err(Incomplete) => {
+                    self.fill()?;
+                    continue;
+                }
+            };
+            // The token is built by a lexer over the buffer, placed where it sits in the source.
+            let mut lexer = create_lexer(&self.buffer, self.options);
+            lexer.pos = self.pos;
+            lexer.base = self.offset;
+            lexer.handle_pattern(&handler, len);
+            self.pos = lexer.pos;
+            let token = lexer.tokens.pop().map(BorrowedToken::into_owned);
+            let errors = std::mem::take(&mut lexer.errors);
+            // A token's errors are inside it, after where it starts.
+            if let Some(token) = &token {
+                self.start = self.locate(token.span.start);
+            }
+            self.push_errors(errors);
+            self.compact();
+            if let Some(token) = token {
+                return Ok(token);
+            }
+        }
+    }
+}
+
+// Yields tokens up to and including `EndOfInput`.
+impl<R: Read> Iterator for ReaderLexer<R> {
+    type Item = io::Result<Token>;
+
+    fn next(&mut self) -> Option<io::Result<Token>> {
+        if self.done {
+            return None;
+        }
+        let token = self.next_token();
+        if token.is_err() {
+            self.done = true;
+        }
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::LineIndex;
+    use crate::lexer::tokenize;
+
+    // Gives one byte per read, so every token and character is split between reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first() else { return Ok(0) };
+            buffer[0] = first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn lexes_a_source_read_a_byte_at_a_time_as_tokenize_does() {
+        let source = "naïve = \"日本\" + 'é'; /* c */ print(naïve, 12.5e2, r\"x\\y\") # `\n";
+        let mut lexer = ReaderLexer::new(Trickle(source.as_bytes()));
+        let tokens: Vec<Token> = lexer.by_ref().collect::<io::Result<_>>().unwrap();
+        let (expected, errors) = tokenize(source);
+        assert_eq!(tokens, expected);
+        assert_eq!(lexer.errors, errors);
+
+        let error = ReaderLexer::new(Trickle(b"x = 1; \xff")).find_map(Result::err).unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid UTF-8 at byte 7");
+    }
+
+    #[test]
+    fn tells_where_each_token_and_error_is_as_the_source_is_dropped() {
+        // Long enough that what's been lexed is dropped part way through.
+        let source = "é = 1; @\nprint(\"a\\q\");\n".repeat(CHUNK / 8) + "x = \"open";
+        let lines = LineIndex::new(&source);
+        let mut lexer = ReaderLexer::new(source.as_bytes());
+        while let Some(token) = lexer.next() {
+            assert_eq!(lexer.line_column(), lines.line_column(token.unwrap().span.start));
+        }
+        let located: Vec<_> = lexer.located_errors().map(|(error, position)| (error.clone(), position)).collect();
+        assert_eq!(located.len(), 2 * (CHUNK / 8) + 1);
+        for (error, position) in located {
+            assert_eq!(position, lines.line_column(error.offset()));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::path::Path;
+use std::io;
+
+use super::error::LexError;
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+use super::mmap::Mmap;
+
+// What to do with source bytes that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    #[default]
+    Strict,
+    // Replace each invalid sequence with U+FFFD.
+    Lossy
+}
+
+// Valid sources are borrowed rather than copied.
+pub fn decode_source(bytes: &[u8], policy: Utf8Policy) -> Result<Cow<'_, str>, LexError> {
+    match std::str::from_utf8(bytes) {
+        Ok(source) => Ok(Cow::Borrowed(source)),
+        Err(error) => match policy {
+            Utf8Policy::Strict => Err(LexError::InvalidUtf8 { offset: error.valid_up_to() }),
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(bytes)),
+        },
+    }
+}
+
+// The contents of a source file. With the `mmap` feature a regular file is mapped into
+// memory instead of read, so even very large files aren't copied. Pipes, such as
+// `/dev/stdin`, and files that say they're empty, as those in `/proc` do, are still read.
+pub struct SourceBytes {
+    #[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+    mapped: Option<Mmap>,
+    bytes: Vec<u8>
+}
+
+impl Deref for SourceBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        #[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+        if let Some(mapped) = &self.mapped {
+            return mapped;
+        }
+        &self.bytes
+    }
+}
+
+pub fn read_source(path: impl AsRef<Path>) -> io::Result<SourceBytes> {
+    #[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+    {
+        use std::io::Read;
+
+        let mut file = std::fs::File::open(path)?;
+        let metadata = file.metadata()?;
+        if metadata.is_file() && metadata.len() > 0 {
+            return Ok(SourceBytes { mapped: Some(Mmap::map(&file)?), bytes: Vec::new() });
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(SourceBytes { mapped: None, bytes })
+    }
+    #[cfg(not(all(feature = "mmap", unix, target_pointer_width = "64")))]
+    Ok(SourceBytes { bytes: std::fs::read(path)? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_cant_be_mapped() {
+        let path = std::env::temp_dir().join(format!("tiny-lang-source-{}.lang", std::process::id()));
+        std::fs::write(&path, "print(1);\n").unwrap();
+        assert_eq!(&*read_source(&path).unwrap(), b"print(1);\n");
+        std::fs::remove_file(&path).unwrap();
+        // Files in `/proc` have a length of 0 but aren't empty.
+        if cfg!(target_os = "linux") {
+            assert!(read_source("/proc/self/status").unwrap().starts_with(b"Name:"));
+        }
+    }
+}
@@ -1,11 +1,37 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Token {
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+
+// `S` is the text of string literals, borrowed from the source by the lexer and owned
+// everywhere after.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token<S = String> {
+    pub kind: TokenKind<S>,
+    pub span: Span
+}
+
+pub type BorrowedToken<'src> = Token<Cow<'src, str>>;
+
+impl BorrowedToken<'_> {
+    pub fn into_owned(self) -> Token {
+        Token { kind: self.kind.map(Cow::into_owned), span: self.span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenKind<S = String> {
     EndOfInput,
     OpMultiply,
     OpDivide,
     OpMod,
     OpAdd,
     OpSubtract,
+    OpIncrement,
+    OpDecrement,
     OpNot,
     OpLess,
     OpLessEqual,
@@ -14,20 +40,284 @@ pub enum Token {
     OpEqual,
     OpNotEqual,
     OpAssign,
+    OpAddAssign,
+    OpSubtractAssign,
+    OpMultiplyAssign,
+    OpDivideAssign,
+    OpModAssign,
     OpAnd,
     OpOr,
+    OpBitAnd,
+    OpBitOr,
+    OpBitXor,
+    OpBitNot,
+    OpShiftLeft,
+    OpShiftRight,
     KeywordIf,
     KeywordElse,
     KeywordWhile,
+    KeywordFor,
+    KeywordDo,
     KeywordPrint,
     KeywordPutc,
+    KeywordFn,
+    KeywordReturn,
+    KeywordImport,
+    KeywordTrue,
+    KeywordFalse,
     OpenParen,
     CloseParen,
     OpenBrace,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
     Semicolon,
     Comma,
-    Indentifier(String),
+    Question,
+    Colon,
+    Hash,
+    Indentifier(Symbol),
     Integer(i64),
-    String (String)
-}
\ No newline at end of file
+    Char(char),
+    Float(f64),
+    String (S),
+    // Only produced when the lexer is asked to keep trivia.
+    Comment(S),
+    // A `///` line comment, documenting the function declared after it.
+    DocComment(S),
+    Whitespace(S)
+}
+impl<S> TokenKind<S> {
+    // Token names as used by the Rosetta Code lexical analyzer task.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::EndOfInput => "End_of_input",
+            TokenKind::OpMultiply => "Op_multiply",
+            TokenKind::OpDivide => "Op_divide",
+            TokenKind::OpMod => "Op_mod",
+            TokenKind::OpAdd => "Op_add",
+            TokenKind::OpSubtract => "Op_subtract",
+            TokenKind::OpIncrement => "Op_increment",
+            TokenKind::OpDecrement => "Op_decrement",
+            TokenKind::OpNot => "Op_not",
+            TokenKind::OpLess => "Op_less",
+            TokenKind::OpLessEqual => "Op_lessequal",
+            TokenKind::OpGreater => "Op_greater",
+            TokenKind::OpGreaterEqual => "Op_greaterequal",
+            TokenKind::OpEqual => "Op_equal",
+            TokenKind::OpNotEqual => "Op_notequal",
+            TokenKind::OpAssign => "Op_assign",
+            TokenKind::OpAddAssign => "Op_addassign",
+            TokenKind::OpSubtractAssign => "Op_subtractassign",
+            TokenKind::OpMultiplyAssign => "Op_multiplyassign",
+            TokenKind::OpDivideAssign => "Op_divideassign",
+            TokenKind::OpModAssign => "Op_modassign",
+            TokenKind::OpAnd => "Op_and",
+            TokenKind::OpOr => "Op_or",
+            TokenKind::OpBitAnd => "Op_bitand",
+            TokenKind::OpBitOr => "Op_bitor",
+            TokenKind::OpBitXor => "Op_bitxor",
+            TokenKind::OpBitNot => "Op_bitnot",
+            TokenKind::OpShiftLeft => "Op_shiftleft",
+            TokenKind::OpShiftRight => "Op_shiftright",
+            TokenKind::KeywordIf => "Keyword_if",
+            TokenKind::KeywordElse => "Keyword_else",
+            TokenKind::KeywordWhile => "Keyword_while",
+            TokenKind::KeywordFor => "Keyword_for",
+            TokenKind::KeywordDo => "Keyword_do",
+            TokenKind::KeywordPrint => "Keyword_print",
+            TokenKind::KeywordPutc => "Keyword_putc",
+            TokenKind::KeywordFn => "Keyword_fn",
+            TokenKind::KeywordReturn => "Keyword_return",
+            TokenKind::KeywordImport => "Keyword_import",
+            TokenKind::KeywordTrue => "Keyword_true",
+            TokenKind::KeywordFalse => "Keyword_false",
+            TokenKind::OpenParen => "LeftParen",
+            TokenKind::CloseParen => "RightParen",
+            TokenKind::OpenBrace => "LeftBrace",
+            TokenKind::CloseBrace => "RightBrace",
+            TokenKind::OpenBracket => "LeftBracket",
+            TokenKind::CloseBracket => "RightBracket",
+            TokenKind::Semicolon => "Semicolon",
+            TokenKind::Comma => "Comma",
+            TokenKind::Question => "Question",
+            TokenKind::Colon => "Colon",
+            TokenKind::Hash => "Hash",
+            TokenKind::Indentifier(_) => "Identifier",
+            TokenKind::Integer(_) => "Integer",
+            TokenKind::Char(_) => "Char",
+            TokenKind::Float(_) => "Float",
+            TokenKind::String(_) => "String",
+            TokenKind::Comment(_) => "Comment",
+            TokenKind::DocComment(_) => "DocComment",
+            TokenKind::Whitespace(_) => "Whitespace",
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenKind::Comment(_) | TokenKind::DocComment(_) | TokenKind::Whitespace(_))
+    }
+
+    // Converts the text of a string or trivia, keeping every other kind as it is.
+    pub fn map<T>(self, f: impl FnOnce(S) -> T) -> TokenKind<T> {
+        match self {
+            TokenKind::EndOfInput => TokenKind::EndOfInput,
+            TokenKind::OpMultiply => TokenKind::OpMultiply,
+            TokenKind::OpDivide => TokenKind::OpDivide,
+            TokenKind::OpMod => TokenKind::OpMod,
+            TokenKind::OpAdd => TokenKind::OpAdd,
+            TokenKind::OpSubtract => TokenKind::OpSubtract,
+            TokenKind::OpIncrement => TokenKind::OpIncrement,
+            TokenKind::OpDecrement => TokenKind::OpDecrement,
+            TokenKind::OpNot => TokenKind::OpNot,
+            TokenKind::OpLess => TokenKind::OpLess,
+            TokenKind::OpLessEqual => TokenKind::OpLessEqual,
+            TokenKind::OpGreater => TokenKind::OpGreater,
+            TokenKind::OpGreaterEqual => TokenKind::OpGreaterEqual,
+            TokenKind::OpEqual => TokenKind::OpEqual,
+            TokenKind::OpNotEqual => TokenKind::OpNotEqual,
+            TokenKind::OpAssign => TokenKind::OpAssign,
+            TokenKind::OpAddAssign => TokenKind::OpAddAssign,
+            TokenKind::OpSubtractAssign => TokenKind::OpSubtractAssign,
+            TokenKind::OpMultiplyAssign => TokenKind::OpMultiplyAssign,
+            TokenKind::OpDivideAssign => TokenKind::OpDivideAssign,
+            TokenKind::OpModAssign => TokenKind::OpModAssign,
+            TokenKind::OpAnd => TokenKind::OpAnd,
+            TokenKind::OpOr => TokenKind::OpOr,
+            TokenKind::OpBitAnd => TokenKind::OpBitAnd,
+            TokenKind::OpBitOr => TokenKind::OpBitOr,
+            TokenKind::OpBitXor => TokenKind::OpBitXor,
+            TokenKind::OpBitNot => TokenKind::OpBitNot,
+            TokenKind::OpShiftLeft => TokenKind::OpShiftLeft,
+            TokenKind::OpShiftRight => TokenKind::OpShiftRight,
+            TokenKind::KeywordIf => TokenKind::KeywordIf,
+            TokenKind::KeywordElse => TokenKind::KeywordElse,
+            TokenKind::KeywordWhile => TokenKind::KeywordWhile,
+            TokenKind::KeywordFor => TokenKind::KeywordFor,
+            TokenKind::KeywordDo => TokenKind::KeywordDo,
+            TokenKind::KeywordPrint => TokenKind::KeywordPrint,
+            TokenKind::KeywordPutc => TokenKind::KeywordPutc,
+            TokenKind::KeywordFn => TokenKind::KeywordFn,
+            TokenKind::KeywordReturn => TokenKind::KeywordReturn,
+            TokenKind::KeywordImport => TokenKind::KeywordImport,
+            TokenKind::KeywordTrue => TokenKind::KeywordTrue,
+            TokenKind::KeywordFalse => TokenKind::KeywordFalse,
+            TokenKind::OpenParen => TokenKind::OpenParen,
+            TokenKind::CloseParen => TokenKind::CloseParen,
+            TokenKind::OpenBrace => TokenKind::OpenBrace,
+            TokenKind::CloseBrace => TokenKind::CloseBrace,
+            TokenKind::OpenBracket => TokenKind::OpenBracket,
+            TokenKind::CloseBracket => TokenKind::CloseBracket,
+            TokenKind::Semicolon => TokenKind::Semicolon,
+            TokenKind::Comma => TokenKind::Comma,
+            TokenKind::Question => TokenKind::Question,
+            TokenKind::Colon => TokenKind::Colon,
+            TokenKind::Hash => TokenKind::Hash,
+            TokenKind::Indentifier(name) => TokenKind::Indentifier(name),
+            TokenKind::Integer(x) => TokenKind::Integer(x),
+            TokenKind::Char(x) => TokenKind::Char(x),
+            TokenKind::Float(x) => TokenKind::Float(x),
+            TokenKind::String(s) => TokenKind::String(f(s)),
+            TokenKind::Comment(s) => TokenKind::Comment(f(s)),
+            TokenKind::DocComment(s) => TokenKind::DocComment(f(s)),
+            TokenKind::Whitespace(s) => TokenKind::Whitespace(f(s)),
+        }
+    }
+}
+
+// A string literal as it would be written in source, with escapes for the characters that need them.
+pub fn quote_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\0' => out.push_str("\\0"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// A character literal as it would be written in source.
+pub fn quote_char(c: char) -> String {
+    match c {
+        '\n' => "'\\n'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        '\r' => "'\\r'".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        '\'' => "'\\''".to_string(),
+        '\0' => "'\\0'".to_string(),
+        c if c.is_control() => format!("'\\u{{{:x}}}'", c as u32),
+        c => format!("'{}'", c),
+    }
+}
+
+impl<S: AsRef<str>> fmt::Display for TokenKind<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenKind::EndOfInput => "<eof>",
+            TokenKind::OpMultiply => "*",
+            TokenKind::OpDivide => "/",
+            TokenKind::OpMod => "%",
+            TokenKind::OpAdd => "+",
+            TokenKind::OpSubtract => "-",
+            TokenKind::OpIncrement => "++",
+            TokenKind::OpDecrement => "--",
+            TokenKind::OpNot => "!",
+            TokenKind::OpLess => "<",
+            TokenKind::OpLessEqual => "<=",
+            TokenKind::OpGreater => ">",
+            TokenKind::OpGreaterEqual => ">=",
+            TokenKind::OpEqual => "==",
+            TokenKind::OpNotEqual => "!=",
+            TokenKind::OpAssign => "=",
+            TokenKind::OpAddAssign => "+=",
+            TokenKind::OpSubtractAssign => "-=",
+            TokenKind::OpMultiplyAssign => "*=",
+            TokenKind::OpDivideAssign => "/=",
+            TokenKind::OpModAssign => "%=",
+            TokenKind::OpAnd => "&&",
+            TokenKind::OpOr => "||",
+            TokenKind::OpBitAnd => "&",
+            TokenKind::OpBitOr => "|",
+            TokenKind::OpBitXor => "^",
+            TokenKind::OpBitNot => "~",
+            TokenKind::OpShiftLeft => "<<",
+            TokenKind::OpShiftRight => ">>",
+            TokenKind::KeywordIf => "if",
+            TokenKind::KeywordElse => "else",
+            TokenKind::KeywordWhile => "while",
+            TokenKind::KeywordFor => "for",
+            TokenKind::KeywordDo => "do",
+            TokenKind::KeywordPrint => "print",
+            TokenKind::KeywordPutc => "putc",
+            TokenKind::KeywordFn => "fn",
+            TokenKind::KeywordReturn => "return",
+            TokenKind::KeywordImport => "import",
+            TokenKind::KeywordTrue => "true",
+            TokenKind::KeywordFalse => "false",
+            TokenKind::OpenParen => "(",
+            TokenKind::CloseParen => ")",
+            TokenKind::OpenBrace => "{",
+            TokenKind::CloseBrace => "}",
+            TokenKind::OpenBracket => "[",
+            TokenKind::CloseBracket => "]",
+            TokenKind::Semicolon => ";",
+            TokenKind::Comma => ",",
+            TokenKind::Question => "?",
+            TokenKind::Colon => ":",
+            TokenKind::Hash => "#",
+            TokenKind::Indentifier(name) => return write!(f, "{}", name),
+            TokenKind::Integer(n) => return write!(f, "{}", n),
+            TokenKind::Char(c) => return f.write_str(&quote_char(*c)),
+            TokenKind::Float(x) => return write!(f, "{:?}", x),
+            TokenKind::String(s) => return f.write_str(&quote_string(s.as_ref())),
+            TokenKind::Comment(s) | TokenKind::DocComment(s) | TokenKind::Whitespace(s) => s.as_ref(),
+        };
+        f.write_str(text)
+    }
+}
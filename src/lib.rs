@@ -1,5 +1,34 @@
+pub mod diagnostics;
+pub mod intern;
 pub mod lexer;
+pub mod preprocess;
+pub mod modules;
+pub mod project;
 pub mod parser;
+pub mod sema;
+pub mod interpreter;
+pub mod stdlib;
+pub mod opt;
+pub mod codegen;
+pub mod ir;
+pub mod passes;
+pub mod vm;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub mod jit;
+pub mod fmt;
+pub mod highlight;
+pub mod doc;
+pub mod repl;
+pub mod debugger;
+pub mod dap;
+pub mod trace;
+pub mod coverage;
+pub mod profile;
+pub mod embed;
 
-pub use lexer::{Token, tokenize};
-pub use parser::parser::{Parser, parse_statement};
\ No newline at end of file
+pub use diagnostics::Diagnostic;
+pub use lexer::{LexError, Token, TokenKind, tokenize, tokens_to_json};
+pub use parser::parser::{Parser, parse_statement, parse_program};
+pub use interpreter::{Interpreter, RuntimeError, Value};
+pub use embed::{Config, Error, RunOutcome, eval_expr, run_str};
+pub use highlight::{HighlightClass, highlight};
@@ -1,26 +1,1188 @@
-use std::{env, fs, io};
+use std::borrow::Cow;
+use std::io::{LineWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io, process, thread};
 
-use tiny_lang::lexer::tokenize;
-use tiny_lang::{Parser, parse_statement};
+use tiny_lang::codegen::{Bytecode, Syntax, compile, emit_asm, emit_c, emit_js, emit_rust, emit_wasm};
+#[cfg(feature = "llvm")]
+use tiny_lang::codegen::{build_native, emit_llvm};
+use tiny_lang::diagnostics::{
+    Diagnostic, LINT_GROUPS, LINTS, Level, LintLevels, Renderer, Severity, SourceFile, explain, format_explanation,
+    stderr_supports_color
+};
+use tiny_lang::fmt::{FormatConfig, format_program_with};
+use tiny_lang::highlight::{THEMES, Theme, to_ansi, to_html};
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+use tiny_lang::jit::Jit;
+use tiny_lang::ir::{build_cfgs, build_ssa, cfgs_to_dot, cfgs_to_text};
+use tiny_lang::interpreter::Overflow;
+use tiny_lang::lexer::{
+    BorrowedToken, LexerOptions, ReaderLexer, SourceBytes, Token, Utf8Policy, decode_source, read_source,
+    token_to_json, token_to_text, tokenize_with_options, tokens_to_json, tokens_to_text
+};
+use tiny_lang::modules::Linker;
+use tiny_lang::project::{CACHE, Cache, Project};
+use tiny_lang::parser::ast::Statement;
+use tiny_lang::parser::comments::{Comments, attach_comments};
+use tiny_lang::parser::cst::{SyntaxNode, print_syntax_tree};
+use tiny_lang::parser::dot::program_to_dot;
+use tiny_lang::parser::grammar::grammar_to_ebnf;
+use tiny_lang::parser::printer::print_program;
+use tiny_lang::passes::{OptLevel, PASSES, PassManager};
+use tiny_lang::preprocess::{Included, has_directives, include, preprocess};
+use tiny_lang::sema::{LintScopes, SemaError, check_assignments, check_reachable, check_shadowing, check_types, check_unused, resolve};
+use tiny_lang::stdlib::Capabilities;
+use tiny_lang::coverage::Coverage;
+use tiny_lang::profile::Profiler;
+use tiny_lang::trace::{Tracer, vm_tracer};
+use tiny_lang::vm::{RunConfig, Vm, VmError};
+use tiny_lang::{Interpreter, Parser, RuntimeError, dap, debugger, doc, parse_program, repl};
 
-fn main() -> io::Result<()> {
+const USAGE: &str = "\
+Usage: tiny-lang <command> [options] <file>... [-- <argument>...]
+       tiny-lang repl
+       tiny-lang dap --port <n>
+       tiny-lang grammar [--format ebnf]
+       tiny-lang highlight [--format ansi|html] [--theme <name>] <file>
+       tiny-lang explain <code>
+
+Commands:
+    lex        Print the token stream; `-` lexes stdin, printing tokens as they're read
+    parse      Print the syntax tree
+    run        Interpret the program, or run a compiled .tbc file in the VM
+    debug      Interpret one program, pausing at breakpoints and steps to inspect it
+    check      Report undeclared names, type errors and warnings
+    compile    Print the generated bytecode
+    build      Compile each program in a directory, those no other file imports or
+               includes, to a .tbc file in its build directory, or -o's; a program
+               that hasn't changed since, nor has anything it brings in, is skipped.
+               With --standalone, package a program and the VM as one executable
+    analyze    Print an analysis of the generated bytecode: --cfg
+    fmt        Print the program in canonical formatting, keeping its comments
+    doc        Print a summary of the program's functions, with the `///` comments
+               before each, as Markdown (the default) or with --format html
+    repl       Start an interactive session
+    dap        Serve the debugger over the Debug Adapter Protocol, for editors, on a
+               port on localhost; the editor's launch request names the program
+    grammar    Print the grammar the parser accepts
+    highlight  Print the source in color, for a terminal (ansi, the default) or as
+               HTML with its stylesheet; --theme is light (default), dark or mono
+    explain    Describe a diagnostic code, such as E0301, with an example
+
+Options:
+    --emit <stage>    Print a pipeline stage instead: tokens, ast, ssa, bytecode or c
+    --target <name>   With compile, what to compile to: bytecode (default), c, llvm,
+                      wasm32, x86_64-asm, rust or js
+    --syntax <name>   With --target x86_64-asm, the assembly syntax: att (default) or intel
+    -o <file>         With compile, write the output to a file instead of stdout; with
+                      --target llvm, a .o file is an object file, a .ll file LLVM IR
+                      and anything else an executable. --target wasm32 needs one.
+                      Bytecode written to a .tbc file is binary, for `run` to load
+    --format <name>   Output format for tokens: text (default) or json;
+                      for the syntax tree: text (default), dot or cst;
+                      for the control-flow graph: text (default) or dot;
+                      for doc: markdown (default) or html
+    --check           With fmt, exit with status 1 if the file isn't formatted. fmt
+                      reads its options from the nearest tinyfmt.toml: indent_width,
+                      brace_style (\"same_line\" or \"next_line\") and max_width
+    --vm              With run, execute the compiled bytecode instead of the syntax tree
+    --jit             With run, compile the program to machine code and execute that;
+                      programs the JIT can't compile are interpreted instead
+    --fuel <n>        With run, stop the program with an error once it has run n
+                      statements, or with --vm n instructions
+    --timeout <secs>  With run --vm or a .tbc file, stop the program with an error once
+                      it has run for that many seconds
+    --gc-stress       With run --vm or a .tbc file, collect garbage before every
+                      allocation, to test the collector
+    --stats           With run --vm or a .tbc file, print what the heap and the string
+                      pool did to stderr once the program ends
+    --trace           With run, log each statement run, or with --vm each instruction,
+                      with its line and the values it uses, to stderr
+    --trace-file <f>  With run, write the --trace log to a file instead
+    --coverage        With run, print how many times each line ran to stderr once the
+                      program ends; the program isn't optimized first
+    --lcov <file>     With run, write the coverage as an lcov tracefile
+    --profile         With run, print the functions and lines the program spent the most
+                      time in to stderr once it ends; it isn't optimized first
+    --overflow <how>  With run, what integer arithmetic that overflows does: wrap
+                      (default), saturate or trap, which is an error
+    --seed <n>        With run, start `rand` from this seed instead of the same one
+                      every run
+    --allow-files     With run, let the program open files with `fopen`, which it
+                      otherwise can't
+    --allow-env       With run, let the program read environment variables with
+                      `getenv`, which it otherwise can't
+    -- <argument>...  With run, give the program these arguments, which it reads with
+                      `argc()` and `argv(i)`; `argv(0)` is the program's file
+    --standalone      With build, write an executable that runs the program by itself,
+                      named by -o or after the file
+    --cfg             With analyze, print the control-flow graph of the top level and of
+                      each function
+    --lossy           Replace invalid UTF-8 in the source instead of rejecting it
+    --trivia          With lex, also print comments and whitespace
+    -D <name>         Define a name for `#if`, keeping what's between `#if <name>` and its
+                      `#else` or `#endif` and dropping what's after the `#else`
+    -I <dir>          Look for imported modules in this directory too, after the
+                      importing file's own
+    -W <lint>         Turn a warning on
+    -A <lint>         Turn a warning off
+    --deny <lint>     Make a warning an error; `--deny warnings` makes them all errors
+    -O0, -O1, -O2     How much to optimize before running or compiling (default -O2)
+    --print-passes    Print each optimization pass as it runs
+    --stop-after <pass>
+                      Run no optimization passes after this one
+
+Lints:
+{lints}
+
+Passes:
+{passes}";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    Tokens,
+    Ast,
+    Ssa,
+    Bytecode,
+    C,
+    Llvm,
+    Wasm,
+    Asm,
+    Rust,
+    Js
+}
+
+impl Emit {
+    // The stages `compile --target` can produce.
+    fn target(s: &str) -> Option<Self> {
+        match s {
+            "bytecode" => Some(Emit::Bytecode),
+            "c" => Some(Emit::C),
+            "llvm" => Some(Emit::Llvm),
+            "wasm32" => Some(Emit::Wasm),
+            "x86_64-asm" => Some(Emit::Asm),
+            "rust" => Some(Emit::Rust),
+            "js" => Some(Emit::Js),
+            _ => None,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "ssa" => Some(Emit::Ssa),
+            "bytecode" => Some(Emit::Bytecode),
+            "c" => Some(Emit::C),
+            _ => None,
+        }
+    }
+}
+
+struct Options {
+    command: String,
+    files: Vec<String>,
+    emit: Option<Emit>,
+    target: Option<Emit>,
+    output: Option<String>,
+    syntax: Option<Syntax>,
+    format: Option<String>,
+    check: bool,
+    vm: bool,
+    jit: bool,
+    standalone: bool,
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    overflow: Option<Overflow>,
+    seed: Option<i64>,
+    capabilities: Capabilities,
+    // Those after `--`, for `argv`.
+    arguments: Vec<String>,
+    gc_stress: bool,
+    stats: bool,
+    trace: bool,
+    trace_file: Option<String>,
+    coverage: bool,
+    lcov: Option<String>,
+    profile: bool,
+    cfg: bool,
+    utf8: Utf8Policy,
+    trivia: bool,
+    defines: Vec<String>,
+    search: Vec<String>,
+    lints: LintLevels,
+    level: Option<OptLevel>,
+    print_passes: bool,
+    stop_after: Option<String>
+}
+
+// The usage text, with the lints and passes there are.
+fn usage_text() -> String {
+    let mut lints = String::new();
+    for lint in LINTS {
+        let default = if lint.default == Level::Allow { " (off by default)" } else { "" };
+        lints.push_str(&format!("    {:<24} {}{}\n", lint.name, lint.description, default));
+    }
+    for (group, members) in LINT_GROUPS {
+        lints.push_str(&format!("    {:<24} {}\n", group, members.join(", ")));
+    }
+    let mut passes = String::new();
+    for pass in PASSES {
+        passes.push_str(&format!("    {:<24} {}\n", pass.name, pass.description));
+    }
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let names = if level.passes().is_empty() { "none".to_string() } else { level.passes().join(", ") };
+        passes.push_str(&format!("    {:<24} {}\n", level, names));
+    }
+    USAGE.replace("{lints}", lints.trim_end()).replace("{passes}", passes.trim_end())
+}
+
+// Says what's wrong with the command line, then how it's used.
+fn usage(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, usage_text());
+    process::exit(2);
+}
+
+// Says which file an I/O error is about, which the error itself doesn't.
+fn in_file(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> io::Error {
+    move |error| io::Error::new(error.kind(), format!("{}: {}", path.as_ref().display(), error))
+}
+
+fn parse_args(args: &[String]) -> Options {
+    let mut args = args.iter().skip(1);
+    let command = args.next().unwrap_or_else(|| {
+        eprintln!("{}", usage_text());
+        process::exit(2);
+    }).clone();
+    let mut files = Vec::new();
+    let mut emit = None;
+    let mut target = None;
+    let mut output = None;
+    let mut syntax = None;
+    let mut format = None;
+    let mut check = false;
+    let mut vm = false;
+    let mut jit = false;
+    let mut standalone = false;
+    let mut fuel = None;
+    let mut timeout = None;
+    let mut overflow = None;
+    let mut seed = None;
+    let mut capabilities = Capabilities::default();
+    let mut arguments = Vec::new();
+    let mut gc_stress = false;
+    let mut stats = false;
+    let mut trace = false;
+    let mut trace_file = None;
+    let mut coverage = false;
+    let mut lcov = None;
+    let mut profile = false;
+    let mut cfg = false;
+    let mut utf8 = Utf8Policy::Strict;
+    let mut trivia = false;
+    let mut defines = Vec::new();
+    let mut search = Vec::new();
+    let mut lints = LintLevels::default();
+    let mut level = None;
+    let mut print_passes = false;
+    let mut stop_after = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => emit = Some(args.next().and_then(|s| Emit::parse(s)).unwrap_or_else(|| usage("--emit expects tokens, ast, ssa, bytecode or c"))),
+            "--target" => target = Some(args.next().and_then(|s| Emit::target(s)).unwrap_or_else(|| usage("--target expects bytecode, c, llvm, wasm32, x86_64-asm, rust or js"))),
+            "--syntax" => syntax = Some(args.next().and_then(|s| Syntax::parse(s)).unwrap_or_else(|| usage("--syntax expects att or intel"))),
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage("-o expects a file")).clone()),
+            "--format" => format = Some(args.next().unwrap_or_else(|| usage("--format expects a format")).clone()),
+            "--check" => check = true,
+            "--vm" => vm = true,
+            "--jit" => jit = true,
+            "--standalone" => standalone = true,
+            "--fuel" => fuel = Some(args.next().and_then(|s| s.parse().ok()).unwrap_or_else(|| usage("--fuel expects a number"))),
+            "--gc-stress" => gc_stress = true,
+            "--stats" => stats = true,
+            "--trace" => trace = true,
+            "--trace-file" => {
+                trace = true;
+                trace_file = Some(args.next().unwrap_or_else(|| usage("--trace-file expects a file")).clone());
+            }
+            "--allow-files" => capabilities.files = true,
+            "--allow-env" => capabilities.env = true,
+            "--" => arguments.extend(args.by_ref().cloned()),
+            "--seed" => seed = Some(args.next().and_then(|s| s.parse().ok()).unwrap_or_else(|| usage("--seed expects a number"))),
+            "--overflow" => overflow = Some(args.next().and_then(|s| Overflow::parse(s)).unwrap_or_else(|| usage("--overflow expects wrap, saturate or trap"))),
+            "--timeout" => {
+                let seconds = args.next().and_then(|s| s.parse().ok()).and_then(|s| Duration::try_from_secs_f64(s).ok());
+                timeout = Some(seconds.unwrap_or_else(|| usage("--timeout expects a number of seconds")));
+            }
+            "--coverage" => coverage = true,
+            "--profile" => profile = true,
+            "--lcov" => lcov = Some(args.next().unwrap_or_else(|| usage("--lcov expects a file")).clone()),
+            "--cfg" => cfg = true,
+            "--lossy" => utf8 = Utf8Policy::Lossy,
+            "--trivia" => trivia = true,
+            "-D" => defines.push(args.next().unwrap_or_else(|| usage("-D expects a name")).clone()),
+            "-I" => search.push(args.next().unwrap_or_else(|| usage("-I expects a directory")).clone()),
+            "-W" | "-A" | "--deny" => {
+                let level = match arg.as_str() {
+                    "-W" => Level::Warn,
+                    "-A" => Level::Allow,
+                    _ => Level::Deny,
+                };
+                let name = args.next().unwrap_or_else(|| usage(&format!("{} expects a lint", arg)));
+                if let Err(message) = lints.set(name, level) {
+                    eprintln!("error: {}", message);
+                    process::exit(2);
+                }
+            }
+            "-O0" | "-O1" | "-O2" => level = OptLevel::parse(arg),
+            "--print-passes" => print_passes = true,
+            "--stop-after" => stop_after = Some(args.next().unwrap_or_else(|| usage("--stop-after expects a pass")).clone()),
+            _ if arg.starts_with("--") || arg.starts_with("-O") => usage(&format!("unknown option `{}`", arg)),
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.is_empty() {
+        usage(&format!("{} expects a file", command));
+    }
+    Options { command, files, emit, target, output, syntax, format, check, vm, jit, standalone, fuel, timeout, overflow, seed, capabilities, arguments, gc_stress, stats, trace, trace_file, coverage, lcov, profile, cfg, utf8, trivia, defines, search, lints, level, print_passes, stop_after }
+}
+
+// A file that lexed and, unless only its tokens are wanted, parsed.
+struct Unit<'a> {
+    name: &'a str,
+    source: Cow<'a, str>,
+    tokens: Vec<Token>,
+    program: Vec<Statement>,
+    // Kept when the lexer kept trivia.
+    comments: Comments,
+    // Those it included, whose text is after its own in `source`.
+    files: Vec<SourceFile>,
+    // Whether it has `#if`s or `#include`s, which leave out some of it or bring more in.
+    directives: bool,
+    syntax: Option<SyntaxNode>,
+    warnings: Vec<Diagnostic>
+}
+
+// A file that didn't, with its diagnostics in source order.
+struct Failed<'a> {
+    name: &'a str,
+    source: Cow<'a, str>,
+    files: Vec<SourceFile>,
+    diagnostics: Vec<Diagnostic>
+}
+
+// What to make of each file.
+#[derive(Clone, Copy)]
+struct Stages<'a> {
+    lexer: LexerOptions,
+    utf8: Utf8Policy,
+    parse: bool,
+    syntax_tree: bool,
+    // The names `-D` defines for `#if`.
+    defines: &'a [String],
+    // Whether to link in the modules it imports, and the directories `-I` adds to look in.
+    link: bool,
+    search: &'a [String],
+    // Whether to resolve names, check types and look for likely mistakes before going on.
+    resolve: bool,
+    check_types: bool,
+    warnings: bool,
+    lints: &'a LintLevels,
+    // Whether to optimize the program once it's been checked, and how.
+    optimize: bool,
+    passes: &'a PassManager
+}
+
+// The file whose text is `source` as it failed, with the text of what it included and
+// imported after its own.
+fn failed<'a>(name: &'a str, source: Cow<'a, str>, included: Included, diagnostics: Vec<Diagnostic>) -> Failed<'a> {
+    let source = if included.files.is_empty() { source } else { Cow::Owned(source.into_owned() + &included.text) };
+    Failed { name, source, files: included.files, diagnostics }
+}
+
+fn load<'a>(name: &'a str, bytes: &'a [u8], stages: Stages<'_>) -> Result<Unit<'a>, Failed<'a>> {
+    let source = decode_source(bytes, stages.utf8).map_err(|error| Failed {
+        name,
+        // Only the valid prefix can be shown, which ends where the error is.
+        source: Cow::Borrowed(std::str::from_utf8(&bytes[..error.offset()]).unwrap()),
+        files: Vec::new(),
+        diagnostics: vec![Diagnostic::from(&error)],
+    })?;
+    let (tokens, errors) = tokenize_with_options(&source, stages.lexer);
+    let tokens: Vec<Token> = tokens.into_iter().map(BorrowedToken::into_owned).collect();
+    if !errors.is_empty() {
+        let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span().start);
+        return Err(Failed { name, source, files: Vec::new(), diagnostics });
+    }
+    if !stages.parse {
+        return Ok(Unit { name, source, tokens, program: Vec::new(), comments: Comments::default(), files: Vec::new(), directives: false, syntax: None, warnings: Vec::new() });
+    }
+    let directives = has_directives(&tokens);
+    let tokens = match preprocess(&source, tokens, stages.defines) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(Failed { name, source, files: Vec::new(), diagnostics: vec![Diagnostic::from(&error)] }),
+    };
+    let mut included = Included::default();
+    let tokens = include(Path::new(name), &source, tokens, stages.lexer, stages.defines, &mut included);
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(diagnostics) => return Err(failed(name, source, included, diagnostics)),
+    };
+    let trivia = stages.lexer.preserve_trivia.then(|| tokens.clone());
+    let mut parser = if stages.syntax_tree { Parser::with_syntax_tree(tokens) } else { Parser::new(tokens) };
+    let program = match parse_program(&mut parser) {
+        Ok(program) => program,
+        Err(error) => return Err(failed(name, source, included, vec![Diagnostic::from(&error)])),
+    };
+    let program = if stages.link {
+        match Linker::new(&source, stages.search, stages.lexer, stages.defines, &mut included).link(Path::new(name), program) {
+            Ok(program) => program,
+            Err(diagnostics) => return Err(failed(name, source, included, diagnostics)),
+        }
+    } else {
+        program
+    };
+    let files = included.files;
+    let source = if files.is_empty() { source } else { Cow::Owned(source.into_owned() + &included.text) };
+    let comments = trivia.map(|tokens| attach_comments(&source, &tokens, &program)).unwrap_or_default();
+    let mut diagnostics = Vec::new();
+    if stages.resolve || stages.check_types {
+        // A variable that's never assigned reads as 0 when the program runs, but a call to a
+        // function that doesn't exist can't be run or compiled.
+        let errors = resolve(&program).1.into_iter()
+            .filter(|error| stages.resolve || matches!(error, SemaError::UndeclaredFunction {..}));
+        diagnostics.extend(errors.map(|error| Diagnostic::from(&error)));
+    }
+    if stages.check_types {
+        diagnostics.extend(check_types(&program).iter().map(Diagnostic::from));
+    }
+    if stages.warnings {
+        let warnings = [check_assignments, check_unused, check_reachable, check_shadowing]
+            .iter()
+            .flat_map(|check| check(&program));
+        diagnostics.extend(warnings.map(|warning| Diagnostic::from(&warning)));
+    }
+    let lints = LintScopes::new(&program, stages.lints);
+    let mut diagnostics: Vec<Diagnostic> = diagnostics.into_iter().filter_map(|d| lints.apply(d)).collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span().start);
+    if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+        return Err(Failed { name, source, files, diagnostics });
+    }
+    let syntax = parser.syntax_tree(&source);
+    let program = if stages.optimize { stages.passes.run_ast(program) } else { program };
+    Ok(Unit { name, source, tokens: Vec::new(), program, comments, files, directives, syntax, warnings: diagnostics })
+}
+
+// `build dir/`: compiles each program in the directory to a `.tbc` file in the directory `-o`
+// names, or in `build` in it, unless the cache there has it as up to date.
+fn build_project(options: &Options, stages: Stages<'_>, color: bool) -> io::Result<()> {
+    let root = Path::new(&options.files[0]);
+    let out = options.output.as_ref().map_or_else(|| root.join("build"), PathBuf::from);
+    let project = Project::discover(root, &options.search, &out)?;
+    let mut cache = Cache::load(&out.join(CACHE));
+    // What a program compiles to depends on these too.
+    let salt = format!("{:?} {:?}", options.level.unwrap_or_default(), options.defines);
+    let mut stale = Vec::new();
+    for program in project.programs() {
+        let key = project.key(program, &salt);
+        let relative = project.relative(program);
+        let output = out.join(relative).with_extension("tbc");
+        if cache.is_fresh(relative, key) && output.is_file() {
+            eprintln!("{} is up to date", relative.display());
+        } else {
+            stale.push((program.path.display().to_string(), relative, output, key));
+        }
+    }
+    let names: Vec<String> = stale.iter().map(|(name, ..)| name.clone()).collect();
+    let files = names.iter().map(|name| read_source(name).map_err(in_file(name))).collect::<io::Result<Vec<_>>>()?;
+    let mut failed = false;
+    for (loaded, (_, relative, output, key)) in load_all(&names, &files, stages).into_iter().zip(&stale) {
+        match loaded {
+            Ok(unit) => {
+                let renderer = Renderer::new(unit.name, &unit.source, color).with_files(&unit.files);
+                for warning in &unit.warnings {
+                    eprintln!("{}", renderer.render(warning));
+                }
+                let bytecode = match compile_optimized(&unit.program, stages.passes) {
+                    Ok((bytecode, _)) => bytecode,
+                    Err(errors) => {
+                        errors.iter().for_each(|error| eprintln!("{}", renderer.render(error)));
+                        failed = true;
+                        continue;
+                    }
+                };
+                fs::create_dir_all(output.parent().unwrap()).map_err(in_file(output.parent().unwrap()))?;
+                fs::write(output, bytecode.to_tbc()).map_err(in_file(output))?;
+                cache.insert(relative, *key);
+                eprintln!("compiled {} to {}", relative.display(), output.display());
+            }
+            Err(Failed { name, source, files, diagnostics }) => {
+                let renderer = Renderer::new(name, &source, color).with_files(&files);
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", renderer.render(diagnostic));
+                }
+                failed = true;
+            }
+        }
+    }
+    fs::create_dir_all(&out).map_err(in_file(&out))?;
+    cache.save(&out.join(CACHE)).map_err(in_file(out.join(CACHE)))?;
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// Loads the files on as many threads as there are cores, keeping them in order.
+fn load_all<'a>(names: &'a [String], files: &'a [SourceBytes], stages: Stages<'_>) -> Vec<Result<Unit<'a>, Failed<'a>>> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(files.len());
+    let mut loaded: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|worker| scope.spawn(move || {
+            (worker..files.len()).step_by(workers)
+                .map(|i| (i, load(&names[i], &files[i], stages)))
+                .collect::<Vec<_>>()
+        })).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    });
+    loaded.sort_by_key(|&(i, _)| i);
+    loaded.into_iter().map(|(_, unit)| unit).collect()
+}
+
+// The program's bytecode after peephole optimization, and how many instructions that removed.
+fn compile_optimized(program: &[Statement], passes: &PassManager) -> Result<(Bytecode, usize), Vec<Diagnostic>> {
+    let mut bytecode = compile(program)?;
+    let before = bytecode.code.len();
+    passes.run_bytecode(&mut bytecode);
+    let removed = before - bytecode.code.len();
+    Ok((bytecode, removed))
+}
+
+// Lowers the program's SSA form to LLVM IR, which is returned unless `output` names an
+// object file or an executable to build from it instead.
+#[cfg(feature = "llvm")]
+fn compile_llvm(program: &[Statement], passes: &PassManager, level: OptLevel, output: Option<&str>) -> Result<Option<String>, String> {
+    let mut module = build_ssa(program);
+    passes.run_ssa(&mut module);
+    let ir = emit_llvm(&module)?;
+    match output.map(Path::new) {
+        Some(path) if path.extension().is_none_or(|extension| extension != "ll") => {
+            let object = path.extension().is_some_and(|extension| extension == "o");
+            build_native(&ir, level, path, object).map(|()| None)
+        }
+        _ => Ok(Some(ir)),
+    }
+}
+
+#[cfg(not(feature = "llvm"))]
+fn compile_llvm(_: &[Statement], _: &PassManager, _: OptLevel, _: Option<&str>) -> Result<Option<String>, String> {
+    Err("tiny-lang was built without LLVM support; rebuild it with `--features llvm`".to_string())
+}
+
+// Runs the program as machine code compiled from its SSA form, or says why it can't be.
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+fn run_jit(program: &[Statement], passes: &PassManager) -> Result<(), String> {
+    let mut module = build_ssa(program);
+    passes.run_ssa(&mut module);
+    Jit::compile(&module)?.run();
+    Ok(())
+}
+
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", target_os = "linux")))]
+fn run_jit(_: &[Statement], _: &PassManager) -> Result<(), String> {
+    eprintln!("error: tiny-lang was built without the JIT; rebuild it with `--features jit` (x86-64 Linux only)");
+    process::exit(2);
+}
+
+// The limits --fuel and --timeout put on the VM, how --overflow has it overflow, whether
+// --gc-stress is on, the --seed `rand` starts from and what the program may use.
+fn run_config(options: &Options) -> RunConfig {
+    let mut config = RunConfig::default().overflow(options.overflow.unwrap_or_default()).gc_stress(options.gc_stress);
+    if let Some(fuel) = options.fuel {
+        config = config.fuel(fuel);
+    }
+    if let Some(timeout) = options.timeout {
+        config = config.timeout(timeout);
+    }
+    if let Some(seed) = options.seed {
+        config = config.seed(seed);
+    }
+    config.capabilities(options.capabilities)
+}
+
+// What `argv` gives the program in the file `name`: its name, then the arguments after `--`.
+fn program_arguments(name: &str, options: &Options) -> Vec<String> {
+    std::iter::once(name.to_string()).chain(options.arguments.iter().cloned()).collect()
+}
+
+// Prints the tokens of stdin as they're lexed, so it needn't all be in memory at once, then
+// its errors, by line and column since the source they're in is gone by then.
+fn lex_stdin(options: &Options, json: bool) -> io::Result<()> {
+    if options.utf8 == Utf8Policy::Lossy {
+        usage("--lossy can't be used when lexing stdin");
+    }
+    let lexer = LexerOptions { preserve_trivia: options.trivia, ..LexerOptions::default() };
+    let mut lexer = ReaderLexer::with_options(io::stdin().lock(), lexer);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    if json {
+        writeln!(out, "[")?;
+    }
+    let mut first = true;
+    while let Some(token) = lexer.next() {
+        let token = token.map_err(in_file("<stdin>"))?;
+        let (line, column) = lexer.line_column();
+        if json {
+            write!(out, "{}  {}", if first { "" } else { ",\n" }, token_to_json(&token, line, column))?;
+        } else {
+            writeln!(out, "{}", token_to_text(&token, line, column))?;
+        }
+        first = false;
+    }
+    if json {
+        writeln!(out, "\n]")?;
+    }
+    out.flush()?;
+    for (error, (line, column)) in lexer.located_errors() {
+        eprintln!("error[{}]: {}\n --> <stdin>:{}:{}", error.code(), error, line, column);
+    }
+    if !lexer.errors.is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// Where --trace writes: the file --trace-file names, a line at a time so that nothing's
+// lost if the program fails, or stderr.
+fn trace_output(options: &Options) -> io::Result<Box<dyn Write>> {
+    Ok(match &options.trace_file {
+        Some(path) => Box::new(LineWriter::new(fs::File::create(path).map_err(in_file(path))?)),
+        None => Box::new(io::stderr()),
+    })
+}
+
+// Runs `bytecode` in the VM as the options say, tracing it with --trace, with lines from
+// `source` if it's known, and printing its heap's statistics with --stats.
+fn run_vm(name: &str, bytecode: &Bytecode, source: Option<&str>, options: &Options) -> io::Result<Result<(), VmError>> {
+    let mut vm = Vm::new(bytecode);
+    vm.arguments(program_arguments(name, options));
+    if options.trace {
+        vm.trace(vm_tracer(source, trace_output(options)?));
+    }
+    let result = vm.run_with(&run_config(options));
+    if options.stats {
+        eprintln!("heap: {}", vm.heap_stats());
+    }
+    Ok(result)
+}
+
+// What ends a standalone executable: the program as a `.tbc` file, its length as a u64 and
+// then these bytes.
+const STANDALONE: &[u8; 8] = b"TLSTAND\0";
+
+// Packages the program with a copy of this executable, which runs it when started.
+fn write_standalone(bytecode: &Bytecode, path: &str) -> io::Result<()> {
+    let mut executable = fs::read(env::current_exe()?)?;
+    let program = bytecode.to_tbc();
+    executable.extend_from_slice(&program);
+    executable.extend_from_slice(&(program.len() as u64).to_le_bytes());
+    executable.extend_from_slice(STANDALONE);
+    fs::write(path, executable).map_err(in_file(path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(in_file(path))?;
+    }
+    Ok(())
+}
+
+// The program packaged with this executable by `build --standalone`, if it's one of those.
+fn standalone_program() -> io::Result<Option<Vec<u8>>> {
+    let mut file = fs::File::open(env::current_exe()?)?;
+    let Ok(end) = file.seek(SeekFrom::End(-16)) else { return Ok(None) };
+    let mut trailer = [0; 16];
+    file.read_exact(&mut trailer)?;
+    let len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if trailer[8..] != *STANDALONE || len > end {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(end - len))?;
+    let mut program = vec![0; len as usize];
+    file.read_exact(&mut program)?;
+    Ok(Some(program))
+}
+
+// Enough stack for calls nested `MAX_CALL_DEPTH` deep even in a debug build, whose frames
+// are much bigger.
+const STACK_SIZE: usize = 64 << 20;
+
+fn main() {
+    let result = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).and_then(|thread| thread.join().unwrap_or_else(|_| process::exit(101)));
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    // A standalone executable only ever runs its own program.
+    if let Some(program) = standalone_program()? {
+        match Bytecode::from_tbc(&program) {
+            Ok(bytecode) => {
+                let mut vm = Vm::new(&bytecode);
+                vm.arguments(env::args().collect());
+                if let Err(error) = vm.run() {
+                    eprintln!("error: {}", error);
+                    process::exit(1);
+                }
+            }
+            Err(message) => {
+                eprintln!("error: the program packaged with this executable is damaged: {}", message);
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
     let args: Vec<String> = env::args().collect();
-    let input = fs::read_to_string(args[1].as_str())?;
-    let source = input.clone();
-    let tokens = tokenize(source);
-       
-    println!("Program:\n{}", input);
-    println!("-------------------------");
-    // println!("Tokens:");
-    // for token in tokens {
-    //     println!("{:?}", token);
-    // }
+    if args.len() == 2 && args[1] == "repl" {
+        return repl::run();
+    }
+    if args.get(1).is_some_and(|command| command == "dap") {
+        let [flag, port] = &args[2..] else { usage("dap expects --port <n>") };
+        let Some(port) = port.parse().ok().filter(|_| flag == "--port") else { usage("dap expects --port <n>") };
+        return dap::listen(port);
+    }
+    if args.get(1).is_some_and(|command| command == "grammar") {
+        match &args[2..] {
+            [] => {}
+            [flag, format] if flag == "--format" && format == "ebnf" => {}
+            _ => usage("grammar only takes --format ebnf"),
+        }
+        print!("{}", grammar_to_ebnf());
+        return Ok(());
+    }
+    if args.get(1).is_some_and(|command| command == "highlight") {
+        let mut format = "ansi";
+        let mut theme = &THEMES[0];
+        let mut file = None;
+        let mut args = args[2..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => format = args.next().map(String::as_str).filter(|f| matches!(*f, "ansi" | "html")).unwrap_or_else(|| usage("--format expects ansi or html")),
+                "--theme" => theme = args.next().and_then(|name| Theme::named(name)).unwrap_or_else(|| usage("--theme expects light, dark or mono")),
+                _ if arg.starts_with("--") => usage(&format!("unknown option `{}`", arg)),
+                _ if file.is_some() => usage("highlight takes one file"),
+                _ => file = Some(arg),
+            }
+        }
+        // A file that doesn't lex or parse still highlights, as far as it can.
+        let file = file.unwrap_or_else(|| usage("highlight expects a file"));
+        let source = String::from_utf8_lossy(&fs::read(file).map_err(in_file(file))?).into_owned();
+        match format {
+            "html" => print!("{}", to_html(&source, theme)),
+            _ => print!("{}", to_ansi(&source, theme)),
+        }
+        return Ok(());
+    }
+    if args.get(1).is_some_and(|command| command == "explain") {
+        let [code] = &args[2..] else { usage("explain expects a code") };
+        match explain(code) {
+            Some(explanation) => print!("{}", format_explanation(explanation)),
+            None => {
+                eprintln!("error: no diagnostic has the code `{}`", code);
+                process::exit(2);
+            }
+        }
+        return Ok(());
+    }
 
-    let mut parser = Parser::new(tokens);
-    let stmt = parse_statement(&mut parser);
+    let options = parse_args(&args);
+    let emit = match (options.command.as_str(), options.emit, options.target) {
+        (_, Some(_), Some(_)) => usage("--emit and --target can't both be given"),
+        ("compile", None, Some(target)) => Some(target),
+        (_, _, Some(_)) => usage("--target is only for compile"),
+        (_, Some(emit), None) => Some(emit),
+        ("lex", None, None) => Some(Emit::Tokens),
+        ("parse", None, None) => Some(Emit::Ast),
+        ("compile", None, None) => Some(Emit::Bytecode),
+        ("build", None, None) => Some(Emit::Bytecode),
+        ("run" | "debug" | "fmt" | "check" | "doc", None, None) => None,
+        ("analyze", None, None) if options.cfg => None,
+        ("analyze", None, None) => usage("analyze expects --cfg"),
+        _ => usage(&format!("unknown command `{}`", options.command)),
+    };
+    if options.check && (options.command != "fmt" || emit.is_some()) {
+        usage("--check is only for fmt");
+    }
+    if options.vm && (options.command != "run" || emit.is_some()) {
+        usage("--vm is only for run");
+    }
+    if options.jit && (options.command != "run" || emit.is_some() || options.vm) {
+        usage("--jit is only for run, without --vm");
+    }
+    if options.standalone && options.command != "build" {
+        usage("--standalone is only for build");
+    }
+    // Without --standalone, build compiles the programs in a directory.
+    let project = options.command == "build" && !options.standalone;
+    if project && (options.files.len() > 1 || !Path::new(&options.files[0]).is_dir()) {
+        usage("build expects one directory, or --standalone");
+    }
+    // The JIT's machine code can't be stopped part way.
+    if options.fuel.is_some() && (options.command != "run" || emit.is_some() || options.jit) {
+        usage("--fuel is only for run, without --jit");
+    }
+    // Machine code can't be traced.
+    if options.trace && (options.command != "run" || emit.is_some() || options.jit) {
+        usage("--trace is only for run, without --jit");
+    }
+    // Compiled machine code wraps, as the hardware does.
+    if options.overflow.is_some() && (options.command != "run" || emit.is_some() || options.jit) {
+        usage("--overflow is only for run, without --jit");
+    }
+    let runs = options.seed.is_some() || options.capabilities != Capabilities::default() || !options.arguments.is_empty();
+    if runs && (options.command != "run" || emit.is_some()) {
+        usage("--seed, --allow-files, --allow-env and -- are only for run");
+    }
+    // Only the VM looks at the clock, or has a heap.
+    let bytecode_file = options.files.iter().any(|file| file.ends_with(".tbc"));
+    if (options.timeout.is_some() || options.gc_stress || options.stats) && (options.command != "run" || emit.is_some() || !(options.vm || bytecode_file)) {
+        usage("--timeout, --gc-stress and --stats are only for run --vm or a .tbc file");
+    }
+    // Coverage and profiles are of the statements the interpreter runs.
+    if (options.coverage || options.lcov.is_some() || options.profile) && (options.command != "run" || emit.is_some() || options.vm || options.jit || bytecode_file) {
+        usage("--coverage, --lcov and --profile are only for run, without --vm or --jit");
+    }
+    // The debugger takes its commands from stdin, for one program at a time.
+    if options.command == "debug" && (options.files.len() > 1 || bytecode_file) {
+        usage("debug expects one source file");
+    }
+    if options.cfg && options.command != "analyze" {
+        usage("--cfg is only for analyze");
+    }
+    if options.trivia && emit != Some(Emit::Tokens) {
+        usage("--trivia is only for lex");
+    }
+    // Only one file's output can go to a file.
+    let compiled = matches!(emit, Some(Emit::Ssa | Emit::Bytecode | Emit::C | Emit::Llvm | Emit::Wasm | Emit::Asm | Emit::Rust | Emit::Js));
+    if options.output.is_some() && (!matches!(options.command.as_str(), "compile" | "build") || !compiled || options.files.len() > 1) {
+        usage("-o is only for compile or build, with one file");
+    }
+    if options.syntax.is_some() && emit != Some(Emit::Asm) {
+        usage("--syntax is only for --target x86_64-asm");
+    }
+    if emit == Some(Emit::Wasm) && options.output.is_none() {
+        eprintln!("error: --target wasm32 writes a binary module; name the file with -o");
+        process::exit(2);
+    }
+    // Compiled bytecode is run as it is: there's no source to check or optimize.
+    if options.command == "run" && bytecode_file {
+        if options.files.len() > 1 || options.jit || options.level.is_some() || options.print_passes
+            || options.stop_after.is_some()
+        {
+            usage("a .tbc file is run as it is, by itself");
+        }
+        let path = &options.files[0];
+        match Bytecode::from_tbc(&fs::read(path).map_err(in_file(path))?) {
+            // Nothing in the file says where in the source it stopped.
+            Ok(bytecode) => {
+                if let Err(error) = run_vm(path, &bytecode, None, &options)? {
+                    eprintln!("error: {}: {}", path, error);
+                    process::exit(1);
+                }
+            }
+            Err(message) => {
+                eprintln!("error: {}: {}", path, message);
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    let format = options.format.as_deref();
+    match (emit, format) {
+        (_, None) | (Some(Emit::Tokens), Some("text" | "json")) | (Some(Emit::Ast), Some("text" | "dot" | "cst")) => {}
+        (None, Some("text" | "dot")) if options.cfg => {}
+        (None, Some("markdown" | "html")) if options.command == "doc" => {}
+        (_, Some(format)) => usage(&format!("--format {} isn't for {}", format, options.command)),
+    }
+    if options.command == "lex" && emit == Some(Emit::Tokens) && options.files == ["-"] {
+        return lex_stdin(&options, format == Some("json"));
+    }
 
-    println!("{:#?}", stmt);
+    let files = if project { Vec::new() } else { options.files.iter().map(|file| read_source(file).map_err(in_file(file))).collect::<io::Result<Vec<_>>>()? };
+    let color = stderr_supports_color();
+    let mut units = Vec::new();
+    let mut failed = false;
+    // The formatter keeps comments, and doc reads them, which the lexer only does with the
+    // rest of the trivia.
+    let comments = matches!(options.command.as_str(), "fmt" | "doc");
+    let lexer = LexerOptions { preserve_trivia: options.trivia || comments, ..LexerOptions::default() };
+    let check = options.command == "check";
+    let analyze = check || matches!(emit, None | Some(Emit::Ssa | Emit::Bytecode | Emit::C | Emit::Llvm | Emit::Wasm | Emit::Asm | Emit::Rust | Emit::Js)) && !comments;
+    // Optimizing would move and remove the statements the debugger pauses at, and those
+    // coverage counts, and inline the functions a profile puts time down to.
+    let covered = options.coverage || options.lcov.is_some();
+    let optimize = analyze && !check && options.command != "debug" && !covered && !options.profile;
+    if !optimize && (options.level.is_some() || options.print_passes || options.stop_after.is_some()) {
+        usage(&format!("{} doesn't optimize the program", options.command));
+    }
+    let mut passes = PassManager::with_level(options.level.unwrap_or_default());
+    passes.print_passes(options.print_passes);
+    if let Some(name) = &options.stop_after
+        && let Err(message) = passes.stop_after(name)
+    {
+        eprintln!("error: {}", message);
+        process::exit(2);
+    }
+    let stages = Stages {
+        lexer,
+        utf8: options.utf8,
+        parse: emit != Some(Emit::Tokens),
+        syntax_tree: format == Some("cst"),
+        defines: &options.defines,
+        // The formatter and doc are of the file alone.
+        link: !comments,
+        search: &options.search,
+        resolve: check,
+        // Programs are checked before they're run or compiled.
+        check_types: analyze,
+        warnings: analyze,
+        lints: &options.lints,
+        optimize,
+        passes: &passes,
+    };
+    if project {
+        return build_project(&options, stages, color);
+    }
+    for loaded in load_all(&options.files, &files, stages) {
+        match loaded {
+            Ok(unit) => {
+                let renderer = Renderer::new(unit.name, &unit.source, color).with_files(&unit.files);
+                for warning in &unit.warnings {
+                    eprintln!("{}", renderer.render(warning));
+                }
+                units.push(unit);
+            }
+            Err(Failed { name, source, files, diagnostics }) => {
+                let renderer = Renderer::new(name, &source, color).with_files(&files);
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", renderer.render(diagnostic));
+                }
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
 
+    // Each file's coverage is a record in the one tracefile.
+    let mut lcov = options.lcov.as_ref().map(|path| fs::File::create(path).map_err(in_file(path))).transpose()?;
+    for (i, unit) in units.iter().enumerate() {
+        let renderer = Renderer::new(unit.name, &unit.source, color).with_files(&unit.files);
+        let report = |diagnostic: Diagnostic| -> ! {
+            eprintln!("{}", renderer.render(&diagnostic));
+            process::exit(1);
+        };
+        let compiled = |program| compile_optimized(program, &passes).unwrap_or_else(|errors| {
+            errors.iter().for_each(|error| eprintln!("{}", renderer.render(error)));
+            process::exit(1);
+        });
+        // Each file's output is headed by its name when there are several, as `head` does.
+        if units.len() > 1 && !matches!(options.command.as_str(), "run" | "check") && !options.check {
+            println!("{}==> {} <==", if i > 0 { "\n" } else { "" }, unit.name);
+        }
+        let program = &unit.program;
+        let write = |text: String| match &options.output {
+            Some(path) => fs::write(path, text).map_err(in_file(path)),
+            None => {
+                print!("{}", text);
+                Ok(())
+            }
+        };
+        match emit {
+            Some(Emit::Tokens) if format == Some("json") => print!("{}", tokens_to_json(&unit.tokens, &unit.source)),
+            Some(Emit::Tokens) => print!("{}", tokens_to_text(&unit.tokens, &unit.source)),
+            Some(Emit::Ast) if format == Some("dot") => print!("{}", program_to_dot(program)),
+            Some(Emit::Ast) if let Some(syntax) = &unit.syntax => print!("{}", print_syntax_tree(syntax)),
+            Some(Emit::Ast) => print!("{}", print_program(program)),
+            Some(Emit::Ssa) => {
+                let mut module = build_ssa(program);
+                passes.run_ssa(&mut module);
+                write(module.to_string())?;
+            }
+            Some(Emit::Bytecode) => {
+                let (bytecode, removed) = compiled(program);
+                if options.standalone {
+                    let stem = Path::new(unit.name).file_stem().unwrap_or_default().to_string_lossy();
+                    write_standalone(&bytecode, options.output.as_deref().unwrap_or(&stem))?;
+                    continue;
+                }
+                if let Some(path) = options.output.as_ref().filter(|path| path.ends_with(".tbc")) {
+                    fs::write(path, bytecode.to_tbc()).map_err(in_file(path))?;
+                    continue;
+                }
+                let summary = format!("; peephole: {} -> {} instructions", bytecode.code.len() + removed, bytecode.code.len());
+                write(format!("{}{}\n", bytecode, summary))?;
+            }
+            Some(Emit::C) => match emit_c(program) {
+                Ok(c) => write(c)?,
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    process::exit(1);
+                }
+            },
+            Some(Emit::Rust) => match emit_rust(program) {
+                Ok(rust) => write(rust)?,
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    process::exit(1);
+                }
+            },
+            Some(Emit::Js) => match emit_js(program) {
+                Ok(js) => write(js)?,
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    process::exit(1);
+                }
+            },
+            Some(Emit::Llvm) => match compile_llvm(program, &passes, options.level.unwrap_or_default(), options.output.as_deref()) {
+                Ok(Some(ir)) => write(ir)?,
+                Ok(None) => {}
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    process::exit(1);
+                }
+            },
+            Some(Emit::Asm) => {
+                let mut module = build_ssa(program);
+                passes.run_ssa(&mut module);
+                match emit_asm(&module, options.syntax.unwrap_or_default()) {
+                    Ok(assembly) => write(assembly)?,
+                    Err(message) => {
+                        eprintln!("error: {}", message);
+                        process::exit(1);
+                    }
+                }
+            }
+            Some(Emit::Wasm) => {
+                let mut module = build_ssa(program);
+                passes.run_ssa(&mut module);
+                match emit_wasm(&module) {
+                    Ok(bytes) => fs::write(options.output.as_ref().unwrap(), bytes).map_err(in_file(options.output.as_ref().unwrap()))?,
+                    Err(message) => {
+                        eprintln!("error: {}", message);
+                        process::exit(1);
+                    }
+                }
+            }
+            // Formatting what the directives leave would lose them and what they left out.
+            None if options.command == "fmt" && unit.directives => {
+                eprintln!("error: {} has `#if` or `#include` directives, which fmt can't keep", unit.name);
+                failed = true;
+            }
+            None if options.command == "fmt" => {
+                let config = FormatConfig::for_file(Path::new(unit.name)).unwrap_or_else(|message| {
+                    eprintln!("error: {}", message);
+                    process::exit(2);
+                });
+                let formatted = format_program_with(program, &unit.comments, &config);
+                if !options.check {
+                    print!("{}", formatted);
+                } else if formatted != unit.source {
+                    eprintln!("{} is not formatted", unit.name);
+                    failed = true;
+                }
+            }
+            None if options.command == "doc" => {
+                let functions = doc::functions(&unit.source, program, &unit.comments);
+                match format {
+                    Some("html") => print!("{}", doc::to_html(unit.name, &functions)),
+                    _ => print!("{}", doc::to_markdown(unit.name, &functions)),
+                }
+            }
+            // Any errors were reported as the file was loaded.
+            None if check => {}
+            None if options.cfg => {
+                let bytecode = compiled(program).0;
+                let cfgs = build_cfgs(&bytecode);
+                match format {
+                    Some("dot") => print!("{}", cfgs_to_dot(&bytecode, &cfgs)),
+                    _ => print!("{}", cfgs_to_text(&bytecode, &cfgs)),
+                }
+            }
+            None if options.vm => {
+                let bytecode = compiled(program).0;
+                if let Err(error) = run_vm(unit.name, &bytecode, Some(&unit.source), &options)? {
+                    match error.span() {
+                        Some(span) => {
+                            let diagnostic = Diagnostic::error(error.to_string(), span);
+                            report(match error.code() {
+                                Some(code) => diagnostic.with_code(code),
+                                None => diagnostic,
+                            })
+                        }
+                        None => {
+                            eprintln!("error: {}", error);
+                            process::exit(1);
+                        }
+                    }
+                }
+            }
+            None if options.command == "debug" => match debugger::run(&unit.source, program) {
+                Ok(()) | Err(RuntimeError::Stopped { .. }) => {}
+                Err(error) => report(Diagnostic::from(&error)),
+            },
+            None if options.jit && let Err(reason) = run_jit(program, &passes) => {
+                eprintln!("note: interpreting {}: {}", unit.name, reason);
+                let interpreter = Interpreter::new().with_capabilities(options.capabilities)
+                    .with_arguments(program_arguments(unit.name, &options));
+                let mut interpreter = match options.seed {
+                    Some(seed) => interpreter.with_seed(seed),
+                    None => interpreter,
+                };
+                interpreter.run(program).unwrap_or_else(|error| report(Diagnostic::from(&error)));
+            }
+            None if options.jit => {}
+            None => {
+                let tracer = if options.trace { Some(Tracer::new(&unit.source, trace_output(&options)?)) } else { None };
+                let mut coverage = covered.then(|| Coverage::new(program));
+                let mut profiler = options.profile.then(|| Profiler::new(&unit.source));
+                let mut interpreter = Interpreter::new();
+                if let Some(fuel) = options.fuel {
+                    interpreter = interpreter.with_fuel(fuel);
+                }
+                interpreter = interpreter.with_overflow(options.overflow.unwrap_or_default());
+                if let Some(seed) = options.seed {
+                    interpreter = interpreter.with_seed(seed);
+                }
+                interpreter = interpreter.with_capabilities(options.capabilities).with_arguments(program_arguments(unit.name, &options));
+                if tracer.is_some() || coverage.is_some() || profiler.is_some() {
+                    interpreter = interpreter.with_hook(((tracer, coverage.as_mut()), profiler.as_mut()));
+                }
+                let result = interpreter.run(program);
+                drop(interpreter);
+                // Coverage and profiles are reported whether or not the program failed.
+                if let Some(profiler) = &mut profiler {
+                    eprint!("{}", profiler.report());
+                }
+                if let Some(coverage) = &coverage {
+                    if options.coverage {
+                        eprint!("{}", coverage.report(unit.name, &unit.source));
+                    }
+                    if let Some(file) = &mut lcov {
+                        file.write_all(coverage.lcov(unit.name, &unit.source).as_bytes()).map_err(in_file(options.lcov.as_ref().unwrap()))?;
+                    }
+                }
+                result.unwrap_or_else(|error| report(Diagnostic::from(&error)));
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
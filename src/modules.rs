@@ -0,0 +1,266 @@
+// `import name;`: the module in `name.lang`, looked for next to the file importing it and then
+// in each directory `-I` adds. To the importer, the module's functions and globals are
+// `name.f` and `name.x`. Linking puts each module's statements, once, before those of the
+// first file to import it, so a module's top level runs before its importer's.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::intern::Symbol;
+use crate::lexer::LexerOptions;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::parser::parser::{Parser, parse_program};
+use crate::preprocess::{Included, include_module};
+
+pub const EXTENSION: &str = "lang";
+
+pub struct Linker<'a> {
+    // The main file's text; the modules' goes after it, with the included files', in `included`.
+    source: &'a str,
+    search: &'a [String],
+    lexer: LexerOptions,
+    defined: &'a [String],
+    included: &'a mut Included,
+    // The modules linked so far, which later imports of them share.
+    linked: Vec<PathBuf>,
+    // Those being imported, outermost first, so one importing itself can be caught.
+    importing: Vec<PathBuf>,
+    program: Vec<Statement>
+}
+
+impl<'a> Linker<'a> {
+    // For the main file whose text is `source`, looking for modules in the directories of
+    // `search` after the importer's own. Modules are lexed with `lexer` and preprocessed with
+    // `defined`, and their text added to `included`.
+    pub fn new(source: &'a str, search: &'a [String], lexer: LexerOptions, defined: &'a [String], included: &'a mut Included) -> Self {
+        Linker { source, search, lexer, defined, included, linked: Vec::new(), importing: Vec::new(), program: Vec::new() }
+    }
+
+    // `program`, from the file at `path`, after the modules it imports and theirs, with its
+    // `import`s left out.
+    pub fn link(mut self, path: &Path, program: Vec<Statement>) -> Result<Vec<Statement>, Vec<Diagnostic>> {
+        let mut program = program;
+        self.importing.extend(path.canonicalize().ok());
+        let imports = self.imports(path, &program)?;
+        check_qualifiers(&mut program, &imports)?;
+        self.program.extend(program.into_iter().filter(|statement| !matches!(statement, Statement::Import {..})));
+        Ok(self.program)
+    }
+
+    // Links the modules `program`, from the file at `path`, imports, returning their names.
+    fn imports(&mut self, path: &Path, program: &[Statement]) -> Result<Vec<Symbol>, Vec<Diagnostic>> {
+        let mut imported: Vec<(Symbol, Span)> = Vec::new();
+        for statement in program {
+            let Statement::Import {module, span} = statement else { continue };
+            if let Some(&(_, first)) = imported.iter().find(|(name, _)| name == module) {
+                return Err(vec![Diagnostic::error(format!("`{}` is imported twice", module), *span)
+                    .with_code("E0109")
+                    .with_note("it's first imported here", first)]);
+            }
+            imported.push((*module, *span));
+            self.module(path, *module, *span)?;
+        }
+        Ok(imported.into_iter().map(|(name, _)| name).collect())
+    }
+
+    // Links the module `name`, imported by the `import` at `at` in the file at `path`, unless
+    // it's been linked already.
+    fn module(&mut self, path: &Path, name: Symbol, at: Span) -> Result<(), Vec<Diagnostic>> {
+        let error = |message: String| vec![Diagnostic::error(message, at).with_code("E0108")];
+        let target = &find_module(path, name, self.search).map_err(|candidates| {
+            let looked: Vec<String> = candidates.iter().map(|candidate| format!("`{}`", candidate.display())).collect();
+            error(format!("can't find module `{}`; looked for {}", name, looked.join(", ")))
+        })?;
+        let canonical = target.canonicalize().map_err(|e| error(format!("can't import `{}`: {}", target.display(), e)))?;
+        if self.importing.contains(&canonical) {
+            return Err(vec![Diagnostic::error(format!("`{}` imports itself", target.display()), at).with_code("E0110")]);
+        }
+        if self.linked.contains(&canonical) {
+            return Ok(());
+        }
+        let text = fs::read_to_string(&canonical).map_err(|e| error(format!("can't import `{}`: {}", target.display(), e)))?;
+        let tokens = include_module(target, &text, at, self.source, self.lexer, self.defined, self.included)?;
+        let mut program = parse_program(&mut Parser::new(tokens)).map_err(|error| vec![Diagnostic::from(&error)])?;
+
+        self.importing.push(canonical.clone());
+        let imports = self.imports(target, &program);
+        self.importing.pop();
+        check_qualifiers(&mut program, &imports?)?;
+        qualify(name, &mut program);
+        self.program.extend(program.into_iter().filter(|statement| !matches!(statement, Statement::Import {..})));
+        self.linked.push(canonical);
+        Ok(())
+    }
+}
+
+// The file of the module `name` that the file at `path` imports: the first of the places to
+// look for it that has one, or else all of them.
+pub fn find_module(path: &Path, name: Symbol, search: &[String]) -> Result<PathBuf, Vec<PathBuf>> {
+    let file = format!("{}.{}", name, EXTENSION);
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let candidates: Vec<PathBuf> = std::iter::once(directory.to_path_buf())
+        .chain(search.iter().map(PathBuf::from))
+        .map(|directory| directory.join(&file))
+        .collect();
+    match candidates.iter().position(|candidate| candidate.is_file()) {
+        Some(i) => Ok(candidates.into_iter().nth(i).unwrap()),
+        None => Err(candidates),
+    }
+}
+
+// `module.name`.
+fn qualified(module: Symbol, name: Symbol) -> Symbol {
+    Symbol::intern(&format!("{}.{}", module, name))
+}
+
+// Qualifies the functions and globals `program`, the module `module`'s, declares, and every
+// use of them in it.
+fn qualify(module: Symbol, program: &mut [Statement]) {
+    let functions: Vec<Symbol> = program.iter()
+        .filter_map(|statement| match statement {
+            Statement::Function {name, ..} => Some(*name),
+            _ => None,
+        })
+        .collect();
+    let globals = function_locals(&[], program);
+    for statement in program {
+        // A function's own variables shadow the globals.
+        let locals = match statement {
+            Statement::Function {parameters, body, ..} => function_locals(parameters, body),
+            _ => Vec::new(),
+        };
+        each_name(statement, &mut |name, _, function| {
+            let declared = if function { functions.contains(name) } else { globals.contains(name) && !locals.contains(name) };
+            if declared {
+                *name = qualified(module, *name);
+            }
+        });
+    }
+}
+
+// Checks that each qualified name in `program` is of one of the modules in `imports`.
+fn check_qualifiers(program: &mut [Statement], imports: &[Symbol]) -> Result<(), Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    for statement in program {
+        each_name(statement, &mut |name, span, _| {
+            if let Some((module, _)) = name.as_str().split_once('.')
+                && !imports.iter().any(|import| import.as_str() == module)
+            {
+                let message = format!("`{}` is from module `{}`, which isn't imported", name, module);
+                errors.push(Diagnostic::error(message, span).with_code("E0111"));
+            }
+        });
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+// Calls `visit` with each name in `statement`, including those of the functions it declares
+// and their parameters, and whether it names a function.
+fn each_name(statement: &mut Statement, visit: &mut impl FnMut(&mut Symbol, Span, bool)) {
+    match statement {
+        Statement::Block {body, ..} => body.iter_mut().for_each(|s| each_name(s, visit)),
+        Statement::Attributed {statement, ..} => each_name(statement, visit),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => each_name_in(expression, visit),
+        Statement::Print {arguments, ..} => arguments.iter_mut().for_each(|a| each_name_in(a, visit)),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            each_name_in(condition, visit);
+            each_name(then_branch, visit);
+            if let Some(else_branch) = else_branch {
+                each_name(else_branch, visit);
+            }
+        }
+        Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+            each_name_in(condition, visit);
+            each_name(body, visit);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                each_name_in(expr, visit);
+            }
+            each_name(body, visit);
+        }
+        Statement::Function {name, parameters, body, span, ..} => {
+            visit(name, *span, true);
+            parameters.iter_mut().for_each(|parameter| visit(parameter, *span, false));
+            body.iter_mut().for_each(|s| each_name(s, visit));
+        }
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                each_name_in(value, visit);
+            }
+        }
+        Statement::Import {..} => {}
+    }
+}
+
+fn each_name_in(expr: &mut Expression, visit: &mut impl FnMut(&mut Symbol, Span, bool)) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+        Expression::Symbol(name, span) => visit(name, *span, false),
+        Expression::Unary {operand, ..} => each_name_in(operand, visit),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => {
+            each_name_in(left, visit);
+            each_name_in(right, visit);
+        }
+        Expression::Call {callee, arguments, span} => {
+            visit(callee, *span, true);
+            arguments.iter_mut().for_each(|a| each_name_in(a, visit));
+        }
+        Expression::Array {elements, ..} => elements.iter_mut().for_each(|e| each_name_in(e, visit)),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            each_name_in(condition, visit);
+            each_name_in(then_value, visit);
+            each_name_in(else_value, visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Renderer;
+    use crate::lexer::tokenize;
+    use crate::parser::printer::print_program;
+
+    #[test]
+    fn imports_modules_into_their_own_namespaces() {
+        let directory = std::env::temp_dir().join(format!("tiny-lang-modules-{}", std::process::id()));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(directory.join("lib/math.lang"), "import util;\nscale = 2;\nfn twice(x) { scale = 3; return util.id(x) * scale; }\nprint(twice(scale));\n").unwrap();
+        fs::write(directory.join("lib/util.lang"), "fn id(x) { return x; }\n").unwrap();
+        fs::write(directory.join("loop.lang"), "import loop;\n").unwrap();
+        let main = directory.join("main.lang");
+        let search = [directory.join("lib").display().to_string()];
+        let link = |source: &str, included: &mut Included| {
+            let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+            Linker::new(source, &search, LexerOptions::default(), &[], included).link(&main, program)
+        };
+
+        let mut included = Included::default();
+        let program = link("import math;\nimport util;\nprint(math.twice(math.scale), util.id(1));\n", &mut included).unwrap();
+        let printed = print_program(&program);
+        // `util` is linked once, before the first module that imports it.
+        assert_eq!(printed.matches("(fn util.id").count(), 1, "{}", printed);
+        assert!(printed.find("util.id").unwrap() < printed.find("math.twice").unwrap(), "{}", printed);
+        assert!(printed.contains("(= math.scale 2)"), "{}", printed);
+        // A function's own `scale` isn't the global.
+        assert!(printed.contains("(= scale 3)"), "{}", printed);
+
+        let errors = |source: &str| {
+            let mut included = Included::default();
+            let errors = link(source, &mut included).unwrap_err();
+            let text = format!("{}{}", source, included.text);
+            let rendered = Renderer::new("main.lang", &text, false).with_files(&included.files).render(&errors[0]);
+            (errors[0].code.unwrap(), rendered)
+        };
+        assert_eq!(errors("import nope;\n").0, "E0108");
+        assert_eq!(errors("import util;\nimport util;\n").0, "E0109");
+        let (code, rendered) = errors("import loop;\n");
+        assert_eq!(code, "E0110");
+        assert!(rendered.contains("loop.lang` is imported here"), "{}", rendered);
+        assert_eq!(errors("print(math.scale);\n").0, "E0111");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use crate::lexer::TokenKind;
+use crate::parser::ast::{AssignmentForm, Expression, Statement, function_locals};
+
+// Whether evaluating `expr` can't do anything observable: assign, call, print or fail.
+fn is_pure(expr: &Expression) -> bool {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => true,
+        // Anything is true or false, but other operators can fail on the wrong types.
+        Expression::Unary {operator: TokenKind::OpNot, operand, ..} => is_pure(operand),
+        Expression::Binary {left, operator: TokenKind::OpAnd | TokenKind::OpOr, right, ..} => is_pure(left) && is_pure(right),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            is_pure(condition) && is_pure(then_value) && is_pure(else_value)
+        }
+        Expression::Array {elements, ..} => elements.iter().all(is_pure),
+        _ => false,
+    }
+}
+
+// There's no `break`, so a loop whose condition is always true is only left by returning.
+fn always(condition: Option<&Expression>) -> bool {
+    condition.is_none_or(|condition| condition.constant_truth() == Some(true))
+}
+
+// Whether control can get past `stmt`.
+fn completes(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Block {body, ..} => body.iter().all(completes),
+        Statement::Attributed {statement, ..} => completes(statement),
+        Statement::If {then_branch, else_branch, ..} => {
+            completes(then_branch) || else_branch.as_ref().is_none_or(|else_branch| completes(else_branch))
+        }
+        Statement::While {condition, ..} => !always(Some(condition)),
+        Statement::DoWhile {body, condition, ..} => completes(body) && !always(Some(condition)),
+        Statement::For {condition, ..} => !always(condition.as_ref()),
+        Statement::Return {..} => false,
+        _ => true,
+    }
+}
+
+fn empty(stmt: &Statement) -> bool {
+    matches!(stmt, Statement::Block {body, ..} if body.is_empty())
+}
+
+// `stmt` with its dead parts removed, or an empty block if none of it is left.
+fn eliminate(stmt: Statement) -> Statement {
+    let span = stmt.span();
+    let nothing = || Statement::Block { body: Vec::new(), span };
+    match stmt {
+        Statement::Block {body, span} => Statement::Block { body: eliminate_in_body(body), span },
+        Statement::Attributed {attributes, statement, span} => match eliminate(*statement) {
+            statement if empty(&statement) => nothing(),
+            statement => Statement::Attributed { attributes, statement: Box::new(statement), span },
+        },
+        Statement::Expression {expression, ..} if is_pure(&expression) => nothing(),
+        Statement::If {condition, then_branch, else_branch, span} => {
+            let then_branch = eliminate(*then_branch);
+            let else_branch = else_branch.map(|else_branch| eliminate(*else_branch)).filter(|else_branch| !empty(else_branch));
+            match condition.constant_truth() {
+                Some(true) => then_branch,
+                Some(false) => else_branch.unwrap_or_else(nothing),
+                None if empty(&then_branch) && else_branch.is_none() && is_pure(&condition) => nothing(),
+                None => Statement::If { condition, then_branch: Box::new(then_branch), else_branch: else_branch.map(Box::new), span },
+            }
+        }
+        Statement::While {condition, ..} if condition.constant_truth() == Some(false) => nothing(),
+        Statement::While {condition, body, span} => Statement::While { condition, body: Box::new(eliminate(*body)), span },
+        Statement::DoWhile {body, condition, ..} if condition.constant_truth() == Some(false) => eliminate(*body),
+        Statement::DoWhile {body, condition, span} => Statement::DoWhile { body: Box::new(eliminate(*body)), condition, span },
+        // The initializer still runs once.
+        Statement::For {initializer, condition: Some(condition), ..} if condition.constant_truth() == Some(false) => {
+            match initializer {
+                Some(expression) => eliminate(Statement::Expression { expression, span }),
+                None => nothing(),
+            }
+        }
+        Statement::For {initializer, condition, step, body, span} => {
+            Statement::For { initializer, condition, step, body: Box::new(eliminate(*body)), span }
+        }
+        Statement::Function {name, parameters, parameter_spans, body, attributes, span} => {
+            let locals = function_locals(&parameters, &body);
+            let mut body = eliminate_in_body(body);
+            // A name is local to a function if it's assigned anywhere in it, even somewhere that
+            // never runs; assigning the 0 locals start with keeps it that way.
+            let remaining = function_locals(&parameters, &body);
+            let removed = locals.into_iter().filter(|name| !remaining.contains(name)).map(|name| Statement::Expression {
+                expression: Expression::Assignment {
+                    assignee: Box::new(Expression::Symbol(name, span)),
+                    value: Box::new(Expression::Number(0, span)),
+                    form: AssignmentForm::Plain,
+                    span,
+                },
+                span,
+            });
+            body.splice(0..0, removed);
+            Statement::Function { name, parameters, parameter_spans, body, attributes, span }
+        }
+        stmt => stmt,
+    }
+}
+
+fn eliminate_in_body(body: Vec<Statement>) -> Vec<Statement> {
+    let mut kept = Vec::new();
+    let mut reachable = true;
+    for stmt in body {
+        // Functions are declared before anything runs.
+        if !reachable && !matches!(stmt, Statement::Function {..}) {
+            continue;
+        }
+        let stmt = eliminate(stmt);
+        reachable &= completes(&stmt);
+        match stmt {
+            // Blocks don't start a scope, so what's left of one can join the enclosing body,
+            // unless that would declare a function in it ahead of time.
+            Statement::Block {body, ..} if !body.iter().any(|s| matches!(s, Statement::Function {..})) => kept.extend(body),
+            stmt => kept.push(stmt),
+        }
+    }
+    kept
+}
+
+// Removes branches whose condition is always false, loops that never run, statements after
+// a `return` or an endless loop, and expression statements with no effect. Run after
+// `fold_constants`, which turns constant conditions into literals.
+pub fn eliminate_dead_code(program: Vec<Statement>) -> Vec<Statement> {
+    eliminate_in_body(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+    use crate::parser::printer::print_program;
+
+    #[test]
+    fn removes_what_can_never_run_or_do_anything() {
+        let source = "if (false) { print(1); } else { print(2); }\nwhile (false) { print(3); }\nx;\nx + 1;\nfn f() { return 1; print(4); }\nwhile (true) { print(5); }\nprint(6);\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let program = eliminate_dead_code(program);
+        assert_eq!(print_program(&program), "\
+(print 2)
+(+ x 1)
+(fn f ()
+  (return 1))
+(while true
+  (block
+    (print 5)))
+");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::diagnostics::Span;
+use crate::interpreter::Value;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement};
+
+// The value of a literal, as the interpreter would evaluate it.
+fn literal(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::Number(n, _) => Some(Value::Integer(*n)),
+        Expression::Char(c, _) => Some(Value::Integer(*c as i64)),
+        Expression::Float(x, _) => Some(Value::Float(*x)),
+        Expression::Boolean(b, _) => Some(Value::Bool(*b)),
+        Expression::String(s, _) => Some(Value::String(s.clone())),
+        _ => None,
+    }
+}
+
+// Infinities and NaN have no literal to write them back as.
+fn to_literal(value: Value, span: Span) -> Option<Expression> {
+    match value {
+        Value::Integer(n) => Some(Expression::Number(n, span)),
+        Value::Float(x) if x.is_finite() => Some(Expression::Float(x, span)),
+        Value::Bool(b) => Some(Expression::Boolean(b, span)),
+        Value::String(s) => Some(Expression::String(s, span)),
+        Value::Float(_) | Value::Array(_) => None,
+    }
+}
+
+// `None` wherever evaluating would be an error, which is left for the program to report
+// when it runs.
+pub(crate) fn evaluate_unary(operator: &TokenKind, operand: &Value) -> Option<Value> {
+    match operator {
+        TokenKind::OpNot => Some(Value::from(!operand.is_truthy())),
+        TokenKind::OpSubtract => match operand {
+            Value::Integer(n) => n.checked_neg().map(Value::Integer),
+            operand => operand.negate(),
+        },
+        TokenKind::OpBitNot => operand.bitwise(&Value::Integer(0), |a, _| !a),
+        TokenKind::OpAdd => operand.checked_arithmetic(&Value::Integer(0), i64::checked_add, |a, b| a + b),
+        _ => None,
+    }
+}
+
+pub(crate) fn evaluate_binary(operator: &TokenKind, l: &Value, r: &Value) -> Option<Value> {
+    match operator {
+        TokenKind::OpAdd => l.concat(r).or_else(|| l.checked_arithmetic(r, i64::checked_add, |a, b| a + b)),
+        TokenKind::OpSubtract => l.checked_arithmetic(r, i64::checked_sub, |a, b| a - b),
+        TokenKind::OpMultiply => l.checked_arithmetic(r, i64::checked_mul, |a, b| a * b),
+        TokenKind::OpDivide => l.checked_arithmetic(r, i64::checked_div, |a, b| a / b),
+        TokenKind::OpMod => l.checked_arithmetic(r, i64::checked_rem, |a, b| a % b),
+        TokenKind::OpBitAnd => l.bitwise(r, |a, b| a & b),
+        TokenKind::OpBitOr => l.bitwise(r, |a, b| a | b),
+        TokenKind::OpBitXor => l.bitwise(r, |a, b| a ^ b),
+        TokenKind::OpShiftLeft => l.bitwise(r, |a, b| a.wrapping_shl(b as u32)),
+        TokenKind::OpShiftRight => l.bitwise(r, |a, b| a.wrapping_shr(b as u32)),
+        TokenKind::OpLess => l.compare(r, i64::lt, f64::lt),
+        TokenKind::OpLessEqual => l.compare(r, i64::le, f64::le),
+        TokenKind::OpGreater => l.compare(r, i64::gt, f64::gt),
+        TokenKind::OpGreaterEqual => l.compare(r, i64::ge, f64::ge),
+        TokenKind::OpEqual => l.equals(r).map(Value::from),
+        TokenKind::OpNotEqual => l.equals(r).map(|equal| Value::from(!equal)),
+        TokenKind::OpAnd => Some(Value::from(l.is_truthy() && r.is_truthy())),
+        TokenKind::OpOr => Some(Value::from(l.is_truthy() || r.is_truthy())),
+        _ => None,
+    }
+}
+
+// What `expr` folds to once its operands have been folded, if anything.
+fn fold(expr: &Expression) -> Option<Expression> {
+    let span = expr.span();
+    match expr {
+        Expression::Unary {operator, operand, ..} => to_literal(evaluate_unary(operator, &literal(operand)?)?, span),
+        // A constant left side decides whether the right is evaluated at all.
+        Expression::Binary {left, operator: TokenKind::OpAnd, ..} if left.constant_truth() == Some(false) => {
+            Some(Expression::Boolean(false, span))
+        }
+        Expression::Binary {left, operator: TokenKind::OpOr, ..} if left.constant_truth() == Some(true) => {
+            Some(Expression::Boolean(true, span))
+        }
+        Expression::Binary {left, operator, right, ..} => {
+            to_literal(evaluate_binary(operator, &literal(left)?, &literal(right)?)?, span)
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            let value = if condition.constant_truth()? { then_value } else { else_value };
+            Some(value.as_ref().clone())
+        }
+        _ => None,
+    }
+}
+
+pub(super) fn fold_expression(expr: &mut Expression) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => {}
+        Expression::Unary {operand, ..} => fold_expression(operand),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..} => {
+            fold_expression(left);
+            fold_expression(right);
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            // Only the parts of an element being stored into are expressions of their own.
+            if let Expression::Index {target, index, ..} = assignee.as_mut() {
+                fold_expression(target);
+                fold_expression(index);
+            }
+            fold_expression(value);
+        }
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => {
+            elements.iter_mut().for_each(fold_expression)
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            fold_expression(condition);
+            fold_expression(then_value);
+            fold_expression(else_value);
+        }
+    }
+    if let Some(folded) = fold(expr) {
+        *expr = folded;
+    }
+}
+
+fn fold_statement(stmt: &mut Statement) {
+    match stmt {
+        Statement::Block {body, ..} | Statement::Function {body, ..} => body.iter_mut().for_each(fold_statement),
+        Statement::Attributed {statement, ..} => fold_statement(statement),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => fold_expression(expression),
+        Statement::Print {arguments, ..} => arguments.iter_mut().for_each(fold_expression),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            fold_expression(condition);
+            fold_statement(then_branch);
+            if let Some(else_branch) = else_branch {
+                fold_statement(else_branch);
+            }
+        }
+        Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+            fold_expression(condition);
+            fold_statement(body);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                fold_expression(expr);
+            }
+            fold_statement(body);
+        }
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                fold_expression(value);
+            }
+        }
+        Statement::Import {..} => {}
+    }
+}
+
+// Evaluates operators whose operands are all literals, and conditionals whose condition is,
+// ahead of time. Anything that would be an error at runtime, such as `1 / 0`, is left alone.
+pub fn fold_constants(program: &mut [Statement]) {
+    program.iter_mut().for_each(fold_statement);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+    use crate::parser::printer::print_program;
+
+    #[test]
+    fn folds_literal_operands_and_leaves_errors_for_runtime() {
+        let source = "x = 1 + 2 * 3;\ny = 1 / 0;\nz = true ? \"a\" + \"b\" : q;\nw = !(2 < 1) && v;\nf = 1.5 * 2;\n";
+        let mut program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        fold_constants(&mut program);
+        assert_eq!(print_program(&program), "\
+(= x 7)
+(= y (/ 1 0))
+(= z \"ab\")
+(= w (&& true v))
+(= f 3.0)
+");
+    }
+}
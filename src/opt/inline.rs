@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Attribute, Expression, Statement, function_locals, names_in_expression};
+
+// The most expression nodes a function's result can have and still be inlined without
+// `#[inline]`.
+const INLINE_SIZE: usize = 10;
+// How many calls deep inlining goes into the calls it inlines, which stops functions that
+// call each other from inlining forever.
+const MAX_DEPTH: usize = 4;
+
+fn size(expr: &Expression) -> usize {
+    1 + match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => 0,
+        Expression::Unary {operand, ..} => size(operand),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => size(left) + size(right),
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => elements.iter().map(size).sum(),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            size(condition) + size(then_value) + size(else_value)
+        }
+    }
+}
+
+// Whether anything in `expr` satisfies `f`.
+fn any(expr: &Expression, f: &impl Fn(&Expression) -> bool) -> bool {
+    f(expr) || match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) | Expression::Symbol(..) => false,
+        Expression::Unary {operand, ..} => any(operand, f),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => any(left, f) || any(right, f),
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => elements.iter().any(|e| any(e, f)),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            any(condition, f) || any(then_value, f) || any(else_value, f)
+        }
+    }
+}
+
+fn is_literal(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..)
+    )
+}
+
+// What evaluating an expression does, in order, as far as substituting arguments goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    // Reads the parameter at this index; `true` if only on some paths.
+    Use(usize, bool),
+    // Something that can fail or has effects.
+    Fallible
+}
+
+fn events(expr: &Expression, parameters: &[Symbol], conditional: bool, out: &mut Vec<Event>) {
+    let mut each = |expr: &Expression, conditional: bool| events(expr, parameters, conditional, out);
+    match expr {
+        Expression::Symbol(name, _) => {
+            if let Some(i) = parameters.iter().position(|parameter| parameter == name) {
+                out.push(Event::Use(i, conditional));
+            }
+        }
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) => {}
+        Expression::Unary {operator: TokenKind::OpNot, operand, ..} => each(operand, conditional),
+        Expression::Binary {left, operator: TokenKind::OpAnd | TokenKind::OpOr, right, ..} => {
+            each(left, conditional);
+            each(right, true);
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            each(condition, conditional);
+            each(then_value, true);
+            each(else_value, true);
+        }
+        Expression::Array {elements, ..} => elements.iter().for_each(|e| each(e, conditional)),
+        Expression::Unary {operand, ..} => {
+            each(operand, conditional);
+            out.push(Event::Fallible);
+        }
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => {
+            each(left, conditional);
+            each(right, conditional);
+            out.push(Event::Fallible);
+        }
+        Expression::Call {arguments, ..} => {
+            arguments.iter().for_each(|e| each(e, conditional));
+            out.push(Event::Fallible);
+        }
+    }
+}
+
+// A function that only returns an expression of its parameters and globals.
+struct Inlinable {
+    parameters: Vec<Symbol>,
+    value: Expression,
+    // The globals it reads, which a caller's locals mustn't hide.
+    globals: Vec<Symbol>,
+    calls: bool,
+    events: Vec<Event>
+}
+
+impl Inlinable {
+    // Whether arguments that are neither literals nor variables can be evaluated where their
+    // parameters are used rather than before the call: each of those parameters has to be
+    // used once, on every path, in order, and before anything in the function that could fail.
+    fn takes_in_place(&self, complex: &[bool]) -> bool {
+        let mut next = 0;
+        let mut remaining = complex.iter().filter(|&&complex| complex).count();
+        for event in &self.events {
+            match *event {
+                Event::Use(i, conditional) if complex[i] => {
+                    if conditional || i < next || remaining == 0 {
+                        return false;
+                    }
+                    next = i + 1;
+                    remaining -= 1;
+                }
+                Event::Use(..) => {}
+                Event::Fallible if remaining > 0 => return false,
+                Event::Fallible => {}
+            }
+        }
+        remaining == 0
+    }
+}
+
+fn count_functions(body: &[Statement], counts: &mut HashMap<Symbol, usize>) {
+    for stmt in body {
+        match stmt {
+            Statement::Function {name, ..} => *counts.entry(*name).or_default() += 1,
+            Statement::Block {body, ..} => count_functions(body, counts),
+            Statement::If {then_branch, else_branch, ..} => {
+                count_functions(std::slice::from_ref(then_branch), counts);
+                if let Some(else_branch) = else_branch {
+                    count_functions(std::slice::from_ref(else_branch), counts);
+                }
+            }
+            Statement::While {body, ..} | Statement::DoWhile {body, ..} | Statement::For {body, ..} => {
+                count_functions(std::slice::from_ref(body), counts)
+            }
+            _ => {}
+        }
+    }
+}
+
+// The functions calls to which can be replaced by what they return: those declared once, at
+// the top level, whose whole body is `return` of an expression that assigns nothing. Without
+// `#[inline]` they also have to be small and call nothing; with `#[noinline]` they never are.
+fn inlinable(program: &[Statement]) -> HashMap<Symbol, Inlinable> {
+    let mut counts = HashMap::new();
+    count_functions(program, &mut counts);
+    let mut functions = HashMap::new();
+    for stmt in program {
+        let Statement::Function {name, parameters, body, attributes, ..} = stmt else { continue };
+        let [Statement::Return {value: Some(value), ..}] = body.as_slice() else { continue };
+        if counts[name] > 1 || attributes.contains(&Attribute::NoInline) {
+            continue;
+        }
+        if any(value, &|e| matches!(e, Expression::Assignment {..})) {
+            continue;
+        }
+        let calls = any(value, &|e| matches!(e, Expression::Call {..}));
+        if !attributes.contains(&Attribute::Inline) && (calls || size(value) > INLINE_SIZE) {
+            continue;
+        }
+        let mut globals = Vec::new();
+        names_in_expression(value, &mut globals);
+        globals.retain(|name| !parameters.contains(name));
+        let mut order = Vec::new();
+        events(value, parameters, false, &mut order);
+        functions.insert(*name, Inlinable { parameters: parameters.clone(), value: value.clone(), globals, calls, events: order });
+    }
+    functions
+}
+
+// `expr` with each parameter replaced by its argument.
+fn substitute(expr: &mut Expression, parameters: &[Symbol], arguments: &[Expression]) {
+    match expr {
+        Expression::Symbol(name, _) => {
+            if let Some(i) = parameters.iter().position(|parameter| parameter == name) {
+                *expr = arguments[i].clone();
+            }
+        }
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+        | Expression::String(..) => {}
+        Expression::Unary {operand, ..} => substitute(operand, parameters, arguments),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => {
+            substitute(left, parameters, arguments);
+            substitute(right, parameters, arguments);
+        }
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => {
+            elements.iter_mut().for_each(|e| substitute(e, parameters, arguments))
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            substitute(condition, parameters, arguments);
+            substitute(then_value, parameters, arguments);
+            substitute(else_value, parameters, arguments);
+        }
+    }
+}
+
+struct Inliner<'a> {
+    functions: &'a HashMap<Symbol, Inlinable>,
+    // The function being inlined into, if it isn't the top level, and its locals.
+    current: Option<Symbol>,
+    locals: Vec<Symbol>
+}
+
+impl Inliner<'_> {
+    // What a call evaluates to, if it can be worked out in place. Arguments are only
+    // substituted when evaluating them where they're used instead, as often as they're used,
+    // does the same: literals always, variables when nothing called could assign them, and
+    // other arguments that assign and call nothing when the function uses them just so.
+    fn inline(&self, callee: Symbol, arguments: &[Expression]) -> Option<Expression> {
+        let function = self.functions.get(&callee)?;
+        if self.current == Some(callee) || arguments.len() != function.parameters.len() {
+            return None;
+        }
+        if function.globals.iter().any(|name| self.locals.contains(name)) {
+            return None;
+        }
+        let reads = |expr: &Expression| !is_literal(expr) && !matches!(expr, Expression::Symbol(..));
+        if function.calls && arguments.iter().any(|argument| !is_literal(argument)) {
+            return None;
+        }
+        let effects = |e: &Expression| matches!(e, Expression::Assignment {..} | Expression::Call {..});
+        let complex: Vec<bool> = arguments.iter().map(reads).collect();
+        if arguments.iter().any(|argument| any(argument, &effects)) || !function.takes_in_place(&complex) {
+            return None;
+        }
+        let mut value = function.value.clone();
+        substitute(&mut value, &function.parameters, arguments);
+        Some(value)
+    }
+
+    fn expression(&self, expr: &mut Expression, depth: usize) {
+        match expr {
+            Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..)
+            | Expression::String(..) | Expression::Symbol(..) => {}
+            Expression::Unary {operand, ..} => self.expression(operand, depth),
+            Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+            | Expression::Assignment {assignee: left, value: right, ..} => {
+                self.expression(left, depth);
+                self.expression(right, depth);
+            }
+            Expression::Array {elements, ..} => elements.iter_mut().for_each(|e| self.expression(e, depth)),
+            Expression::Call {callee, arguments, ..} => {
+                arguments.iter_mut().for_each(|e| self.expression(e, depth));
+                if depth < MAX_DEPTH && let Some(mut value) = self.inline(*callee, arguments) {
+                    self.expression(&mut value, depth + 1);
+                    *expr = value;
+                }
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                self.expression(condition, depth);
+                self.expression(then_value, depth);
+                self.expression(else_value, depth);
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Block {body, ..} => body.iter_mut().for_each(|s| self.statement(s)),
+            Statement::Attributed {statement, ..} => self.statement(statement),
+            Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => self.expression(expression, 0),
+            Statement::Print {arguments, ..} => arguments.iter_mut().for_each(|a| self.expression(a, 0)),
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                self.expression(condition, 0);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+                self.expression(condition, 0);
+                self.statement(body);
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                for expr in [initializer, condition, step].into_iter().flatten() {
+                    self.expression(expr, 0);
+                }
+                self.statement(body);
+            }
+            Statement::Function {name, parameters, body, ..} => {
+                let mut inliner = Inliner { functions: self.functions, current: Some(*name), locals: function_locals(parameters, body) };
+                body.iter_mut().for_each(|s| inliner.statement(s));
+            }
+            Statement::Return {value, ..} => {
+                if let Some(value) = value {
+                    self.expression(value, 0);
+                }
+            }
+            Statement::Import {..} => {}
+        }
+    }
+}
+
+// Replaces calls to small functions that only return an expression, such as
+// `fn square(x) { return x * x; }`, with that expression. `#[inline]` inlines a function
+// whatever its size, and `#[noinline]` stops it being inlined.
+pub fn inline_functions(program: &mut [Statement]) {
+    let functions = inlinable(program);
+    if functions.is_empty() {
+        return;
+    }
+    let mut inliner = Inliner { functions: &functions, current: None, locals: Vec::new() };
+    program.iter_mut().for_each(|stmt| inliner.statement(stmt));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+    use crate::parser::printer::print_program;
+
+    #[test]
+    fn inlines_small_functions_that_only_return_an_expression() {
+        let source = "fn square(x) { return x * x; }\n#[noinline]\nfn twice(x) { return x + x; }\nfn next() { return n = n + 1; }\nn = 0;\nprint(square(3), square(a), twice(2), square(next()), \"\\n\");\n";
+        let mut program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        inline_functions(&mut program);
+        assert_eq!(print_program(&program), "\
+(fn square (x)
+  (return (* x x)))
+(fn twice (x) #[noinline]
+  (return (+ x x)))
+(fn next ()
+  (return (= n (+ n 1))))
+(= n 0)
+(print (* 3 3) (* a a) (call twice 2) (call square (call next)) \"\\n\")
+");
+    }
+}
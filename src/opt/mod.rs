@@ -0,0 +1,8 @@
+mod fold;
+mod dce;
+mod inline;
+
+pub use fold::fold_constants;
+pub(crate) use fold::{evaluate_binary, evaluate_unary};
+pub use dce::eliminate_dead_code;
+pub use inline::inline_functions;
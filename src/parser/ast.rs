@@ -1,19 +1,340 @@
-use crate::lexer::Token;
+use std::fmt;
+
+use crate::diagnostics::{Level, Span};
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
-    Number(i64),
-    String(String),
-    Symbol(String),
+    // Every expression keeps a span for diagnostics: literals and names their token's,
+    // operators their operator's, and arrays and indexing their brackets'.
+    Number(i64, Span),
+    Char(char, Span),
+    Float(f64, Span),
+    Boolean(bool, Span),
+    String(String, Span),
+    // Calls keep the name's span.
+    Symbol(Symbol, Span),
+    Unary {
+        operator: TokenKind,
+        operand: Box<Expression>,
+        span: Span,
+    },
     Binary {
         left: Box<Expression>,
-        operator: Token,
+        operator: TokenKind,
         right: Box<Expression>,
+        span: Span,
+    },
+    Assignment {
+        assignee: Box<Expression>,
+        value: Box<Expression>,
+        // How it was written, which only matters to printing it again.
+        form: AssignmentForm,
+        span: Span,
+    },
+    Call {
+        callee: Symbol,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    Conditional {
+        condition: Box<Expression>,
+        then_value: Box<Expression>,
+        else_value: Box<Expression>,
+        span: Span,
+    },
+    Array {elements: Vec<Expression>, span: Span},
+    Index {
+        target: Box<Expression>,
+        index: Box<Expression>,
+        span: Span,
+    }
+}
+
+// `x += e` and `++x` are `x = x + e` and `x = x + 1`, whose value is the addition, to
+// everything but the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssignmentForm {
+    #[default]
+    Plain,
+    // `x += e`, or another operator's.
+    Compound,
+    // `++x` or `--x`.
+    Prefix
+}
+
+impl Expression {
+    // From the start of the expression to its end, leaving out any parentheses around it.
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Number(_, span) | Expression::Char(_, span) | Expression::Float(_, span)
+            | Expression::Boolean(_, span) | Expression::String(_, span) | Expression::Symbol(_, span)
+            | Expression::Array {span, ..} => *span,
+            Expression::Unary {operand, span, ..} => span.to(operand.span()),
+            Expression::Binary {left, right, ..} => left.span().to(right.span()),
+            Expression::Assignment {assignee, value, span, ..} => assignee.span().to(*span).to(value.span()),
+            Expression::Call {arguments, span, ..} => arguments.last().map_or(*span, |last| span.to(last.span())),
+            Expression::Conditional {condition, else_value, ..} => condition.span().to(else_value.span()),
+            Expression::Index {target, span, ..} => target.span().to(*span),
+        }
+    }
+
+    // Whether the expression is a literal, or the negation of one, that's always true or false.
+    pub fn constant_truth(&self) -> Option<bool> {
+        match self {
+            Expression::Number(n, _) => Some(*n != 0),
+            Expression::Char(c, _) => Some(*c != '\0'),
+            Expression::Float(x, _) => Some(*x != 0.0),
+            Expression::Boolean(b, _) => Some(*b),
+            Expression::String(s, _) => Some(!s.is_empty()),
+            Expression::Unary {operator: TokenKind::OpNot, operand, ..} => operand.constant_truth().map(|b| !b),
+            _ => None,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
-    Block {body: Vec<Statement>},
-    Expression {expression: Expression}
-}
\ No newline at end of file
+    // Statements span from their first token to their last, except that a function's
+    // span is its name's.
+    Block {body: Vec<Statement>, span: Span},
+    Expression {expression: Expression, span: Span},
+    Print {arguments: Vec<Expression>, span: Span},
+    Putc {expression: Expression, span: Span},
+    If {
+        condition: Expression,
+        then_branch: Box<Statement>,
+        else_branch: Option<Box<Statement>>,
+        span: Span,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+        span: Span,
+    },
+    DoWhile {
+        body: Box<Statement>,
+        condition: Expression,
+        span: Span,
+    },
+    For {
+        initializer: Option<Expression>,
+        condition: Option<Expression>,
+        step: Option<Expression>,
+        body: Box<Statement>,
+        span: Span,
+    },
+    Function {
+        name: Symbol,
+        parameters: Vec<Symbol>,
+        // Where each parameter's name is, in the same order.
+        parameter_spans: Vec<Span>,
+        body: Vec<Statement>,
+        attributes: Vec<Attribute>,
+        span: Span,
+    },
+    Return {value: Option<Expression>, span: Span},
+    // Any other statement with attributes written before it; a function keeps its own.
+    Attributed {
+        attributes: Vec<Attribute>,
+        statement: Box<Statement>,
+        span: Span,
+    },
+    // `import name;`, at the top level: the module `name`, whose functions and globals are
+    // then `name.f` and `name.x`.
+    Import {module: Symbol, span: Span}
+}
+
+// A directive such as `#[inline]` or `#[allow(unused)]` written before a function or a
+// statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Attribute {
+    // Inline calls to the function whatever its size.
+    Inline,
+    // Never inline calls to it.
+    NoInline,
+    // `#[allow(...)]`, `#[warn(...)]` or `#[deny(...)]`: the level of the lints named, as
+    // `-A`, `-W` and `--deny` take them, for the warnings in what it's written before.
+    Lint(Level, Vec<Symbol>)
+}
+
+impl Attribute {
+    pub const NAMES: &[&str] = &["inline", "noinline", "allow", "warn", "deny"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attribute::Inline => "inline",
+            Attribute::NoInline => "noinline",
+            Attribute::Lint(Level::Allow, _) => "allow",
+            Attribute::Lint(Level::Warn, _) => "warn",
+            Attribute::Lint(Level::Deny, _) => "deny",
+        }
+    }
+
+    // Whether only a function can have it.
+    pub fn is_function_only(&self) -> bool {
+        matches!(self, Attribute::Inline | Attribute::NoInline)
+    }
+}
+
+// As it's written, such as `#[allow(unused, shadowing)]`.
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Attribute::Lint(_, lints) => {
+                let lints: Vec<&str> = lints.iter().map(|lint| lint.as_str()).collect();
+                write!(f, "#[{}({})]", self.name(), lints.join(", "))
+            }
+            _ => write!(f, "#[{}]", self.name()),
+        }
+    }
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Block {span, ..} | Statement::Expression {span, ..} | Statement::Print {span, ..}
+            | Statement::Putc {span, ..} | Statement::If {span, ..} | Statement::While {span, ..}
+            | Statement::DoWhile {span, ..} | Statement::For {span, ..} | Statement::Function {span, ..}
+            | Statement::Return {span, ..} | Statement::Attributed {span, ..} | Statement::Import {span, ..} => *span,
+        }
+    }
+}
+
+fn assigned_in_expression(expr: &Expression, names: &mut Vec<Symbol>) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) | Expression::Symbol(..) => {}
+        Expression::Unary {operand, ..} => assigned_in_expression(operand, names),
+        Expression::Binary {left, right, ..} => {
+            assigned_in_expression(left, names);
+            assigned_in_expression(right, names);
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            match assignee.as_ref() {
+                Expression::Symbol(name, _) if !names.contains(name) => names.push(*name),
+                // Storing into an element doesn't make the array itself local.
+                Expression::Index {target, index, ..} => {
+                    assigned_in_expression(target, names);
+                    assigned_in_expression(index, names);
+                }
+                _ => {}
+            }
+            assigned_in_expression(value, names);
+        }
+        Expression::Call {arguments, ..} | Expression::Array {elements: arguments, ..} => {
+            arguments.iter().for_each(|a| assigned_in_expression(a, names))
+        }
+        Expression::Index {target, index, ..} => {
+            assigned_in_expression(target, names);
+            assigned_in_expression(index, names);
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            assigned_in_expression(condition, names);
+            assigned_in_expression(then_value, names);
+            assigned_in_expression(else_value, names);
+        }
+    }
+}
+
+fn assigned_in_statement(stmt: &Statement, names: &mut Vec<Symbol>) {
+    match stmt {
+        Statement::Block {body, ..} => body.iter().for_each(|s| assigned_in_statement(s, names)),
+        Statement::Attributed {statement, ..} => assigned_in_statement(statement, names),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => assigned_in_expression(expression, names),
+        Statement::Print {arguments, ..} => arguments.iter().for_each(|a| assigned_in_expression(a, names)),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            assigned_in_expression(condition, names);
+            assigned_in_statement(then_branch, names);
+            if let Some(else_branch) = else_branch {
+                assigned_in_statement(else_branch, names);
+            }
+        }
+        Statement::While {condition, body, ..} => {
+            assigned_in_expression(condition, names);
+            assigned_in_statement(body, names);
+        }
+        Statement::DoWhile {body, condition, ..} => {
+            assigned_in_statement(body, names);
+            assigned_in_expression(condition, names);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                assigned_in_expression(expr, names);
+            }
+            assigned_in_statement(body, names);
+        }
+        Statement::Function {..} | Statement::Import {..} => {}
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                assigned_in_expression(value, names);
+            }
+        }
+    }
+}
+
+// A function's local variables: its parameters, then every other name it assigns to.
+// Any other name a function reads refers to a global.
+pub fn function_locals(parameters: &[Symbol], body: &[Statement]) -> Vec<Symbol> {
+    let mut names = parameters.to_vec();
+    body.iter().for_each(|s| assigned_in_statement(s, &mut names));
+    names
+}
+
+// Every name an expression or statement reads or assigns, leaving out function bodies.
+pub fn names_in_expression(expr: &Expression, names: &mut Vec<Symbol>) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+        Expression::Symbol(name, _) => names.push(*name),
+        Expression::Unary {operand, ..} => names_in_expression(operand, names),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..}
+        | Expression::Assignment {assignee: left, value: right, ..} => {
+            names_in_expression(left, names);
+            names_in_expression(right, names);
+        }
+        Expression::Call {arguments: elements, ..} | Expression::Array {elements, ..} => {
+            elements.iter().for_each(|e| names_in_expression(e, names))
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            names_in_expression(condition, names);
+            names_in_expression(then_value, names);
+            names_in_expression(else_value, names);
+        }
+    }
+}
+
+pub fn names_in_statement(stmt: &Statement, names: &mut Vec<Symbol>) {
+    match stmt {
+        Statement::Block {body, ..} => body.iter().for_each(|s| names_in_statement(s, names)),
+        Statement::Attributed {statement, ..} => names_in_statement(statement, names),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => names_in_expression(expression, names),
+        Statement::Print {arguments, ..} => arguments.iter().for_each(|a| names_in_expression(a, names)),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            names_in_expression(condition, names);
+            names_in_statement(then_branch, names);
+            if let Some(else_branch) = else_branch {
+                names_in_statement(else_branch, names);
+            }
+        }
+        Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+            names_in_expression(condition, names);
+            names_in_statement(body, names);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                names_in_expression(expr, names);
+            }
+            names_in_statement(body, names);
+        }
+        Statement::Function {..} | Statement::Import {..} => {}
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                names_in_expression(value, names);
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+// Comments, which the parser skips, attached to the statements they're about, so tools that
+// print the program again can keep them. A comment on the same line as the end of a
+// statement trails it, one before a statement leads it, and one with only a `}` after it
+// closes the block or function that brace ends, and the `///` ones that lead a function
+// are its documentation. Comments are found in the tokens of the lexer's trivia-keeping
+// mode. String literals written raw or between triple quotes are kept here as well, as the
+// AST only has what's in them.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::diagnostics::Span;
+use crate::lexer::{Token, TokenKind};
+use crate::parser::ast::Statement;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    // The comment as written, with its `//` or `/*` and `*/`.
+    pub text: String,
+    pub span: Span,
+    // Whether it's a `///` doc comment.
+    pub doc: bool
+}
+
+// The comments of a program, keyed by where the statements they're attached to start.
+#[derive(Debug, Clone, Default)]
+pub struct Comments {
+    leading: HashMap<usize, Vec<Comment>>,
+    trailing: HashMap<usize, Vec<Comment>>,
+    closing: HashMap<usize, Vec<Comment>>,
+    end: Vec<Comment>,
+    // The string literals to print as written, keyed by where they start.
+    literals: HashMap<usize, String>
+}
+
+impl Comments {
+    // The comments before the statement, and for a function its attributes too.
+    pub fn leading(&self, statement: &Statement) -> &[Comment] {
+        self.leading.get(&statement.span().start).map_or(&[], Vec::as_slice)
+    }
+
+    // The documentation of a function: the text of the `///` comments right before it, each
+    // without its `///` and the space after that, a line apiece.
+    pub fn doc(&self, function: &Statement) -> Option<String> {
+        if !matches!(function, Statement::Function { .. }) {
+            return None;
+        }
+        let leading = self.leading(function);
+        let first = leading.iter().rposition(|comment| !comment.doc).map_or(0, |i| i + 1);
+        let lines: Vec<&str> = leading[first..].iter()
+            .map(|comment| {
+                let text = &comment.text["///".len()..];
+                text.strip_prefix(' ').unwrap_or(text).trim_end()
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    // The comments after the statement on the line it ends on.
+    pub fn trailing(&self, statement: &Statement) -> &[Comment] {
+        self.trailing.get(&statement.span().start).map_or(&[], Vec::as_slice)
+    }
+
+    // The comments after the last statement of a block or a function's body, before its `}`.
+    pub fn closing(&self, statement: &Statement) -> &[Comment] {
+        self.closing.get(&statement.span().start).map_or(&[], Vec::as_slice)
+    }
+
+    // The literal at `span` as it was written, if it's a string that isn't printed the
+    // usual way: `r"\n"` isn't `"\\n"`, and a `"""` string may run over several lines.
+    pub fn literal(&self, span: Span) -> Option<&str> {
+        self.literals.get(&span.start).map(String::as_str)
+    }
+
+    // The comments after the program's last statement.
+    pub fn end(&self) -> &[Comment] {
+        &self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_empty() && self.closing.is_empty() && self.end.is_empty()
+    }
+}
+
+// Where the statements of a program start and end, by their keys. The blocks that are the
+// bodies of `if`s and loops, and an `else if`'s `if`, aren't statements of their own here:
+// the formatter writes them as part of the statement they're in.
+#[derive(Default)]
+struct Extents {
+    starts: BTreeMap<usize, usize>,
+    ends: HashMap<usize, usize>,
+    // Where each block's and function's `}` is.
+    closes: HashMap<usize, usize>,
+    // In the order the statements start, so each is after the ones it's in.
+    all: Vec<(Span, usize)>
+}
+
+impl Extents {
+    fn statement(&mut self, source: &str, code: &[&Token], statement: &Statement, own: bool) {
+        let key = statement.span().start;
+        let extent = extent(code, statement);
+        if own {
+            self.starts.entry(extent.start).or_insert(key);
+            // Statements in this one that end where it does come after it, so a comment
+            // after them trails the innermost.
+            self.ends.insert(extent.end, key);
+            self.all.push((extent, key));
+        }
+        match statement {
+            Statement::Block { body, .. } => {
+                if source[..extent.end].ends_with('}') {
+                    self.closes.insert(extent.end - 1, key);
+                }
+                body.iter().for_each(|statement| self.statement(source, code, statement, true));
+            }
+            Statement::Function { body, .. } => {
+                self.closes.insert(extent.end - 1, key);
+                body.iter().for_each(|statement| self.statement(source, code, statement, true));
+            }
+            Statement::If { then_branch, else_branch, .. } => {
+                self.body(source, code, then_branch);
+                match else_branch.as_deref() {
+                    Some(chained @ Statement::If { .. }) => self.statement(source, code, chained, false),
+                    Some(else_branch) => self.body(source, code, else_branch),
+                    None => {}
+                }
+            }
+            Statement::While { body, .. } | Statement::DoWhile { body, .. } | Statement::For { body, .. } => {
+                self.body(source, code, body)
+            }
+            // Its statement starts after the attributes, not where it does.
+            Statement::Attributed { statement, .. } => self.statement(source, code, statement, true),
+            Statement::Expression { .. } | Statement::Print { .. } | Statement::Putc { .. } | Statement::Return { .. }
+            | Statement::Import { .. } => {}
+        }
+    }
+
+    fn body(&mut self, source: &str, code: &[&Token], body: &Statement) {
+        self.statement(source, code, body, !matches!(body, Statement::Block { .. }));
+    }
+}
+
+// From a statement's first token to its last. A function's span is only its name's, so its
+// extent is found from the tokens: back over `fn` and any attributes, and on to the `}`
+// that closes its body.
+fn extent(code: &[&Token], statement: &Statement) -> Span {
+    let span = statement.span();
+    if !matches!(statement, Statement::Function { .. }) {
+        return span;
+    }
+    let Ok(name) = code.binary_search_by_key(&span.start, |token| token.span.start) else { return span };
+    let mut first = name.saturating_sub(1);
+    while first > 0 && code[first - 1].kind == TokenKind::CloseBracket {
+        match code[..first].iter().rposition(|token| token.kind == TokenKind::Hash) {
+            Some(hash) => first = hash,
+            None => break,
+        }
+    }
+    let mut depth = 0;
+    for token in &code[name..] {
+        match token.kind {
+            TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseBrace if depth == 1 => return Span::new(code[first].span.start, token.span.end),
+            TokenKind::CloseBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    span
+}
+
+// Attaches the comments among `tokens`, lexed from `source` keeping trivia, to the
+// statements of `program`, which was parsed from them.
+pub fn attach_comments(source: &str, tokens: &[Token], program: &[Statement]) -> Comments {
+    let code: Vec<&Token> = tokens.iter()
+        .filter(|token| !token.kind.is_trivia() && token.kind != TokenKind::EndOfInput)
+        .collect();
+    let mut extents = Extents::default();
+    program.iter().for_each(|statement| extents.statement(source, &code, statement, true));
+
+    let mut comments = Comments::default();
+    let mut next = 0;
+    for token in tokens {
+        let text = &source[token.span.start..token.span.end];
+        if matches!(token.kind, TokenKind::String(_)) && (text.starts_with("r\"") || text.starts_with("\"\"\"")) {
+            comments.literals.insert(token.span.start, text.to_string());
+        }
+        if !matches!(token.kind, TokenKind::Comment(_) | TokenKind::DocComment(_)) {
+            if next < code.len() && code[next].span == token.span {
+                next += 1;
+            }
+            continue;
+        }
+        let comment = Comment { text: text.to_string(), span: token.span, doc: matches!(token.kind, TokenKind::DocComment(_)) };
+        let previous = next.checked_sub(1).map(|i| code[i].span);
+        let following = code.get(next).map(|token| (token.span, &token.kind));
+
+        if let Some(previous) = previous
+            && let Some(&key) = extents.ends.get(&previous.end)
+            && !source[previous.end..comment.span.start].contains('\n')
+        {
+            comments.trailing.entry(key).or_default().push(comment);
+        } else if let Some((span, _)) = following
+            && let Some(&key) = extents.starts.get(&span.start)
+        {
+            comments.leading.entry(key).or_default().push(comment);
+        } else if let Some((span, TokenKind::CloseBrace)) = following
+            && let Some(&key) = extents.closes.get(&span.start)
+        {
+            comments.closing.entry(key).or_default().push(comment);
+        } else if following.is_none() {
+            comments.end.push(comment);
+        // One in the middle of a statement goes before the innermost statement it's in.
+        } else if let Some(&(_, key)) = extents.all.iter().rev().find(|(extent, _)| extent.start <= comment.span.start && comment.span.start < extent.end) {
+            comments.leading.entry(key).or_default().push(comment);
+        } else {
+            comments.end.push(comment);
+        }
+    }
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::{FormatConfig, format_program_with};
+    use crate::lexer::{BorrowedToken, LexerOptions, tokenize_with_options};
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn keeps_comments_where_they_were() {
+        let source = "// sum\n#[inline]\nfn add(a, b) {\n    return a + b; // both\n    // unreachable\n}\nx = add(1, /* two */ 2);\nif (x) { print(x); } // printed\n// done\n";
+        let options = LexerOptions { preserve_trivia: true, ..LexerOptions::default() };
+        let tokens: Vec<Token> = tokenize_with_options(source, options).0.into_iter().map(BorrowedToken::into_owned).collect();
+        let program = parse_program(&mut Parser::new(tokens.clone())).unwrap();
+        let comments = attach_comments(source, &tokens, &program);
+
+        let texts = |comments: &[Comment]| comments.iter().map(|comment| comment.text.clone()).collect::<Vec<_>>();
+        assert_eq!(texts(comments.leading(&program[0])), ["// sum"]);
+        assert_eq!(texts(comments.leading(&program[1])), ["/* two */"]);
+        assert_eq!(texts(comments.trailing(&program[2])), ["// printed"]);
+        assert_eq!(texts(comments.end()), ["// done"]);
+        assert_eq!(format_program_with(&program, &comments, &FormatConfig::default()), "\
+// sum
+#[inline]
+fn add(a, b) {
+    return a + b; // both
+    // unreachable
+}
+/* two */
+x = add(1, 2);
+if (x) {
+    print(x);
+} // printed
+// done
+");
+    }
+}
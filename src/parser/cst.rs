@@ -0,0 +1,245 @@
+use std::fmt;
+
+use crate::diagnostics::Span;
+use crate::lexer::{Token, TokenKind};
+
+// What a node of the concrete syntax tree stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Program,
+    Block,
+    EmptyStatement,
+    ExpressionStatement,
+    Print,
+    Putc,
+    If,
+    While,
+    DoWhile,
+    For,
+    Function,
+    Attribute,
+    // A statement other than a function, with its attributes.
+    Attributed,
+    ParameterList,
+    Return,
+    Import,
+    Literal,
+    Name,
+    Paren,
+    Array,
+    Unary,
+    Binary,
+    Assignment,
+    Call,
+    Conditional,
+    Index
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    // Text the lexer didn't recognize.
+    Skipped
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span
+}
+
+// A token with the text it was lexed from and the trivia between it and the previous token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+    pub leading: Vec<Trivia>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken)
+}
+
+// A lossless tree: its tokens and their trivia, in order, spell out the whole source.
+// The program's trailing trivia belongs to its final `EndOfInput` token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>
+}
+
+impl SyntaxNode {
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    // From the start of its first token's trivia to the end of its last token.
+    pub fn span(&self) -> Span {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => {
+                let start = first.leading.first().map_or(first.span.start, |trivia| trivia.span.start);
+                Span::new(start, last.span.end)
+            }
+            _ => Span::default(),
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+}
+
+// Writes the source text back out exactly.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens() {
+            for trivia in &token.leading {
+                f.write_str(&trivia.text)?;
+            }
+            f.write_str(&token.text)?;
+        }
+        Ok(())
+    }
+}
+
+// What the parser records as it goes, to be turned into a tree afterwards.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Event {
+    Start(SyntaxKind),
+    Token,
+    Finish
+}
+
+// Splits the text between two tokens into whitespace and comments.
+fn trivia(text: &str, start: usize) -> Vec<Trivia> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let whitespace = rest.len() - rest.trim_start().len();
+        let (kind, len) = if rest.starts_with("//") {
+            (TriviaKind::LineComment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("/*") && let Some(end) = rest[2..].find("*/") {
+            (TriviaKind::BlockComment, end + 4)
+        } else if whitespace > 0 {
+            (TriviaKind::Whitespace, whitespace)
+        } else {
+            (TriviaKind::Skipped, rest.chars().next().map_or(1, char::len_utf8))
+        };
+        // Unrecognized text runs until the next comment or whitespace.
+        if let (TriviaKind::Skipped, Some(Trivia { kind: TriviaKind::Skipped, text, span })) = (kind, out.last_mut()) {
+            text.push_str(&rest[..len]);
+            span.end += len;
+        } else {
+            out.push(Trivia { kind, text: rest[..len].to_string(), span: Span::new(start + pos, start + pos + len) });
+        }
+        pos += len;
+    }
+    out
+}
+
+fn syntax_token(token: &Token, source: &str, trivia_start: usize) -> SyntaxToken {
+    let span = token.span;
+    SyntaxToken {
+        kind: token.kind.clone(),
+        text: source[span.start..span.end].to_string(),
+        span,
+        leading: trivia(&source[trivia_start..span.start], trivia_start),
+    }
+}
+
+// Replays the parser's events over the tokens it read from `source`.
+pub(crate) fn build(source: &str, tokens: &[Token], events: &[Event]) -> SyntaxNode {
+    let mut stack = vec![SyntaxNode { kind: SyntaxKind::Program, children: Vec::new() }];
+    let mut tokens = tokens.iter();
+    let mut end = 0;
+    for event in events {
+        match event {
+            Event::Start(kind) => stack.push(SyntaxNode { kind: *kind, children: Vec::new() }),
+            Event::Token => {
+                let token = tokens.next().unwrap();
+                stack.last_mut().unwrap().children.push(SyntaxElement::Token(syntax_token(token, source, end)));
+                end = token.span.end;
+            }
+            Event::Finish => {
+                let node = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(SyntaxElement::Node(node));
+            }
+        }
+    }
+    let eof = Token { kind: TokenKind::EndOfInput, span: Span::new(source.len(), source.len()) };
+    let mut root = stack.pop().unwrap();
+    root.children.push(SyntaxElement::Token(syntax_token(tokens.last().unwrap_or(&eof), source, end)));
+    root
+}
+
+fn print_node(node: &SyntaxNode, depth: usize, out: &mut String) {
+    let span = node.span();
+    out.push_str(&format!("{}{:?}@{}..{}\n", "  ".repeat(depth), node.kind, span.start, span.end));
+    for child in &node.children {
+        match child {
+            SyntaxElement::Node(node) => print_node(node, depth + 1, out),
+            SyntaxElement::Token(token) => {
+                let indent = "  ".repeat(depth + 1);
+                for trivia in &token.leading {
+                    out.push_str(&format!("{}{:?}@{}..{} {:?}\n", indent, trivia.kind, trivia.span.start, trivia.span.end, trivia.text));
+                }
+                out.push_str(&format!("{}{}@{}..{} {:?}\n", indent, token.kind.name(), token.span.start, token.span.end, token.text));
+            }
+        }
+    }
+}
+
+// One line per node, token and piece of trivia, indented by depth, with its byte range.
+pub fn print_syntax_tree(node: &SyntaxNode) -> String {
+    let mut out = String::new();
+    print_node(node, 0, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{BorrowedToken, LexerOptions, tokenize_with_options};
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn spells_out_the_source_it_was_parsed_from() {
+        let source = "// start\nfn add(a, b) {\n    return a  +  b; /* sum */\n}\nif (add(1, 2)) { print(\"x\\n\"); }\n\n";
+        let options = LexerOptions { preserve_trivia: true, ..LexerOptions::default() };
+        let tokens = tokenize_with_options(source, options).0.into_iter().map(BorrowedToken::into_owned).collect();
+        let mut parser = Parser::with_syntax_tree(tokens);
+        parse_program(&mut parser).unwrap();
+        let tree = parser.syntax_tree(source).unwrap();
+
+        assert_eq!(tree.to_string(), source);
+        assert_eq!(tree.kind, SyntaxKind::Program);
+        assert_eq!(tree.span(), Span::new(0, source.len()));
+        let kinds: Vec<SyntaxKind> = tree.nodes().map(|node| node.kind).collect();
+        assert_eq!(kinds, [SyntaxKind::Function, SyntaxKind::If]);
+        let first = &tree.tokens()[0];
+        assert_eq!((first.kind.clone(), first.leading[0].kind), (TokenKind::KeywordFn, TriviaKind::LineComment));
+        assert!(print_syntax_tree(&tree).contains("BlockComment@"));
+    }
+}
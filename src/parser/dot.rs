@@ -0,0 +1,231 @@
+use crate::lexer::{quote_char, quote_string};
+use crate::parser::ast::{Expression, Statement};
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+struct Graph {
+    out: String,
+    nodes: usize
+}
+
+impl Graph {
+    fn node(&mut self, label: &str, shape: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        self.out.push_str(&format!("    n{} [label=\"{}\", shape={}];\n", id, escape(label), shape));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => self.out.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n", from, to, label)),
+            None => self.out.push_str(&format!("    n{} -> n{};\n", from, to)),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match expr {
+            Expression::Number(n, _) => self.node(&n.to_string(), "ellipse"),
+            Expression::Char(c, _) => self.node(&quote_char(*c), "ellipse"),
+            Expression::Float(x, _) => self.node(&format!("{:?}", x), "ellipse"),
+            Expression::Boolean(b, _) => self.node(&b.to_string(), "ellipse"),
+            Expression::String(s, _) => self.node(&quote_string(s), "ellipse"),
+            Expression::Symbol(name, _) => self.node(name.as_str(), "ellipse"),
+            Expression::Unary {operator, operand, ..} => {
+                let id = self.node(&operator.to_string(), "circle");
+                let child = self.expression(operand);
+                self.edge(id, child, None);
+                id
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                let id = self.node(&operator.to_string(), "circle");
+                let left = self.expression(left);
+                self.edge(id, left, None);
+                let right = self.expression(right);
+                self.edge(id, right, None);
+                id
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                let id = self.node("=", "circle");
+                let assignee = self.expression(assignee);
+                self.edge(id, assignee, None);
+                let value = self.expression(value);
+                self.edge(id, value, None);
+                id
+            }
+            Expression::Call {callee, arguments, ..} => {
+                let id = self.node(&format!("call {}", callee), "box");
+                for arg in arguments {
+                    let child = self.expression(arg);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Expression::Array {elements, ..} => {
+                let id = self.node("[]", "box");
+                for element in elements {
+                    let child = self.expression(element);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let id = self.node("?:", "circle");
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("cond"));
+                let then_value = self.expression(then_value);
+                self.edge(id, then_value, Some("then"));
+                let else_value = self.expression(else_value);
+                self.edge(id, else_value, Some("else"));
+                id
+            }
+            Expression::Index {target, index, ..} => {
+                let id = self.node("index", "circle");
+                let target = self.expression(target);
+                self.edge(id, target, None);
+                let index = self.expression(index);
+                self.edge(id, index, None);
+                id
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> usize {
+        match stmt {
+            Statement::Block {body, ..} => {
+                let id = self.node("block", "box");
+                for stmt in body {
+                    let child = self.statement(stmt);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Statement::Expression {expression, ..} => self.expression(expression),
+            Statement::Print {arguments, ..} => {
+                let id = self.node("print", "box");
+                for arg in arguments {
+                    let child = self.expression(arg);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Statement::Putc {expression, ..} => {
+                let id = self.node("putc", "box");
+                let child = self.expression(expression);
+                self.edge(id, child, None);
+                id
+            }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let id = self.node("if", "box");
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("cond"));
+                let then_branch = self.statement(then_branch);
+                self.edge(id, then_branch, Some("then"));
+                if let Some(else_branch) = else_branch {
+                    let else_branch = self.statement(else_branch);
+                    self.edge(id, else_branch, Some("else"));
+                }
+                id
+            }
+            Statement::While {condition, body, ..} => {
+                let id = self.node("while", "box");
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("cond"));
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                let id = self.node("do-while", "box");
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("cond"));
+                id
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                let id = self.node("for", "box");
+                for (label, expr) in [("init", initializer), ("cond", condition), ("step", step)] {
+                    if let Some(expr) = expr {
+                        let child = self.expression(expr);
+                        self.edge(id, child, Some(label));
+                    }
+                }
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Statement::Function {name, parameters, body, attributes, ..} => {
+                let attributes: String = attributes.iter().map(|attribute| format!("{} ", attribute)).collect();
+                let parameters = parameters.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", ");
+                let id = self.node(&format!("{}fn {}({})", attributes, name, parameters), "box");
+                for stmt in body {
+                    let child = self.statement(stmt);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Statement::Attributed {attributes, statement, ..} => {
+                let attributes: Vec<String> = attributes.iter().map(|attribute| attribute.to_string()).collect();
+                let id = self.node(&attributes.join(" "), "box");
+                let child = self.statement(statement);
+                self.edge(id, child, None);
+                id
+            }
+            Statement::Return {value, ..} => {
+                let id = self.node("return", "box");
+                if let Some(value) = value {
+                    let child = self.expression(value);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Statement::Import {module, ..} => self.node(&format!("import {}", module), "box"),
+        }
+    }
+}
+
+pub fn program_to_dot(program: &[Statement]) -> String {
+    let mut graph = Graph { out: String::new(), nodes: 0 };
+    graph.out.push_str("digraph ast {\n");
+    graph.out.push_str("    node [fontname=\"monospace\"];\n");
+    let root = graph.node("program", "box");
+    for stmt in program {
+        let child = graph.statement(stmt);
+        graph.edge(root, child, None);
+    }
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn labels_edges_and_escapes_quotes() {
+        let source = "if (x < 1) print(\"a\\\"b\");\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        assert_eq!(program_to_dot(&program), r#"digraph ast {
+    node [fontname="monospace"];
+    n0 [label="program", shape=box];
+    n1 [label="if", shape=box];
+    n2 [label="<", shape=circle];
+    n3 [label="x", shape=ellipse];
+    n2 -> n3;
+    n4 [label="1", shape=ellipse];
+    n2 -> n4;
+    n1 -> n2 [label="cond"];
+    n5 [label="print", shape=box];
+    n6 [label="\"a\\\"b\"", shape=ellipse];
+    n5 -> n6;
+    n1 -> n5 [label="then"];
+    n0 -> n1;
+}
+"#);
+    }
+}
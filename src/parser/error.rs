@@ -0,0 +1,47 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Label, Span};
+use crate::lexer::TokenKind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // Looked up by `tiny-lang explain`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    // Somewhere else worth pointing at, such as a misspelt keyword that led to the error.
+    pub note: Option<Label>
+}
+
+impl ParseError {
+    pub fn new(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self {code, message: message.into(), span, note: None}
+    }
+
+    pub fn with_note(self, message: impl Into<String>, span: Span) -> Self {
+        Self {note: Some(Label { span, message: message.into() }), ..self}
+    }
+}
+
+pub(crate) fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::EndOfInput => "end of input".to_string(),
+        kind => format!("`{}`", kind),
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let diagnostic = Diagnostic::error(error.message.clone(), error.span).with_code(error.code);
+        match &error.note {
+            Some(note) => diagnostic.with_note(note.message.clone(), note.span),
+            None => diagnostic,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::lexer::TokenKind;
+use crate::parser::cst::SyntaxKind;
+use crate::parser::parser::{BindingPower, infix_bp, prefix_kind};
+
+// Every token that can stand between or before operands.
+const OPERATORS: &[TokenKind] = &[
+    TokenKind::OpAssign, TokenKind::OpAddAssign, TokenKind::OpSubtractAssign, TokenKind::OpMultiplyAssign,
+    TokenKind::OpDivideAssign, TokenKind::OpModAssign, TokenKind::OpOr, TokenKind::OpAnd, TokenKind::OpBitOr,
+    TokenKind::OpBitXor, TokenKind::OpBitAnd, TokenKind::OpEqual, TokenKind::OpNotEqual, TokenKind::OpLess,
+    TokenKind::OpLessEqual, TokenKind::OpGreater, TokenKind::OpGreaterEqual, TokenKind::OpShiftLeft,
+    TokenKind::OpShiftRight, TokenKind::OpAdd, TokenKind::OpSubtract, TokenKind::OpMultiply, TokenKind::OpDivide,
+    TokenKind::OpMod, TokenKind::OpNot, TokenKind::OpBitNot, TokenKind::OpIncrement, TokenKind::OpDecrement,
+];
+
+const STATEMENTS: &str = r##"program = { import | statement } ;
+(* The module's functions and globals are then "name.f" and "name.x". *)
+import = "import" identifier ";" ;
+
+statement = function | attributed | return | block | if | while | do_while | for | print
+          | putc | ";" | expression ";" ;
+
+(* Functions can only be declared at the top level, and return only used inside them. *)
+function = { attribute } "fn" identifier "(" [ identifier { "," identifier } ] ")" block ;
+(* "inline" and "noinline" are only for functions; the lints are those of -A, -W and --deny. *)
+attributed = attribute { attribute } statement ;
+attribute = "#" "[" ( "inline" | "noinline" | lint_level "(" identifier { "," identifier } ")" ) "]" ;
+lint_level = "allow" | "warn" | "deny" ;
+return = "return" [ expression ] ";" ;
+block = "{" { statement } "}" ;
+if = "if" "(" expression ")" statement [ "else" statement ] ;
+while = "while" "(" expression ")" statement ;
+do_while = "do" statement "while" "(" expression ")" ";" ;
+for = "for" "(" [ expression ] ";" [ expression ] ";" [ expression ] ")" statement ;
+print = "print" "(" expression { "," expression } ")" ";" ;
+putc = "putc" "(" expression ")" ";" ;
+"##;
+
+const OPERANDS: &str = r#"(* Only names can be called. *)
+postfix = primary { "(" [ arguments ] ")" | "[" expression "]" } ;
+arguments = expression { "," expression } ;
+primary = integer | float | char | string | "true" | "false" | identifier
+        | "(" expression ")" | "[" [ arguments ] "]" ;
+
+identifier = ? "_" or a character with XID_Start, then characters with XID_Continue, and then
+    the same again after a "." in a name qualified by its module's ? ;
+integer = ? [0-9]+ ? ;
+float = ? [0-9]+ ( "." [0-9]+ [ exponent ] | exponent ), exponent = [eE] [-+]? [0-9]+ ? ;
+char = ? "'" a character or escape "'" ? ;
+string = ? '"' characters and escapes '"' | '"""' characters and escapes '"""' | 'r"' characters '"' ? ;
+"#;
+
+fn rule_name(bp: BindingPower) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{:?}", bp).chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+fn alternatives(operators: &[&TokenKind]) -> String {
+    let quoted: Vec<String> = operators.iter().map(|op| format!("\"{}\"", op)).collect();
+    match quoted.as_slice() {
+        [single] => single.clone(),
+        _ => format!("( {} )", quoted.join(" | ")),
+    }
+}
+
+// The expression rules, one per binding power in the parser's operator table.
+fn expressions() -> String {
+    let mut out = String::from("expression = assignment ;\n");
+    let mut bp = BindingPower::Assignment;
+    while bp < BindingPower::Unary {
+        let name = rule_name(bp);
+        let operand = rule_name(bp.next());
+        if bp == BindingPower::Conditional {
+            out.push_str(&format!("{} = {} [ \"?\" expression \":\" {} ] ;\n", name, operand, name));
+        } else {
+            let operators: Vec<&TokenKind> = OPERATORS.iter()
+                .filter(|op| infix_bp(op).is_some_and(|(left, _)| left == bp))
+                .collect();
+            let right_associative = infix_bp(operators[0]).is_some_and(|(left, right)| left == right);
+            if right_associative {
+                out.push_str("(* Only names and indexing can be assigned to. *)\n");
+                out.push_str(&format!("{} = {} [ {} {} ] ;\n", name, operand, alternatives(&operators), name));
+            } else {
+                out.push_str(&format!("{} = {} {{ {} {} }} ;\n", name, operand, alternatives(&operators), operand));
+            }
+        }
+        bp = bp.next();
+    }
+    let prefix: Vec<&TokenKind> = OPERATORS.iter().filter(|op| prefix_kind(op) == SyntaxKind::Unary).collect();
+    out.push_str(&format!("unary = {} unary | postfix ;\n", alternatives(&prefix)));
+    out
+}
+
+// The grammar the parser accepts, in ISO EBNF. Lexical rules are given as special sequences.
+pub fn grammar_to_ebnf() -> String {
+    format!("(* tiny-lang *)\n\n{}\n{}\n{}", STATEMENTS, expressions(), OPERANDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expression_rules_follow_the_operator_table() {
+        let ebnf = grammar_to_ebnf();
+        assert!(ebnf.contains("assignment = conditional [ ( \"=\" | \"+=\" | \"-=\" | \"*=\" | \"/=\" | \"%=\" ) assignment ] ;\n"));
+        assert!(ebnf.contains("additive = multiplicative { ( \"+\" | \"-\" ) multiplicative } ;\n"));
+        assert!(ebnf.contains("unary = ( \"+\" | \"-\" | \"!\" | \"~\" | \"++\" | \"--\" ) unary | postfix ;\n"));
+    }
+}
@@ -1,2 +1,9 @@
+#[allow(clippy::module_inception)]
 pub mod parser;
-pub mod ast;
\ No newline at end of file
+pub mod ast;
+pub mod comments;
+pub mod cst;
+pub mod dot;
+pub mod error;
+pub mod grammar;
+pub mod printer;
@@ -1,12 +1,24 @@
-use crate::{lexer::Token, parser::ast::{Expression, Statement}};
+use crate::diagnostics::{LINT_GROUPS, LINTS, Level, LintLevels, Span, most_similar};
+use crate::intern::Symbol;
+use crate::lexer::{KEYWORDS, Token, TokenKind};
+use crate::parser::ast::{AssignmentForm, Attribute, Expression, Statement};
+use crate::parser::cst::{self, Event, SyntaxKind, SyntaxNode};
+use crate::parser::error::{ParseError, describe};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum  BindingPower {
     Default,
     Comma,
     Assignment,
-    Logical,
+    Conditional,
+    LogicalOr,
+    LogicalAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Equality,
     Relational,
+    Shift,
     Additive,
     Multiplicative,
     Unary,
@@ -16,13 +28,20 @@ pub enum  BindingPower {
 }
 
 impl BindingPower {
-    fn next(self) -> Self {
+    pub(crate) fn next(self) -> Self {
         match self {
             Self::Default        => Self::Comma,
             Self::Comma          => Self::Assignment,
-            Self::Assignment     => Self::Logical,
-            Self::Logical        => Self::Relational,
-            Self::Relational     => Self::Additive,
+            Self::Assignment     => Self::Conditional,
+            Self::Conditional    => Self::LogicalOr,
+            Self::LogicalOr      => Self::LogicalAnd,
+            Self::LogicalAnd     => Self::BitwiseOr,
+            Self::BitwiseOr      => Self::BitwiseXor,
+            Self::BitwiseXor     => Self::BitwiseAnd,
+            Self::BitwiseAnd     => Self::Equality,
+            Self::Equality       => Self::Relational,
+            Self::Relational     => Self::Shift,
+            Self::Shift          => Self::Additive,
             Self::Additive       => Self::Multiplicative,
             Self::Multiplicative => Self::Unary,
             Self::Unary          => Self::Call,
@@ -35,77 +54,659 @@ impl BindingPower {
 
 pub struct Parser {
     tokens: Vec<Token>,
-    pos: usize
+    pos: usize,
+    in_function: bool,
+    // Kept only when a concrete syntax tree is wanted too.
+    events: Option<Vec<Event>>
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self {tokens, pos: 0}
+    // Comments and whitespace are skipped if the lexer kept them.
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        tokens.retain(|token| !token.kind.is_trivia());
+        Self {tokens, pos: 0, in_function: false, events: None}
     }
 
-    fn peek(&self) -> &Token {
-        self.tokens.get(self.pos).unwrap_or(&Token::EndOfInput)
+    // Also records what's needed for `syntax_tree`.
+    pub fn with_syntax_tree(tokens: Vec<Token>) -> Self {
+        Self {events: Some(Vec::new()), ..Self::new(tokens)}
+    }
+
+    // The lossless tree of everything parsed so far, given the source the tokens came from.
+    pub fn syntax_tree(&self, source: &str) -> Option<SyntaxNode> {
+        self.events.as_ref().map(|events| cst::build(source, &self.tokens, events))
+    }
+
+    fn checkpoint(&self) -> usize {
+        self.events.as_ref().map_or(0, Vec::len)
+    }
+
+    // Makes everything recorded since `checkpoint` into a node.
+    fn wrap(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        if let Some(events) = &mut self.events {
+            events.insert(checkpoint, Event::Start(kind));
+            events.push(Event::Finish);
+        }
+    }
+
+    fn peek(&self) -> &TokenKind {
+        self.tokens.get(self.pos).map_or(&TokenKind::EndOfInput, |t| &t.kind)
+    }
+
+    fn span(&self) -> Span {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(t) => t.span,
+            None => Span::default(),
+        }
+    }
+
+    // The span of the token read last.
+    fn previous_span(&self) -> Span {
+        self.pos.checked_sub(1).and_then(|pos| self.tokens.get(pos)).map_or_else(|| self.span(), |t| t.span)
     }
 
     fn next(&mut self) -> Token {
-        let t = self.tokens[self.pos].clone();
+        let t = match self.tokens.get(self.pos) {
+            Some(t) => t.clone(),
+            None => Token { kind: TokenKind::EndOfInput, span: self.span() },
+        };
+        if self.pos < self.tokens.len() && let Some(events) = &mut self.events {
+            events.push(Event::Token);
+        }
         self.pos += 1;
         t
     }
 
-    fn expect(&mut self, expected: &Token) -> Token {
-        let t = self.next();
-        assert_eq!(&t, expected, "Expected {:?}", expected);
-        t
+    fn expect(&mut self, expected: &TokenKind) -> Result<Token, ParseError> {
+        if self.peek() != expected {
+            let message = format!("expected {}, found {}", describe(expected), describe(self.peek()));
+            return Err(ParseError::new("E0201", message, self.span()));
+        }
+        Ok(self.next())
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek() == &TokenKind::EndOfInput
     }
 
+    fn identifier(&mut self) -> Result<Symbol, ParseError> {
+        match self.peek().clone() {
+            TokenKind::Indentifier(name) => {
+                self.next();
+                Ok(name)
+            }
+            kind => Err(ParseError::new("E0201", format!("expected an identifier, found {}", describe(&kind)), self.span())),
+        }
+    }
+
+}
+
+pub(crate) fn infix_bp(op: &TokenKind) -> Option<(BindingPower, BindingPower)> {
+    match op {
+        // Assignment is right associative, so its right side doesn't bind tighter.
+        TokenKind::OpAssign | TokenKind::OpAddAssign | TokenKind::OpSubtractAssign | TokenKind::OpMultiplyAssign
+        | TokenKind::OpDivideAssign | TokenKind::OpModAssign => Some((BindingPower::Assignment, BindingPower::Assignment)),
+        TokenKind::OpOr => Some((BindingPower::LogicalOr, BindingPower::LogicalOr.next())),
+        TokenKind::OpAnd => Some((BindingPower::LogicalAnd, BindingPower::LogicalAnd.next())),
+        TokenKind::OpBitOr => Some((BindingPower::BitwiseOr, BindingPower::BitwiseOr.next())),
+        TokenKind::OpBitXor => Some((BindingPower::BitwiseXor, BindingPower::BitwiseXor.next())),
+        TokenKind::OpBitAnd => Some((BindingPower::BitwiseAnd, BindingPower::BitwiseAnd.next())),
+        TokenKind::OpEqual | TokenKind::OpNotEqual => Some((BindingPower::Equality, BindingPower::Equality.next())),
+        TokenKind::OpLess | TokenKind::OpLessEqual | TokenKind::OpGreater | TokenKind::OpGreaterEqual => Some((BindingPower::Relational, BindingPower::Relational.next())),
+        TokenKind::OpShiftLeft | TokenKind::OpShiftRight => Some((BindingPower::Shift, BindingPower::Shift.next())),
+        TokenKind::OpAdd | TokenKind::OpSubtract => Some((BindingPower::Additive, BindingPower::Additive.next())),
+        TokenKind::OpMultiply | TokenKind::OpDivide | TokenKind::OpMod => Some((BindingPower::Multiplicative, BindingPower::Multiplicative.next())),
+        _ => None,
+    }
 }
 
-fn infix_bp(op: &Token) -> Option<(BindingPower, BindingPower)> {
+// The binary operator behind a compound assignment such as `+=`.
+fn compound_operator(op: &TokenKind) -> Option<TokenKind> {
     match op {
-        Token::OpAdd | Token::OpSubtract => Some((BindingPower::Additive, BindingPower::Additive.next())),
-        Token::OpMultiply | Token::OpDivide => Some((BindingPower::Multiplicative, BindingPower::Multiplicative.next())),
+        TokenKind::OpAddAssign => Some(TokenKind::OpAdd),
+        TokenKind::OpSubtractAssign => Some(TokenKind::OpSubtract),
+        TokenKind::OpMultiplyAssign => Some(TokenKind::OpMultiply),
+        TokenKind::OpDivideAssign => Some(TokenKind::OpDivide),
+        TokenKind::OpModAssign => Some(TokenKind::OpMod),
         _ => None,
     }
 }
 
-pub fn parse_expression(parser: &mut Parser, min_bp: BindingPower) -> Expression {
-    let mut lhs = match parser.next() {
-        Token::Integer(n) => Expression::Number(n),
-        Token::String(s) => Expression::String(s),
-        Token::Indentifier(s) => Expression::Symbol(s),
-        Token::OpenParen => {
-            let inner = parse_expression(parser, BindingPower::Default);
-            parser.expect(&Token::CloseParen);
+pub(crate) fn prefix_kind(kind: &TokenKind) -> SyntaxKind {
+    match kind {
+        TokenKind::Indentifier(_) => SyntaxKind::Name,
+        TokenKind::OpenParen => SyntaxKind::Paren,
+        TokenKind::OpenBracket => SyntaxKind::Array,
+        TokenKind::OpSubtract | TokenKind::OpAdd | TokenKind::OpNot | TokenKind::OpBitNot
+        | TokenKind::OpIncrement | TokenKind::OpDecrement => SyntaxKind::Unary,
+        _ => SyntaxKind::Literal,
+    }
+}
+
+pub fn parse_expression(parser: &mut Parser, min_bp: BindingPower) -> Result<Expression, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let token = parser.next();
+    let kind = prefix_kind(&token.kind);
+    let mut lhs = match token.kind {
+        TokenKind::Integer(n) => Expression::Number(n, token.span),
+        TokenKind::Char(c) => Expression::Char(c, token.span),
+        TokenKind::Float(x) => Expression::Float(x, token.span),
+        TokenKind::KeywordTrue => Expression::Boolean(true, token.span),
+        TokenKind::KeywordFalse => Expression::Boolean(false, token.span),
+        TokenKind::String(s) => Expression::String(s, token.span),
+        TokenKind::Indentifier(s) => Expression::Symbol(s, token.span),
+        TokenKind::OpenParen => {
+            let inner = parse_expression(parser, BindingPower::Default)?;
+            parser.expect(&TokenKind::CloseParen)?;
             inner
         }
-        t => panic!("Unexpected token in prefix position: {:?}", t),
+        TokenKind::OpenBracket => {
+            let (elements, close) = parse_arguments(parser, &TokenKind::CloseBracket)?;
+            Expression::Array { elements, span: token.span.to(close) }
+        }
+        op @ (TokenKind::OpSubtract | TokenKind::OpAdd | TokenKind::OpNot | TokenKind::OpBitNot) => {
+            let operand = parse_expression(parser, BindingPower::Unary)?;
+            Expression::Unary { operator: op, operand: Box::new(operand), span: token.span }
+        }
+        // `++x` is sugar for `x = x + 1`.
+        op @ (TokenKind::OpIncrement | TokenKind::OpDecrement) => {
+            let operand = parse_expression(parser, BindingPower::Unary)?;
+            if !matches!(operand, Expression::Symbol(..) | Expression::Index {..}) {
+                return Err(ParseError::new("E0203", format!("invalid operand for `{}`", op), token.span));
+            }
+            let operator = if op == TokenKind::OpIncrement { TokenKind::OpAdd } else { TokenKind::OpSubtract };
+            let value = Expression::Binary {
+                left: Box::new(operand.clone()),
+                operator,
+                right: Box::new(Expression::Number(1, token.span)),
+                span: token.span,
+            };
+            Expression::Assignment { assignee: Box::new(operand), value: Box::new(value), form: AssignmentForm::Prefix, span: token.span }
+        }
+        kind => {
+            let message = format!("expected an expression, found {}", describe(&kind));
+            return Err(ParseError::new("E0202", message, token.span));
+        }
     };
+    parser.wrap(checkpoint, kind);
 
     loop {
         let op = parser.peek().clone();
 
+        if op == TokenKind::OpenParen {
+            if BindingPower::Call < min_bp {
+                break;
+            }
+            let open_span = parser.next().span;
+            let (callee, span) = match lhs {
+                Expression::Symbol(name, span) => (name, span),
+                _ => return Err(ParseError::new("E0204", "only named functions can be called", open_span)),
+            };
+            let (arguments, _) = parse_arguments(parser, &TokenKind::CloseParen)?;
+            lhs = Expression::Call { callee, arguments, span };
+            parser.wrap(checkpoint, SyntaxKind::Call);
+            continue;
+        }
+
+        // `c ? a : b` is right associative; its middle operand is parsed as if parenthesized.
+        if op == TokenKind::Question {
+            if BindingPower::Conditional < min_bp {
+                break;
+            }
+            let span = parser.next().span;
+            let then_value = parse_expression(parser, BindingPower::Default)?;
+            parser.expect(&TokenKind::Colon)?;
+            let else_value = parse_expression(parser, BindingPower::Conditional)?;
+            lhs = Expression::Conditional {
+                condition: Box::new(lhs),
+                then_value: Box::new(then_value),
+                else_value: Box::new(else_value),
+                span,
+            };
+            parser.wrap(checkpoint, SyntaxKind::Conditional);
+            continue;
+        }
+
+        if op == TokenKind::OpenBracket {
+            if BindingPower::Call < min_bp {
+                break;
+            }
+            let open = parser.next().span;
+            let index = parse_expression(parser, BindingPower::Default)?;
+            let close = parser.expect(&TokenKind::CloseBracket)?.span;
+            lhs = Expression::Index { target: Box::new(lhs), index: Box::new(index), span: open.to(close) };
+            parser.wrap(checkpoint, SyntaxKind::Index);
+            continue;
+        }
+
         let (left_bp, right_bp) = match infix_bp(&op) {
             Some(bp) => bp,
             None => break,
         };
 
-        if left_bp <= min_bp {
+        if left_bp < min_bp {
             break;
         }
 
+        let op_span = parser.next().span;
+
+        let rhs = parse_expression(parser, right_bp)?;
+        let kind = if op == TokenKind::OpAssign || compound_operator(&op).is_some() { SyntaxKind::Assignment } else { SyntaxKind::Binary };
+        parser.wrap(checkpoint, kind);
+
+        lhs = match op {
+            TokenKind::OpAssign => {
+                if !matches!(lhs, Expression::Symbol(..) | Expression::Index {..}) {
+                    return Err(ParseError::new("E0203", "invalid assignment target", op_span));
+                }
+                Expression::Assignment { assignee: Box::new(lhs), value: Box::new(rhs), form: AssignmentForm::Plain, span: op_span }
+            }
+            // `x += e` is sugar for `x = x + e`.
+            _ if let Some(operator) = compound_operator(&op) => {
+                if !matches!(lhs, Expression::Symbol(..) | Expression::Index {..}) {
+                    return Err(ParseError::new("E0203", "invalid assignment target", op_span));
+                }
+                let value = Expression::Binary { left: Box::new(lhs.clone()), operator, right: Box::new(rhs), span: op_span };
+                Expression::Assignment { assignee: Box::new(lhs), value: Box::new(value), form: AssignmentForm::Compound, span: op_span }
+            }
+            _ => Expression::Binary { left: Box::new(lhs), operator: op, right: Box::new(rhs), span: op_span }
+        }
+    }
+
+    Ok(lhs)
+}
+
+// A comma separated list of expressions after its opening paren or bracket, and the closing one's span.
+fn parse_arguments(parser: &mut Parser, close: &TokenKind) -> Result<(Vec<Expression>, Span), ParseError> {
+    let mut arguments = Vec::new();
+    if parser.peek() != close {
+        arguments.push(parse_expression(parser, BindingPower::Comma)?);
+        while parser.peek() == &TokenKind::Comma {
+            parser.next();
+            arguments.push(parse_expression(parser, BindingPower::Comma)?);
+        }
+    }
+    let close = parser.expect(close)?;
+    Ok((arguments, close.span))
+}
+
+fn parse_paren_expression(parser: &mut Parser) -> Result<Expression, ParseError> {
+    parser.expect(&TokenKind::OpenParen)?;
+    let expr = parse_expression(parser, BindingPower::Default)?;
+    parser.expect(&TokenKind::CloseParen)?;
+    Ok(expr)
+}
+
+fn parse_block(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::OpenBrace)?;
+    let mut body = Vec::new();
+    while parser.peek() != &TokenKind::CloseBrace && !parser.at_end() {
+        body.push(parse_statement(parser)?);
+    }
+    parser.expect(&TokenKind::CloseBrace)?;
+    parser.wrap(checkpoint, SyntaxKind::Block);
+    Ok(Statement::Block { body, span: start.to(parser.previous_span()) })
+}
+
+fn parse_if(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordIf)?;
+    let condition = parse_paren_expression(parser)?;
+    let then_branch = Box::new(parse_statement(parser)?);
+    let else_branch = if parser.peek() == &TokenKind::KeywordElse {
+        parser.next();
+        Some(Box::new(parse_statement(parser)?))
+    } else {
+        None
+    };
+    parser.wrap(checkpoint, SyntaxKind::If);
+    Ok(Statement::If { condition, then_branch, else_branch, span: start.to(parser.previous_span()) })
+}
+
+fn parse_while(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordWhile)?;
+    let condition = parse_paren_expression(parser)?;
+    let body = Box::new(parse_statement(parser)?);
+    parser.wrap(checkpoint, SyntaxKind::While);
+    Ok(Statement::While { condition, body, span: start.to(parser.previous_span()) })
+}
+
+fn parse_do_while(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordDo)?;
+    let body = Box::new(parse_statement(parser)?);
+    parser.expect(&TokenKind::KeywordWhile)?;
+    let condition = parse_paren_expression(parser)?;
+    parser.expect(&TokenKind::Semicolon)?;
+    parser.wrap(checkpoint, SyntaxKind::DoWhile);
+    Ok(Statement::DoWhile { body, condition, span: start.to(parser.previous_span()) })
+}
+
+// Each clause of a `for` header may be left empty.
+fn parse_for_clause(parser: &mut Parser, terminator: &TokenKind) -> Result<Option<Expression>, ParseError> {
+    let expr = if parser.peek() == terminator {
+        None
+    } else {
+        Some(parse_expression(parser, BindingPower::Default)?)
+    };
+    parser.expect(terminator)?;
+    Ok(expr)
+}
+
+fn parse_for(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordFor)?;
+    parser.expect(&TokenKind::OpenParen)?;
+    let initializer = parse_for_clause(parser, &TokenKind::Semicolon)?;
+    let condition = parse_for_clause(parser, &TokenKind::Semicolon)?;
+    let step = parse_for_clause(parser, &TokenKind::CloseParen)?;
+    let body = Box::new(parse_statement(parser)?);
+    parser.wrap(checkpoint, SyntaxKind::For);
+    Ok(Statement::For { initializer, condition, step, body, span: start.to(parser.previous_span()) })
+}
+
+fn parse_print(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordPrint)?;
+    parser.expect(&TokenKind::OpenParen)?;
+    let mut arguments = vec![parse_expression(parser, BindingPower::Comma)?];
+    while parser.peek() == &TokenKind::Comma {
+        parser.next();
+        arguments.push(parse_expression(parser, BindingPower::Comma)?);
+    }
+    parser.expect(&TokenKind::CloseParen)?;
+    parser.expect(&TokenKind::Semicolon)?;
+    parser.wrap(checkpoint, SyntaxKind::Print);
+    Ok(Statement::Print { arguments, span: start.to(parser.previous_span()) })
+}
+
+fn parse_putc(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::KeywordPutc)?;
+    let expression = parse_paren_expression(parser)?;
+    parser.expect(&TokenKind::Semicolon)?;
+    parser.wrap(checkpoint, SyntaxKind::Putc);
+    Ok(Statement::Putc { expression, span: start.to(parser.previous_span()) })
+}
+
+// `#[name]`, or `#[name(argument, ...)]` for those that take arguments.
+fn parse_attribute(parser: &mut Parser) -> Result<(Attribute, Span), ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    parser.expect(&TokenKind::Hash)?;
+    parser.expect(&TokenKind::OpenBracket)?;
+    let name_span = parser.span();
+    let name = parser.identifier()?;
+    let mut arguments = Vec::new();
+    let parenthesized = parser.peek() == &TokenKind::OpenParen;
+    if parenthesized {
         parser.next();
+        while parser.peek() != &TokenKind::CloseParen {
+            arguments.push((parser.span(), parser.identifier()?));
+            if parser.peek() != &TokenKind::Comma {
+                break;
+            }
+            parser.next();
+        }
+        parser.expect(&TokenKind::CloseParen)?;
+    }
+    parser.expect(&TokenKind::CloseBracket)?;
+    parser.wrap(checkpoint, SyntaxKind::Attribute);
+    let span = start.to(parser.previous_span());
+    let attribute = match name.as_str() {
+        "inline" | "noinline" if parenthesized => {
+            return Err(ParseError::new("E0209", format!("`{}` takes no arguments", name), span));
+        }
+        "inline" => Attribute::Inline,
+        "noinline" => Attribute::NoInline,
+        "allow" | "warn" | "deny" => {
+            if arguments.is_empty() {
+                let message = format!("`{}` takes the lints it's for, as in `#[{}(unused)]`", name, name);
+                return Err(ParseError::new("E0209", message, span));
+            }
+            if let Some((span, lint)) = arguments.iter().find(|(_, lint)| !LintLevels::is_known(lint.as_str())) {
+                let names = LINTS.iter().map(|lint| lint.name).chain(LINT_GROUPS.iter().map(|(group, _)| *group));
+                let message = match most_similar(lint.as_str(), names, |name| name) {
+                    Some(similar) => format!("unknown lint `{}`; did you mean `{}`?", lint, similar),
+                    None => format!("unknown lint `{}`", lint),
+                };
+                return Err(ParseError::new("E0209", message, *span));
+            }
+            let level = match name.as_str() {
+                "allow" => Level::Allow,
+                "warn" => Level::Warn,
+                _ => Level::Deny,
+            };
+            Attribute::Lint(level, arguments.into_iter().map(|(_, lint)| lint).collect())
+        }
+        _ => {
+            let message = match most_similar(name.as_str(), Attribute::NAMES.iter().copied(), |name| name) {
+                Some(similar) => format!("unknown attribute `{}`; did you mean `{}`?", name, similar),
+                None => format!("unknown attribute `{}`", name),
+            };
+            return Err(ParseError::new("E0207", message, name_span));
+        }
+    };
+    Ok((attribute, span))
+}
+
+fn parse_attributes(parser: &mut Parser) -> Result<Vec<(Attribute, Span)>, ParseError> {
+    let mut attributes = Vec::new();
+    while parser.peek() == &TokenKind::Hash {
+        attributes.push(parse_attribute(parser)?);
+    }
+    Ok(attributes)
+}
+
+// A statement with attributes before it, or a function, which keeps its own.
+fn parse_attributed(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let start = parser.span();
+    let attributes = parse_attributes(parser)?;
+    if parser.peek() == &TokenKind::KeywordFn {
+        return parse_function(parser, checkpoint, attributes);
+    }
+    if let Some((attribute, span)) = attributes.iter().find(|(attribute, _)| attribute.is_function_only()) {
+        let message = format!("`#[{}]` can only be written before a function", attribute.name());
+        return Err(ParseError::new("E0209", message, *span));
+    }
+    let statement = parse_statement(parser)?;
+    parser.wrap(checkpoint, SyntaxKind::Attributed);
+    let attributes = attributes.into_iter().map(|(attribute, _)| attribute).collect();
+    Ok(Statement::Attributed { attributes, statement: Box::new(statement), span: start.to(parser.previous_span()) })
+}
+
+// The function at `fn`, after any attributes, which started at `checkpoint`.
+fn parse_function(parser: &mut Parser, checkpoint: usize, with: Vec<(Attribute, Span)>) -> Result<Statement, ParseError> {
+    let span = parser.span();
+    if parser.in_function {
+        return Err(ParseError::new("E0205", "functions can only be declared at the top level", span));
+    }
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for (attribute, span) in with {
+        let conflicting = match attribute {
+            Attribute::Inline => Some(Attribute::NoInline),
+            Attribute::NoInline => Some(Attribute::Inline),
+            Attribute::Lint(..) => None,
+        };
+        if let Some(conflicting) = conflicting.filter(|conflicting| attributes.contains(conflicting)) {
+            let message = format!("`{}` conflicts with `{}`", attribute.name(), conflicting.name());
+            return Err(ParseError::new("E0208", message, span));
+        }
+        if !attributes.contains(&attribute) {
+            attributes.push(attribute);
+        }
+    }
+    parser.expect(&TokenKind::KeywordFn)?;
+    let name_span = parser.span();
+    let name = parser.identifier()?;
 
-        let rhs = parse_expression(parser, right_bp);
+    let parameters_checkpoint = parser.checkpoint();
+    parser.expect(&TokenKind::OpenParen)?;
+    let mut parameters = Vec::new();
+    let mut parameter_spans = Vec::new();
+    if parser.peek() != &TokenKind::CloseParen {
+        parameter_spans.push(parser.span());
+        parameters.push(parser.identifier()?);
+        while parser.peek() == &TokenKind::Comma {
+            parser.next();
+            parameter_spans.push(parser.span());
+            parameters.push(parser.identifier()?);
+        }
+    }
+    parser.expect(&TokenKind::CloseParen)?;
+    parser.wrap(parameters_checkpoint, SyntaxKind::ParameterList);
+
+    parser.in_function = true;
+    let body = parse_block(parser);
+    parser.in_function = false;
+    let body = match body? {
+        Statement::Block {body, ..} => body,
+        _ => unreachable!(),
+    };
+    parser.wrap(checkpoint, SyntaxKind::Function);
+    Ok(Statement::Function { name, parameters, parameter_spans, body, attributes, span: name_span })
+}
+
+fn parse_return(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let span = parser.span();
+    if !parser.in_function {
+        return Err(ParseError::new("E0206", "`return` outside of a function", span));
+    }
+    parser.expect(&TokenKind::KeywordReturn)?;
+    let value = if parser.peek() == &TokenKind::Semicolon {
+        None
+    } else {
+        Some(parse_expression(parser, BindingPower::Default)?)
+    };
+    parser.expect(&TokenKind::Semicolon)?;
+    parser.wrap(checkpoint, SyntaxKind::Return);
+    Ok(Statement::Return { value, span: span.to(parser.previous_span()) })
+}
+
+// `import name;`, which `parse_program` only takes before a statement at the top level.
+fn parse_import(parser: &mut Parser) -> Result<Statement, ParseError> {
+    let checkpoint = parser.checkpoint();
+    let span = parser.expect(&TokenKind::KeywordImport)?.span;
+    let name_span = parser.span();
+    let module = parser.identifier()?;
+    if module.as_str().contains('.') {
+        return Err(ParseError::new("E0201", format!("expected a module name, found `{}`", module), name_span));
+    }
+    parser.expect(&TokenKind::Semicolon)?;
+    parser.wrap(checkpoint, SyntaxKind::Import);
+    Ok(Statement::Import { module, span: span.to(parser.previous_span()) })
+}
+
+pub fn parse_statement(parser: &mut Parser) -> Result<Statement, ParseError> {
+    match parser.peek() {
+        TokenKind::KeywordImport => Err(ParseError::new("E0210", "modules can only be imported at the top level", parser.span())),
+        TokenKind::KeywordFn => {
+            let checkpoint = parser.checkpoint();
+            parse_function(parser, checkpoint, Vec::new())
+        }
+        TokenKind::Hash => parse_attributed(parser),
+        TokenKind::KeywordReturn => parse_return(parser),
+        TokenKind::OpenBrace => parse_block(parser),
+        TokenKind::KeywordIf => parse_if(parser),
+        TokenKind::KeywordWhile => parse_while(parser),
+        TokenKind::KeywordFor => parse_for(parser),
+        TokenKind::KeywordDo => parse_do_while(parser),
+        TokenKind::KeywordPrint => parse_print(parser),
+        TokenKind::KeywordPutc => parse_putc(parser),
+        TokenKind::Semicolon => {
+            let checkpoint = parser.checkpoint();
+            let span = parser.next().span;
+            parser.wrap(checkpoint, SyntaxKind::EmptyStatement);
+            Ok(Statement::Block { body: Vec::new(), span })
+        }
+        _ => {
+            let checkpoint = parser.checkpoint();
+            let start = parser.span();
+            let first = parser.peek().clone();
+            let second = parser.tokens.get(parser.pos + 1).map(|t| t.kind.clone());
+            let statement = parse_expression(parser, BindingPower::Default)
+                .and_then(|expr| Ok((expr, parser.expect(&TokenKind::Semicolon)?.span)));
+            let (expr, end) = statement.map_err(|error| suggest_keyword(error, &first, second.as_ref(), start))?;
+            parser.wrap(checkpoint, SyntaxKind::ExpressionStatement);
+            Ok(Statement::Expression { expression: expr, span: start.to(end) })
+        }
+    }
+}
+
+// A misspelt keyword such as `whlie` starts an expression statement, which then has a syntax
+// error further on; point back at it with the keyword it looks like. A name that's followed by
+// an operator, as in `id = 1 +;`, was meant as a name.
+fn suggest_keyword(error: ParseError, first: &TokenKind, second: Option<&TokenKind>, span: Span) -> ParseError {
+    let TokenKind::Indentifier(name) = first else {
+        return error;
+    };
+    if second.is_some_and(|kind| infix_bp(kind).is_some() || matches!(kind, TokenKind::OpenBracket | TokenKind::OpIncrement | TokenKind::OpDecrement)) {
+        return error;
+    }
+    // `true` and `false` are expressions, so sema suggests them instead.
+    let keywords = KEYWORDS.iter().copied().filter(|keyword| !matches!(*keyword, "true" | "false"));
+    match most_similar(name.as_str(), keywords, |keyword| keyword) {
+        Some(keyword) if error.span != span => {
+            error.with_note(format!("`{}` isn't a keyword; did you mean `{}`?", name, keyword), span)
+        }
+        _ => error,
+    }
+}
 
-        lhs = Expression::Binary { left: Box::new(lhs), operator: op, right: Box::new(rhs) }
+pub fn parse_program(parser: &mut Parser) -> Result<Vec<Statement>, ParseError> {
+    let mut program = Vec::new();
+    while !parser.at_end() {
+        let statement = if parser.peek() == &TokenKind::KeywordImport { parse_import(parser) } else { parse_statement(parser) };
+        program.push(statement?);
     }
+    Ok(program)
+}
 
-    lhs
+// Input that's one expression, such as `1 + 2 * 3`, and nothing else.
+pub fn parse_lone_expression(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let expression = parse_expression(parser, BindingPower::Default)?;
+    parser.expect(&TokenKind::EndOfInput)?;
+    Ok(expression)
 }
 
-pub fn parse_statement(parser: &mut Parser) -> Statement {
-    let expr = parse_expression(parser, BindingPower::Default);
-    parser.expect(&Token::Semicolon);
-    Statement::Expression { expression: expr }
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+    use crate::parser::printer::print_program;
+
+    fn error(source: &str) -> (&'static str, String, String) {
+        let error = parse_program(&mut Parser::new(tokenize(source).0)).unwrap_err();
+        (error.code, error.message, source[error.span.start..error.span.end].to_string())
+    }
+
+    #[test]
+    fn binds_operators_by_precedence_and_associativity() {
+        let source = "x = 1 + 2 * 3 - -4 << 1;\ny = a ? b : c ? d : e;\nz = w = !f(1, g[2]) && h || i;\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        assert_eq!(print_program(&program), "\
+(= x (<< (- (+ 1 (* 2 3)) (- 4)) 1))
+(= y (? a b (? c d e)))
+(= z (= w (|| (&& (! (call f 1 (index g 2))) h) i)))
+");
+    }
+
+    #[test]
+    fn reports_what_went_wrong_and_where() {
+        assert_eq!(error("print(1) x = 2;"), ("E0201", "expected `;`, found `x`".to_string(), "x".to_string()));
+        assert_eq!(error("return 1;"), ("E0206", "`return` outside of a function".to_string(), "return".to_string()));
+        assert_eq!(error("1 = 2;").0, "E0203");
+        assert_eq!(error("fn f() { fn g() {} }").0, "E0205");
+    }
+}
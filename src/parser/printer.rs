@@ -0,0 +1,147 @@
+use crate::lexer::{quote_char, quote_string};
+use crate::parser::ast::{Expression, Statement};
+
+const INDENT: &str = "  ";
+
+pub fn print_expression(expr: &Expression) -> String {
+    match expr {
+        Expression::Number(n, _) => n.to_string(),
+        Expression::Char(c, _) => quote_char(*c),
+        Expression::Float(x, _) => format!("{:?}", x),
+        Expression::Boolean(b, _) => b.to_string(),
+        Expression::String(s, _) => quote_string(s),
+        Expression::Symbol(name, _) => name.to_string(),
+        Expression::Unary {operator, operand, ..} => format!("({} {})", operator, print_expression(operand)),
+        Expression::Binary {left, operator, right, ..} => {
+            format!("({} {} {})", operator, print_expression(left), print_expression(right))
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            format!("(= {} {})", print_expression(assignee), print_expression(value))
+        }
+        Expression::Call {callee, arguments, ..} => {
+            let mut out = format!("(call {}", callee);
+            for arg in arguments {
+                out.push(' ');
+                out.push_str(&print_expression(arg));
+            }
+            out.push(')');
+            out
+        }
+        Expression::Array {elements, ..} => {
+            let elements: Vec<String> = elements.iter().map(print_expression).collect();
+            format!("[{}]", elements.join(" "))
+        }
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            format!("(? {} {} {})", print_expression(condition), print_expression(then_value), print_expression(else_value))
+        }
+        Expression::Index {target, index, ..} => format!("(index {} {})", print_expression(target), print_expression(index)),
+    }
+}
+
+fn child(out: &mut String, stmt: &Statement, depth: usize) {
+    out.push('\n');
+    out.push_str(&INDENT.repeat(depth));
+    statement(out, stmt, depth);
+}
+
+fn statement(out: &mut String, stmt: &Statement, depth: usize) {
+    match stmt {
+        Statement::Block {body, ..} => {
+            out.push_str("(block");
+            for stmt in body {
+                child(out, stmt, depth + 1);
+            }
+            out.push(')');
+        }
+        Statement::Expression {expression, ..} => out.push_str(&print_expression(expression)),
+        Statement::Print {arguments, ..} => {
+            out.push_str("(print");
+            for arg in arguments {
+                out.push(' ');
+                out.push_str(&print_expression(arg));
+            }
+            out.push(')');
+        }
+        Statement::Putc {expression, ..} => {
+            out.push_str(&format!("(putc {})", print_expression(expression)));
+        }
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            out.push_str(&format!("(if {}", print_expression(condition)));
+            child(out, then_branch, depth + 1);
+            if let Some(else_branch) = else_branch {
+                child(out, else_branch, depth + 1);
+            }
+            out.push(')');
+        }
+        Statement::While {condition, body, ..} => {
+            out.push_str(&format!("(while {}", print_expression(condition)));
+            child(out, body, depth + 1);
+            out.push(')');
+        }
+        Statement::DoWhile {body, condition, ..} => {
+            out.push_str(&format!("(do-while {}", print_expression(condition)));
+            child(out, body, depth + 1);
+            out.push(')');
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            let clause = |expr: &Option<Expression>| expr.as_ref().map_or("()".to_string(), print_expression);
+            out.push_str(&format!("(for {} {} {}", clause(initializer), clause(condition), clause(step)));
+            child(out, body, depth + 1);
+            out.push(')');
+        }
+        Statement::Function {name, parameters, body, attributes, ..} => {
+            out.push_str(&format!("(fn {} ({})", name, parameters.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(" ")));
+            for attribute in attributes {
+                out.push_str(&format!(" {}", attribute));
+            }
+            for stmt in body {
+                child(out, stmt, depth + 1);
+            }
+            out.push(')');
+        }
+        Statement::Return {value: Some(value), ..} => out.push_str(&format!("(return {})", print_expression(value))),
+        Statement::Return {value: None, ..} => out.push_str("(return)"),
+        Statement::Import {module, ..} => out.push_str(&format!("(import {})", module)),
+        Statement::Attributed {attributes, statement, ..} => {
+            out.push_str("(attributed");
+            for attribute in attributes {
+                out.push_str(&format!(" {}", attribute));
+            }
+            child(out, statement, depth + 1);
+            out.push(')');
+        }
+    }
+}
+
+// One top-level statement per line, S-expression style.
+pub fn print_program(program: &[Statement]) -> String {
+    let mut out = String::new();
+    for stmt in program {
+        statement(&mut out, stmt, 0);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn prints_statements_as_s_expressions() {
+        let source = "fn k(a, b) { while (a < b) { a = a + 1; } return a; }\nif (k(1, 2)) print(\"yes\\n\"); else putc('\\n');\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        assert_eq!(print_program(&program), "\
+(fn k (a b)
+  (while (< a b)
+    (block
+      (= a (+ a 1))))
+  (return a))
+(if (call k 1 2)
+  (print \"yes\\n\")
+  (putc '\\n'))
+");
+    }
+}
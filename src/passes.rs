@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::codegen::{Bytecode, optimize_bytecode};
+use crate::ir::{Module, hoist_loop_invariants, simplify_module};
+use crate::opt::{eliminate_dead_code, fold_constants, inline_functions};
+use crate::parser::ast::Statement;
+
+// What a pass works on. Syntax tree passes run before anything else; the SSA and bytecode
+// passes run only when those forms are built.
+#[derive(Clone, Copy)]
+pub enum Transform {
+    Ast(fn(&mut Vec<Statement>)),
+    Ssa(fn(&mut Module)),
+    Bytecode(fn(&mut Bytecode))
+}
+
+pub struct Pass {
+    pub name: &'static str,
+    pub transform: Transform,
+    pub description: &'static str
+}
+
+pub const PASSES: &[Pass] = &[
+    Pass { name: "fold", transform: Transform::Ast(|program| fold_constants(program)), description: "evaluates constant expressions" },
+    Pass { name: "dce", transform: Transform::Ast(|program| *program = eliminate_dead_code(std::mem::take(program))), description: "removes code that can never run" },
+    Pass { name: "inline", transform: Transform::Ast(|program| inline_functions(program)), description: "inlines calls to small functions" },
+    Pass { name: "simplify", transform: Transform::Ssa(simplify_module), description: "folds constants and simplifies integer arithmetic in the SSA form" },
+    Pass { name: "licm", transform: Transform::Ssa(hoist_loop_invariants), description: "hoists loop-invariant values out of loops in the SSA form" },
+    Pass { name: "peephole", transform: Transform::Bytecode(|bytecode| { optimize_bytecode(bytecode); }), description: "rewrites short sequences of bytecode" },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    O0,
+    O1,
+    #[default]
+    O2
+}
+
+impl OptLevel {
+    // The level a `-O` flag names, such as `-O1`.
+    pub fn parse(flag: &str) -> Option<Self> {
+        match flag {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" => Some(OptLevel::O1),
+            "-O2" => Some(OptLevel::O2),
+            _ => None,
+        }
+    }
+
+    // The passes the level runs, in order. Constants are folded again after inlining,
+    // which often leaves calls with literal arguments foldable.
+    pub fn passes(self) -> &'static [&'static str] {
+        match self {
+            OptLevel::O0 => &[],
+            OptLevel::O1 => &["fold", "dce", "simplify", "peephole"],
+            OptLevel::O2 => &["fold", "dce", "inline", "fold", "simplify", "licm", "peephole"],
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("-O{}", *self as u8))
+    }
+}
+
+pub fn pass(name: &str) -> Option<&'static Pass> {
+    PASSES.iter().find(|pass| pass.name == name)
+}
+
+// Runs registered passes in the order they were registered, each on the form of the
+// program it works on.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<&'static Pass>,
+    // How many of the passes to run, if not all of them.
+    limit: Option<usize>,
+    // Whether to say on stderr which passes run.
+    print: bool
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_level(level: OptLevel) -> Self {
+        let mut manager = Self::new();
+        for name in level.passes() {
+            manager.register(pass(name).unwrap());
+        }
+        manager
+    }
+
+    pub fn register(&mut self, pass: &'static Pass) {
+        self.passes.push(pass);
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = &'static Pass> + '_ {
+        self.passes.iter().copied()
+    }
+
+    // Runs no passes after the first one called `name`, of any form. Passes that aren't
+    // registered are an error.
+    pub fn stop_after(&mut self, name: &str) -> Result<(), String> {
+        if pass(name).is_none() {
+            return Err(format!("unknown pass `{}`", name));
+        }
+        match self.passes.iter().position(|pass| pass.name == name) {
+            Some(index) => {
+                self.limit = Some(index + 1);
+                Ok(())
+            }
+            None => Err(format!("pass `{}` isn't run at this optimization level", name)),
+        }
+    }
+
+    pub fn print_passes(&mut self, print: bool) {
+        self.print = print;
+    }
+
+    fn each(&self, mut run: impl FnMut(&Transform) -> bool) {
+        let limit = self.limit.unwrap_or(self.passes.len());
+        for pass in &self.passes[..limit] {
+            if run(&pass.transform) && self.print {
+                eprintln!("pass: {}", pass.name);
+            }
+        }
+    }
+
+    pub fn run_ast(&self, mut program: Vec<Statement>) -> Vec<Statement> {
+        self.each(|transform| match transform {
+            Transform::Ast(run) => {
+                run(&mut program);
+                true
+            }
+            _ => false,
+        });
+        program
+    }
+
+    pub fn run_ssa(&self, module: &mut Module) {
+        self.each(|transform| match transform {
+            Transform::Ssa(run) => {
+                run(module);
+                true
+            }
+            _ => false,
+        });
+    }
+
+    pub fn run_bytecode(&self, bytecode: &mut Bytecode) {
+        self.each(|transform| match transform {
+            Transform::Bytecode(run) => {
+                run(bytecode);
+                true
+            }
+            _ => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::printer::print_program;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn optimized(manager: &PassManager, source: &str) -> String {
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        print_program(&manager.run_ast(program))
+    }
+
+    #[test]
+    fn stops_after_the_pass_it_was_told_to() {
+        let source = "fn one() { return 1; }\nprint(one() + 2 * 3);\n";
+        let mut manager = PassManager::with_level(OptLevel::O2);
+        manager.stop_after("fold").unwrap();
+        assert_eq!(optimized(&manager, source), "\
+(fn one ()
+  (return 1))
+(print (+ (call one) 6))
+");
+        assert_eq!(optimized(&PassManager::with_level(OptLevel::O2), source), "\
+(fn one ()
+  (return 1))
+(print 7)
+");
+        assert_eq!(optimized(&PassManager::with_level(OptLevel::O0), source), "\
+(fn one ()
+  (return 1))
+(print (+ (call one) (* 2 3)))
+");
+        let mut manager = PassManager::with_level(OptLevel::O1);
+        assert_eq!(manager.stop_after("inline"), Err("pass `inline` isn't run at this optimization level".to_string()));
+        assert_eq!(manager.stop_after("unroll"), Err("unknown pass `unroll`".to_string()));
+    }
+}
@@ -0,0 +1,164 @@
+// Conditional compilation: the tokens between `#if NAME` and its `#else` or `#endif` are
+// only kept if NAME is defined, as with `-D NAME`, and those between `#else` and `#endif`
+// only if it isn't. They nest. The `#include`s that are kept are left for `include`.
+
+use crate::diagnostics::{Span, most_similar};
+use crate::lexer::{Token, TokenKind};
+
+use super::PreprocessError;
+
+pub const DIRECTIVES: &[&str] = &["if", "else", "endif", "include"];
+
+// An `#if` whose `#endif` hasn't been reached.
+struct Open {
+    span: Span,
+    // Whether its tokens are kept: those of the `#if` part, then after `#else` the others.
+    taking: bool,
+    // The `#else`, once there's been one.
+    otherwise: Option<Span>
+}
+
+// The directive whose `#` is at `i`, if that's one: its name and the index of the token
+// that's its name. A `#` followed by `[` starts an attribute instead.
+fn directive<S>(tokens: &[Token<S>], i: usize) -> Option<(String, usize)> {
+    let next = next_code(tokens, i)?;
+    let name = match &tokens[next].kind {
+        TokenKind::KeywordIf => "if".to_string(),
+        TokenKind::KeywordElse => "else".to_string(),
+        TokenKind::Indentifier(name) => name.to_string(),
+        _ => return None,
+    };
+    Some((name, next))
+}
+
+// The index of the first token after `i` that isn't trivia.
+fn next_code<S>(tokens: &[Token<S>], i: usize) -> Option<usize> {
+    (i + 1..tokens.len()).find(|&j| !tokens[j].kind.is_trivia())
+}
+
+// Whether there's a line break between the end of `before` and `offset`.
+fn breaks(source: &str, before: usize, offset: usize) -> bool {
+    source[before..offset].contains('\n')
+}
+
+// Whether the file has any directives, which `fmt` can't keep.
+pub fn has_directives<S>(tokens: &[Token<S>]) -> bool {
+    (0..tokens.len()).any(|i| matches!(tokens[i].kind, TokenKind::Hash) && directive(tokens, i).is_some())
+}
+
+// The tokens of `source` the definitions in `defined` keep, without the directives.
+pub fn preprocess<S>(source: &str, tokens: Vec<Token<S>>, defined: &[String]) -> Result<Vec<Token<S>>, PreprocessError> {
+    let mut kept = vec![false; tokens.len()];
+    let mut open: Vec<Open> = Vec::new();
+    // Where the last token that isn't trivia ended.
+    let mut previous = 0;
+    let mut skip_to = None;
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(last) = skip_to {
+            if i <= last {
+                continue;
+            }
+            skip_to = None;
+        }
+        let Some((name, mut last)) = (matches!(token.kind, TokenKind::Hash)).then(|| directive(&tokens, i)).flatten() else {
+            if !token.kind.is_trivia() && !matches!(token.kind, TokenKind::EndOfInput) {
+                previous = token.span.end;
+            }
+            if open.iter().all(|open| open.taking) || matches!(token.kind, TokenKind::EndOfInput) {
+                kept[i] = true;
+            }
+            continue;
+        };
+        let span = token.span.to(tokens[last].span);
+        if previous > 0 && !breaks(source, previous, token.span.start) {
+            return Err(PreprocessError::new("E0104", format!("`#{}` must start a line of its own", name), span));
+        }
+        match name.as_str() {
+            "if" => {
+                let argument = next_code(&tokens, last).filter(|&j| !breaks(source, tokens[last].span.end, tokens[j].span.start));
+                let Some(TokenKind::Indentifier(tested)) = argument.map(|j| &tokens[j].kind) else {
+                    return Err(PreprocessError::new("E0104", "`#if` takes the name to test, as in `#if DEBUG`", span));
+                };
+                let taking = defined.iter().any(|name| name == tested.as_str());
+                last = argument.unwrap();
+                open.push(Open { span: token.span.to(tokens[last].span), taking, otherwise: None });
+            }
+            "else" => {
+                let Some(innermost) = open.last_mut() else {
+                    return Err(PreprocessError::new("E0104", "`#else` without an `#if`", span));
+                };
+                if let Some(otherwise) = innermost.otherwise {
+                    return Err(PreprocessError::new("E0104", "an `#if` can only have one `#else`", span)
+                        .with_note("the first `#else` is here", otherwise));
+                }
+                innermost.taking = !innermost.taking;
+                innermost.otherwise = Some(span);
+            }
+            "endif" => {
+                if open.pop().is_none() {
+                    return Err(PreprocessError::new("E0104", "`#endif` without an `#if`", span));
+                }
+            }
+            "include" => {
+                let argument = next_code(&tokens, last).filter(|&j| !breaks(source, tokens[last].span.end, tokens[j].span.start));
+                let Some(TokenKind::String(_)) = argument.map(|j| &tokens[j].kind) else {
+                    return Err(PreprocessError::new("E0104", "`#include` takes the file to include, as in `#include \"util.lang\"`", span));
+                };
+                last = argument.unwrap();
+                if open.iter().all(|open| open.taking) {
+                    kept[i..=last].fill(true);
+                }
+            }
+            _ => {
+                let message = match most_similar(&name, DIRECTIVES.iter().copied(), |name| name) {
+                    Some(similar) => format!("unknown directive `#{}`; did you mean `#{}`?", name, similar),
+                    None => format!("unknown directive `#{}`", name),
+                };
+                return Err(PreprocessError::new("E0104", message, span));
+            }
+        }
+        let span = tokens[last].span;
+        if let Some(after) = next_code(&tokens, last)
+            && !matches!(tokens[after].kind, TokenKind::EndOfInput)
+            && !breaks(source, span.end, tokens[after].span.start)
+        {
+            return Err(PreprocessError::new("E0104", format!("nothing can follow `#{}` on its line", name), tokens[after].span));
+        }
+        previous = span.end;
+        skip_to = Some(last);
+    }
+    if let Some(unclosed) = open.last() {
+        return Err(PreprocessError::new("E0105", "`#if` without an `#endif`", unclosed.span));
+    }
+    Ok(tokens.into_iter().zip(kept).filter(|(_, kept)| *kept).map(|(token, _)| token).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    fn kept(source: &str, defined: &[&str]) -> Result<String, String> {
+        let defined: Vec<String> = defined.iter().map(|name| name.to_string()).collect();
+        let tokens = preprocess(source, tokenize(source).0, &defined).map_err(|error| error.message)?;
+        let texts: Vec<&str> = tokens.iter()
+            .filter(|token| token.kind != TokenKind::EndOfInput)
+            .map(|token| &source[token.span.start..token.span.end])
+            .collect();
+        Ok(texts.join(" "))
+    }
+
+    #[test]
+    fn keeps_the_branches_whose_names_are_defined() {
+        let source = "a = 1;\n#if C\nputc(65);\n#if DEBUG\nprint(a);\n#endif\n#else\nprint(\"A\");\n#endif // C\nb = 2;\n";
+        assert_eq!(kept(source, &[]).unwrap(), "a = 1 ; print ( \"A\" ) ; b = 2 ;");
+        assert_eq!(kept(source, &["C"]).unwrap(), "a = 1 ; putc ( 65 ) ; b = 2 ;");
+        assert_eq!(kept(source, &["C", "DEBUG"]).unwrap(), "a = 1 ; putc ( 65 ) ; print ( a ) ; b = 2 ;");
+        assert_eq!(kept("#if A\n", &[]).unwrap_err(), "`#if` without an `#endif`");
+        assert_eq!(kept("#if A\n#else\n#else\n#endif\n", &[]).unwrap_err(), "an `#if` can only have one `#else`");
+        assert_eq!(kept("x = 1; #endif\n", &[]).unwrap_err(), "`#endif` must start a line of its own");
+        assert_eq!(kept("#if A x = 1;\n#endif\n", &[]).unwrap_err(), "nothing can follow `#if` on its line");
+        assert_eq!(kept("#endf\n", &[]).unwrap_err(), "unknown directive `#endf`; did you mean `#endif`?");
+        assert_eq!(kept("#[inline]\nfn f() {}\n", &[]).unwrap(), "# [ inline ] fn f ( ) { }");
+    }
+}
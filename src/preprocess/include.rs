@@ -0,0 +1,160 @@
+// `#include "util.lang"`: the tokens of another file, found relative to the one including
+// it, in place of the directive. The text of each included file goes after the main file's
+// in the source the spans are offsets into, with a `SourceFile` saying whose text is where,
+// so diagnostics can name the file and the chain of `#include`s that led to it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{Diagnostic, SourceFile, Span};
+use crate::lexer::{BorrowedToken, LexerOptions, Token, TokenKind, tokenize_with_options};
+
+use super::{PreprocessError, preprocess};
+
+// The files the main one included, directly or not.
+#[derive(Debug, Default)]
+pub struct Included {
+    // Their text, to go after the main file's; each starts a line of its own.
+    pub text: String,
+    pub files: Vec<SourceFile>
+}
+
+struct Includer<'a> {
+    // Where the main file's text ends, and so the first included file's starts.
+    base: usize,
+    lexer: LexerOptions,
+    defined: &'a [String],
+    included: Included,
+    // The files being included, outermost first, so one including itself can be caught.
+    including: Vec<PathBuf>
+}
+
+impl Includer<'_> {
+    // `tokens`, with each `#include` replaced by the tokens of the file it names, found
+    // relative to `path`, which the tokens are from.
+    fn expand(&mut self, path: &Path, tokens: Vec<Token>) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let mut expanded = Vec::with_capacity(tokens.len());
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            if token.kind != TokenKind::Hash || !matches!(tokens.peek().map(|token| &token.kind), Some(TokenKind::Indentifier(name)) if name.as_str() == "include") {
+                expanded.push(token);
+                continue;
+            }
+            // The preprocessor has checked that a string follows, on the same line.
+            let argument = tokens.find(|token| matches!(token.kind, TokenKind::String(_))).unwrap();
+            let TokenKind::String(file) = argument.kind else { unreachable!() };
+            let directive = token.span.to(argument.span);
+            expanded.extend(self.file(path, &file, directive)?);
+        }
+        Ok(expanded)
+    }
+
+    // The tokens of `file`, included by the directive at `directive` in `path`.
+    fn file(&mut self, path: &Path, file: &str, directive: Span) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let target = directory.join(file);
+        let error = |message: String| vec![Diagnostic::from(&PreprocessError::new("E0106", message, directive))];
+        let canonical = target.canonicalize().map_err(|e| error(format!("can't include `{}`: {}", target.display(), e)))?;
+        if self.including.contains(&canonical) {
+            return Err(vec![Diagnostic::from(&PreprocessError::new("E0107", format!("`{}` includes itself", target.display()), directive))]);
+        }
+        let text = fs::read_to_string(&canonical).map_err(|e| error(format!("can't include `{}`: {}", target.display(), e)))?;
+
+        self.including.push(canonical);
+        let tokens = self.add(&target, &text, directive, false);
+        self.including.pop();
+        let mut tokens = tokens?;
+        // The main file's end is the only one.
+        tokens.pop();
+        Ok(tokens)
+    }
+
+    // The tokens of the file at `path`, whose text is `text`, which goes after that of the
+    // files already included, as brought in by `at`. Its `#if`s and `#include`s are done.
+    fn add(&mut self, path: &Path, text: &str, at: Span, imported: bool) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        self.included.text.push('\n');
+        let start = self.base + self.included.text.len();
+        self.included.text.push_str(text);
+        let name = path.display().to_string();
+        self.included.files.push(SourceFile { name, span: Span::new(start, start + text.len()), included_at: at, imported });
+
+        let shift = |span: Span| Span::new(span.start + start, span.end + start);
+        let (tokens, errors) = tokenize_with_options(text, self.lexer);
+        if !errors.is_empty() {
+            return Err(errors.iter().map(|error| shifted(Diagnostic::from(error), shift)).collect());
+        }
+        let tokens: Vec<Token> = tokens.into_iter().map(BorrowedToken::into_owned).collect();
+        let tokens = preprocess(text, tokens, self.defined).map_err(|error| vec![shifted(Diagnostic::from(&error), shift)])?;
+        let tokens = tokens.into_iter().map(|token| Token { span: shift(token.span), ..token }).collect();
+        self.expand(path, tokens)
+    }
+}
+
+fn shifted(mut diagnostic: Diagnostic, shift: impl Fn(Span) -> Span) -> Diagnostic {
+    for label in &mut diagnostic.spans {
+        label.span = shift(label.span);
+    }
+    diagnostic
+}
+
+// Expands the `#include`s in `tokens`, which `preprocess` has left of the main file at
+// `path`, whose text is `source`. The files are lexed with `lexer` and preprocessed with
+// `defined`. What was included is in `included` even if there's an error in it, so the
+// error can be shown.
+pub fn include(path: &Path, source: &str, tokens: Vec<Token>, lexer: LexerOptions, defined: &[String], included: &mut Included) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let mut includer = Includer { base: source.len(), lexer, defined, included: Included::default(), including: Vec::new() };
+    includer.including.extend(path.canonicalize().ok());
+    let result = includer.expand(path, tokens);
+    *included = includer.included;
+    result
+}
+
+// The tokens of the module at `path`, whose text is `text`, for the `import` at `at` in a
+// program whose main file's text is `source`. The module's text goes after that of the files
+// in `included`, as `include` does with an included file's, up to and including its end.
+pub fn include_module(path: &Path, text: &str, at: Span, source: &str, lexer: LexerOptions, defined: &[String], included: &mut Included) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let mut includer = Includer { base: source.len(), lexer, defined, included: std::mem::take(included), including: Vec::new() };
+    includer.including.extend(path.canonicalize().ok());
+    let result = includer.add(path, text, at, true);
+    *included = includer.included;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Renderer;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn includes_files_relative_to_the_including_one() {
+        let directory = std::env::temp_dir().join(format!("tiny-lang-include-{}", std::process::id()));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(directory.join("lib/twice.lang"), "fn twice(x) { return x * 2; }").unwrap();
+        fs::write(directory.join("lib/util.lang"), "#include \"twice.lang\"\n#if LOUD\nprint(1);\n#endif\nx = @;\n").unwrap();
+        fs::write(directory.join("lib/loop.lang"), "#include \"../main.lang\"\n").unwrap();
+
+        let main = directory.join("main.lang");
+        let source = "#include \"lib/twice.lang\"\nprint(twice(2));";
+        fs::write(&main, source).unwrap();
+        let mut included = Included::default();
+        let tokens = include(&main, source, tokenize(source).0, LexerOptions::default(), &[], &mut included).unwrap();
+        let text = format!("{}{}", source, included.text);
+        let texts: Vec<&str> = tokens.iter().map(|token| &text[token.span.start..token.span.end]).collect();
+        assert_eq!(texts[..4], ["fn", "twice", "(", "x"]);
+        assert_eq!(tokens.last().unwrap().kind, TokenKind::EndOfInput);
+
+        // An error in an included file shows where it is, and the `#include`s that led there.
+        let source = "y = 1;\n#include \"lib/util.lang\"\n";
+        let errors = include(&main, source, tokenize(source).0, LexerOptions::default(), &[], &mut included).unwrap_err();
+        let text = format!("{}{}", source, included.text);
+        let rendered = Renderer::new("main.lang", &text, false).with_files(&included.files).render(&errors[0]);
+        assert!(rendered.contains(&format!("--> {}:5:5\n", directory.join("lib/util.lang").display())), "{}", rendered);
+        assert!(rendered.contains("--> main.lang:2:1\n"), "{}", rendered);
+
+        let source = "#include \"lib/loop.lang\"\n";
+        let errors = include(&main, source, tokenize(source).0, LexerOptions::default(), &[], &mut included).unwrap_err();
+        assert_eq!(errors[0].code, Some("E0107"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
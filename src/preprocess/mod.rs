@@ -0,0 +1,49 @@
+// The pass between lexing and parsing, which acts on the directives that start with `#`,
+// each on a line of its own: `#if`, `#else` and `#endif`, and `#include`, for the main file
+// and for each module it imports.
+
+mod conditional;
+mod include;
+
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Label, Span};
+
+pub use conditional::{DIRECTIVES, has_directives, preprocess};
+pub use include::{Included, include, include_module};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    // Looked up by `tiny-lang explain`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub note: Option<Label>
+}
+
+impl PreprocessError {
+    fn new(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self { code, message: message.into(), span, note: None }
+    }
+
+    fn with_note(self, message: impl Into<String>, span: Span) -> Self {
+        Self { note: Some(Label { span, message: message.into() }), ..self }
+    }
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<&PreprocessError> for Diagnostic {
+    fn from(error: &PreprocessError) -> Self {
+        let diagnostic = Diagnostic::error(error.message.clone(), error.span).with_code(error.code);
+        match &error.note {
+            Some(note) => diagnostic.with_note(note.message.clone(), note.span),
+            None => diagnostic,
+        }
+    }
+}
+
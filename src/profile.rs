@@ -0,0 +1,148 @@
+// `run --profile`: where the interpreter spends its time. Each statement's time runs until
+// the next statement starts, so a call's time is its body's and a line's is its own, and
+// the tables put the hottest functions and lines first.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::diagnostics::LineIndex;
+use crate::intern::Symbol;
+use crate::interpreter::{Action, Hook, Scope};
+use crate::parser::ast::Statement;
+
+// How many of the hottest lines the report shows.
+const HOT_LINES: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    statements: u64,
+    time: Duration
+}
+
+// Where the program was: the line, and the function it was in, if any.
+#[derive(Clone, Copy)]
+struct Place {
+    line: usize,
+    function: Option<Symbol>
+}
+
+pub struct Profiler<'s> {
+    source: &'s str,
+    lines: LineIndex<'s>,
+    by_line: HashMap<usize, Counts>,
+    // Keyed by function; `None` is the top level.
+    by_function: HashMap<Option<Symbol>, Counts>,
+    calls: HashMap<Symbol, u64>,
+    // The calls the program was in at the last statement, by function and where each was
+    // called from, outermost first.
+    stack: Vec<(Symbol, usize)>,
+    // The statement that's running and when it started.
+    last: Option<(Place, Instant)>,
+    started: Instant
+}
+
+impl<'s> Profiler<'s> {
+    pub fn new(source: &'s str) -> Self {
+        Profiler {
+            source,
+            lines: LineIndex::new(source),
+            by_line: HashMap::new(),
+            by_function: HashMap::new(),
+            calls: HashMap::new(),
+            stack: Vec::new(),
+            last: None,
+            started: Instant::now(),
+        }
+    }
+
+    // Puts the time since the last statement started down to it.
+    fn charge(&mut self, now: Instant) {
+        if let Some((place, started)) = self.last.take() {
+            let time = now - started;
+            self.by_line.entry(place.line).or_default().time += time;
+            self.by_function.entry(place.function).or_default().time += time;
+        }
+    }
+
+    // The hot-spot tables, once the program has finished.
+    pub fn report(&mut self) -> String {
+        let now = Instant::now();
+        self.charge(now);
+        let total = now - self.started;
+        let statements: u64 = self.by_line.values().map(|counts| counts.statements).sum();
+        let percent = |time: Duration| 100.0 * time.as_secs_f64() / total.as_secs_f64().max(f64::MIN_POSITIVE);
+        let mut out = format!("profile: {} statements in {:?}\n\n", statements, total);
+
+        let mut functions: Vec<(Option<Symbol>, Counts)> = self.by_function.iter().map(|(&function, &counts)| (function, counts)).collect();
+        functions.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(b.1.statements.cmp(&a.1.statements)));
+        writeln!(out, "{:<24} {:>10} {:>12} {:>14} {:>7}", "function", "calls", "statements", "time", "").unwrap();
+        for (function, counts) in functions {
+            let (name, calls) = match function {
+                Some(function) => (function.to_string(), self.calls.get(&function).copied().unwrap_or_default()),
+                None => ("top level".to_string(), 1),
+            };
+            let time = format!("{:?}", counts.time);
+            writeln!(out, "{:<24} {:>10} {:>12} {:>14} {:>6.1}%", name, calls, counts.statements, time, percent(counts.time)).unwrap();
+        }
+
+        let mut lines: Vec<(usize, Counts)> = self.by_line.iter().map(|(&line, &counts)| (line, counts)).collect();
+        lines.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(b.1.statements.cmp(&a.1.statements)).then(a.0.cmp(&b.0)));
+        writeln!(out, "\n{:<24} {:>10} {:>12} {:>14} {:>7}", "line", "", "statements", "time", "").unwrap();
+        for (line, counts) in lines.into_iter().take(HOT_LINES) {
+            let text = self.source.lines().nth(line - 1).unwrap_or_default().trim();
+            let text: String = format!("{:>4} | {}", line, text).chars().take(35).collect();
+            let time = format!("{:?}", counts.time);
+            writeln!(out, "{:<35} {:>12} {:>14} {:>6.1}%", text, counts.statements, time, percent(counts.time)).unwrap();
+        }
+        out
+    }
+}
+
+impl Hook for Profiler<'_> {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        if matches!(statement, Statement::Block { .. } | Statement::Function { .. } | Statement::Attributed { .. }) {
+            return Action::Continue;
+        }
+        let now = Instant::now();
+        // The calls that weren't there at the last statement have started since. One that
+        // returned and was made again from the same place had a statement of its caller
+        // run in between, which dropped it from the stack.
+        let calls = scope.calls();
+        let kept = self.stack.iter().zip(calls).take_while(|&(&seen, &(function, call))| seen == (function, call.start)).count();
+        self.stack.truncate(kept);
+        for &(function, call) in &calls[kept..] {
+            *self.calls.entry(function).or_default() += 1;
+            self.stack.push((function, call.start));
+        }
+        let place = Place { line: self.lines.line_column(statement.span().start).0, function: calls.last().map(|&(function, _)| function) };
+        self.charge(now);
+        self.by_line.entry(place.line).or_default().statements += 1;
+        self.by_function.entry(place.function).or_default().statements += 1;
+        self.last = Some((place, now));
+        Action::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn counts_calls_and_statements() {
+        let source = "fn f(n) {\n    if (n < 2) return n;\n    return f(n - 1) + f(n - 2);\n}\nx = f(5) + f(1);\n";
+        let (tokens, _) = tokenize(source);
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let mut profiler = Profiler::new(source);
+        Interpreter::with_output(std::io::sink()).with_hook(&mut profiler).run(&program).unwrap();
+
+        // f(5) makes 15 calls in all and f(1) one, each running an `if` and a `return`.
+        assert_eq!(profiler.calls[&Symbol::from("f")], 16);
+        assert_eq!(profiler.by_function[&Some(Symbol::from("f"))].statements, 16 * 2);
+        assert_eq!(profiler.by_line[&5].statements, 1);
+        assert!(profiler.report().starts_with("profile: 33 statements in "));
+    }
+}
@@ -0,0 +1,223 @@
+// `tiny-lang build dir/`: every `.lang` file in a directory, and the graph of the modules they
+// import and the files they include. The files no other one imports or includes are the
+// programs, each compiled with what it brings in. A program whose key, the hash of its text
+// and of everything it brings in, is the one the cache has for it is up to date.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::lexer::{TokenKind, tokenize};
+use crate::modules::{EXTENSION, find_module};
+
+// Where the cache is kept, among the compiled programs.
+pub const CACHE: &str = "cache";
+
+// A file of the project, or one it brings in from outside it.
+pub struct File {
+    pub path: PathBuf,
+    // Where it is in the project's files.
+    index: usize,
+    // The hash of its text alone.
+    hash: u64,
+    // What it imports and includes, as indexes into the project's files.
+    dependencies: Vec<usize>
+}
+
+pub struct Project {
+    pub root: PathBuf,
+    pub files: Vec<File>,
+    // The files under `root`, which come first in `files`.
+    own: usize
+}
+
+impl Project {
+    // The `.lang` files under `root`, in sorted order, and what they bring in, looking for
+    // modules in `search` too. Hidden directories and `skip`, where the output goes, are left
+    // out.
+    pub fn discover(root: &Path, search: &[String], skip: &Path) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        walk(root, skip, &mut paths)?;
+        paths.sort();
+        let mut project = Project { root: root.to_path_buf(), files: Vec::new(), own: paths.len() };
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+        for path in &paths {
+            let i = project.files.len();
+            index.insert(path.canonicalize()?, i);
+            project.files.push(File { path: path.clone(), index: i, hash: 0, dependencies: Vec::new() });
+        }
+        // Files from outside `root` are added as they're found.
+        let mut i = 0;
+        while i < project.files.len() {
+            let path = project.files[i].path.clone();
+            let bytes = fs::read(&path)?;
+            project.files[i].hash = fnv(FNV_OFFSET, &bytes);
+            for dependency in dependencies(&path, &String::from_utf8_lossy(&bytes), search) {
+                // One that can't be found is reported when the program is compiled.
+                let Ok(canonical) = dependency.canonicalize() else { continue };
+                let next = project.files.len();
+                let j = *index.entry(canonical).or_insert(next);
+                if j == next {
+                    project.files.push(File { path: dependency, index: j, hash: 0, dependencies: Vec::new() });
+                }
+                if !project.files[i].dependencies.contains(&j) {
+                    project.files[i].dependencies.push(j);
+                }
+            }
+            i += 1;
+        }
+        Ok(project)
+    }
+
+    // The files under the root that nothing else in it imports or includes.
+    pub fn programs(&self) -> Vec<&File> {
+        let brought_in: Vec<usize> = self.files[..self.own].iter().flat_map(|file| file.dependencies.iter().copied()).collect();
+        (0..self.own).filter(|i| !brought_in.contains(i)).map(|i| &self.files[i]).collect()
+    }
+
+    // The hash of `file`'s text, of `salt`, such as the options it's compiled with, and of
+    // the text of everything it brings in, directly or not.
+    pub fn key(&self, file: &File, salt: &str) -> u64 {
+        let mut seen = vec![file.index];
+        let mut hash = fnv(FNV_OFFSET, salt.as_bytes());
+        let mut i = 0;
+        // Each file once, in the order they're found, so an import cycle ends.
+        while i < seen.len() {
+            let file = &self.files[seen[i]];
+            hash = fnv(hash, &file.hash.to_le_bytes());
+            for &dependency in &file.dependencies {
+                if !seen.contains(&dependency) {
+                    seen.push(dependency);
+                }
+            }
+            i += 1;
+        }
+        hash
+    }
+
+    // `file`'s path from the root, as the cache and the output name it.
+    pub fn relative<'a>(&self, file: &'a File) -> &'a Path {
+        file.path.strip_prefix(&self.root).unwrap_or(&file.path)
+    }
+}
+
+fn walk(directory: &Path, skip: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_dir() {
+            if !hidden && path != skip {
+                walk(&path, skip, paths)?;
+            }
+        } else if path.extension().is_some_and(|extension| extension == EXTENSION) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// The modules the file at `path`, whose text is `source`, imports, and the files it includes.
+// Those in a branch of an `#if` count too, so a change to them is never missed.
+fn dependencies(path: &Path, source: &str, search: &[String]) -> Vec<PathBuf> {
+    let tokens = tokenize(source).0;
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut found = Vec::new();
+    for window in tokens.windows(3) {
+        match [&window[0].kind, &window[1].kind, &window[2].kind] {
+            [TokenKind::KeywordImport, TokenKind::Indentifier(name), TokenKind::Semicolon] => {
+                found.extend(find_module(path, *name, search).ok());
+            }
+            [TokenKind::Hash, TokenKind::Indentifier(name), TokenKind::String(file)] if name.as_str() == "include" => {
+                found.push(directory.join(&**file));
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+// FNV-1a, which unlike the standard library's hasher is the same from one build to the next.
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// The key each program was last compiled with, as lines of a hexadecimal key and the
+// program's path from the root.
+#[derive(Debug, Default)]
+pub struct Cache {
+    keys: HashMap<PathBuf, u64>
+}
+
+impl Cache {
+    // The cache at `path`; none yet, or one that can't be read, is empty.
+    pub fn load(path: &Path) -> Self {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        let keys = text.lines()
+            .filter_map(|line| {
+                let (key, file) = line.split_once(' ')?;
+                Some((PathBuf::from(file), u64::from_str_radix(key, 16).ok()?))
+            })
+            .collect();
+        Cache { keys }
+    }
+
+    pub fn is_fresh(&self, file: &Path, key: u64) -> bool {
+        self.keys.get(file) == Some(&key)
+    }
+
+    pub fn insert(&mut self, file: &Path, key: u64) {
+        self.keys.insert(file.to_path_buf(), key);
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut lines: Vec<String> = self.keys.iter().map(|(file, key)| format!("{:016x} {}", key, file.display())).collect();
+        lines.sort_by(|a, b| a[17..].cmp(&b[17..]));
+        fs::write(path, lines.into_iter().map(|line| line + "\n").collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_are_keyed_by_what_they_bring_in() {
+        let directory = std::env::temp_dir().join(format!("tiny-lang-project-{}", std::process::id()));
+        let build = directory.join("build");
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::create_dir_all(&build).unwrap();
+        fs::write(directory.join("main.lang"), "import shapes;\nprint(shapes.area(2, 3));\n").unwrap();
+        fs::write(directory.join("other.lang"), "#include \"lib/util.lang\"\nprint(1);\n").unwrap();
+        fs::write(directory.join("lib/shapes.lang"), "fn area(w, h) { return w * h; }\n").unwrap();
+        fs::write(directory.join("lib/util.lang"), "x = 1;\n").unwrap();
+        fs::write(build.join("stale.lang"), "").unwrap();
+        let search = [directory.join("lib").display().to_string()];
+
+        let project = Project::discover(&directory, &search, &build).unwrap();
+        let programs: Vec<&Path> = project.programs().into_iter().map(|file| project.relative(file)).collect();
+        assert_eq!(programs, [Path::new("main.lang"), Path::new("other.lang")]);
+
+        let key = |project: &Project| project.key(project.programs()[0], "-O2");
+        let before = key(&project);
+        assert_eq!(before, key(&Project::discover(&directory, &search, &build).unwrap()));
+        assert_ne!(before, project.key(project.programs()[0], "-O0"));
+        fs::write(directory.join("lib/shapes.lang"), "fn area(w, h) { return h * w; }\n").unwrap();
+        let after = key(&Project::discover(&directory, &search, &build).unwrap());
+        assert_ne!(before, after);
+
+        let mut cache = Cache::default();
+        cache.insert(Path::new("main.lang"), after);
+        cache.save(&build.join(CACHE)).unwrap();
+        let cache = Cache::load(&build.join(CACHE));
+        assert!(cache.is_fresh(Path::new("main.lang"), after));
+        assert!(!cache.is_fresh(Path::new("main.lang"), before));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
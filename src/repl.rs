@@ -0,0 +1,132 @@
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::diagnostics::{Diagnostic, Renderer, stderr_supports_color};
+use crate::lexer::{Token, TokenKind, tokenize};
+use crate::parser::ast::{Expression, Statement};
+use crate::parser::parser::{Parser, parse_program};
+use crate::sema::check_types;
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+// Input is complete once every paren and brace is closed and the last token ends a statement.
+fn is_complete(tokens: &[Token]) -> bool {
+    let mut depth = 0i32;
+    for token in tokens {
+        match token.kind {
+            TokenKind::OpenParen | TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseParen | TokenKind::CloseBrace => depth -= 1,
+            _ => {}
+        }
+    }
+
+    let last = tokens.iter().rev().map(|t| &t.kind).find(|k| **k != TokenKind::EndOfInput);
+    depth <= 0 && matches!(last, None | Some(TokenKind::Semicolon) | Some(TokenKind::CloseBrace))
+}
+
+fn execute(interpreter: &mut Interpreter, stmt: &Statement) -> Result<(), RuntimeError> {
+    match stmt {
+        Statement::Expression {expression: Expression::Assignment {..}, ..} => interpreter.execute(stmt),
+        Statement::Expression {expression, ..} => {
+            println!("{}", interpreter.evaluate(expression)?);
+            Ok(())
+        }
+        _ => interpreter.execute(stmt),
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown error"
+    }
+}
+
+pub fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut interpreter = Interpreter::new();
+    let mut buffer = String::new();
+    let color = stderr_supports_color();
+
+    // Runtime errors that are still panics are reported briefly and keep the session alive.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| eprintln!("error: {}", panic_message(info.payload()))));
+
+    loop {
+        print!("{}", if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT });
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        buffer.push_str(&line);
+
+        let renderer = Renderer::new("<repl>", &buffer, color);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let (tokens, errors) = tokenize(buffer.as_str());
+            if !errors.is_empty() {
+                for error in &errors {
+                    eprint!("{}", renderer.render(&Diagnostic::from(error)));
+                }
+                return true;
+            }
+            if !is_complete(&tokens) {
+                return false;
+            }
+
+            let program = match parse_program(&mut Parser::new(tokens)) {
+                Ok(program) => program,
+                Err(error) => {
+                    eprint!("{}", renderer.render(&Diagnostic::from(&error)));
+                    return true;
+                }
+            };
+            let errors = check_types(&program);
+            if !errors.is_empty() {
+                for error in &errors {
+                    eprint!("{}", renderer.render(&Diagnostic::from(error)));
+                }
+                return true;
+            }
+            for stmt in &program {
+                if let Err(error) = execute(&mut interpreter, stmt) {
+                    eprint!("{}", renderer.render(&Diagnostic::from(&error)));
+                    break;
+                }
+            }
+            true
+        }));
+
+        if !matches!(result, Ok(false)) {
+            buffer.clear();
+        }
+        stdout.flush()?;
+    }
+
+    panic::set_hook(default_hook);
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_brackets_to_close_and_statements_to_end() {
+        let complete = |input: &str| is_complete(&tokenize(input).0);
+        assert!(complete(""));
+        assert!(complete("x = 1;"));
+        assert!(complete("fn f(n) {\n    return n;\n}"));
+        assert!(!complete("x = 1"));
+        assert!(!complete("fn f(n) {\n    return n;"));
+        assert!(!complete("print(f(1),"));
+    }
+}
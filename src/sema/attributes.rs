@@ -0,0 +1,100 @@
+// `#[allow(...)]`, `#[warn(...)]` and `#[deny(...)]`: the lint levels a function or a
+// statement sets for the warnings in it, over those of the command line. One inside another
+// overrides it.
+
+use crate::diagnostics::{Diagnostic, Level, LintLevels, Span};
+use crate::intern::Symbol;
+use crate::parser::ast::{Attribute, Statement};
+
+pub struct LintScopes<'l> {
+    levels: &'l LintLevels,
+    // In the order the statements start, so each is after the ones it's in.
+    scopes: Vec<(Span, Vec<(Level, Symbol)>)>
+}
+
+impl<'l> LintScopes<'l> {
+    pub fn new(program: &[Statement], levels: &'l LintLevels) -> Self {
+        let mut scopes = Vec::new();
+        program.iter().for_each(|statement| collect(statement, &mut scopes));
+        LintScopes { levels, scopes }
+    }
+
+    // The levels the warnings at `offset` are reported at.
+    pub fn at(&self, offset: usize) -> LintLevels {
+        let mut levels = self.levels.clone();
+        for (_, lints) in self.scopes.iter().filter(|(span, _)| span.start <= offset && offset < span.end) {
+            for &(level, lint) in lints {
+                // The parser has checked the names.
+                let _ = levels.set(lint.as_str(), level);
+            }
+        }
+        levels
+    }
+
+    // `LintLevels::apply` with the levels where the diagnostic is.
+    pub fn apply(&self, diagnostic: Diagnostic) -> Option<Diagnostic> {
+        if self.scopes.is_empty() {
+            return self.levels.apply(diagnostic);
+        }
+        self.at(diagnostic.span().start).apply(diagnostic)
+    }
+}
+
+fn lints(attributes: &[Attribute]) -> Vec<(Level, Symbol)> {
+    attributes.iter()
+        .flat_map(|attribute| match attribute {
+            Attribute::Lint(level, lints) => lints.iter().map(|&lint| (*level, lint)).collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+fn collect(statement: &Statement, scopes: &mut Vec<(Span, Vec<(Level, Symbol)>)>) {
+    match statement {
+        Statement::Attributed { attributes, statement: inner, span } => {
+            scopes.push((*span, lints(attributes)));
+            collect(inner, scopes);
+        }
+        // A function's span is its name's; its scope goes on to the end of its body.
+        Statement::Function { attributes, body, span, .. } => {
+            let end = body.last().map_or(span.end, |last| last.span().end.max(span.end));
+            let lints = lints(attributes);
+            if !lints.is_empty() {
+                scopes.push((Span::new(span.start, end), lints));
+            }
+            body.iter().for_each(|statement| collect(statement, scopes));
+        }
+        Statement::Block { body, .. } => body.iter().for_each(|statement| collect(statement, scopes)),
+        Statement::If { then_branch, else_branch, .. } => {
+            collect(then_branch, scopes);
+            if let Some(else_branch) = else_branch {
+                collect(else_branch, scopes);
+            }
+        }
+        Statement::While { body, .. } | Statement::DoWhile { body, .. } | Statement::For { body, .. } => collect(body, scopes),
+        Statement::Expression { .. } | Statement::Print { .. } | Statement::Putc { .. } | Statement::Return { .. }
+        | Statement::Import { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+    use crate::sema::check_unused;
+
+    #[test]
+    fn attributes_set_the_levels_of_what_they_are_before() {
+        let source = "#[deny(unused)]\nfn f() {\n    a = 1;\n    #[allow(unused)]\n    b = 2;\n    return 0;\n}\nprint(f());\n";
+        let tokens = tokenize(source).0;
+        let program = parse_program(&mut Parser::new(tokens)).unwrap();
+        let levels = LintLevels::default();
+        let scopes = LintScopes::new(&program, &levels);
+        let warnings: Vec<Diagnostic> = check_unused(&program).iter().map(Diagnostic::from).filter_map(|d| scopes.apply(d)).collect();
+        let messages: Vec<String> = warnings.iter().map(|d| format!("{:?} {}", d.severity, d.message)).collect();
+        assert!(messages.iter().all(|message| message.starts_with("Error")), "{:?}", messages);
+        assert!(messages.iter().any(|message| message.contains("`a`")), "{:?}", messages);
+        assert!(!messages.iter().any(|message| message.contains("`b`")), "{:?}", messages);
+    }
+}
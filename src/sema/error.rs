@@ -0,0 +1,113 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+
+use super::scope::{Declaration, DeclarationKind};
+use super::types::Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemaError {
+    // An undeclared name keeps what it may have been meant as: the variable or function in
+    // scope whose name is closest to it, if any is close.
+    UndeclaredVariable { name: Symbol, span: Span, suggestion: Option<Declaration> },
+    UndeclaredFunction { name: Symbol, span: Span, suggestion: Option<Declaration> },
+    // An operator applied to operands of types it doesn't support, e.g. `"a" * 3`.
+    TypeMismatch { operator: TokenKind, left: Type, right: Type, span: Span },
+    InvalidOperand { operator: TokenKind, operand: Type, span: Span },
+    // Anything else of the wrong type, such as a string used as a condition.
+    WrongType { expected: &'static str, found: Type, span: Span },
+    // An assignment of a different type than the variable was first given.
+    ConflictingAssignment { name: Symbol, first: Type, first_span: Span, found: Type, span: Span },
+    // A call to a function or builtin with more or fewer arguments than it takes.
+    ArgumentCount { name: Symbol, expected: usize, found: usize, span: Span },
+    // A second function with the name of one defined before it.
+    DuplicateFunction { name: Symbol, first_span: Span, span: Span }
+}
+
+// What's wrong with a call to `name` with `found` arguments when it takes `expected`, which
+// the interpreter and the compiler report the same way.
+pub(crate) fn argument_count(name: Symbol, expected: usize, found: usize) -> String {
+    let plural = if expected == 1 { "" } else { "s" };
+    let were = if found == 1 { "was" } else { "were" };
+    format!("`{}` takes {} argument{} but {} {} given", name, expected, plural, found, were)
+}
+
+impl SemaError {
+    pub fn span(&self) -> Span {
+        match self {
+            SemaError::UndeclaredVariable { span, .. } | SemaError::UndeclaredFunction { span, .. }
+            | SemaError::TypeMismatch { span, .. } | SemaError::InvalidOperand { span, .. }
+            | SemaError::WrongType { span, .. } | SemaError::ConflictingAssignment { span, .. }
+            | SemaError::ArgumentCount { span, .. } | SemaError::DuplicateFunction { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemaError::UndeclaredVariable { .. } => "E0301",
+            SemaError::UndeclaredFunction { .. } => "E0302",
+            SemaError::TypeMismatch { .. } => "E0303",
+            SemaError::InvalidOperand { .. } => "E0304",
+            SemaError::WrongType { .. } => "E0305",
+            SemaError::ConflictingAssignment { .. } => "E0306",
+            SemaError::ArgumentCount { .. } => "E0307",
+            SemaError::DuplicateFunction { .. } => "E0308",
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<&Declaration> {
+        match self {
+            SemaError::UndeclaredVariable { suggestion, .. } | SemaError::UndeclaredFunction { suggestion, .. } => suggestion.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemaError::UndeclaredVariable { name, .. } => write!(f, "use of undeclared variable `{}`", name)?,
+            SemaError::UndeclaredFunction { name, .. } => write!(f, "call to undeclared function `{}`", name)?,
+            SemaError::TypeMismatch { operator, left, right, .. } => {
+                write!(f, "cannot apply `{}` to {} and {}", operator, left, right)?
+            }
+            SemaError::InvalidOperand { operator, operand, .. } => write!(f, "cannot apply `{}` to {}", operator, operand)?,
+            SemaError::WrongType { expected, found, .. } => write!(f, "expected {}, found {}", expected, found)?,
+            SemaError::ConflictingAssignment { name, first, found, .. } => {
+                write!(f, "`{}` is assigned {} here but {} before", name, found.with_article(), first.with_article())?
+            }
+            SemaError::ArgumentCount { name, expected, found, .. } => write!(f, "{}", argument_count(*name, *expected, *found))?,
+            SemaError::DuplicateFunction { name, .. } => write!(f, "function `{}` is already defined", name)?,
+        }
+        if let Some(suggestion) = self.suggestion() {
+            write!(f, ", did you mean `{}`?", suggestion.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&SemaError> for Diagnostic {
+    fn from(error: &SemaError) -> Self {
+        let diagnostic = Diagnostic::error(error.to_string(), error.span()).with_code(error.code());
+        if let SemaError::ConflictingAssignment { name, first, first_span, .. } = error {
+            let note = format!("`{}` is first assigned {} here", name, first.with_article());
+            return diagnostic.with_note(note, *first_span);
+        }
+        if let SemaError::DuplicateFunction { name, first_span, .. } = error {
+            return diagnostic.with_note(format!("`{}` is first defined here", name), *first_span);
+        }
+        let Some(similar) = error.suggestion() else {
+            return diagnostic;
+        };
+        let note = match similar.kind {
+            DeclarationKind::Function => format!("a function named `{}` is declared here", similar.name),
+            // Builtins have nowhere to point at.
+            DeclarationKind::Builtin => return diagnostic,
+            DeclarationKind::Global | DeclarationKind::Local => format!("a variable named `{}` is assigned here", similar.name),
+            DeclarationKind::Parameter => format!("`{}` is a parameter of this function", similar.name),
+        };
+        diagnostic.with_note(note, similar.span)
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+
+use super::warning::Warning;
+
+// The variables assigned on every path to a point, or `None` where no path reaches it.
+type Assigned = Option<HashSet<Symbol>>;
+
+fn join(a: Assigned, b: Assigned) -> Assigned {
+    match (a, b) {
+        (None, other) | (other, None) => other,
+        (Some(a), Some(b)) => Some(a.intersection(&b).copied().collect()),
+    }
+}
+
+struct Flow {
+    // The variables that belong to the code being checked. Parameters are assigned on
+    // entry, and a global read in a function depends on when it's called.
+    tracked: Vec<Symbol>,
+    // Each variable is only warned about once.
+    warned: HashSet<Symbol>,
+    warnings: Vec<Warning>
+}
+
+impl Flow {
+    fn read(&mut self, name: Symbol, span: Span, assigned: &mut Assigned) {
+        if let Some(assigned) = assigned
+            && self.tracked.contains(&name)
+            && !assigned.contains(&name)
+            && self.warned.insert(name) {
+            self.warnings.push(Warning::UseBeforeAssignment { name, span });
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression, assigned: &mut Assigned) {
+        match expr {
+            Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+            Expression::Symbol(name, span) => self.read(*name, *span, assigned),
+            Expression::Unary {operand, ..} => self.expression(operand, assigned),
+            // The right side of `&&` and `||` doesn't always run.
+            Expression::Binary {left, operator: TokenKind::OpAnd | TokenKind::OpOr, right, ..} => {
+                self.expression(left, assigned);
+                self.expression(right, &mut assigned.clone());
+            }
+            Expression::Binary {left, right, ..} => {
+                self.expression(left, assigned);
+                self.expression(right, assigned);
+            }
+            // The value is evaluated before the element it's stored into.
+            Expression::Assignment {assignee, value, ..} => {
+                self.expression(value, assigned);
+                match assignee.as_ref() {
+                    Expression::Symbol(name, _) => {
+                        if let Some(assigned) = assigned {
+                            assigned.insert(*name);
+                        }
+                    }
+                    assignee => self.expression(assignee, assigned),
+                }
+            }
+            Expression::Call {arguments, ..} | Expression::Array {elements: arguments, ..} => {
+                arguments.iter().for_each(|a| self.expression(a, assigned));
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                self.expression(condition, assigned);
+                let mut otherwise = assigned.clone();
+                self.expression(then_value, assigned);
+                self.expression(else_value, &mut otherwise);
+                *assigned = join(assigned.take(), otherwise);
+            }
+            Expression::Index {target, index, ..} => {
+                self.expression(target, assigned);
+                self.expression(index, assigned);
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement, assigned: &mut Assigned) {
+        match stmt {
+            Statement::Block {body, ..} => body.iter().for_each(|s| self.statement(s, assigned)),
+            Statement::Attributed {statement, ..} => self.statement(statement, assigned),
+            Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => self.expression(expression, assigned),
+            Statement::Print {arguments, ..} => arguments.iter().for_each(|a| self.expression(a, assigned)),
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                self.expression(condition, assigned);
+                let mut otherwise = assigned.clone();
+                self.statement(then_branch, assigned);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch, &mut otherwise);
+                }
+                *assigned = join(assigned.take(), otherwise);
+            }
+            // A loop's body may not run at all.
+            Statement::While {condition, body, ..} => {
+                self.expression(condition, assigned);
+                self.statement(body, &mut assigned.clone());
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                self.statement(body, assigned);
+                self.expression(condition, assigned);
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer, assigned);
+                }
+                let mut looping = match condition {
+                    Some(condition) => {
+                        self.expression(condition, assigned);
+                        assigned.clone()
+                    }
+                    // Without a condition the loop is only left by returning.
+                    None => assigned.take(),
+                };
+                self.statement(body, &mut looping);
+                if let Some(step) = step {
+                    self.expression(step, &mut looping);
+                }
+            }
+            Statement::Function {..} | Statement::Import {..} => {}
+            Statement::Return {value, ..} => {
+                if let Some(value) = value {
+                    self.expression(value, assigned);
+                }
+                *assigned = None;
+            }
+        }
+    }
+}
+
+fn check_body(tracked: Vec<Symbol>, body: &[Statement], warnings: &mut Vec<Warning>) {
+    let mut flow = Flow { tracked, warned: HashSet::new(), warnings: Vec::new() };
+    let mut assigned = Some(HashSet::new());
+    body.iter().for_each(|stmt| flow.statement(stmt, &mut assigned));
+    warnings.append(&mut flow.warnings);
+}
+
+// Warns about variables that may be read before they're assigned, along some path through
+// the top level or a function.
+pub fn check_assignments(program: &[Statement]) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    check_body(function_locals(&[], program), program, &mut warnings);
+    for stmt in program {
+        if let Statement::Function {parameters, body, ..} = stmt {
+            let locals = function_locals(parameters, body).split_off(parameters.len());
+            check_body(locals, body, &mut warnings);
+        }
+    }
+    warnings.sort_by_key(|warning| warning.span().start);
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    fn warnings(source: &str) -> Vec<(String, &str)> {
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        check_assignments(&program).iter().map(|w| (w.to_string(), &source[w.span().start..w.span().end])).collect()
+    }
+
+    #[test]
+    fn warns_about_reads_some_path_reaches_before_an_assignment() {
+        assert_eq!(warnings("\
+if (c) { a = 1; }
+print(a);
+b = 0;
+while (b < 3) { d = b; b = b + 1; }
+print(d, d);
+x = c && (e = 1);
+print(e);
+fn f(n) { if (n) { return 1; } else { m = 2; } return m; }
+"), [
+            ("`a` may be read before it's assigned".to_string(), "a"),
+            ("`d` may be read before it's assigned".to_string(), "d"),
+            ("`e` may be read before it's assigned".to_string(), "e"),
+        ]);
+    }
+}
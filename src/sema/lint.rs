@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+
+use super::resolve::{Occurrence, names_in_statement};
+use super::warning::Warning;
+
+// The variables whose current values may still be read.
+type Live = HashSet<Symbol>;
+
+// Works backwards from the end of the top level or a function body.
+struct Liveness<'a> {
+    tracked: &'a [Symbol],
+    // What a call may read: the globals read by any function.
+    read_by_calls: &'a HashSet<Symbol>,
+    // Loops are run to a fixed point before anything in them is reported.
+    report: bool,
+    dead: Vec<(Symbol, Span)>
+}
+
+impl Liveness<'_> {
+    fn expression(&mut self, expr: &Expression, live: &mut Live) {
+        match expr {
+            Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+            Expression::Symbol(name, _) => { live.insert(*name); }
+            Expression::Unary {operand, ..} => self.expression(operand, live),
+            // The right side of `&&` and `||` doesn't always run.
+            Expression::Binary {left, operator: TokenKind::OpAnd | TokenKind::OpOr, right, ..} => {
+                let mut right_live = live.clone();
+                self.expression(right, &mut right_live);
+                live.extend(right_live);
+                self.expression(left, live);
+            }
+            Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..} => {
+                self.expression(right, live);
+                self.expression(left, live);
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                match assignee.as_ref() {
+                    Expression::Symbol(name, _) => {
+                        if !live.remove(name) && self.report && self.tracked.contains(name) {
+                            self.dead.push((*name, expr.span()));
+                        }
+                    }
+                    assignee => self.expression(assignee, live),
+                }
+                self.expression(value, live);
+            }
+            Expression::Call {arguments, ..} => {
+                live.extend(self.read_by_calls);
+                arguments.iter().rev().for_each(|a| self.expression(a, live));
+            }
+            Expression::Array {elements, ..} => elements.iter().rev().for_each(|e| self.expression(e, live)),
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                let mut otherwise = live.clone();
+                self.expression(then_value, live);
+                self.expression(else_value, &mut otherwise);
+                live.extend(otherwise);
+                self.expression(condition, live);
+            }
+        }
+    }
+
+    fn optional(&mut self, expr: &Option<Expression>, live: &mut Live) {
+        if let Some(expr) = expr {
+            self.expression(expr, live);
+        }
+    }
+
+    // What's live right after a loop's condition, where it either leaves the loop with
+    // `after` live or goes round again. `pass` goes back through the loop from there.
+    fn fixed_point(&mut self, after: &Live, mut pass: impl FnMut(&mut Self, &mut Live)) -> Live {
+        let report = std::mem::replace(&mut self.report, false);
+        let mut bottom = after.clone();
+        loop {
+            let mut next = bottom.clone();
+            pass(self, &mut next);
+            next.extend(after.iter().copied());
+            if next == bottom {
+                break;
+            }
+            bottom = next;
+        }
+        self.report = report;
+        if report {
+            pass(self, &mut bottom.clone());
+        }
+        bottom
+    }
+
+    fn statement(&mut self, stmt: &Statement, live: &mut Live) {
+        match stmt {
+            Statement::Block {body, ..} => body.iter().rev().for_each(|s| self.statement(s, live)),
+            Statement::Attributed {statement, ..} => self.statement(statement, live),
+            Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => self.expression(expression, live),
+            Statement::Print {arguments, ..} => arguments.iter().rev().for_each(|a| self.expression(a, live)),
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                let mut otherwise = live.clone();
+                self.statement(then_branch, live);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch, &mut otherwise);
+                }
+                live.extend(otherwise);
+                self.expression(condition, live);
+            }
+            Statement::While {condition, body, ..} => {
+                let mut live_in = self.fixed_point(live, |this, live| {
+                    this.expression(condition, live);
+                    this.statement(body, live);
+                });
+                self.expression(condition, &mut live_in);
+                *live = live_in;
+            }
+            Statement::DoWhile {body, condition, ..} => {
+                let mut live_in = self.fixed_point(live, |this, live| {
+                    this.expression(condition, live);
+                    this.statement(body, live);
+                });
+                self.expression(condition, &mut live_in);
+                self.statement(body, &mut live_in);
+                *live = live_in;
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                // Without a condition the loop is only left by returning.
+                let after = if condition.is_some() { live.clone() } else { Live::new() };
+                let mut live_in = self.fixed_point(&after, |this, live| {
+                    this.optional(condition, live);
+                    this.optional(step, live);
+                    this.statement(body, live);
+                });
+                self.optional(condition, &mut live_in);
+                self.optional(initializer, &mut live_in);
+                *live = live_in;
+            }
+            Statement::Function {..} | Statement::Import {..} => {}
+            Statement::Return {value, ..} => {
+                live.clear();
+                self.optional(value, live);
+            }
+        }
+    }
+}
+
+// The names read in `body` other than `locals`.
+fn reads(body: &[Statement], locals: &[Symbol], reads: &mut HashSet<Symbol>) {
+    for stmt in body {
+        names_in_statement(stmt, &mut |name, _, occurrence| {
+            if occurrence == Occurrence::Read && !locals.contains(&name) {
+                reads.insert(name);
+            }
+        });
+    }
+}
+
+struct Scope<'a> {
+    body: &'a [Statement],
+    variables: Vec<Symbol>,
+    // Assigned by the caller, so only stores to them are checked.
+    parameters: &'a [Symbol],
+    // Every name read where these variables are visible.
+    reads: HashSet<Symbol>,
+    read_by_calls: HashSet<Symbol>
+}
+
+fn check_scope(scope: Scope, warnings: &mut Vec<Warning>) {
+    let mut unused = Vec::new();
+    for stmt in scope.body {
+        names_in_statement(stmt, &mut |name, span, occurrence| {
+            if occurrence == Occurrence::Write
+                && scope.variables.contains(&name)
+                && !scope.parameters.contains(&name)
+                && !scope.reads.contains(&name)
+                && !unused.contains(&name) {
+                unused.push(name);
+                warnings.push(Warning::UnusedVariable { name, span });
+            }
+        });
+    }
+
+    let mut liveness = Liveness { tracked: &scope.variables, read_by_calls: &scope.read_by_calls, report: true, dead: Vec::new() };
+    let mut live = Live::new();
+    scope.body.iter().rev().for_each(|stmt| liveness.statement(stmt, &mut live));
+    // Stores to unused variables were already reported with them.
+    warnings.extend(liveness.dead.into_iter()
+        .filter(|(name, _)| !unused.contains(name))
+        .map(|(name, span)| Warning::UnusedAssignment { name, span }));
+}
+
+// Warns about variables that are assigned but never read, and about assignments whose
+// value is overwritten or forgotten on every path before it's read.
+pub fn check_unused(program: &[Statement]) -> Vec<Warning> {
+    let mut read_by_calls = HashSet::new();
+    let mut warnings = Vec::new();
+    for stmt in program {
+        if let Statement::Function {parameters, body, ..} = stmt {
+            let variables = function_locals(parameters, body);
+            reads(body, &variables, &mut read_by_calls);
+            let mut local_reads = HashSet::new();
+            reads(body, &[], &mut local_reads);
+            let scope = Scope { body, variables, parameters, reads: local_reads, read_by_calls: HashSet::new() };
+            check_scope(scope, &mut warnings);
+        }
+    }
+    let mut global_reads = read_by_calls.clone();
+    reads(program, &[], &mut global_reads);
+    let scope = Scope { body: program, variables: function_locals(&[], program), parameters: &[], reads: global_reads, read_by_calls };
+    check_scope(scope, &mut warnings);
+
+    warnings.sort_by_key(|warning| warning.span().start);
+    // Stores in loop conditions are looked at twice.
+    warnings.dedup();
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    fn warnings(source: &str) -> Vec<(String, &str)> {
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        check_unused(&program).iter().map(|w| (w.to_string(), &source[w.span().start..w.span().end])).collect()
+    }
+
+    #[test]
+    fn warns_about_variables_and_stores_nothing_reads() {
+        assert_eq!(warnings("\
+unused = 1;
+x = 1;
+x = 2;
+print(x);
+fn f(n) { t = n; t = 3; return n; }
+g = 5;
+fn h() { return g; }
+print(h());
+i = 0;
+while (i < 3) { i = i + 1; }
+"), [
+            ("`unused` is assigned but never read".to_string(), "unused"),
+            ("value assigned to `x` is never read".to_string(), "x = 1"),
+            ("`t` is assigned but never read".to_string(), "t"),
+        ]);
+    }
+}
@@ -0,0 +1,23 @@
+mod scope;
+mod error;
+mod resolve;
+mod types;
+mod warning;
+mod flow;
+mod lint;
+mod reachable;
+mod shadow;
+mod attributes;
+
+pub use scope::{Declaration, DeclarationKind, Scope, ScopeId, SymbolTable};
+pub use error::SemaError;
+pub(crate) use error::argument_count;
+pub use resolve::resolve;
+pub(crate) use resolve::{Occurrence, names_in_expression};
+pub use types::{Type, Types, check_types, infer_types};
+pub use warning::Warning;
+pub use flow::check_assignments;
+pub use lint::check_unused;
+pub use reachable::check_reachable;
+pub use shadow::check_shadowing;
+pub use attributes::LintScopes;
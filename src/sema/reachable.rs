@@ -0,0 +1,97 @@
+use crate::parser::ast::{Expression, Statement};
+
+use super::warning::Warning;
+
+// There's no `break`, so a loop whose condition is always true is only left by returning.
+fn always(condition: Option<&Expression>) -> bool {
+    condition.is_none_or(|condition| condition.constant_truth() == Some(true))
+}
+
+struct Reachability {
+    warnings: Vec<Warning>
+}
+
+impl Reachability {
+    // Warns about the first statement that follows one control never gets past.
+    fn body(&mut self, body: &[Statement]) -> bool {
+        let mut stops = None;
+        for stmt in body {
+            // Functions are declared before anything runs.
+            if let Statement::Function {..} = stmt {
+                self.statement(stmt);
+                continue;
+            }
+            if let Some(after) = stops {
+                self.warnings.push(Warning::Unreachable { span: stmt.span(), after });
+                return false;
+            }
+            if !self.statement(stmt) {
+                stops = Some(stmt.span());
+            }
+        }
+        stops.is_none()
+    }
+
+    // Whether control can get past `stmt`.
+    fn statement(&mut self, stmt: &Statement) -> bool {
+        match stmt {
+            Statement::Block {body, ..} => self.body(body),
+            Statement::Attributed {statement, ..} => self.statement(statement),
+            Statement::Expression {..} | Statement::Print {..} | Statement::Putc {..} => true,
+            Statement::If {then_branch, else_branch, ..} => {
+                let then_completes = self.statement(then_branch);
+                let else_completes = else_branch.as_ref().is_none_or(|else_branch| self.statement(else_branch));
+                then_completes || else_completes
+            }
+            Statement::While {condition, body, ..} => {
+                self.statement(body);
+                !always(Some(condition))
+            }
+            Statement::DoWhile {body, condition, ..} => self.statement(body) && !always(Some(condition)),
+            Statement::For {condition, body, ..} => {
+                self.statement(body);
+                !always(condition.as_ref())
+            }
+            Statement::Function {body, ..} => {
+                self.body(body);
+                true
+            }
+            Statement::Return {..} => false,
+            Statement::Import {..} => true,
+        }
+    }
+}
+
+// Warns about statements that can never run because they follow an endless loop or a
+// `return`, once per block.
+pub fn check_reachable(program: &[Statement]) -> Vec<Warning> {
+    let mut reachability = Reachability { warnings: Vec::new() };
+    reachability.body(program);
+    reachability.warnings.sort_by_key(|warning| warning.span().start);
+    reachability.warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    fn warnings(source: &str) -> Vec<(String, &str)> {
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        check_reachable(&program).iter().map(|w| (w.to_string(), &source[w.span().start..w.span().end])).collect()
+    }
+
+    #[test]
+    fn warns_once_about_what_follows_a_return_or_an_endless_loop() {
+        assert_eq!(warnings("\
+fn f(n) { return n; print(1); print(2); }
+while (true) { print(3); }
+print(4);
+print(5);
+"), [
+            ("unreachable statement".to_string(), "print(1);"),
+            ("unreachable statement".to_string(), "print(4);"),
+        ]);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::diagnostics::{Span, most_similar};
+use crate::intern::Symbol;
+use crate::parser::ast::{Expression, Statement};
+use crate::stdlib;
+
+use super::error::SemaError;
+use super::scope::{DeclarationKind, ScopeId, SymbolTable};
+
+// Functions the interpreter provides, callable unless a program declares its own, besides
+// those of the standard library.
+const BUILTINS: &[&str] = &["len"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Occurrence {
+    Read,
+    Write,
+    Call
+}
+
+pub(crate) fn names_in_expression(expr: &Expression, visit: &mut impl FnMut(Symbol, Span, Occurrence)) {
+    match expr {
+        Expression::Number(..) | Expression::Char(..) | Expression::Float(..) | Expression::Boolean(..) | Expression::String(..) => {}
+        Expression::Symbol(name, span) => visit(*name, *span, Occurrence::Read),
+        Expression::Unary {operand, ..} => names_in_expression(operand, visit),
+        Expression::Binary {left, right, ..} | Expression::Index {target: left, index: right, ..} => {
+            names_in_expression(left, visit);
+            names_in_expression(right, visit);
+        }
+        Expression::Assignment {assignee, value, ..} => {
+            match assignee.as_ref() {
+                Expression::Symbol(name, span) => visit(*name, *span, Occurrence::Write),
+                // Storing into an element reads the array.
+                assignee => names_in_expression(assignee, visit),
+            }
+            names_in_expression(value, visit);
+        }
+        Expression::Call {callee, arguments, span} => {
+            visit(*callee, *span, Occurrence::Call);
+            arguments.iter().for_each(|a| names_in_expression(a, visit));
+        }
+        Expression::Array {elements, ..} => elements.iter().for_each(|e| names_in_expression(e, visit)),
+        Expression::Conditional {condition, then_value, else_value, ..} => {
+            names_in_expression(condition, visit);
+            names_in_expression(then_value, visit);
+            names_in_expression(else_value, visit);
+        }
+    }
+}
+
+// Every name in `stmt` in source order, leaving out function declarations.
+pub(super) fn names_in_statement(stmt: &Statement, visit: &mut impl FnMut(Symbol, Span, Occurrence)) {
+    match stmt {
+        Statement::Block {body, ..} => body.iter().for_each(|s| names_in_statement(s, visit)),
+        Statement::Attributed {statement, ..} => names_in_statement(statement, visit),
+        Statement::Expression {expression, ..} | Statement::Putc {expression, ..} => names_in_expression(expression, visit),
+        Statement::Print {arguments, ..} => arguments.iter().for_each(|a| names_in_expression(a, visit)),
+        Statement::If {condition, then_branch, else_branch, ..} => {
+            names_in_expression(condition, visit);
+            names_in_statement(then_branch, visit);
+            if let Some(else_branch) = else_branch {
+                names_in_statement(else_branch, visit);
+            }
+        }
+        Statement::While {condition, body, ..} => {
+            names_in_expression(condition, visit);
+            names_in_statement(body, visit);
+        }
+        Statement::DoWhile {body, condition, ..} => {
+            names_in_statement(body, visit);
+            names_in_expression(condition, visit);
+        }
+        Statement::For {initializer, condition, step, body, ..} => {
+            for expr in [initializer, condition, step].into_iter().flatten() {
+                names_in_expression(expr, visit);
+            }
+            names_in_statement(body, visit);
+        }
+        Statement::Function {..} | Statement::Import {..} => {}
+        Statement::Return {value, ..} => {
+            if let Some(value) = value {
+                names_in_expression(value, visit);
+            }
+        }
+    }
+}
+
+struct Resolver {
+    table: SymbolTable,
+    errors: Vec<SemaError>
+}
+
+impl Resolver {
+    fn declare_assigned(&mut self, scope: ScopeId, body: &[Statement], kind: DeclarationKind) {
+        for stmt in body {
+            names_in_statement(stmt, &mut |name, span, occurrence| {
+                if occurrence == Occurrence::Write {
+                    self.table.declare(scope, name, kind, span);
+                }
+            });
+        }
+    }
+
+    fn resolve_uses(&mut self, scope: ScopeId, body: &[Statement]) {
+        for stmt in body {
+            names_in_statement(stmt, &mut |name, span, occurrence| self.resolve_use(scope, name, span, occurrence));
+        }
+    }
+
+    fn resolve_use(&mut self, scope: ScopeId, name: Symbol, span: Span, occurrence: Occurrence) {
+        let variable = occurrence != Occurrence::Call;
+        if let Some(index) = self.table.lookup_index(scope, name, variable) {
+            self.table.uses.push((span, index));
+            return;
+        }
+        let declarations = self.table.visible(scope).filter(|declaration| declaration.kind.is_variable() == variable);
+        // Only names in scope are suggested: a keyword can't be a variable or a function.
+        let suggestion = most_similar(name.as_str(), declarations, |declaration| declaration.name.as_str()).cloned();
+        self.errors.push(if variable {
+            SemaError::UndeclaredVariable { name, span, suggestion }
+        } else {
+            SemaError::UndeclaredFunction { name, span, suggestion }
+        });
+    }
+}
+
+// Builds the program's scopes and resolves every name in it to what it refers to.
+// A function's scope holds its parameters and every name it assigns to; any other
+// variable it reads must be assigned at the top level.
+pub fn resolve(program: &[Statement]) -> (SymbolTable, Vec<SemaError>) {
+    let mut resolver = Resolver { table: SymbolTable::default(), errors: Vec::new() };
+    let global = SymbolTable::GLOBAL;
+    // Functions can be called before the statement that declares them, and each name is
+    // given to one function.
+    for stmt in program {
+        if let Statement::Function {name, span, ..} = stmt {
+            if let Some(first) = resolver.table.lookup(global, *name, false) {
+                resolver.errors.push(SemaError::DuplicateFunction { name: *name, first_span: first.span, span: *span });
+                continue;
+            }
+            resolver.table.declare(global, *name, DeclarationKind::Function, *span);
+        }
+    }
+    for builtin in BUILTINS.iter().copied().chain(stdlib::BUILTINS.iter().map(|builtin| builtin.name)) {
+        resolver.table.declare(global, Symbol::intern(builtin), DeclarationKind::Builtin, Span::default());
+    }
+    resolver.declare_assigned(global, program, DeclarationKind::Global);
+
+    resolver.resolve_uses(global, program);
+    for stmt in program {
+        if let Statement::Function {name, parameters, parameter_spans, body, ..} = stmt {
+            let scope = resolver.table.push_scope(global, *name);
+            for (parameter, span) in parameters.iter().zip(parameter_spans) {
+                resolver.table.declare(scope, *parameter, DeclarationKind::Parameter, *span);
+            }
+            resolver.declare_assigned(scope, body, DeclarationKind::Local);
+            resolver.resolve_uses(scope, body);
+        }
+    }
+    // Each function's names were resolved after the top level's.
+    resolver.errors.sort_by_key(|error| error.span().start);
+    (resolver.table, resolver.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn suggests_only_names_in_scope() {
+        let source = "print(n);\nfoo();\ncount = 1;\nprint(cout);\nfn add(a, b) { return a + b; }\nprint(ad(1, 2));\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let messages: Vec<String> = resolve(&program).1.iter().map(SemaError::to_string).collect();
+        assert_eq!(messages, [
+            "use of undeclared variable `n`",
+            "call to undeclared function `foo`",
+            "use of undeclared variable `cout`, did you mean `count`?",
+            "call to undeclared function `ad`, did you mean `add`?",
+        ]);
+    }
+
+    #[test]
+    fn a_function_is_defined_once() {
+        let source = "fn f() { return 1; }\nfn g() { return 2; }\nfn f(a) { return a; }\nprint(f());\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let errors = resolve(&program).1;
+        assert_eq!(errors.len(), 1);
+        let SemaError::DuplicateFunction { name, first_span, span } = &errors[0] else { panic!("{:?}", errors) };
+        assert_eq!(errors[0].to_string(), format!("function `{}` is already defined", name));
+        assert!(source[first_span.start..].starts_with("f() {"));
+        assert!(source[span.start..].starts_with("f(a) {"));
+    }
+}
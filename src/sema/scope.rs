@@ -0,0 +1,106 @@
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+
+pub type ScopeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Function,
+    // Provided by the interpreter rather than declared; these have an empty span.
+    Builtin,
+    Global,
+    Parameter,
+    Local
+}
+
+impl DeclarationKind {
+    pub fn is_variable(self) -> bool {
+        !matches!(self, DeclarationKind::Function | DeclarationKind::Builtin)
+    }
+}
+
+// A name and where it was first bound: a function's name, or the first assignment to a
+// variable. Parameters point at their function's name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: Symbol,
+    pub kind: DeclarationKind,
+    pub span: Span,
+    pub scope: ScopeId
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    pub parent: Option<ScopeId>,
+    // The function this is the body of; `None` for the global scope.
+    pub function: Option<Symbol>,
+    // Indices into the table's declarations, in source order.
+    pub declarations: Vec<usize>
+}
+
+// The global scope is always the first, with one scope per function beneath it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolTable {
+    pub scopes: Vec<Scope>,
+    pub declarations: Vec<Declaration>,
+    // Each name that resolved, by where it was used, with its declaration's index.
+    pub uses: Vec<(Span, usize)>
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self {
+            scopes: vec![Scope { parent: None, function: None, declarations: Vec::new() }],
+            declarations: Vec::new(),
+            uses: Vec::new(),
+        }
+    }
+}
+
+impl SymbolTable {
+    pub const GLOBAL: ScopeId = 0;
+
+    pub(super) fn push_scope(&mut self, parent: ScopeId, function: Symbol) -> ScopeId {
+        self.scopes.push(Scope { parent: Some(parent), function: Some(function), declarations: Vec::new() });
+        self.scopes.len() - 1
+    }
+
+    // Names already declared in `scope` keep their first declaration.
+    pub(super) fn declare(&mut self, scope: ScopeId, name: Symbol, kind: DeclarationKind, span: Span) {
+        let declared = self.scopes[scope].declarations.iter().any(|&i| {
+            let declaration = &self.declarations[i];
+            declaration.name == name && declaration.kind.is_variable() == kind.is_variable()
+        });
+        if !declared {
+            self.declarations.push(Declaration { name, kind, span, scope });
+            self.scopes[scope].declarations.push(self.declarations.len() - 1);
+        }
+    }
+
+    // The declaration `name` refers to from `scope`, looking outwards. Functions and
+    // variables are named separately.
+    pub fn lookup(&self, scope: ScopeId, name: Symbol, variable: bool) -> Option<&Declaration> {
+        self.lookup_index(scope, name, variable).map(|i| &self.declarations[i])
+    }
+
+    pub(super) fn lookup_index(&self, scope: ScopeId, name: Symbol, variable: bool) -> Option<usize> {
+        self.visible_indices(scope).find(|&i| {
+            let declaration = &self.declarations[i];
+            declaration.name == name && declaration.kind.is_variable() == variable
+        })
+    }
+
+    // Every declaration visible from `scope`, innermost first.
+    pub fn visible(&self, scope: ScopeId) -> impl Iterator<Item = &Declaration> {
+        self.visible_indices(scope).map(|i| &self.declarations[i])
+    }
+
+    fn visible_indices(&self, scope: ScopeId) -> impl Iterator<Item = usize> {
+        std::iter::successors(Some(scope), |&scope| self.scopes[scope].parent)
+            .flat_map(|scope| self.scopes[scope].declarations.iter().copied())
+    }
+
+    pub fn declaration_at(&self, span: Span) -> Option<&Declaration> {
+        self.uses.iter().find(|(use_span, _)| *use_span == span).map(|&(_, i)| &self.declarations[i])
+    }
+}
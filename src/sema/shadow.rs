@@ -0,0 +1,49 @@
+use crate::parser::ast::Statement;
+
+use super::resolve::resolve;
+use super::scope::{DeclarationKind, SymbolTable};
+use super::warning::Warning;
+
+// Warns about parameters and locals with the same name as a global, which the function
+// then can't read.
+pub fn check_shadowing(program: &[Statement]) -> Vec<Warning> {
+    let (table, _) = resolve(program);
+    let mut warnings = Vec::new();
+    for scope in &table.scopes[SymbolTable::GLOBAL + 1..] {
+        for declaration in scope.declarations.iter().map(|&i| &table.declarations[i]) {
+            if let Some(outer) = table.lookup(SymbolTable::GLOBAL, declaration.name, true) {
+                warnings.push(Warning::Shadowing {
+                    name: declaration.name,
+                    span: declaration.span,
+                    outer: outer.span,
+                    parameter: declaration.kind == DeclarationKind::Parameter,
+                });
+            }
+        }
+    }
+    warnings.sort_by_key(|warning| warning.span().start);
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Span;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn points_at_the_parameter_or_local_that_shadows_the_global() {
+        let source = "g = 1;\nh = 2;\nfn f(x, g) {\n    h = 3;\n    return x;\n}\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let spans: Vec<(String, Span)> = check_shadowing(&program).iter().map(|w| (w.to_string(), w.span())).collect();
+        let at = |text: &str, after: &str| {
+            let start = source.find(after).unwrap() + after.len();
+            Span::new(start, start + text.len())
+        };
+        assert_eq!(spans, [
+            ("parameter `g` shadows a global variable".to_string(), at("g", "fn f(x, ")),
+            ("local variable `h` shadows a global variable".to_string(), at("h", "{\n    ")),
+        ]);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostics::Span;
+
+use crate::intern::Symbol;
+use crate::lexer::TokenKind;
+use crate::parser::ast::{Expression, Statement, function_locals};
+use crate::stdlib::{self, Kind};
+
+use super::error::SemaError;
+
+// What the checker knows about a value. Calls, array elements and variables that haven't
+// been assigned yet are `Unknown`, which is allowed anywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Integer,
+    Float,
+    Bool,
+    String,
+    Array,
+    Unknown
+}
+
+impl Type {
+    // Booleans count as 0 and 1, as in the interpreter.
+    fn is_integer(self) -> bool {
+        matches!(self, Type::Integer | Type::Bool | Type::Unknown)
+    }
+
+    fn is_number(self) -> bool {
+        self.is_integer() || self == Type::Float
+    }
+
+    fn could_be_string(self) -> bool {
+        matches!(self, Type::String | Type::Unknown)
+    }
+
+    pub(super) fn with_article(self) -> String {
+        match self {
+            Type::Integer | Type::Array | Type::Unknown => format!("an {}", self),
+            _ => format!("a {}", self),
+        }
+    }
+
+    // Integers stay integers unless the other side is a float.
+    fn arithmetic(self, other: Type) -> Type {
+        match (self, other) {
+            (Type::Float, _) | (_, Type::Float) => Type::Float,
+            (Type::Unknown, _) | (_, Type::Unknown) => Type::Unknown,
+            _ => Type::Integer,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Integer => "integer",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Array => "array",
+            Type::Unknown => "unknown",
+        })
+    }
+}
+
+// The operators' rules are the interpreter's, except that strings and arrays can't be
+// used as conditions even though they have a truth value at runtime.
+fn unary(operator: &TokenKind, operand: Type) -> Option<Type> {
+    match operator {
+        TokenKind::OpNot if operand.is_number() => Some(Type::Bool),
+        TokenKind::OpSubtract | TokenKind::OpAdd if operand.is_number() => Some(operand.arithmetic(Type::Integer)),
+        TokenKind::OpBitNot if operand.is_integer() => Some(Type::Integer),
+        _ => None,
+    }
+}
+
+fn binary(operator: &TokenKind, left: Type, right: Type) -> Option<Type> {
+    let numbers = left.is_number() && right.is_number();
+    let strings = left.could_be_string() && right.could_be_string();
+    match operator {
+        TokenKind::OpAdd if numbers => Some(left.arithmetic(right)),
+        TokenKind::OpAdd if strings => Some(Type::String),
+        TokenKind::OpSubtract | TokenKind::OpMultiply | TokenKind::OpDivide | TokenKind::OpMod if numbers => {
+            Some(left.arithmetic(right))
+        }
+        TokenKind::OpBitAnd | TokenKind::OpBitOr | TokenKind::OpBitXor | TokenKind::OpShiftLeft | TokenKind::OpShiftRight
+            if left.is_integer() && right.is_integer() => Some(Type::Integer),
+        TokenKind::OpLess | TokenKind::OpLessEqual | TokenKind::OpGreater | TokenKind::OpGreaterEqual if numbers => {
+            Some(Type::Bool)
+        }
+        TokenKind::OpEqual | TokenKind::OpNotEqual if numbers || strings => Some(Type::Bool),
+        TokenKind::OpAnd | TokenKind::OpOr if numbers => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+// A variable's type is inferred from the first assignment to it whose type is known.
+struct Function {
+    name: Symbol,
+    locals: Vec<Symbol>,
+    types: HashMap<Symbol, (Type, Span)>,
+    // What its `return`s give so far, or `None` before the first.
+    returns: Option<Type>
+}
+
+// The types of the variables, by the function they're local to, if any, and of what each
+// function returns.
+#[derive(Default)]
+struct Known {
+    variables: HashMap<(Option<Symbol>, Symbol), Type>,
+    returns: HashMap<Symbol, Type>
+}
+
+struct Checker {
+    // User functions and how many parameters they take. They take precedence over builtins.
+    functions: HashMap<Symbol, usize>,
+    globals: HashMap<Symbol, (Type, Span)>,
+    // The function being checked, if any.
+    function: Option<Function>,
+    errors: Vec<SemaError>,
+    // Each expression's type, by its span.
+    expressions: HashMap<Span, Type>,
+    // What an earlier pass over the program found, for variables used ahead of their first
+    // assignment and for calls; and what this one has found in the functions it's checked.
+    earlier: Known,
+    found: Known
+}
+
+impl Checker {
+    fn new(program: &[Statement], earlier: Known) -> Self {
+        let functions = program.iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Function {name, parameters, ..} => Some((*name, parameters.len())),
+                _ => None,
+            })
+            .collect();
+        Checker {
+            functions, globals: HashMap::new(), function: None, errors: Vec::new(), expressions: HashMap::new(), earlier,
+            found: Known::default(),
+        }
+    }
+
+    // The function `name` is local to, if any.
+    fn owner(&self, name: Symbol) -> Option<Symbol> {
+        self.function.as_ref().filter(|function| function.locals.contains(&name)).map(|function| function.name)
+    }
+
+    fn variables(&mut self, name: Symbol) -> &mut HashMap<Symbol, (Type, Span)> {
+        match &mut self.function {
+            Some(function) if function.locals.contains(&name) => &mut function.types,
+            _ => &mut self.globals,
+        }
+    }
+
+    fn variable(&mut self, name: Symbol) -> Type {
+        match self.variables(name).get(&name) {
+            Some(&(found, _)) => found,
+            None => self.earlier.variables.get(&(self.owner(name), name)).copied().unwrap_or(Type::Unknown),
+        }
+    }
+
+    fn assign(&mut self, name: Symbol, found: Type, span: Span) {
+        if found == Type::Unknown {
+            return;
+        }
+        let variables = self.variables(name);
+        match variables.get(&name) {
+            Some(&(first, first_span)) if first != found => {
+                self.errors.push(SemaError::ConflictingAssignment { name, first, first_span, found, span });
+            }
+            Some(_) => {}
+            None => { variables.insert(name, (found, span)); }
+        }
+    }
+
+    fn expect(&mut self, expr: &Expression, expected: &'static str, accepts: fn(Type) -> bool) -> Type {
+        let found = self.expression(expr);
+        if !accepts(found) {
+            self.errors.push(SemaError::WrongType { expected, found, span: expr.span() });
+        }
+        found
+    }
+
+    fn condition(&mut self, condition: &Expression) {
+        self.expect(condition, "a number or bool for the condition", Type::is_number);
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Type {
+        let found = self.infer(expr);
+        self.expressions.insert(expr.span(), found);
+        found
+    }
+
+    fn infer(&mut self, expr: &Expression) -> Type {
+        match expr {
+            Expression::Number(..) | Expression::Char(..) => Type::Integer,
+            Expression::Float(..) => Type::Float,
+            Expression::Boolean(..) => Type::Bool,
+            Expression::String(..) => Type::String,
+            Expression::Symbol(name, _) => self.variable(*name),
+            Expression::Unary {operator, operand, ..} => {
+                let found = self.expression(operand);
+                unary(operator, found).unwrap_or_else(|| {
+                    self.errors.push(SemaError::InvalidOperand { operator: operator.clone(), operand: found, span: expr.span() });
+                    Type::Unknown
+                })
+            }
+            Expression::Binary {left, operator, right, ..} => {
+                let (l, r) = (self.expression(left), self.expression(right));
+                binary(operator, l, r).unwrap_or_else(|| {
+                    self.errors.push(SemaError::TypeMismatch { operator: operator.clone(), left: l, right: r, span: expr.span() });
+                    Type::Unknown
+                })
+            }
+            Expression::Assignment {assignee, value, ..} => {
+                if let Expression::Index {target, index, ..} = assignee.as_ref() {
+                    self.element(target, index);
+                }
+                let found = self.expression(value);
+                if let Expression::Symbol(name, _) = assignee.as_ref() {
+                    self.assign(*name, found, expr.span());
+                }
+                found
+            }
+            Expression::Call {callee, arguments, span} => {
+                let builtin = stdlib::builtin(callee.as_str()).filter(|_| !self.functions.contains_key(callee));
+                let expected = match self.functions.get(callee) {
+                    Some(&parameters) => Some(parameters),
+                    None if callee.as_str() == "len" => Some(1),
+                    None => builtin.map(|builtin| builtin.arity()),
+                };
+                if let Some(expected) = expected && expected != arguments.len() {
+                    self.errors.push(SemaError::ArgumentCount { name: *callee, expected, found: arguments.len(), span: *span });
+                }
+                if callee.as_str() == "len" && !self.functions.contains_key(callee) && let [argument] = arguments.as_slice() {
+                    let accepts = |found| matches!(found, Type::Array | Type::String | Type::Unknown);
+                    self.expect(argument, "an array or a string", accepts);
+                    return Type::Integer;
+                }
+                arguments.iter().for_each(|argument| { self.expression(argument); });
+                match builtin {
+                    Some(builtin) if builtin.returns == Kind::String => Type::String,
+                    Some(_) => Type::Unknown,
+                    None => self.earlier.returns.get(callee).copied().unwrap_or(Type::Unknown),
+                }
+            }
+            Expression::Conditional {condition, then_value, else_value, ..} => {
+                self.condition(condition);
+                let (then_type, else_type) = (self.expression(then_value), self.expression(else_value));
+                if then_type == else_type { then_type } else { Type::Unknown }
+            }
+            Expression::Array {elements, ..} => {
+                elements.iter().for_each(|element| { self.expression(element); });
+                Type::Array
+            }
+            Expression::Index {target, index, ..} => {
+                self.element(target, index);
+                Type::Unknown
+            }
+        }
+    }
+
+    fn element(&mut self, target: &Expression, index: &Expression) {
+        self.expect(target, "an array to index", |found| matches!(found, Type::Array | Type::Unknown));
+        self.expect(index, "an integer index", Type::is_integer);
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Block {body, ..} => body.iter().for_each(|s| self.statement(s)),
+            Statement::Attributed {statement, ..} => self.statement(statement),
+            Statement::Function {name, parameters, body, ..} => {
+                let locals = function_locals(parameters, body);
+                self.function = Some(Function { name: *name, locals, types: HashMap::new(), returns: None });
+                body.iter().for_each(|s| self.statement(s));
+                let function = self.function.take().unwrap();
+                for (local, (found, _)) in function.types {
+                    self.found.variables.insert((Some(*name), local), found);
+                }
+                // Falling off the end returns 0.
+                let returns = match body.last() {
+                    Some(Statement::Return {value: Some(_), ..}) => function.returns.unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                };
+                self.found.returns.insert(*name, returns);
+            }
+            Statement::Expression {expression, ..} => { self.expression(expression); }
+            Statement::Print {arguments, ..} => arguments.iter().for_each(|a| { self.expression(a); }),
+            Statement::Putc {expression, ..} => { self.expect(expression, "an integer character code", Type::is_integer); }
+            Statement::If {condition, then_branch, else_branch, ..} => {
+                self.condition(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::While {condition, body, ..} | Statement::DoWhile {body, condition, ..} => {
+                self.condition(condition);
+                self.statement(body);
+            }
+            Statement::For {initializer, condition, step, body, ..} => {
+                for expr in [initializer, step].into_iter().flatten() {
+                    self.expression(expr);
+                }
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+                self.statement(body);
+            }
+            Statement::Return {value, ..} => {
+                let found = value.as_ref().map_or(Type::Unknown, |value| self.expression(value));
+                if let Some(function) = &mut self.function {
+                    function.returns = match function.returns {
+                        Some(returns) if returns != found => Some(Type::Unknown),
+                        _ => Some(found),
+                    };
+                }
+            }
+            Statement::Import {..} => {}
+        }
+    }
+
+    fn check(mut self, program: &[Statement]) -> Self {
+        program.iter().for_each(|stmt| self.statement(stmt));
+        for (name, (found, _)) in &self.globals {
+            self.found.variables.insert((None, *name), *found);
+        }
+        self
+    }
+}
+
+// The types the checker found for a program's expressions.
+pub struct Types {
+    expressions: HashMap<Span, Type>
+}
+
+impl Types {
+    // The type of `expr`, which must be from the program the types are of.
+    pub fn of(&self, expr: &Expression) -> Type {
+        self.expressions.get(&expr.span()).copied().unwrap_or(Type::Unknown)
+    }
+}
+
+// The type of each expression in the program, for the backends. The program is checked
+// twice, and the second time uses are known by what the first found: the variables'
+// types, even ahead of their first assignment, and what functions that always return
+// one type return.
+pub fn infer_types(program: &[Statement]) -> Types {
+    let first = Checker::new(program, Known::default()).check(program);
+    let second = Checker::new(program, first.found).check(program);
+    Types { expressions: second.expressions }
+}
+
+// Reports operations that would fail on the types of their operands, calls with the wrong
+// number of arguments, and variables assigned values of different types, in source order.
+pub fn check_types(program: &[Statement]) -> Vec<SemaError> {
+    let mut checker = Checker::new(program, Known::default()).check(program);
+    checker.errors.sort_by_key(|error| error.span().start);
+    checker.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, parse_program, tokenize};
+
+    fn check(source: &str) -> Vec<String> {
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        check_types(&program).iter().map(|error| format!("{} {}", error.code(), error)).collect()
+    }
+
+    #[test]
+    fn calls_pass_as_many_arguments_as_are_taken() {
+        assert_eq!(check("fn f(a) { return a; }\nprint(f(1, 2), len(), abs(1));\n"), [
+            "E0307 `f` takes 1 argument but 2 were given",
+            "E0307 `len` takes 1 argument but 0 were given",
+        ]);
+        // A user function takes precedence over the builtin with its name.
+        assert!(check("fn abs(a, b) { return a; }\nprint(abs(1, 2));\n").is_empty());
+    }
+
+    #[test]
+    fn infers_variables_ahead_of_their_assignment_and_what_calls_return() {
+        let source = "fn show() { print(b, f()); }\nfn f() { return 1 < 2; }\nb = true;\nprint(b ? 1 : 2.5);\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let types = infer_types(&program);
+        let Statement::Function {body, ..} = &program[0] else { unreachable!() };
+        let Statement::Print {arguments, ..} = &body[0] else { unreachable!() };
+        assert_eq!(arguments.iter().map(|argument| types.of(argument)).collect::<Vec<_>>(), [Type::Bool, Type::Bool]);
+        let Statement::Print {arguments, ..} = &program[3] else { unreachable!() };
+        assert_eq!(types.of(&arguments[0]), Type::Unknown);
+    }
+}
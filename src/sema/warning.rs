@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::diagnostics::{Diagnostic, Span};
+use crate::intern::Symbol;
+
+// Likely mistakes that still leave a program that runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    // A read that some path reaches without assigning the variable first, where the
+    // interpreter reads zero.
+    UseBeforeAssignment { name: Symbol, span: Span },
+    // A variable that's never read, at its first assignment.
+    UnusedVariable { name: Symbol, span: Span },
+    // An assignment whose value is always overwritten or forgotten before it's read.
+    UnusedAssignment { name: Symbol, span: Span },
+    // A statement that can't run, and the one control never gets past before it.
+    Unreachable { span: Span, after: Span },
+    // A function's parameter or local with the same name as a global.
+    // Parameters point at their function's name.
+    Shadowing { name: Symbol, span: Span, outer: Span, parameter: bool }
+}
+
+impl Warning {
+    pub fn span(&self) -> Span {
+        match self {
+            Warning::UseBeforeAssignment { span, .. } | Warning::UnusedVariable { span, .. }
+            | Warning::UnusedAssignment { span, .. } | Warning::Unreachable { span, .. }
+            | Warning::Shadowing { span, .. } => *span,
+        }
+    }
+
+    // Each lint's code; see `LINTS` for their names.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::UseBeforeAssignment { .. } => "W0101",
+            Warning::UnusedVariable { .. } => "W0102",
+            Warning::UnusedAssignment { .. } => "W0103",
+            Warning::Unreachable { .. } => "W0104",
+            Warning::Shadowing { .. } => "W0105",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UseBeforeAssignment { name, .. } => write!(f, "`{}` may be read before it's assigned", name),
+            Warning::UnusedVariable { name, .. } => write!(f, "`{}` is assigned but never read", name),
+            Warning::UnusedAssignment { name, .. } => write!(f, "value assigned to `{}` is never read", name),
+            Warning::Unreachable { .. } => write!(f, "unreachable statement"),
+            Warning::Shadowing { name, parameter: true, .. } => write!(f, "parameter `{}` shadows a global variable", name),
+            Warning::Shadowing { name, .. } => write!(f, "local variable `{}` shadows a global variable", name),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        let diagnostic = Diagnostic::warning(warning.to_string(), warning.span()).with_code(warning.code());
+        match warning {
+            Warning::Unreachable { after, .. } => diagnostic.with_note("any code after this is unreachable", *after),
+            Warning::Shadowing { name, outer, .. } => diagnostic.with_note(format!("the global `{}` is assigned here", name), *outer),
+            _ => diagnostic,
+        }
+    }
+}
@@ -0,0 +1,1340 @@
+// The functions every program can call without declaring them: `abs`, `min`, `max`, `pow`,
+// `chr`, `ord`, `str` and `int`, `getc` and `readint` to read input, `rand` and `seed`, and
+// `clock` and `sleep`, `fopen`, `fgetc`, `fputs` and `fclose` for files, which a program may
+// only use if what runs it allows that, `argc` and `argv`, and `getenv`, which is allowed or
+// not in the same way. Each is written once
+// over `Value`s, which the interpreter calls directly and the VM through
+// `Instruction::Builtin`, and once each in C, Rust and JavaScript for those backends'
+// runtimes. A program's own function of the same name takes precedence.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::Value;
+
+// What the builtins that aren't pure act on, one for each run of a program.
+pub struct Context<'a> {
+    // Where `getc` and `readint` read from.
+    input: Box<dyn Read + 'a>,
+    // The byte `readint` read past the end of its integer, which the next read gets.
+    peeked: Option<u8>,
+    // The state of `rand`'s generator, which `seed` sets. Like C's `rand`, it starts out the
+    // same every run.
+    random: u64,
+    clock: Clock,
+    capabilities: Capabilities,
+    // The files `fopen` has opened, by the number it gave for each; `fclose` leaves a gap,
+    // which the next one fills.
+    files: Vec<Option<OpenFile>>,
+    // The program's name and then its arguments, for `argv`.
+    arguments: Vec<String>
+}
+
+// What a program may reach beyond its input and what it prints: nothing, unless what runs it
+// allows more.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    // Opening files with `fopen`, to read or write.
+    pub files: bool,
+    // Reading environment variables with `getenv`.
+    pub env: bool
+}
+
+enum OpenFile {
+    Read(BufReader<File>),
+    Write(BufWriter<File>)
+}
+
+// What `clock` and `sleep` go by.
+enum Clock {
+    // The time since the program started.
+    Real(Instant),
+    // How long the program has slept, without really sleeping, so that a program that
+    // times itself does the same thing every run.
+    Virtual(u64)
+}
+
+impl Default for Context<'_> {
+    fn default() -> Self {
+        Self::with_input(io::stdin())
+    }
+}
+
+impl<'a> Context<'a> {
+    // A context whose program reads `input` instead of stdin, such as a `&[u8]`. It's read a
+    // byte at a time, so a file should be behind a `BufReader`.
+    pub fn with_input(input: impl Read + 'a) -> Self {
+        // This crate's own tests go by virtual time, so that they're the same every run.
+        let clock = if cfg!(test) { Clock::Virtual(0) } else { Clock::Real(Instant::now()) };
+        Context { input: Box::new(input), peeked: None, random: 0, clock, capabilities: Capabilities::default(), files: Vec::new(), arguments: Vec::new() }
+    }
+
+    // Gives the program `arguments`, the first of which is its name, as C's `main` is.
+    pub fn arguments(&mut self, arguments: Vec<String>) {
+        self.arguments = arguments;
+    }
+
+    // Lets the program do what `capabilities` allows.
+    pub fn allow(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    // Where in `files` the file `value` is, as `name` was given it, if it's open.
+    fn file(&self, name: &str, value: &Value) -> Result<usize, String> {
+        let n = value.integer().ok_or_else(|| expected(name, "a file", value))?;
+        usize::try_from(n).ok()
+            .filter(|&i| self.files.get(i).is_some_and(Option::is_some))
+            .ok_or_else(|| format!("`{}` was given {}, which isn't an open file", name, n))
+    }
+
+    // Has `clock` and `sleep` use a clock that only `sleep` moves, and then straight away,
+    // such as for tests.
+    pub fn virtual_time(&mut self) {
+        self.clock = Clock::Virtual(0);
+    }
+
+    // Starts `rand` over from `seed`, as `seed(seed)` does.
+    pub fn seed(&mut self, seed: i64) {
+        self.random = seed as u64;
+    }
+
+    // The next number from SplitMix64, whose whole state is one integer.
+    fn next_random(&mut self) -> u64 {
+        self.random = self.random.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let z = (self.random ^ (self.random >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // The next byte of the input, or `None` at its end.
+    fn byte(&mut self) -> Result<Option<u8>, String> {
+        if let Some(byte) = self.peeked.take() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0];
+        loop {
+            match self.input.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("can't read the input: {}", e)),
+            }
+        }
+    }
+}
+
+// The type of a builtin's parameter or result in C. The interpreter and the VM take any
+// value the builtin can make sense of, such as a float for `abs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Long,
+    String
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub takes: &'static [Kind],
+    pub returns: Kind,
+    // `Err` is why the arguments can't be taken, such as `chr(-1)`.
+    pub call: fn(&mut Context, &[Value]) -> Result<Value, String>,
+    // Its definition in C, as `tl_` and its name. It needs `headers` as well as <stdio.h>,
+    // and `setup` to run at the start of `main`, which takes `argc` and `argv` if it names
+    // them.
+    pub c: &'static str,
+    pub headers: &'static [&'static str],
+    pub setup: &'static str,
+    // Its definition in Rust, also as `tl_` and its name, last after the items it shares
+    // with other builtins, which are written once however many use them; and what to run
+    // at the start of `main` there.
+    pub rust: &'static [&'static str],
+    pub rust_setup: &'static str,
+    // Its definition in JavaScript in the same way, over `BigInt`s, or nothing for what a
+    // script may not do in a browser, such as reading input or files.
+    pub js: &'static [&'static str]
+}
+
+impl Builtin {
+    pub fn arity(&self) -> usize {
+        self.takes.len()
+    }
+}
+
+// What the Rust definitions of the builtins share. Failing stops the program the way a
+// runtime error in the interpreter does, keeping what it's printed so far.
+pub(crate) const RUST_FAIL: &str = "\
+fn tl_fail(message: String) -> ! {
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    eprintln!(\"error: {}\", message);
+    std::process::exit(1);
+}
+";
+
+const RUST_INPUT: &str = "\
+// The next byte of stdin, which is only taken from it if `take` is set.
+fn tl_byte(take: bool) -> Option<u8> {
+    let mut input = std::io::stdin().lock();
+    let byte = std::io::BufRead::fill_buf(&mut input).ok()?.first().copied();
+    if take && byte.is_some() {
+        std::io::BufRead::consume(&mut input, 1);
+    }
+    byte
+}
+";
+
+const RUST_RANDOM: &str = "static TL_RANDOM: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);\n";
+
+const RUST_START: &str = "static TL_START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();\n";
+
+const RUST_FILES: &str = "\
+enum TlFile {
+    Read(std::io::BufReader<std::fs::File>),
+    Write(std::io::BufWriter<std::fs::File>)
+}
+
+static TL_FILES: std::sync::Mutex<Vec<Option<TlFile>>> = std::sync::Mutex::new(Vec::new());
+
+// Where in `TL_FILES` the file `file` is, as `name` was given it, if it's open.
+fn tl_file(name: &str, file: i64) -> usize {
+    let open = TL_FILES.lock().unwrap().get(file as usize).is_some_and(Option::is_some);
+    if file < 0 || !open {
+        tl_fail(format!(\"`{}` was given {}, which isn't an open file\", name, file));
+    }
+    file as usize
+}
+";
+
+const RUST_ARGUMENTS: &str = "static TL_ARGUMENTS: std::sync::LazyLock<Vec<String>> = std::sync::LazyLock::new(|| std::env::args().collect());\n";
+
+// What the JavaScript definitions share. Under Node, failing stops the program as in Rust;
+// in a browser it throws, after writing out what's been printed.
+pub(crate) const JS_FAIL: &str = "\
+function tl_fail(message) {
+    if (typeof process === \"object\" && process.exit) {
+        process.stderr.write(`error: ${message}\\n`);
+        process.exit(1);
+    }
+    if (output) {
+        console.log(output);
+        output = \"\";
+    }
+    throw new Error(message);
+}
+";
+
+const JS_RANDOM: &str = "let tl_random = 0n;\n";
+
+// Bytecode names a builtin by its index here, so new ones go at the end.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "abs",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: abs,
+        c: "static long tl_abs(long x) {\n    return x < 0 ? (long) -(unsigned long) x : x;\n}\n",
+        headers: &[],
+        setup: "",
+        rust: &["\
+fn tl_abs(x: i64) -> i64 {
+    x.wrapping_abs()
+}
+"],
+        rust_setup: "",
+        js: &["\
+function tl_abs(x) {
+    return BigInt.asIntN(64, x < 0n ? -x : x);
+}
+"],
+    },
+    Builtin {
+        name: "min",
+        takes: &[Kind::Long, Kind::Long],
+        returns: Kind::Long,
+        call: min,
+        c: "static long tl_min(long a, long b) {\n    return a < b ? a : b;\n}\n",
+        headers: &[],
+        setup: "",
+        rust: &["\
+fn tl_min(a: i64, b: i64) -> i64 {
+    a.min(b)
+}
+"],
+        rust_setup: "",
+        js: &["\
+function tl_min(a, b) {
+    return a < b ? a : b;
+}
+"],
+    },
+    Builtin {
+        name: "max",
+        takes: &[Kind::Long, Kind::Long],
+        returns: Kind::Long,
+        call: max,
+        c: "static long tl_max(long a, long b) {\n    return a > b ? a : b;\n}\n",
+        headers: &[],
+        setup: "",
+        rust: &["\
+fn tl_max(a: i64, b: i64) -> i64 {
+    a.max(b)
+}
+"],
+        rust_setup: "",
+        js: &["\
+function tl_max(a, b) {
+    return a > b ? a : b;
+}
+"],
+    },
+    Builtin {
+        name: "pow",
+        takes: &[Kind::Long, Kind::Long],
+        returns: Kind::Long,
+        call: pow,
+        c: "\
+static long tl_pow(long base, long exponent) {
+    unsigned long result = 1, square = (unsigned long) base;
+    if (exponent < 0) {
+        fprintf(stderr, \"error: `pow` can't raise an integer to a negative power\\n\");
+        exit(1);
+    }
+    for (; exponent > 0; exponent >>= 1) {
+        if (exponent & 1) {
+            result *= square;
+        }
+        square *= square;
+    }
+    return (long) result;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_pow(base: i64, exponent: i64) -> i64 {
+    if exponent < 0 {
+        tl_fail(\"`pow` can't raise an integer to a negative power\".to_string());
+    }
+    let (mut result, mut base, mut exponent) = (1i64, base, exponent as u64);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+"],
+        rust_setup: "",
+        js: &[JS_FAIL, "\
+function tl_pow(base, exponent) {
+    if (exponent < 0n) {
+        tl_fail(\"`pow` can't raise an integer to a negative power\");
+    }
+    let result = 1n;
+    for (; exponent > 0n; exponent >>= 1n) {
+        if (exponent & 1n) {
+            result = BigInt.asIntN(64, result * base);
+        }
+        base = BigInt.asIntN(64, base * base);
+    }
+    return result;
+}
+"],
+    },
+    Builtin {
+        name: "chr",
+        takes: &[Kind::Long],
+        returns: Kind::String,
+        call: chr,
+        c: "\
+static const char *tl_chr(long code) {
+    unsigned char *s = malloc(5);
+    if (code < 0 || code > 0x10FFFF || (code >= 0xD800 && code <= 0xDFFF)) {
+        fprintf(stderr, \"error: %ld isn't a character\\n\", code);
+        exit(1);
+    }
+    if (code < 0x80) {
+        s[0] = code; s[1] = 0;
+    } else if (code < 0x800) {
+        s[0] = 0xC0 | code >> 6; s[1] = 0x80 | (code & 0x3F); s[2] = 0;
+    } else if (code < 0x10000) {
+        s[0] = 0xE0 | code >> 12; s[1] = 0x80 | (code >> 6 & 0x3F); s[2] = 0x80 | (code & 0x3F); s[3] = 0;
+    } else {
+        s[0] = 0xF0 | code >> 18; s[1] = 0x80 | (code >> 12 & 0x3F); s[2] = 0x80 | (code >> 6 & 0x3F);
+        s[3] = 0x80 | (code & 0x3F); s[4] = 0;
+    }
+    return (const char *)s;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_chr(code: i64) -> String {
+    match u32::try_from(code).ok().and_then(char::from_u32) {
+        Some(c) => c.to_string(),
+        None => tl_fail(format!(\"{} isn't a character\", code)),
+    }
+}
+"],
+        rust_setup: "",
+        js: &[JS_FAIL, "\
+function tl_chr(code) {
+    if (code < 0n || code > 0x10ffffn || (code >= 0xd800n && code <= 0xdfffn)) {
+        tl_fail(`${code} isn't a character`);
+    }
+    return String.fromCodePoint(Number(code));
+}
+"],
+    },
+    Builtin {
+        name: "ord",
+        takes: &[Kind::String],
+        returns: Kind::Long,
+        call: ord,
+        c: "\
+static long tl_ord(const char *s) {
+    const unsigned char *u = (const unsigned char *)s;
+    long code;
+    int n = u[0] < 0x80 ? 0 : u[0] < 0xE0 ? 1 : u[0] < 0xF0 ? 2 : 3;
+    if (!u[0] || u[n + 1]) {
+        fprintf(stderr, \"error: `ord` takes a string of one character\\n\");
+        exit(1);
+    }
+    code = n == 0 ? u[0] : u[0] & (0x3F >> n);
+    while (n-- > 0) {
+        code = code << 6 | (*++u & 0x3F);
+    }
+    return code;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_ord(s: String) -> i64 {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c as i64,
+        _ => tl_fail(format!(\"`ord` takes a string of one character, not {:?}\", s)),
+    }
+}
+"],
+        rust_setup: "",
+        js: &[JS_FAIL, "\
+function tl_ord(s) {
+    const chars = [...s];
+    if (chars.length !== 1) {
+        tl_fail(`\\`ord\\` takes a string of one character, not ${JSON.stringify(s)}`);
+    }
+    return BigInt(chars[0].codePointAt(0));
+}
+"],
+    },
+    Builtin {
+        name: "str",
+        takes: &[Kind::Long],
+        returns: Kind::String,
+        call: str,
+        c: "\
+static const char *tl_str(long x) {
+    char *s = malloc(24);
+    sprintf(s, \"%ld\", x);
+    return s;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &["\
+fn tl_str(x: i64) -> String {
+    x.to_string()
+}
+"],
+        rust_setup: "",
+        js: &["\
+function tl_str(x) {
+    return String(x);
+}
+"],
+    },
+    Builtin {
+        name: "int",
+        takes: &[Kind::String],
+        returns: Kind::Long,
+        call: int,
+        c: "\
+static long tl_int(const char *s) {
+    char *end;
+    errno = 0;
+    long n = strtol(s, &end, 10);
+    if (end == s || *end || errno == ERANGE) {
+        fprintf(stderr, \"error: can't convert \\\"%s\\\" to an integer\\n\", s);
+        exit(1);
+    }
+    return n;
+}
+",
+        headers: &["errno.h", "stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_int(s: String) -> i64 {
+    s.trim().parse().unwrap_or_else(|_| tl_fail(format!(\"can't convert {:?} to an integer\", s)))
+}
+"],
+        rust_setup: "",
+        js: &[JS_FAIL, "\
+function tl_int(s) {
+    const digits = s.trim();
+    const n = /^[+-]?[0-9]+$/.test(digits) ? BigInt(digits) : null;
+    if (n === null || n !== BigInt.asIntN(64, n)) {
+        tl_fail(`can't convert ${JSON.stringify(s)} to an integer`);
+    }
+    return n;
+}
+"],
+    },
+    Builtin {
+        name: "getc",
+        takes: &[],
+        returns: Kind::Long,
+        call: getc,
+        c: "\
+static long tl_getc(void) {
+    int c = getchar();
+    return c == EOF ? -1L : (long)c;
+}
+",
+        headers: &[],
+        setup: "",
+        rust: &[RUST_INPUT, "\
+fn tl_getc() -> i64 {
+    tl_byte(true).map_or(-1, i64::from)
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "readint",
+        takes: &[],
+        returns: Kind::Long,
+        call: readint,
+        c: "\
+static long tl_readint(void) {
+    char digits[64];
+    size_t length = 0;
+    int c = getchar();
+    while (isspace(c)) {
+        c = getchar();
+    }
+    if (c == '-' || c == '+') {
+        digits[length++] = (char)c;
+        c = getchar();
+    }
+    size_t signs = length;
+    for (; isdigit(c) && length < sizeof digits - 1; c = getchar()) {
+        digits[length++] = (char)c;
+    }
+    digits[length] = '\\0';
+    if (isdigit(c)) {
+        fprintf(stderr, \"error: %s\", digits);
+        for (; isdigit(c); c = getchar()) {
+            fputc(c, stderr);
+        }
+        fprintf(stderr, \" is too big for an integer\\n\");
+        exit(1);
+    }
+    ungetc(c, stdin);
+    if (length == signs) {
+        if (c == EOF) {
+            fprintf(stderr, \"error: `readint` reached the end of the input\\n\");
+        } else {
+            fprintf(stderr, \"error: `readint` expected an integer, found '%c'\\n\", c);
+        }
+        exit(1);
+    }
+    errno = 0;
+    long n = strtol(digits, NULL, 10);
+    if (errno == ERANGE) {
+        fprintf(stderr, \"error: %s is too big for an integer\\n\", digits);
+        exit(1);
+    }
+    return n;
+}
+",
+        headers: &["ctype.h", "errno.h", "stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_INPUT, "\
+fn tl_readint() -> i64 {
+    while tl_byte(false).is_some_and(|byte| byte.is_ascii_whitespace()) {
+        tl_byte(true);
+    }
+    let mut digits = String::new();
+    if let Some(sign @ (b'-' | b'+')) = tl_byte(false) {
+        digits.push(sign as char);
+        tl_byte(true);
+    }
+    while let Some(digit) = tl_byte(false).filter(u8::is_ascii_digit) {
+        digits.push(digit as char);
+        tl_byte(true);
+    }
+    if !digits.ends_with(|c: char| c.is_ascii_digit()) {
+        tl_fail(match tl_byte(false) {
+            Some(byte) => format!(\"`readint` expected an integer, found {:?}\", byte as char),
+            None => \"`readint` reached the end of the input\".to_string(),
+        });
+    }
+    digits.parse().unwrap_or_else(|_| tl_fail(format!(\"{} is too big for an integer\", digits)))
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    // The C backend's `long` is taken to be 64 bits, as it is on 64-bit Linux and macOS, so
+    // that a seed gives the same numbers there as here. The state is a tentative definition
+    // in both, which C allows to be repeated.
+    Builtin {
+        name: "rand",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: rand,
+        c: "\
+static unsigned long tl_rand_state;
+static long tl_rand(long n) {
+    unsigned long z;
+    if (n <= 0) {
+        fprintf(stderr, \"error: `rand` takes a positive bound, not %ld\\n\", n);
+        exit(1);
+    }
+    z = tl_rand_state += 0x9e3779b97f4a7c15UL;
+    z = (z ^ z >> 30) * 0xbf58476d1ce4e5b9UL;
+    z = (z ^ z >> 27) * 0x94d049bb133111ebUL;
+    return (long)((z ^ z >> 31) % (unsigned long)n);
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_RANDOM, "\
+fn tl_rand(n: i64) -> i64 {
+    use std::sync::atomic::Ordering::Relaxed;
+    if n <= 0 {
+        tl_fail(format!(\"`rand` takes a positive bound, not {}\", n));
+    }
+    let z = TL_RANDOM.fetch_add(0x9e37_79b9_7f4a_7c15, Relaxed).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    ((z ^ (z >> 31)) % n as u64) as i64
+}
+"],
+        rust_setup: "",
+        js: &[JS_FAIL, JS_RANDOM, "\
+function tl_rand(n) {
+    if (n <= 0n) {
+        tl_fail(`\\`rand\\` takes a positive bound, not ${n}`);
+    }
+    let z = tl_random = BigInt.asUintN(64, tl_random + 0x9e3779b97f4a7c15n);
+    z = BigInt.asUintN(64, (z ^ (z >> 30n)) * 0xbf58476d1ce4e5b9n);
+    z = BigInt.asUintN(64, (z ^ (z >> 27n)) * 0x94d049bb133111ebn);
+    return (z ^ (z >> 31n)) % n;
+}
+"],
+    },
+    Builtin {
+        name: "seed",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: seed,
+        c: "\
+static unsigned long tl_rand_state;
+static long tl_seed(long seed) {
+    tl_rand_state = (unsigned long)seed;
+    return 0;
+}
+",
+        headers: &[],
+        setup: "",
+        rust: &[RUST_RANDOM, "\
+fn tl_seed(seed: i64) -> i64 {
+    TL_RANDOM.store(seed as u64, std::sync::atomic::Ordering::Relaxed);
+    0
+}
+"],
+        rust_setup: "",
+        js: &[JS_RANDOM, "\
+function tl_seed(seed) {
+    tl_random = BigInt.asUintN(64, seed);
+    return 0n;
+}
+"],
+    },
+    // C has no clock that measures time passing, so these are POSIX's.
+    Builtin {
+        name: "clock",
+        takes: &[],
+        returns: Kind::Long,
+        call: clock,
+        c: "\
+static struct timeval tl_clock_start;
+static long tl_clock(void) {
+    struct timeval now;
+    gettimeofday(&now, NULL);
+    return (now.tv_sec - tl_clock_start.tv_sec) * 1000L + (now.tv_usec - tl_clock_start.tv_usec) / 1000L;
+}
+",
+        headers: &["sys/time.h"],
+        setup: "gettimeofday(&tl_clock_start, NULL);",
+        rust: &[RUST_START, "\
+fn tl_clock() -> i64 {
+    TL_START.get_or_init(std::time::Instant::now).elapsed().as_millis() as i64
+}
+"],
+        rust_setup: "TL_START.get_or_init(std::time::Instant::now);",
+        js: &["\
+const tl_start = performance.now();
+function tl_clock() {
+    return BigInt(Math.floor(performance.now() - tl_start));
+}
+"],
+    },
+    Builtin {
+        name: "sleep",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: sleep,
+        c: "\
+static long tl_sleep(long ms) {
+    struct timespec duration;
+    if (ms < 0) {
+        fprintf(stderr, \"error: `sleep` can't sleep for %ld milliseconds\\n\", ms);
+        exit(1);
+    }
+    duration.tv_sec = ms / 1000;
+    duration.tv_nsec = ms % 1000 * 1000000L;
+    nanosleep(&duration, NULL);
+    return 0;
+}
+",
+        headers: &["stdlib.h", "time.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_sleep(ms: i64) -> i64 {
+    match u64::try_from(ms) {
+        Ok(ms) => std::thread::sleep(std::time::Duration::from_millis(ms)),
+        Err(_) => tl_fail(format!(\"`sleep` can't sleep for {} milliseconds\", ms)),
+    }
+    0
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    // Compiled to C, a program is trusted with files as any other C program is. The table of
+    // them is a tentative definition in each, like `rand`'s state.
+    Builtin {
+        name: "fopen",
+        takes: &[Kind::String, Kind::String],
+        returns: Kind::Long,
+        call: fopen,
+        c: "\
+static FILE *tl_files[64];
+static long tl_fopen(const char *path, const char *mode) {
+    long i;
+    if (strcmp(mode, \"r\") && strcmp(mode, \"w\") && strcmp(mode, \"a\")) {
+        fprintf(stderr, \"error: `fopen` opens files with \\\"r\\\", \\\"w\\\" or \\\"a\\\", not \\\"%s\\\"\\n\", mode);
+        exit(1);
+    }
+    for (i = 0; i < 64 && tl_files[i]; i++)
+        ;
+    if (i == 64 || !(tl_files[i] = fopen(path, mode))) {
+        return -1;
+    }
+    return i;
+}
+",
+        headers: &["stdlib.h", "string.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_FILES, "\
+fn tl_fopen(path: String, mode: String) -> i64 {
+    let mut options = std::fs::OpenOptions::new();
+    match mode.as_str() {
+        \"r\" => options.read(true),
+        \"w\" => options.write(true).create(true).truncate(true),
+        \"a\" => options.append(true).create(true),
+        _ => tl_fail(format!(\"`fopen` opens files with \\\"r\\\", \\\"w\\\" or \\\"a\\\", not {:?}\", mode)),
+    };
+    let Ok(file) = options.open(&path) else { return -1 };
+    let file = if mode == \"r\" { TlFile::Read(std::io::BufReader::new(file)) } else { TlFile::Write(std::io::BufWriter::new(file)) };
+    let mut files = TL_FILES.lock().unwrap();
+    let i = files.iter().position(Option::is_none).unwrap_or(files.len());
+    if i == files.len() {
+        files.push(None);
+    }
+    files[i] = Some(file);
+    i as i64
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "fgetc",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: fgetc,
+        c: "\
+static FILE *tl_files[64];
+static long tl_fgetc(long file) {
+    int c;
+    if (file < 0 || file >= 64 || !tl_files[file]) {
+        fprintf(stderr, \"error: `fgetc` was given %ld, which isn't an open file\\n\", file);
+        exit(1);
+    }
+    c = fgetc(tl_files[file]);
+    return c == EOF ? -1L : (long)c;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_FILES, "\
+fn tl_fgetc(file: i64) -> i64 {
+    let i = tl_file(\"fgetc\", file);
+    let Some(TlFile::Read(file)) = &mut TL_FILES.lock().unwrap()[i] else {
+        tl_fail(\"`fgetc` reads files opened with \\\"r\\\"\".to_string());
+    };
+    let mut byte = [0];
+    match std::io::Read::read(file, &mut byte) {
+        Ok(0) => -1,
+        Ok(_) => byte[0] as i64,
+        Err(e) => tl_fail(format!(\"`fgetc` can't read the file: {}\", e)),
+    }
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "fputs",
+        takes: &[Kind::String, Kind::Long],
+        returns: Kind::Long,
+        call: fputs,
+        c: "\
+static FILE *tl_files[64];
+static long tl_fputs(const char *s, long file) {
+    if (file < 0 || file >= 64 || !tl_files[file]) {
+        fprintf(stderr, \"error: `fputs` was given %ld, which isn't an open file\\n\", file);
+        exit(1);
+    }
+    if (fputs(s, tl_files[file]) == EOF) {
+        fprintf(stderr, \"error: `fputs` can't write to file %ld\\n\", file);
+        exit(1);
+    }
+    return 0;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_FILES, "\
+fn tl_fputs(s: String, file: i64) -> i64 {
+    let i = tl_file(\"fputs\", file);
+    let Some(TlFile::Write(file)) = &mut TL_FILES.lock().unwrap()[i] else {
+        tl_fail(\"`fputs` writes to files opened with \\\"w\\\" or \\\"a\\\"\".to_string());
+    };
+    if let Err(e) = std::io::Write::write_all(file, s.as_bytes()) {
+        tl_fail(format!(\"`fputs` can't write to the file: {}\", e));
+    }
+    0
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "fclose",
+        takes: &[Kind::Long],
+        returns: Kind::Long,
+        call: fclose,
+        c: "\
+static FILE *tl_files[64];
+static long tl_fclose(long file) {
+    if (file < 0 || file >= 64 || !tl_files[file]) {
+        fprintf(stderr, \"error: `fclose` was given %ld, which isn't an open file\\n\", file);
+        exit(1);
+    }
+    if (fclose(tl_files[file]) == EOF) {
+        tl_files[file] = NULL;
+        fprintf(stderr, \"error: `fclose` can't finish writing file %ld\\n\", file);
+        exit(1);
+    }
+    tl_files[file] = NULL;
+    return 0;
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, RUST_FILES, "\
+fn tl_fclose(file: i64) -> i64 {
+    let i = tl_file(\"fclose\", file);
+    if let Some(TlFile::Write(mut file)) = TL_FILES.lock().unwrap()[i].take()
+        && let Err(e) = std::io::Write::flush(&mut file)
+    {
+        tl_fail(format!(\"`fclose` can't finish writing the file: {}\", e));
+    }
+    0
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "argc",
+        takes: &[],
+        returns: Kind::Long,
+        call: argc,
+        c: "\
+static int tl_argument_count;
+static char **tl_arguments;
+static long tl_argc(void) {
+    return tl_argument_count;
+}
+",
+        headers: &[],
+        setup: "tl_argument_count = argc; tl_arguments = argv;",
+        rust: &[RUST_ARGUMENTS, "\
+fn tl_argc() -> i64 {
+    TL_ARGUMENTS.len() as i64
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "argv",
+        takes: &[Kind::Long],
+        returns: Kind::String,
+        call: argv,
+        c: "\
+static int tl_argument_count;
+static char **tl_arguments;
+static const char *tl_argv(long i) {
+    if (i < 0 || i >= tl_argument_count) {
+        fprintf(stderr, \"error: `argv` was given %ld, but there are %d arguments\\n\", i, tl_argument_count);
+        exit(1);
+    }
+    return tl_arguments[i];
+}
+",
+        headers: &["stdlib.h"],
+        setup: "tl_argument_count = argc; tl_arguments = argv;",
+        rust: &[RUST_FAIL, RUST_ARGUMENTS, "\
+fn tl_argv(i: i64) -> String {
+    match usize::try_from(i).ok().and_then(|i| TL_ARGUMENTS.get(i)) {
+        Some(argument) => argument.clone(),
+        None => tl_fail(format!(\"`argv` was given {}, but there are {} arguments\", i, TL_ARGUMENTS.len())),
+    }
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+    Builtin {
+        name: "getenv",
+        takes: &[Kind::String],
+        returns: Kind::String,
+        call: getenv,
+        c: "\
+static const char *tl_getenv(const char *name) {
+    const char *value = getenv(name);
+    return value ? value : \"\";
+}
+",
+        headers: &["stdlib.h"],
+        setup: "",
+        rust: &[RUST_FAIL, "\
+fn tl_getenv(name: String) -> String {
+    if name.is_empty() || name.contains(['=', '\\0']) {
+        tl_fail(format!(\"{:?} can't be the name of an environment variable\", name));
+    }
+    std::env::var_os(&name).unwrap_or_default().to_string_lossy().into_owned()
+}
+"],
+        rust_setup: "",
+        js: &[],
+    },
+];
+
+pub fn builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+// The index of the builtin `name` in `BUILTINS`.
+pub fn index(name: &str) -> Option<usize> {
+    BUILTINS.iter().position(|builtin| builtin.name == name)
+}
+
+fn expected(name: &str, what: &str, found: &Value) -> String {
+    format!("`{}` expects {}, found {}", name, what, found.type_name())
+}
+
+fn abs(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::Float(x) => Ok(Value::Float(x.abs())),
+        value => value.integer().map(|n| Value::Integer(n.wrapping_abs())).ok_or_else(|| expected("abs", "a number", value)),
+    }
+}
+
+// A number as a float, for arithmetic in which the other side is one.
+fn float(name: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Float(x) => Ok(*x),
+        value => value.integer().map(|n| n as f64).ok_or_else(|| expected(name, "numbers", value)),
+    }
+}
+
+// The lesser or greater of two numbers: an integer if both are, and otherwise a float.
+fn extreme(name: &str, arguments: &[Value], less: bool) -> Result<Value, String> {
+    let [a, b] = arguments else { unreachable!() };
+    if let Some((a, b)) = a.integers(b) {
+        return Ok(Value::Integer(if less { a.min(b) } else { a.max(b) }));
+    }
+    let (a, b) = (float(name, a)?, float(name, b)?);
+    Ok(Value::Float(if less { a.min(b) } else { a.max(b) }))
+}
+
+fn min(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    extreme("min", arguments, true)
+}
+
+fn max(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    extreme("max", arguments, false)
+}
+
+// By squaring and multiplying, as far as an exponent goes.
+fn wrapping_pow(mut base: i64, mut exponent: u64) -> i64 {
+    let mut result = 1i64;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+
+// Integer powers wrap, as the rest of integer arithmetic does by default.
+fn pow(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let [base, exponent] = arguments else { unreachable!() };
+    if let Some((base, exponent)) = base.integers(exponent) {
+        if exponent < 0 {
+            return Err("`pow` can't raise an integer to a negative power".to_string());
+        }
+        return Ok(Value::Integer(wrapping_pow(base, exponent as u64)));
+    }
+    Ok(Value::Float(float("pow", base)?.powf(float("pow", exponent)?)))
+}
+
+// A character literal is its code, so `chr('a')` is "a".
+fn chr(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let value = &arguments[0];
+    let code = value.integer().ok_or_else(|| expected("chr", "an integer", value))?;
+    let c = u32::try_from(code).ok().and_then(char::from_u32).ok_or_else(|| format!("{} isn't a character", code))?;
+    Ok(Value::String(c.to_string()))
+}
+
+// The code of a string's one character; a character literal is already its code.
+fn ord(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::String(s) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Integer(c as i64)),
+                _ => Err(format!("`ord` takes a string of one character, not {:?}", s)),
+            }
+        }
+        Value::Integer(n) => Ok(Value::Integer(*n)),
+        value => Err(expected("ord", "a string", value)),
+    }
+}
+
+// As `print` would show it.
+fn str(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(arguments[0].to_string()))
+}
+
+// Floats are truncated toward zero; strings are parsed, allowing spaces around the digits.
+fn int(_: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::Float(x) if x.is_finite() => Ok(Value::Integer(*x as i64)),
+        Value::Float(x) => Err(format!("{:?} has no integer value", x)),
+        Value::String(s) => s.trim().parse().map(Value::Integer).map_err(|_| format!("can't convert {:?} to an integer", s)),
+        value => value.integer().map(Value::Integer).ok_or_else(|| expected("int", "a number or a string", value)),
+    }
+}
+
+// The next byte of the input, or -1 at its end.
+fn getc(context: &mut Context, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer(context.byte()?.map_or(-1, i64::from)))
+}
+
+// The next integer in the input, after any whitespace, with an optional sign.
+fn readint(context: &mut Context, _: &[Value]) -> Result<Value, String> {
+    let mut byte = context.byte()?;
+    while byte.is_some_and(|byte| byte.is_ascii_whitespace()) {
+        byte = context.byte()?;
+    }
+    let mut digits = String::new();
+    if let Some(sign @ (b'-' | b'+')) = byte {
+        digits.push(sign as char);
+        byte = context.byte()?;
+    }
+    while let Some(digit) = byte.filter(u8::is_ascii_digit) {
+        digits.push(digit as char);
+        byte = context.byte()?;
+    }
+    context.peeked = byte;
+    if !digits.ends_with(|c: char| c.is_ascii_digit()) {
+        return Err(match byte {
+            Some(byte) => format!("`readint` expected an integer, found {:?}", byte as char),
+            None => "`readint` reached the end of the input".to_string(),
+        });
+    }
+    digits.parse().map(Value::Integer).map_err(|_| format!("{} is too big for an integer", digits))
+}
+
+// A number from 0 up to but not including `n`.
+fn rand(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let value = &arguments[0];
+    match value.integer() {
+        Some(n) if n > 0 => Ok(Value::Integer((context.next_random() % n as u64) as i64)),
+        Some(n) => Err(format!("`rand` takes a positive bound, not {}", n)),
+        None => Err(expected("rand", "an integer", value)),
+    }
+}
+
+fn seed(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let value = &arguments[0];
+    context.seed(value.integer().ok_or_else(|| expected("seed", "an integer", value))?);
+    Ok(Value::Integer(0))
+}
+
+// The milliseconds since the program started.
+fn clock(context: &mut Context, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer(match context.clock {
+        Clock::Real(start) => start.elapsed().as_millis() as i64,
+        Clock::Virtual(now) => now as i64,
+    }))
+}
+
+fn sleep(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let value = &arguments[0];
+    let ms = value.integer().ok_or_else(|| expected("sleep", "an integer", value))?;
+    let ms = u64::try_from(ms).map_err(|_| format!("`sleep` can't sleep for {} milliseconds", ms))?;
+    match &mut context.clock {
+        Clock::Real(_) => thread::sleep(Duration::from_millis(ms)),
+        Clock::Virtual(now) => *now += ms,
+    }
+    Ok(Value::Integer(0))
+}
+
+fn string<'v>(name: &str, value: &'v Value) -> Result<&'v str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => Err(expected(name, "a string", value)),
+    }
+}
+
+// A number for the file at the path, opened to read ("r"), write ("w") or append to
+// ("a"), or -1 if it can't be opened.
+fn fopen(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    if !context.capabilities.files {
+        return Err("`fopen` needs access to files, which isn't allowed".to_string());
+    }
+    let (path, mode) = (string("fopen", &arguments[0])?, string("fopen", &arguments[1])?);
+    let mut options = OpenOptions::new();
+    match mode {
+        "r" => options.read(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        mode => return Err(format!("`fopen` opens files with \"r\", \"w\" or \"a\", not {:?}", mode)),
+    };
+    let Ok(file) = options.open(path) else { return Ok(Value::Integer(-1)) };
+    let file = if mode == "r" { OpenFile::Read(BufReader::new(file)) } else { OpenFile::Write(BufWriter::new(file)) };
+    let i = context.files.iter().position(Option::is_none).unwrap_or(context.files.len());
+    if i == context.files.len() {
+        context.files.push(None);
+    }
+    context.files[i] = Some(file);
+    Ok(Value::Integer(i as i64))
+}
+
+// The next byte of a file opened to read, or -1 at its end.
+fn fgetc(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let i = context.file("fgetc", &arguments[0])?;
+    let Some(OpenFile::Read(file)) = &mut context.files[i] else {
+        return Err("`fgetc` reads files opened with \"r\"".to_string());
+    };
+    let mut byte = [0];
+    match file.read(&mut byte) {
+        Ok(0) => Ok(Value::Integer(-1)),
+        Ok(_) => Ok(Value::Integer(byte[0] as i64)),
+        Err(e) => Err(format!("`fgetc` can't read the file: {}", e)),
+    }
+}
+
+// Writes a string to a file opened to write or append to, as C's `fputs` does, string first.
+fn fputs(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let s = string("fputs", &arguments[0])?;
+    let i = context.file("fputs", &arguments[1])?;
+    let Some(OpenFile::Write(file)) = &mut context.files[i] else {
+        return Err("`fputs` writes to files opened with \"w\" or \"a\"".to_string());
+    };
+    file.write_all(s.as_bytes()).map_err(|e| format!("`fputs` can't write to the file: {}", e))?;
+    Ok(Value::Integer(0))
+}
+
+fn fclose(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let i = context.file("fclose", &arguments[0])?;
+    if let Some(OpenFile::Write(mut file)) = context.files[i].take() {
+        file.flush().map_err(|e| format!("`fclose` can't finish writing the file: {}", e))?;
+    }
+    Ok(Value::Integer(0))
+}
+
+// How many arguments the program has, counting its name.
+fn argc(context: &mut Context, _: &[Value]) -> Result<Value, String> {
+    Ok(Value::Integer(context.arguments.len() as i64))
+}
+
+// The program's name for 0, and then each of its arguments.
+fn argv(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    let value = &arguments[0];
+    let i = value.integer().ok_or_else(|| expected("argv", "an integer", value))?;
+    usize::try_from(i).ok()
+        .and_then(|i| context.arguments.get(i))
+        .map(|argument| Value::String(argument.clone()))
+        .ok_or_else(|| format!("`argv` was given {}, but there are {} arguments", i, context.arguments.len()))
+}
+
+// The value of an environment variable, or the empty string if it isn't set.
+fn getenv(context: &mut Context, arguments: &[Value]) -> Result<Value, String> {
+    if !context.capabilities.env {
+        return Err("`getenv` needs access to the environment, which isn't allowed".to_string());
+    }
+    let name = string("getenv", &arguments[0])?;
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return Err(format!("{:?} can't be the name of an environment variable", name));
+    }
+    let value = std::env::var_os(name).unwrap_or_default();
+    Ok(Value::String(value.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &[Value]) -> Result<Value, String> {
+        (builtin(name).unwrap().call)(&mut Context::with_input(io::empty()), arguments)
+    }
+
+    #[test]
+    fn builtins_take_integers_floats_and_strings() {
+        let (i, f, s) = (Value::Integer, Value::Float, |s: &str| Value::String(s.to_string()));
+        assert_eq!(call("abs", &[i(-3)]), Ok(i(3)));
+        assert_eq!(call("abs", &[f(-1.5)]), Ok(f(1.5)));
+        assert_eq!(call("min", &[i(2), i(-1)]), Ok(i(-1)));
+        assert_eq!(call("max", &[i(2), f(2.5)]), Ok(f(2.5)));
+        assert_eq!(call("pow", &[i(2), i(10)]), Ok(i(1024)));
+        assert_eq!(call("pow", &[i(-1), i(4294967296)]), Ok(i(1)));
+        assert_eq!(call("pow", &[i(2), i(64)]), Ok(i(0)));
+        assert_eq!(call("pow", &[f(4.0), f(0.5)]), Ok(f(2.0)));
+        assert!(call("pow", &[i(2), i(-1)]).is_err());
+        assert_eq!(call("chr", &[i('é' as i64)]), Ok(s("é")));
+        assert!(call("chr", &[i(-1)]).is_err());
+        assert_eq!(call("ord", &[s("é")]), Ok(i(233)));
+        assert!(call("ord", &[s("ab")]).is_err());
+        assert_eq!(call("str", &[f(1.0)]), Ok(s("1.0")));
+        assert_eq!(call("int", &[s(" 42 ")]), Ok(i(42)));
+        assert_eq!(call("int", &[f(-2.7)]), Ok(i(-2)));
+        assert_eq!(call("abs", &[s("x")]), Err("`abs` expects a number, found string".to_string()));
+    }
+
+    #[test]
+    fn input_is_read_from_the_context() {
+        let mut context = Context::with_input(&b"  12\n-3x"[..]);
+        let mut call = |name: &str| (builtin(name).unwrap().call)(&mut context, &[]);
+        assert_eq!(call("readint"), Ok(Value::Integer(12)));
+        assert_eq!(call("getc"), Ok(Value::Integer('\n' as i64)));
+        assert_eq!(call("readint"), Ok(Value::Integer(-3)));
+        assert_eq!(call("readint"), Err("`readint` expected an integer, found 'x'".to_string()));
+        assert_eq!(call("getc"), Ok(Value::Integer('x' as i64)));
+        assert_eq!(call("getc"), Ok(Value::Integer(-1)));
+        assert_eq!(call("readint"), Err("`readint` reached the end of the input".to_string()));
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_numbers_every_time() {
+        let numbers = |seed: i64| {
+            let mut context = Context::with_input(io::empty());
+            context.seed(seed);
+            (0..8).map(|_| rand(&mut context, &[Value::Integer(100)]).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(7), numbers(7));
+        assert_ne!(numbers(7), numbers(8));
+        assert!(numbers(7).iter().all(|n| matches!(n, Value::Integer(0..100))));
+        assert!(call("rand", &[Value::Integer(0)]).is_err());
+    }
+
+    #[test]
+    fn virtual_time_passes_only_when_the_program_sleeps() {
+        let mut context = Context::with_input(io::empty());
+        context.virtual_time();
+        assert_eq!(clock(&mut context, &[]), Ok(Value::Integer(0)));
+        sleep(&mut context, &[Value::Integer(60_000)]).unwrap();
+        assert_eq!(clock(&mut context, &[]), Ok(Value::Integer(60_000)));
+        assert!(sleep(&mut context, &[Value::Integer(-1)]).is_err());
+    }
+
+    #[test]
+    fn files_are_only_opened_if_allowed() {
+        let path = std::env::temp_dir().join(format!("tiny-lang-stdlib-{}.txt", std::process::id()));
+        let s = |s: &str| Value::String(s.to_string());
+        let path_value = s(&path.display().to_string());
+        let mut context = Context::with_input(io::empty());
+        assert!(fopen(&mut context, &[path_value.clone(), s("w")]).is_err());
+
+        context.allow(Capabilities { files: true, ..Capabilities::default() });
+        let file = fopen(&mut context, &[path_value.clone(), s("w")]).unwrap();
+        assert_eq!(file, Value::Integer(0));
+        fputs(&mut context, &[s("hi"), file.clone()]).unwrap();
+        fclose(&mut context, std::slice::from_ref(&file)).unwrap();
+        assert!(fclose(&mut context, std::slice::from_ref(&file)).is_err());
+
+        let file = fopen(&mut context, &[path_value, s("r")]).unwrap();
+        let bytes: Vec<Value> = (0..3).map(|_| fgetc(&mut context, std::slice::from_ref(&file)).unwrap()).collect();
+        assert_eq!(bytes, [Value::Integer('h' as i64), Value::Integer('i' as i64), Value::Integer(-1)]);
+        assert!(fputs(&mut context, &[s("x"), file]).is_err());
+        assert_eq!(fopen(&mut context, &[s("/nonexistent/file"), s("r")]), Ok(Value::Integer(-1)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn the_environment_is_only_read_if_allowed() {
+        let path = Value::String("PATH".to_string());
+        let mut context = Context::with_input(io::empty());
+        assert!(getenv(&mut context, std::slice::from_ref(&path)).is_err());
+        context.allow(Capabilities { env: true, ..Capabilities::default() });
+        let expected = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(getenv(&mut context, &[path]), Ok(Value::String(expected)));
+        let unset = Value::String("TINY_LANG_SURELY_UNSET".to_string());
+        assert_eq!(getenv(&mut context, &[unset]), Ok(Value::String(String::new())));
+    }
+
+    #[test]
+    fn arguments_start_with_the_programs_name() {
+        let mut context = Context::with_input(io::empty());
+        context.arguments(vec!["main.lang".to_string(), "-n".to_string()]);
+        assert_eq!(argc(&mut context, &[]), Ok(Value::Integer(2)));
+        assert_eq!(argv(&mut context, &[Value::Integer(1)]), Ok(Value::String("-n".to_string())));
+        assert_eq!(argv(&mut context, &[Value::Integer(2)]), Err("`argv` was given 2, but there are 2 arguments".to_string()));
+    }
+}
@@ -0,0 +1,111 @@
+// `run --trace`: a line for each statement the interpreter runs, or each instruction the VM
+// does, with where it is in the source and the values it works on, indented by how deep in
+// calls the program is.
+
+use std::io::Write;
+
+use crate::diagnostics::LineIndex;
+use crate::debugger::shown;
+use crate::interpreter::{Action, Hook, Scope};
+use crate::parser::ast::{Expression, Statement};
+use crate::sema::{Occurrence, names_in_expression};
+use crate::vm::Step;
+
+// The expressions a statement evaluates itself, as opposed to the statements in its body.
+fn own_expressions(statement: &Statement) -> Vec<&Expression> {
+    match statement {
+        Statement::Expression { expression, .. } | Statement::Putc { expression, .. } => vec![expression],
+        Statement::Print { arguments, .. } => arguments.iter().collect(),
+        Statement::If { condition, .. } | Statement::While { condition, .. } | Statement::DoWhile { condition, .. } => {
+            vec![condition]
+        }
+        Statement::For { initializer, condition, step, .. } => [initializer, condition, step].into_iter().flatten().collect(),
+        Statement::Return { value, .. } => value.iter().collect(),
+        Statement::Block { .. } | Statement::Function { .. } | Statement::Attributed { .. } | Statement::Import { .. } => Vec::new(),
+    }
+}
+
+// Traces the statements the interpreter runs to `output`. Failing to write doesn't stop the
+// program, which is what's being looked at.
+pub struct Tracer<'s, W> {
+    source: &'s str,
+    lines: LineIndex<'s>,
+    output: W
+}
+
+impl<'s, W: Write> Tracer<'s, W> {
+    pub fn new(source: &'s str, output: W) -> Self {
+        Tracer { source, lines: LineIndex::new(source), output }
+    }
+}
+
+impl<W: Write> Hook for Tracer<'_, W> {
+    fn statement(&mut self, statement: &Statement, scope: &Scope<'_>) -> Action {
+        if matches!(statement, Statement::Block { .. } | Statement::Function { .. } | Statement::Attributed { .. } | Statement::Import { .. }) {
+            return Action::Continue;
+        }
+        let start = statement.span().start;
+        let (line, _) = self.lines.line_column(start);
+        // The statement as far as the end of its first line, which for an `if` or a loop
+        // is its condition.
+        let end = self.source[start..].find('\n').map_or(self.source.len(), |end| start + end);
+        let text = self.source[start..end].trim_end();
+
+        // The variables it reads, each once, as they are before it runs.
+        let mut read = Vec::new();
+        for expression in own_expressions(statement) {
+            names_in_expression(expression, &mut |name, _, occurrence| {
+                if occurrence == Occurrence::Read && !read.contains(&name) {
+                    read.push(name);
+                }
+            });
+        }
+        let values: Vec<String> = read.iter()
+            .filter_map(|&name| Some(format!("{} = {}", name, shown(scope.variable(name.as_str())?))))
+            .collect();
+        let indent = "  ".repeat(scope.depth());
+        let _ = if values.is_empty() {
+            writeln!(self.output, "{:>4} | {}{}", line, indent, text)
+        } else {
+            writeln!(self.output, "{:>4} | {}{}    [{}]", line, indent, text, values.join(", "))
+        };
+        Action::Continue
+    }
+}
+
+// Traces the instructions the VM runs to `output`, with their lines if there's `source` to
+// find them in. Failing to write doesn't stop the program.
+pub fn vm_tracer<'a>(source: Option<&'a str>, mut output: impl Write + 'a) -> impl FnMut(&Step<'_>) + 'a {
+    let lines = source.map(LineIndex::new);
+    move |step| {
+        let line = match (&lines, step.span) {
+            (Some(lines), Some(span)) => lines.line_column(span.start).0.to_string(),
+            _ => "?".to_string(),
+        };
+        let stack: Vec<String> = step.stack.iter().map(shown).collect();
+        let indent = "  ".repeat(step.depth);
+        let instruction = format!("{}{}", indent, step.instruction);
+        let _ = writeln!(output, "{:>5} {:>4} | {:<24} [{}]", step.pc, line, instruction, stack.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::tokenize;
+    use crate::parser::parser::{Parser, parse_program};
+
+    #[test]
+    fn traces_each_statement_with_the_values_it_reads() {
+        let source = "fn double(n) {\n    return n * 2;\n}\nx = 4;\nif (x > 1)\n    x = double(x);\n";
+        let program = parse_program(&mut Parser::new(tokenize(source).0)).unwrap();
+        let mut tracer = Tracer::new(source, Vec::new());
+        Interpreter::with_output(std::io::sink()).with_hook(&mut tracer).run(&program).unwrap();
+        assert_eq!(String::from_utf8(tracer.output).unwrap(), "   4 | x = 4;
+   5 | if (x > 1)    [x = 4]
+   6 | x = double(x);    [x = 4]
+   2 |   return n * 2;    [n = 4]
+");
+    }
+}
@@ -0,0 +1,238 @@
+// The VM's heap: strings and arrays live here, and values on the stack, in locals and in
+// globals refer to them by handle. Objects nothing refers to any more are freed by a
+// mark-and-sweep collector, which only ever runs when an object is allocated, so a handle
+// is safe to hold as long as it's reachable from a root by the next allocation.
+//
+// Strings are interned: there's only ever one string object with given contents, so two
+// strings are equal exactly when their handles are.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Collections happen once this many objects have been allocated, at the least, and
+// otherwise once the heap has doubled since the last one.
+const MIN_THRESHOLD: usize = 1024;
+
+// A reference to an object on the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle(u32);
+
+// A value as the VM holds it: numbers and bools inline, everything else on the heap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Word {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Object(Handle)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    String(Rc<str>),
+    Array(Vec<Word>)
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::String(_) => "string",
+            Object::Array(_) => "array",
+        }
+    }
+
+    fn children(&self) -> impl Iterator<Item = Handle> + '_ {
+        let words = match self {
+            Object::String(_) => &[][..],
+            Object::Array(elements) => &elements[..],
+        };
+        words.iter().filter_map(|word| match word {
+            Word::Object(handle) => Some(*handle),
+            _ => None,
+        })
+    }
+}
+
+// What the heap has done, for `--stats` and for tests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub allocated: usize,
+    pub freed: usize,
+    pub collections: usize,
+    // How many strings are in the pool now.
+    pub interned: usize,
+    // How many times a string was found in the pool rather than allocated.
+    pub reused: usize
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} objects allocated, {} freed, {} collections; ", self.allocated, self.freed, self.collections)?;
+        write!(f, "{} strings interned, {} reused", self.interned, self.reused)
+    }
+}
+
+#[derive(Default)]
+pub struct Heap {
+    // Each slot holds an object or, once it's freed, nothing until it's reused.
+    objects: Vec<Option<Object>>,
+    marks: Vec<bool>,
+    free: Vec<u32>,
+    live: usize,
+    // Every string on the heap, by its contents.
+    pool: HashMap<Rc<str>, Handle>,
+    // How many live objects there may be before the next collection.
+    threshold: usize,
+    // Whether to collect before every allocation, to find handles that aren't rooted.
+    stress: bool,
+    stats: HeapStats
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap { threshold: MIN_THRESHOLD, ..Heap::default() }
+    }
+
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats { interned: self.pool.len(), ..self.stats }
+    }
+
+    // How many objects are allocated and not yet freed.
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    // Whether the next allocation should collect first.
+    pub fn wants_collection(&self) -> bool {
+        self.stress || self.live >= self.threshold
+    }
+
+    // The string with contents `s`, if there is one.
+    pub fn find_string(&mut self, s: &str) -> Option<Handle> {
+        let handle = self.pool.get(s).copied();
+        self.stats.reused += usize::from(handle.is_some());
+        handle
+    }
+
+    // Adds `object` to the heap without collecting: the caller decides when to with
+    // `wants_collection` and `collect`, since only it knows the roots. A string mustn't be
+    // on the heap already; look for it with `find_string` first.
+    pub fn insert(&mut self, object: Object) -> Handle {
+        self.live += 1;
+        self.stats.allocated += 1;
+        let string = match &object {
+            Object::String(s) => Some(Rc::clone(s)),
+            Object::Array(_) => None,
+        };
+        let handle = match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(object);
+                Handle(index)
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marks.push(false);
+                Handle(u32::try_from(self.objects.len() - 1).expect("too many objects on the heap"))
+            }
+        };
+        if let Some(s) = string {
+            let previous = self.pool.insert(s, handle);
+            assert!(previous.is_none(), "a string was interned twice");
+        }
+        handle
+    }
+
+    pub fn get(&self, handle: Handle) -> &Object {
+        self.objects[handle.0 as usize].as_ref().expect("use of a freed object")
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> &mut Object {
+        self.objects[handle.0 as usize].as_mut().expect("use of a freed object")
+    }
+
+    // Frees every object that can't be reached from `roots`, following arrays to their
+    // elements, and returns how many were freed.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Handle>) -> usize {
+        let mut pending: Vec<Handle> = roots.into_iter().collect();
+        while let Some(handle) = pending.pop() {
+            let index = handle.0 as usize;
+            if self.marks[index] {
+                continue;
+            }
+            self.marks[index] = true;
+            pending.extend(self.get(handle).children());
+        }
+
+        let mut freed = 0;
+        for (index, (object, mark)) in self.objects.iter_mut().zip(&mut self.marks).enumerate() {
+            if std::mem::take(mark) {
+                continue;
+            }
+            if let Some(object) = object.take() {
+                if let Object::String(s) = object {
+                    self.pool.remove(&s);
+                }
+                self.free.push(index as u32);
+                freed += 1;
+            }
+        }
+        self.live -= freed;
+        self.threshold = (self.live * 2).max(MIN_THRESHOLD);
+        self.stats.freed += freed;
+        self.stats.collections += 1;
+        freed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(heap: &mut Heap, s: &str) -> Handle {
+        heap.find_string(s).unwrap_or_else(|| heap.insert(Object::String(s.into())))
+    }
+
+    #[test]
+    fn frees_only_what_is_unreachable() {
+        let mut heap = Heap::new();
+        let kept = string(&mut heap, "kept");
+        let inner = string(&mut heap, "inner");
+        let array = heap.insert(Object::Array(vec![Word::Integer(1), Word::Object(inner)]));
+        string(&mut heap, "garbage");
+        assert_eq!(heap.collect([kept, array]), 1);
+        assert_eq!(heap.get(inner), &Object::String("inner".into()));
+        assert_eq!(heap.live(), 4 - 1);
+
+        // The freed slot is reused.
+        let reused = string(&mut heap, "new");
+        assert_eq!(heap.objects.len(), 4);
+        assert_eq!(heap.collect([reused]), 3);
+    }
+
+    #[test]
+    fn interns_strings_until_they_are_freed() {
+        let mut heap = Heap::new();
+        let a = string(&mut heap, "same");
+        assert_eq!(string(&mut heap, "same"), a);
+        assert_ne!(string(&mut heap, "other"), a);
+        heap.collect([]);
+        assert_eq!(heap.find_string("same"), None);
+        assert_eq!(heap.stats().reused, 1);
+        assert_eq!(heap.stats().interned, 0);
+    }
+
+    #[test]
+    fn collects_cycles() {
+        let mut heap = Heap::new();
+        let a = heap.insert(Object::Array(Vec::new()));
+        let b = heap.insert(Object::Array(vec![Word::Object(a)]));
+        *heap.get_mut(a) = Object::Array(vec![Word::Object(b)]);
+        assert_eq!(heap.collect([a]), 0);
+        assert_eq!(heap.collect([]), 2);
+        assert_eq!(heap.stats(), HeapStats { allocated: 2, freed: 2, collections: 2, interned: 0, reused: 0 });
+    }
+}
@@ -0,0 +1,607 @@
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use crate::codegen::{Bytecode, Instruction};
+use crate::diagnostics::Span;
+use crate::intern::Symbol;
+use crate::interpreter::{MAX_CALL_DEPTH, Overflow, Value, division_by_zero, nested_too_deep, out_of_bounds, overflow, wrong_type};
+use crate::lexer::TokenKind;
+use crate::stdlib::{BUILTINS, Capabilities, Context};
+
+use super::heap::{Handle, Heap, HeapStats, Object, Word};
+
+// How many instructions run between looks at the clock, which costs far more than one.
+const CLOCK_INTERVAL: u32 = 4096;
+
+// Limits on how long `Vm::run_with` lets a program run, so that one that never halts, such
+// as `while (1);`, can't hang its host. There are none by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunConfig {
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    overflow: Overflow,
+    gc_stress: bool,
+    seed: Option<i64>,
+    virtual_time: bool,
+    capabilities: Capabilities
+}
+
+impl RunConfig {
+    // Stops the program once it has run `fuel` instructions.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    // Makes integer arithmetic that overflows behave as `overflow` says, rather than wrap.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    // Starts `rand` from `seed` rather than the same seed every run.
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Has `clock` and `sleep` go by a clock that only `sleep` moves, without really
+    // sleeping, so that a program that times itself does the same thing every run.
+    pub fn virtual_time(mut self, virtual_time: bool) -> Self {
+        self.virtual_time = virtual_time;
+        self
+    }
+
+    // Lets the program use what `capabilities` allows, such as files; by default it can only
+    // read its input and print.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    // Collects garbage before every allocation rather than once the heap has grown, so that
+    // a value the VM forgot to keep reachable is freed, and its use caught, straight away.
+    pub fn gc_stress(mut self, gc_stress: bool) -> Self {
+        self.gc_stress = gc_stress;
+        self
+    }
+
+    // Stops the program once it has run for `timeout`. The clock is only checked every few
+    // thousand instructions, so it can run a little over.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+// Why the VM stopped the program. `span` is the statement it was running, or the division,
+// unless the bytecode doesn't say.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    FuelExhausted { span: Option<Span> },
+    TimedOut { timeout: Duration, span: Option<Span> },
+    // An integer `/` or `%` by zero.
+    DivisionByZero { operator: TokenKind, span: Option<Span> },
+    // Integer arithmetic whose result doesn't fit in an i64, when overflow traps.
+    Overflow { operator: TokenKind, span: Option<Span> },
+    // A builtin given arguments it can't take, such as `chr(-1)`.
+    Builtin { name: &'static str, message: String, span: Option<Span> },
+    // An index below zero or past the end of the array.
+    IndexOutOfBounds { index: i64, length: usize, span: Option<Span> },
+    // Anything else of the wrong type, such as indexing a number.
+    WrongType { expected: &'static str, found: &'static str, span: Option<Span> },
+    // An operator applied to operands of types it doesn't support, e.g. `"a" * 3`.
+    TypeMismatch { operator: &'static str, left: &'static str, right: &'static str, span: Option<Span> },
+    InvalidOperand { operator: &'static str, operand: &'static str, span: Option<Span> },
+    // Calls nested more than `MAX_CALL_DEPTH` deep, the functions called outermost first.
+    StackOverflow { chain: Vec<Symbol>, span: Option<Span> }
+}
+
+impl VmError {
+    // The code the interpreter's error for the same thing has, if it has one.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            VmError::FuelExhausted { .. } => Some("E0404"),
+            VmError::TimedOut { .. } => None,
+            VmError::DivisionByZero { .. } => Some("E0406"),
+            VmError::Overflow { .. } => Some("E0407"),
+            VmError::Builtin { .. } => Some("E0403"),
+            VmError::IndexOutOfBounds { .. } => Some("E0409"),
+            VmError::WrongType { .. } => Some("E0410"),
+            VmError::TypeMismatch { .. } => Some("E0401"),
+            VmError::InvalidOperand { .. } => Some("E0402"),
+            VmError::StackOverflow { .. } => Some("E0405"),
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            VmError::FuelExhausted { span } | VmError::TimedOut { span, .. } | VmError::DivisionByZero { span, .. }
+            | VmError::Overflow { span, .. } | VmError::Builtin { span, .. }
+            | VmError::IndexOutOfBounds { span, .. } | VmError::WrongType { span, .. }
+            | VmError::TypeMismatch { span, .. } | VmError::InvalidOperand { span, .. }
+            | VmError::StackOverflow { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::FuelExhausted { .. } => write!(f, "the program ran out of fuel"),
+            VmError::TimedOut { timeout, .. } => write!(f, "the program was stopped after running for {:?}", timeout),
+            VmError::DivisionByZero { operator, .. } => write!(f, "{}", division_by_zero(operator)),
+            VmError::Overflow { operator, .. } => write!(f, "{}", overflow(operator)),
+            VmError::Builtin { message, .. } => write!(f, "{}", message),
+            VmError::IndexOutOfBounds { index, length, .. } => write!(f, "{}", out_of_bounds(*index, *length)),
+            VmError::WrongType { expected, found, .. } => write!(f, "{}", wrong_type(expected, found)),
+            VmError::TypeMismatch { operator, left, right, .. } => {
+                write!(f, "cannot apply `{}` to {} and {}", operator, left, right)
+            }
+            VmError::InvalidOperand { operator, operand, .. } => write!(f, "cannot apply `{}` to {}", operator, operand),
+            VmError::StackOverflow { chain, .. } => write!(f, "{}", nested_too_deep(chain)),
+        }
+    }
+}
+
+// How many values from the top of the stack a trace shows.
+const TRACED_STACK: usize = 3;
+
+// What a trace is told about an instruction before it runs.
+pub struct Step<'s> {
+    pub pc: usize,
+    pub instruction: Instruction,
+    // The statement it's from, if the bytecode says.
+    pub span: Option<Span>,
+    // The top few values on the stack, the top one last.
+    pub stack: &'s [Value],
+    // How many calls deep the program is.
+    pub depth: usize
+}
+
+type Trace<'a> = dyn FnMut(&Step<'_>) + 'a;
+
+struct Frame {
+    // The index of the function it's a call of.
+    function: usize,
+    return_pc: usize,
+    locals: Vec<Word>
+}
+
+// A number or bool as a `Value`, so that the interpreter's arithmetic applies to it.
+fn number(word: Word) -> Option<Value> {
+    match word {
+        Word::Integer(n) => Some(Value::Integer(n)),
+        Word::Float(x) => Some(Value::Float(x)),
+        Word::Bool(b) => Some(Value::Bool(b)),
+        Word::Object(_) => None,
+    }
+}
+
+pub struct Vm<'a> {
+    bytecode: &'a Bytecode,
+    globals: Vec<Word>,
+    stack: Vec<Word>,
+    frames: Vec<Frame>,
+    heap: Heap,
+    // The string constants, allocated once up front and never freed.
+    strings: Vec<Handle>,
+    // Told about each instruction before it runs, if anything is.
+    trace: Option<Box<Trace<'a>>>,
+    // What builtins such as `getc` read from.
+    context: Context<'a>,
+    pc: usize
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(bytecode: &'a Bytecode) -> Self {
+        let mut heap = Heap::new();
+        let strings = bytecode.strings.iter()
+            .map(|s| heap.find_string(s).unwrap_or_else(|| heap.insert(Object::String(s.as_str().into()))))
+            .collect();
+        Self {
+            bytecode,
+            globals: vec![Word::Integer(0); bytecode.globals.len()],
+            stack: Vec::new(),
+            frames: Vec::new(),
+            heap,
+            strings,
+            trace: None,
+            context: Context::default(),
+            pc: 0,
+        }
+    }
+
+    pub fn heap_stats(&self) -> HeapStats {
+        self.heap.stats()
+    }
+
+    // Has `getc` and `readint` read `input` instead of stdin.
+    pub fn input(&mut self, input: impl Read + 'a) {
+        self.context = Context::with_input(input);
+    }
+
+    // Gives the program `arguments` for `argc` and `argv`, the first being its name.
+    pub fn arguments(&mut self, arguments: Vec<String>) {
+        self.context.arguments(arguments);
+    }
+
+    // Calls `trace` before each instruction runs, such as to log it with `--trace`.
+    pub fn trace(&mut self, trace: impl FnMut(&Step<'_>) + 'a) {
+        self.trace = Some(Box::new(trace));
+    }
+
+    fn trace_instruction(&mut self, instruction: Instruction) {
+        let top = self.stack.len().saturating_sub(TRACED_STACK);
+        let stack: Vec<Value> = self.stack[top..].iter().map(|&word| self.value(word)).collect();
+        let step = Step { pc: self.pc, instruction, span: self.bytecode.span(self.pc), stack: &stack, depth: self.frames.len() };
+        if let Some(trace) = &mut self.trace {
+            trace(&step);
+        }
+    }
+
+    // Puts `object` on the heap, first collecting whatever the program can no longer reach
+    // if it's time to. The roots are everything the program can name, and whatever
+    // `object` itself refers to, which isn't on the heap yet.
+    fn allocate(&mut self, object: Object) -> Handle {
+        if self.heap.wants_collection() {
+            let words = self.stack.iter()
+                .chain(&self.globals)
+                .chain(self.frames.iter().flat_map(|frame| &frame.locals));
+            let pending = match &object {
+                Object::Array(elements) => &elements[..],
+                Object::String(_) => &[][..],
+            };
+            let handles = words.chain(pending).filter_map(|word| match word {
+                Word::Object(handle) => Some(*handle),
+                _ => None,
+            });
+            let roots: Vec<Handle> = handles.chain(self.strings.iter().copied()).collect();
+            self.heap.collect(roots);
+        }
+        self.heap.insert(object)
+    }
+
+    // The string with contents `s`, allocating it unless it's in the pool already.
+    fn string(&mut self, s: String) -> Handle {
+        match self.heap.find_string(&s) {
+            Some(handle) => handle,
+            None => self.allocate(Object::String(s.into())),
+        }
+    }
+
+    fn is_string(&self, word: Word) -> bool {
+        matches!(word, Word::Object(handle) if matches!(self.heap.get(handle), Object::String(_)))
+    }
+
+    // A word as a `Value`, copying any object it refers to off the heap.
+    fn value(&self, word: Word) -> Value {
+        match word {
+            Word::Object(handle) => match self.heap.get(handle) {
+                Object::String(s) => Value::String(s.to_string()),
+                Object::Array(elements) => Value::array(elements.iter().map(|&element| self.value(element)).collect()),
+            },
+            word => number(word).unwrap(),
+        }
+    }
+
+    // A `Value` as a word, allocating it on the heap if it isn't a number or bool.
+    fn word(&mut self, value: Value) -> Word {
+        match value {
+            Value::Integer(n) => Word::Integer(n),
+            Value::Float(x) => Word::Float(x),
+            Value::Bool(b) => Word::Bool(b),
+            Value::String(s) => Word::Object(self.string(s)),
+            Value::Array(elements) => {
+                let elements: Vec<Value> = elements.borrow().clone();
+                let mut words = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let word = self.word(element);
+                    // Elements allocated so far must survive allocating the rest.
+                    self.stack.push(word);
+                    words.push(word);
+                }
+                self.stack.truncate(self.stack.len() - words.len());
+                Word::Object(self.allocate(Object::Array(words)))
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        let word = self.stack.pop().expect("Stack underflow");
+        self.value(word)
+    }
+
+    fn push(&mut self, value: Value) {
+        let word = self.word(value);
+        self.stack.push(word);
+    }
+
+    fn binary(&mut self, operator: &'static str, op: impl Fn(&Value, &Value) -> Option<Value>) -> Result<(), VmError> {
+        let r = self.pop();
+        let l = self.pop();
+        let value = op(&l, &r).ok_or_else(|| {
+            VmError::TypeMismatch { operator, left: l.type_name(), right: r.type_name(), span: self.bytecode.span(self.pc - 1) }
+        })?;
+        self.push(value);
+        Ok(())
+    }
+
+    fn unary(&mut self, operator: &'static str, op: impl Fn(&Value) -> Option<Value>) -> Result<(), VmError> {
+        let value = self.pop();
+        let result = op(&value)
+            .ok_or_else(|| VmError::InvalidOperand { operator, operand: value.type_name(), span: self.bytecode.span(self.pc - 1) })?;
+        self.push(result);
+        Ok(())
+    }
+
+    fn type_name(&self, word: Word) -> &'static str {
+        match word {
+            Word::Object(handle) => self.heap.get(handle).type_name(),
+            word => number(word).unwrap().type_name(),
+        }
+    }
+
+    // Pops an index and the array under it, checking the index against the array's bounds.
+    fn element(&mut self) -> Result<(Handle, usize), VmError> {
+        let span = self.bytecode.span(self.pc - 1);
+        let index = self.stack.pop().expect("Stack underflow");
+        let array = self.stack.pop().expect("Stack underflow");
+        let length = match array {
+            Word::Object(handle) if let Object::Array(elements) = self.heap.get(handle) => elements.len(),
+            word => return Err(VmError::WrongType { expected: "an array", found: self.type_name(word), span }),
+        };
+        let Some(index) = number(index).and_then(|value| value.integer()) else {
+            return Err(VmError::WrongType { expected: "an integer index", found: self.type_name(index), span });
+        };
+        if index < 0 || index as usize >= length {
+            return Err(VmError::IndexOutOfBounds { index, length, span });
+        }
+        let Word::Object(handle) = array else { unreachable!() };
+        Ok((handle, index as usize))
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("Local access outside of a function")
+    }
+
+    pub fn run(&mut self) -> Result<(), VmError> {
+        self.run_with(&RunConfig::default())
+    }
+
+    // Whether the two operands on top of the stack divide an integer by zero.
+    fn divides_by_zero(&self) -> bool {
+        self.integers().is_some_and(|(_, r)| r == 0)
+    }
+
+    // The two operands on top of the stack, if arithmetic on them is integer arithmetic.
+    fn integers(&self) -> Option<(i64, i64)> {
+        let [.., l, r] = self.stack[..] else { return None };
+        number(l)?.integers(&number(r)?)
+    }
+
+    pub fn run_with(&mut self, config: &RunConfig) -> Result<(), VmError> {
+        self.heap.set_stress(config.gc_stress);
+        if let Some(seed) = config.seed {
+            self.context.seed(seed);
+        }
+        if config.virtual_time {
+            self.context.virtual_time();
+        }
+        self.context.allow(config.capabilities);
+        let mut fuel = config.fuel;
+        let deadline = config.timeout.map(|timeout| (Instant::now() + timeout, timeout));
+        let mut ticks = 0u32;
+        loop {
+            if let Some(fuel) = &mut fuel {
+                *fuel = fuel.checked_sub(1).ok_or(VmError::FuelExhausted { span: self.bytecode.span(self.pc) })?;
+            }
+            if let Some((deadline, timeout)) = deadline {
+                ticks = ticks.wrapping_add(1);
+                if ticks.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                    return Err(VmError::TimedOut { timeout, span: self.bytecode.span(self.pc) });
+                }
+            }
+            let instruction = self.bytecode.code[self.pc];
+            if self.trace.is_some() {
+                self.trace_instruction(instruction);
+            }
+            self.pc += 1;
+            match instruction {
+                Instruction::Fetch(slot) => self.stack.push(self.globals[slot]),
+                Instruction::Store(slot) => self.globals[slot] = self.stack.pop().expect("Stack underflow"),
+                Instruction::LFetch(slot) => {
+                    let word = self.frame().locals[slot];
+                    self.stack.push(word);
+                }
+                Instruction::LStore(slot) => {
+                    let word = self.stack.pop().expect("Stack underflow");
+                    self.frame().locals[slot] = word;
+                }
+                Instruction::Push(n) => self.stack.push(Word::Integer(n)),
+                Instruction::PushBool(b) => self.stack.push(Word::Bool(b)),
+                Instruction::PushFloat(x) => self.stack.push(Word::Float(x)),
+                Instruction::PushStr(index) => self.stack.push(Word::Object(self.strings[index])),
+                Instruction::Div | Instruction::Mod if self.divides_by_zero() => {
+                    let operator = if instruction == Instruction::Div { TokenKind::OpDivide } else { TokenKind::OpMod };
+                    return Err(VmError::DivisionByZero { operator, span: self.bytecode.span(self.pc - 1) });
+                }
+                Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div | Instruction::Mod
+                    if let Some((a, b)) = self.integers() =>
+                {
+                    let operator = match instruction {
+                        Instruction::Add => TokenKind::OpAdd,
+                        Instruction::Sub => TokenKind::OpSubtract,
+                        Instruction::Mul => TokenKind::OpMultiply,
+                        Instruction::Div => TokenKind::OpDivide,
+                        _ => TokenKind::OpMod,
+                    };
+                    let n = config.overflow.binary(&operator, a, b)
+                        .ok_or_else(|| VmError::Overflow { operator, span: self.bytecode.span(self.pc - 1) })?;
+                    self.stack.truncate(self.stack.len() - 2);
+                    self.stack.push(Word::Integer(n));
+                }
+                Instruction::Neg if let Some(n) = self.stack.last().and_then(|&word| number(word)?.integer()) => {
+                    let n = config.overflow.negate(n)
+                        .ok_or(VmError::Overflow { operator: TokenKind::OpSubtract, span: self.bytecode.span(self.pc - 1) })?;
+                    *self.stack.last_mut().unwrap() = Word::Integer(n);
+                }
+                // Floats, strings, or operands of the wrong types.
+                Instruction::Add => self.binary("+", |l, r| l.concat(r).or_else(|| l.arithmetic(r, |a, b| a + b, |a, b| a + b)))?,
+                Instruction::Sub => self.binary("-", |l, r| l.arithmetic(r, |a, b| a - b, |a, b| a - b))?,
+                Instruction::Mul => self.binary("*", |l, r| l.arithmetic(r, |a, b| a * b, |a, b| a * b))?,
+                Instruction::Div => self.binary("/", |l, r| l.arithmetic(r, |a, b| a / b, |a, b| a / b))?,
+                Instruction::Mod => self.binary("%", |l, r| l.arithmetic(r, |a, b| a % b, |a, b| a % b))?,
+                Instruction::BitAnd => self.binary("&", |l, r| l.bitwise(r, |a, b| a & b))?,
+                Instruction::BitOr => self.binary("|", |l, r| l.bitwise(r, |a, b| a | b))?,
+                Instruction::BitXor => self.binary("^", |l, r| l.bitwise(r, |a, b| a ^ b))?,
+                Instruction::Shl => self.binary("<<", |l, r| l.bitwise(r, |a, b| a.wrapping_shl(b as u32)))?,
+                Instruction::Shr => self.binary(">>", |l, r| l.bitwise(r, |a, b| a.wrapping_shr(b as u32)))?,
+                Instruction::BitNot => self.unary("~", |v| v.bitwise(&Value::Integer(0), |a, _| !a))?,
+                Instruction::Lt => self.binary("<", |l, r| l.compare(r, i64::lt, f64::lt))?,
+                Instruction::Gt => self.binary(">", |l, r| l.compare(r, i64::gt, f64::gt))?,
+                Instruction::Le => self.binary("<=", |l, r| l.compare(r, i64::le, f64::le))?,
+                Instruction::Ge => self.binary(">=", |l, r| l.compare(r, i64::ge, f64::ge))?,
+                // Interned strings are equal exactly when they're the same object.
+                Instruction::Eq | Instruction::Ne
+                    if let [.., l @ Word::Object(a), r @ Word::Object(b)] = self.stack[..] && self.is_string(l) && self.is_string(r) =>
+                {
+                    self.stack.truncate(self.stack.len() - 2);
+                    self.stack.push(Word::Bool((a == b) == (instruction == Instruction::Eq)));
+                }
+                Instruction::Eq => self.binary("==", |l, r| l.equals(r).map(Value::from))?,
+                Instruction::Ne => self.binary("!=", |l, r| l.equals(r).map(|equal| Value::from(!equal)))?,
+                Instruction::Neg => self.unary("-", Value::negate)?,
+                Instruction::Not => {
+                    let value = self.pop();
+                    self.stack.push(Word::Bool(!value.is_truthy()));
+                }
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Jmp(target) => self.pc = target,
+                Instruction::Jz(target) => {
+                    if !self.pop().is_truthy() {
+                        self.pc = target;
+                    }
+                }
+                Instruction::Call(index) => {
+                    if self.frames.len() >= MAX_CALL_DEPTH {
+                        let chain = self.frames.iter().map(|frame| frame.function).chain([index])
+                            .map(|function| Symbol::intern(&self.bytecode.functions[function].name))
+                            .collect();
+                        return Err(VmError::StackOverflow { chain, span: self.bytecode.span(self.pc - 1) });
+                    }
+                    let function = &self.bytecode.functions[index];
+                    // Arguments are on the stack in order; the remaining locals start at zero.
+                    let mut locals = self.stack.split_off(self.stack.len() - function.arity);
+                    locals.resize(function.locals, Word::Integer(0));
+                    self.frames.push(Frame { function: index, return_pc: self.pc, locals });
+                    self.pc = function.entry;
+                }
+                Instruction::Builtin(index) => {
+                    let builtin = &BUILTINS[index];
+                    let words = self.stack.split_off(self.stack.len() - builtin.arity());
+                    let arguments: Vec<Value> = words.into_iter().map(|word| self.value(word)).collect();
+                    let result = (builtin.call)(&mut self.context, &arguments)
+                        .map_err(|message| VmError::Builtin { name: builtin.name, message, span: self.bytecode.span(self.pc - 1) })?;
+                    self.push(result);
+                }
+                Instruction::Array(n) => {
+                    let elements = self.stack.split_off(self.stack.len() - n);
+                    let array = self.allocate(Object::Array(elements));
+                    self.stack.push(Word::Object(array));
+                }
+                Instruction::Index => {
+                    let (array, index) = self.element()?;
+                    let Object::Array(elements) = self.heap.get(array) else { unreachable!() };
+                    self.stack.push(elements[index]);
+                }
+                Instruction::SetIndex => {
+                    let (array, index) = self.element()?;
+                    let value = *self.stack.last().expect("Stack underflow");
+                    let Object::Array(elements) = self.heap.get_mut(array) else { unreachable!() };
+                    elements[index] = value;
+                }
+                Instruction::Len => {
+                    let word = self.stack.pop().expect("Stack underflow");
+                    let length = match word {
+                        Word::Object(handle) => match self.heap.get(handle) {
+                            Object::Array(elements) => elements.len(),
+                            Object::String(s) => s.chars().count(),
+                        },
+                        word => {
+                            let span = self.bytecode.span(self.pc - 1);
+                            return Err(VmError::WrongType { expected: "an array or a string", found: self.type_name(word), span });
+                        }
+                    };
+                    self.stack.push(Word::Integer(length as i64));
+                }
+                Instruction::Ret => {
+                    let frame = self.frames.pop().expect("Return outside of a function");
+                    self.pc = frame.return_pc;
+                }
+                Instruction::Prtc => {
+                    let value = self.pop();
+                    let Some(code) = value.integer() else {
+                        let span = self.bytecode.span(self.pc - 1);
+                        return Err(VmError::WrongType { expected: "an integer character code", found: value.type_name(), span });
+                    };
+                    let ch = char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+                    print!("{}", ch);
+                }
+                // Strings are printed where they are, rather than copied off the heap.
+                Instruction::Prti => match self.stack.pop().expect("Stack underflow") {
+                    Word::Object(handle) if let Object::String(s) = self.heap.get(handle) => print!("{}", s),
+                    word => print!("{}", self.value(word)),
+                },
+                Instruction::Prts => {
+                    let index = self.pop().as_integer() as usize;
+                    print!("{}", self.bytecode.strings[index]);
+                }
+                Instruction::Halt => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::compile;
+    use crate::{Parser, parse_program, tokenize};
+
+    use super::{RunConfig, Vm};
+
+    #[test]
+    fn collects_strings_the_program_drops() {
+        let (tokens, _) = tokenize("s = \"\";\nfor (i = 0; i < 100; i = i + 1) { t = \"a\" + \"b\"; s = s + t; }\n");
+        let bytecode = compile(&parse_program(&mut Parser::new(tokens)).unwrap()).unwrap();
+        let mut vm = Vm::new(&bytecode);
+        vm.run_with(&RunConfig::default().gc_stress(true)).unwrap();
+        let s = vm.globals[bytecode.globals.iter().position(|name| name == "s").unwrap()];
+        assert_eq!(vm.value(s).to_string(), "ab".repeat(100));
+        let stats = vm.heap_stats();
+        assert_eq!(stats.collections, stats.allocated - bytecode.strings.len());
+        // Every `s` is freed but the last, and the one it was made from, which is in `s` still
+        // while the next one is allocated. `t` is always the same string.
+        assert_eq!(stats.freed, 99 - 2);
+        assert_eq!(stats.reused, 100);
+    }
+
+    #[test]
+    fn arrays_live_on_the_heap() {
+        let run = |source: &str| {
+            let bytecode = compile(&parse_program(&mut Parser::new(tokenize(source).0)).unwrap()).unwrap();
+            let mut vm = Vm::new(&bytecode);
+            let result = vm.run_with(&RunConfig::default().gc_stress(true));
+            result.map(|()| vm.value(vm.globals[bytecode.globals.iter().position(|name| name == "a").unwrap()]))
+        };
+        let a = run("a = [1, \"two\", [3]];\na[0] = a[0] + len(a) + len(a[1]);\na[2][0] = [4];\n").unwrap();
+        assert_eq!(a.to_string(), "[7, two, [[4]]]");
+        let error = run("a = [1, 2, 3];\nprint(a[5]);\n").unwrap_err();
+        assert_eq!((error.code(), error.to_string().as_str()), (Some("E0409"), "index 5 is out of bounds for an array of length 3"));
+        assert_eq!(error.span().unwrap().start, 23);
+        assert_eq!(run("a = 4;\nprint(a[0]);\n").unwrap_err().code(), Some("E0410"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod heap;
+mod machine;
+
+pub use machine::{RunConfig, Step, Vm, VmError};
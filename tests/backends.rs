@@ -0,0 +1,341 @@
+// Runs programs through the backends and checks that each prints what the interpreter
+// does. A backend whose tools aren't installed, or that tiny-lang was built without, is
+// skipped.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::{env, fs, io};
+
+use Backend::*;
+
+const TINY_LANG: &str = env!("CARGO_BIN_EXE_tiny-lang");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    Vm,
+    Jit,
+    Llvm,
+    Asm,
+    Wasm,
+    C,
+    Rust,
+    Js
+}
+
+// The backends that compile the SSA form, which only have integers, bools and printed
+// strings.
+const SSA: &[Backend] = &[Jit, Llvm, Asm, Wasm];
+
+const ALL: &[Backend] = &[Vm, Jit, Llvm, Asm, Wasm, C, Rust, Js];
+
+// Calls the host's imports for a module `emit_wasm` wrote, printing what they're given.
+const WASM_HOST: &str = r#"
+const fs = require("fs");
+let memory;
+const out = [];
+const env = {
+    print_int: (n) => out.push(String(n)),
+    print_bool: (b) => out.push(b ? "true" : "false"),
+    print_string: (offset, len) => out.push(Buffer.from(memory.buffer, offset, len).toString()),
+    putc: (c) => out.push(String.fromCodePoint(c)),
+};
+WebAssembly.instantiate(fs.readFileSync(process.argv[1]), { env }).then(({ instance }) => {
+    memory = instance.exports.memory;
+    instance.exports._start();
+    process.stdout.write(out.join(""));
+});
+"#;
+
+// A directory of its own for each test's files, removed afterwards.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("tiny-lang-{}-{}", name, process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Scratch(path)
+    }
+
+    fn file(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// What a command printed, which it must have exited successfully after.
+fn stdout(output: Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "exited with {}: {}", output.status, stderr);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn tiny_lang(arguments: &[&str]) -> Output {
+    Command::new(TINY_LANG).args(arguments).output().unwrap()
+}
+
+// Runs an installed program, or gives `None` if it isn't installed.
+fn tool(program: impl AsRef<Path>, arguments: &[&str]) -> Option<Output> {
+    tool_reading(program, arguments, "")
+}
+
+// Runs an installed program with `input` as its stdin.
+fn tool_reading(program: impl AsRef<Path>, arguments: &[&str], input: &str) -> Option<Output> {
+    let spawned = Command::new(program.as_ref()).args(arguments)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        Err(error) => panic!("couldn't run {}: {}", program.as_ref().display(), error),
+    };
+    // A program that doesn't read all of it may have exited before it's written.
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    Some(child.wait_with_output().unwrap())
+}
+
+// How the program ran when `backend` compiled it, with `input` as its stdin, if it can be
+// run here.
+fn run(scratch: &Scratch, backend: Backend, file: &str, input: &str) -> Option<Output> {
+    let executable = scratch.file("program");
+    match backend {
+        Vm => tool_reading(TINY_LANG, &["run", "--vm", file], input),
+        Jit if cfg!(all(feature = "jit", target_arch = "x86_64", target_os = "linux")) => {
+            tool_reading(TINY_LANG, &["run", "--jit", file], input)
+        }
+        Llvm if cfg!(feature = "llvm") => {
+            tool("llc", &["--version"])?;
+            stdout(tiny_lang(&["compile", "--target", "llvm", file, "-o", &executable]));
+            tool_reading(&executable, &[], input)
+        }
+        Asm if cfg!(all(target_arch = "x86_64", target_os = "linux")) => {
+            let assembly = scratch.file("program.s");
+            stdout(tiny_lang(&["compile", "--target", "x86_64-asm", file, "-o", &assembly]));
+            stdout(tool("cc", &[&assembly, "-o", &executable])?);
+            tool_reading(&executable, &[], input)
+        }
+        Wasm => {
+            let module = scratch.file("program.wasm");
+            stdout(tiny_lang(&["compile", "--target", "wasm32", file, "-o", &module]));
+            tool_reading("node", &["-e", WASM_HOST, &module], input)
+        }
+        C => {
+            let source = scratch.file("program.c");
+            stdout(tiny_lang(&["compile", "--target", "c", file, "-o", &source]));
+            stdout(tool("cc", &[&source, "-o", &executable])?);
+            tool_reading(&executable, &[], input)
+        }
+        Rust => {
+            let source = scratch.file("program.rs");
+            stdout(tiny_lang(&["compile", "--target", "rust", file, "-o", &source]));
+            stdout(tool("rustc", &["--edition", "2024", &source, "-o", &executable])?);
+            tool_reading(&executable, &[], input)
+        }
+        Js => {
+            let script = scratch.file("program.js");
+            stdout(tiny_lang(&["compile", "--target", "js", file, "-o", &script]));
+            tool_reading("node", &[&script], input)
+        }
+        Jit | Llvm | Asm => None,
+    }
+}
+
+// Checks that each of `backends` prints what the interpreter does for `source`.
+fn check(name: &str, source: &str, backends: &[Backend]) {
+    let scratch = Scratch::new(name);
+    let file = scratch.file("program.lang");
+    fs::write(&file, source).unwrap();
+    let expected = stdout(tiny_lang(&["run", &file]));
+    for &backend in backends {
+        if let Some(output) = run(&scratch, backend, &file, "") {
+            assert_eq!(stdout(output), expected, "{:?} printed something else", backend);
+        }
+    }
+}
+
+// Checks that each of `backends` prints what the interpreter does for `source` before
+// failing with `message`.
+fn check_failure(name: &str, source: &str, backends: &[Backend], message: &str) {
+    check_failure_reading(name, source, "", backends, message);
+}
+
+// The same, for a program reading `input`.
+fn check_failure_reading(name: &str, source: &str, input: &str, backends: &[Backend], message: &str) {
+    let scratch = Scratch::new(name);
+    let file = scratch.file("program.lang");
+    fs::write(&file, source).unwrap();
+    let expected = tool_reading(TINY_LANG, &["run", &file], input).unwrap().stdout;
+    for &backend in backends {
+        if let Some(output) = run(&scratch, backend, &file, input) {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.code(), Some(1), "{:?} exited with {}: {}", backend, output.status, stderr);
+            assert!(stderr.contains(message), "{:?} failed with {}", backend, stderr);
+            assert_eq!(output.stdout, expected, "{:?} printed something else", backend);
+        }
+    }
+}
+
+#[test]
+fn the_examples_print_the_same_on_every_backend() {
+    // 01 and 06 are examples of parse errors.
+    for example in ["00", "02", "03", "04", "05", "07"] {
+        let source = fs::read_to_string(format!("{}/tests/{}.lang", env!("CARGO_MANIFEST_DIR"), example)).unwrap();
+        check(&format!("example-{}", example), &source, ALL);
+    }
+}
+
+#[test]
+fn bools_print_as_bools() {
+    let source = "\
+x = 5;
+print(x > 3 && x < 10, \"\\n\");
+b = x > 3 && x < 10;
+print(b, \"\\n\");
+print(x > 3 ? true : false, \" \", x > 3 ? 1 : 0, \"\\n\");
+print(x < 3 || x == 4, \" \", !b, \"\\n\");
+fn flip(n) { f = false; i = 0; while (i < n) { f = !f; i = i + 1; } return f; }
+fn even(n) { return n == 0 ? true : odd(n - 1); }
+fn odd(n) { return n == 0 ? false : even(n - 1); }
+print(flip(3), \" \", even(4), \" \", flip(2) ? 7 : 8, \"\\n\");
+";
+    check("bools", source, &[&[Vm, Js], SSA].concat());
+
+    // Without the SSA form's phis, the C and Rust backends know a variable or a call is a
+    // bool by the checker's types.
+    let source = "\
+x = 5;
+b = x > 3 && x < 10;
+print(b, \" \", x > 3 ? true : false, \" \", x > 3 ? 1 : 0, \" \", !b, \"\\n\");
+fn flip(n) { f = false; i = 0; while (i < n) { f = !f; i = i + 1; } return f; }
+fn show() { print(early, \"\\n\"); }
+early = x == 5;
+show();
+print(flip(3), \" \", flip(2) ? 7 : 8, \"\\n\");
+";
+    check("bool-variables", source, &[C, Rust]);
+}
+
+#[test]
+fn strings_print_as_strings() {
+    let source = "\
+s = \"a\" + str(42) + \"!\";
+print(s, \" \", len(s), \" \", s == \"a42!\", \" \", s != \"a\", \"\\n\");
+";
+    check("strings", source, &[Vm, C, Rust, Js]);
+}
+
+#[test]
+fn variables_and_functions_can_share_a_name() {
+    let source = "\
+f = 1;
+console = 2;
+fn f(a) { return a + 1; }
+print(f(f + console), \"\\n\");
+";
+    check("shared-names", source, &[Vm, Jit, Llvm, Asm, Wasm, Rust, Js]);
+}
+
+#[test]
+fn builtins_do_what_the_interpreters_do() {
+    let source = "\
+seed(5);
+print(rand(100), \" \", rand(100), \" \", rand(100), \"\\n\");
+s = str(42) + \"!\";
+print(s, \" \", abs(-3), \" \", min(2, 7), \" \", max(2, 7), \" \", pow(2, 10), \"\\n\");
+print(chr(65), \" \", ord(\"A\"), \" \", int(\"12\") + 1, \" \", str(7) == \"7\", \"\\n\");
+b = 1 < 2;
+print(str(b) + \" \" + str(3 > 4), \"\\n\");
+print(pow(-1, 4294967296), \" \", pow(3, 9223372036854775807), \" \", abs(-9223372036854775807 - 1), \"\\n\");
+";
+    check("builtins", source, &[Vm, C, Rust, Js]);
+}
+
+#[test]
+fn the_jit_interprets_what_it_cant_compile() {
+    if !cfg!(all(feature = "jit", target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let scratch = Scratch::new("jit-fallback");
+    let file = scratch.file("program.lang");
+    fs::write(&file, "a = [1, 2];\nprint(a[1] * 1.5, \" \", len(a), \"\\n\");\n").unwrap();
+    let output = tiny_lang(&["run", "--jit", &file]);
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(stderr.contains("note: interpreting"), "{}", stderr);
+    assert_eq!(stdout(output), "3.0 2\n");
+}
+
+#[test]
+fn dividing_the_lowest_integer_by_minus_one_wraps() {
+    let source = "\
+fn divide(a, b) { return a / b; }
+fn remainder(a, b) { return a % b; }
+low = -9223372036854775807 - 1;
+print(divide(low, -1), \" \", remainder(low, -1), \" \", divide(7, -2), \" \", remainder(-7, 2), \"\\n\");
+";
+    check("division", source, ALL);
+}
+
+#[test]
+fn integer_arithmetic_wraps() {
+    let source = "\
+fn add(a, b) { return a + b; }
+high = 9223372036854775807;
+print(9223372036854775807 + 1, \" \", add(high, 1), \" \", high * 2, \" \", -high - 2, \" \", -(-high - 1), \"\\n\");
+";
+    check("overflow", source, ALL);
+}
+
+#[test]
+fn shift_counts_wrap_modulo_64() {
+    let source = "\
+fn shift(a, b) { return (a << b) + (a >> b); }
+print(1 << 64, \" \", 1 << -1, \" \", -8 >> 1, \" \", shift(16, 66), \"\\n\");
+";
+    check("shifts", source, ALL);
+}
+
+#[test]
+fn operands_and_arguments_are_evaluated_left_to_right() {
+    let source = "\
+fn side(n) { print(\"s\", n, \" \"); return n; }
+fn two(a, b) { return a - b; }
+x = 1;
+print(two(side(1), side(2)), \" \", side(3) - side(4), \" \", two(x, x = 5), \" \", x, \"\\n\");
+";
+    check("evaluation-order", source, ALL);
+}
+
+#[test]
+fn calls_nest_as_deep_as_in_the_interpreter() {
+    let source = "fn down(n) { return n == 0 ? 0 : down(n - 1); }\nprint(down(999), \"\\n\");\nprint(down(1000));\n";
+    check_failure("deep-recursion", source, &[Vm, Jit], "calls are nested more than 1000 deep: `down` (1001 times)");
+}
+
+#[test]
+fn dividing_by_zero_is_an_error() {
+    check_failure("divide-by-zero", "x = 0;\nprint(1, \"\\n\");\nprint(7 / x);\n", &[Vm, Jit, Llvm, Asm, C, Rust, Js], "cannot divide by zero");
+    check_failure("remainder-by-zero", "x = 0;\nprint(7 % x);\n", &[Vm, Jit, Llvm, Asm, C, Rust, Js], "cannot take the remainder of a division by zero");
+    check("constant-division-by-zero", "if (0) { print(1 / 0); }\nprint(2, \"\\n\");\n", &[Vm, Jit, Llvm, Asm, C, Rust, Js]);
+}
+
+#[test]
+fn reading_an_integer_too_big_is_an_error() {
+    let source = "print(readint(), \"\\n\");\nprint(readint(), \"\\n\");\n";
+    let readers = &[Vm, C, Rust];
+    let big = "99999999999999999999";
+    let message = format!("{} is too big for an integer", big);
+    check_failure_reading("readint-too-big", source, &format!(" -12\n{}\n", big), readers, &message);
+    // More digits than the C runtime buffers are still all in the message.
+    let long = "9".repeat(80);
+    let message = format!("{} is too big for an integer", long);
+    check_failure_reading("readint-very-long", source, &format!("1 {}", long), readers, &message);
+    check_failure_reading("readint-letter", source, "7 x", readers, "`readint` expected an integer, found 'x'");
+    check_failure_reading("readint-end", source, "7", readers, "`readint` reached the end of the input");
+    let message = format!("can't convert \"{}\" to an integer", big);
+    check_failure("int-too-big", &format!("print(int(\"{}\"));\n", big), &[Vm, C, Rust, Js], &message);
+}